@@ -0,0 +1,185 @@
+//! Benchmarks for the work every `/meet` command does before it reaches a
+//! handler: checking Slack's signature, validating the text, the per-user
+//! and global rate limits, and sealing or opening a stored token.
+//!
+//! Subjects are built once outside the timed loop, the way `AppState` holds
+//! them. Run with `cargo bench`; `cargo bench -- --save-baseline main` and
+//! later `cargo bench -- --baseline main` compare against a saved run.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::hint::black_box;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use meet_slack_bot::config::RateLimitConfig;
+use meet_slack_bot::crypto::{CipherKind, TokenCrypto};
+use meet_slack_bot::rate_limiter::RateLimiter;
+use meet_slack_bot::slack::verification::{verify_slack_request, VerificationConfig};
+use meet_slack_bot::validation::InputValidator;
+
+const SIGNING_SECRET: &str = "8f742231b10e8888abcd99yyyzzz85a5";
+
+/// A slash command body as Slack sends it.
+const COMMAND_BODY: &str = "token=gIkuvaNzQIHg97ATvDxqgjtO&team_id=T0001ABCD&team_domain=acme\
+    &enterprise_id=E0001ABCD&enterprise_name=Acme%20Inc&channel_id=C2147483705\
+    &channel_name=general&user_id=U2147483697&user_name=alice&command=%2Fmeet\
+    &text=Weekly%20planning%20with%20the%20design%20team&api_app_id=A123456\
+    &is_enterprise_install=false\
+    &response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678\
+    &trigger_id=13345224609.738474920.8088930838d88f008e0";
+
+fn verification(c: &mut Criterion) {
+    let config = VerificationConfig::default();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .to_string();
+    let mut mac = Hmac::<Sha256>::new_from_slice(SIGNING_SECRET.as_bytes()).unwrap();
+    mac.update(format!("v0:{}:{}", timestamp, COMMAND_BODY).as_bytes());
+    let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+
+    let mut group = c.benchmark_group("verify_slack_request");
+    group.throughput(Throughput::Bytes(COMMAND_BODY.len() as u64));
+    group.bench_function("slash_command", |b| {
+        b.iter(|| {
+            verify_slack_request(
+                &config,
+                SIGNING_SECRET,
+                black_box(&signature),
+                black_box(&timestamp),
+                black_box(COMMAND_BODY),
+            )
+            .unwrap()
+        })
+    });
+    group.finish();
+}
+
+fn validation(c: &mut Criterion) {
+    let validator = InputValidator::default();
+    let inputs = [
+        ("short", "Standup".to_string()),
+        (
+            "long",
+            "Quarterly planning: roadmap, hiring, budget and open questions. ".repeat(30),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("validate_text_input");
+    for (name, text) in &inputs {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), text, |b, text| {
+            b.iter(|| validator.validate_text_input(black_box(text), "Meeting title"))
+        });
+    }
+    group.finish();
+}
+
+fn rate_limiting(c: &mut Criterion) {
+    const ENDPOINT: &str = "/slack/commands";
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("check_user_limit");
+    for existing in [1usize, 100, 10_000] {
+        // A limit no user reaches, so every call takes the allowed path,
+        // and one window per user, as after that many users' first commands
+        let limiter = RateLimiter::with_config(RateLimitConfig {
+            user_commands_per_minute: u32::MAX,
+            ..Default::default()
+        });
+        runtime.block_on(async {
+            for i in 0..existing {
+                limiter
+                    .check_user_limit(&format!("U{:010}", i), ENDPOINT)
+                    .await
+                    .unwrap();
+            }
+        });
+
+        group.bench_with_input(
+            BenchmarkId::from_parameter(existing),
+            &limiter,
+            |b, limiter| {
+                let mut i = 0usize;
+                b.to_async(&runtime).iter(|| {
+                    i = (i + 1) % existing;
+                    let user_id = format!("U{:010}", i);
+                    async move { limiter.check_user_limit(&user_id, ENDPOINT).await.unwrap() }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+/// The global limit of `/slack/commands`, checked by every command. Tasks
+/// on a multi-threaded runtime check it at once, as concurrent commands do.
+fn endpoint_limiting(c: &mut Criterion) {
+    const ENDPOINT: &str = "/slack/commands";
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(8)
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("check_endpoint_limit");
+    for tasks in [1u64, 8] {
+        group.bench_function(BenchmarkId::from_parameter(tasks), |b| {
+            b.to_async(&runtime).iter_custom(|iters| async move {
+                // A fresh limiter each batch, with a limit no batch reaches
+                let limiter = RateLimiter::with_config(RateLimitConfig {
+                    global_commands_per_minute: u32::MAX,
+                    ..Default::default()
+                });
+                let started = Instant::now();
+                let handles: Vec<_> = (0..tasks)
+                    .map(|_| {
+                        let limiter = limiter.clone();
+                        tokio::spawn(async move {
+                            for _ in 0..iters.div_ceil(tasks) {
+                                black_box(limiter.check_endpoint_limit(ENDPOINT).await.is_ok());
+                            }
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    handle.await.unwrap();
+                }
+                started.elapsed()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn token_crypto(c: &mut Criterion) {
+    let key = TokenCrypto::generate_key();
+    let token = "ya29.a0AfB_byC1234567890abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    // What the database binds an access token to
+    let aad = b"oauth:42";
+
+    let mut group = c.benchmark_group("token_crypto");
+    for cipher in CipherKind::ALL {
+        let crypto = TokenCrypto::from_key(&key).unwrap().with_cipher(cipher);
+        let sealed = crypto.encrypt_with_aad(token, aad).unwrap();
+
+        group.bench_function(BenchmarkId::new("encrypt", cipher.name()), |b| {
+            b.iter(|| crypto.encrypt_with_aad(black_box(token), aad).unwrap())
+        });
+        group.bench_function(BenchmarkId::new("decrypt", cipher.name()), |b| {
+            b.iter(|| crypto.decrypt_with_aad(black_box(&sealed), aad).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    verification,
+    validation,
+    rate_limiting,
+    endpoint_limiting,
+    token_crypto
+);
+criterion_main!(benches);
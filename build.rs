@@ -0,0 +1,35 @@
+//! Bakes the git commit and build time into the binary for `/version`.
+//!
+//! `GIT_SHA` in the environment wins over asking git, for builds without a
+//! `.git` directory such as Docker images. When neither is available the
+//! value is simply left out and the binary reports "unknown".
+
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+
+    let git_sha = std::env::var("GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.trim().is_empty())
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        });
+    if let Some(sha) = git_sha {
+        println!("cargo:rustc-env=GIT_SHA={}", sha.trim());
+    }
+
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        println!("cargo:rustc-env=BUILD_TIMESTAMP={}", now.as_secs());
+    }
+}
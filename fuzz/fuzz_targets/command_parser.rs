@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/command_parser.rs"]
+#[allow(dead_code)]
+mod command_parser;
+
+fuzz_target!(|text: &str| {
+    let _ = command_parser::parse(text);
+});
@@ -0,0 +1,25 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[path = "../../src/validation.rs"]
+#[allow(dead_code)]
+mod validation;
+
+use validation::InputValidator;
+
+fuzz_target!(|text: &str| {
+    let validator = InputValidator::default();
+
+    if let Ok(sanitized) = validator.validate_text_input(text, "fuzz") {
+        assert!(sanitized.value.len() <= text.len());
+        assert!(!sanitized.value.to_lowercase().contains("<script"));
+
+        let again = validator
+            .validate_text_input(&sanitized.value, "fuzz")
+            .expect("sanitized text must stay valid");
+        assert_eq!(again.value, sanitized.value);
+    }
+
+    let _ = validator.validate_meeting_title(text);
+});
@@ -0,0 +1,3 @@
+-- Record whether a meeting's link is a joinable Meet URL or only a calendar
+-- event page (when Google returned no conference data).
+ALTER TABLE meetings ADD COLUMN link_kind TEXT NOT NULL DEFAULT 'meet';
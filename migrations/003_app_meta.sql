@@ -0,0 +1,7 @@
+-- Small key/value store for application bookkeeping, such as the canary used
+-- to check at startup that the encryption key matches the stored data.
+CREATE TABLE app_meta (
+    key TEXT PRIMARY KEY NOT NULL,
+    value TEXT NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
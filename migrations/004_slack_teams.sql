@@ -0,0 +1,12 @@
+-- Workspaces the app is installed in. bot_token and incoming_webhook_url are
+-- stored encrypted, like the Google OAuth tokens.
+CREATE TABLE slack_teams (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    slack_team_id TEXT UNIQUE NOT NULL,
+    team_name TEXT,
+    bot_user_id TEXT,
+    bot_token TEXT NOT NULL,
+    incoming_webhook_url TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,13 @@
+-- Security-relevant events kept for compliance: Google accounts connected
+-- and disconnected, token refresh failures, rejected Slack signatures and
+-- admin actions. detail is a JSON object that never holds token material.
+CREATE TABLE audit_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    actor_slack_id TEXT,
+    slack_team_id TEXT,
+    event_type TEXT NOT NULL,
+    detail TEXT NOT NULL DEFAULT '{}'
+);
+
+CREATE INDEX idx_audit_log_created_at ON audit_log(created_at);
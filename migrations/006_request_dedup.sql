@@ -0,0 +1,11 @@
+-- The meeting each Slack trigger_id created, so a retried or double-submitted
+-- command returns the same meeting instead of creating another. Rows are
+-- only needed while Slack might still retry and are pruned after a day.
+CREATE TABLE request_dedup (
+    trigger_id TEXT PRIMARY KEY NOT NULL,
+    meeting_id INTEGER NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (meeting_id) REFERENCES meetings (id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_request_dedup_created_at ON request_dedup(created_at);
@@ -0,0 +1,10 @@
+-- Per-workspace settings, such as feature flag overrides. Keyed by Slack team
+-- id rather than slack_teams so a workspace can be configured before the app
+-- is installed there.
+CREATE TABLE team_settings (
+    slack_team_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (slack_team_id, key)
+);
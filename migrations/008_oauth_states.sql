@@ -0,0 +1,8 @@
+-- OAuth state parameters handed out by /auth/google, so the callback only
+-- accepts a state this service issued, and only once. Stored as a SHA-256
+-- hash since the state is as good as a one-time password until used.
+CREATE TABLE oauth_states (
+    state_hash TEXT PRIMARY KEY NOT NULL,
+    slack_user_id TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,5 @@
+-- The reminder Slack was asked to post in the channel before a scheduled
+-- meeting starts, so cancelling the meeting can delete it again. Both are
+-- NULL for meetings without one.
+ALTER TABLE meetings ADD COLUMN reminder_channel_id TEXT;
+ALTER TABLE meetings ADD COLUMN reminder_message_id TEXT;
@@ -0,0 +1,3 @@
+-- Whether a meeting's link was shared in the channel or, with
+-- `/meet --quiet`, shown only to the user who created it.
+ALTER TABLE meetings ADD COLUMN visibility TEXT NOT NULL DEFAULT 'channel';
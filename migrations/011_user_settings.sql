@@ -0,0 +1,10 @@
+-- Per-user preferences, set with `/meet set <key> <value>`. They go with
+-- the user.
+CREATE TABLE user_settings (
+    user_id INTEGER NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, key),
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,4 @@
+-- The user whose Google account created a meeting, when it isn't the
+-- meeting's own user: the workspace's shared account. NULL for meetings
+-- created with their user's own account.
+ALTER TABLE meetings ADD COLUMN created_with_account_user_id INTEGER REFERENCES users (id) ON DELETE SET NULL;
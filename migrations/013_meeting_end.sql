@@ -0,0 +1,4 @@
+-- When a meeting was set to end, from the start and duration given to
+-- `/meet`, so stored links past it can be flagged as possibly dead. NULL
+-- for meetings created without a duration.
+ALTER TABLE meetings ADD COLUMN ends_at DATETIME;
@@ -0,0 +1,5 @@
+-- The channel `/meet` was run in, so `/meet join` can find the latest
+-- meeting of a channel. NULL for meetings created before it was stored.
+ALTER TABLE meetings ADD COLUMN channel_id TEXT;
+
+CREATE INDEX idx_meetings_channel_id ON meetings(channel_id, created_at);
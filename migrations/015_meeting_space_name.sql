@@ -0,0 +1,4 @@
+-- The Meet space behind a meeting, `spaces/{id}`, so `/meet attendance`
+-- can look up its conference records. NULL for meetings created before it
+-- was stored.
+ALTER TABLE meetings ADD COLUMN space_name TEXT;
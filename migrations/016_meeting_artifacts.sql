@@ -0,0 +1,4 @@
+-- Whether Meet was asked to record or transcribe the meeting by itself,
+-- so its announcement can tell participants.
+ALTER TABLE meetings ADD COLUMN auto_recording BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE meetings ADD COLUMN auto_transcription BOOLEAN NOT NULL DEFAULT 0;
@@ -0,0 +1,26 @@
+-- Polls from `/meet poll`, asking a channel when to meet. `options` holds
+-- the offered times as a JSON array; booking one closes the poll. Polls go
+-- with the user who created them.
+CREATE TABLE polls (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    slack_team_id TEXT NOT NULL,
+    channel_id TEXT NOT NULL,
+    title TEXT,
+    options TEXT NOT NULL,
+    booked_option INTEGER,
+    booked_at DATETIME,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+);
+
+-- One vote per user and poll, by the index of the chosen option; voting
+-- again changes it.
+CREATE TABLE poll_votes (
+    poll_id INTEGER NOT NULL,
+    slack_user_id TEXT NOT NULL,
+    option_index INTEGER NOT NULL,
+    voted_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (poll_id, slack_user_id),
+    FOREIGN KEY (poll_id) REFERENCES polls (id) ON DELETE CASCADE
+);
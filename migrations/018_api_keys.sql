@@ -0,0 +1,21 @@
+-- Keys for the REST API, one set per workspace, made and revoked by the
+-- bot's admins with `/meet admin api-key`. Only a SHA-256 hash of each key
+-- is kept; the key itself is shown once, when it is created.
+CREATE TABLE api_keys (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    slack_team_id TEXT NOT NULL,
+    key_hash TEXT UNIQUE NOT NULL,
+    -- The start of the key, so admins can tell their keys apart
+    prefix TEXT NOT NULL,
+    -- The admin who made the key, whose meetings it creates by default
+    created_by TEXT NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_used_at DATETIME,
+    revoked_at DATETIME
+);
+
+CREATE INDEX idx_api_keys_team ON api_keys(slack_team_id);
+
+-- Where a meeting was asked for: `slack` for `/meet` and its shortcuts,
+-- `api` for the REST API.
+ALTER TABLE meetings ADD COLUMN source TEXT NOT NULL DEFAULT 'slack';
@@ -0,0 +1,9 @@
+-- The workspace a meeting was created in, so a workspace's meetings of
+-- the day can be counted against its daily limit without a join. Older
+-- meetings take their user's current workspace.
+ALTER TABLE meetings ADD COLUMN slack_team_id TEXT;
+
+UPDATE meetings
+SET slack_team_id = (SELECT slack_team_id FROM users WHERE users.id = meetings.user_id);
+
+CREATE INDEX idx_meetings_team_created ON meetings(slack_team_id, created_at);
@@ -0,0 +1,12 @@
+-- Short links to meetings, `/m/{slug}`, made with `/meet share-link` for
+-- pasting where a Meet link is unwieldy, such as status pages. The slug is
+-- random so links can't be found by counting; a link only redirects while
+-- it is public, and keeps its slug when turned off and on again.
+CREATE TABLE short_links (
+    meeting_id INTEGER PRIMARY KEY,
+    slug TEXT UNIQUE NOT NULL,
+    public BOOLEAN NOT NULL DEFAULT FALSE,
+    click_count INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (meeting_id) REFERENCES meetings (id) ON DELETE CASCADE
+);
@@ -0,0 +1,8 @@
+-- Where a meeting's announcement was posted, so it can be edited to say
+-- the meeting was cancelled or ended. The command's response URL takes
+-- edits for 30 minutes after the command; a message the bot posted itself
+-- can be edited with `chat.update` for as long as it exists. All are NULL
+-- for meetings never announced in a channel.
+ALTER TABLE meetings ADD COLUMN announcement_response_url TEXT;
+ALTER TABLE meetings ADD COLUMN announcement_channel_id TEXT;
+ALTER TABLE meetings ADD COLUMN announcement_ts TEXT;
@@ -0,0 +1,5 @@
+-- The Meet space a user turned `reuse_personal_space` on for, returned by
+-- `/meet` each time instead of a new one. Both are NULL until the user
+-- first creates a meeting with the setting on.
+ALTER TABLE users ADD COLUMN personal_meet_link TEXT;
+ALTER TABLE users ADD COLUMN personal_space_name TEXT;
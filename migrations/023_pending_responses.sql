@@ -0,0 +1,19 @@
+-- Response URLs of commands the bot still means to answer through them,
+-- such as the one announcing a meeting, used to edit the announcement
+-- once the meeting stops. Anyone holding a response URL can post into its
+-- conversation until Slack stops taking it, so it is stored encrypted and
+-- deleted once used or expired.
+CREATE TABLE pending_responses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    response_url TEXT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_pending_responses_expires_at ON pending_responses(expires_at);
+
+-- Announcements point at their response URL instead of holding it. Those
+-- recorded in plaintext so far are dropped: most have expired, and the
+-- meetings of the rest get a follow-up message when they stop instead.
+ALTER TABLE meetings ADD COLUMN announcement_response_id INTEGER REFERENCES pending_responses (id) ON DELETE SET NULL;
+ALTER TABLE meetings DROP COLUMN announcement_response_url;
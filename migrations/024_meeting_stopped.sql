@@ -0,0 +1,4 @@
+-- When a meeting was cancelled with `/meet cancel` or ended with
+-- `/meet end`, so it isn't stopped twice or offered by `/meet join`. NULL
+-- for meetings nobody stopped.
+ALTER TABLE meetings ADD COLUMN stopped_at DATETIME;
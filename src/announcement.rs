@@ -0,0 +1,117 @@
+//! How a workspace's meeting announcements read, set by admins with
+//! `/meet set team announce-emoji`, `announce-prefix` and
+//! `announce-mention`, so "🎥 Google Meet created by @alice" can become
+//! "📹 Video call started by @alice".
+//!
+//! The emoji and wording are validated when they are set and stored as
+//! typed in `team_settings`; the wording is escaped when shown.
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::database::Database;
+use crate::slack::blocks;
+
+/// Prefix shared by every key of the style in `team_settings`.
+const SETTING_PREFIX: &str = "announce_";
+/// The emoji an announcement starts with.
+pub const EMOJI_KEY: &str = "announce_emoji";
+/// The words after the emoji.
+pub const PREFIX_KEY: &str = "announce_prefix";
+/// `false` when announcements leave out who created the meeting.
+pub const MENTION_KEY: &str = "announce_mention";
+
+const DEFAULT_EMOJI: &str = "🎥";
+const DEFAULT_PREFIX: &str = "Google Meet created";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnouncementStyle {
+    pub emoji: String,
+    pub prefix: String,
+    /// Whether announcements credit the meeting's creator.
+    pub mention_creator: bool,
+}
+
+impl Default for AnnouncementStyle {
+    fn default() -> Self {
+        Self {
+            emoji: DEFAULT_EMOJI.to_string(),
+            prefix: DEFAULT_PREFIX.to_string(),
+            mention_creator: true,
+        }
+    }
+}
+
+impl AnnouncementStyle {
+    fn from_settings(settings: &HashMap<String, String>) -> Self {
+        let defaults = Self::default();
+        Self {
+            emoji: settings.get(EMOJI_KEY).cloned().unwrap_or(defaults.emoji),
+            prefix: settings.get(PREFIX_KEY).cloned().unwrap_or(defaults.prefix),
+            mention_creator: settings.get(MENTION_KEY).map(String::as_str) != Some("false"),
+        }
+    }
+
+    /// The first line of the announcement of a meeting `creator` made, such
+    /// as "🎥 Google Meet created by <@alice>".
+    pub fn headline(&self, creator: &str) -> String {
+        let headline = format!("{} {}", self.emoji, blocks::escape(&self.prefix));
+        if self.mention_creator {
+            format!("{} by <@{}>", headline, creator)
+        } else {
+            headline
+        }
+    }
+}
+
+/// The announcement style of `slack_team_id`; the default without one.
+pub async fn for_team(db: &Database, slack_team_id: &str) -> Result<AnnouncementStyle> {
+    let settings: HashMap<String, String> = db
+        .team_settings_with_prefix(slack_team_id, SETTING_PREFIX)
+        .await?
+        .into_iter()
+        .collect();
+    Ok(AnnouncementStyle::from_settings(&settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn style(settings: &[(&str, &str)]) -> AnnouncementStyle {
+        AnnouncementStyle::from_settings(
+            &settings
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_headline() {
+        assert_eq!(
+            style(&[]).headline("U012AB3CD"),
+            "🎥 Google Meet created by <@U012AB3CD>"
+        );
+        assert_eq!(
+            style(&[
+                (EMOJI_KEY, ":video_camera:"),
+                (PREFIX_KEY, "Video call started"),
+            ])
+            .headline("U012AB3CD"),
+            ":video_camera: Video call started by <@U012AB3CD>"
+        );
+        assert_eq!(
+            style(&[(MENTION_KEY, "false")]).headline("U012AB3CD"),
+            "🎥 Google Meet created"
+        );
+    }
+
+    #[test]
+    fn test_wording_is_escaped() {
+        assert_eq!(
+            style(&[(PREFIX_KEY, "Q&A <!channel>")]).headline("U1"),
+            "🎥 Q&amp;A &lt;!channel&gt; by <@U1>"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Keys for the REST API. A bot admin makes one for their workspace with
+//! `/meet admin api-key create` and is shown it once; the bot keeps only a
+//! SHA-256 hash, as it does for OAuth states, and the first few characters
+//! so admins can tell keys apart.
+
+use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::database::{models::ApiKey, Database};
+use crate::secret::SecretString;
+
+/// What every key starts with, so one pasted somewhere it shouldn't be is
+/// easy to recognize.
+pub const KEY_PREFIX: &str = "msb_";
+
+/// Characters of a key kept in the clear: the prefix and eight more.
+const SHOWN_LEN: usize = KEY_PREFIX.len() + 8;
+
+/// Makes a new key for `slack_team_id` on behalf of `created_by` and stores
+/// its hash, returning the stored key and the key itself.
+pub async fn create(
+    db: &Database,
+    slack_team_id: &str,
+    created_by: &str,
+    now: DateTime<Utc>,
+) -> Result<(ApiKey, SecretString)> {
+    let key = generate();
+    let stored = db
+        .create_api_key(
+            slack_team_id,
+            &hash(&key),
+            &key[..SHOWN_LEN],
+            created_by,
+            now.naive_utc(),
+        )
+        .await?;
+    Ok((stored, key.into()))
+}
+
+/// The key `key` stands for, unless it is unknown or was revoked, marked as
+/// used at `now`.
+pub async fn authenticate(db: &Database, key: &str, now: DateTime<Utc>) -> Result<Option<ApiKey>> {
+    if !key.starts_with(KEY_PREFIX) {
+        return Ok(None);
+    }
+    db.use_api_key(&hash(key), now.naive_utc()).await
+}
+
+/// A new random key: [`KEY_PREFIX`] and 32 bytes in base64url.
+fn generate() -> String {
+    let random_bytes: [u8; 32] = rand::thread_rng().gen();
+    format!(
+        "{}{}",
+        KEY_PREFIX,
+        general_purpose::URL_SAFE_NO_PAD.encode(random_bytes)
+    )
+}
+
+/// How a key is stored: hex SHA-256. Keys are random, so a salt or a slow
+/// hash would add nothing.
+fn hash(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_db;
+
+    #[tokio::test]
+    async fn test_only_the_hash_is_stored() {
+        let (db, _pool) = test_db().await;
+        let now = Utc::now();
+
+        let (stored, key) = create(&db, "T012AB3C4", "U012AB3CD", now).await.unwrap();
+        let key = key.expose();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert_eq!(key.len(), KEY_PREFIX.len() + 43);
+        assert_eq!(stored.prefix, key[..SHOWN_LEN]);
+        assert_ne!(hash(key), key);
+
+        let found = authenticate(&db, key, now).await.unwrap().unwrap();
+        assert_eq!(found.id, stored.id);
+        assert_eq!(found.created_by, "U012AB3CD");
+        assert!(authenticate(&db, &key[..key.len() - 1], now)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(authenticate(&db, &hash(key), now).await.unwrap().is_none());
+
+        let (_, other) = create(&db, "T012AB3C4", "U012AB3CD", now).await.unwrap();
+        assert_ne!(other.expose(), key);
+    }
+}
@@ -0,0 +1,373 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::auth::oauth::OAuthError;
+use crate::database::{models::User, Database};
+use crate::digest::DigestSchedule;
+
+/// Longest `X-Forwarded-For` value kept; the header is caller-controlled.
+const MAX_FORWARDED_FOR_LEN: usize = 200;
+
+/// How often audit entries past their retention are deleted.
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditEventType {
+    GoogleConnected,
+    GoogleDisconnected,
+    TokenRefreshFailed,
+    SignatureRejected,
+    AuditLogViewed,
+    LogFilterChanged,
+    FeatureFlagChanged,
+    DigestChanged,
+    TeamSettingChanged,
+    DataExported,
+    ApiKeyChanged,
+    SlackInstalled,
+}
+
+impl AuditEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditEventType::GoogleConnected => "google_connected",
+            AuditEventType::GoogleDisconnected => "google_disconnected",
+            AuditEventType::TokenRefreshFailed => "token_refresh_failed",
+            AuditEventType::SignatureRejected => "signature_rejected",
+            AuditEventType::AuditLogViewed => "audit_log_viewed",
+            AuditEventType::LogFilterChanged => "log_filter_changed",
+            AuditEventType::FeatureFlagChanged => "feature_flag_changed",
+            AuditEventType::DigestChanged => "digest_changed",
+            AuditEventType::TeamSettingChanged => "team_setting_changed",
+            AuditEventType::DataExported => "data_exported",
+            AuditEventType::ApiKeyChanged => "api_key_changed",
+            AuditEventType::SlackInstalled => "slack_installed",
+        }
+    }
+}
+
+/// A security-relevant event for the audit log.
+///
+/// Events are only built through the constructors below, which put nothing
+/// but identifiers and fixed reason codes into `detail`: no tokens, no
+/// error messages from Google, nothing the user typed.
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub event_type: AuditEventType,
+    pub actor_slack_id: Option<String>,
+    pub slack_team_id: Option<String>,
+    pub detail: Value,
+}
+
+impl AuditEvent {
+    fn by_user(event_type: AuditEventType, user: &User, detail: Value) -> Self {
+        Self {
+            event_type,
+            actor_slack_id: Some(user.slack_user_id.clone()),
+            slack_team_id: Some(user.slack_team_id.clone()),
+            detail,
+        }
+    }
+
+    pub fn google_connected(user: &User, scope: Option<&str>) -> Self {
+        Self::by_user(
+            AuditEventType::GoogleConnected,
+            user,
+            json!({ "scope": scope }),
+        )
+    }
+
+    /// `reason` says why the token went: `logout` when the user asked, or
+    /// `undecryptable_token` when a corrupt row was purged.
+    pub fn google_disconnected(user: &User, reason: &'static str) -> Self {
+        Self::by_user(
+            AuditEventType::GoogleDisconnected,
+            user,
+            json!({ "reason": reason }),
+        )
+    }
+
+    /// The user asked for a copy of their data; `delivery` is `dm` when the
+    /// file was sent to them, or `link` when they got a download link.
+    pub fn data_exported(user: &User, delivery: &'static str) -> Self {
+        Self::by_user(
+            AuditEventType::DataExported,
+            user,
+            json!({ "delivery": delivery }),
+        )
+    }
+
+    pub fn token_refresh_failed(user: &User, error: &OAuthError) -> Self {
+        // The message of a failed refresh can echo Google's response, so
+        // only the kind of failure is kept
+        let reason = match error {
+            OAuthError::NoRefreshToken => "no_refresh_token",
+            OAuthError::RefreshFailed(_) => "refresh_failed",
+            OAuthError::InvalidToken => "invalid_token",
+        };
+        Self::by_user(
+            AuditEventType::TokenRefreshFailed,
+            user,
+            json!({ "reason": reason }),
+        )
+    }
+
+    /// A request claiming to come from Slack failed signature checks.
+    pub fn signature_rejected(
+        reason: &'static str,
+        ip: Option<IpAddr>,
+        forwarded_for: Option<&str>,
+    ) -> Self {
+        let forwarded_for = forwarded_for.map(|value| {
+            let mut end = value.len().min(MAX_FORWARDED_FOR_LEN);
+            while !value.is_char_boundary(end) {
+                end -= 1;
+            }
+            &value[..end]
+        });
+        Self {
+            event_type: AuditEventType::SignatureRejected,
+            actor_slack_id: None,
+            slack_team_id: None,
+            detail: json!({
+                "reason": reason,
+                "ip": ip.map(|ip| ip.to_string()),
+                "forwarded_for": forwarded_for,
+            }),
+        }
+    }
+
+    /// An admin read the audit log with the admin token.
+    pub fn audit_log_viewed(before: Option<i64>, limit: i64) -> Self {
+        Self {
+            event_type: AuditEventType::AuditLogViewed,
+            actor_slack_id: None,
+            slack_team_id: None,
+            detail: json!({ "actor": "admin_token", "before": before, "limit": limit }),
+        }
+    }
+
+    /// An admin changed the log filter with the admin token.
+    pub fn log_filter_changed(filter: &str, revert_after_minutes: Option<u64>) -> Self {
+        Self {
+            event_type: AuditEventType::LogFilterChanged,
+            actor_slack_id: None,
+            slack_team_id: None,
+            detail: json!({
+                "actor": "admin_token",
+                "filter": filter,
+                "revert_after_minutes": revert_after_minutes,
+            }),
+        }
+    }
+
+    /// A bot admin overrode a feature flag for their workspace; `enabled` is
+    /// `None` when the override was dropped.
+    pub fn feature_flag_changed(
+        slack_user_id: &str,
+        slack_team_id: &str,
+        flag: &str,
+        enabled: Option<bool>,
+    ) -> Self {
+        Self {
+            event_type: AuditEventType::FeatureFlagChanged,
+            actor_slack_id: Some(slack_user_id.to_string()),
+            slack_team_id: Some(slack_team_id.to_string()),
+            detail: json!({ "flag": flag, "enabled": enabled }),
+        }
+    }
+
+    /// A bot admin set a workspace setting, or dropped it when `set` is
+    /// false. The value isn't kept, as users typed it.
+    pub fn team_setting_changed(
+        slack_user_id: &str,
+        slack_team_id: &str,
+        key: &str,
+        set: bool,
+    ) -> Self {
+        Self {
+            event_type: AuditEventType::TeamSettingChanged,
+            actor_slack_id: Some(slack_user_id.to_string()),
+            slack_team_id: Some(slack_team_id.to_string()),
+            detail: json!({ "key": key, "set": set }),
+        }
+    }
+
+    /// A bot admin made REST API key `key_id`, or revoked it when `revoked`.
+    pub fn api_key_changed(
+        slack_user_id: &str,
+        slack_team_id: &str,
+        key_id: i64,
+        revoked: bool,
+    ) -> Self {
+        Self {
+            event_type: AuditEventType::ApiKeyChanged,
+            actor_slack_id: Some(slack_user_id.to_string()),
+            slack_team_id: Some(slack_team_id.to_string()),
+            detail: json!({ "key_id": key_id, "revoked": revoked }),
+        }
+    }
+
+    /// The app was installed into a workspace, or reinstalled, and its bot
+    /// token stored. `slack_user_id` is whoever Slack says installed it.
+    pub fn slack_installed(
+        slack_user_id: Option<&str>,
+        slack_team_id: &str,
+        scope: Option<&str>,
+    ) -> Self {
+        Self {
+            event_type: AuditEventType::SlackInstalled,
+            actor_slack_id: slack_user_id.map(str::to_string),
+            slack_team_id: Some(slack_team_id.to_string()),
+            detail: json!({ "scope": scope }),
+        }
+    }
+
+    /// A bot admin set up or changed the weekly digest, or stopped it when
+    /// `schedule` is `None`.
+    pub fn digest_changed(
+        slack_user_id: &str,
+        slack_team_id: &str,
+        schedule: Option<&DigestSchedule>,
+    ) -> Self {
+        Self {
+            event_type: AuditEventType::DigestChanged,
+            actor_slack_id: Some(slack_user_id.to_string()),
+            slack_team_id: Some(slack_team_id.to_string()),
+            detail: json!({
+                "channel_id": schedule.map(|schedule| &schedule.channel_id),
+                "weekday": schedule.map(|schedule| schedule.weekday.to_string()),
+                "hour": schedule.map(|schedule| schedule.hour),
+            }),
+        }
+    }
+}
+
+/// Writes `event` to the audit log. A failed write is reported but doesn't
+/// fail the request that caused it.
+pub async fn record(db: &Database, event: AuditEvent) {
+    if let Err(e) = db.record_audit(&event).await {
+        error!(
+            tags.error_kind = "audit",
+            "Failed to record {} audit event: {}",
+            event.event_type.as_str(),
+            e
+        );
+    }
+}
+
+/// Deletes audit entries older than `retention_days` at `now`.
+pub async fn prune_expired(
+    db: &Database,
+    retention_days: u32,
+    now: DateTime<Utc>,
+) -> anyhow::Result<()> {
+    let cutoff = (now - chrono::Duration::days(retention_days.into())).naive_utc();
+    let pruned = db.prune_audit_log(cutoff).await?;
+    if pruned > 0 {
+        info!(
+            "Pruned {} audit log entries older than {} days",
+            pruned, retention_days
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_db;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn test_events_are_paged_newest_first() {
+        let (db, _pool) = test_db().await;
+        let user = db.create_user("U012AB3CD", "T012AB3C4").await.unwrap();
+
+        record(&db, AuditEvent::google_connected(&user, Some("scope"))).await;
+        record(&db, AuditEvent::google_disconnected(&user, "logout")).await;
+        record(&db, AuditEvent::audit_log_viewed(None, 50)).await;
+
+        let first = db.audit_log_page(None, 2).await.unwrap();
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[0].event_type, "audit_log_viewed");
+        assert_eq!(first[1].event_type, "google_disconnected");
+        assert_eq!(first[1].actor_slack_id.as_deref(), Some("U012AB3CD"));
+        assert_eq!(first[1].slack_team_id.as_deref(), Some("T012AB3C4"));
+        assert_eq!(first[1].detail["reason"], "logout");
+
+        let rest = db.audit_log_page(Some(first[1].id), 2).await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].event_type, "google_connected");
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_recent_events() {
+        let (db, _pool) = test_db().await;
+        record(&db, AuditEvent::audit_log_viewed(None, 50)).await;
+
+        let yesterday = (chrono::Utc::now() - chrono::Duration::days(1)).naive_utc();
+        assert_eq!(db.prune_audit_log(yesterday).await.unwrap(), 0);
+
+        let tomorrow = (chrono::Utc::now() + chrono::Duration::days(1)).naive_utc();
+        assert_eq!(db.prune_audit_log(tomorrow).await.unwrap(), 1);
+        assert!(db.audit_log_page(None, 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retention_keeps_events_exactly_at_the_boundary_day() {
+        let (db, pool) = test_db().await;
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let boundary = now - chrono::Duration::days(30);
+        for (event_type, created_at) in [
+            ("at_boundary", boundary),
+            ("just_past", boundary - chrono::Duration::seconds(1)),
+        ] {
+            sqlx::query("INSERT INTO audit_log (event_type, created_at) VALUES (?1, ?2)")
+                .bind(event_type)
+                .bind(created_at.naive_utc())
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        prune_expired(&db, 30, now).await.unwrap();
+
+        let events = db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, "at_boundary");
+    }
+
+    #[tokio::test]
+    async fn test_detail_never_contains_tokens() {
+        let (db, _pool) = test_db().await;
+        let user = db.create_user("U012AB3CD", "T012AB3C4").await.unwrap();
+
+        let refresh_error = OAuthError::RefreshFailed(
+            "invalid_grant: token 1//0gSECRET revoked, access ya29.SECRET".to_string(),
+        );
+        record(&db, AuditEvent::token_refresh_failed(&user, &refresh_error)).await;
+        record(
+            &db,
+            AuditEvent::signature_rejected(
+                "signature_mismatch",
+                Some("203.0.113.9".parse().unwrap()),
+                Some(&"x".repeat(1000)),
+            ),
+        )
+        .await;
+
+        let events = db.audit_log_page(None, 10).await.unwrap();
+        let serialized = serde_json::to_string(&events).unwrap();
+        assert!(!serialized.contains("SECRET"), "{}", serialized);
+        assert_eq!(events[1].detail["reason"], "refresh_failed");
+        assert_eq!(events[0].detail["ip"], "203.0.113.9");
+        assert_eq!(
+            events[0].detail["forwarded_for"].as_str().unwrap().len(),
+            MAX_FORWARDED_FOR_LEN
+        );
+    }
+}
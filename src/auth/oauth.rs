@@ -1,13 +1,25 @@
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use oauth2::{basic::BasicClient, RefreshToken, TokenResponse};
 use tracing::{error, info, warn};
 
 use crate::database::models::OAuthToken;
+use crate::http_client::oauth2_http_client;
+
+/// Lets the bot create Meet spaces. Required.
+pub const MEET_CREATE_SCOPE: &str = "https://www.googleapis.com/auth/meetings.space.created";
+
+/// Lets the bot read the conference records of the user's meetings, with
+/// their participants, recordings and transcripts, for `/meet attendance`
+/// and `/meet notes`. Users may leave it out when they connect.
+pub const MEET_READONLY_SCOPE: &str = "https://www.googleapis.com/auth/meetings.space.readonly";
+
+/// The scopes asked for when a user connects Google.
+pub const REQUESTED_SCOPES: [&str; 2] = [MEET_CREATE_SCOPE, MEET_READONLY_SCOPE];
 
 #[derive(Debug)]
 pub enum OAuthError {
     NoRefreshToken,
-    TokenExpired,
     RefreshFailed(String),
     InvalidToken,
 }
@@ -16,7 +28,6 @@ impl std::fmt::Display for OAuthError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             OAuthError::NoRefreshToken => write!(f, "No refresh token available"),
-            OAuthError::TokenExpired => write!(f, "Token has expired"),
             OAuthError::RefreshFailed(msg) => write!(f, "Token refresh failed: {}", msg),
             OAuthError::InvalidToken => write!(f, "Invalid token format"),
         }
@@ -25,11 +36,14 @@ impl std::fmt::Display for OAuthError {
 
 impl std::error::Error for OAuthError {}
 
+/// Refreshes `token` if it expires within its refresh margin of `now`.
 pub async fn refresh_token_if_needed(
     client: &BasicClient,
+    http: &reqwest::Client,
     token: &OAuthToken,
+    now: DateTime<Utc>,
 ) -> Result<Option<OAuthToken>, OAuthError> {
-    if !token.expires_soon() {
+    if !token.expires_soon_at(now) {
         return Ok(None);
     }
 
@@ -43,34 +57,33 @@ pub async fn refresh_token_if_needed(
         .as_ref()
         .ok_or(OAuthError::NoRefreshToken)?;
 
-    let refresh_token = RefreshToken::new(refresh_token_str.clone());
+    let refresh_token = RefreshToken::new(refresh_token_str.expose().to_string());
 
     match client
         .exchange_refresh_token(&refresh_token)
-        .request_async(oauth2::reqwest::async_http_client)
+        .request_async(|request| oauth2_http_client(http, request))
         .await
     {
         Ok(token_result) => {
             info!("Successfully refreshed token for user {}", token.user_id);
 
             let expires_at = token_result.expires_in().map(|duration| {
-                (chrono::Utc::now() + chrono::Duration::from_std(duration).unwrap_or_default())
-                    .naive_utc()
+                (now + chrono::Duration::from_std(duration).unwrap_or_default()).naive_utc()
             });
 
             // Create new token with refreshed values
             let new_token = OAuthToken {
                 id: token.id,
                 user_id: token.user_id,
-                access_token: token_result.access_token().secret().clone(),
+                access_token: token_result.access_token().secret().clone().into(),
                 refresh_token: token_result
                     .refresh_token()
-                    .map(|rt| rt.secret().clone())
+                    .map(|rt| rt.secret().clone().into())
                     .or_else(|| token.refresh_token.clone()), // Keep old refresh token if new one not provided
                 expires_at,
                 scope: token.scope.clone(), // Keep existing scope
                 created_at: token.created_at,
-                updated_at: Some(chrono::Utc::now().naive_utc()),
+                updated_at: Some(now.naive_utc()),
             };
 
             Ok(Some(new_token))
@@ -83,9 +96,7 @@ pub async fn refresh_token_if_needed(
 }
 
 pub fn validate_token_scopes(token: &OAuthToken) -> Result<(), OAuthError> {
-    let required_scopes = [
-        "https://www.googleapis.com/auth/meetings.space.created",
-    ];
+    let required_scopes = [MEET_CREATE_SCOPE];
 
     if let Some(ref scope) = token.scope {
         let token_scopes: Vec<&str> = scope.split_whitespace().collect();
@@ -104,7 +115,19 @@ pub fn validate_token_scopes(token: &OAuthToken) -> Result<(), OAuthError> {
     }
 }
 
+/// Whether the user granted `scope` along with `token`.
+pub fn has_scope(token: &OAuthToken, scope: &str) -> bool {
+    token
+        .scope
+        .as_deref()
+        .is_some_and(|scopes| scopes.split_whitespace().any(|granted| granted == scope))
+}
+
 /// Check if a token is valid and not expired
 pub fn is_token_valid(token: &OAuthToken) -> bool {
-    !token.is_expired() && validate_token_scopes(token).is_ok()
+    is_token_valid_at(token, Utc::now())
+}
+
+pub fn is_token_valid_at(token: &OAuthToken, now: DateTime<Utc>) -> bool {
+    !token.is_expired_at(now) && validate_token_scopes(token).is_ok()
 }
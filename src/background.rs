@@ -0,0 +1,270 @@
+//! Periodic background jobs and the registry reporting how they are doing.
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// What the last runs of a job did, as shown by `/admin/jobs` and `/ready`.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub interval_secs: u64,
+    /// Whether the job's loop is still going; false once it has stopped.
+    pub active: bool,
+    pub runs: u64,
+    pub failures: u64,
+    /// Whether the most recent run failed.
+    pub failing: bool,
+    pub last_run: Option<DateTime<Utc>>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<DateTime<Utc>>,
+}
+
+/// Shared record of every job started with [`JobRegistry::spawn_periodic`].
+#[derive(Debug, Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<BTreeMap<&'static str, JobStatus>>>,
+}
+
+impl JobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every job by name.
+    pub fn snapshot(&self) -> BTreeMap<&'static str, JobStatus> {
+        self.jobs.lock().unwrap().clone()
+    }
+
+    fn update(&self, name: &'static str, change: impl FnOnce(&mut JobStatus)) {
+        if let Some(status) = self.jobs.lock().unwrap().get_mut(name) {
+            change(status);
+        }
+    }
+
+    /// Runs `job` every `interval` until `shutdown` is cancelled, each run
+    /// delayed by a random extra of up to `jitter` so instances started
+    /// together don't all hit the database at once. The first run comes
+    /// after the jitter alone.
+    ///
+    /// A run returning an error, or panicking, is logged and recorded, and
+    /// the job carries on at its next tick. A run in progress when
+    /// `shutdown` is cancelled is allowed to finish.
+    pub fn spawn_periodic<F, Fut>(
+        &self,
+        name: &'static str,
+        interval: Duration,
+        shutdown: CancellationToken,
+        jitter: Duration,
+        mut job: F,
+    ) -> JoinHandle<()>
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.jobs.lock().unwrap().insert(
+            name,
+            JobStatus {
+                interval_secs: interval.as_secs(),
+                active: true,
+                runs: 0,
+                failures: 0,
+                failing: false,
+                last_run: None,
+                last_success: None,
+                last_error: None,
+                last_error_at: None,
+            },
+        );
+
+        let registry = self.clone();
+        tokio::spawn(async move {
+            info!(
+                job = name,
+                "Started background job {} every {:?}", name, interval
+            );
+
+            let mut delay = random_jitter(jitter);
+            loop {
+                tokio::select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = shutdown.cancelled() => break,
+                }
+
+                registry.update(name, |status| {
+                    status.runs += 1;
+                    status.last_run = Some(Utc::now());
+                });
+                // A run of its own so a panic ends that run rather than the loop
+                let result = match tokio::spawn(job()).await {
+                    Ok(result) => result,
+                    Err(e) => Err(anyhow::anyhow!("run panicked: {}", e)),
+                };
+                match result {
+                    Ok(()) => registry.update(name, |status| {
+                        status.failing = false;
+                        status.last_success = Some(Utc::now());
+                    }),
+                    Err(e) => {
+                        error!(
+                            tags.error_kind = "background_job",
+                            job = name,
+                            "Background job {} failed: {:#}",
+                            name,
+                            e
+                        );
+                        registry.update(name, |status| {
+                            status.failures += 1;
+                            status.failing = true;
+                            status.last_error = Some(format!("{:#}", e));
+                            status.last_error_at = Some(Utc::now());
+                        });
+                    }
+                }
+
+                delay = interval + random_jitter(jitter);
+            }
+
+            registry.update(name, |status| status.active = false);
+            info!(job = name, "Stopped background job {}", name);
+        })
+    }
+}
+
+fn random_jitter(jitter: Duration) -> Duration {
+    if jitter.is_zero() {
+        Duration::ZERO
+    } else {
+        rand::thread_rng().gen_range(Duration::ZERO..jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    const INTERVAL: Duration = Duration::from_secs(60);
+
+    /// Lets the job's tasks run up to their next sleep.
+    async fn settle() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_job_runs_every_interval() {
+        let registry = JobRegistry::new();
+        let shutdown = CancellationToken::new();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        let handle = registry.spawn_periodic(
+            "count",
+            INTERVAL,
+            shutdown.clone(),
+            Duration::ZERO,
+            move || {
+                let counter = counter.clone();
+                async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            },
+        );
+
+        settle().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(INTERVAL - Duration::from_secs(1)).await;
+        settle().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        settle().await;
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+        let status = &registry.snapshot()["count"];
+        assert_eq!(status.runs, 2);
+        assert_eq!(status.interval_secs, 60);
+        assert!(status.last_success.is_some());
+        assert!(!status.failing);
+
+        shutdown.cancel();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failures_and_panics_do_not_stop_the_job() {
+        let registry = JobRegistry::new();
+        let shutdown = CancellationToken::new();
+        let runs = Arc::new(AtomicU32::new(0));
+
+        let counter = runs.clone();
+        let handle = registry.spawn_periodic(
+            "flaky",
+            INTERVAL,
+            shutdown.clone(),
+            Duration::ZERO,
+            move || {
+                let run = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    match run {
+                        0 => anyhow::bail!("database is locked"),
+                        1 => panic!("unexpected state"),
+                        _ => Ok(()),
+                    }
+                }
+            },
+        );
+
+        settle().await;
+        let status = &registry.snapshot()["flaky"];
+        assert_eq!(status.last_error.as_deref(), Some("database is locked"));
+        assert!(status.failing);
+
+        tokio::time::advance(INTERVAL).await;
+        settle().await;
+        let status = &registry.snapshot()["flaky"];
+        assert_eq!(status.failures, 2);
+        assert!(status.last_error.as_deref().unwrap().contains("panicked"));
+
+        tokio::time::advance(INTERVAL).await;
+        settle().await;
+        let status = &registry.snapshot()["flaky"];
+        assert_eq!(status.runs, 3);
+        assert!(!status.failing);
+
+        shutdown.cancel();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cancellation_stops_the_job() {
+        let registry = JobRegistry::new();
+        let shutdown = CancellationToken::new();
+
+        let handle =
+            registry.spawn_periodic("idle", INTERVAL, shutdown.clone(), INTERVAL, || async {
+                Ok(())
+            });
+        settle().await;
+        assert!(registry.snapshot()["idle"].active);
+
+        shutdown.cancel();
+        handle.await.unwrap();
+
+        let status = &registry.snapshot()["idle"];
+        assert!(!status.active);
+        // Cancelled during the initial jitter, before the first run
+        assert_eq!(status.runs, 0);
+    }
+}
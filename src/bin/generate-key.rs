@@ -1,13 +1,406 @@
-use aes_gcm::{aead::OsRng, Aes256Gcm, KeyInit};
-use base64::{engine::general_purpose, Engine as _};
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+
+use meet_slack_bot::crypto::{keys, TokenCrypto};
+
+const USAGE: &str = "Usage: generate-key [--labeled [ID]]
+       generate-key --write-env PATH [--force]
+       generate-key --check
+
+  --labeled [ID]    print an id:key entry for TOKEN_ENCRYPTION_KEYS
+  --write-env PATH  store a new TOKEN_ENCRYPTION_KEY in the given .env file
+  --force           with --write-env, replace an existing key
+  --check           validate the configured key(s) with a round-trip";
+
+const ENV_VAR: &str = "TOKEN_ENCRYPTION_KEY";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Mode {
+    Print { label: Option<String> },
+    WriteEnv { path: PathBuf, force: bool },
+    Check,
+    Help,
+}
 
 fn main() {
-    let key = Aes256Gcm::generate_key(OsRng);
-    let key_b64 = general_purpose::STANDARD.encode(key);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let mode = match parse_args(&args) {
+        Ok(mode) => mode,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let result = match mode {
+        Mode::Help => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Mode::Print { label } => {
+            print_key(label);
+            Ok(())
+        }
+        Mode::WriteEnv { path, force } => write_env(&path, force),
+        Mode::Check => check(|name| std::env::var(name).ok()).map(|report| print!("{}", report)),
+    };
 
-    println!("Generated TOKEN_ENCRYPTION_KEY:");
-    println!("{}", key_b64);
-    println!();
-    println!("This is a 32-byte AES-256 key encoded in base64.");
+    if let Err(e) = result {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String]) -> Result<Mode> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        [] => Ok(Mode::Print { label: None }),
+        ["--help"] | ["-h"] => Ok(Mode::Help),
+        ["--labeled"] => Ok(Mode::Print {
+            label: Some(default_key_id()),
+        }),
+        ["--labeled", id] if !id.starts_with("--") => Ok(Mode::Print {
+            label: Some(id.to_string()),
+        }),
+        ["--check"] => Ok(Mode::Check),
+        ["--write-env", path]
+        | ["--write-env", path, "--force"]
+        | ["--force", "--write-env", path]
+            if !path.starts_with("--") =>
+        {
+            Ok(Mode::WriteEnv {
+                path: PathBuf::from(path),
+                force: args.contains(&"--force"),
+            })
+        }
+        ["--force"] => Err(anyhow!("--force only applies to --write-env")),
+        _ => Err(anyhow!("Unrecognized arguments: {}", args.join(" "))),
+    }
+}
+
+fn print_key(label: Option<String>) {
+    let key = TokenCrypto::generate_key();
+
+    match label {
+        None => {
+            println!("Generated {}:", ENV_VAR);
+            println!("{}", key);
+            println!();
+            println!("This is a 32-byte key encoded in base64. It works with either");
+            println!("TOKEN_CIPHER (aes256gcm or xchacha20).");
+        }
+        Some(id) => {
+            println!("Generated TOKEN_ENCRYPTION_KEYS entry:");
+            println!("{}:{}", id, key);
+            println!();
+            println!("Put it first in TOKEN_ENCRYPTION_KEYS to make it the primary key,");
+            println!("and keep the previous entries until old tokens are re-encrypted.");
+        }
+    }
     println!("Keep this secret and secure!");
 }
+
+fn write_env(path: &Path, force: bool) -> Result<()> {
+    let replaced = write_env_key(path, &TokenCrypto::generate_key(), force)?;
+
+    if replaced {
+        println!("Replaced {} in {}.", ENV_VAR, path.display());
+        println!("Tokens encrypted with the previous key can no longer be read.");
+    } else {
+        println!("Added {} to {}.", ENV_VAR, path.display());
+    }
+    Ok(())
+}
+
+/// Sets `TOKEN_ENCRYPTION_KEY=<key>` in the .env file at `path`, appending
+/// the line or replacing an existing one, and returns whether a key was
+/// replaced. Replacing a non-empty key requires `force`: without the old key
+/// every stored token becomes unreadable.
+///
+/// The new contents are written to a temporary file next to `path` and
+/// renamed over it, so a crash never leaves a half-written file behind.
+fn write_env_key(path: &Path, key: &str, force: bool) -> Result<bool> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+
+    let new_line = format!("{}={}", ENV_VAR, key);
+    let mut replaced = false;
+    let mut had_key = false;
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in existing.lines() {
+        match env_line_value(line, ENV_VAR) {
+            Some(value) if !replaced => {
+                if !value.is_empty() && !force {
+                    bail!(
+                        "{} already sets {}. Replacing it makes every token encrypted with \
+                         the current key unreadable; re-run with --force if that is intended.",
+                        path.display(),
+                        ENV_VAR
+                    );
+                }
+                replaced = true;
+                had_key = !value.is_empty();
+                lines.push(new_line.clone());
+            }
+            // Drop duplicates so the file has a single, unambiguous key
+            Some(_) => {}
+            None => lines.push(line.to_string()),
+        }
+    }
+
+    if !replaced {
+        lines.push(new_line);
+    }
+
+    let mut contents = lines.join("\n");
+    contents.push('\n');
+
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file path", path.display()))?;
+    let tmp_path = path.with_file_name(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    write_private(&tmp_path, &contents)
+        .with_context(|| format!("Failed to write {}", tmp_path.display()))?;
+    copy_permissions(path, &tmp_path)?;
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(had_key)
+}
+
+/// The value assigned to `var` on this line (`VAR=value` or
+/// `export VAR=value`), if any. Commented-out lines don't count.
+fn env_line_value<'a>(line: &'a str, var: &str) -> Option<&'a str> {
+    let line = line.trim_start();
+    let line = line.strip_prefix("export ").unwrap_or(line).trim_start();
+    let value = line.strip_prefix(var)?.trim_start().strip_prefix('=')?;
+    Some(value.trim().trim_matches('"').trim_matches('\''))
+}
+
+/// Creates `path` readable by its owner only, since it holds a secret.
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let mut file = options.open(path)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()
+}
+
+/// Gives the replacement the permissions of the file it replaces, if any.
+fn copy_permissions(original: &Path, tmp_path: &Path) -> Result<()> {
+    match std::fs::metadata(original) {
+        Ok(metadata) => std::fs::set_permissions(tmp_path, metadata.permissions())
+            .with_context(|| format!("Failed to set permissions on {}", tmp_path.display())),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Loads the key(s) the bot would use, through `var`, and checks they
+/// encrypt and decrypt. Returns a short report for the operator.
+fn check(var: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let provider = keys::provider_from_config(&var)?;
+    let crypto = TokenCrypto::from_config(&var)?;
+
+    const PROBE: &str = "generate-key --check";
+    let encrypted = crypto
+        .encrypt(PROBE)
+        .map_err(|e| anyhow!("Round-trip encryption failed: {}", e))?;
+    let decrypted = crypto
+        .decrypt(&encrypted)
+        .map_err(|e| anyhow!("Round-trip decryption failed: {}", e))?;
+    if decrypted != PROBE {
+        bail!("Round-trip returned different plaintext");
+    }
+
+    Ok(format!(
+        "Keys from {} are valid.\nPrimary key id: {}\nCipher: {}\nRound-trip: ok\n",
+        provider.describe(),
+        crypto.primary_key_id(),
+        crypto.cipher().name()
+    ))
+}
+
+/// Date-based id such as `k20250603`, so rotated keys sort naturally.
+fn default_key_id() -> String {
+    format!("k{}", chrono::Utc::now().format("%Y%m%d"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn key_file(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write!(file, "{}", contents).unwrap();
+        file
+    }
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse_args(&[]).unwrap(), Mode::Print { label: None });
+        assert_eq!(
+            parse_args(&args(&["--labeled", "k1"])).unwrap(),
+            Mode::Print {
+                label: Some("k1".to_string())
+            }
+        );
+        assert!(matches!(
+            parse_args(&args(&["--labeled"])).unwrap(),
+            Mode::Print { label: Some(id) } if id.starts_with('k')
+        ));
+        assert_eq!(parse_args(&args(&["--check"])).unwrap(), Mode::Check);
+        assert_eq!(
+            parse_args(&args(&["--write-env", ".env"])).unwrap(),
+            Mode::WriteEnv {
+                path: PathBuf::from(".env"),
+                force: false
+            }
+        );
+        assert_eq!(
+            parse_args(&args(&["--write-env", ".env", "--force"])).unwrap(),
+            Mode::WriteEnv {
+                path: PathBuf::from(".env"),
+                force: true
+            }
+        );
+
+        assert!(parse_args(&args(&["--write-env"])).is_err());
+        assert!(parse_args(&args(&["--write-env", "--force"])).is_err());
+        assert!(parse_args(&args(&["--force"])).is_err());
+        assert!(parse_args(&args(&["--check", "--labeled"])).is_err());
+    }
+
+    #[test]
+    fn test_write_env_creates_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+
+        assert!(!write_env_key(&path, "new-key", false).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "TOKEN_ENCRYPTION_KEY=new-key\n"
+        );
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn test_write_env_appends_and_keeps_other_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            "PORT=3000\n# TOKEN_ENCRYPTION_KEY=commented\nTOKEN_ENCRYPTION_KEY=\n",
+        )
+        .unwrap();
+
+        assert!(!write_env_key(&path, "new-key", false).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "PORT=3000\n# TOKEN_ENCRYPTION_KEY=commented\nTOKEN_ENCRYPTION_KEY=new-key\n"
+        );
+        assert!(!dir.path().join("..env.tmp").exists());
+    }
+
+    #[test]
+    fn test_write_env_refuses_to_replace_without_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        let original = "PORT=3000\nexport TOKEN_ENCRYPTION_KEY=\"old-key\"\n";
+        std::fs::write(&path, original).unwrap();
+
+        let err = write_env_key(&path, "new-key", false).unwrap_err();
+        assert!(err.to_string().contains("--force"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), original);
+    }
+
+    #[test]
+    fn test_write_env_replaces_with_force() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(
+            &path,
+            "TOKEN_ENCRYPTION_KEY=old-key\nPORT=3000\nTOKEN_ENCRYPTION_KEY=older-key\n",
+        )
+        .unwrap();
+
+        assert!(write_env_key(&path, "new-key", true).unwrap());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "TOKEN_ENCRYPTION_KEY=new-key\nPORT=3000\n"
+        );
+    }
+
+    #[test]
+    fn test_check_accepts_a_valid_key_file() {
+        let file = key_file(&format!("{}\n", TokenCrypto::generate_key()));
+        let path = file.path().to_str().unwrap();
+
+        let report = check(lookup(&[("TOKEN_ENCRYPTION_KEY_FILE", path)])).unwrap();
+        assert!(report.contains("Primary key id: default"));
+        assert!(report.contains("Cipher: aes256gcm"));
+        assert!(report.contains("Round-trip: ok"));
+
+        let labeled = key_file(&format!("k2:{}", TokenCrypto::generate_key()));
+        let report = check(lookup(&[
+            (
+                "TOKEN_ENCRYPTION_KEY_FILE",
+                labeled.path().to_str().unwrap(),
+            ),
+            ("TOKEN_CIPHER", "xchacha20"),
+        ]))
+        .unwrap();
+        assert!(report.contains("Primary key id: k2"));
+        assert!(report.contains("Cipher: xchacha20"));
+    }
+
+    #[test]
+    fn test_check_rejects_bad_keys() {
+        let short = key_file("dG9vc2hvcnQ=");
+        assert!(check(lookup(&[(
+            "TOKEN_ENCRYPTION_KEY_FILE",
+            short.path().to_str().unwrap()
+        )]))
+        .is_err());
+
+        let not_base64 = key_file("not a key!");
+        assert!(check(lookup(&[(
+            "TOKEN_ENCRYPTION_KEY_FILE",
+            not_base64.path().to_str().unwrap()
+        )]))
+        .is_err());
+
+        assert!(check(lookup(&[])).is_err());
+    }
+}
@@ -0,0 +1,519 @@
+use anyhow::{anyhow, bail, Context, Result};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+
+use meet_slack_bot::crypto::TokenCrypto;
+use meet_slack_bot::database::{models::OAuthToken, Database};
+
+const USAGE: &str = "Usage: loadtest --url URL [options]
+
+Sends signed /meet commands to a running bot and reports how it held up.
+Point it at an instance started with GOOGLE_FAKE=true, so no meetings are
+created at Google.
+
+  --url URL           base URL of the bot, e.g. http://localhost:3000
+  --secret SECRET     Slack signing secret (default: $SLACK_SIGNING_SECRET)
+  --users N           synthetic users the commands are spread over (default 20)
+  --rate N            commands per second (default 10)
+  --duration SECS     how long to send for (default 30)
+  --text TEXT         command text (default \"Load test\")
+  --seed              first give every synthetic user a Google token, in the
+                      database at $DATABASE_URL with the key(s) the bot uses
+                      (TOKEN_ENCRYPTION_KEY and friends); without it every
+                      command is answered with a sign-in prompt
+  --json              print the report as JSON instead of a table";
+
+/// Slack abandons a command that isn't answered within this long.
+const SLACK_DEADLINE: Duration = Duration::from_secs(3);
+
+const TEAM_ID: &str = "TLOADTEST0";
+const CHANNEL_ID: &str = "CLOADTEST0";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Options {
+    url: String,
+    secret: String,
+    users: usize,
+    rate: f64,
+    duration: Duration,
+    text: String,
+    seed: bool,
+    json: bool,
+}
+
+/// How the bot answered one command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Outcome {
+    /// Meeting shared in the channel.
+    Created,
+    /// Acknowledged, with the meeting to follow on the response URL.
+    Acknowledged,
+    AuthRequired,
+    RateLimited,
+    /// An ❌ reply: the command was taken but failed.
+    Failed,
+    /// Any other reply, e.g. help text for an unexpected command.
+    Other,
+    /// Not a 200, or no usable reply at all.
+    HttpError,
+}
+
+impl Outcome {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Created => "created",
+            Outcome::Acknowledged => "acknowledged",
+            Outcome::AuthRequired => "auth_required",
+            Outcome::RateLimited => "rate_limited",
+            Outcome::Failed => "failed",
+            Outcome::Other => "other",
+            Outcome::HttpError => "http_error",
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if matches!(args.as_slice(), [flag] if flag == "--help" || flag == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let options = match parse_args(&args, |name| std::env::var(name).ok()) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let runtime = tokio::runtime::Runtime::new().expect("failed to start the runtime");
+    if let Err(e) = runtime.block_on(run(options)) {
+        eprintln!("Error: {:#}", e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args(args: &[String], env: impl Fn(&str) -> Option<String>) -> Result<Options> {
+    let mut options = Options {
+        url: String::new(),
+        secret: env("SLACK_SIGNING_SECRET").unwrap_or_default(),
+        users: 20,
+        rate: 10.0,
+        duration: Duration::from_secs(30),
+        text: "Load test".to_string(),
+        seed: false,
+        json: false,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| anyhow!("{} needs a value", arg))
+                .map(String::as_str)
+        };
+        match arg.as_str() {
+            "--url" => options.url = value()?.trim_end_matches('/').to_string(),
+            "--secret" => options.secret = value()?.to_string(),
+            "--users" => options.users = parse_positive(arg, value()?)?,
+            "--rate" => {
+                options.rate = value()?
+                    .parse()
+                    .ok()
+                    .filter(|rate: &f64| *rate > 0.0 && rate.is_finite())
+                    .ok_or_else(|| anyhow!("--rate must be a positive number"))?
+            }
+            "--duration" => {
+                options.duration = Duration::from_secs(parse_positive(arg, value()?)? as u64)
+            }
+            "--text" => options.text = value()?.to_string(),
+            "--seed" => options.seed = true,
+            "--json" => options.json = true,
+            _ => bail!("Unrecognized argument: {}", arg),
+        }
+    }
+
+    if options.url.is_empty() {
+        bail!("--url is required");
+    }
+    if options.secret.is_empty() {
+        bail!("--secret or SLACK_SIGNING_SECRET is required");
+    }
+    Ok(options)
+}
+
+fn parse_positive(flag: &str, value: &str) -> Result<usize> {
+    value
+        .parse()
+        .ok()
+        .filter(|n| *n > 0)
+        .ok_or_else(|| anyhow!("{} must be a positive whole number", flag))
+}
+
+fn user_id(index: usize) -> String {
+    format!("ULOAD{:06}", index)
+}
+
+async fn run(options: Options) -> Result<()> {
+    if options.seed {
+        seed_tokens(options.users).await?;
+    }
+
+    let client = reqwest::Client::new();
+    let url = format!("{}/slack/commands", options.url);
+    let interval = Duration::from_secs_f64(1.0 / options.rate);
+    let mut ticker = tokio::time::interval(interval);
+    let started = Instant::now();
+    let mut requests = JoinSet::new();
+    let mut sent = 0usize;
+
+    eprintln!(
+        "Sending {} commands/s from {} users to {} for {:?}",
+        options.rate, options.users, url, options.duration
+    );
+    while started.elapsed() < options.duration {
+        ticker.tick().await;
+        let body = command_body(&user_id(sent % options.users), &options.text, sent);
+        let request = signed_request(&client, &url, &options.secret, body);
+        requests.spawn(async move {
+            let sent_at = Instant::now();
+            let outcome = match request.send().await {
+                Ok(response) => classify(response.status().as_u16(), response.json().await.ok()),
+                Err(_) => Outcome::HttpError,
+            };
+            (sent_at.elapsed(), outcome)
+        });
+        sent += 1;
+    }
+
+    let mut results = Vec::with_capacity(sent);
+    while let Some(result) = requests.join_next().await {
+        results.push(result?);
+    }
+    let report = Report::new(results, started.elapsed());
+    if options.json {
+        println!("{}", serde_json::to_string_pretty(&report.to_json())?);
+    } else {
+        print!("{}", report.table());
+    }
+    Ok(())
+}
+
+/// Registers the synthetic users with a Google token that stays valid for
+/// a day, so their commands get as far as creating a meeting.
+async fn seed_tokens(users: usize) -> Result<()> {
+    let database_url = std::env::var("DATABASE_URL").context("--seed needs DATABASE_URL")?;
+    let crypto = TokenCrypto::from_config(|name| std::env::var(name).ok())?;
+    let db = Database::new(&database_url, crypto).await?;
+    for index in 0..users {
+        let user = db.create_user(&user_id(index), TEAM_ID).await?;
+        db.store_oauth_token(&OAuthToken::new(
+            user.id,
+            "loadtest-access-token".into(),
+            None,
+            Some(chrono::Utc::now() + chrono::Duration::days(1)),
+            Some("https://www.googleapis.com/auth/meetings.space.created".to_string()),
+        ))
+        .await?;
+    }
+    db.close().await;
+    eprintln!("Seeded Google tokens for {} users", users);
+    Ok(())
+}
+
+/// The form body Slack sends for `/meet <text>`. Every command gets its own
+/// trigger id, as it would from Slack, so none are taken for retries.
+fn command_body(user_id: &str, text: &str, sequence: usize) -> String {
+    serde_urlencoded::to_string([
+        ("token", "loadtest"),
+        ("team_id", TEAM_ID),
+        ("team_domain", "loadtest"),
+        ("channel_id", CHANNEL_ID),
+        ("channel_name", "loadtest"),
+        ("user_id", user_id),
+        ("user_name", user_id),
+        ("command", "/meet"),
+        ("text", text),
+        (
+            "response_url",
+            "https://hooks.slack.com/commands/0/loadtest",
+        ),
+        ("trigger_id", &format!("{}.loadtest", sequence)),
+    ])
+    .expect("form fields are plain strings")
+}
+
+fn signed_request(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    body: String,
+) -> reqwest::RequestBuilder {
+    let timestamp = chrono::Utc::now().timestamp().to_string();
+    client
+        .post(url)
+        .header("content-type", "application/x-www-form-urlencoded")
+        .header("x-slack-signature", signature(secret, &timestamp, &body))
+        .header("x-slack-request-timestamp", timestamp)
+        .body(body)
+}
+
+/// Slack's `v0=` request signature.
+fn signature(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("any key length works");
+    mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+    format!("v0={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn classify(status: u16, reply: Option<Value>) -> Outcome {
+    let Some(reply) = reply.filter(|_| status == 200) else {
+        return Outcome::HttpError;
+    };
+    let text = reply["text"].as_str().unwrap_or_default();
+    if text.starts_with('🔐') {
+        Outcome::AuthRequired
+    } else if reply["response_type"] == "in_channel" {
+        Outcome::Created
+    } else if text.starts_with('⏳') {
+        Outcome::Acknowledged
+    } else if text.starts_with('⏱') || text.starts_with('🚫') || text.starts_with('✋') {
+        Outcome::RateLimited
+    } else if text.starts_with('❌') {
+        Outcome::Failed
+    } else {
+        Outcome::Other
+    }
+}
+
+struct Report {
+    elapsed: Duration,
+    /// Sorted, shortest first.
+    latencies: Vec<Duration>,
+    outcomes: BTreeMap<Outcome, usize>,
+}
+
+impl Report {
+    fn new(results: Vec<(Duration, Outcome)>, elapsed: Duration) -> Self {
+        let mut latencies: Vec<Duration> = results.iter().map(|(latency, _)| *latency).collect();
+        latencies.sort();
+        let mut outcomes = BTreeMap::new();
+        for (_, outcome) in results {
+            *outcomes.entry(outcome).or_insert(0) += 1;
+        }
+        Self {
+            elapsed,
+            latencies,
+            outcomes,
+        }
+    }
+
+    /// Nearest-rank percentile; zero without any requests.
+    fn percentile(&self, percent: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (percent / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+
+    fn over_deadline(&self) -> usize {
+        self.latencies
+            .iter()
+            .filter(|latency| **latency > SLACK_DEADLINE)
+            .count()
+    }
+
+    fn to_json(&self) -> Value {
+        let millis = |d: Duration| d.as_secs_f64() * 1000.0;
+        json!({
+            "requests": self.latencies.len(),
+            "elapsed_seconds": self.elapsed.as_secs_f64(),
+            "latency_ms": {
+                "p50": millis(self.percentile(50.0)),
+                "p90": millis(self.percentile(90.0)),
+                "p99": millis(self.percentile(99.0)),
+                "max": millis(self.percentile(100.0)),
+            },
+            "over_slack_deadline": self.over_deadline(),
+            "outcomes": self
+                .outcomes
+                .iter()
+                .map(|(outcome, count)| (outcome.as_str(), *count))
+                .collect::<BTreeMap<_, _>>(),
+        })
+    }
+
+    fn table(&self) -> String {
+        let mut out = String::new();
+        let mut row = |label: &str, value: String| {
+            out.push_str(&format!("{:<22}{:>12}\n", label, value));
+        };
+        row("requests", self.latencies.len().to_string());
+        row("elapsed", format!("{:.1}s", self.elapsed.as_secs_f64()));
+        for (label, percent) in [("p50", 50.0), ("p90", 90.0), ("p99", 99.0), ("max", 100.0)] {
+            row(
+                label,
+                format!("{:.1}ms", self.percentile(percent).as_secs_f64() * 1000.0),
+            );
+        }
+        row("over 3s deadline", self.over_deadline().to_string());
+        for (outcome, count) in &self.outcomes {
+            row(outcome.as_str(), count.to_string());
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use meet_slack_bot::slack::verification::verify_slack_request;
+    use meet_slack_bot::validation::InputValidator;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let options = parse_args(
+            &args(&[
+                "--url",
+                "http://localhost:3000/",
+                "--users",
+                "5",
+                "--rate",
+                "2.5",
+                "--json",
+            ]),
+            |name| (name == "SLACK_SIGNING_SECRET").then(|| "secret".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(options.url, "http://localhost:3000");
+        assert_eq!(options.secret, "secret");
+        assert_eq!(options.users, 5);
+        assert_eq!(options.rate, 2.5);
+        assert_eq!(options.duration, Duration::from_secs(30));
+        assert!(options.json);
+        assert!(!options.seed);
+    }
+
+    #[test]
+    fn test_rejects_bad_args() {
+        let no_env = |_: &str| None;
+        for (bad, expected) in [
+            (vec![], "--url is required"),
+            (
+                vec!["--url", "http://x"],
+                "SLACK_SIGNING_SECRET is required",
+            ),
+            (vec!["--url"], "--url needs a value"),
+            (
+                vec!["--url", "http://x", "--secret", "s", "--users", "0"],
+                "--users must be",
+            ),
+            (
+                vec!["--url", "http://x", "--secret", "s", "--rate", "-1"],
+                "--rate must be",
+            ),
+            (vec!["--verbose"], "Unrecognized argument"),
+        ] {
+            let error = parse_args(&args(&bad), no_env).unwrap_err().to_string();
+            assert!(error.contains(expected), "{:?}: {}", bad, error);
+        }
+    }
+
+    #[test]
+    fn test_commands_pass_the_bots_checks() {
+        let validator = InputValidator::default();
+        validator.validate_slack_user_id(&user_id(42)).unwrap();
+        validator.validate_slack_team_id(TEAM_ID).unwrap();
+        validator.validate_slack_channel_id(CHANNEL_ID).unwrap();
+
+        let body = command_body(&user_id(42), "Standup", 7);
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        verify_slack_request(
+            &Default::default(),
+            "secret",
+            &signature("secret", &timestamp, &body),
+            &timestamp,
+            &body,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_classifies_replies() {
+        let cases = [
+            (
+                json!({"response_type": "in_channel", "text": "🎥 Google Meet created"}),
+                Outcome::Created,
+            ),
+            (
+                json!({"response_type": "ephemeral", "text": "⏳ Creating your Google Meet…"}),
+                Outcome::Acknowledged,
+            ),
+            (
+                json!({"response_type": "ephemeral", "text": "🔐 Authentication needed", "blocks": []}),
+                Outcome::AuthRequired,
+            ),
+            (
+                json!({"response_type": "ephemeral", "text": "⏱️ Please slow down!"}),
+                Outcome::RateLimited,
+            ),
+            (
+                json!({"response_type": "ephemeral", "text": "🚫 Service temporarily unavailable"}),
+                Outcome::RateLimited,
+            ),
+            (
+                json!({"response_type": "ephemeral", "text": "✋ Hang on, I'm already creating your meeting."}),
+                Outcome::RateLimited,
+            ),
+            (
+                json!({"response_type": "ephemeral", "text": "❌ Sorry"}),
+                Outcome::Failed,
+            ),
+            (
+                json!({"response_type": "ephemeral", "text": "*Usage*"}),
+                Outcome::Other,
+            ),
+        ];
+        for (reply, expected) in cases {
+            assert_eq!(classify(200, Some(reply.clone())), expected, "{}", reply);
+        }
+        assert_eq!(classify(401, Some(json!({}))), Outcome::HttpError);
+        assert_eq!(classify(200, None), Outcome::HttpError);
+    }
+
+    #[test]
+    fn test_report() {
+        let results = (1..=100)
+            .map(|ms| {
+                let outcome = if ms % 10 == 0 {
+                    Outcome::RateLimited
+                } else {
+                    Outcome::Created
+                };
+                (Duration::from_millis(ms * 40), outcome)
+            })
+            .collect();
+        let report = Report::new(results, Duration::from_secs(10));
+
+        assert_eq!(report.percentile(50.0), Duration::from_millis(2000));
+        assert_eq!(report.percentile(99.0), Duration::from_millis(3960));
+        assert_eq!(report.percentile(100.0), Duration::from_millis(4000));
+        assert_eq!(report.over_deadline(), 25);
+
+        let json = report.to_json();
+        assert_eq!(json["requests"], 100);
+        assert_eq!(json["outcomes"]["created"], 90);
+        assert_eq!(json["outcomes"]["rate_limited"], 10);
+        assert!(report.table().contains("over 3s deadline"));
+    }
+}
@@ -0,0 +1,57 @@
+use chrono::DateTime;
+use serde_json::{json, Value};
+
+/// Shown for anything the build couldn't determine, such as the commit of
+/// a crate built from a tarball.
+pub const UNKNOWN: &str = "unknown";
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Abbreviated commit the binary was built from, set by `build.rs`.
+pub const GIT_SHA: &str = or_unknown(option_env!("GIT_SHA"));
+
+/// Unix time of the build, set by `build.rs`.
+const BUILD_TIMESTAMP: Option<&str> = option_env!("BUILD_TIMESTAMP");
+
+const fn or_unknown(value: Option<&'static str>) -> &'static str {
+    match value {
+        Some(value) => value,
+        None => UNKNOWN,
+    }
+}
+
+/// When the binary was built, as RFC 3339.
+pub fn build_time() -> String {
+    format_build_time(BUILD_TIMESTAMP)
+}
+
+fn format_build_time(timestamp: Option<&str>) -> String {
+    timestamp
+        .and_then(|secs| secs.parse().ok())
+        .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_else(|| UNKNOWN.to_string())
+}
+
+/// The version, commit and build time, as served by `/version`.
+pub fn as_json() -> Value {
+    json!({
+        "version": VERSION,
+        "git_sha": GIT_SHA,
+        "build_time": build_time(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_build_info_reads_unknown() {
+        assert_eq!(or_unknown(None), "unknown");
+        assert_eq!(or_unknown(Some("0123456789ab")), "0123456789ab");
+        assert_eq!(format_build_time(None), "unknown");
+        assert_eq!(format_build_time(Some("not a number")), "unknown");
+        assert_eq!(format_build_time(Some("0")), "1970-01-01T00:00:00+00:00");
+    }
+}
@@ -0,0 +1,120 @@
+use axum::response::Response;
+use std::any::Any;
+use tower_http::catch_panic::CatchPanicLayer;
+use tracing::error;
+
+use crate::error::internal_error_response;
+use crate::telemetry::metrics;
+
+type PanicHandler = fn(Box<dyn Any + Send + 'static>) -> Response;
+
+/// Catches panics in the layers and handlers inside it, logging and
+/// counting them. Must sit inside [`crate::error::reply`], which shapes the
+/// response like that of any other internal error.
+pub fn layer() -> CatchPanicLayer<PanicHandler> {
+    CatchPanicLayer::custom(on_panic as PanicHandler)
+}
+
+fn on_panic(panic: Box<dyn Any + Send + 'static>) -> Response {
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("non-string panic payload");
+    error!(tags.error_kind = "panic", "Handler panicked: {}", message);
+    metrics::record_handler_panic();
+
+    internal_error_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_id::{self, REQUEST_ID_HEADER};
+    use axum::{body::Body, extract::Request, http::StatusCode, middleware, routing::post, Router};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/slack/commands",
+                post(|| async {
+                    panic!("command handler bug");
+                    #[allow(unreachable_code)]
+                    ""
+                }),
+            )
+            .route(
+                "/slack/events",
+                post(|| async {
+                    panic!("{} handler bug", "event");
+                    #[allow(unreachable_code)]
+                    ""
+                }),
+            )
+            .route("/slack/interactions", post(|| async { "fine" }))
+            .layer(layer())
+            .layer(middleware::from_fn(crate::error::reply))
+            .layer(middleware::from_fn(request_id::propagate))
+    }
+
+    async fn send(uri: &str) -> (StatusCode, String, String) {
+        let response = app()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let request_id = response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            request_id,
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_panicking_command_gets_an_ephemeral_reply() {
+        let (status, request_id, body) = send("/slack/commands").await;
+
+        assert_eq!(status, StatusCode::OK);
+        let reply: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(reply["response_type"], "ephemeral");
+        assert_eq!(
+            reply["text"],
+            format!(
+                "❌ Something went wrong. Please try again. (ref: {})",
+                &request_id[..6]
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_other_panics_get_a_json_500() {
+        let (status, request_id, body) = send("/slack/events").await;
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        let reply: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(reply["error"], "internal_error");
+        assert_eq!(reply["request_id"], request_id);
+    }
+
+    #[tokio::test]
+    async fn test_normal_responses_pass_through() {
+        let (status, _, body) = send("/slack/interactions").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "fine");
+    }
+}
@@ -0,0 +1,169 @@
+//! Which channels of a workspace meetings may be created in, set by admins
+//! with `/meet set team allow-channel` and `deny-channel` so that, say,
+//! announcement channels stay free of meeting links.
+//!
+//! Both lists hold channel ids and are stored comma-separated in
+//! `team_settings`. A channel on the blocklist is refused even when it is
+//! on the allowlist too, and an empty allowlist allows every channel that
+//! isn't blocked.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+
+use crate::database::Database;
+
+/// The `team_settings` key of the channels meetings are limited to.
+pub const ALLOWED_KEY: &str = "allowed_channels";
+
+/// The `team_settings` key of the channels meetings are refused in.
+pub const BLOCKED_KEY: &str = "blocked_channels";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChannelPolicy {
+    pub allowed: BTreeSet<String>,
+    pub blocked: BTreeSet<String>,
+}
+
+/// Why a channel was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Refusal {
+    Blocked,
+    NotAllowed,
+}
+
+impl ChannelPolicy {
+    /// Whether meetings may be created in `channel_id`. The blocklist wins
+    /// over the allowlist, and an empty allowlist allows every channel.
+    pub fn check(&self, channel_id: &str) -> Result<(), Refusal> {
+        if self.blocked.contains(channel_id) {
+            Err(Refusal::Blocked)
+        } else if !self.allowed.is_empty() && !self.allowed.contains(channel_id) {
+            Err(Refusal::NotAllowed)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The reply to a command refused with `refusal`, naming the policy.
+    pub fn refusal_message(&self, refusal: Refusal) -> String {
+        match refusal {
+            Refusal::Blocked => {
+                "🚫 Your workspace admins don't allow creating meetings in this channel."
+                    .to_string()
+            }
+            Refusal::NotAllowed => format!(
+                "🚫 Your workspace admins only allow creating meetings in {}.",
+                mentions(&self.allowed)
+            ),
+        }
+    }
+}
+
+/// `channels` as Slack channel links, comma-separated.
+pub fn mentions(channels: &BTreeSet<String>) -> String {
+    channels
+        .iter()
+        .map(|id| format!("<#{}>", id))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The channel policy of `team`; without one, every channel is allowed.
+pub async fn for_team(db: &Database, team: &str) -> Result<ChannelPolicy> {
+    Ok(ChannelPolicy {
+        allowed: parse_list(db.get_team_setting(team, ALLOWED_KEY).await?.as_deref()),
+        blocked: parse_list(db.get_team_setting(team, BLOCKED_KEY).await?.as_deref()),
+    })
+}
+
+/// Stores `channels` under `key`, dropping the setting once it's empty.
+pub async fn save_list(
+    db: &Database,
+    team: &str,
+    key: &str,
+    channels: &BTreeSet<String>,
+) -> Result<()> {
+    if channels.is_empty() {
+        db.delete_team_setting(team, key).await
+    } else {
+        let value = channels.iter().cloned().collect::<Vec<_>>().join(",");
+        db.set_team_setting(team, key, &value).await
+    }
+}
+
+fn parse_list(value: Option<&str>) -> BTreeSet<String> {
+    value
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(allowed: &[&str], blocked: &[&str]) -> ChannelPolicy {
+        ChannelPolicy {
+            allowed: allowed.iter().map(|id| id.to_string()).collect(),
+            blocked: blocked.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let cases = [
+            // No lists: everything goes
+            (policy(&[], &[]), "C1", Ok(())),
+            // Only a blocklist
+            (policy(&[], &["C1"]), "C1", Err(Refusal::Blocked)),
+            (policy(&[], &["C1"]), "C2", Ok(())),
+            // Only an allowlist
+            (policy(&["C1"], &[]), "C1", Ok(())),
+            (policy(&["C1"], &[]), "C2", Err(Refusal::NotAllowed)),
+            // The blocklist wins over the allowlist
+            (policy(&["C1", "C2"], &["C1"]), "C1", Err(Refusal::Blocked)),
+            (policy(&["C1", "C2"], &["C1"]), "C2", Ok(())),
+            (
+                policy(&["C1", "C2"], &["C1"]),
+                "C3",
+                Err(Refusal::NotAllowed),
+            ),
+        ];
+
+        for (policy, channel, expected) in cases {
+            assert_eq!(
+                policy.check(channel),
+                expected,
+                "{:?} in {}",
+                policy,
+                channel
+            );
+        }
+    }
+
+    #[test]
+    fn test_refusal_names_the_policy() {
+        let policy = policy(&["C2", "C1"], &["C3"]);
+
+        assert_eq!(
+            policy.refusal_message(Refusal::NotAllowed),
+            "🚫 Your workspace admins only allow creating meetings in <#C1>, <#C2>."
+        );
+        assert!(policy
+            .refusal_message(Refusal::Blocked)
+            .contains("don't allow creating meetings in this channel"));
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert!(parse_list(None).is_empty());
+        assert!(parse_list(Some("")).is_empty());
+        assert_eq!(
+            parse_list(Some("C2, C1,,C2")),
+            ["C1", "C2"].iter().map(|id| id.to_string()).collect()
+        );
+    }
+}
@@ -0,0 +1,1862 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Weekday};
+use std::collections::BTreeSet;
+
+use crate::digest;
+
+/// A `/meet` invocation after parsing the free-form command text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MeetCommand {
+    Create {
+        title: Option<String>,
+        duration: Option<Duration>,
+        start: Option<StartSpec>,
+        attendees: Vec<Attendee>,
+        /// People given with `--cohost`, to be made co-hosts of the space.
+        cohosts: Vec<Attendee>,
+        flags: BTreeSet<String>,
+    },
+    List {
+        limit: Option<u32>,
+    },
+    Cancel {
+        meeting_id: Option<i64>,
+    },
+    /// Ends the call of the user's latest meeting, or meeting `meeting_id`.
+    End {
+        meeting_id: Option<i64>,
+    },
+    /// The channel's latest meeting, shown to everyone with `share`.
+    Join {
+        share: bool,
+    },
+    /// Who joined the user's latest meeting, or the one at `meeting`, a
+    /// Meet link or code.
+    Attendance {
+        meeting: Option<String>,
+    },
+    /// The recordings and transcripts of the user's latest meeting, or the
+    /// one at `meeting`, shown to everyone with `share`.
+    Notes {
+        meeting: Option<String>,
+        share: bool,
+    },
+    /// A short link to the user's latest meeting, or the one at `meeting`,
+    /// that anyone can follow; `off` stops it working.
+    ShareLink {
+        meeting: Option<String>,
+        off: bool,
+    },
+    /// A poll for the channel to vote on when to meet, among `options`.
+    Poll {
+        title: Option<String>,
+        options: Vec<StartSpec>,
+    },
+    Status,
+    Stats,
+    Logout,
+    ExportData,
+    Help,
+    Set {
+        scope: SetScope,
+        key: String,
+        value: String,
+    },
+    Admin(AdminCommand),
+}
+
+impl MeetCommand {
+    /// Short name of the subcommand, for logs and metrics.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MeetCommand::Create { .. } => "create",
+            MeetCommand::List { .. } => "list",
+            MeetCommand::Cancel { .. } => "cancel",
+            MeetCommand::End { .. } => "end",
+            MeetCommand::Join { .. } => "join",
+            MeetCommand::Attendance { .. } => "attendance",
+            MeetCommand::Notes { .. } => "notes",
+            MeetCommand::ShareLink { .. } => "share-link",
+            MeetCommand::Poll { .. } => "poll",
+            MeetCommand::Status => "status",
+            MeetCommand::Stats => "stats",
+            MeetCommand::Logout => "logout",
+            MeetCommand::ExportData => "export-my-data",
+            MeetCommand::Help => "help",
+            MeetCommand::Set { .. } => "set",
+            MeetCommand::Admin(_) => "admin",
+        }
+    }
+}
+
+/// When a meeting should start, as typed by the user. Resolving this to an
+/// absolute instant needs the user's timezone and happens later.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartSpec {
+    pub day: Option<DaySpec>,
+    pub time: Option<NaiveTime>,
+}
+
+impl StartSpec {
+    /// Time used when only a day is given.
+    pub const DEFAULT_TIME: NaiveTime = match NaiveTime::from_hms_opt(9, 0, 0) {
+        Some(time) => time,
+        None => unreachable!(),
+    };
+
+    /// Resolves the spec to an absolute instant, interpreting it in the
+    /// timezone of `now`. A bare time means today, a bare day means
+    /// [`Self::DEFAULT_TIME`], and a weekday means its next occurrence (today
+    /// if that time hasn't passed yet). Returns `None` for local times that
+    /// don't exist, e.g. inside a DST gap.
+    pub fn resolve<Tz: TimeZone>(&self, now: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let today = now.date_naive();
+        let time = self.time.unwrap_or(Self::DEFAULT_TIME);
+
+        let date = match self.day {
+            None | Some(DaySpec::Today) => today,
+            Some(DaySpec::Tomorrow) => today.succ_opt()?,
+            Some(DaySpec::Date(date)) => date,
+            Some(DaySpec::Weekday(weekday)) => {
+                let days_ahead = (7 + weekday.num_days_from_monday() as i64
+                    - today.weekday().num_days_from_monday() as i64)
+                    % 7;
+                let days_ahead = if days_ahead == 0 && time <= now.time() {
+                    7
+                } else {
+                    days_ahead
+                };
+                today + Duration::days(days_ahead)
+            }
+        };
+
+        now.timezone()
+            .from_local_datetime(&date.and_time(time))
+            .earliest()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DaySpec {
+    Today,
+    Tomorrow,
+    Weekday(Weekday),
+    Date(NaiveDate),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Attendee {
+    /// An escaped Slack mention such as `<@U123ABC|alice>`.
+    SlackUser(String),
+    /// A plain `@handle` that Slack did not escape.
+    Handle(String),
+    /// An email address, either bare or escaped as `<mailto:...>`.
+    Email(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetScope {
+    User,
+    Team,
+}
+
+/// `/meet admin` actions, for the bot's admins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    /// List the feature flags and whether they are on in the workspace.
+    Flags,
+    /// Override a feature flag for the workspace; `None` drops the override.
+    /// The flag is checked by the handler, which can list the known ones.
+    SetFlag { flag: String, enabled: Option<bool> },
+    /// Show where and when the weekly digest is posted.
+    Digest,
+    /// Post the weekly digest to `channel_id`, or the channel the command
+    /// was run in when `None`, every `weekday` at `hour` UTC.
+    SetDigest {
+        channel_id: Option<String>,
+        weekday: Weekday,
+        hour: u32,
+    },
+    /// Stop posting the weekly digest.
+    DigestOff,
+    /// List the workspace's REST API keys.
+    ApiKeys,
+    /// Make a REST API key for the workspace.
+    CreateApiKey,
+    /// Revoke the workspace's REST API key numbered `id`.
+    RevokeApiKey { id: i64 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    #[error("A quoted title is missing its closing quote")]
+    UnterminatedQuote,
+
+    #[error("`--{flag}` needs a value")]
+    MissingFlagValue { flag: &'static str },
+
+    #[error("Flags must look like `--name`")]
+    MalformedFlag,
+
+    #[error("`--cohost` takes a Slack mention or an email")]
+    InvalidCohost,
+
+    #[error("I couldn't understand the duration. Try something like `30m` or `1h30m`")]
+    InvalidDuration,
+
+    #[error("I couldn't understand the start time. Try something like `14:00` or `tomorrow 2pm`")]
+    InvalidTime,
+
+    #[error("The duration was given more than once")]
+    DuplicateDuration,
+
+    #[error("The start time was given more than once")]
+    DuplicateStart,
+
+    #[error("`{subcommand}` doesn't take that argument")]
+    UnexpectedArgument { subcommand: &'static str },
+
+    #[error(
+        "A poll needs {MIN_POLL_OPTIONS} to {MAX_POLL_OPTIONS} times separated by commas, e.g. `poll \"Retro\" tomorrow 14:00, friday 10:00`"
+    )]
+    InvalidPoll,
+
+    #[error("`set` needs a setting name, e.g. `set <name> <value>`")]
+    MissingSetKey,
+
+    #[error(
+        "Try `admin flags`, `admin flags <flag> on|off|default`, `admin digest here|#channel [weekday] [hour]` or `admin api-key create|revoke <id>`"
+    )]
+    InvalidAdminCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    quoted: bool,
+}
+
+/// Parses the text following `/meet` into a [`MeetCommand`].
+///
+/// Anything that isn't a recognized subcommand creates a meeting: mentions and
+/// emails become attendees, duration (`30m`, `1h30m`) and time (`14:00`,
+/// `tomorrow 2pm`) tokens are picked out, and the remaining words form the
+/// title. Quoted text is always treated as part of the title.
+pub fn parse(text: &str) -> Result<MeetCommand, ParseError> {
+    let text = text.trim();
+    let tokens = tokenize(text)?;
+
+    let Some(first) = tokens.first() else {
+        return Ok(empty_create());
+    };
+
+    if !first.quoted {
+        match first.text.to_lowercase().as_str() {
+            "help" | "--help" | "-h" => return no_args(&tokens, "help", MeetCommand::Help),
+            "status" => return no_args(&tokens, "status", MeetCommand::Status),
+            "stats" => return no_args(&tokens, "stats", MeetCommand::Stats),
+            "logout" | "disconnect" => return no_args(&tokens, "logout", MeetCommand::Logout),
+            "export-my-data" => return no_args(&tokens, "export-my-data", MeetCommand::ExportData),
+            "list" => return parse_list(&tokens),
+            "cancel" => {
+                return parse_meeting_id(&tokens, "cancel", |meeting_id| MeetCommand::Cancel {
+                    meeting_id,
+                })
+            }
+            "end" => {
+                return parse_meeting_id(&tokens, "end", |meeting_id| MeetCommand::End {
+                    meeting_id,
+                })
+            }
+            "join" => return parse_join(&tokens),
+            "attendance" => return parse_attendance(&tokens),
+            "notes" => return parse_notes(&tokens),
+            "share-link" => return parse_share_link(&tokens),
+            "poll" => return parse_poll(&tokens),
+            "new" => return parse_new(tokens),
+            "set" => return parse_set(text),
+            "admin" => return parse_admin(&tokens),
+            _ => {}
+        }
+    }
+
+    parse_create(tokens)
+}
+
+fn empty_create() -> MeetCommand {
+    MeetCommand::Create {
+        title: None,
+        duration: None,
+        start: None,
+        attendees: Vec::new(),
+        cohosts: Vec::new(),
+        flags: BTreeSet::new(),
+    }
+}
+
+fn no_args(
+    tokens: &[Token],
+    subcommand: &'static str,
+    command: MeetCommand,
+) -> Result<MeetCommand, ParseError> {
+    if tokens.len() > 1 {
+        return Err(ParseError::UnexpectedArgument { subcommand });
+    }
+    Ok(command)
+}
+
+fn parse_list(tokens: &[Token]) -> Result<MeetCommand, ParseError> {
+    match tokens {
+        [_] => Ok(MeetCommand::List { limit: None }),
+        [_, limit] => limit
+            .text
+            .parse::<u32>()
+            .ok()
+            .filter(|limit| *limit > 0)
+            .map(|limit| MeetCommand::List { limit: Some(limit) })
+            .ok_or(ParseError::UnexpectedArgument { subcommand: "list" }),
+        _ => Err(ParseError::UnexpectedArgument { subcommand: "list" }),
+    }
+}
+
+fn parse_join(tokens: &[Token]) -> Result<MeetCommand, ParseError> {
+    match tokens {
+        [_] => Ok(MeetCommand::Join { share: false }),
+        [_, flag] if flag.text.eq_ignore_ascii_case("--share") => {
+            Ok(MeetCommand::Join { share: true })
+        }
+        _ => Err(ParseError::UnexpectedArgument { subcommand: "join" }),
+    }
+}
+
+fn parse_attendance(tokens: &[Token]) -> Result<MeetCommand, ParseError> {
+    match tokens {
+        [_] => Ok(MeetCommand::Attendance { meeting: None }),
+        [_, meeting] => Ok(MeetCommand::Attendance {
+            meeting: Some(meeting_argument(meeting)),
+        }),
+        _ => Err(ParseError::UnexpectedArgument {
+            subcommand: "attendance",
+        }),
+    }
+}
+
+fn parse_notes(tokens: &[Token]) -> Result<MeetCommand, ParseError> {
+    let (flags, meetings): (Vec<&Token>, Vec<&Token>) = tokens[1..]
+        .iter()
+        .partition(|token| token.text.starts_with("--"));
+    let share = match flags.as_slice() {
+        [] => false,
+        [flag] if flag.text.eq_ignore_ascii_case("--share") => true,
+        _ => {
+            return Err(ParseError::UnexpectedArgument {
+                subcommand: "notes",
+            })
+        }
+    };
+    match meetings.as_slice() {
+        [] => Ok(MeetCommand::Notes {
+            meeting: None,
+            share,
+        }),
+        [meeting] => Ok(MeetCommand::Notes {
+            meeting: Some(meeting_argument(meeting)),
+            share,
+        }),
+        _ => Err(ParseError::UnexpectedArgument {
+            subcommand: "notes",
+        }),
+    }
+}
+
+fn parse_share_link(tokens: &[Token]) -> Result<MeetCommand, ParseError> {
+    let (flags, meetings): (Vec<&Token>, Vec<&Token>) = tokens[1..]
+        .iter()
+        .partition(|token| token.text.starts_with("--"));
+    let off = match flags.as_slice() {
+        [] => false,
+        [flag] if flag.text.eq_ignore_ascii_case("--off") => true,
+        _ => {
+            return Err(ParseError::UnexpectedArgument {
+                subcommand: "share-link",
+            })
+        }
+    };
+    match meetings.as_slice() {
+        [] => Ok(MeetCommand::ShareLink { meeting: None, off }),
+        [meeting] => Ok(MeetCommand::ShareLink {
+            meeting: Some(meeting_argument(meeting)),
+            off,
+        }),
+        _ => Err(ParseError::UnexpectedArgument {
+            subcommand: "share-link",
+        }),
+    }
+}
+
+/// Fewest times a poll offers.
+pub const MIN_POLL_OPTIONS: usize = 2;
+/// Most times a poll offers, which keeps its message readable.
+pub const MAX_POLL_OPTIONS: usize = 10;
+
+/// `poll ["title"] <when>, <when>, ...`: quoted text is the title, and the
+/// rest is times separated by commas. Words before the first time are part
+/// of the title too, so `poll retro friday 10:00, friday 14:00` works.
+fn parse_poll(tokens: &[Token]) -> Result<MeetCommand, ParseError> {
+    let mut title_words: Vec<&str> = tokens[1..]
+        .iter()
+        .filter(|token| token.quoted)
+        .map(|token| token.text.as_str())
+        .collect();
+    let times = tokens[1..]
+        .iter()
+        .filter(|token| !token.quoted)
+        .map(|token| token.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let mut options = Vec::new();
+    for (i, segment) in times.split(',').enumerate() {
+        let words: Vec<&str> = segment.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let (spec, used) = if i == 0 {
+            parse_when_suffix(&words)?
+        } else {
+            (parse_when(&words)?, words.len())
+        };
+        title_words.extend(&words[..words.len() - used]);
+        options.push(spec);
+    }
+
+    if !(MIN_POLL_OPTIONS..=MAX_POLL_OPTIONS).contains(&options.len()) {
+        return Err(ParseError::InvalidPoll);
+    }
+    let title = title_words.join(" ");
+    Ok(MeetCommand::Poll {
+        title: (!title.is_empty()).then_some(title),
+        options,
+    })
+}
+
+/// The time at the end of `words`, as one or two words, and how many it
+/// took.
+fn parse_when_suffix(words: &[&str]) -> Result<(StartSpec, usize), ParseError> {
+    if words.len() >= 2 {
+        if let Ok(spec) = parse_when(&words[words.len() - 2..]) {
+            return Ok((spec, 2));
+        }
+    }
+    match words.last() {
+        Some(word) => parse_when(&[word]).map(|spec| (spec, 1)),
+        None => Err(ParseError::InvalidPoll),
+    }
+}
+
+/// A Meet link or code given as an argument. Slack sends links as `<url>`
+/// or `<url|label>`.
+fn meeting_argument(token: &Token) -> String {
+    let meeting = token.text.trim_start_matches('<').trim_end_matches('>');
+    meeting.split('|').next().unwrap_or_default().to_string()
+}
+
+/// A subcommand taking a meeting as `#id`, or `last` or nothing for the
+/// latest one.
+fn parse_meeting_id(
+    tokens: &[Token],
+    subcommand: &'static str,
+    command: fn(Option<i64>) -> MeetCommand,
+) -> Result<MeetCommand, ParseError> {
+    match tokens {
+        [_] => Ok(command(None)),
+        [_, target] if target.text.eq_ignore_ascii_case("last") => Ok(command(None)),
+        [_, target] => target
+            .text
+            .trim_start_matches('#')
+            .parse::<i64>()
+            .ok()
+            .filter(|id| *id > 0)
+            .map(|id| command(Some(id)))
+            .ok_or(ParseError::UnexpectedArgument { subcommand }),
+        _ => Err(ParseError::UnexpectedArgument { subcommand }),
+    }
+}
+
+/// `set` keeps its value verbatim (quotes, braces and all), so it works on the
+/// raw text rather than on tokens.
+fn parse_set(text: &str) -> Result<MeetCommand, ParseError> {
+    let rest = split_word(text).1;
+    let (mut scope, mut rest) = (SetScope::User, rest);
+
+    let (word, after) = split_word(rest);
+    if word.eq_ignore_ascii_case("team") {
+        scope = SetScope::Team;
+        rest = after;
+    }
+
+    let (key, value) = split_word(rest);
+    if key.is_empty() {
+        return Err(ParseError::MissingSetKey);
+    }
+
+    Ok(MeetCommand::Set {
+        scope,
+        key: key.to_lowercase(),
+        value: value.to_string(),
+    })
+}
+
+fn parse_admin(tokens: &[Token]) -> Result<MeetCommand, ParseError> {
+    let words: Vec<String> = tokens[1..]
+        .iter()
+        .map(|token| token.text.to_lowercase())
+        .collect();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+
+    let command = match words.as_slice() {
+        ["flags"] => AdminCommand::Flags,
+        ["flags", flag, state] => AdminCommand::SetFlag {
+            flag: flag.to_string(),
+            enabled: match *state {
+                "on" => Some(true),
+                "off" => Some(false),
+                "default" => None,
+                _ => return Err(ParseError::InvalidAdminCommand),
+            },
+        },
+        ["digest"] => AdminCommand::Digest,
+        ["digest", "off"] => AdminCommand::DigestOff,
+        ["digest", _, schedule @ ..] => parse_digest_schedule(&tokens[2].text, schedule)?,
+        ["api-key" | "api-keys"] => AdminCommand::ApiKeys,
+        ["api-key", "create"] => AdminCommand::CreateApiKey,
+        ["api-key", "revoke", id] => AdminCommand::RevokeApiKey {
+            id: id
+                .trim_start_matches('#')
+                .parse()
+                .map_err(|_| ParseError::InvalidAdminCommand)?,
+        },
+        _ => return Err(ParseError::InvalidAdminCommand),
+    };
+    Ok(MeetCommand::Admin(command))
+}
+
+/// `here` or a channel as Slack escapes it (`<#C012AB3CD|general>`), then an
+/// optional weekday and UTC hour. Channel ids keep their case.
+fn parse_digest_schedule(channel: &str, schedule: &[&str]) -> Result<AdminCommand, ParseError> {
+    let channel_id = if channel.eq_ignore_ascii_case("here") {
+        None
+    } else {
+        let id = channel_mention(channel).ok_or(ParseError::InvalidAdminCommand)?;
+        Some(id.to_string())
+    };
+
+    let (weekday, hour) = match schedule {
+        [] => (digest::DEFAULT_WEEKDAY, digest::DEFAULT_HOUR),
+        [weekday] => (parse_weekday(weekday)?, digest::DEFAULT_HOUR),
+        [weekday, hour] => (parse_weekday(weekday)?, parse_hour(hour)?),
+        _ => return Err(ParseError::InvalidAdminCommand),
+    };
+    Ok(AdminCommand::SetDigest {
+        channel_id,
+        weekday,
+        hour,
+    })
+}
+
+/// The id of a channel as Slack escapes it in command text,
+/// `<#C012AB3CD|general>` or `<#C012AB3CD>`.
+pub fn channel_mention(text: &str) -> Option<&str> {
+    text.strip_prefix("<#")
+        .and_then(|rest| rest.strip_suffix('>'))
+        .map(|rest| rest.split('|').next().unwrap_or(rest))
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+/// The id of a user as Slack escapes it in command text,
+/// `<@U012AB3CD|alice>` or `<@U012AB3CD>`.
+pub fn user_mention(text: &str) -> Option<&str> {
+    text.strip_prefix("<@")
+        .and_then(|rest| rest.strip_suffix('>'))
+        .map(|rest| rest.split('|').next().unwrap_or(rest))
+        .filter(|id| !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn parse_weekday(word: &str) -> Result<Weekday, ParseError> {
+    word.parse().map_err(|_| ParseError::InvalidAdminCommand)
+}
+
+fn parse_hour(word: &str) -> Result<u32, ParseError> {
+    word.parse()
+        .ok()
+        .filter(|hour| *hour < 24)
+        .ok_or(ParseError::InvalidAdminCommand)
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    match text.find(char::is_whitespace) {
+        Some(index) => (&text[..index], text[index..].trim()),
+        None => (text, ""),
+    }
+}
+
+/// `new [...]`: a meeting like any other, with the `new` flag so it gets a
+/// space of its own even for a user with a standing room.
+fn parse_new(mut tokens: Vec<Token>) -> Result<MeetCommand, ParseError> {
+    tokens.remove(0);
+    let mut command = parse_create(tokens)?;
+    if let MeetCommand::Create { flags, .. } = &mut command {
+        flags.insert("new".to_string());
+    }
+    Ok(command)
+}
+
+fn parse_create(tokens: Vec<Token>) -> Result<MeetCommand, ParseError> {
+    let mut title_words: Vec<String> = Vec::new();
+    let mut duration = None;
+    let mut start: Option<StartSpec> = None;
+    let mut attendees = Vec::new();
+    let mut cohosts = Vec::new();
+    let mut flags = BTreeSet::new();
+
+    let mut set_duration = |value: Duration| {
+        if duration.replace(value).is_some() {
+            return Err(ParseError::DuplicateDuration);
+        }
+        Ok(())
+    };
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        let next = tokens.get(i + 1).filter(|t| !t.quoted);
+
+        if token.quoted {
+            title_words.push(token.text.clone());
+            i += 1;
+            continue;
+        }
+
+        let lower = token.text.to_lowercase();
+
+        if let Some(flag) = lower.strip_prefix("--") {
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (flag, None),
+            };
+
+            if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                return Err(ParseError::MalformedFlag);
+            }
+
+            match name {
+                "for" | "duration" => {
+                    let (value, consumed) = flag_value(inline_value, next, "duration")?;
+                    set_duration(parse_duration(&value).ok_or(ParseError::InvalidDuration)?)?;
+                    i += 1 + consumed;
+                }
+                "at" | "start" => {
+                    if start.is_some() {
+                        return Err(ParseError::DuplicateStart);
+                    }
+                    let (spec, consumed) = match inline_value {
+                        Some(value) => (parse_when(&[value.as_str()])?, 0),
+                        None => {
+                            let words: Vec<&str> = tokens[i + 1..]
+                                .iter()
+                                .take_while(|t| !t.quoted)
+                                .take(2)
+                                .map(|t| t.text.as_str())
+                                .collect();
+                            parse_when_prefix(&words)?
+                        }
+                    };
+                    start = Some(spec);
+                    i += 1 + consumed;
+                }
+                "cohost" => {
+                    // The value keeps its case, which Slack user ids need
+                    let inline_value = token
+                        .text
+                        .split_once('=')
+                        .map(|(_, value)| value.to_string());
+                    let (value, consumed) = flag_value(inline_value, next, "cohost")?;
+                    cohosts.push(parse_attendee(&value).ok_or(ParseError::InvalidCohost)?);
+                    i += 1 + consumed;
+                }
+                "title" => {
+                    let value = match inline_value {
+                        Some(value) => value,
+                        None => tokens
+                            .get(i + 1)
+                            .map(|t| t.text.clone())
+                            .ok_or(ParseError::MissingFlagValue { flag: "title" })?,
+                    };
+                    title_words.push(value);
+                    i += if token.text.contains('=') { 1 } else { 2 };
+                }
+                _ => {
+                    if inline_value.is_some() {
+                        return Err(ParseError::MalformedFlag);
+                    }
+                    flags.insert(name.to_string());
+                    i += 1;
+                }
+            }
+            continue;
+        }
+
+        if let Some(attendee) = parse_attendee(&token.text) {
+            attendees.push(attendee);
+            i += 1;
+            continue;
+        }
+
+        // Keyword forms: "for 30m", "at 14:00", "on friday"
+        if let Some(next) = next {
+            match lower.as_str() {
+                "for" => {
+                    if let Some(value) = parse_duration(&next.text) {
+                        set_duration(value)?;
+                        i += 2;
+                        continue;
+                    }
+                }
+                "at" | "on" => {
+                    let words: Vec<&str> = tokens[i + 1..]
+                        .iter()
+                        .take_while(|t| !t.quoted)
+                        .take(2)
+                        .map(|t| t.text.as_str())
+                        .collect();
+                    if let Ok((spec, consumed)) = parse_when_prefix(&words) {
+                        merge_start(&mut start, spec)?;
+                        i += 1 + consumed;
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(value) = parse_duration(&token.text) {
+            set_duration(value)?;
+            i += 1;
+            continue;
+        }
+
+        if let Some(time) = parse_time(&token.text) {
+            let mut spec = StartSpec {
+                day: None,
+                time: Some(time),
+            };
+            let mut consumed = 1;
+            if let Some(day) = next.and_then(|t| parse_day(&t.text)) {
+                spec.day = Some(day);
+                consumed = 2;
+            }
+            merge_start(&mut start, spec)?;
+            i += consumed;
+            continue;
+        }
+
+        // A day word only counts when a time follows it ("friday 10:00");
+        // on its own it is just part of the title.
+        if let (Some(day), Some(time)) = (
+            parse_day(&token.text),
+            next.and_then(|t| parse_time(&t.text)),
+        ) {
+            merge_start(
+                &mut start,
+                StartSpec {
+                    day: Some(day),
+                    time: Some(time),
+                },
+            )?;
+            i += 2;
+            continue;
+        }
+
+        title_words.push(token.text.clone());
+        i += 1;
+    }
+
+    let title = Some(title_words.join(" "))
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    Ok(MeetCommand::Create {
+        title,
+        duration,
+        start,
+        attendees,
+        cohosts,
+        flags,
+    })
+}
+
+fn flag_value(
+    inline_value: Option<String>,
+    next: Option<&Token>,
+    flag: &'static str,
+) -> Result<(String, usize), ParseError> {
+    match inline_value {
+        Some(value) if !value.is_empty() => Ok((value, 0)),
+        Some(_) => Err(ParseError::MissingFlagValue { flag }),
+        None => next
+            .map(|t| (t.text.clone(), 1))
+            .ok_or(ParseError::MissingFlagValue { flag }),
+    }
+}
+
+fn merge_start(start: &mut Option<StartSpec>, spec: StartSpec) -> Result<(), ParseError> {
+    match start {
+        None => {
+            *start = Some(spec);
+            Ok(())
+        }
+        // "on friday ... at 10:00" fills in the missing half
+        Some(existing) if existing.day.is_none() && spec.time.is_none() => {
+            existing.day = spec.day;
+            Ok(())
+        }
+        Some(existing) if existing.time.is_none() && spec.day.is_none() => {
+            existing.time = spec.time;
+            Ok(())
+        }
+        Some(_) => Err(ParseError::DuplicateStart),
+    }
+}
+
+/// Parses one or two words as a day and/or time, returning how many words
+/// were used.
+fn parse_when_prefix(words: &[&str]) -> Result<(StartSpec, usize), ParseError> {
+    if words.len() == 2 {
+        if let Ok(spec) = parse_when(words) {
+            return Ok((spec, 2));
+        }
+    }
+    match words.first() {
+        Some(word) => parse_when(&[word]).map(|spec| (spec, 1)),
+        None => Err(ParseError::MissingFlagValue { flag: "at" }),
+    }
+}
+
+fn parse_when(words: &[&str]) -> Result<StartSpec, ParseError> {
+    match words {
+        [word] => {
+            if let Some(time) = parse_time(word) {
+                Ok(StartSpec {
+                    day: None,
+                    time: Some(time),
+                })
+            } else if let Some(day) = parse_day(word) {
+                Ok(StartSpec {
+                    day: Some(day),
+                    time: None,
+                })
+            } else {
+                Err(ParseError::InvalidTime)
+            }
+        }
+        [first, second] => {
+            if let (Some(day), Some(time)) = (parse_day(first), parse_time(second)) {
+                Ok(StartSpec {
+                    day: Some(day),
+                    time: Some(time),
+                })
+            } else if let (Some(time), Some(day)) = (parse_time(first), parse_day(second)) {
+                Ok(StartSpec {
+                    day: Some(day),
+                    time: Some(time),
+                })
+            } else {
+                Err(ParseError::InvalidTime)
+            }
+        }
+        _ => Err(ParseError::InvalidTime),
+    }
+}
+
+/// Splits text into whitespace-separated tokens, keeping double-quoted
+/// (straight or curly) sections together.
+fn tokenize(text: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '"' || c == '“' {
+            chars.next();
+            let closing = if c == '"' { '"' } else { '”' };
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some(ch) if ch == closing => break,
+                    Some(ch) => value.push(ch),
+                    None => return Err(ParseError::UnterminatedQuote),
+                }
+            }
+            tokens.push(Token {
+                text: value,
+                quoted: true,
+            });
+            continue;
+        }
+
+        let mut value = String::new();
+        while let Some(&ch) = chars.peek() {
+            if ch.is_whitespace() {
+                break;
+            }
+            value.push(ch);
+            chars.next();
+        }
+        tokens.push(Token {
+            text: value,
+            quoted: false,
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn parse_attendee(word: &str) -> Option<Attendee> {
+    if let Some(inner) = word.strip_prefix('<').and_then(|w| w.strip_suffix('>')) {
+        let target = inner.split('|').next().unwrap_or_default();
+        if let Some(user_id) = target.strip_prefix('@') {
+            if !user_id.is_empty() {
+                return Some(Attendee::SlackUser(user_id.to_string()));
+            }
+        }
+        if let Some(email) = target.strip_prefix("mailto:") {
+            if !email.is_empty() {
+                return Some(Attendee::Email(email.to_string()));
+            }
+        }
+        return None;
+    }
+
+    if let Some(handle) = word.strip_prefix('@') {
+        if !handle.is_empty() && !handle.contains('@') {
+            return Some(Attendee::Handle(handle.to_string()));
+        }
+        return None;
+    }
+
+    let (local, domain) = word.split_once('@')?;
+    if !local.is_empty() && domain.contains('.') && !domain.starts_with('.') {
+        return Some(Attendee::Email(word.to_string()));
+    }
+
+    None
+}
+
+/// Parses `30m`, `45min`, `1h`, `2hrs`, `1h30m` (case-insensitive).
+pub fn parse_duration(word: &str) -> Option<Duration> {
+    let word = word.to_lowercase();
+    let mut rest = word.as_str();
+    let mut total_minutes: i64 = 0;
+    let mut seen_hours = false;
+    let mut seen_minutes = false;
+
+    while !rest.is_empty() {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || digits > 4 {
+            return None;
+        }
+        let number: i64 = rest[..digits].parse().ok()?;
+        rest = &rest[digits..];
+
+        let unit_len = rest.chars().take_while(|c| c.is_ascii_alphabetic()).count();
+        let unit = &rest[..unit_len];
+        rest = &rest[unit_len..];
+
+        match unit {
+            "h" | "hr" | "hrs" | "hour" | "hours" if !seen_hours && !seen_minutes => {
+                seen_hours = true;
+                total_minutes += number * 60;
+            }
+            "m" | "min" | "mins" | "minute" | "minutes" if !seen_minutes => {
+                seen_minutes = true;
+                total_minutes += number;
+            }
+            _ => return None,
+        }
+    }
+
+    (total_minutes > 0).then(|| Duration::minutes(total_minutes))
+}
+
+/// Parses `14:00`, `9:30`, `2pm`, `2:30pm` and `12am`.
+pub fn parse_time(word: &str) -> Option<NaiveTime> {
+    let word = word.to_lowercase();
+
+    let (clock, meridiem) = if let Some(clock) = word.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = word.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (word.as_str(), None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour, minute),
+        Some(_) => return None,
+        None if meridiem.is_some() => (clock, "00"),
+        None => return None,
+    };
+
+    if hour.is_empty() || hour.len() > 2 || !hour.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    if !minute.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut hour: u32 = hour.parse().ok()?;
+    let minute: u32 = minute.parse().ok()?;
+
+    if let Some(pm) = meridiem {
+        if hour == 0 || hour > 12 {
+            return None;
+        }
+        hour = match (hour, pm) {
+            (12, false) => 0,
+            (12, true) => 12,
+            (h, true) => h + 12,
+            (h, false) => h,
+        };
+    }
+
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+/// Parses `today`, `tomorrow`, weekday names (full or three-letter) and ISO
+/// dates.
+pub fn parse_day(word: &str) -> Option<DaySpec> {
+    let lower = word.to_lowercase();
+    let day = match lower.as_str() {
+        "today" => DaySpec::Today,
+        "tomorrow" | "tmrw" => DaySpec::Tomorrow,
+        "mon" | "monday" => DaySpec::Weekday(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => DaySpec::Weekday(Weekday::Tue),
+        "wed" | "wednesday" => DaySpec::Weekday(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => DaySpec::Weekday(Weekday::Thu),
+        "fri" | "friday" => DaySpec::Weekday(Weekday::Fri),
+        "sat" | "saturday" => DaySpec::Weekday(Weekday::Sat),
+        "sun" | "sunday" => DaySpec::Weekday(Weekday::Sun),
+        _ => DaySpec::Date(NaiveDate::parse_from_str(&lower, "%Y-%m-%d").ok()?),
+    };
+    Some(day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type CreateParts = (
+        Option<String>,
+        Option<Duration>,
+        Option<StartSpec>,
+        Vec<Attendee>,
+        BTreeSet<String>,
+    );
+
+    fn create(text: &str) -> CreateParts {
+        match parse(text).unwrap() {
+            MeetCommand::Create {
+                title,
+                duration,
+                start,
+                attendees,
+                flags,
+                ..
+            } => (title, duration, start, attendees, flags),
+            other => panic!("expected Create for {:?}, got {:?}", text, other),
+        }
+    }
+
+    fn title(text: &str) -> Option<String> {
+        create(text).0
+    }
+
+    fn time(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn test_empty_text_creates_untitled_meeting() {
+        assert_eq!(parse("").unwrap(), empty_create());
+        assert_eq!(parse("   ").unwrap(), empty_create());
+    }
+
+    #[test]
+    fn test_plain_title() {
+        assert_eq!(title("Weekly standup"), Some("Weekly standup".to_string()));
+    }
+
+    #[test]
+    fn test_title_whitespace_is_collapsed() {
+        assert_eq!(
+            title("  Weekly    standup \t"),
+            Some("Weekly standup".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unicode_title() {
+        assert_eq!(
+            title("Spotkanie zespołu 🎉"),
+            Some("Spotkanie zespołu 🎉".to_string())
+        );
+    }
+
+    #[test]
+    fn test_quoted_title_is_verbatim() {
+        assert_eq!(
+            title("\"list  of things\""),
+            Some("list  of things".to_string())
+        );
+    }
+
+    #[test]
+    fn test_curly_quotes() {
+        assert_eq!(
+            title("“help desk” sync"),
+            Some("help desk sync".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unterminated_quote() {
+        assert_eq!(parse("\"oops"), Err(ParseError::UnterminatedQuote));
+    }
+
+    #[test]
+    fn test_quoted_keyword_is_title_not_subcommand() {
+        assert_eq!(title("\"help\""), Some("help".to_string()));
+        assert_eq!(
+            title("\"status\" review"),
+            Some("status review".to_string())
+        );
+    }
+
+    #[test]
+    fn test_subcommands() {
+        assert_eq!(parse("help").unwrap(), MeetCommand::Help);
+        assert_eq!(parse("HELP").unwrap(), MeetCommand::Help);
+        assert_eq!(parse("--help").unwrap(), MeetCommand::Help);
+        assert_eq!(parse("status").unwrap(), MeetCommand::Status);
+        assert_eq!(parse("stats").unwrap(), MeetCommand::Stats);
+        assert_eq!(parse("logout").unwrap(), MeetCommand::Logout);
+        assert_eq!(parse("disconnect").unwrap(), MeetCommand::Logout);
+        assert_eq!(parse("export-my-data").unwrap(), MeetCommand::ExportData);
+    }
+
+    #[test]
+    fn test_subcommand_with_extra_arguments() {
+        assert_eq!(
+            parse("status please"),
+            Err(ParseError::UnexpectedArgument {
+                subcommand: "status"
+            })
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(parse("list").unwrap(), MeetCommand::List { limit: None });
+        assert_eq!(
+            parse("list 5").unwrap(),
+            MeetCommand::List { limit: Some(5) }
+        );
+        assert!(parse("list 0").is_err());
+        assert!(parse("list lots").is_err());
+        assert!(parse("list 1 2").is_err());
+    }
+
+    #[test]
+    fn test_cancel() {
+        assert_eq!(
+            parse("cancel").unwrap(),
+            MeetCommand::Cancel { meeting_id: None }
+        );
+        assert_eq!(
+            parse("cancel last").unwrap(),
+            MeetCommand::Cancel { meeting_id: None }
+        );
+        assert_eq!(
+            parse("cancel #42").unwrap(),
+            MeetCommand::Cancel {
+                meeting_id: Some(42)
+            }
+        );
+        assert!(parse("cancel everything").is_err());
+    }
+
+    #[test]
+    fn test_end() {
+        assert_eq!(parse("end").unwrap(), MeetCommand::End { meeting_id: None });
+        assert_eq!(
+            parse("End #7").unwrap(),
+            MeetCommand::End {
+                meeting_id: Some(7)
+            }
+        );
+        assert_eq!(
+            parse("end now").unwrap_err(),
+            ParseError::UnexpectedArgument { subcommand: "end" }
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(parse("join").unwrap(), MeetCommand::Join { share: false });
+        assert_eq!(
+            parse("join --share").unwrap(),
+            MeetCommand::Join { share: true }
+        );
+        assert_eq!(
+            parse("join now"),
+            Err(ParseError::UnexpectedArgument { subcommand: "join" })
+        );
+    }
+
+    #[test]
+    fn test_attendance() {
+        assert_eq!(
+            parse("attendance").unwrap(),
+            MeetCommand::Attendance { meeting: None }
+        );
+        assert_eq!(
+            parse("attendance abc-defg-hij").unwrap(),
+            MeetCommand::Attendance {
+                meeting: Some("abc-defg-hij".to_string())
+            }
+        );
+        assert_eq!(
+            parse("attendance <https://meet.google.com/abc-defg-hij|Standup>").unwrap(),
+            MeetCommand::Attendance {
+                meeting: Some("https://meet.google.com/abc-defg-hij".to_string())
+            }
+        );
+        assert_eq!(
+            parse("attendance of standup"),
+            Err(ParseError::UnexpectedArgument {
+                subcommand: "attendance"
+            })
+        );
+    }
+
+    #[test]
+    fn test_notes() {
+        assert_eq!(
+            parse("notes").unwrap(),
+            MeetCommand::Notes {
+                meeting: None,
+                share: false
+            }
+        );
+        assert_eq!(
+            parse("notes --share <https://meet.google.com/abc-defg-hij>").unwrap(),
+            MeetCommand::Notes {
+                meeting: Some("https://meet.google.com/abc-defg-hij".to_string()),
+                share: true
+            }
+        );
+        assert_eq!(
+            parse("notes abc-defg-hij --SHARE").unwrap(),
+            MeetCommand::Notes {
+                meeting: Some("abc-defg-hij".to_string()),
+                share: true
+            }
+        );
+        assert_eq!(
+            parse("notes abc-defg-hij --loud"),
+            Err(ParseError::UnexpectedArgument {
+                subcommand: "notes"
+            })
+        );
+        assert_eq!(
+            parse("notes of standup"),
+            Err(ParseError::UnexpectedArgument {
+                subcommand: "notes"
+            })
+        );
+    }
+
+    #[test]
+    fn test_share_link() {
+        assert_eq!(
+            parse("share-link").unwrap(),
+            MeetCommand::ShareLink {
+                meeting: None,
+                off: false
+            }
+        );
+        assert_eq!(
+            parse("share-link <https://meet.google.com/abc-defg-hij> --off").unwrap(),
+            MeetCommand::ShareLink {
+                meeting: Some("https://meet.google.com/abc-defg-hij".to_string()),
+                off: true
+            }
+        );
+        for text in [
+            "share-link --public",
+            "share-link abc-defg-hij xyz-abcd-efg",
+        ] {
+            assert_eq!(
+                parse(text),
+                Err(ParseError::UnexpectedArgument {
+                    subcommand: "share-link"
+                }),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_poll() {
+        let at = |day, hour| StartSpec {
+            day: Some(day),
+            time: NaiveTime::from_hms_opt(hour, 0, 0),
+        };
+        assert_eq!(
+            parse("poll \"retro\" tomorrow 14:00, tomorrow 16:00, friday 10:00").unwrap(),
+            MeetCommand::Poll {
+                title: Some("retro".to_string()),
+                options: vec![
+                    at(DaySpec::Tomorrow, 14),
+                    at(DaySpec::Tomorrow, 16),
+                    at(DaySpec::Weekday(Weekday::Fri), 10),
+                ],
+            }
+        );
+        assert_eq!(
+            parse("poll Sprint review fri 10:00,2pm fri,").unwrap(),
+            MeetCommand::Poll {
+                title: Some("Sprint review".to_string()),
+                options: vec![
+                    at(DaySpec::Weekday(Weekday::Fri), 10),
+                    at(DaySpec::Weekday(Weekday::Fri), 14),
+                ],
+            }
+        );
+        assert_eq!(
+            parse("poll 14:00, 15:00").unwrap(),
+            MeetCommand::Poll {
+                title: None,
+                options: vec![
+                    StartSpec {
+                        day: None,
+                        time: NaiveTime::from_hms_opt(14, 0, 0),
+                    },
+                    StartSpec {
+                        day: None,
+                        time: NaiveTime::from_hms_opt(15, 0, 0),
+                    },
+                ],
+            }
+        );
+
+        assert_eq!(
+            parse("poll \"retro\" tomorrow 14:00"),
+            Err(ParseError::InvalidPoll)
+        );
+        assert_eq!(parse("poll"), Err(ParseError::InvalidPoll));
+        let eleven = ["10:00"; 11].join(", ");
+        assert_eq!(
+            parse(&format!("poll {}", eleven)),
+            Err(ParseError::InvalidPoll)
+        );
+        assert_eq!(
+            parse("poll friday 10:00, after lunch"),
+            Err(ParseError::InvalidTime)
+        );
+    }
+
+    #[test]
+    fn test_set_user_setting() {
+        assert_eq!(
+            parse("set color 6").unwrap(),
+            MeetCommand::Set {
+                scope: SetScope::User,
+                key: "color".to_string(),
+                value: "6".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_team_setting_keeps_value_verbatim() {
+        assert_eq!(
+            parse("set team title-template \"[{channel}]  {text}\"").unwrap(),
+            MeetCommand::Set {
+                scope: SetScope::Team,
+                key: "title-template".to_string(),
+                value: "\"[{channel}]  {text}\"".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_set_without_key() {
+        assert_eq!(parse("set"), Err(ParseError::MissingSetKey));
+        assert_eq!(parse("set team"), Err(ParseError::MissingSetKey));
+    }
+
+    #[test]
+    fn test_set_without_value() {
+        assert_eq!(
+            parse("set calendar").unwrap(),
+            MeetCommand::Set {
+                scope: SetScope::User,
+                key: "calendar".to_string(),
+                value: String::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_admin_flags() {
+        assert_eq!(
+            parse("admin flags").unwrap(),
+            MeetCommand::Admin(AdminCommand::Flags)
+        );
+        assert_eq!(
+            parse("admin FLAGS Cancel On").unwrap(),
+            MeetCommand::Admin(AdminCommand::SetFlag {
+                flag: "cancel".to_string(),
+                enabled: Some(true),
+            })
+        );
+        assert_eq!(
+            parse("admin flags cancel default").unwrap(),
+            MeetCommand::Admin(AdminCommand::SetFlag {
+                flag: "cancel".to_string(),
+                enabled: None,
+            })
+        );
+        for text in ["admin", "admin flags cancel", "admin flags cancel maybe"] {
+            assert_eq!(
+                parse(text),
+                Err(ParseError::InvalidAdminCommand),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_admin_digest() {
+        assert_eq!(
+            parse("admin digest").unwrap(),
+            MeetCommand::Admin(AdminCommand::Digest)
+        );
+        assert_eq!(
+            parse("admin digest OFF").unwrap(),
+            MeetCommand::Admin(AdminCommand::DigestOff)
+        );
+        assert_eq!(
+            parse("admin digest here").unwrap(),
+            MeetCommand::Admin(AdminCommand::SetDigest {
+                channel_id: None,
+                weekday: Weekday::Mon,
+                hour: 9,
+            })
+        );
+        assert_eq!(
+            parse("admin digest <#C012AB3CD|team-leads> Friday 16").unwrap(),
+            MeetCommand::Admin(AdminCommand::SetDigest {
+                channel_id: Some("C012AB3CD".to_string()),
+                weekday: Weekday::Fri,
+                hour: 16,
+            })
+        );
+        assert_eq!(
+            parse("admin digest <#C012AB3CD> tue").unwrap(),
+            MeetCommand::Admin(AdminCommand::SetDigest {
+                channel_id: Some("C012AB3CD".to_string()),
+                weekday: Weekday::Tue,
+                hour: 9,
+            })
+        );
+        for text in [
+            // Unescaped channel names can't be told apart from ids
+            "admin digest #team-leads",
+            "admin digest here someday",
+            "admin digest here monday 24",
+            "admin digest here monday 9 extra",
+        ] {
+            assert_eq!(
+                parse(text),
+                Err(ParseError::InvalidAdminCommand),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_admin_api_keys() {
+        assert_eq!(
+            parse("admin api-keys").unwrap(),
+            MeetCommand::Admin(AdminCommand::ApiKeys)
+        );
+        assert_eq!(
+            parse("admin api-key create").unwrap(),
+            MeetCommand::Admin(AdminCommand::CreateApiKey)
+        );
+        assert_eq!(
+            parse("admin api-key revoke #3").unwrap(),
+            MeetCommand::Admin(AdminCommand::RevokeApiKey { id: 3 })
+        );
+        for text in [
+            "admin api-key revoke",
+            "admin api-key revoke msb_abcdefgh",
+            "admin api-key create ci",
+        ] {
+            assert_eq!(
+                parse(text),
+                Err(ParseError::InvalidAdminCommand),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_boolean_flags() {
+        let (title, _, _, _, flags) = create("--quiet 1:1 with Bob --QR");
+        assert_eq!(title, Some("1:1 with Bob".to_string()));
+        assert!(flags.contains("quiet"));
+        assert!(flags.contains("qr"));
+    }
+
+    #[test]
+    fn test_new_makes_a_one_off_meeting() {
+        let (title, duration, _, _, flags) = create("new Design review 30m");
+        assert_eq!(title, Some("Design review".to_string()));
+        assert_eq!(duration, Some(Duration::minutes(30)));
+        assert!(flags.contains("new"));
+
+        let (title, _, _, _, flags) = create("NEW");
+        assert_eq!(title, None);
+        assert_eq!(flags.into_iter().collect::<Vec<_>>(), ["new"]);
+        // Quoted, it is a title like any other
+        let (title, _, _, _, flags) = create("\"new\" hires");
+        assert_eq!(title, Some("new hires".to_string()));
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn test_only_flags() {
+        let (title, duration, start, attendees, flags) = create("--quiet");
+        assert_eq!(title, None);
+        assert_eq!(duration, None);
+        assert_eq!(start, None);
+        assert!(attendees.is_empty());
+        assert_eq!(flags.len(), 1);
+    }
+
+    #[test]
+    fn test_malformed_flags() {
+        assert_eq!(parse("--"), Err(ParseError::MalformedFlag));
+        assert_eq!(parse("--bad!flag"), Err(ParseError::MalformedFlag));
+        assert_eq!(parse("--quiet=yes"), Err(ParseError::MalformedFlag));
+    }
+
+    #[test]
+    fn test_duration_tokens() {
+        assert_eq!(create("retro 30m").1, Some(Duration::minutes(30)));
+        assert_eq!(create("retro 1h").1, Some(Duration::minutes(60)));
+        assert_eq!(create("retro 1h30m").1, Some(Duration::minutes(90)));
+        assert_eq!(create("retro 45MIN").1, Some(Duration::minutes(45)));
+        assert_eq!(create("retro for 2hours").1, Some(Duration::minutes(120)));
+    }
+
+    #[test]
+    fn test_duration_flag() {
+        assert_eq!(create("--for 15m sync").1, Some(Duration::minutes(15)));
+        assert_eq!(create("--duration=1h sync").1, Some(Duration::minutes(60)));
+        assert_eq!(parse("--duration soon"), Err(ParseError::InvalidDuration));
+        assert_eq!(
+            parse("--duration"),
+            Err(ParseError::MissingFlagValue { flag: "duration" })
+        );
+    }
+
+    #[test]
+    fn test_duplicate_duration() {
+        assert_eq!(parse("sync 30m 1h"), Err(ParseError::DuplicateDuration));
+    }
+
+    #[test]
+    fn test_non_durations_stay_in_title() {
+        assert_eq!(title("Q3 planning"), Some("Q3 planning".to_string()));
+        assert_eq!(title("1on1 prep"), Some("1on1 prep".to_string()));
+        assert_eq!(title("m30 review"), Some("m30 review".to_string()));
+        assert_eq!(title("0m"), Some("0m".to_string()));
+    }
+
+    #[test]
+    fn test_time_tokens() {
+        let (title, _, start, _, _) = create("sync at 14:00");
+        assert_eq!(title, Some("sync".to_string()));
+        assert_eq!(
+            start,
+            Some(StartSpec {
+                day: None,
+                time: Some(time(14, 0))
+            })
+        );
+
+        assert_eq!(create("sync 2pm").2.unwrap().time, Some(time(14, 0)));
+        assert_eq!(create("sync 2:30pm").2.unwrap().time, Some(time(14, 30)));
+        assert_eq!(create("sync 12am").2.unwrap().time, Some(time(0, 0)));
+        assert_eq!(create("sync 12pm").2.unwrap().time, Some(time(12, 0)));
+    }
+
+    #[test]
+    fn test_day_and_time() {
+        let (title, _, start, _, _) = create("retro tomorrow 14:00");
+        assert_eq!(title, Some("retro".to_string()));
+        assert_eq!(
+            start,
+            Some(StartSpec {
+                day: Some(DaySpec::Tomorrow),
+                time: Some(time(14, 0))
+            })
+        );
+
+        let start = create("retro 10:00 friday").2.unwrap();
+        assert_eq!(start.day, Some(DaySpec::Weekday(Weekday::Fri)));
+
+        let start = create("retro on 2030-01-15 at 9:30").2.unwrap();
+        assert_eq!(
+            start.day,
+            Some(DaySpec::Date(NaiveDate::from_ymd_opt(2030, 1, 15).unwrap()))
+        );
+        assert_eq!(start.time, Some(time(9, 30)));
+    }
+
+    #[test]
+    fn test_day_word_alone_is_title() {
+        let (title, _, start, _, _) = create("friday drinks");
+        assert_eq!(title, Some("friday drinks".to_string()));
+        assert_eq!(start, None);
+    }
+
+    #[test]
+    fn test_at_flag() {
+        let start = create("--at tomorrow 9am standup").2.unwrap();
+        assert_eq!(start.day, Some(DaySpec::Tomorrow));
+        assert_eq!(start.time, Some(time(9, 0)));
+
+        let (title, _, start, _, _) = create("--at=16:15 standup");
+        assert_eq!(title, Some("standup".to_string()));
+        assert_eq!(start.unwrap().time, Some(time(16, 15)));
+
+        assert_eq!(parse("--at never"), Err(ParseError::InvalidTime));
+    }
+
+    #[test]
+    fn test_duplicate_start() {
+        assert_eq!(parse("sync 10:00 11:00"), Err(ParseError::DuplicateStart));
+    }
+
+    #[test]
+    fn test_invalid_clock_values_stay_in_title() {
+        assert_eq!(title("25:00 club"), Some("25:00 club".to_string()));
+        assert_eq!(title("13pm"), Some("13pm".to_string()));
+        assert_eq!(title("ratio 3:1"), Some("ratio 3:1".to_string()));
+    }
+
+    #[test]
+    fn test_mentions_become_attendees() {
+        let (title, _, _, attendees, _) = create("standup <@U123ABC|alice> <@W456DEF> @bob");
+        assert_eq!(title, Some("standup".to_string()));
+        assert_eq!(
+            attendees,
+            vec![
+                Attendee::SlackUser("U123ABC".to_string()),
+                Attendee::SlackUser("W456DEF".to_string()),
+                Attendee::Handle("bob".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_emails_become_attendees() {
+        let (_, _, _, attendees, _) =
+            create("sync <mailto:ann@example.com|ann@example.com> joe@example.org");
+        assert_eq!(
+            attendees,
+            vec![
+                Attendee::Email("ann@example.com".to_string()),
+                Attendee::Email("joe@example.org".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cohost_flag() {
+        let MeetCommand::Create {
+            title,
+            attendees,
+            cohosts,
+            ..
+        } = parse("standup <@U123ABC|alice> --cohost <@U123ABC|alice> --COHOST=ann@example.com")
+            .unwrap()
+        else {
+            panic!("expected Create");
+        };
+        assert_eq!(title, Some("standup".to_string()));
+        assert_eq!(attendees, vec![Attendee::SlackUser("U123ABC".to_string())]);
+        assert_eq!(
+            cohosts,
+            vec![
+                Attendee::SlackUser("U123ABC".to_string()),
+                Attendee::Email("ann@example.com".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            parse("standup --cohost"),
+            Err(ParseError::MissingFlagValue { flag: "cohost" })
+        );
+        assert_eq!(
+            parse("standup --cohost tomorrow"),
+            Err(ParseError::InvalidCohost)
+        );
+    }
+
+    #[test]
+    fn test_channel_mentions_stay_in_title() {
+        assert_eq!(
+            title("sync for <#C123|general>"),
+            Some("sync for <#C123|general>".to_string())
+        );
+    }
+
+    #[test]
+    fn test_title_flag() {
+        assert_eq!(
+            title("--title \"big sync\" 30m"),
+            Some("big sync".to_string())
+        );
+        assert_eq!(
+            parse("--title"),
+            Err(ParseError::MissingFlagValue { flag: "title" })
+        );
+    }
+
+    #[test]
+    fn test_full_command() {
+        let (title, duration, start, attendees, flags) =
+            create("\"Quarterly review\" tomorrow 3pm for 1h <@U1|a> --quiet");
+        assert_eq!(title, Some("Quarterly review".to_string()));
+        assert_eq!(duration, Some(Duration::minutes(60)));
+        assert_eq!(
+            start,
+            Some(StartSpec {
+                day: Some(DaySpec::Tomorrow),
+                time: Some(time(15, 0))
+            })
+        );
+        assert_eq!(attendees, vec![Attendee::SlackUser("U1".to_string())]);
+        assert!(flags.contains("quiet"));
+    }
+
+    #[test]
+    fn test_start_spec_resolution() {
+        use chrono::Utc;
+
+        // A Wednesday afternoon
+        let now = Utc.with_ymd_and_hms(2025, 6, 4, 15, 0, 0).unwrap();
+        let at = |day, time: Option<(u32, u32)>| {
+            StartSpec {
+                day,
+                time: time.map(|(h, m)| NaiveTime::from_hms_opt(h, m, 0).unwrap()),
+            }
+            .resolve(&now)
+            .unwrap()
+        };
+
+        assert_eq!(
+            at(None, Some((16, 30))),
+            Utc.with_ymd_and_hms(2025, 6, 4, 16, 30, 0).unwrap()
+        );
+        assert_eq!(
+            at(Some(DaySpec::Tomorrow), None),
+            Utc.with_ymd_and_hms(2025, 6, 5, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at(Some(DaySpec::Weekday(Weekday::Fri)), Some((10, 0))),
+            Utc.with_ymd_and_hms(2025, 6, 6, 10, 0, 0).unwrap()
+        );
+        // Same weekday: later today, or next week once the time has passed
+        assert_eq!(
+            at(Some(DaySpec::Weekday(Weekday::Wed)), Some((17, 0))),
+            Utc.with_ymd_and_hms(2025, 6, 4, 17, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at(Some(DaySpec::Weekday(Weekday::Wed)), Some((9, 0))),
+            Utc.with_ymd_and_hms(2025, 6, 11, 9, 0, 0).unwrap()
+        );
+        assert_eq!(
+            at(
+                Some(DaySpec::Date(
+                    NaiveDate::from_ymd_opt(2025, 12, 24).unwrap()
+                )),
+                Some((8, 15))
+            ),
+            Utc.with_ymd_and_hms(2025, 12, 24, 8, 15, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_garbage_input_does_not_panic() {
+        for input in [
+            "<<<>>>",
+            "<@>",
+            "<mailto:>",
+            "@",
+            "@@",
+            "a@b",
+            "::::",
+            "99999999999h",
+            "--at",
+            "—",
+            "\u{0}\u{1}",
+            "\"\"",
+            "<@U1|",
+        ] {
+            let _ = parse(input);
+        }
+        assert_eq!(title("@@"), Some("@@".to_string()));
+        assert_eq!(title("a@b"), Some("a@b".to_string()));
+    }
+
+    #[test]
+    fn test_mentions() {
+        assert_eq!(channel_mention("<#C012AB3CD|general>"), Some("C012AB3CD"));
+        assert_eq!(user_mention("<@U012AB3CD|alice>"), Some("U012AB3CD"));
+        assert_eq!(user_mention("<@W012AB3CD>"), Some("W012AB3CD"));
+        for text in ["@alice", "<@>", "<@U1|", "<#C012AB3CD>", "<@U1 2>"] {
+            assert_eq!(user_mention(text), None, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_error_messages_do_not_echo_input() {
+        let err = parse("--duration <script>").unwrap_err();
+        assert!(!err.to_string().contains("<script>"));
+    }
+}
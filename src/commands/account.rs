@@ -0,0 +1,198 @@
+//! `/meet status` and `/meet logout`: the user's Google connection.
+
+use axum::async_trait;
+use tracing::{info, warn};
+
+use super::{auth_url, CommandContext, CommandHandler};
+use crate::audit::{self, AuditEvent};
+use crate::auth::oauth::is_token_valid_at;
+use crate::database::models::User;
+use crate::error::AppError;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::shared_account;
+use crate::AppState;
+
+pub struct StatusHandler;
+
+#[async_trait]
+impl CommandHandler for StatusHandler {
+    fn name(&self) -> &'static str {
+        "status"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        handle_status(ctx.state, ctx.payload, user).await
+    }
+}
+
+pub struct LogoutHandler;
+
+#[async_trait]
+impl CommandHandler for LogoutHandler {
+    fn name(&self) -> &'static str {
+        "logout"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        handle_logout(ctx.state, user).await
+    }
+}
+
+/// Whether the user's Google account is connected. In a workspace with a
+/// working shared account, members don't need their own, so they are told
+/// about that instead.
+async fn handle_status(
+    state: AppState,
+    payload: SlashCommandPayload,
+    user: User,
+) -> Result<SlackResponse, AppError> {
+    let shared = shared_account::for_team(&state.db, &payload.team_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to look up the shared account of team {}: {:#}",
+                payload.team_id, e
+            );
+            None
+        });
+    let mut connected = "✅ Your Google account is connected.".to_string();
+    if let Some(account) = shared {
+        if account.slack_user_id == user.slack_user_id {
+            connected.push_str(" Everyone in this workspace creates meetings with it.");
+        } else if !account.failing {
+            return Ok(SlackResponse::ephemeral(format!(
+                "🔗 Meetings in this workspace are created with <@{}>'s shared Google account, so you don't need to connect your own.",
+                account.slack_user_id
+            )));
+        }
+    }
+
+    match state.db.get_oauth_token(user.id).await {
+        Ok(Some(token))
+            if is_token_valid_at(&token, state.clock.now()) || token.refresh_token.is_some() =>
+        {
+            Ok(SlackResponse::ephemeral(connected))
+        }
+        Ok(_) => Ok(SlackResponse::with_auth_prompt(auth_url(
+            &state,
+            &payload.user_id,
+        ))),
+        Err(e) => {
+            warn!("Failed to load token for status of user {}: {}", user.id, e);
+            Ok(SlackResponse::with_auth_prompt(auth_url(
+                &state,
+                &payload.user_id,
+            )))
+        }
+    }
+}
+
+async fn handle_logout(state: AppState, user: User) -> Result<SlackResponse, AppError> {
+    state.db.delete_oauth_token(user.id).await?;
+    info!("Disconnected Google account for user {}", user.id);
+    audit::record(&state.db, AuditEvent::google_disconnected(&user, "logout")).await;
+    Ok(SlackResponse::ephemeral(
+        "👋 Your Google account has been disconnected.".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{connected_user, payload, test_state, RESPONSE_URL};
+    use super::*;
+    use crate::database::models::OAuthToken;
+
+    #[tokio::test]
+    async fn test_status_reports_a_connected_account() {
+        let (state, _pool) = test_state().await;
+        let user = connected_user(&state).await;
+
+        let response = handle_status(state, payload("status", RESPONSE_URL), user)
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "✅ Your Google account is connected.");
+    }
+
+    #[tokio::test]
+    async fn test_status_prompts_without_a_token() {
+        let (state, _pool) = test_state().await;
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+
+        let response = handle_status(state, payload("status", RESPONSE_URL), user)
+            .await
+            .unwrap();
+
+        assert!(response.is_auth_prompt());
+    }
+
+    #[tokio::test]
+    async fn test_status_shows_shared_mode() {
+        let (state, _pool) = test_state().await;
+        let owner = connected_user(&state).await;
+        shared_account::enable(&state.db, "T012AB3C4", "U012AB3CD", "U0ADMIN01")
+            .await
+            .unwrap();
+        let member = state
+            .db
+            .create_user("U098ZY7XW", "T012AB3C4")
+            .await
+            .unwrap();
+        let mut member_payload = payload("status", RESPONSE_URL);
+        member_payload.user_id = "U098ZY7XW".to_string();
+
+        let response = handle_status(state.clone(), member_payload, member)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.text,
+            "🔗 Meetings in this workspace are created with <@U012AB3CD>'s shared Google account, so you don't need to connect your own."
+        );
+
+        let response = handle_status(state, payload("status", RESPONSE_URL), owner)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.text,
+            "✅ Your Google account is connected. Everyone in this workspace creates meetings with it."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_logout_is_audited() {
+        let (state, _pool) = test_state().await;
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+        state
+            .db
+            .store_oauth_token(&OAuthToken::new(
+                user.id,
+                "ya29.access-secret".into(),
+                Some("1//refresh-secret".into()),
+                None,
+                None,
+            ))
+            .await
+            .unwrap();
+
+        let response = handle_logout(state.clone(), user).await.unwrap();
+        assert!(response.text.contains("disconnected"));
+
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].event_type, "google_disconnected");
+        assert_eq!(audit[0].detail, serde_json::json!({ "reason": "logout" }));
+        assert_eq!(audit[0].slack_team_id.as_deref(), Some("T012AB3C4"));
+        let serialized = serde_json::to_string(&audit).unwrap();
+        assert!(!serialized.contains("secret"), "{}", serialized);
+    }
+}
@@ -0,0 +1,509 @@
+//! `/meet admin`: workspace administration for the Slack users listed in
+//! `ADMIN_SLACK_USERS`.
+
+use axum::async_trait;
+use chrono::Datelike;
+use tracing::info;
+
+use super::{CommandContext, CommandHandler};
+use crate::api_keys;
+use crate::audit::{self, AuditEvent};
+use crate::command_parser::{AdminCommand, MeetCommand};
+use crate::digest::{self, DigestSchedule};
+use crate::error::AppError;
+use crate::features::{Feature, FeatureFlags};
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::AppState;
+
+pub struct AdminHandler;
+
+#[async_trait]
+impl CommandHandler for AdminHandler {
+    fn name(&self) -> &'static str {
+        "admin"
+    }
+
+    fn needs_user(&self) -> bool {
+        false
+    }
+
+    async fn handle(&self, ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let MeetCommand::Admin(command) = ctx.command else {
+            return Err(AppError::Internal(anyhow::anyhow!(
+                "admin handler got /meet {}",
+                ctx.command.name()
+            )));
+        };
+
+        let admins = &ctx.state.config.admin.slack_users;
+        if !admins.contains(&ctx.payload.user_id) {
+            info!("Refused /meet admin to {}", ctx.payload.user_id);
+            return Ok(SlackResponse::ephemeral(
+                "🚫 Only the bot's admins can use this command.".to_string(),
+            ));
+        }
+
+        match command {
+            AdminCommand::Flags => list_flags(&ctx.state, &ctx.payload).await,
+            AdminCommand::SetFlag { flag, enabled } => {
+                set_flag(&ctx.state, &ctx.payload, &flag, enabled).await
+            }
+            AdminCommand::Digest => show_digest(&ctx.state, &ctx.payload).await,
+            AdminCommand::SetDigest {
+                channel_id,
+                weekday,
+                hour,
+            } => {
+                let schedule = DigestSchedule {
+                    channel_id: channel_id.unwrap_or_else(|| ctx.payload.channel_id.clone()),
+                    weekday,
+                    hour,
+                    configured_by: Some(ctx.payload.user_id.clone()),
+                };
+                set_digest(&ctx.state, &ctx.payload, schedule).await
+            }
+            AdminCommand::DigestOff => stop_digest(&ctx.state, &ctx.payload).await,
+            AdminCommand::ApiKeys => list_api_keys(&ctx.state, &ctx.payload).await,
+            AdminCommand::CreateApiKey => create_api_key(&ctx.state, &ctx.payload).await,
+            AdminCommand::RevokeApiKey { id } => revoke_api_key(&ctx.state, &ctx.payload, id).await,
+        }
+    }
+}
+
+async fn list_flags(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+) -> Result<SlackResponse, AppError> {
+    let states = state
+        .config
+        .features
+        .states(&state.db, &payload.team_id)
+        .await?;
+
+    let mut text = "*Feature flags in this workspace*".to_string();
+    for (feature, flag) in states {
+        let source = match flag.team_override {
+            Some(_) => "set for this workspace",
+            None => "global setting",
+        };
+        text.push_str(&format!(
+            "\n• `{}` — *{}* ({}) — {}",
+            feature,
+            if flag.enabled { "on" } else { "off" },
+            source,
+            feature.description(&payload.command)
+        ));
+    }
+    text.push_str(&format!(
+        "\nChange one with `{} admin flags <flag> on|off|default`.",
+        payload.command
+    ));
+    Ok(SlackResponse::ephemeral(text))
+}
+
+async fn set_flag(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    flag: &str,
+    enabled: Option<bool>,
+) -> Result<SlackResponse, AppError> {
+    let feature: Feature = match flag.parse() {
+        Ok(feature) => feature,
+        Err(_) => {
+            let known: Vec<String> = Feature::ALL.iter().map(|f| format!("`{}`", f)).collect();
+            return Ok(SlackResponse::ephemeral(format!(
+                "❓ There's no feature flag `{}`. Known flags: {}.",
+                flag,
+                known.join(", ")
+            )));
+        }
+    };
+
+    FeatureFlags::set_override(&state.db, feature, &payload.team_id, enabled).await?;
+    info!(
+        "{} set feature flag {} to {:?} for team {}",
+        payload.user_id, feature, enabled, payload.team_id
+    );
+    audit::record(
+        &state.db,
+        AuditEvent::feature_flag_changed(
+            &payload.user_id,
+            &payload.team_id,
+            feature.name(),
+            enabled,
+        ),
+    )
+    .await;
+
+    let text = match enabled {
+        Some(true) => format!("✅ `{}` is now on in this workspace.", feature),
+        Some(false) => format!("✅ `{}` is now off in this workspace.", feature),
+        None => {
+            let global = state.config.features.is_globally_enabled(feature);
+            format!(
+                "✅ `{}` now follows the global setting, which is {}.",
+                feature,
+                if global { "on" } else { "off" }
+            )
+        }
+    };
+    Ok(SlackResponse::ephemeral(text))
+}
+
+async fn show_digest(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+) -> Result<SlackResponse, AppError> {
+    let text = match digest::schedule(&state.db, &payload.team_id).await? {
+        Some(schedule) => format!(
+            "📅 The weekly digest goes to <#{}> {}. Stop it with `{} admin digest off`.",
+            schedule.channel_id,
+            schedule.describe(),
+            payload.command
+        ),
+        None => format!(
+            "There's no weekly digest in this workspace. Set one up with \
+             `{} admin digest here|#channel [weekday] [hour]`; the hour is in UTC.",
+            payload.command
+        ),
+    };
+    Ok(SlackResponse::ephemeral(text))
+}
+
+async fn set_digest(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    schedule: DigestSchedule,
+) -> Result<SlackResponse, AppError> {
+    // Digests are posted with the workspace's bot token
+    if state.db.get_slack_team(&payload.team_id).await?.is_none() {
+        return Ok(SlackResponse::ephemeral(
+            "❌ The bot isn't installed in this workspace with a bot token, so it can't post a digest here."
+                .to_string(),
+        ));
+    }
+
+    let now = state.clock.now();
+    digest::configure(&state.db, &payload.team_id, &schedule, now).await?;
+    info!(
+        "{} set the weekly digest of team {} to {} {}",
+        payload.user_id,
+        payload.team_id,
+        schedule.channel_id,
+        schedule.describe()
+    );
+    audit::record(
+        &state.db,
+        AuditEvent::digest_changed(&payload.user_id, &payload.team_id, Some(&schedule)),
+    )
+    .await;
+
+    let first = schedule.next_due(now);
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ The weekly digest will go to <#{}> {}, starting {} {}. \
+         Make sure the bot is in that channel.",
+        schedule.channel_id,
+        schedule.describe(),
+        digest::weekday_name(first.weekday()),
+        first.format("%-d %B")
+    )))
+}
+
+async fn stop_digest(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+) -> Result<SlackResponse, AppError> {
+    digest::disable(&state.db, &payload.team_id).await?;
+    info!(
+        "{} stopped the weekly digest of team {}",
+        payload.user_id, payload.team_id
+    );
+    audit::record(
+        &state.db,
+        AuditEvent::digest_changed(&payload.user_id, &payload.team_id, None),
+    )
+    .await;
+
+    Ok(SlackResponse::ephemeral(
+        "✅ The weekly digest is off.".to_string(),
+    ))
+}
+
+async fn list_api_keys(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+) -> Result<SlackResponse, AppError> {
+    let keys = state.db.team_api_keys(&payload.team_id).await?;
+    if keys.is_empty() {
+        return Ok(SlackResponse::ephemeral(format!(
+            "There are no API keys in this workspace. Make one with `{} admin api-key create`.",
+            payload.command
+        )));
+    }
+
+    let mut text = "*API keys of this workspace*".to_string();
+    for key in keys {
+        text.push_str(&format!(
+            "\n• #{} `{}…` by <@{}>, made {} — {}",
+            key.id,
+            key.prefix,
+            key.created_by,
+            key.created_at.format("%Y-%m-%d"),
+            match key.last_used_at {
+                Some(used) => format!("last used {}", used.format("%Y-%m-%d %H:%M UTC")),
+                None => "never used".to_string(),
+            }
+        ));
+    }
+    text.push_str(&format!(
+        "\nRevoke one with `{} admin api-key revoke <number>`.",
+        payload.command
+    ));
+    Ok(SlackResponse::ephemeral(text))
+}
+
+async fn create_api_key(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+) -> Result<SlackResponse, AppError> {
+    let (stored, key) = api_keys::create(
+        &state.db,
+        &payload.team_id,
+        &payload.user_id,
+        state.clock.now(),
+    )
+    .await?;
+    info!(
+        "{} made API key {} for team {}",
+        payload.user_id, stored.id, payload.team_id
+    );
+    audit::record(
+        &state.db,
+        AuditEvent::api_key_changed(&payload.user_id, &payload.team_id, stored.id, false),
+    )
+    .await;
+
+    Ok(SlackResponse::ephemeral(format!(
+        "🔑 API key #{} is ready. Copy it now; it won't be shown again:\n`{}`\n\
+         Send it as `Authorization: Bearer <key>` with `POST {}/api/v1/meetings`. \
+         Meetings are created with your Google account unless the request names a `slack_user_id`. \
+         Revoke it with `{} admin api-key revoke {}`.",
+        stored.id,
+        key.expose(),
+        state.config.google.public_base_url(),
+        payload.command,
+        stored.id
+    )))
+}
+
+async fn revoke_api_key(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    id: i64,
+) -> Result<SlackResponse, AppError> {
+    let revoked = state
+        .db
+        .revoke_api_key(&payload.team_id, id, state.clock.now().naive_utc())
+        .await?;
+    if !revoked {
+        return Ok(SlackResponse::ephemeral(format!(
+            "❓ This workspace has no API key #{}. `{} admin api-keys` lists them.",
+            id, payload.command
+        )));
+    }
+    info!(
+        "{} revoked API key {} of team {}",
+        payload.user_id, id, payload.team_id
+    );
+    audit::record(
+        &state.db,
+        AuditEvent::api_key_changed(&payload.user_id, &payload.team_id, id, true),
+    )
+    .await;
+
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ API key #{} is revoked; requests with it are refused from now on.",
+        id
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, test_state_with, RESPONSE_URL};
+    use super::super::CommandRegistry;
+    use crate::api_keys;
+    use crate::command_parser;
+    use crate::config::Config;
+    use crate::database::models::SlackTeam;
+    use crate::digest;
+    use crate::google::fake::FakeGoogleApi;
+    use std::sync::Arc;
+
+    async fn admin(config: Config, text: &str) -> (String, crate::AppState) {
+        let (state, _pool) = test_state_with(Arc::new(FakeGoogleApi::succeeding()), config).await;
+        let response = run(&state, text).await;
+        (response, state)
+    }
+
+    async fn run(state: &crate::AppState, text: &str) -> String {
+        let command = command_parser::parse(text).unwrap();
+        CommandRegistry::new()
+            .dispatch(state.clone(), payload(text, RESPONSE_URL), command, false)
+            .await
+            .unwrap()
+            .text
+    }
+
+    fn admin_config() -> Config {
+        let mut config = Config::for_tests();
+        config.admin.slack_users = vec!["U012AB3CD".to_string()];
+        config
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_can_use_admin_commands() {
+        let (text, _state) = admin(Config::for_tests(), "admin flags").await;
+        assert!(text.starts_with('🚫'), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_flags_are_listed_and_overridden() {
+        let (text, state) = admin(admin_config(), "admin flags").await;
+        assert!(
+            text.contains("• `cancel` — *off* (global setting)"),
+            "{}",
+            text
+        );
+        assert!(text.contains("• `settings` — *off*"), "{}", text);
+
+        let text = run(&state, "admin flags cancel on").await;
+        assert_eq!(text, "✅ `cancel` is now on in this workspace.");
+        let text = run(&state, "admin flags").await;
+        assert!(
+            text.contains("• `cancel` — *on* (set for this workspace)"),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "admin flags cancel default").await;
+        assert_eq!(
+            text,
+            "✅ `cancel` now follows the global setting, which is off."
+        );
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].event_type, "feature_flag_changed");
+    }
+
+    #[tokio::test]
+    async fn test_digest_is_set_up_and_stopped() {
+        let (text, state) = admin(admin_config(), "admin digest").await;
+        assert!(
+            text.starts_with("There's no weekly digest in this workspace."),
+            "{}",
+            text
+        );
+
+        // Not without a bot token to post with
+        let text = run(&state, "admin digest here").await;
+        assert!(text.starts_with('❌'), "{}", text);
+
+        state
+            .db
+            .store_slack_team(&SlackTeam::new("T012AB3C4".to_string(), "xoxb-bot".into()))
+            .await
+            .unwrap();
+        let text = run(&state, "admin digest <#C098ZY7XW|leads> friday 16").await;
+        assert!(
+            text.starts_with("✅ The weekly digest will go to <#C098ZY7XW> every Friday at 16:00 UTC, starting Friday "),
+            "{}",
+            text
+        );
+        let text = run(&state, "admin digest").await;
+        assert_eq!(
+            text,
+            "📅 The weekly digest goes to <#C098ZY7XW> every Friday at 16:00 UTC. Stop it with `/meet admin digest off`."
+        );
+
+        // `here` is the channel the command was run in
+        run(&state, "admin digest here").await;
+        let schedule = digest::schedule(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(schedule.channel_id, "C012AB3CD");
+        assert_eq!(schedule.configured_by.as_deref(), Some("U012AB3CD"));
+
+        let text = run(&state, "admin digest off").await;
+        assert_eq!(text, "✅ The weekly digest is off.");
+        assert!(digest::schedule(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 3);
+        assert_eq!(audit[0].event_type, "digest_changed");
+        assert!(audit[0].detail["channel_id"].is_null());
+        assert_eq!(audit[1].detail["channel_id"], "C012AB3CD");
+    }
+
+    #[tokio::test]
+    async fn test_api_keys_are_made_listed_and_revoked() {
+        let (text, state) = admin(admin_config(), "admin api-keys").await;
+        assert!(
+            text.starts_with("There are no API keys in this workspace."),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "admin api-key create").await;
+        assert!(
+            text.starts_with(
+                "🔑 API key #1 is ready. Copy it now; it won't be shown again:\n`msb_"
+            ),
+            "{}",
+            text
+        );
+        let key = text.split('`').nth(1).unwrap().to_string();
+        let now = state.clock.now();
+        assert!(api_keys::authenticate(&state.db, &key, now)
+            .await
+            .unwrap()
+            .is_some());
+
+        let text = run(&state, "admin api-keys").await;
+        assert!(
+            text.contains(&format!("• #1 `{}…` by <@U012AB3CD>", &key[..12])),
+            "{}",
+            text
+        );
+        assert!(!text.contains(&key), "{}", text);
+
+        let text = run(&state, "admin api-key revoke 2").await;
+        assert!(
+            text.starts_with("❓ This workspace has no API key #2."),
+            "{}",
+            text
+        );
+        let text = run(&state, "admin api-key revoke 1").await;
+        assert!(text.starts_with("✅ API key #1 is revoked"), "{}", text);
+        assert!(api_keys::authenticate(&state.db, &key, now)
+            .await
+            .unwrap()
+            .is_none());
+
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].event_type, "api_key_changed");
+        assert_eq!(audit[0].detail["revoked"], true);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_flags_are_named() {
+        let (text, _state) = admin(admin_config(), "admin flags modal on").await;
+        assert_eq!(
+            text,
+            "❓ There's no feature flag `modal`. Known flags: `cancel`, `settings`."
+        );
+    }
+}
@@ -0,0 +1,496 @@
+//! `/meet attendance [link]`: who joined the user's latest meeting, or the
+//! one at a Meet link or code, from Google's conference records. Also the
+//! lookup of past meetings `/meet notes` shares.
+
+use axum::async_trait;
+use chrono::Duration;
+use tracing::{info, warn};
+
+use super::create::{ready_token, TokenCheck};
+use super::{auth_url, CommandContext, CommandHandler};
+use crate::auth::oauth::{has_scope, MEET_READONLY_SCOPE};
+use crate::command_parser::MeetCommand;
+use crate::database::models::{Meeting, OAuthToken, User};
+use crate::error::AppError;
+use crate::google::{GoogleApiError, Participant};
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::observability::{self, Phase};
+use crate::slack::blocks;
+use crate::AppState;
+
+pub struct AttendanceHandler;
+
+#[async_trait]
+impl CommandHandler for AttendanceHandler {
+    fn name(&self) -> &'static str {
+        "attendance"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let meeting = match ctx.command {
+            MeetCommand::Attendance { meeting } => meeting,
+            _ => None,
+        };
+        handle_attendance(ctx.state, ctx.payload, user, meeting).await
+    }
+}
+
+/// What a past meeting is looked up for, to word the replies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Lookup {
+    Attendance,
+    Notes,
+}
+
+impl Lookup {
+    pub(super) fn emoji(self) -> &'static str {
+        match self {
+            Lookup::Attendance => "📊",
+            Lookup::Notes => "📝",
+        }
+    }
+
+    /// What is looked up, as in "its attendance".
+    fn subject(self) -> &'static str {
+        match self {
+            Lookup::Attendance => "attendance",
+            Lookup::Notes => "recordings and transcripts",
+        }
+    }
+}
+
+/// A meeting of the user's that Google can be asked about, with the token
+/// to ask with.
+pub(super) struct PastMeeting {
+    pub meeting: Meeting,
+    pub space_name: String,
+    pub token: OAuthToken,
+}
+
+/// Looks the meeting up, then asks Google who joined its latest call. Only
+/// a call that has ended is summarized, as participants still in it have
+/// no time to report yet.
+async fn handle_attendance(
+    state: AppState,
+    payload: SlashCommandPayload,
+    user: User,
+    target: Option<String>,
+) -> Result<SlackResponse, AppError> {
+    let lookup = Lookup::Attendance;
+    let PastMeeting {
+        meeting,
+        space_name,
+        token,
+    } = match past_meeting(&state, &payload, &user, target.as_deref(), lookup).await? {
+        Ok(past) => past,
+        Err(reply) => return Ok(reply),
+    };
+
+    let label = meeting_label(&meeting);
+    let records = match observability::timed(
+        Phase::Google,
+        state
+            .google
+            .list_conference_records(&token.access_token, &space_name),
+    )
+    .await
+    {
+        Ok(records) => records,
+        Err(e) => return google_failure(&state, &payload, e, lookup),
+    };
+    let Some(latest) = records.first() else {
+        return Ok(SlackResponse::ephemeral(format!(
+            "📊 Nobody has joined {} yet.",
+            label
+        )));
+    };
+    let Some(ended_at) = latest.end_time else {
+        return Ok(SlackResponse::ephemeral(format!(
+            "📊 {} is still going on. Its attendance is ready once everyone has left.",
+            label
+        )));
+    };
+
+    let participants = match observability::timed(
+        Phase::Google,
+        state
+            .google
+            .list_participants(&token.access_token, &latest.name),
+    )
+    .await
+    {
+        Ok(participants) => participants,
+        Err(e) => return google_failure(&state, &payload, e, lookup),
+    };
+    info!(
+        "Showing attendance of {} ({} participants) to user {}",
+        latest.name,
+        participants.len(),
+        user.id
+    );
+
+    let mut text = format!(
+        "📊 *{}*: {} joined the call on {}–{} UTC",
+        label,
+        match participants.len() {
+            1 => "1 person".to_string(),
+            n => format!("{} people", n),
+        },
+        latest.start_time.format("%Y-%m-%d %H:%M"),
+        ended_at.format("%H:%M")
+    );
+    for (participant, time_in_call) in by_time_in_call(participants, ended_at) {
+        text.push_str(&format!(
+            "\n• {} — {}",
+            participant.display_name,
+            format_minutes(time_in_call)
+        ));
+    }
+    if records.len() > 1 {
+        text.push_str(&format!(
+            "\n_The link was used for {} calls; this is the latest._",
+            records.len()
+        ));
+    }
+    Ok(SlackResponse::ephemeral(text))
+}
+
+/// The user's meeting at `target`, or their latest one, once it is known
+/// that Google can be asked about it with a token of theirs. `Err` holds
+/// the reply when it can't.
+pub(super) async fn past_meeting(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    user: &User,
+    target: Option<&str>,
+    lookup: Lookup,
+) -> Result<Result<PastMeeting, SlackResponse>, AppError> {
+    let meeting = match find_meeting(state, user, target, lookup).await? {
+        Ok(meeting) => meeting,
+        Err(reply) => return Ok(Err(SlackResponse::ephemeral(reply))),
+    };
+    let Some(space_name) = meeting.space_name.clone() else {
+        return Ok(Err(SlackResponse::ephemeral(format!(
+            "{} That meeting was created before the bot kept track of Meet spaces, so its {} can't be looked up.",
+            lookup.emoji(),
+            lookup.subject()
+        ))));
+    };
+    if meeting.created_with_account_user_id.is_some() {
+        return Ok(Err(SlackResponse::ephemeral(format!(
+            "{} That meeting was created with your workspace's shared Google account, so only that account can see its {}.",
+            lookup.emoji(),
+            lookup.subject()
+        ))));
+    }
+
+    let token = match ready_token(state, user).await? {
+        TokenCheck::Ready(token) => token,
+        TokenCheck::NeedsAuth => {
+            return Ok(Err(SlackResponse::with_auth_prompt(auth_url(
+                state,
+                &payload.user_id,
+            ))))
+        }
+    };
+    if !has_scope(&token, MEET_READONLY_SCOPE) {
+        info!("{} hasn't granted the read-only Meet scope", user.id);
+        return Ok(Err(missing_scope(state, payload, lookup)));
+    }
+
+    Ok(Ok(PastMeeting {
+        meeting,
+        space_name,
+        token,
+    }))
+}
+
+/// The user's meeting at `target`, a Meet link or code, or their latest one
+/// with a known space. `Err` holds the reply when there is none.
+async fn find_meeting(
+    state: &AppState,
+    user: &User,
+    target: Option<&str>,
+    lookup: Lookup,
+) -> Result<Result<Meeting, String>, AppError> {
+    let Some(target) = target else {
+        let meeting = observability::timed(
+            Phase::Database,
+            state.db.get_latest_user_space_meeting(user.id),
+        )
+        .await?;
+        return Ok(meeting.ok_or_else(|| {
+            format!(
+                "{} You have no meetings whose {} can be looked up yet.",
+                lookup.emoji(),
+                lookup.subject()
+            )
+        }));
+    };
+
+    let link = if target.contains('/') {
+        target.to_string()
+    } else {
+        format!("https://meet.google.com/{}", target)
+    };
+    let Ok(link) = state.validator.validate_meet_link(&link) else {
+        return Ok(Err(
+            "❌ That isn't a Google Meet link or meeting code.".to_string()
+        ));
+    };
+    let meeting = observability::timed(
+        Phase::Database,
+        state.db.get_user_meeting_by_link(user.id, &link),
+    )
+    .await?;
+    Ok(meeting.ok_or_else(|| {
+        format!(
+            "{} You haven't created a meeting at {}.",
+            lookup.emoji(),
+            link
+        )
+    }))
+}
+
+/// The participants with how long each was in the call, longest first. The
+/// time runs from their first join to their last leave, so breaks in
+/// between count too; Google reports separate sessions only on request.
+fn by_time_in_call(
+    participants: Vec<Participant>,
+    ended_at: chrono::DateTime<chrono::Utc>,
+) -> Vec<(Participant, Duration)> {
+    let mut participants: Vec<(Participant, Duration)> = participants
+        .into_iter()
+        .map(|participant| {
+            let left_at = participant.latest_end_time.unwrap_or(ended_at);
+            let time_in_call = left_at - participant.earliest_start_time;
+            (participant, time_in_call.max(Duration::zero()))
+        })
+        .collect();
+    participants.sort_by(|(a, a_time), (b, b_time)| {
+        b_time
+            .cmp(a_time)
+            .then_with(|| a.display_name.cmp(&b.display_name))
+    });
+    participants
+}
+
+fn format_minutes(duration: Duration) -> String {
+    match duration.num_minutes() {
+        0 => "under a minute".to_string(),
+        minutes => format!("{} min", minutes),
+    }
+}
+
+pub(super) fn meeting_label(meeting: &Meeting) -> String {
+    match &meeting.title {
+        Some(title) => format!("<{}|{}>", meeting.meet_link, blocks::title(title)),
+        None => format!("<{}>", meeting.meet_link),
+    }
+}
+
+fn missing_scope(state: &AppState, payload: &SlashCommandPayload, lookup: Lookup) -> SlackResponse {
+    SlackResponse::ephemeral(format!(
+        "{} Looking up {} needs permission to see your meetings, which wasn't given when you connected Google. <{}|Connect Google again> and allow it.",
+        lookup.emoji(),
+        lookup.subject(),
+        auth_url(state, &payload.user_id)
+    ))
+}
+
+/// The reply when Google failed to answer a lookup.
+pub(super) fn google_failure(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    e: GoogleApiError,
+    lookup: Lookup,
+) -> Result<SlackResponse, AppError> {
+    match e {
+        GoogleApiError::MissingScope => Ok(missing_scope(state, payload, lookup)),
+        GoogleApiError::Unauthorized => Ok(SlackResponse::with_auth_prompt(auth_url(
+            state,
+            &payload.user_id,
+        ))),
+        e => {
+            warn!(
+                "Failed to look up {} for {}: {}",
+                lookup.subject(),
+                payload.user_id,
+                e
+            );
+            Ok(SlackResponse::ephemeral(format!(
+                "❌ Google didn't send the {}. Please try again in a moment.",
+                lookup.subject()
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, Fixture, RESPONSE_URL};
+    use super::*;
+    use crate::auth::oauth::REQUESTED_SCOPES;
+    use crate::google::fake::FakeGoogleApi;
+    use crate::google::ConferenceRecord;
+    use chrono::{DateTime, Utc};
+    use std::sync::Arc;
+
+    const SPACE: &str = "spaces/abc";
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn participant(name: &str, joined: &str, left: &str) -> Participant {
+        Participant {
+            display_name: name.to_string(),
+            earliest_start_time: at(joined),
+            latest_end_time: Some(at(left)),
+        }
+    }
+
+    fn conference(name: &str, started: &str, ended: Option<&str>) -> ConferenceRecord {
+        ConferenceRecord {
+            name: name.to_string(),
+            start_time: at(started),
+            end_time: ended.map(at),
+        }
+    }
+
+    async fn run(state: &AppState, user: &User, target: Option<&str>) -> String {
+        handle_attendance(
+            state.clone(),
+            payload("attendance", RESPONSE_URL),
+            user.clone(),
+            target.map(str::to_string),
+        )
+        .await
+        .unwrap()
+        .text
+    }
+
+    #[tokio::test]
+    async fn test_summarizes_the_latest_call() {
+        let google = FakeGoogleApi::succeeding()
+            .with_conference(
+                SPACE,
+                conference(
+                    "conferenceRecords/old",
+                    "2024-03-04T10:00:00Z",
+                    Some("2024-03-04T10:20:00Z"),
+                ),
+                vec![],
+            )
+            .with_conference(
+                SPACE,
+                conference(
+                    "conferenceRecords/new",
+                    "2024-03-05T10:00:00Z",
+                    Some("2024-03-05T10:30:00Z"),
+                ),
+                vec![
+                    participant("Bob", "2024-03-05T10:05:00Z", "2024-03-05T10:15:30Z"),
+                    participant("Alice", "2024-03-05T10:00:00Z", "2024-03-05T10:30:00Z"),
+                    participant("Dial-in", "2024-03-05T10:29:40Z", "2024-03-05T10:30:00Z"),
+                ],
+            );
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+
+        let text = run(&state, &user, None).await;
+
+        assert_eq!(
+            text,
+            "📊 *<https://meet.google.com/abc-defg-hij|Standup>*: 3 people joined the call on 2024-03-05 10:00–10:30 UTC\n\
+             • Alice — 30 min\n\
+             • Bob — 10 min\n\
+             • Dial-in — under a minute\n\
+             _The link was used for 2 calls; this is the latest._"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_the_call_to_end() {
+        let google = FakeGoogleApi::succeeding().with_conference(
+            SPACE,
+            conference("conferenceRecords/now", "2024-03-05T10:00:00Z", None),
+            vec![],
+        );
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+
+        let text = run(&state, &user, Some("ABC-DEFG-HIJ")).await;
+        assert!(text.contains("is still going on"), "{}", text);
+
+        let (state, user) = Fixture::new()
+            .google(Arc::new(FakeGoogleApi::succeeding()))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+        let text = run(&state, &user, None).await;
+        assert!(text.starts_with("📊 Nobody has joined"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_asks_for_the_scope_when_it_was_not_granted() {
+        let google =
+            FakeGoogleApi::succeeding().failing_conferences(|| GoogleApiError::MissingScope);
+
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google))
+            .connected("https://www.googleapis.com/auth/meetings.space.created")
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+        let text = run(&state, &user, None).await;
+        assert!(text.contains("Connect Google again"), "{}", text);
+
+        // Google has the last word on what the token allows
+        let google =
+            FakeGoogleApi::succeeding().failing_conferences(|| GoogleApiError::MissingScope);
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+        let text = run(&state, &user, None).await;
+        assert!(text.contains("Connect Google again"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_only_the_users_own_meetings_are_looked_up() {
+        let (state, user) = Fixture::new()
+            .google(Arc::new(FakeGoogleApi::succeeding()))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+
+        assert_eq!(
+            run(&state, &user, Some("xyz-wxyz-xyz")).await,
+            "📊 You haven't created a meeting at https://meet.google.com/xyz-wxyz-xyz."
+        );
+        assert_eq!(
+            run(&state, &user, Some("https://example.com/abc-defg-hij")).await,
+            "❌ That isn't a Google Meet link or meeting code."
+        );
+    }
+}
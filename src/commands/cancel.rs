@@ -0,0 +1,369 @@
+//! `/meet cancel [#id]` and `/meet end [#id]`: stop the user's latest
+//! meeting, or the one with that id. Ending also ends the call at Google;
+//! either way the meeting's announcement is edited to say so, its reminder
+//! is taken back, and `/meet join` stops offering it.
+
+use axum::async_trait;
+use tracing::{info, warn};
+
+use super::attendance::meeting_label;
+use super::create::{ready_token, TokenCheck};
+use super::{auth_url, CommandContext, CommandHandler};
+use crate::command_parser::MeetCommand;
+use crate::database::models::{Meeting, User};
+use crate::error::AppError;
+use crate::features::Feature;
+use crate::google::GoogleApiError;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::meeting_end::{self, Ending};
+use crate::observability::{self, Phase};
+use crate::reminders;
+use crate::AppState;
+
+pub struct CancelHandler;
+
+#[async_trait]
+impl CommandHandler for CancelHandler {
+    fn name(&self) -> &'static str {
+        "cancel"
+    }
+
+    fn feature(&self) -> Option<Feature> {
+        Some(Feature::Cancel)
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let meeting_id = match ctx.command {
+            MeetCommand::Cancel { meeting_id } => meeting_id,
+            _ => None,
+        };
+        handle_stop(ctx.state, ctx.payload, user, meeting_id, Ending::Cancelled).await
+    }
+}
+
+pub struct EndHandler;
+
+#[async_trait]
+impl CommandHandler for EndHandler {
+    fn name(&self) -> &'static str {
+        "end"
+    }
+
+    fn feature(&self) -> Option<Feature> {
+        Some(Feature::Cancel)
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let meeting_id = match ctx.command {
+            MeetCommand::End { meeting_id } => meeting_id,
+            _ => None,
+        };
+        handle_stop(ctx.state, ctx.payload, user, meeting_id, Ending::Ended).await
+    }
+}
+
+/// Stops the meeting once, telling its channel. A meeting being ended has
+/// its call ended first, so a failure at Google leaves it to try again.
+async fn handle_stop(
+    state: AppState,
+    payload: SlashCommandPayload,
+    user: User,
+    meeting_id: Option<i64>,
+    ending: Ending,
+) -> Result<SlackResponse, AppError> {
+    let meeting = match meeting_id {
+        Some(meeting_id) => observability::timed(Phase::Database, state.db.get_meeting(meeting_id))
+            .await?
+            .filter(|meeting| meeting.user_id == user.id),
+        None => observability::timed(Phase::Database, state.db.get_user_meetings(user.id, 1))
+            .await?
+            .into_iter()
+            .next(),
+    };
+    let Some(meeting) = meeting else {
+        return Ok(SlackResponse::ephemeral(match meeting_id {
+            Some(meeting_id) => format!("❌ You haven't created meeting #{}.", meeting_id),
+            None => "You haven't created any meetings yet.".to_string(),
+        }));
+    };
+    let Some(id) = meeting.id else {
+        return Err(AppError::Internal(anyhow::anyhow!(
+            "stored meeting has no id"
+        )));
+    };
+    let label = meeting_label(&meeting);
+
+    let call_ended = match ending {
+        Ending::Cancelled => false,
+        Ending::Ended => match end_call(&state, &payload, &user, &meeting).await? {
+            Ok(call_ended) => call_ended,
+            Err(reply) => return Ok(reply),
+        },
+    };
+
+    let now = state.clock.now();
+    let stopped =
+        observability::timed(Phase::Database, state.db.stop_meeting(id, now.naive_utc())).await?;
+    if !stopped {
+        return Ok(SlackResponse::ephemeral(if call_ended {
+            format!("⏹️ Ended the call in {}.", label)
+        } else {
+            format!("{} was already cancelled or ended.", label)
+        }));
+    }
+    info!("User {} stopped meeting {} ({:?})", user.id, id, ending);
+
+    if let Err(e) =
+        meeting_end::update_announcement(&state, &meeting, ending, &payload.user_id).await
+    {
+        warn!(
+            "Failed to update the announcement of meeting {}: {:#}",
+            id, e
+        );
+    }
+
+    let reminder_cancelled = match observability::timed(
+        Phase::Database,
+        reminders::cancel(&state.db, state.slack.as_ref(), &payload.team_id, id),
+    )
+    .await
+    {
+        Ok(cancelled) => cancelled,
+        Err(e) => {
+            warn!("Failed to cancel the reminder of meeting {}: {:#}", id, e);
+            false
+        }
+    };
+
+    let reply = match ending {
+        Ending::Cancelled => format!("🚫 Cancelled {}.", label),
+        Ending::Ended if call_ended => format!("⏹️ Ended {} and its call.", label),
+        Ending::Ended => format!("⏹️ Ended {}; nobody was in its call.", label),
+    };
+    Ok(SlackResponse::ephemeral(if reminder_cancelled {
+        format!("{} Its reminder won't be posted.", reply)
+    } else {
+        reply
+    }))
+}
+
+/// Ends the call going on in `meeting`'s space, returning whether there
+/// was one. `Err` holds the reply when Google can't be asked or refused.
+async fn end_call(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    user: &User,
+    meeting: &Meeting,
+) -> Result<Result<bool, SlackResponse>, AppError> {
+    let Some(space_name) = &meeting.space_name else {
+        return Ok(Err(SlackResponse::ephemeral(
+            "⏹️ That meeting was created before the bot kept track of Meet spaces, so its call can't be ended from Slack. Use `cancel` to mark it cancelled.".to_string(),
+        )));
+    };
+    if meeting.created_with_account_user_id.is_some() {
+        return Ok(Err(SlackResponse::ephemeral(
+            "⏹️ That meeting was created with your workspace's shared Google account, so only that account can end its call.".to_string(),
+        )));
+    }
+
+    let token = match ready_token(state, user).await? {
+        TokenCheck::Ready(token) => token,
+        TokenCheck::NeedsAuth => {
+            return Ok(Err(SlackResponse::with_auth_prompt(auth_url(
+                state,
+                &payload.user_id,
+            ))))
+        }
+    };
+    match observability::timed(
+        Phase::Google,
+        state
+            .google
+            .end_active_conference(&token.access_token, space_name),
+    )
+    .await
+    {
+        Ok(call_ended) => Ok(Ok(call_ended)),
+        Err(GoogleApiError::Unauthorized | GoogleApiError::MissingScope) => Ok(Err(
+            SlackResponse::with_auth_prompt(auth_url(state, &payload.user_id)),
+        )),
+        Err(e) => {
+            warn!("Failed to end the call in {}: {}", space_name, e);
+            Ok(Err(SlackResponse::ephemeral(
+                "❌ Google didn't end the call. Please try again in a moment.".to_string(),
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, Fixture, MEETING_LINK, RESPONSE_URL};
+    use super::*;
+    use crate::command_parser;
+    use crate::config::Config;
+    use crate::features::FeatureFlags;
+    use crate::google::fake::FakeGoogleApi;
+    use crate::google::ConferenceRecord;
+    use crate::slack::fake::FakeSlackApi;
+    use std::sync::Arc;
+
+    const SPACE: &str = "spaces/abc";
+
+    /// State with the flag on, the bot installed, and U012AB3CD's meeting
+    /// in [`SPACE`] announced by the bot in C012AB3CD.
+    async fn state(google: Arc<FakeGoogleApi>) -> (AppState, Arc<FakeSlackApi>, Meeting) {
+        let mut config = Config::for_tests();
+        config.features = FeatureFlags::new([Feature::Cancel]);
+        let fixture = Fixture::new()
+            .google(google)
+            .config(config)
+            .installed()
+            .meeting_in(SPACE)
+            .build()
+            .await;
+        let meeting = fixture.meeting.unwrap();
+        fixture
+            .state
+            .db
+            .set_announcement_message(meeting.id.unwrap(), "C012AB3CD", "1709628600.000200")
+            .await
+            .unwrap();
+        (fixture.state, fixture.slack, meeting)
+    }
+
+    async fn run(state: &AppState, text: &str) -> String {
+        state
+            .commands
+            .dispatch(
+                state.clone(),
+                payload(text, RESPONSE_URL),
+                command_parser::parse(text).unwrap(),
+                false,
+            )
+            .await
+            .unwrap()
+            .text
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_edits_the_announcement_once() {
+        let (state, slack, meeting) = state(Arc::new(FakeGoogleApi::succeeding())).await;
+
+        let reply = run(&state, &format!("cancel #{}", meeting.id.unwrap())).await;
+
+        assert_eq!(reply, format!("🚫 Cancelled <{}|Standup>.", MEETING_LINK));
+        let updated = slack.updated();
+        assert_eq!(updated.len(), 1);
+        assert!(
+            updated[0].1.text.contains("🚫 Cancelled by <@U012AB3CD>"),
+            "{}",
+            updated[0].1.text
+        );
+        assert_eq!(
+            run(&state, "cancel").await,
+            format!("<{}|Standup> was already cancelled or ended.", MEETING_LINK)
+        );
+        assert_eq!(slack.updated().len(), 1);
+        // Nor is it offered to the channel any more
+        assert!(state
+            .db
+            .get_latest_channel_meeting("C012AB3CD")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cancelling_takes_the_reminder_back() {
+        let (state, slack, meeting) = state(Arc::new(FakeGoogleApi::succeeding())).await;
+        let now = state.clock.now();
+        reminders::schedule(
+            &state.db,
+            slack.as_ref(),
+            &payload("standup", RESPONSE_URL),
+            &meeting,
+            now + chrono::Duration::hours(1),
+            chrono::Duration::minutes(10),
+            now,
+        )
+        .await
+        .unwrap();
+        assert_eq!(slack.scheduled().len(), 1);
+
+        let reply = run(&state, "cancel").await;
+
+        assert_eq!(
+            reply,
+            format!(
+                "🚫 Cancelled <{}|Standup>. Its reminder won't be posted.",
+                MEETING_LINK
+            )
+        );
+        assert!(slack.scheduled().is_empty());
+        assert_eq!(
+            state
+                .db
+                .meeting_reminder(meeting.id.unwrap())
+                .await
+                .unwrap(),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ending_ends_the_call_at_google() {
+        let live = ConferenceRecord {
+            name: "conferenceRecords/now".to_string(),
+            start_time: chrono::Utc::now(),
+            end_time: None,
+        };
+        let google = Arc::new(FakeGoogleApi::succeeding().with_conference(SPACE, live, Vec::new()));
+        let (state, slack, _meeting) = state(google.clone()).await;
+
+        let reply = run(&state, "end").await;
+
+        assert_eq!(
+            reply,
+            format!("⏹️ Ended <{}|Standup> and its call.", MEETING_LINK)
+        );
+        assert_eq!(google.ended(), vec![SPACE.to_string()]);
+        assert!(
+            slack.updated()[0]
+                .1
+                .text
+                .contains("⏹️ Ended by <@U012AB3CD>"),
+            "{:?}",
+            slack.updated()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_the_creator_can_stop_a_meeting() {
+        let (state, slack, meeting) = state(Arc::new(FakeGoogleApi::succeeding())).await;
+        let mut other = payload("cancel", RESPONSE_URL);
+        other.user_id = "U098ZY7XW".to_string();
+        let id = meeting.id.unwrap();
+
+        let reply = state
+            .commands
+            .dispatch(
+                state.clone(),
+                other,
+                MeetCommand::Cancel {
+                    meeting_id: Some(id),
+                },
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            reply.text,
+            format!("❌ You haven't created meeting #{}.", id)
+        );
+        assert!(slack.updated().is_empty());
+    }
+}
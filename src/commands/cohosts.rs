@@ -0,0 +1,242 @@
+//! `/meet --cohost @someone`: co-hosts made members of a new meeting's
+//! space, so they can admit people when its creator is late.
+
+use tracing::{info, warn};
+
+use crate::command_parser::Attendee;
+use crate::database::models::{MeetLinkKind, Meeting};
+use crate::google::GoogleApiError;
+use crate::handlers::slack::SlashCommandPayload;
+use crate::observability::{self, Phase};
+use crate::secret::SecretString;
+use crate::slack::api::SlackApiError;
+use crate::AppState;
+
+/// Makes each of `cohosts`, a Slack user or an email, a co-host of the new
+/// `meeting`, with the token of the account that created it. Returns one
+/// line per co-host saying how it went, for the creator.
+pub(super) async fn add_cohosts(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    access_token: &SecretString,
+    meeting: &Meeting,
+    cohosts: &[Attendee],
+) -> Vec<String> {
+    let space_name = match (meeting.link_kind, meeting.space_name.as_deref()) {
+        (MeetLinkKind::Meet, Some(space_name)) => space_name,
+        // Only Meet spaces have members; a calendar event would need its
+        // attendees edited, which the bot has no access to
+        _ => {
+            return cohosts
+                .iter()
+                .map(|cohost| {
+                    format!(
+                        "⚠️ {} wasn't made a co-host: Google gave a calendar event instead of a Meet space, and only Meet spaces have co-hosts.",
+                        who(cohost)
+                    )
+                })
+                .collect()
+        }
+    };
+
+    let mut lines = Vec::with_capacity(cohosts.len());
+    for cohost in cohosts {
+        let email = match email_of(state, payload, cohost).await {
+            Ok(email) => email,
+            Err(line) => {
+                lines.push(line);
+                continue;
+            }
+        };
+        let added = observability::timed(
+            Phase::Google,
+            state
+                .google
+                .add_space_member(access_token, space_name, &email),
+        )
+        .await;
+        lines.push(match added {
+            Ok(()) => {
+                info!("Added a co-host to {}", space_name);
+                format!("👥 {} is a co-host of the meeting.", who(cohost))
+            }
+            Err(GoogleApiError::MemberRejected { .. }) => format!(
+                "⚠️ {} wasn't made a co-host: Google only takes Google accounts in your organization. They can still join with the link.",
+                who(cohost)
+            ),
+            Err(GoogleApiError::MissingScope) => format!(
+                "⚠️ {} wasn't made a co-host: Google didn't allow the bot to add members to your meetings.",
+                who(cohost)
+            ),
+            Err(e) => {
+                warn!("Failed to add a co-host to {}: {}", space_name, e);
+                format!(
+                    "⚠️ {} wasn't made a co-host: Google didn't answer. Add them from the meeting's host controls.",
+                    who(cohost)
+                )
+            }
+        });
+    }
+    lines
+}
+
+/// How a co-host is named to the creator.
+fn who(cohost: &Attendee) -> String {
+    match cohost {
+        Attendee::SlackUser(user_id) => format!("<@{}>", user_id),
+        Attendee::Handle(handle) => format!("@{}", handle),
+        Attendee::Email(email) => email.clone(),
+    }
+}
+
+/// The Google account email of `cohost`: an email as given, or the one in a
+/// Slack user's profile. `Err` holds the line for the creator when there is
+/// none.
+async fn email_of(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    cohost: &Attendee,
+) -> Result<String, String> {
+    let user_id = match cohost {
+        Attendee::Email(email) => return Ok(email.clone()),
+        Attendee::Handle(handle) => {
+            return Err(format!(
+                "⚠️ @{} wasn't made a co-host: pick them from Slack's suggestions so the bot knows who they are, or give their email.",
+                handle
+            ))
+        }
+        Attendee::SlackUser(user_id) => user_id,
+    };
+
+    let team = match observability::timed(
+        Phase::Database,
+        state.db.get_slack_team(&payload.team_id),
+    )
+    .await
+    {
+        Ok(Some(team)) => team,
+        Ok(None) => {
+            return Err(format!(
+                "⚠️ <@{}> wasn't made a co-host: the bot can't look up emails in this workspace. Give their email instead.",
+                user_id
+            ))
+        }
+        Err(e) => {
+            warn!("Failed to look up team {}: {:#}", payload.team_id, e);
+            return Err(format!(
+                "⚠️ <@{}> wasn't made a co-host: their email couldn't be looked up. Give their email instead.",
+                user_id
+            ));
+        }
+    };
+    match state.slack.user_email(&team.bot_token, user_id).await {
+        Ok(Some(email)) => Ok(email),
+        Ok(None) => Err(format!(
+            "⚠️ <@{}> wasn't made a co-host: their Slack profile has no email. Give their email instead.",
+            user_id
+        )),
+        Err(SlackApiError::Api(code)) if code == "missing_scope" => Err(format!(
+            "⚠️ <@{}> wasn't made a co-host: the bot needs the `users:read.email` scope to look up emails. Give their email instead.",
+            user_id
+        )),
+        Err(e) => {
+            warn!("Failed to look up the email of {}: {}", user_id, e);
+            Err(format!(
+                "⚠️ <@{}> wasn't made a co-host: their email couldn't be looked up. Give their email instead.",
+                user_id
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, Fixture, TestState, RESPONSE_URL};
+    use super::*;
+    use crate::google::fake::FakeGoogleApi;
+    use std::sync::Arc;
+
+    fn meeting() -> Meeting {
+        Meeting::new(
+            1,
+            "https://meet.google.com/abc-defg-hij".to_string(),
+            None,
+            MeetLinkKind::Meet,
+        )
+        .with_space("spaces/abc")
+    }
+
+    #[tokio::test]
+    async fn test_mentions_and_emails_become_cohosts() {
+        let google = Arc::new(FakeGoogleApi::succeeding().rejecting_member("joe@example.org"));
+        let TestState { state, slack, .. } = Fixture::new()
+            .google(google.clone())
+            .installed()
+            .build()
+            .await;
+        slack.set_email("U0BOB", "bob@example.com");
+
+        let lines = add_cohosts(
+            &state,
+            &payload("", RESPONSE_URL),
+            &"ya29.access".into(),
+            &meeting(),
+            &[
+                Attendee::SlackUser("U0BOB".to_string()),
+                Attendee::Email("joe@example.org".to_string()),
+                Attendee::SlackUser("U0NOEMAIL".to_string()),
+                Attendee::Handle("carol".to_string()),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            google.members(),
+            [("spaces/abc".to_string(), "bob@example.com".to_string())]
+        );
+        assert_eq!(lines[0], "👥 <@U0BOB> is a co-host of the meeting.");
+        assert!(
+            lines[1].starts_with("⚠️ joe@example.org wasn't made a co-host: Google only takes Google accounts in your organization."),
+            "{}",
+            lines[1]
+        );
+        assert!(
+            lines[2].contains("their Slack profile has no email"),
+            "{}",
+            lines[2]
+        );
+        assert!(
+            lines[3].starts_with("⚠️ @carol wasn't made a co-host"),
+            "{}",
+            lines[3]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_calendar_events_get_no_cohosts() {
+        let google = Arc::new(FakeGoogleApi::succeeding());
+        let TestState { state, .. } = Fixture::new()
+            .google(google.clone())
+            .installed()
+            .build()
+            .await;
+        let mut meeting = meeting();
+        meeting.link_kind = MeetLinkKind::Calendar;
+
+        let lines = add_cohosts(
+            &state,
+            &payload("", RESPONSE_URL),
+            &"ya29.access".into(),
+            &meeting,
+            &[Attendee::Email("ann@example.com".to_string())],
+        )
+        .await;
+
+        assert!(google.members().is_empty());
+        assert!(
+            lines[0].contains("only Meet spaces have co-hosts"),
+            "{}",
+            lines[0]
+        );
+    }
+}
@@ -0,0 +1,2342 @@
+//! `/meet [title]`: creates a Google Meet and shares it in the channel, or
+//! with `--quiet` shows it to the user alone.
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use tracing::{error, info, warn, Instrument};
+
+use super::{auth_url, cohosts, outside_hours, Claim, CommandContext, CommandHandler};
+use crate::announcement::{self, AnnouncementStyle};
+use crate::audit::{self, AuditEvent};
+use crate::auth::oauth::{is_token_valid_at, refresh_token_if_needed};
+use crate::command_parser::{Attendee, MeetCommand};
+use crate::crypto::CryptoError;
+use crate::database::models::{
+    MeetLinkKind, Meeting, MeetingSource, MeetingVisibility, OAuthToken, User,
+};
+use crate::database::Database;
+use crate::error::{AppError, RateLimit};
+use crate::google::{Artifacts, GoogleApiError, MeetingOptions};
+use crate::handlers::auth::create_oauth_client;
+use crate::handlers::slack::{send_followup, SlackResponse, SlashCommandPayload};
+use crate::ics;
+use crate::meeting_end;
+use crate::observability::{self, Phase};
+use crate::personal_space::{self, Standing};
+use crate::qr;
+use crate::quota;
+use crate::recording;
+use crate::reminders;
+use crate::shared_account::{self, SharedAccount};
+use crate::telemetry::metrics;
+use crate::timezones;
+use crate::title_template::{self, DefaultTitleMode, TitleValues};
+use crate::validation::SanitizedText;
+use crate::working_hours;
+use crate::AppState;
+
+/// How long a trigger id is remembered. Slack only retries within seconds,
+/// so a day is plenty.
+const REQUEST_DEDUP_RETENTION: chrono::Duration = chrono::Duration::days(1);
+
+const TITLE_SANITIZED_NOTE: &str =
+    "ℹ️ I removed some unsupported characters from your meeting title.";
+const TITLE_TRUNCATED_NOTE: &str = "ℹ️ Your meeting title was too long, so I shortened it.";
+const ALREADY_CREATING: &str = "✋ Hang on, I'm already creating your meeting.";
+pub(super) const CREATION_FAILED: &str = "❌ Failed to create Google Meet link. Please try again.";
+const SHARED_ACCOUNT_REJECTED: &str =
+    "❌ Google rejected this workspace's shared account, so I couldn't create your meeting. I've let your admins know.";
+
+/// What `/meet` asked for, carried through the meeting queue to the worker
+/// creating it.
+#[derive(Debug, Clone, Default)]
+pub struct MeetingRequest {
+    pub title: Option<String>,
+    /// When the meeting starts, if later than now.
+    pub starts_at: Option<DateTime<Utc>>,
+    /// When the meeting ends, if a duration was given.
+    pub ends_at: Option<DateTime<Utc>>,
+    pub visibility: MeetingVisibility,
+    /// What Meet records or transcribes by itself.
+    pub artifacts: Artifacts,
+    /// Slack users and emails to make co-hosts.
+    pub cohosts: Vec<Attendee>,
+    /// Slack users and emails the command mentioned.
+    pub attendees: Vec<Attendee>,
+    pub source: MeetingSource,
+    /// Whether to post a QR code of the link with the announcement.
+    pub qr_code: bool,
+    /// Whether the user's standing room may be given instead of a new
+    /// space, if they turned it on.
+    pub reuse_personal_space: bool,
+}
+
+pub struct CreateMeetingHandler;
+
+#[async_trait]
+impl CommandHandler for CreateMeetingHandler {
+    fn name(&self) -> &'static str {
+        "create"
+    }
+
+    fn follows_channel_policy(&self) -> bool {
+        true
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let CommandContext {
+            state,
+            payload,
+            command,
+            text_was_modified,
+            ..
+        } = ctx;
+        let MeetCommand::Create {
+            title,
+            duration,
+            start,
+            attendees,
+            cohosts,
+            mut flags,
+        } = command
+        else {
+            return Err(AppError::Internal(anyhow::anyhow!(
+                "create handler got /meet {}",
+                command.name()
+            )));
+        };
+
+        // Slack retries commands it got no answer to in time, and users
+        // submit twice; a trigger that already made a meeting gets it back
+        if let Some(meeting) = observability::timed(
+            Phase::Database,
+            state.db.meeting_for_trigger(&payload.trigger_id),
+        )
+        .await?
+        {
+            info!("Meeting already created for trigger {}", payload.trigger_id);
+            return Ok(meeting_response(&state, &payload, Ok(meeting)).await);
+        }
+
+        for attendee in attendees.iter().chain(&cohosts) {
+            if let Attendee::Email(email) = attendee {
+                state.validator.validate_email(email)?;
+            }
+        }
+
+        if let Some(duration) = duration {
+            state.validator.validate_meeting_duration(duration)?;
+        }
+
+        let starts_at = match start {
+            Some(start) => {
+                let tz = timezones::of_user(&state, &payload.team_id, &payload.user_id).await?;
+                let now = state.clock.now();
+                let Some(start) = start.resolve(&now.with_timezone(&tz)) else {
+                    return Ok(SlackResponse::ephemeral(
+                        "❌ That start time doesn't exist.".to_string(),
+                    ));
+                };
+                let start = start.with_timezone(&Utc);
+                state.validator.validate_meeting_time(start, now)?;
+                Some(start)
+            }
+            None => None,
+        };
+
+        let quiet = flags.remove("quiet");
+        let record = flags.remove("record");
+        let transcribe = flags.remove("transcribe");
+        let outside_hours = flags.remove(working_hours::OUTSIDE_HOURS_FLAG);
+        let qr_code = flags.remove(qr::FLAG);
+        let one_off = flags.remove(personal_space::FLAG);
+        if !flags.is_empty() {
+            return Ok(SlackResponse::ephemeral(format!(
+                "❌ That option isn't supported. Run `{} help` to see what's available.",
+                payload.command
+            )));
+        }
+
+        let title = title
+            .as_deref()
+            .map(|t| state.validator.validate_meeting_title(t))
+            .transpose()?;
+
+        if quota::reached(&state, &payload.team_id, &payload.user_id).await {
+            return Err(AppError::RateLimited(RateLimit::Team));
+        }
+
+        if let (Some(starts_at), false) = (starts_at, outside_hours) {
+            if let Some(confirmation) =
+                outside_hours::check(&state, &payload, starts_at, &attendees).await
+            {
+                return Ok(confirmation);
+            }
+        }
+
+        // One creation per user at a time, so a double submit makes one
+        // meeting; Slack's retries of the running command wait for it and
+        // get its meeting
+        let guard = loop {
+            match state.in_flight.claim(&payload.user_id, &payload.trigger_id) {
+                Claim::Claimed(guard) => break guard,
+                Claim::Retry(running) => {
+                    info!(
+                        "Waiting for the running creation of trigger {}",
+                        payload.trigger_id
+                    );
+                    running.finished().await;
+                    if let Some(meeting) = observability::timed(
+                        Phase::Database,
+                        state.db.meeting_for_trigger(&payload.trigger_id),
+                    )
+                    .await?
+                    {
+                        return Ok(meeting_response(&state, &payload, Ok(meeting)).await);
+                    }
+                }
+                Claim::Busy => {
+                    info!("User {} is already creating a meeting", payload.user_id);
+                    metrics::record_rate_limit_block("in_flight");
+                    return Ok(SlackResponse::ephemeral(ALREADY_CREATING.to_string()));
+                }
+            }
+        };
+
+        if let Some(note) = title_sanitization_note(text_was_modified, title.as_ref()) {
+            send_followup(&state, &payload, SlackResponse::ephemeral(note.to_string()));
+        }
+
+        let request = MeetingRequest {
+            title: title.map(|t| t.value),
+            starts_at,
+            ends_at: duration
+                .map(|duration| starts_at.unwrap_or_else(|| state.clock.now()) + duration),
+            visibility: if quiet {
+                MeetingVisibility::Quiet
+            } else {
+                preferred_visibility(&state, &user).await
+            },
+            artifacts: team_artifacts(&state, &payload.team_id)
+                .await
+                .union(Artifacts {
+                    recording: record,
+                    transcription: transcribe,
+                }),
+            cohosts,
+            attendees,
+            source: MeetingSource::Slack,
+            qr_code,
+            // A standing room keeps what it records from when it was made
+            reuse_personal_space: !(one_off || record || transcribe),
+        };
+
+        // Slack gives up on a command after three seconds, so when Google
+        // is slow the command is acknowledged and the meeting posted to
+        // the response URL once it exists
+        let ack_deadline = state.config.slack.ack_deadline;
+        state
+            .meeting_queue
+            .submit(user, payload, request, Some(guard), ack_deadline)
+            .await
+    }
+}
+
+/// The visibility `user` chose for meetings created without `--quiet`.
+/// Failing to look it up shares the meeting in the channel as usual.
+async fn preferred_visibility(state: &AppState, user: &User) -> MeetingVisibility {
+    let setting = observability::timed(
+        Phase::Database,
+        state
+            .db
+            .get_user_setting(user.id, MeetingVisibility::SETTING_KEY),
+    )
+    .await;
+    match setting {
+        Ok(value) => value
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default(),
+        Err(e) => {
+            warn!(
+                "Failed to look up the meeting visibility of user {}: {:#}",
+                user.id, e
+            );
+            MeetingVisibility::default()
+        }
+    }
+}
+
+/// What the meetings of `team_id` record or transcribe by default. Failing
+/// to look it up generates nothing, as for a workspace without defaults.
+pub(crate) async fn team_artifacts(state: &AppState, team_id: &str) -> Artifacts {
+    match observability::timed(Phase::Database, recording::for_team(&state.db, team_id)).await {
+        Ok(artifacts) => artifacts,
+        Err(e) => {
+            warn!(
+                "Failed to look up the recording defaults of team {}: {:#}",
+                team_id, e
+            );
+            Artifacts::default()
+        }
+    }
+}
+
+/// What [`create_meeting`] came to, with the reply for the user either way.
+pub(super) enum Creation {
+    /// The meeting exists; the reply announces it.
+    Created(SlackResponse),
+    /// No meeting came of it; the reply says why, or asks the user to
+    /// connect their Google account.
+    NotCreated(SlackResponse),
+}
+
+impl Creation {
+    pub(super) fn into_response(self) -> SlackResponse {
+        match self {
+            Creation::Created(response) | Creation::NotCreated(response) => response,
+        }
+    }
+}
+
+/// Creates the meeting with the workspace's shared Google account if it has
+/// a usable one, and otherwise with the user's own token; run by the
+/// meeting queue's workers. Tokens due for a refresh are refreshed first. A
+/// meeting starting later gets its reminder scheduled, unless it is quiet.
+/// Google failing in a way that may pass comes back as an error, so the
+/// worker can try again; anything else is the reply for the user.
+pub(super) async fn create_meeting(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    user: &User,
+    request: &MeetingRequest,
+) -> Result<Creation, AppError> {
+    let shared = shared_token(state, payload, user).await;
+    // The shared account's failures are reported to the workspace instead
+    if shared.is_none() {
+        let now = state.clock.now();
+        if let Some(text) = state
+            .google_failures
+            .short_circuit(&payload.user_id, now)
+            .await
+        {
+            info!(
+                "Google keeps refusing {}, not asking it again yet",
+                payload.user_id
+            );
+            metrics::record_rate_limit_block("google_failures");
+            return Ok(Creation::NotCreated(SlackResponse::ephemeral(text)));
+        }
+    }
+    let token = match &shared {
+        Some((_, token)) => token.clone(),
+        None => match ready_token(state, user).await? {
+            TokenCheck::Ready(token) => token,
+            TokenCheck::NeedsAuth => {
+                return Ok(Creation::NotCreated(SlackResponse::with_auth_prompt(
+                    auth_url(state, &payload.user_id),
+                )))
+            }
+        },
+    };
+
+    let result = create_meet_link(state, &token, payload, user, request).await;
+    if shared.is_none() {
+        match &result {
+            Ok(_) => state.google_failures.reset(&payload.user_id).await,
+            Err(e) => {
+                if let Some(e) = e.downcast_ref::<GoogleApiError>() {
+                    let now = state.clock.now();
+                    state
+                        .google_failures
+                        .record_failure(&payload.user_id, e, now)
+                        .await;
+                }
+            }
+        }
+    }
+    match result {
+        Err(e)
+            if e.downcast_ref::<GoogleApiError>()
+                .is_some_and(GoogleApiError::is_transient) =>
+        {
+            Err(e.into())
+        }
+        Err(e) if matches!(e.downcast_ref(), Some(GoogleApiError::Unauthorized)) => match &shared {
+            Some((account, _)) => {
+                warn!(
+                    "Google rejected the shared account of team {}",
+                    payload.team_id
+                );
+                shared_account::report_failure(
+                    &state.db,
+                    state.slack.as_ref(),
+                    &payload.command,
+                    &payload.team_id,
+                    account,
+                    "Google rejected its access token",
+                )
+                .await;
+                Ok(Creation::NotCreated(SlackResponse::ephemeral(
+                    SHARED_ACCOUNT_REJECTED.to_string(),
+                )))
+            }
+            None => Ok(Creation::NotCreated(
+                meeting_response(state, payload, Err(e)).await,
+            )),
+        },
+        Ok(meeting) => {
+            if let Some((account, _)) = &shared {
+                shared_account::report_working(&state.db, &payload.team_id, account).await;
+            }
+            if meeting.visibility == MeetingVisibility::Channel {
+                meeting_end::record_announcement(state, payload, &meeting).await;
+            }
+            if request.qr_code {
+                // Uploading takes a few calls to Slack, by which time the
+                // announcement this returns has been posted
+                let (state, payload, meeting) = (state.clone(), payload.clone(), meeting.clone());
+                tokio::spawn(
+                    async move { qr::share(&state, &payload, &meeting).await }.in_current_span(),
+                );
+            }
+            match request.starts_at {
+                Some(starts_at) => {
+                    let reminder = match meeting.visibility {
+                        MeetingVisibility::Channel => {
+                            schedule_reminder(state, payload, user, &meeting, starts_at).await
+                        }
+                        // A reminder would share it with the channel
+                        MeetingVisibility::Quiet => None,
+                    };
+                    // A meeting that couldn't be stored has no file to serve
+                    let ics_url = meeting.id.map(|meeting_id| {
+                        ics::url(
+                            state.config.google.public_base_url(),
+                            &ics::sign_token(
+                                &state.config.slack.signing_secret,
+                                meeting_id,
+                                starts_at,
+                            ),
+                        )
+                    });
+                    let local_times = local_start_times(state, payload, request, starts_at).await;
+                    Ok(Creation::Created(SlackResponse::meeting_scheduled(
+                        &payload.user_name,
+                        &meeting,
+                        &announcement_style(state, &payload.team_id).await,
+                        starts_at,
+                        reminder,
+                        ics_url.as_deref(),
+                        local_times.as_deref(),
+                    )))
+                }
+                None => Ok(Creation::Created(
+                    meeting_response(state, payload, Ok(meeting)).await,
+                )),
+            }
+        }
+        result => Ok(Creation::NotCreated(
+            meeting_response(state, payload, result).await,
+        )),
+    }
+}
+
+/// `starts_at` in the timezones of whoever created the meeting and the
+/// Slack users it mentions, for its announcement.
+async fn local_start_times(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    request: &MeetingRequest,
+    starts_at: DateTime<Utc>,
+) -> Option<String> {
+    let mut user_ids = vec![payload.user_id.as_str()];
+    for attendee in &request.attendees {
+        if let Attendee::SlackUser(user_id) = attendee {
+            if !user_ids.contains(&user_id.as_str()) {
+                user_ids.push(user_id);
+            }
+        }
+    }
+    let found = timezones::of_users(state, &payload.team_id, &user_ids).await;
+    let zones: Vec<_> = found.into_iter().map(|(_, tz)| tz).collect();
+    timezones::start_times(starts_at, &zones)
+}
+
+/// Creates a meeting asked for other than through Slack, as the REST API
+/// does: with the same accounts and Google call as [`create_meeting`], but
+/// handing back the meeting rather than a reply, and `None` when `user`
+/// has no usable Google token. `payload` stands in for the command, with
+/// the workspace and user filled in and a `trigger_id` unique to the
+/// request.
+pub async fn create_detached_meeting(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    user: &User,
+    request: &MeetingRequest,
+) -> Result<Option<Meeting>, AppError> {
+    let shared = shared_token(state, payload, user).await;
+    let token = match &shared {
+        Some((_, token)) => token.clone(),
+        None => match ready_token(state, user).await? {
+            TokenCheck::Ready(token) => token,
+            TokenCheck::NeedsAuth => return Ok(None),
+        },
+    };
+
+    let meeting = match create_meet_link(state, &token, payload, user, request).await {
+        Ok(meeting) => meeting,
+        Err(e) => {
+            if let (Some((account, _)), Some(GoogleApiError::Unauthorized)) =
+                (&shared, e.downcast_ref())
+            {
+                shared_account::report_failure(
+                    &state.db,
+                    state.slack.as_ref(),
+                    &payload.command,
+                    &payload.team_id,
+                    account,
+                    "Google rejected its access token",
+                )
+                .await;
+            }
+            return Err(e.into());
+        }
+    };
+    if let Some((account, _)) = &shared {
+        shared_account::report_working(&state.db, &payload.team_id, account).await;
+    }
+    Ok(Some(meeting))
+}
+
+/// Whether a user's Google token can be used.
+pub(super) enum TokenCheck {
+    Ready(OAuthToken),
+    /// The user has to connect Google (again).
+    NeedsAuth,
+}
+
+/// The Google token of `user`, refreshed if due. A token that can't be
+/// refreshed or decrypted needs the user to connect again; one that needs
+/// an encryption key missing from the configuration is an error, so the
+/// token is kept for when the key is back.
+pub(super) async fn ready_token(state: &AppState, user: &User) -> Result<TokenCheck, AppError> {
+    let mut token = match observability::timed(Phase::Database, state.db.get_oauth_token(user.id))
+        .await
+    {
+        Ok(Some(token)) => token,
+        Ok(None) => return Ok(TokenCheck::NeedsAuth),
+        Err(e) => {
+            return match e.downcast_ref::<CryptoError>() {
+                Some(CryptoError::KeyUnavailable { key_id }) => {
+                    error!(
+                        tags.error_kind = "crypto_key_unavailable",
+                        "Token for user {} needs encryption key {:?}, which is not configured",
+                        user.id,
+                        key_id
+                    );
+                    Err(e.into())
+                }
+                Some(crypto_error) => {
+                    warn!(
+                        "Token decryption failed for user {}: {}. Prompting for re-authentication.",
+                        user.id, crypto_error
+                    );
+
+                    match state.db.delete_oauth_token(user.id).await {
+                        Ok(()) => {
+                            audit::record(
+                                &state.db,
+                                AuditEvent::google_disconnected(user, "undecryptable_token"),
+                            )
+                            .await
+                        }
+                        Err(delete_err) => warn!("Failed to delete invalid token: {}", delete_err),
+                    }
+                    Ok(TokenCheck::NeedsAuth)
+                }
+                None => Err(e.into()),
+            };
+        }
+    };
+
+    if token.expires_soon_at(state.clock.now()) {
+        info!(
+            "Token expired or expiring soon for user {}, attempting refresh",
+            user.id
+        );
+
+        let client = create_oauth_client(state)?;
+
+        match observability::timed(
+            Phase::Refresh,
+            refresh_token_if_needed(&client, &state.http, &token, state.clock.now()),
+        )
+        .await
+        {
+            Ok(Some(refreshed_token)) => {
+                info!("Successfully refreshed token for user {}", user.id);
+
+                observability::timed(
+                    Phase::Database,
+                    state.db.store_oauth_token(&refreshed_token),
+                )
+                .await?;
+
+                token = refreshed_token;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to refresh token for user {}: {}", user.id, e);
+                audit::record(&state.db, AuditEvent::token_refresh_failed(user, &e)).await;
+                return Ok(TokenCheck::NeedsAuth);
+            }
+        }
+    }
+
+    if !is_token_valid_at(&token, state.clock.now()) {
+        warn!(
+            "Token invalid or missing required scopes for user {}",
+            user.id
+        );
+        return Ok(TokenCheck::NeedsAuth);
+    }
+
+    Ok(TokenCheck::Ready(token))
+}
+
+/// The token of the workspace's shared account, unless it has none, `user`
+/// is the shared account, or its token can't be used. The admins hear
+/// about the last, and the user's own token is used meanwhile.
+async fn shared_token(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    user: &User,
+) -> Option<(SharedAccount, OAuthToken)> {
+    let account = match observability::timed(
+        Phase::Database,
+        shared_account::for_team(&state.db, &payload.team_id),
+    )
+    .await
+    {
+        Ok(account) => account?,
+        Err(e) => {
+            warn!(
+                "Failed to look up the shared account of team {}: {:#}",
+                payload.team_id, e
+            );
+            return None;
+        }
+    };
+    if account.slack_user_id == user.slack_user_id {
+        return None;
+    }
+
+    let problem = match state.db.get_user_by_slack_id(&account.slack_user_id).await {
+        Ok(Some(owner)) => match ready_token(state, &owner).await {
+            Ok(TokenCheck::Ready(token)) => return Some((account, token)),
+            Ok(TokenCheck::NeedsAuth) => "it isn't connected to Google anymore".to_string(),
+            Err(e) => {
+                e.log();
+                "its token couldn't be loaded".to_string()
+            }
+        },
+        Ok(None) => "it isn't connected to Google".to_string(),
+        Err(e) => {
+            warn!(
+                "Failed to look up the shared account of team {}: {:#}",
+                payload.team_id, e
+            );
+            return None;
+        }
+    };
+    warn!(
+        "Can't use the shared account of team {} ({}), using {}'s own",
+        payload.team_id, problem, payload.user_id
+    );
+    shared_account::report_failure(
+        &state.db,
+        state.slack.as_ref(),
+        &payload.command,
+        &payload.team_id,
+        &account,
+        &problem,
+    )
+    .await;
+    None
+}
+
+/// Schedules the reminder of `user`'s meeting starting at `starts_at`,
+/// returning how long before the start it will be posted. Failing to is
+/// only logged; the meeting stands without one.
+async fn schedule_reminder(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    user: &User,
+    meeting: &Meeting,
+    starts_at: DateTime<Utc>,
+) -> Option<chrono::Duration> {
+    let default = state
+        .config
+        .slack
+        .meeting_reminder
+        .and_then(|lead| chrono::Duration::from_std(lead).ok());
+    let lead = reminders::lead_for(&state.db, user, default).await?;
+    match reminders::schedule(
+        &state.db,
+        state.slack.as_ref(),
+        payload,
+        meeting,
+        starts_at,
+        lead,
+        state.clock.now(),
+    )
+    .await
+    {
+        Ok(scheduled) => scheduled.then_some(lead),
+        Err(e) => {
+            warn!(
+                "Failed to schedule the reminder of a meeting by {}: {:#}",
+                payload.user_id, e
+            );
+            None
+        }
+    }
+}
+
+/// How `slack_team_id` announces meetings. The meeting exists by now, so it
+/// is announced in the default style if the team's can't be read.
+async fn announcement_style(state: &AppState, slack_team_id: &str) -> AnnouncementStyle {
+    announcement::for_team(&state.db, slack_team_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to read the announcement style of team {}: {:#}",
+                slack_team_id, e
+            );
+            AnnouncementStyle::default()
+        })
+}
+
+/// The reply to a meeting creation: the link for the channel, or what went
+/// wrong for the user alone.
+pub(super) async fn meeting_response(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    result: anyhow::Result<Meeting>,
+) -> SlackResponse {
+    let e = match result {
+        Ok(meeting) => {
+            let style = announcement_style(state, &payload.team_id).await;
+            return SlackResponse::meeting_created(&payload.user_name, &meeting, &style);
+        }
+        Err(e) => e,
+    };
+
+    match AppError::from(e) {
+        AppError::Google(GoogleApiError::Unauthorized) => {
+            warn!(
+                "Google rejected the access token of {}, prompting to reconnect",
+                payload.user_id
+            );
+            SlackResponse::with_auth_prompt(auth_url(state, &payload.user_id))
+        }
+        e => {
+            e.log();
+            SlackResponse::ephemeral(
+                e.slack_text()
+                    .unwrap_or_else(|| CREATION_FAILED.to_string()),
+            )
+        }
+    }
+}
+
+/// Creates the Meet space and records the meeting, titled by the
+/// workspace's template if it has one and tagged with the kind of link
+/// Google returned and who it is shown to. It belongs to `user` even when
+/// `token` is the workspace's shared account. With their standing room on,
+/// the user gets that space instead of a new one, and a note saying so.
+/// When a concurrent request for the same trigger got there first, its
+/// meeting is returned instead.
+async fn create_meet_link(
+    state: &AppState,
+    token: &OAuthToken,
+    payload: &SlashCommandPayload,
+    user: &User,
+    request: &MeetingRequest,
+) -> anyhow::Result<Meeting> {
+    let trigger_id = &payload.trigger_id;
+    let title = meeting_title(state, payload, request.title.clone(), request.starts_at).await;
+    let options = MeetingOptions {
+        artifacts: request.artifacts,
+        ..MeetingOptions::default()
+    };
+    let (created, standing) =
+        if request.reuse_personal_space && personal_space::enabled(state, user).await {
+            let (space, standing) = personal_space::space(state, token, user, &options).await?;
+            (space, Some(standing))
+        } else {
+            let created = state
+                .google
+                .create_meeting(&token.access_token, &options)
+                .await?;
+            (created, None)
+        };
+    match standing {
+        Some(Standing::Reused) => info!("Reusing standing Meet space {}", created.name),
+        _ => info!("Google created Meet space {}", created.name),
+    }
+    let raw_link = created.meeting_uri;
+
+    let (meet_link, link_kind) = match state.validator.validate_meet_link(&raw_link) {
+        Ok(link) => (link, MeetLinkKind::Meet),
+        Err(e) => match state.validator.validate_calendar_link(&raw_link) {
+            Ok(link) => {
+                warn!(
+                    "Google returned no Meet link ({}), storing calendar link",
+                    e
+                );
+                (link, MeetLinkKind::Calendar)
+            }
+            Err(_) => anyhow::bail!("Google returned an unusable meeting link: {}", e),
+        },
+    };
+
+    // The meeting exists at Google now, so the link goes to Slack even if
+    // it can't be recorded; it only misses from `/meet list`
+    let mut meeting = Meeting::new(user.id, meet_link, title, link_kind)
+        .with_visibility(request.visibility)
+        .with_team(&payload.team_id)
+        .with_space(&created.name)
+        .with_artifacts(request.artifacts)
+        .with_end(request.ends_at)
+        .with_source(request.source);
+    // Meetings from the API aren't created in any channel
+    if request.source == MeetingSource::Slack {
+        meeting = meeting.with_channel(&payload.channel_id);
+    }
+    if token.user_id != user.id {
+        meeting.created_with_account_user_id = Some(token.user_id);
+    }
+    let meeting = match observability::timed(
+        Phase::Database,
+        state.db.create_meeting_for_trigger(&meeting, trigger_id),
+    )
+    .await
+    {
+        Ok(stored) => stored,
+        Err(e) => {
+            error!(
+                tags.error_kind = "meeting_not_stored",
+                "Failed to store meeting for user {}, sharing the link anyway: {:#}", user.id, e
+            );
+            metrics::record_degraded_response("meeting_not_stored");
+            meeting
+        }
+    };
+
+    if !request.cohosts.is_empty() {
+        let lines = cohosts::add_cohosts(
+            state,
+            payload,
+            &token.access_token,
+            &meeting,
+            &request.cohosts,
+        )
+        .await;
+        send_followup(state, payload, SlackResponse::ephemeral(lines.join("\n")));
+    }
+    if let Some(standing) = standing {
+        send_followup(
+            state,
+            payload,
+            SlackResponse::ephemeral(standing.note(&payload.command)),
+        );
+    }
+
+    metrics::record_meeting_created(match meeting.link_kind {
+        MeetLinkKind::Meet => "meet",
+        MeetLinkKind::Calendar => "calendar",
+    });
+    Ok(meeting)
+}
+
+/// The title for a meeting the user typed `title` for: the workspace's
+/// title template filled in, `title` itself without one, or the
+/// workspace's default title when the user typed none. Dates are the day
+/// the meeting starts where the user is. Failing to look the workspace's
+/// settings up only costs the meeting its templated name.
+async fn meeting_title(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    title: Option<String>,
+    starts_at: Option<DateTime<Utc>>,
+) -> Option<String> {
+    let starts_at = starts_at.unwrap_or_else(|| state.clock.now());
+    let template = observability::timed(
+        Phase::Database,
+        title_template::for_team(&state.db, &payload.team_id),
+    )
+    .await
+    .unwrap_or_else(|e| {
+        warn!(
+            "Failed to look up the title template of team {}: {:#}",
+            payload.team_id, e
+        );
+        None
+    });
+
+    if let Some(template) = template {
+        let date =
+            timezones::local_date(state, &payload.team_id, &payload.user_id, starts_at).await;
+        let rendered = template.render(&TitleValues {
+            channel: &payload.channel_name,
+            user: &payload.user_name,
+            date,
+            text: title.as_deref(),
+        })?;
+        return match state.validator.validate_meeting_title(&rendered) {
+            Ok(rendered) => Some(rendered.value),
+            Err(e) => {
+                warn!("Templated title rejected ({}), keeping the typed one", e);
+                title
+            }
+        };
+    }
+    if title.is_some() {
+        return title;
+    }
+
+    let mode = observability::timed(
+        Phase::Database,
+        title_template::default_title_mode(&state.db, &payload.team_id),
+    )
+    .await
+    .unwrap_or_else(|e| {
+        warn!(
+            "Failed to look up the default title of team {}: {:#}",
+            payload.team_id, e
+        );
+        DefaultTitleMode::default()
+    });
+    let date = timezones::local_date(state, &payload.team_id, &payload.user_id, starts_at).await;
+    Some(title_template::default_title(
+        mode,
+        &payload.channel_name,
+        date,
+    ))
+}
+
+/// Forgets trigger ids past [`REQUEST_DEDUP_RETENTION`] at `now`.
+pub async fn prune_request_dedup(db: &Database, now: DateTime<Utc>) -> anyhow::Result<()> {
+    let cutoff = (now - REQUEST_DEDUP_RETENTION).naive_utc();
+    let pruned = db.prune_request_dedup(cutoff).await?;
+    if pruned > 0 {
+        info!("Pruned {} remembered trigger ids", pruned);
+    }
+    Ok(())
+}
+
+/// The note to show when the meeting title differs from what the user typed;
+/// nothing when it went through untouched.
+fn title_sanitization_note(
+    text_was_modified: bool,
+    title: Option<&SanitizedText>,
+) -> Option<&'static str> {
+    let title = title?;
+    if title.was_truncated {
+        Some(TITLE_TRUNCATED_NOTE)
+    } else if text_was_modified || title.was_modified {
+        Some(TITLE_SANITIZED_NOTE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{
+        connected_user, payload, test_state, test_state_with, Fixture, TestState, RESPONSE_URL,
+    };
+    use super::*;
+    use crate::command_parser;
+    use crate::config::Config;
+    use crate::database::models::PersonalSpace;
+    use crate::google::fake::{FakeGoogleApi, FAKE_MEETING_URI};
+    use crate::google::{
+        ConferenceArtifact, ConferenceRecord, CreatedMeeting, GoogleApi, Participant,
+    };
+    use crate::secret::SecretString;
+
+    use crate::time::TestClock;
+    use crate::validation::InputValidator;
+    use axum::response::Json;
+    use chrono::Timelike;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn request(title: Option<&str>, starts_at: Option<DateTime<Utc>>) -> MeetingRequest {
+        MeetingRequest {
+            title: title.map(str::to_string),
+            starts_at,
+            ..MeetingRequest::default()
+        }
+    }
+
+    fn create_title(text: &str) -> (bool, Option<SanitizedText>) {
+        let validator = InputValidator::default();
+        let sanitized = validator.validate_text_input(text, "Command text").unwrap();
+        match command_parser::parse(&sanitized.value).unwrap() {
+            MeetCommand::Create { title, .. } => (
+                sanitized.was_modified,
+                title.map(|t| validator.validate_meeting_title(&t).unwrap()),
+            ),
+            other => panic!("expected a create command, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_note_only_when_title_was_modified() {
+        let (modified, title) = create_title("Stand\u{0007}up \u{202E}sync");
+        assert_eq!(title.as_ref().unwrap().value, "Standup sync");
+        assert_eq!(
+            title_sanitization_note(modified, title.as_ref()),
+            Some(TITLE_SANITIZED_NOTE)
+        );
+
+        let (modified, title) = create_title("Standup sync");
+        assert_eq!(title.as_ref().unwrap().value, "Standup sync");
+        assert_eq!(title_sanitization_note(modified, title.as_ref()), None);
+
+        let (modified, title) = create_title("Retro 🎉 zespołu");
+        assert_eq!(title_sanitization_note(modified, title.as_ref()), None);
+    }
+
+    #[test]
+    fn test_note_when_title_was_truncated() {
+        let (modified, title) = create_title(&"word ".repeat(60));
+        assert!(title.as_ref().unwrap().was_truncated);
+        assert_eq!(
+            title_sanitization_note(modified, title.as_ref()),
+            Some(TITLE_TRUNCATED_NOTE)
+        );
+    }
+
+    #[test]
+    fn test_no_note_without_a_title() {
+        assert_eq!(title_sanitization_note(true, None), None);
+    }
+
+    #[tokio::test]
+    async fn test_corrupted_token_is_purged_and_user_reauthenticates() {
+        let (state, pool) = test_state().await;
+
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+        state
+            .db
+            .store_oauth_token(&OAuthToken::new(user.id, "access".into(), None, None, None))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE oauth_tokens SET access_token = 'v2:default:AAAA' WHERE user_id = ?1")
+            .bind(user.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let payload = payload("", RESPONSE_URL);
+
+        let response = create_meeting(&state, &payload, &user, &MeetingRequest::default())
+            .await
+            .unwrap()
+            .into_response();
+
+        assert!(response.is_auth_prompt());
+        let blocks = response.blocks.as_ref().unwrap();
+        assert_eq!(
+            blocks[1].buttons()[0].url.as_deref(),
+            Some("https://bot.example.com/auth/google?user_id=U012AB3CD")
+        );
+        assert!(state.db.get_oauth_token(user.id).await.unwrap().is_none());
+
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].event_type, "google_disconnected");
+        assert_eq!(audit[0].detail["reason"], "undecryptable_token");
+        assert_eq!(audit[0].actor_slack_id.as_deref(), Some("U012AB3CD"));
+    }
+
+    #[tokio::test]
+    async fn test_meeting_is_created_and_stored() {
+        let google = Arc::new(FakeGoogleApi::succeeding());
+        let (state, _pool) = test_state_with(google.clone(), Config::for_tests()).await;
+        let user = connected_user(&state).await;
+        let payload = payload("", RESPONSE_URL);
+
+        let response = create_meeting(&state, &payload, &user, &request(Some("Standup"), None))
+            .await
+            .unwrap()
+            .into_response();
+
+        assert_eq!(response.response_type, "in_channel");
+        assert!(
+            response.text.contains(FAKE_MEETING_URI),
+            "{}",
+            response.text
+        );
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        assert_eq!(meetings.len(), 1);
+        assert_eq!(meetings[0].meet_link, FAKE_MEETING_URI);
+        assert_eq!(google.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_team_title_template_names_the_meeting() {
+        let (state, _pool) = test_state().await;
+        state
+            .db
+            .set_team_setting(
+                "T012AB3C4",
+                title_template::SETTING_KEY,
+                "[#{channel}] {text} — {date}",
+            )
+            .await
+            .unwrap();
+        let user = connected_user(&state).await;
+        let starts_at = "2030-06-03T09:00:00Z".parse().unwrap();
+
+        let response = create_meeting(
+            &state,
+            &payload("Standup", RESPONSE_URL),
+            &user,
+            &request(Some("Standup"), Some(starts_at)),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        assert_eq!(
+            meetings[0].title.as_deref(),
+            Some("[#general] Standup — 2030-06-03")
+        );
+        let blocks = serde_json::to_string(&response.blocks).unwrap();
+        assert!(
+            blocks.contains("*[#general] Standup — 2030-06-03*"),
+            "{}",
+            blocks
+        );
+
+        // Without a typed title the template still names it
+        let mut payload = payload("", RESPONSE_URL);
+        payload.trigger_id = "4.5.6".to_string();
+        create_meeting(&state, &payload, &user, &request(None, Some(starts_at)))
+            .await
+            .unwrap()
+            .into_response();
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        assert!(meetings
+            .iter()
+            .any(|meeting| meeting.title.as_deref() == Some("[#general] — 2030-06-03")));
+    }
+
+    #[tokio::test]
+    async fn test_untitled_meetings_get_a_default_title() {
+        let (state, _pool) = test_state().await;
+        let user = connected_user(&state).await;
+        let starts_at = Some("2030-06-03T09:00:00Z".parse().unwrap());
+        let create = |channel: &str, trigger: &str| {
+            let mut payload = payload("", RESPONSE_URL);
+            payload.channel_name = channel.to_string();
+            payload.trigger_id = trigger.to_string();
+            let (state, user) = (state.clone(), user.clone());
+            async move {
+                create_meeting(&state, &payload, &user, &request(None, starts_at))
+                    .await
+                    .unwrap()
+                    .into_response();
+                let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+                let newest = meetings.into_iter().max_by_key(|m| m.id).unwrap();
+                newest.title.unwrap()
+            }
+        };
+
+        assert_eq!(create("general", "1").await, "#general sync — Jun 3");
+        assert_eq!(create("directmessage", "2").await, "Meeting — Jun 3");
+        assert_eq!(create("privategroup", "3").await, "Meeting — Jun 3");
+
+        state
+            .db
+            .set_team_setting(
+                "T012AB3C4",
+                title_template::DEFAULT_TITLE_MODE_KEY,
+                "generic",
+            )
+            .await
+            .unwrap();
+        assert_eq!(create("general", "4").await, "Meeting — Jun 3");
+    }
+
+    #[tokio::test]
+    async fn test_title_dates_are_the_creators_own() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        let user = connected_user(&state).await;
+        // Late on June 3rd in UTC, already June 4th in Tokyo
+        let starts_at = Some("2030-06-03T22:00:00Z".parse().unwrap());
+        let mut payload = payload("", RESPONSE_URL);
+
+        payload.trigger_id = "1".to_string();
+        create_meeting(&state, &payload, &user, &request(None, starts_at))
+            .await
+            .unwrap()
+            .into_response();
+        slack.set_timezone("U012AB3CD", "Asia/Tokyo");
+        payload.trigger_id = "2".to_string();
+        create_meeting(&state, &payload, &user, &request(None, starts_at))
+            .await
+            .unwrap()
+            .into_response();
+        state
+            .db
+            .set_team_setting("T012AB3C4", title_template::SETTING_KEY, "{text} {date}")
+            .await
+            .unwrap();
+        payload.trigger_id = "3".to_string();
+        create_meeting(&state, &payload, &user, &request(Some("Retro"), starts_at))
+            .await
+            .unwrap()
+            .into_response();
+
+        let mut titles: Vec<String> = state
+            .db
+            .get_user_meetings(user.id, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter_map(|meeting| meeting.title)
+            .collect();
+        titles.sort();
+        assert_eq!(
+            titles,
+            [
+                "#general sync — Jun 3",
+                "#general sync — Jun 4",
+                "Retro 2030-06-04"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reminder_lead_is_the_creators_own() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        let user = connected_user(&state).await;
+        state
+            .db
+            .set_user_setting(user.id, reminders::SETTING_KEY, "30")
+            .await
+            .unwrap();
+        let starts_at = (Utc::now() + chrono::Duration::hours(2))
+            .with_nanosecond(0)
+            .unwrap();
+
+        let response = create_meeting(
+            &state,
+            &payload("Standup", RESPONSE_URL),
+            &user,
+            &request(Some("Standup"), Some(starts_at)),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert!(
+            response
+                .text
+                .ends_with("I'll post a reminder here 30 minutes before."),
+            "{}",
+            response.text
+        );
+        assert_eq!(
+            slack.scheduled()[0].post_at,
+            starts_at - chrono::Duration::minutes(30)
+        );
+
+        // Turned off, there's none however the deployment is set up
+        state
+            .db
+            .set_user_setting(user.id, reminders::SETTING_KEY, "0")
+            .await
+            .unwrap();
+        create_meeting(
+            &state,
+            &payload("Standup", RESPONSE_URL),
+            &user,
+            &request(Some("Standup"), Some(starts_at)),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(slack.scheduled().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_meeting_gets_a_reminder() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        let user = connected_user(&state).await;
+        let starts_at = (Utc::now() + chrono::Duration::hours(2))
+            .with_nanosecond(0)
+            .unwrap();
+
+        let response = create_meeting(
+            &state,
+            &payload("Standup", RESPONSE_URL),
+            &user,
+            &request(Some("Standup"), Some(starts_at)),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert_eq!(response.response_type, "in_channel");
+        assert!(
+            response
+                .text
+                .contains(&format!("<!date^{}^", starts_at.timestamp()))
+                && response
+                    .text
+                    .ends_with("I'll post a reminder here 10 minutes before."),
+            "{}",
+            response.text
+        );
+        let scheduled = slack.scheduled();
+        assert_eq!(scheduled.len(), 1);
+        assert_eq!(scheduled[0].message.channel, "C012AB3CD");
+        assert_eq!(
+            scheduled[0].post_at,
+            starts_at - chrono::Duration::minutes(10)
+        );
+        let meeting = &state.db.get_user_meetings(user.id, 1).await.unwrap()[0];
+        let reminder = state
+            .db
+            .meeting_reminder(meeting.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reminder.message_id, scheduled[0].id);
+
+        // Without a bot token for the workspace the meeting goes ahead alone
+        let (state, _pool) = test_state().await;
+        let user = connected_user(&state).await;
+        let response = create_meeting(
+            &state,
+            &payload("Standup", RESPONSE_URL),
+            &user,
+            &request(None, Some(starts_at)),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        assert_eq!(response.response_type, "in_channel");
+        assert!(!response.text.contains("reminder"), "{}", response.text);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_meeting_shows_its_start_across_timezones() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        slack.set_timezone("U012AB3CD", "Europe/Warsaw");
+        slack.set_timezone("U0000A001", "Europe/Berlin");
+        slack.set_timezone("U0000B0B1", "Asia/Kolkata");
+        let user = connected_user(&state).await;
+        let starts_at = state.clock.now() + chrono::Duration::hours(2);
+        let scheduled = |user_ids: &[&str]| MeetingRequest {
+            attendees: user_ids
+                .iter()
+                .map(|user_id| Attendee::SlackUser(user_id.to_string()))
+                .chain([Attendee::Email("bob@example.com".to_string())])
+                .collect(),
+            ..request(Some("Planning"), Some(starts_at))
+        };
+        let announce = |request: MeetingRequest, trigger_id: &str| {
+            let state = state.clone();
+            let user = user.clone();
+            let mut payload = payload("Planning", RESPONSE_URL);
+            payload.trigger_id = trigger_id.to_string();
+            async move {
+                let response = create_meeting(&state, &payload, &user, &request)
+                    .await
+                    .unwrap()
+                    .into_response();
+                serde_json::to_string(&response.blocks).unwrap()
+            }
+        };
+
+        let blocks = announce(scheduled(&["U0000A001", "U0000B0B1"]), "1").await;
+        let expected = timezones::start_times(
+            starts_at,
+            &[
+                "Europe/Warsaw".parse().unwrap(),
+                "Asia/Kolkata".parse().unwrap(),
+            ],
+        )
+        .unwrap();
+        assert!(
+            blocks.contains(&format!("\"🌍 {}\"", expected)),
+            "{}",
+            blocks
+        );
+
+        // Everyone it mentions shares the creator's time
+        let blocks = announce(scheduled(&["U0000A001"]), "2").await;
+        assert!(!blocks.contains('🌍'), "{}", blocks);
+    }
+
+    async fn create(state: &AppState) -> SlackResponse {
+        create_with_trigger(state, "1.2.3").await
+    }
+
+    /// `/meet Standup` as a separate submission, with its own trigger id.
+    async fn create_with_trigger(state: &AppState, trigger_id: &str) -> SlackResponse {
+        let mut payload = payload("Standup", RESPONSE_URL);
+        payload.trigger_id = trigger_id.to_string();
+        state
+            .commands
+            .dispatch(
+                state.clone(),
+                payload,
+                command_parser::parse("Standup").unwrap(),
+                false,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shared_account_creates_members_meetings() {
+        let (state, _pool) = test_state().await;
+        let owner = connected_user(&state).await;
+        shared_account::enable(&state.db, "T012AB3C4", "U012AB3CD", "U0ADMIN01")
+            .await
+            .unwrap();
+        let member = state
+            .db
+            .create_user("U098ZY7XW", "T012AB3C4")
+            .await
+            .unwrap();
+        let mut member_payload = payload("Standup", RESPONSE_URL);
+        member_payload.user_id = "U098ZY7XW".to_string();
+        member_payload.user_name = "bob".to_string();
+        member_payload.trigger_id = "4.5.6".to_string();
+
+        let response = create_meeting(
+            &state,
+            &member_payload,
+            &member,
+            &request(Some("Standup"), None),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert_eq!(response.response_type, "in_channel");
+        assert!(response.text.contains("<@bob>"), "{}", response.text);
+        let meetings = state.db.get_user_meetings(member.id, 10).await.unwrap();
+        assert_eq!(meetings.len(), 1);
+        assert_eq!(meetings[0].created_with_account_user_id, Some(owner.id));
+
+        // The owner's own meetings are theirs alone
+        create_meeting(
+            &state,
+            &payload("Standup", RESPONSE_URL),
+            &owner,
+            &MeetingRequest::default(),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let meetings = state.db.get_user_meetings(owner.id, 10).await.unwrap();
+        assert_eq!(meetings.len(), 1);
+        assert_eq!(meetings[0].created_with_account_user_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_shared_account_is_reported() {
+        let google = Arc::new(FakeGoogleApi::failing(|| GoogleApiError::Unauthorized));
+        let TestState { state, slack, .. } =
+            Fixture::new().google(google).installed().build().await;
+        connected_user(&state).await;
+        shared_account::enable(&state.db, "T012AB3C4", "U012AB3CD", "U0ADMIN01")
+            .await
+            .unwrap();
+        let member = state
+            .db
+            .create_user("U098ZY7XW", "T012AB3C4")
+            .await
+            .unwrap();
+
+        let response = create_meeting(
+            &state,
+            &payload("", RESPONSE_URL),
+            &member,
+            &MeetingRequest::default(),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert_eq!(response.text, SHARED_ACCOUNT_REJECTED);
+        assert!(!response.is_auth_prompt());
+        assert_eq!(slack.posted()[0].channel, "U0ADMIN01");
+    }
+
+    #[tokio::test]
+    async fn test_unconnected_shared_account_falls_back_to_the_users_own() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        state
+            .db
+            .create_user("U0SHARED1", "T012AB3C4")
+            .await
+            .unwrap();
+        shared_account::enable(&state.db, "T012AB3C4", "U0SHARED1", "U0ADMIN01")
+            .await
+            .unwrap();
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+
+        let response = create_meeting(
+            &state,
+            &payload("", RESPONSE_URL),
+            &user,
+            &MeetingRequest::default(),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert!(response.is_auth_prompt());
+        let posted = slack.posted();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].channel, "U0ADMIN01");
+        assert!(
+            posted[0].text.contains("isn't connected to Google"),
+            "{}",
+            posted[0].text
+        );
+
+        // Once connected, the user's own account does it, and the admin
+        // isn't told again
+        state
+            .db
+            .store_oauth_token(&OAuthToken::new(
+                user.id,
+                "ya29.access".into(),
+                Some("1//refresh".into()),
+                Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+                Some("https://www.googleapis.com/auth/meetings.space.created".into()),
+            ))
+            .await
+            .unwrap();
+        let mut payload = payload("", RESPONSE_URL);
+        payload.trigger_id = "4.5.6".to_string();
+        let response = create_meeting(&state, &payload, &user, &MeetingRequest::default())
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.response_type, "in_channel");
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        assert_eq!(meetings[0].created_with_account_user_id, None);
+        assert_eq!(slack.posted().len(), 1);
+    }
+
+    /// Runs `/meet <text>` with the trigger id `trigger_id`.
+    async fn run(state: &AppState, text: &str, trigger_id: &str) -> SlackResponse {
+        let mut payload = payload(text, RESPONSE_URL);
+        payload.trigger_id = trigger_id.to_string();
+        state
+            .commands
+            .dispatch(
+                state.clone(),
+                payload,
+                command_parser::parse(text).unwrap(),
+                false,
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_quiet_meetings_are_shown_to_their_creator() {
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        let (state, _pool) = test_state_with(Arc::new(FakeGoogleApi::succeeding()), config).await;
+        let user = connected_user(&state).await;
+        let visibility = |title: &'static str| {
+            let state = state.clone();
+            let user_id = user.id;
+            async move {
+                let meetings = state.db.get_user_meetings(user_id, 10).await.unwrap();
+                let meeting = meetings
+                    .into_iter()
+                    .find(|meeting| meeting.title.as_deref() == Some(title))
+                    .unwrap();
+                meeting.visibility
+            }
+        };
+
+        let loud = run(&state, "Standup", "1").await;
+        assert_eq!(loud.response_type, "in_channel");
+        assert_eq!(visibility("Standup").await, MeetingVisibility::Channel);
+
+        let quiet = run(&state, "--quiet 1:1 with Bob", "2").await;
+        assert_eq!(quiet.response_type, "ephemeral");
+        assert!(quiet.text.contains(FAKE_MEETING_URI), "{}", quiet.text);
+        assert_eq!(visibility("1:1 with Bob").await, MeetingVisibility::Quiet);
+
+        // The preference makes every meeting quiet
+        state
+            .db
+            .set_user_setting(user.id, MeetingVisibility::SETTING_KEY, "quiet")
+            .await
+            .unwrap();
+        let response = run(&state, "Planning", "3").await;
+        assert_eq!(response.response_type, "ephemeral");
+        assert_eq!(visibility("Planning").await, MeetingVisibility::Quiet);
+
+        let response = run(&state, "--loud Retro", "4").await;
+        assert!(response.text.starts_with("❌ That option isn't supported"));
+    }
+
+    #[tokio::test]
+    async fn test_qr_code_is_posted_after_the_announcement() {
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        let TestState { state, slack, .. } =
+            Fixture::new().config(config).installed().build().await;
+        connected_user(&state).await;
+
+        let response = run(&state, "--qr Room 4 standup", "1").await;
+        assert_eq!(response.response_type, "in_channel");
+        for _ in 0..100 {
+            if !slack.images().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let images = slack.images();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].channel_id, "C012AB3CD");
+        assert_eq!(images[0].bytes, qr::png(FAKE_MEETING_URI).unwrap());
+
+        // Without the flag there is none
+        run(&state, "Room 4 retro", "2").await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(slack.images().len(), 1);
+    }
+
+    /// Google creating each space at a new link, and a user with their
+    /// standing room on.
+    async fn standing_room_state() -> (AppState, Arc<FakeGoogleApi>, User) {
+        const CODES: [&str; 3] = ["abc-defg-hij", "klm-nopq-rst", "uvw-xyza-bcd"];
+        let created = std::sync::atomic::AtomicUsize::new(0);
+        let google = Arc::new(FakeGoogleApi::responding(move || {
+            let n = created.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(CreatedMeeting {
+                name: format!("spaces/{}", n),
+                meeting_uri: format!("https://meet.google.com/{}", CODES[n]),
+            })
+        }));
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        let (state, _pool) = test_state_with(google.clone(), config).await;
+        let user = connected_user(&state).await;
+        state
+            .db
+            .set_user_setting(user.id, personal_space::SETTING_KEY, "true")
+            .await
+            .unwrap();
+        (state, google, user)
+    }
+
+    /// The link of the meeting `title` of `user`.
+    async fn link_of(state: &AppState, user: &User, title: &str) -> String {
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        meetings
+            .into_iter()
+            .find(|meeting| meeting.title.as_deref() == Some(title))
+            .unwrap()
+            .meet_link
+    }
+
+    #[tokio::test]
+    async fn test_standing_room_is_created_on_first_use() {
+        let (state, google, user) = standing_room_state().await;
+
+        let response = run(&state, "Standup", "1").await;
+
+        assert_eq!(response.response_type, "in_channel");
+        assert_eq!(google.calls(), 1);
+        assert_eq!(
+            link_of(&state, &user, "Standup").await,
+            "https://meet.google.com/abc-defg-hij"
+        );
+        assert_eq!(
+            state.db.personal_space(user.id).await.unwrap(),
+            Some(PersonalSpace {
+                meet_link: "https://meet.google.com/abc-defg-hij".to_string(),
+                space_name: "spaces/0".to_string(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_standing_room_is_reused() {
+        let (state, google, user) = standing_room_state().await;
+        run(&state, "Standup", "1").await;
+
+        run(&state, "Retro", "2").await;
+        assert_eq!(google.calls(), 1);
+        assert_eq!(
+            link_of(&state, &user, "Retro").await,
+            "https://meet.google.com/abc-defg-hij"
+        );
+
+        // `new`, and asking for a recording the room wasn't made with, get
+        // a space of their own and leave the room as it is
+        run(&state, "new Planning", "3").await;
+        run(&state, "--record Demo", "4").await;
+        assert_eq!(google.calls(), 3);
+        assert_eq!(
+            link_of(&state, &user, "Planning").await,
+            "https://meet.google.com/klm-nopq-rst"
+        );
+        assert_eq!(
+            link_of(&state, &user, "Demo").await,
+            "https://meet.google.com/uvw-xyza-bcd"
+        );
+        let space = state.db.personal_space(user.id).await.unwrap().unwrap();
+        assert_eq!(space.space_name, "spaces/0");
+    }
+
+    #[tokio::test]
+    async fn test_deleted_standing_room_is_recreated() {
+        let (state, google, user) = standing_room_state().await;
+        run(&state, "Standup", "1").await;
+        google.delete_space("spaces/0");
+
+        run(&state, "Retro", "2").await;
+
+        assert_eq!(google.calls(), 2);
+        assert_eq!(
+            link_of(&state, &user, "Retro").await,
+            "https://meet.google.com/klm-nopq-rst"
+        );
+        let space = state.db.personal_space(user.id).await.unwrap().unwrap();
+        assert_eq!(space.space_name, "spaces/1");
+        // And the new one is kept from then on
+        run(&state, "Planning", "3").await;
+        assert_eq!(google.calls(), 2);
+    }
+
+    #[test]
+    fn test_standing_room_notes_name_the_command() {
+        assert!(Standing::Created
+            .note("/meet-dev")
+            .contains("`/meet-dev new` makes a one-off meeting"));
+        assert!(Standing::Recreated
+            .note("/meet")
+            .starts_with("🏠 Your standing room was deleted at Google"));
+    }
+
+    #[tokio::test]
+    async fn test_recording_is_requested_and_announced() {
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        let google = Arc::new(FakeGoogleApi::succeeding());
+        let (state, _pool) = test_state_with(google.clone(), config).await;
+        let user = connected_user(&state).await;
+
+        let response = run(&state, "Standup", "1").await;
+        assert_eq!(
+            google.last_request().unwrap().artifacts,
+            Artifacts::default()
+        );
+        assert!(
+            !response.text.contains("automatically"),
+            "{}",
+            response.text
+        );
+
+        let response = run(&state, "--record Planning", "2").await;
+        assert_eq!(
+            google.last_request().unwrap().artifacts,
+            Artifacts {
+                recording: true,
+                transcription: false,
+            }
+        );
+        assert!(
+            response
+                .text
+                .contains("🔴 This meeting is recorded automatically."),
+            "{}",
+            response.text
+        );
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        let planning = meetings
+            .iter()
+            .find(|meeting| meeting.title.as_deref() == Some("Planning"))
+            .unwrap();
+        assert!(planning.auto_recording);
+        assert!(!planning.auto_transcription);
+
+        // The workspace's default adds to what the command asks for
+        state
+            .db
+            .set_team_setting("T012AB3C4", recording::TRANSCRIBE_KEY, "true")
+            .await
+            .unwrap();
+        let response = run(&state, "--record Retro", "3").await;
+        assert_eq!(
+            google.last_request().unwrap().artifacts,
+            Artifacts {
+                recording: true,
+                transcription: true,
+            }
+        );
+        assert!(
+            response
+                .text
+                .contains("🔴 This meeting is recorded and transcribed automatically."),
+            "{}",
+            response.text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refused_recording_is_explained() {
+        let google = Arc::new(FakeGoogleApi::failing(|| {
+            GoogleApiError::ArtifactsUnavailable
+        }));
+        let (state, _pool) = test_state_with(google, Config::for_tests()).await;
+        connected_user(&state).await;
+
+        let response = run(&state, "--transcribe Standup", "1").await;
+
+        assert_eq!(response.response_type, "ephemeral");
+        assert!(response.text.contains("`--record`"), "{}", response.text);
+    }
+
+    #[tokio::test]
+    async fn test_google_refusing_an_account_is_not_asked_every_time() {
+        let refusals = std::sync::atomic::AtomicUsize::new(3);
+        let google = Arc::new(FakeGoogleApi::responding(move || {
+            let left = refusals.load(std::sync::atomic::Ordering::SeqCst);
+            if left > 0 {
+                refusals.store(left - 1, std::sync::atomic::Ordering::SeqCst);
+                return Err(GoogleApiError::Api {
+                    status: 403,
+                    message: r#"{"error": {"code": 403, "status": "PERMISSION_DENIED"}}"#
+                        .to_string(),
+                });
+            }
+            Ok(CreatedMeeting {
+                name: "spaces/abc".to_string(),
+                meeting_uri: FAKE_MEETING_URI.to_string(),
+            })
+        }));
+        let (mut state, _pool) = test_state_with(google.clone(), Config::for_tests()).await;
+        let clock = TestClock::new(Utc::now());
+        state.clock = clock.clone();
+        let user = connected_user(&state).await;
+        let create = |trigger: &str| {
+            let mut payload = payload("Standup", RESPONSE_URL);
+            payload.trigger_id = trigger.to_string();
+            let (state, user) = (state.clone(), user.clone());
+            async move {
+                create_meeting(&state, &payload, &user, &MeetingRequest::default())
+                    .await
+                    .unwrap()
+                    .into_response()
+            }
+        };
+
+        for trigger in ["1", "2", "3"] {
+            let response = create(trigger).await;
+            assert_eq!(response.text, CREATION_FAILED, "{}", response.text);
+        }
+        let response = create("4").await;
+        assert_eq!(google.calls(), 3);
+        assert_eq!(response.response_type, "ephemeral");
+        assert!(
+            response.text.starts_with("🚫 Google keeps refusing")
+                && response.text.contains("in 10 minutes"),
+            "{}",
+            response.text
+        );
+
+        clock.advance(chrono::Duration::minutes(10));
+        let response = create("5").await;
+        assert_eq!(google.calls(), 4);
+        assert!(
+            response.text.contains(FAKE_MEETING_URI),
+            "{}",
+            response.text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_meeting_end_is_stored_with_a_duration() {
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        let (mut state, _pool) =
+            test_state_with(Arc::new(FakeGoogleApi::succeeding()), config).await;
+        let now = Utc::now().with_nanosecond(0).unwrap();
+        state.clock = TestClock::new(now);
+        let user = connected_user(&state).await;
+
+        run(&state, "Standup 45m", "1").await;
+        run(&state, "Planning tomorrow 9:00 1h30m", "2").await;
+        run(&state, "Retro", "3").await;
+
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        let ends_at = |title: &str| {
+            meetings
+                .iter()
+                .find(|meeting| meeting.title.as_deref() == Some(title))
+                .unwrap()
+                .ends_at
+        };
+        let tomorrow = now.date_naive() + chrono::Days::new(1);
+        assert_eq!(
+            ends_at("Standup"),
+            Some((now + chrono::Duration::minutes(45)).naive_utc())
+        );
+        assert_eq!(ends_at("Planning"), tomorrow.and_hms_opt(10, 30, 0));
+        assert_eq!(ends_at("Retro"), None);
+    }
+
+    #[tokio::test]
+    async fn test_start_is_read_where_the_creator_is() {
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        let TestState {
+            mut state, slack, ..
+        } = Fixture::new().config(config).installed().build().await;
+        state.clock = TestClock::new("2030-06-01T12:00:00Z".parse().unwrap());
+        state
+            .db
+            .set_team_setting("T012AB3C4", quota::TIMEZONE_KEY, "America/New_York")
+            .await
+            .unwrap();
+        let user = connected_user(&state).await;
+
+        // Without a Slack timezone, in the workspace's
+        run(&state, "Retro 2030-06-03 09:00 1h", "1").await;
+        // Their own wins over it
+        slack.set_timezone("U012AB3CD", "Asia/Tokyo");
+        run(&state, "Planning 2030-06-03 09:00 1h", "2").await;
+
+        let meetings = state.db.get_user_meetings(user.id, 10).await.unwrap();
+        let ends_at = |title: &str| {
+            meetings
+                .iter()
+                .find(|meeting| meeting.title.as_deref() == Some(title))
+                .unwrap()
+                .ends_at
+        };
+        let utc = |time: &str| Some(time.parse::<DateTime<Utc>>().unwrap().naive_utc());
+        assert_eq!(ends_at("Retro"), utc("2030-06-03T14:00:00Z"));
+        assert_eq!(ends_at("Planning"), utc("2030-06-03T01:00:00Z"));
+    }
+
+    #[tokio::test]
+    async fn test_daily_limit_refuses_meetings_past_it() {
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        config.admin.slack_users = vec!["U012AB3CD".to_string()];
+        let (state, _pool) = test_state_with(Arc::new(FakeGoogleApi::succeeding()), config).await;
+        let user = connected_user(&state).await;
+
+        // Without a limit there is none
+        for trigger_id in ["1", "2", "3"] {
+            assert_eq!(
+                run(&state, "Standup", trigger_id).await.response_type,
+                "in_channel"
+            );
+        }
+
+        state
+            .db
+            .set_team_setting("T012AB3C4", quota::LIMIT_KEY, "3")
+            .await
+            .unwrap();
+        let mut over = payload("Standup", RESPONSE_URL);
+        over.trigger_id = "4".to_string();
+        let result = state
+            .commands
+            .dispatch(
+                state.clone(),
+                over,
+                command_parser::parse("Standup").unwrap(),
+                false,
+            )
+            .await;
+        assert!(
+            matches!(result, Err(AppError::RateLimited(RateLimit::Team))),
+            "{:?}",
+            result
+        );
+        assert_eq!(
+            state.db.get_user_meetings(user.id, 10).await.unwrap().len(),
+            3
+        );
+
+        state
+            .db
+            .set_team_setting("T012AB3C4", quota::LIMIT_KEY, "3 exempt-admins")
+            .await
+            .unwrap();
+        assert_eq!(
+            run(&state, "Standup", "5").await.response_type,
+            "in_channel"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_quiet_scheduled_meeting_gets_no_reminder() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        let user = connected_user(&state).await;
+        let request = MeetingRequest {
+            visibility: MeetingVisibility::Quiet,
+            ..request(
+                Some("1:1"),
+                Some(state.clock.now() + chrono::Duration::hours(2)),
+            )
+        };
+
+        let response = create_meeting(&state, &payload("", RESPONSE_URL), &user, &request)
+            .await
+            .unwrap()
+            .into_response();
+
+        assert_eq!(response.response_type, "ephemeral");
+        assert!(!response.text.contains("reminder"), "{}", response.text);
+        assert!(slack.scheduled().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retried_command_returns_the_first_meeting() {
+        let google = Arc::new(FakeGoogleApi::succeeding());
+        let (state, _pool) = test_state_with(google.clone(), Config::for_tests()).await;
+        let user = connected_user(&state).await;
+
+        let first = create(&state).await;
+        let retry = create(&state).await;
+
+        assert_eq!(retry.response_type, "in_channel");
+        assert_eq!(retry.text, first.text);
+        assert_eq!(google.calls(), 1);
+        assert_eq!(
+            state.db.get_user_meetings(user.id, 10).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_simultaneous_identical_commands_create_one_meeting() {
+        // Slow enough that both get past the dedup check before either stores
+        let google = Arc::new(FakeGoogleApi::succeeding().with_delay(Duration::from_millis(50)));
+        let (state, _pool) = test_state_with(google.clone(), Config::for_tests()).await;
+        let user = connected_user(&state).await;
+
+        let (first, second) = tokio::join!(create(&state), create(&state));
+
+        // The second waits for the first and gets its meeting
+        assert_eq!(google.calls(), 1);
+        for response in [first, second] {
+            assert_eq!(response.response_type, "in_channel");
+            assert!(
+                response.text.contains(FAKE_MEETING_URI),
+                "{}",
+                response.text
+            );
+        }
+        assert_eq!(
+            state.db.get_user_meetings(user.id, 10).await.unwrap().len(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_double_submit_creates_one_meeting() {
+        let google = Arc::new(FakeGoogleApi::succeeding().with_delay(Duration::from_millis(50)));
+        let (state, _pool) = test_state_with(google.clone(), Config::for_tests()).await;
+        let user = connected_user(&state).await;
+
+        let (first, second) = tokio::join!(
+            create_with_trigger(&state, "1.1"),
+            create_with_trigger(&state, "1.2")
+        );
+
+        let (rejected, created): (Vec<_>, Vec<_>) = [first, second]
+            .into_iter()
+            .partition(|response| response.text == ALREADY_CREATING);
+        assert_eq!(rejected.len(), 1);
+        assert!(
+            created[0].text.contains(FAKE_MEETING_URI),
+            "{}",
+            created[0].text
+        );
+        assert_eq!(google.calls(), 1);
+        assert_eq!(
+            state.db.get_user_meetings(user.id, 10).await.unwrap().len(),
+            1
+        );
+
+        // Within the cooldown, even with the first one done
+        let third = create_with_trigger(&state, "1.3").await;
+        assert_eq!(third.text, ALREADY_CREATING);
+    }
+
+    #[tokio::test]
+    async fn test_failed_creation_lets_the_user_try_again() {
+        let google = Arc::new(FakeGoogleApi::failing(|| GoogleApiError::QuotaExceeded));
+        let mut config = Config::for_tests();
+        config.rate_limit.create_cooldown = Duration::ZERO;
+        let (state, _pool) = test_state_with(google.clone(), config).await;
+        connected_user(&state).await;
+
+        let first = create_with_trigger(&state, "1.1").await;
+        let second = create_with_trigger(&state, "1.2").await;
+
+        assert!(first.text.starts_with('❌'), "{}", first.text);
+        assert_eq!(second.text, first.text, "the guard was released");
+        assert_eq!(google.calls(), 2);
+    }
+
+    /// Google answering after the database went away.
+    struct DatabaseLostDuringCall {
+        pool: sqlx::SqlitePool,
+    }
+
+    #[async_trait]
+    impl GoogleApi for DatabaseLostDuringCall {
+        async fn create_meeting(
+            &self,
+            access_token: &SecretString,
+            options: &MeetingOptions,
+        ) -> Result<CreatedMeeting, GoogleApiError> {
+            self.pool.close().await;
+            FakeGoogleApi::succeeding()
+                .create_meeting(access_token, options)
+                .await
+        }
+
+        async fn get_space(
+            &self,
+            access_token: &SecretString,
+            space_name: &str,
+        ) -> Result<Option<CreatedMeeting>, GoogleApiError> {
+            FakeGoogleApi::succeeding()
+                .get_space(access_token, space_name)
+                .await
+        }
+
+        async fn add_space_member(
+            &self,
+            access_token: &SecretString,
+            space_name: &str,
+            email: &str,
+        ) -> Result<(), GoogleApiError> {
+            FakeGoogleApi::succeeding()
+                .add_space_member(access_token, space_name, email)
+                .await
+        }
+
+        async fn end_active_conference(
+            &self,
+            access_token: &SecretString,
+            space_name: &str,
+        ) -> Result<bool, GoogleApiError> {
+            FakeGoogleApi::succeeding()
+                .end_active_conference(access_token, space_name)
+                .await
+        }
+
+        async fn list_conference_records(
+            &self,
+            access_token: &SecretString,
+            space_name: &str,
+        ) -> Result<Vec<ConferenceRecord>, GoogleApiError> {
+            FakeGoogleApi::succeeding()
+                .list_conference_records(access_token, space_name)
+                .await
+        }
+
+        async fn list_participants(
+            &self,
+            access_token: &SecretString,
+            conference_record: &str,
+        ) -> Result<Vec<Participant>, GoogleApiError> {
+            FakeGoogleApi::succeeding()
+                .list_participants(access_token, conference_record)
+                .await
+        }
+
+        async fn list_recordings(
+            &self,
+            access_token: &SecretString,
+            conference_record: &str,
+        ) -> Result<Vec<ConferenceArtifact>, GoogleApiError> {
+            FakeGoogleApi::succeeding()
+                .list_recordings(access_token, conference_record)
+                .await
+        }
+
+        async fn list_transcripts(
+            &self,
+            access_token: &SecretString,
+            conference_record: &str,
+        ) -> Result<Vec<ConferenceArtifact>, GoogleApiError> {
+            FakeGoogleApi::succeeding()
+                .list_transcripts(access_token, conference_record)
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_link_is_shared_when_storing_the_meeting_fails() {
+        let (mut state, pool) = test_state().await;
+        let user = connected_user(&state).await;
+        state.google = Arc::new(DatabaseLostDuringCall { pool });
+
+        let response = create_meeting(
+            &state,
+            &payload("", RESPONSE_URL),
+            &user,
+            &request(Some("Standup"), None),
+        )
+        .await
+        .unwrap()
+        .into_response();
+
+        assert_eq!(response.response_type, "in_channel");
+        assert!(
+            response.text.contains(FAKE_MEETING_URI),
+            "{}",
+            response.text
+        );
+        assert!(state.db.check_writes().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_quota_errors_ask_the_user_to_wait() {
+        let (state, _pool) = test_state_with(
+            Arc::new(FakeGoogleApi::failing(|| GoogleApiError::QuotaExceeded)),
+            Config::for_tests(),
+        )
+        .await;
+        let user = connected_user(&state).await;
+        let payload = payload("", RESPONSE_URL);
+
+        let response = create_meeting(&state, &payload, &user, &MeetingRequest::default())
+            .await
+            .unwrap()
+            .into_response();
+
+        assert!(
+            response.text.contains("try again in a few minutes"),
+            "{}",
+            response.text
+        );
+        assert!(state
+            .db
+            .get_user_meetings(user.id, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_token_prompts_to_reconnect() {
+        let (state, _pool) = test_state_with(
+            Arc::new(FakeGoogleApi::failing(|| GoogleApiError::Unauthorized)),
+            Config::for_tests(),
+        )
+        .await;
+        let user = connected_user(&state).await;
+        let payload = payload("", RESPONSE_URL);
+
+        let response = create_meeting(&state, &payload, &user, &MeetingRequest::default())
+            .await
+            .unwrap()
+            .into_response();
+
+        assert!(response.is_auth_prompt());
+    }
+
+    #[tokio::test]
+    async fn test_slow_google_is_acknowledged_and_followed_up() {
+        use axum::{extract::State, routing::post, Router};
+        use tokio::sync::mpsc;
+
+        let (sender, mut followups) = mpsc::unbounded_channel();
+        let hooks = Router::new()
+            .route(
+                "/commands/1/2",
+                post(
+                    |State(sender): State<mpsc::UnboundedSender<Value>>,
+                     Json(body): Json<Value>| async move {
+                        sender.send(body).unwrap();
+                    },
+                ),
+            )
+            .with_state(sender);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let response_url = format!("http://{}/commands/1/2", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, hooks).await.unwrap() });
+
+        let mut config = Config::for_tests();
+        config.slack.ack_deadline = Duration::from_millis(20);
+        let (state, _pool) = test_state_with(
+            Arc::new(FakeGoogleApi::succeeding().with_delay(Duration::from_millis(300))),
+            config,
+        )
+        .await;
+        let user = connected_user(&state).await;
+        let payload = payload("", &response_url);
+
+        let response = state
+            .commands
+            .dispatch(
+                state.clone(),
+                payload,
+                command_parser::parse("").unwrap(),
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(response.text.starts_with('⏳'), "{}", response.text);
+        assert_eq!(response.response_type, "ephemeral");
+
+        let followup = tokio::time::timeout(Duration::from_secs(5), followups.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(followup["response_type"], "in_channel");
+        assert!(followup["text"]
+            .as_str()
+            .unwrap()
+            .contains(FAKE_MEETING_URI));
+        assert_eq!(
+            state.db.get_user_meetings(user.id, 10).await.unwrap().len(),
+            1
+        );
+    }
+}
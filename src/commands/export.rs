@@ -0,0 +1,143 @@
+//! `/meet export-my-data`: a copy of the user's data, sent to them as a
+//! file or, without a bot token, as a download link.
+
+use axum::async_trait;
+use tracing::{info, warn};
+
+use super::{CommandContext, CommandHandler};
+use crate::audit::{self, AuditEvent};
+use crate::database::models::User;
+use crate::error::AppError;
+use crate::export;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::slack::api::FileUpload;
+use crate::AppState;
+
+pub struct ExportDataHandler;
+
+#[async_trait]
+impl CommandHandler for ExportDataHandler {
+    fn name(&self) -> &'static str {
+        "export-my-data"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        handle_export(ctx.state, ctx.payload, user).await
+    }
+}
+
+/// Sends the export to the user's DMs when the workspace installed the bot,
+/// falling back to a download link when it didn't or the upload fails.
+async fn handle_export(
+    state: AppState,
+    payload: SlashCommandPayload,
+    user: User,
+) -> Result<SlackResponse, AppError> {
+    let now = state.clock.now();
+    let team = state
+        .db
+        .get_slack_team(&payload.team_id)
+        .await
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to look up the bot token of team {}: {:#}",
+                payload.team_id, e
+            );
+            None
+        });
+
+    if let Some(team) = team {
+        let export = export::gather(&state.db, &user, now).await?;
+        let file = FileUpload {
+            channels: user.slack_user_id.clone(),
+            filename: export::filename(&user.slack_user_id),
+            filetype: "json".to_string(),
+            title: "Your Google Meet bot data".to_string(),
+            initial_comment: "📦 Here is everything this bot keeps about you.".to_string(),
+            content: export.to_json(),
+        };
+        match state.slack.upload_file(&team.bot_token, &file).await {
+            Ok(()) => {
+                info!("Sent data export to user {}", user.id);
+                audit::record(&state.db, AuditEvent::data_exported(&user, "dm")).await;
+                return Ok(SlackResponse::ephemeral(
+                    "📦 I've sent you your data in a direct message.".to_string(),
+                ));
+            }
+            Err(e) => warn!(
+                "Failed to send data export to user {}, falling back to a link: {}",
+                user.id, e
+            ),
+        }
+    }
+
+    let token = export::sign_link_token(
+        &state.config.slack.signing_secret,
+        &user.slack_user_id,
+        now + export::LINK_TTL,
+    );
+    info!("Issued data export link to user {}", user.id);
+    audit::record(&state.db, AuditEvent::data_exported(&user, "link")).await;
+    Ok(SlackResponse::ephemeral(format!(
+        "📦 <{}/export/{}|Download your data>. The link works for {} minutes; don't share it.",
+        state.config.google.public_base_url(),
+        token,
+        export::LINK_TTL.num_minutes()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{connected_user, payload, Fixture, TestState, RESPONSE_URL};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_export_is_sent_in_a_dm() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        let user = connected_user(&state).await;
+
+        let response = handle_export(state.clone(), payload("export-my-data", RESPONSE_URL), user)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            response.text,
+            "📦 I've sent you your data in a direct message."
+        );
+        let uploaded = slack.uploaded();
+        assert_eq!(uploaded.len(), 1);
+        assert_eq!(uploaded[0].channels, "U012AB3CD");
+        assert!(uploaded[0]
+            .content
+            .contains("\"slack_user_id\": \"U012AB3CD\""));
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit[0].event_type, "data_exported");
+        assert_eq!(audit[0].detail, serde_json::json!({ "delivery": "dm" }));
+    }
+
+    #[tokio::test]
+    async fn test_export_falls_back_to_a_link() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        slack.fail_channel("U012AB3CD", "not_allowed_token_type");
+        let user = connected_user(&state).await;
+
+        let response = handle_export(state.clone(), payload("export-my-data", RESPONSE_URL), user)
+            .await
+            .unwrap();
+
+        let prefix = format!(
+            "📦 <{}/export/U012AB3CD.",
+            state.config.google.public_base_url()
+        );
+        assert!(response.text.starts_with(&prefix), "{}", response.text);
+        assert!(
+            response.text.contains("works for 15 minutes"),
+            "{}",
+            response.text
+        );
+        assert!(slack.uploaded().is_empty());
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit[0].detail, serde_json::json!({ "delivery": "link" }));
+    }
+}
@@ -0,0 +1,60 @@
+//! `/meet help`: usage for every subcommand.
+
+use axum::async_trait;
+
+use super::{CommandContext, CommandHandler};
+use crate::error::AppError;
+use crate::handlers::slack::SlackResponse;
+
+pub struct HelpHandler;
+
+#[async_trait]
+impl CommandHandler for HelpHandler {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn needs_user(&self) -> bool {
+        false
+    }
+
+    async fn handle(&self, ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        Ok(SlackResponse::ephemeral(help_text(&ctx.payload.command)))
+    }
+}
+
+fn help_text(command: &str) -> String {
+    format!(
+        "*Usage*\n\
+         • `{0} [title]` — create a Google Meet and share it in the channel\n\
+         • `{0} --quiet [title]` — create a Google Meet only you can see\n\
+         • `{0} [title] --cohost @someone` — make someone a co-host who can admit people\n\
+         • `{0} --record [title]` / `{0} --transcribe [title]` — have Meet record or transcribe the meeting\n\
+         • `{0} --qr [title]` — also post a QR code of the link, for joining from a phone\n\
+         • `{0} new [title]` — create a new meeting even if you use a standing room\n\
+         • `{0} list [n]` — show your recent meetings\n\
+         • `{0} join [--share]` — show the latest meeting of this channel, or post it with `--share`\n\
+         • `{0} attendance [link]` — see who joined your latest meeting, or the one at a Meet link\n\
+         • `{0} notes [link] [--share]` — get the recordings and transcripts of your latest meeting\n\
+         • `{0} share-link [link] [--off]` — get a short link to your latest meeting\n\
+         • `{0} poll [\"title\"] <time>, <time>, ...` — let the channel vote on when to meet\n\
+         • `{0} stats` — see how many meetings you've created\n\
+         • `{0} status` — check whether your Google account is connected\n\
+         • `{0} logout` — disconnect your Google account\n\
+         • `{0} export-my-data` — get a copy of what the bot keeps about you\n\
+         • `{0} help` — show this message",
+        command
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_help_uses_the_invoked_command_name() {
+        let text = help_text("/meet-dev");
+        assert!(text.contains("`/meet-dev list [n]`"), "{}", text);
+        assert!(!text.contains("`/meet "), "{}", text);
+    }
+}
@@ -0,0 +1,182 @@
+//! One meeting creation per user at a time. A double-tapped Enter sends two
+//! `/meet` commands a moment apart, with different trigger ids and well
+//! within the rate limit; the second is turned away while the first runs
+//! and for a short cooldown after it started. Slack's retries of the
+//! running command wait for it instead, and get its meeting.
+
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// The latest meeting creation of a user.
+struct Creation {
+    trigger_id: String,
+    started_at: Instant,
+    running: bool,
+    /// Closed once the creation finished.
+    finished: watch::Receiver<()>,
+}
+
+pub struct InFlightCommands {
+    cooldown: Duration,
+    users: DashMap<String, Creation>,
+}
+
+/// Whether a meeting creation may go ahead.
+pub enum Claim {
+    /// It may; the user is blocked until the guard is dropped and the
+    /// cooldown has passed.
+    Claimed(InFlightGuard),
+    /// Slack retrying the command that is running. It waits for the
+    /// creation to finish, then gets its meeting through the trigger's dedup
+    /// entry, or claims again if none was made.
+    Retry(RunningCreation),
+    /// Another command of the user is creating a meeting, or just did.
+    Busy,
+}
+
+impl InFlightCommands {
+    pub fn new(cooldown: Duration) -> Self {
+        Self {
+            cooldown,
+            users: DashMap::new(),
+        }
+    }
+
+    /// Claims `slack_user_id` for creating the meeting of `trigger_id`. A
+    /// trigger whose creation finished may claim again straight away, as it
+    /// made no meeting if it is asked for one again.
+    pub fn claim(self: &Arc<Self>, slack_user_id: &str, trigger_id: &str) -> Claim {
+        let (finished, finished_rx) = watch::channel(());
+        let creation = Creation {
+            trigger_id: trigger_id.to_string(),
+            started_at: Instant::now(),
+            running: true,
+            finished: finished_rx,
+        };
+        match self.users.entry(slack_user_id.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let last = entry.get();
+                let same_trigger = last.trigger_id == trigger_id;
+                if same_trigger && last.running {
+                    return Claim::Retry(RunningCreation(last.finished.clone()));
+                }
+                if !same_trigger && (last.running || last.started_at.elapsed() < self.cooldown) {
+                    return Claim::Busy;
+                }
+                entry.insert(creation);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(creation);
+            }
+        }
+        Claim::Claimed(InFlightGuard {
+            commands: self.clone(),
+            slack_user_id: slack_user_id.to_string(),
+            _finished: finished,
+        })
+    }
+
+    /// Forgets users whose last creation finished and cooled down.
+    pub fn prune(&self) {
+        self.users.retain(|_, creation| {
+            creation.running || creation.started_at.elapsed() < self.cooldown
+        });
+    }
+}
+
+/// A creation another command of the same trigger is running.
+pub struct RunningCreation(watch::Receiver<()>);
+
+impl RunningCreation {
+    /// Waits until the creation finished, however it ended.
+    pub async fn finished(mut self) {
+        // Nothing is ever sent; the guard closes the channel when dropped
+        while self.0.changed().await.is_ok() {}
+    }
+}
+
+/// Marks the user's creation finished when dropped, however it ended.
+pub struct InFlightGuard {
+    commands: Arc<InFlightCommands>,
+    slack_user_id: String,
+    _finished: watch::Sender<()>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if let Some(mut creation) = self.commands.users.get_mut(&self.slack_user_id) {
+            creation.running = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claimed(claim: Claim) -> InFlightGuard {
+        match claim {
+            Claim::Claimed(guard) => guard,
+            _ => panic!("expected the claim to succeed"),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_user_is_blocked_while_running_and_during_the_cooldown() {
+        let commands = Arc::new(InFlightCommands::new(Duration::from_secs(2)));
+        let guard = claimed(commands.claim("U012AB3CD", "1.1"));
+
+        assert!(matches!(commands.claim("U012AB3CD", "1.2"), Claim::Busy));
+        assert!(matches!(
+            commands.claim("U012AB3CD", "1.1"),
+            Claim::Retry(_)
+        ));
+        claimed(commands.claim("U098ZY7XW", "2.1"));
+
+        // Still running after the cooldown
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert!(matches!(commands.claim("U012AB3CD", "1.2"), Claim::Busy));
+
+        drop(guard);
+        claimed(commands.claim("U012AB3CD", "1.2"));
+        assert!(matches!(commands.claim("U012AB3CD", "1.3"), Claim::Busy));
+        tokio::time::advance(Duration::from_secs(2)).await;
+        claimed(commands.claim("U012AB3CD", "1.3"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_waits_for_the_running_creation() {
+        let commands = Arc::new(InFlightCommands::new(Duration::from_secs(2)));
+        let guard = claimed(commands.claim("U012AB3CD", "1.1"));
+        let Claim::Retry(running) = commands.claim("U012AB3CD", "1.1") else {
+            panic!("expected a retry");
+        };
+
+        let waiting = tokio::spawn(running.finished());
+        tokio::task::yield_now().await;
+        assert!(!waiting.is_finished());
+        drop(guard);
+        waiting.await.unwrap();
+
+        // It made no meeting if the trigger asks again, so it may try again
+        claimed(commands.claim("U012AB3CD", "1.1"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_prune_keeps_running_and_recent_creations() {
+        let commands = Arc::new(InFlightCommands::new(Duration::from_secs(2)));
+        let _running = claimed(commands.claim("U012AB3CD", "1.1"));
+        drop(claimed(commands.claim("U098ZY7XW", "2.1")));
+        tokio::time::advance(Duration::from_secs(3)).await;
+        drop(claimed(commands.claim("U0AAAAAAAA", "3.1")));
+
+        commands.prune();
+
+        assert_eq!(commands.users.len(), 2);
+        assert!(!commands.users.contains_key("U098ZY7XW"));
+    }
+}
@@ -0,0 +1,152 @@
+//! `/meet join [--share]`: the latest meeting created in the channel, by
+//! anyone, for channels where the link keeps getting asked for.
+
+use axum::async_trait;
+
+use super::{CommandContext, CommandHandler};
+use crate::command_parser::MeetCommand;
+use crate::error::AppError;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::observability::{self, Phase};
+use crate::AppState;
+
+pub struct JoinHandler;
+
+#[async_trait]
+impl CommandHandler for JoinHandler {
+    fn name(&self) -> &'static str {
+        "join"
+    }
+
+    fn needs_user(&self) -> bool {
+        false
+    }
+
+    async fn handle(&self, ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let share = matches!(ctx.command, MeetCommand::Join { share: true });
+        handle_join(ctx.state, ctx.payload, share).await
+    }
+}
+
+async fn handle_join(
+    state: AppState,
+    payload: SlashCommandPayload,
+    share: bool,
+) -> Result<SlackResponse, AppError> {
+    let meeting = observability::timed(
+        Phase::Database,
+        state.db.get_latest_channel_meeting(&payload.channel_id),
+    )
+    .await?;
+
+    Ok(match meeting {
+        Some(meeting) => SlackResponse::channel_meeting(
+            &meeting,
+            state.clock.now(),
+            share.then_some(payload.user_id.as_str()),
+        ),
+        None => SlackResponse::ephemeral(format!(
+            "There are no meetings in this channel yet. Run `{}` to start one.",
+            payload.command
+        )),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, test_state, RESPONSE_URL};
+    use super::*;
+    use crate::database::models::{MeetLinkKind, Meeting, MeetingVisibility};
+
+    async fn create(
+        state: &AppState,
+        slack_user_id: &str,
+        channel_id: &str,
+        code: &str,
+    ) -> Meeting {
+        let user = state
+            .db
+            .create_user(slack_user_id, "T012AB3C4")
+            .await
+            .unwrap();
+        state
+            .db
+            .create_meeting(
+                &Meeting::new(
+                    user.id,
+                    format!("https://meet.google.com/{}", code),
+                    Some("Incident bridge".to_string()),
+                    MeetLinkKind::Meet,
+                )
+                .with_channel(channel_id),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_finds_meetings_created_by_others() {
+        let (state, _pool) = test_state().await;
+        create(&state, "U012AB3CD", "C012AB3CD", "aaa-bbbb-ccc").await;
+        create(&state, "U098ZY7XW", "C012AB3CD", "ddd-eeee-fff").await;
+
+        let response = handle_join(state, payload("join", RESPONSE_URL), false)
+            .await
+            .unwrap();
+
+        assert_eq!(response.response_type, "ephemeral");
+        assert!(
+            response
+                .text
+                .starts_with("🔗 This channel's latest meeting: <https://meet.google.com/ddd-eeee-fff|Incident bridge>"),
+            "{}",
+            response.text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_looks_at_the_invoking_channel() {
+        let (state, _pool) = test_state().await;
+        create(&state, "U012AB3CD", "C012AB3CD", "aaa-bbbb-ccc").await;
+        create(&state, "U098ZY7XW", "C0OTHER01", "ddd-eeee-fff").await;
+
+        let response = handle_join(state.clone(), payload("join --share", RESPONSE_URL), true)
+            .await
+            .unwrap();
+        assert_eq!(response.response_type, "in_channel");
+        assert!(
+            response.text.contains("aaa-bbbb-ccc") && response.text.contains("<@U012AB3CD> shared"),
+            "{}",
+            response.text
+        );
+
+        let mut elsewhere = payload("join", RESPONSE_URL);
+        elsewhere.channel_id = "C0EMPTY01".to_string();
+        let response = handle_join(state, elsewhere, false).await.unwrap();
+        assert_eq!(
+            response.text,
+            "There are no meetings in this channel yet. Run `/meet` to start one."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skips_quiet_meetings() {
+        let (state, _pool) = test_state().await;
+        create(&state, "U012AB3CD", "C012AB3CD", "aaa-bbbb-ccc").await;
+        let quiet = Meeting::new(
+            1,
+            "https://meet.google.com/qqq-qqqq-qqq".to_string(),
+            None,
+            MeetLinkKind::Meet,
+        )
+        .with_channel("C012AB3CD")
+        .with_visibility(MeetingVisibility::Quiet);
+        state.db.create_meeting(&quiet).await.unwrap();
+
+        let response = handle_join(state, payload("join", RESPONSE_URL), false)
+            .await
+            .unwrap();
+
+        assert!(response.text.contains("aaa-bbbb-ccc"), "{}", response.text);
+    }
+}
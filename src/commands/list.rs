@@ -0,0 +1,78 @@
+//! `/meet list [n]`: the user's recent meetings.
+
+use axum::async_trait;
+
+use super::{CommandContext, CommandHandler};
+use crate::command_parser::MeetCommand;
+use crate::database::models::User;
+use crate::error::AppError;
+use crate::handlers::slack::SlackResponse;
+use crate::observability::{self, Phase};
+use crate::AppState;
+
+const DEFAULT_LIST_LIMIT: u32 = 5;
+const MAX_LIST_LIMIT: u32 = 20;
+
+pub struct ListMeetingsHandler;
+
+#[async_trait]
+impl CommandHandler for ListMeetingsHandler {
+    fn name(&self) -> &'static str {
+        "list"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let limit = match ctx.command {
+            MeetCommand::List { limit } => limit,
+            _ => None,
+        };
+        handle_list_meetings(ctx.state, user, limit).await
+    }
+}
+
+async fn handle_list_meetings(
+    state: AppState,
+    user: User,
+    limit: Option<u32>,
+) -> Result<SlackResponse, AppError> {
+    let limit = limit.unwrap_or(DEFAULT_LIST_LIMIT).min(MAX_LIST_LIMIT);
+
+    let meetings = observability::timed(
+        Phase::Database,
+        state.db.get_user_meetings(user.id, limit as i64),
+    )
+    .await?;
+
+    Ok(SlackResponse::meeting_list(&meetings, state.clock.now()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{connected_user, test_state};
+    use super::*;
+    use crate::database::models::{MeetLinkKind, Meeting};
+
+    #[tokio::test]
+    async fn test_lists_newest_meetings_up_to_the_limit() {
+        let (state, _pool) = test_state().await;
+        let user = connected_user(&state).await;
+        for code in ["aaa-bbbb-ccc", "ddd-eeee-fff"] {
+            state
+                .db
+                .create_meeting(&Meeting::new(
+                    user.id,
+                    format!("https://meet.google.com/{}", code),
+                    Some("Standup".to_string()),
+                    MeetLinkKind::Meet,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let response = handle_list_meetings(state, user, Some(1)).await.unwrap();
+
+        assert!(response.text.starts_with("Your recent meetings:"));
+        assert_eq!(response.text.lines().count(), 2, "{}", response.text);
+    }
+}
@@ -0,0 +1,687 @@
+//! The `/meet` subcommands. Each is a [`CommandHandler`] in the
+//! [`CommandRegistry`] built at startup, which the slash command endpoint
+//! dispatches to once the request is verified and parsed.
+
+use axum::async_trait;
+use std::collections::HashMap;
+use tracing::info;
+
+use crate::channel_policy;
+use crate::command_parser::MeetCommand;
+use crate::database::models::User;
+use crate::error::AppError;
+use crate::features::Feature;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::observability::{self, Phase};
+use crate::AppState;
+
+mod account;
+mod admin;
+mod attendance;
+mod cancel;
+mod cohosts;
+mod create;
+mod export;
+mod help;
+mod in_flight;
+mod join;
+mod list;
+mod notes;
+mod outside_hours;
+mod poll;
+mod queue;
+mod settings;
+mod share_link;
+mod stats;
+
+pub use account::{LogoutHandler, StatusHandler};
+pub use admin::AdminHandler;
+pub use attendance::AttendanceHandler;
+pub use cancel::{CancelHandler, EndHandler};
+pub(crate) use create::team_artifacts;
+pub use create::{
+    create_detached_meeting, prune_request_dedup, CreateMeetingHandler, MeetingRequest,
+};
+pub use export::ExportDataHandler;
+pub use help::HelpHandler;
+pub use in_flight::{Claim, InFlightCommands, InFlightGuard};
+pub use join::JoinHandler;
+pub use list::ListMeetingsHandler;
+pub use notes::NotesHandler;
+pub use poll::PollHandler;
+pub use queue::{CreateMeetingJob, MeetingQueue, QUEUE_FULL};
+pub use settings::SettingsHandler;
+pub use share_link::ShareLinkHandler;
+pub use stats::StatsHandler;
+
+/// Everything a handler gets to work with.
+pub struct CommandContext {
+    pub state: AppState,
+    pub payload: SlashCommandPayload,
+    pub command: MeetCommand,
+    /// The Slack user running the command, registered on first use. Only
+    /// looked up for handlers that [need it](CommandHandler::needs_user).
+    pub user: Option<User>,
+    /// Whether sanitizing the command text removed anything.
+    pub text_was_modified: bool,
+}
+
+impl CommandContext {
+    /// Takes the user out of the context, for handlers that need one.
+    pub fn take_user(&mut self) -> Result<User, AppError> {
+        self.user.take().ok_or_else(|| {
+            AppError::Internal(anyhow::anyhow!(
+                "{} ran without a user",
+                self.command.name()
+            ))
+        })
+    }
+}
+
+#[async_trait]
+pub trait CommandHandler: Send + Sync {
+    /// The subcommand handled, as given by [`MeetCommand::name`].
+    fn name(&self) -> &'static str;
+
+    /// Whether the user has to be looked up, and registered if new, before
+    /// the command runs.
+    fn needs_user(&self) -> bool {
+        true
+    }
+
+    /// The flag the subcommand is gated behind, if it is experimental.
+    fn feature(&self) -> Option<Feature> {
+        None
+    }
+
+    /// Whether the subcommand creates meetings, and so is refused in the
+    /// channels the workspace's [channel policy](channel_policy) rules out.
+    fn follows_channel_policy(&self) -> bool {
+        false
+    }
+
+    async fn handle(&self, ctx: CommandContext) -> Result<SlackResponse, AppError>;
+}
+
+/// Reply to a gated subcommand whose flag is off for the workspace.
+pub const FEATURE_NOT_ENABLED: &str = "🧪 This feature isn't enabled for your workspace.";
+
+/// Handlers by subcommand name.
+#[derive(Default)]
+pub struct CommandRegistry {
+    handlers: HashMap<&'static str, Box<dyn CommandHandler>>,
+}
+
+impl CommandRegistry {
+    /// A registry with every built-in subcommand.
+    pub fn new() -> Self {
+        let mut registry = Self::default();
+        registry.register(CreateMeetingHandler);
+        registry.register(ListMeetingsHandler);
+        registry.register(CancelHandler);
+        registry.register(EndHandler);
+        registry.register(JoinHandler);
+        registry.register(AttendanceHandler);
+        registry.register(NotesHandler);
+        registry.register(ShareLinkHandler);
+        registry.register(PollHandler);
+        registry.register(StatusHandler);
+        registry.register(StatsHandler);
+        registry.register(LogoutHandler);
+        registry.register(ExportDataHandler);
+        registry.register(HelpHandler);
+        registry.register(AdminHandler);
+        registry.register(SettingsHandler);
+        registry
+    }
+
+    /// Adds `handler`, replacing any handler of the same name.
+    pub fn register(&mut self, handler: impl CommandHandler + 'static) {
+        self.handlers.insert(handler.name(), Box::new(handler));
+    }
+
+    /// Runs the handler for `command`, after checking its feature flag and
+    /// the channel policy and resolving the user if the handler needs one. Subcommands without a
+    /// handler are answered with a note that they aren't available yet.
+    pub async fn dispatch(
+        &self,
+        state: AppState,
+        payload: SlashCommandPayload,
+        command: MeetCommand,
+        text_was_modified: bool,
+    ) -> Result<SlackResponse, AppError> {
+        let Some(handler) = self.handlers.get(command.name()) else {
+            info!("No handler for /meet {}", command.name());
+            return Ok(SlackResponse::ephemeral(
+                "🚧 That command isn't available yet.".to_string(),
+            ));
+        };
+
+        if let Some(feature) = handler.feature() {
+            let enabled = observability::timed(
+                Phase::Database,
+                state
+                    .config
+                    .features
+                    .is_enabled(&state.db, feature, &payload.team_id),
+            )
+            .await?;
+            if !enabled {
+                info!(
+                    "/meet {} is off for team {}",
+                    command.name(),
+                    payload.team_id
+                );
+                return Ok(SlackResponse::ephemeral(FEATURE_NOT_ENABLED.to_string()));
+            }
+        }
+
+        if handler.follows_channel_policy() {
+            let policy = observability::timed(
+                Phase::Database,
+                channel_policy::for_team(&state.db, &payload.team_id),
+            )
+            .await?;
+            if let Err(refusal) = policy.check(&payload.channel_id) {
+                info!(
+                    "/meet {} refused in channel {} of team {} ({:?})",
+                    command.name(),
+                    payload.channel_id,
+                    payload.team_id,
+                    refusal
+                );
+                return Ok(SlackResponse::ephemeral(policy.refusal_message(refusal)));
+            }
+        }
+
+        let user = if handler.needs_user() {
+            Some(resolve_user(&state, &payload).await?)
+        } else {
+            None
+        };
+
+        handler
+            .handle(CommandContext {
+                state,
+                payload,
+                command,
+                user,
+                text_was_modified,
+            })
+            .await
+    }
+}
+
+/// Finds the Slack user, registering them on their first command.
+async fn resolve_user(state: &AppState, payload: &SlashCommandPayload) -> Result<User, AppError> {
+    let existing = observability::timed(
+        Phase::Database,
+        state.db.get_user_by_slack_id(&payload.user_id),
+    )
+    .await?;
+    match existing {
+        Some(user) => Ok(user),
+        None => Ok(observability::timed(
+            Phase::Database,
+            state.db.create_user(&payload.user_id, &payload.team_id),
+        )
+        .await?),
+    }
+}
+
+/// Where a user goes to connect their Google account.
+fn auth_url(state: &AppState, slack_user_id: &str) -> String {
+    format!(
+        "{}/auth/google?user_id={}",
+        state.config.google.public_base_url(),
+        slack_user_id
+    )
+}
+
+/// State and payloads for the handler tests.
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::sync::Arc;
+
+    use super::{CommandRegistry, InFlightCommands, MeetingQueue};
+    use crate::background::JobRegistry;
+    use crate::config::Config;
+    use crate::database::models::{MeetLinkKind, Meeting, OAuthToken, SlackTeam, User};
+    use crate::database::test_db;
+    use crate::google::fake::FakeGoogleApi;
+    use crate::google_failures::GoogleFailures;
+    use crate::handlers::slack::SlashCommandPayload;
+    use crate::observability::ErrorRateMonitor;
+    use crate::rate_limiter::RateLimiter;
+    use crate::slack::fake::FakeSlackApi;
+    use crate::telemetry::{logging::LogFilter, metrics};
+    use crate::time::SystemClock;
+    use crate::validation::InputValidator;
+    use crate::AppState;
+
+    pub const RESPONSE_URL: &str = "https://hooks.slack.com/commands/1/2";
+
+    pub const MEETING_LINK: &str = "https://meet.google.com/abc-defg-hij";
+
+    pub async fn test_state() -> (AppState, sqlx::SqlitePool) {
+        Fixture::new().build().await.into_parts()
+    }
+
+    pub async fn test_state_with(
+        google: Arc<FakeGoogleApi>,
+        config: Config,
+    ) -> (AppState, sqlx::SqlitePool) {
+        Fixture::new()
+            .google(google)
+            .config(config)
+            .build()
+            .await
+            .into_parts()
+    }
+
+    /// The state a test starts from. Unless told otherwise, Google
+    /// succeeds, the config is [`Config::for_tests`], the bot isn't
+    /// installed and nobody is registered.
+    #[derive(Default)]
+    pub struct Fixture {
+        google: Option<Arc<FakeGoogleApi>>,
+        slack: Option<Arc<FakeSlackApi>>,
+        config: Option<Config>,
+        installed: bool,
+        scopes: Option<String>,
+        space: Option<String>,
+    }
+
+    /// What a [`Fixture`] built.
+    pub struct TestState {
+        pub state: AppState,
+        pub pool: sqlx::SqlitePool,
+        pub slack: Arc<FakeSlackApi>,
+        /// U012AB3CD, when the fixture connected them.
+        pub user: Option<User>,
+        /// Their meeting, when the fixture gave them one.
+        pub meeting: Option<Meeting>,
+    }
+
+    impl TestState {
+        pub fn into_parts(self) -> (AppState, sqlx::SqlitePool) {
+            (self.state, self.pool)
+        }
+
+        /// The state and the user the fixture connected.
+        pub fn into_state_and_user(self) -> (AppState, User) {
+            let user = self.user.expect("the fixture connected nobody");
+            (self.state, user)
+        }
+    }
+
+    impl Fixture {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        pub fn google(mut self, google: Arc<FakeGoogleApi>) -> Self {
+            self.google = Some(google);
+            self
+        }
+
+        /// Slack as the test set it up, such as with users' timezones.
+        pub fn slack(mut self, slack: Arc<FakeSlackApi>) -> Self {
+            self.slack = Some(slack);
+            self
+        }
+
+        pub fn config(mut self, config: Config) -> Self {
+            self.config = Some(config);
+            self
+        }
+
+        /// Stores T012AB3C4's bot token, as installing the app does.
+        pub fn installed(mut self) -> Self {
+            self.installed = true;
+            self
+        }
+
+        /// Registers U012AB3CD with a fresh token granting `scopes`.
+        pub fn connected(mut self, scopes: &str) -> Self {
+            self.scopes = Some(scopes.to_string());
+            self
+        }
+
+        /// Gives U012AB3CD a meeting in `space`, titled Standup and
+        /// announced in C012AB3CD; they are connected as for creating
+        /// meetings unless [`Fixture::connected`] says otherwise.
+        pub fn meeting_in(mut self, space: &str) -> Self {
+            self.space = Some(space.to_string());
+            self
+        }
+
+        pub async fn build(self) -> TestState {
+            let (db, pool) = test_db().await;
+            let config = self.config.unwrap_or_else(Config::for_tests);
+            let slack = self.slack.unwrap_or_default();
+
+            let state = AppState {
+                db,
+                rate_limiter: RateLimiter::new(),
+                google_failures: GoogleFailures::new(&config.rate_limit),
+                validator: Arc::new(InputValidator::default()),
+                in_flight: Arc::new(InFlightCommands::new(config.rate_limit.create_cooldown)),
+                meeting_queue: MeetingQueue::new(config.queue, reqwest::Client::new()),
+                clock: SystemClock::shared(),
+                config: Arc::new(config),
+                google: self
+                    .google
+                    .unwrap_or_else(|| Arc::new(FakeGoogleApi::succeeding())),
+                slack: slack.clone(),
+                http: reqwest::Client::new(),
+                commands: Arc::new(CommandRegistry::new()),
+                error_rate: Arc::new(ErrorRateMonitor::new(&Default::default())),
+                jobs: JobRegistry::new(),
+                metrics: metrics::install(),
+                log_filter: LogFilter::new("info").unwrap().1,
+            };
+            state.meeting_queue.start(state.clone());
+
+            if self.installed {
+                state
+                    .db
+                    .store_slack_team(&SlackTeam::new("T012AB3C4".to_string(), "xoxb-bot".into()))
+                    .await
+                    .unwrap();
+            }
+            let user = match (self.scopes, &self.space) {
+                (Some(scopes), _) => Some(connected_with(&state, &scopes).await),
+                (None, Some(_)) => Some(connected_user(&state).await),
+                (None, None) => None,
+            };
+            let meeting = match (&self.space, &user) {
+                (Some(space), Some(user)) => Some(
+                    state
+                        .db
+                        .create_meeting(
+                            &Meeting::new(
+                                user.id,
+                                MEETING_LINK.to_string(),
+                                Some("Standup".to_string()),
+                                MeetLinkKind::Meet,
+                            )
+                            .with_team("T012AB3C4")
+                            .with_channel("C012AB3CD")
+                            .with_space(space),
+                        )
+                        .await
+                        .unwrap(),
+                ),
+                _ => None,
+            };
+
+            TestState {
+                state,
+                pool,
+                slack,
+                user,
+                meeting,
+            }
+        }
+    }
+
+    /// The payload of `/meet <text>` from U012AB3CD, answering to
+    /// `response_url`.
+    pub fn payload(text: &str, response_url: &str) -> SlashCommandPayload {
+        let body = format!(
+            "token=x&team_id=T012AB3C4&team_domain=acme&channel_id=C012AB3CD&channel_name=general&user_id=U012AB3CD&user_name=alice&command=%2Fmeet&{}&trigger_id=1.2.3",
+            serde_urlencoded::to_string([("text", text), ("response_url", response_url)]).unwrap()
+        );
+        serde_urlencoded::from_str(&body).unwrap()
+    }
+
+    /// Registers U012AB3CD with a fresh token for creating meetings.
+    pub async fn connected_user(state: &AppState) -> User {
+        connected_with(
+            state,
+            "https://www.googleapis.com/auth/meetings.space.created",
+        )
+        .await
+    }
+
+    async fn connected_with(state: &AppState, scopes: &str) -> User {
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+        state
+            .db
+            .store_oauth_token(&OAuthToken::new(
+                user.id,
+                "ya29.access".into(),
+                Some("1//refresh".into()),
+                Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+                Some(scopes.to_string()),
+            ))
+            .await
+            .unwrap();
+        user
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::{payload, test_state, RESPONSE_URL};
+    use super::*;
+    use crate::command_parser;
+    use crate::features::FeatureFlags;
+    use std::sync::Arc;
+
+    struct Echo;
+
+    #[async_trait]
+    impl CommandHandler for Echo {
+        fn name(&self) -> &'static str {
+            "status"
+        }
+
+        fn needs_user(&self) -> bool {
+            false
+        }
+
+        async fn handle(&self, ctx: CommandContext) -> Result<SlackResponse, AppError> {
+            Ok(SlackResponse::ephemeral(format!(
+                "{} without user: {}",
+                ctx.command.name(),
+                ctx.user.is_none()
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_handler_replaces_builtin() {
+        let (state, _pool) = test_state().await;
+        let mut registry = CommandRegistry::new();
+        registry.register(Echo);
+
+        let response = registry
+            .dispatch(
+                state.clone(),
+                payload("status", RESPONSE_URL),
+                MeetCommand::Status,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "status without user: true");
+        // Nothing needed the user, so nobody was registered
+        assert!(state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_is_registered_for_handlers_needing_one() {
+        let (state, _pool) = test_state().await;
+
+        let response = CommandRegistry::new()
+            .dispatch(
+                state.clone(),
+                payload("list", RESPONSE_URL),
+                MeetCommand::List { limit: None },
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.text, "You haven't created any meetings yet.");
+        assert!(state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    /// A `cancel` handler behind its flag.
+    struct GatedCancel;
+
+    #[async_trait]
+    impl CommandHandler for GatedCancel {
+        fn name(&self) -> &'static str {
+            "cancel"
+        }
+
+        fn feature(&self) -> Option<Feature> {
+            Some(Feature::Cancel)
+        }
+
+        async fn handle(&self, _ctx: CommandContext) -> Result<SlackResponse, AppError> {
+            Ok(SlackResponse::ephemeral("Cancelled".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_gated_subcommands_follow_their_flag() {
+        let (state, _pool) = test_state().await;
+        let mut registry = CommandRegistry::new();
+        registry.register(GatedCancel);
+        let command = command_parser::parse("cancel").unwrap();
+        let dispatch = |state: AppState| {
+            registry.dispatch(
+                state,
+                payload("cancel", RESPONSE_URL),
+                command.clone(),
+                false,
+            )
+        };
+
+        let response = dispatch(state.clone()).await.unwrap();
+        assert_eq!(response.text, FEATURE_NOT_ENABLED);
+        // Denied before the user was needed
+        assert!(state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .is_none());
+
+        FeatureFlags::set_override(&state.db, Feature::Cancel, "T012AB3C4", Some(true))
+            .await
+            .unwrap();
+        assert_eq!(dispatch(state.clone()).await.unwrap().text, "Cancelled");
+
+        // A global flag applies unless the team turned it off
+        let mut config = (*state.config).clone();
+        config.features = FeatureFlags::new([Feature::Cancel]);
+        let state = AppState {
+            config: Arc::new(config),
+            ..state
+        };
+        FeatureFlags::set_override(&state.db, Feature::Cancel, "T012AB3C4", None)
+            .await
+            .unwrap();
+        assert_eq!(dispatch(state.clone()).await.unwrap().text, "Cancelled");
+        FeatureFlags::set_override(&state.db, Feature::Cancel, "T012AB3C4", Some(false))
+            .await
+            .unwrap();
+        assert_eq!(dispatch(state).await.unwrap().text, FEATURE_NOT_ENABLED);
+    }
+
+    #[tokio::test]
+    async fn test_meetings_follow_the_channel_policy() {
+        let (state, _pool) = test_state().await;
+        let registry = CommandRegistry::new();
+        let run = |text: &'static str| {
+            registry.dispatch(
+                state.clone(),
+                payload(text, RESPONSE_URL),
+                command_parser::parse(text).unwrap(),
+                false,
+            )
+        };
+        let blocked = ["C012AB3CD"].iter().map(|id| id.to_string()).collect();
+        channel_policy::save_list(
+            &state.db,
+            "T012AB3C4",
+            channel_policy::BLOCKED_KEY,
+            &blocked,
+        )
+        .await
+        .unwrap();
+
+        let response = run("Standup").await.unwrap();
+        assert_eq!(
+            response.text,
+            "🚫 Your workspace admins don't allow creating meetings in this channel."
+        );
+        // Refused before the user was needed
+        assert!(state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .is_none());
+        // Other subcommands still work there
+        let response = run("list").await.unwrap();
+        assert_eq!(response.text, "You haven't created any meetings yet.");
+
+        let allowed = ["C098ZY7XW"].iter().map(|id| id.to_string()).collect();
+        channel_policy::save_list(
+            &state.db,
+            "T012AB3C4",
+            channel_policy::BLOCKED_KEY,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+        channel_policy::save_list(
+            &state.db,
+            "T012AB3C4",
+            channel_policy::ALLOWED_KEY,
+            &allowed,
+        )
+        .await
+        .unwrap();
+        let response = run("Standup").await.unwrap();
+        assert_eq!(
+            response.text,
+            "🚫 Your workspace admins only allow creating meetings in <#C098ZY7XW>."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unhandled_subcommands_are_not_available_yet() {
+        let (state, _pool) = test_state().await;
+        let command = command_parser::parse("cancel 3").unwrap();
+
+        // Every built-in subcommand has a handler by now
+        let response = CommandRegistry::default()
+            .dispatch(state, payload("cancel 3", RESPONSE_URL), command, false)
+            .await
+            .unwrap();
+
+        assert!(response.text.starts_with('🚧'), "{}", response.text);
+    }
+}
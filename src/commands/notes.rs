@@ -0,0 +1,371 @@
+//! `/meet notes [link] [--share]`: the recordings and transcripts Meet made
+//! of the user's latest meeting, or the one at a Meet link or code.
+
+use axum::async_trait;
+use tracing::info;
+
+use super::attendance::{google_failure, meeting_label, past_meeting, Lookup, PastMeeting};
+use super::{CommandContext, CommandHandler};
+use crate::command_parser::MeetCommand;
+use crate::database::models::User;
+use crate::error::AppError;
+use crate::google::ConferenceArtifact;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::observability::{self, Phase};
+use crate::AppState;
+
+pub struct NotesHandler;
+
+#[async_trait]
+impl CommandHandler for NotesHandler {
+    fn name(&self) -> &'static str {
+        "notes"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let (meeting, share) = match ctx.command {
+            MeetCommand::Notes { meeting, share } => (meeting, share),
+            _ => (None, false),
+        };
+        handle_notes(ctx.state, ctx.payload, user, meeting, share).await
+    }
+}
+
+/// Looks the meeting up, then asks Google for the recordings and
+/// transcripts of its latest call. Meet only starts generating the files
+/// once the call is over, and takes a while to finish them.
+async fn handle_notes(
+    state: AppState,
+    payload: SlashCommandPayload,
+    user: User,
+    target: Option<String>,
+    share: bool,
+) -> Result<SlackResponse, AppError> {
+    let lookup = Lookup::Notes;
+    let PastMeeting {
+        meeting,
+        space_name,
+        token,
+    } = match past_meeting(&state, &payload, &user, target.as_deref(), lookup).await? {
+        Ok(past) => past,
+        Err(reply) => return Ok(reply),
+    };
+
+    let label = meeting_label(&meeting);
+    let records = match observability::timed(
+        Phase::Google,
+        state
+            .google
+            .list_conference_records(&token.access_token, &space_name),
+    )
+    .await
+    {
+        Ok(records) => records,
+        Err(e) => return google_failure(&state, &payload, e, lookup),
+    };
+    let Some(latest) = records.first() else {
+        return Ok(SlackResponse::ephemeral(format!(
+            "📝 Nobody has joined {} yet, so there's nothing recorded.",
+            label
+        )));
+    };
+    let Some(ended_at) = latest.end_time else {
+        return Ok(SlackResponse::ephemeral(format!(
+            "📝 {} is still going on. Recordings and transcripts are ready a while after everyone has left.",
+            label
+        )));
+    };
+
+    let artifacts = tokio::try_join!(
+        observability::timed(
+            Phase::Google,
+            state
+                .google
+                .list_recordings(&token.access_token, &latest.name),
+        ),
+        observability::timed(
+            Phase::Google,
+            state
+                .google
+                .list_transcripts(&token.access_token, &latest.name),
+        ),
+    );
+    let (recordings, transcripts) = match artifacts {
+        Ok(artifacts) => artifacts,
+        Err(e) => return google_failure(&state, &payload, e, lookup),
+    };
+    info!(
+        "Showing {} recordings and {} transcripts of {} to user {}",
+        recordings.len(),
+        transcripts.len(),
+        latest.name,
+        user.id
+    );
+
+    if recordings.is_empty() && transcripts.is_empty() {
+        return Ok(SlackResponse::ephemeral(format!(
+            "📝 {} wasn't recorded or transcribed. Start either in Meet, or create the meeting with `{} --record` or `{} --transcribe`.",
+            label, payload.command, payload.command
+        )));
+    }
+    let ready = recordings
+        .iter()
+        .chain(&transcripts)
+        .any(|artifact| artifact.link.is_some());
+    if !ready {
+        return Ok(SlackResponse::ephemeral(format!(
+            "📝 Google is still processing the {} of {}. Please try again in a few minutes.",
+            what(&recordings, &transcripts),
+            label
+        )));
+    }
+
+    let mut lines = artifact_lines("🎥", "Recording", &recordings);
+    lines.extend(artifact_lines("📄", "Transcript", &transcripts));
+    let call = format!(
+        "the call on {}–{} UTC",
+        latest.start_time.format("%Y-%m-%d %H:%M"),
+        ended_at.format("%H:%M")
+    );
+    if share {
+        return Ok(SlackResponse::in_channel(format!(
+            "📝 <@{}> shared the {} of *{}*, {}:\n{}",
+            payload.user_id,
+            what(&recordings, &transcripts),
+            label,
+            call,
+            lines.join("\n")
+        )));
+    }
+    Ok(SlackResponse::ephemeral(format!(
+        "📝 *{}*, {}:\n{}\n_Run `{} notes {} --share` to post this in the channel._",
+        label,
+        call,
+        lines.join("\n"),
+        payload.command,
+        meeting.meet_link
+    )))
+}
+
+/// What there is, as in "the recording and transcript".
+fn what(recordings: &[ConferenceArtifact], transcripts: &[ConferenceArtifact]) -> String {
+    let kinds: Vec<&str> = [
+        (recordings.len(), "recording", "recordings"),
+        (transcripts.len(), "transcript", "transcripts"),
+    ]
+    .into_iter()
+    .filter_map(|(count, one, many)| match count {
+        0 => None,
+        1 => Some(one),
+        _ => Some(many),
+    })
+    .collect();
+    kinds.join(" and ")
+}
+
+/// One line per artifact, told apart by start time when there are several,
+/// as when recording was stopped and started again.
+fn artifact_lines(emoji: &str, kind: &str, artifacts: &[ConferenceArtifact]) -> Vec<String> {
+    artifacts
+        .iter()
+        .map(|artifact| {
+            let name = if artifacts.len() > 1 {
+                format!("{} from {} UTC", kind, artifact.start_time.format("%H:%M"))
+            } else {
+                kind.to_string()
+            };
+            match &artifact.link {
+                Some(link) => format!("• {} <{}|{}>", emoji, link, name),
+                None => format!("• {} {} (still processing)", emoji, name),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, Fixture, RESPONSE_URL};
+    use super::*;
+    use crate::auth::oauth::REQUESTED_SCOPES;
+    use crate::google::fake::FakeGoogleApi;
+    use crate::google::{ConferenceRecord, GoogleApiError};
+    use std::sync::Arc;
+
+    const SPACE: &str = "spaces/abc";
+    const RECORD: &str = "conferenceRecords/new";
+
+    fn artifact(name: &str, started: &str, link: Option<&str>) -> ConferenceArtifact {
+        ConferenceArtifact {
+            name: name.to_string(),
+            start_time: started.parse().unwrap(),
+            link: link.map(str::to_string),
+        }
+    }
+
+    /// Google with an ended call in [`SPACE`].
+    fn google() -> FakeGoogleApi {
+        FakeGoogleApi::succeeding().with_conference(
+            SPACE,
+            ConferenceRecord {
+                name: RECORD.to_string(),
+                start_time: "2024-03-05T10:00:00Z".parse().unwrap(),
+                end_time: Some("2024-03-05T10:30:00Z".parse().unwrap()),
+            },
+            vec![],
+        )
+    }
+
+    async fn run(state: &AppState, user: &User, share: bool) -> SlackResponse {
+        handle_notes(
+            state.clone(),
+            payload("notes", RESPONSE_URL),
+            user.clone(),
+            None,
+            share,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_lists_the_files_of_the_latest_call() {
+        let google = google()
+            .with_recording(
+                RECORD,
+                artifact(
+                    "conferenceRecords/new/recordings/1",
+                    "2024-03-05T10:00:00Z",
+                    Some("https://drive.google.com/file/d/1/view"),
+                ),
+            )
+            .with_recording(
+                RECORD,
+                artifact(
+                    "conferenceRecords/new/recordings/2",
+                    "2024-03-05T10:20:00Z",
+                    None,
+                ),
+            )
+            .with_transcript(
+                RECORD,
+                artifact(
+                    "conferenceRecords/new/transcripts/1",
+                    "2024-03-05T10:00:00Z",
+                    Some("https://docs.google.com/document/d/1/edit"),
+                ),
+            );
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+
+        let response = run(&state, &user, false).await;
+        assert_eq!(response.response_type, "ephemeral");
+        assert_eq!(
+            response.text,
+            "📝 *<https://meet.google.com/abc-defg-hij|Standup>*, the call on 2024-03-05 10:00–10:30 UTC:\n\
+             • 🎥 <https://drive.google.com/file/d/1/view|Recording from 10:00 UTC>\n\
+             • 🎥 Recording from 10:20 UTC (still processing)\n\
+             • 📄 <https://docs.google.com/document/d/1/edit|Transcript>\n\
+             _Run `/meet notes https://meet.google.com/abc-defg-hij --share` to post this in the channel._"
+        );
+
+        let response = run(&state, &user, true).await;
+        assert_eq!(response.response_type, "in_channel");
+        assert!(
+            response.text.starts_with(
+                "📝 <@U012AB3CD> shared the recordings and transcript of *<https://meet.google.com/abc-defg-hij|Standup>*"
+            ),
+            "{}",
+            response.text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_asks_to_retry_while_files_are_processed() {
+        let google = google().with_transcript(
+            RECORD,
+            artifact(
+                "conferenceRecords/new/transcripts/1",
+                "2024-03-05T10:00:00Z",
+                None,
+            ),
+        );
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+
+        // Nothing to share yet, so it stays with the user
+        let response = run(&state, &user, true).await;
+
+        assert_eq!(response.response_type, "ephemeral");
+        assert_eq!(
+            response.text,
+            "📝 Google is still processing the transcript of <https://meet.google.com/abc-defg-hij|Standup>. Please try again in a few minutes."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_says_when_nothing_was_recorded() {
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google()))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+
+        let response = run(&state, &user, false).await;
+
+        assert!(
+            response.text.starts_with(
+                "📝 <https://meet.google.com/abc-defg-hij|Standup> wasn't recorded or transcribed."
+            ),
+            "{}",
+            response.text
+        );
+        assert!(response.text.contains("`/meet --record`"));
+    }
+
+    #[tokio::test]
+    async fn test_asks_for_the_scope_when_it_was_not_granted() {
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google()))
+            .connected("https://www.googleapis.com/auth/meetings.space.created")
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+        let response = run(&state, &user, false).await;
+        assert!(
+            response.text.starts_with(
+                "📝 Looking up recordings and transcripts needs permission to see your meetings"
+            ),
+            "{}",
+            response.text
+        );
+
+        let google = google().failing_conferences(|| GoogleApiError::MissingScope);
+        let (state, user) = Fixture::new()
+            .google(Arc::new(google))
+            .connected(&REQUESTED_SCOPES.join(" "))
+            .meeting_in(SPACE)
+            .build()
+            .await
+            .into_state_and_user();
+        let response = run(&state, &user, false).await;
+        assert!(
+            response.text.contains("Connect Google again"),
+            "{}",
+            response.text
+        );
+    }
+}
@@ -0,0 +1,142 @@
+//! The check holding back a scheduled meeting that starts outside the
+//! working hours of someone it mentions, until its creator confirms.
+
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+
+use crate::command_parser::Attendee;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::observability::{self, Phase};
+use crate::timezones;
+use crate::working_hours::{self, WorkingHours};
+use crate::AppState;
+
+/// The confirmation to ask for when a meeting at `starts_at` is outside
+/// working hours for any of the Slack users among `attendees`; `None` to
+/// create it right away. Timezones that can't be looked up, as without the
+/// workspace's bot token, are left out of the check rather than holding
+/// the meeting back.
+pub(super) async fn check(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    starts_at: DateTime<Utc>,
+    attendees: &[Attendee],
+) -> Option<SlackResponse> {
+    let mut user_ids: Vec<&str> = Vec::new();
+    for attendee in attendees {
+        if let Attendee::SlackUser(user_id) = attendee {
+            if !user_ids.contains(&user_id.as_str()) {
+                user_ids.push(user_id);
+            }
+        }
+    }
+    if user_ids.is_empty() {
+        return None;
+    }
+
+    let timezones = timezones::of_users(state, &payload.team_id, &user_ids).await;
+
+    let hours = match observability::timed(
+        Phase::Database,
+        working_hours::for_team(&state.db, &payload.team_id),
+    )
+    .await
+    {
+        Ok(hours) => hours,
+        Err(e) => {
+            warn!(
+                "Failed to look up the working hours of team {}: {:#}",
+                payload.team_id, e
+            );
+            WorkingHours::default()
+        }
+    };
+    let outside = working_hours::outside_hours(starts_at, hours, &timezones);
+    if outside.is_empty() {
+        return None;
+    }
+    info!(
+        "Meeting of {} starts outside working hours for {} attendees",
+        payload.user_id,
+        outside.len()
+    );
+    Some(working_hours::confirmation(
+        &payload.command,
+        payload.text.as_deref().unwrap_or_default(),
+        starts_at,
+        hours,
+        &outside,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, test_state, Fixture, TestState, RESPONSE_URL};
+    use super::*;
+    use crate::database::models::SlackTeam;
+    use crate::slack::fake::FakeSlackApi;
+    use std::sync::Arc;
+
+    fn mentioning(user_ids: &[&str]) -> Vec<Attendee> {
+        user_ids
+            .iter()
+            .map(|user_id| Attendee::SlackUser(user_id.to_string()))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_asks_only_when_someone_is_outside_working_hours() {
+        let (mut state, _pool) = test_state().await;
+        let slack = Arc::new(FakeSlackApi::new());
+        state.slack = slack.clone();
+        let payload = payload("Sync <@U0000A001> <@U0000B0B1> 22:00", RESPONSE_URL);
+        // Tuesday 22:00 UTC: 7:00 on Wednesday in Tokyo, 14:00 in Los Angeles
+        let starts_at: DateTime<Utc> = "2030-06-04T22:00:00Z".parse().unwrap();
+        slack.set_timezone("U0000A001", "Asia/Tokyo");
+        slack.set_timezone("U0000B0B1", "America/Los_Angeles");
+        let attendees = mentioning(&["U0000A001", "U0000B0B1"]);
+
+        // Without the bot token there is no way to look the timezones up
+        assert!(check(&state, &payload, starts_at, &attendees)
+            .await
+            .is_none());
+
+        state
+            .db
+            .store_slack_team(&SlackTeam::new("T012AB3C4".to_string(), "xoxb-bot".into()))
+            .await
+            .unwrap();
+        let reply = check(&state, &payload, starts_at, &attendees)
+            .await
+            .unwrap();
+        assert!(reply.text.contains("for <@U0000A001>."), "{}", reply.text);
+        assert!(check(&state, &payload, starts_at, &attendees[1..])
+            .await
+            .is_none());
+
+        state
+            .db
+            .set_team_setting("T012AB3C4", working_hours::SETTING_KEY, "7-16")
+            .await
+            .unwrap();
+        assert!(check(&state, &payload, starts_at, &attendees)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_timezones_are_left_out() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        slack.set_timezone("U0000A001", "Mars/Olympus_Mons");
+
+        let starts_at: DateTime<Utc> = "2030-06-04T03:00:00Z".parse().unwrap();
+        assert!(check(
+            &state,
+            &payload("", RESPONSE_URL),
+            starts_at,
+            &mentioning(&["U0000A001", "U0000NOTZ1"]),
+        )
+        .await
+        .is_none());
+    }
+}
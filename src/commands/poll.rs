@@ -0,0 +1,207 @@
+//! `/meet poll ["title"] <when>, <when>, ...`: asks the channel when to
+//! meet. The votes and booking are handled by [`crate::polls`].
+
+use axum::async_trait;
+use chrono::Utc;
+use std::collections::HashSet;
+use tracing::info;
+
+use super::{CommandContext, CommandHandler};
+use crate::command_parser::MeetCommand;
+use crate::database::models::Poll;
+use crate::error::AppError;
+use crate::handlers::slack::SlackResponse;
+use crate::observability::{self, Phase};
+use crate::polls;
+use crate::timezones;
+
+pub struct PollHandler;
+
+#[async_trait]
+impl CommandHandler for PollHandler {
+    fn name(&self) -> &'static str {
+        "poll"
+    }
+
+    /// Polls end in a meeting, so they go where meetings may be created.
+    fn follows_channel_policy(&self) -> bool {
+        true
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let CommandContext {
+            state,
+            payload,
+            command,
+            ..
+        } = ctx;
+        let MeetCommand::Poll { title, options } = command else {
+            return Err(AppError::Internal(anyhow::anyhow!(
+                "poll handler got /meet {}",
+                command.name()
+            )));
+        };
+
+        // Read where the user is, like the start of `/meet`
+        let tz = timezones::of_user(&state, &payload.team_id, &payload.user_id).await?;
+        let now = state.clock.now();
+        let mut times = Vec::with_capacity(options.len());
+        for option in options {
+            let Some(at) = option.resolve(&now.with_timezone(&tz)) else {
+                return Ok(SlackResponse::ephemeral(
+                    "❌ One of those times doesn't exist.".to_string(),
+                ));
+            };
+            let at = at.with_timezone(&Utc);
+            state.validator.validate_meeting_time(at, now)?;
+            times.push(at);
+        }
+        let mut seen = HashSet::new();
+        if !times.iter().all(|at| seen.insert(*at)) {
+            return Ok(SlackResponse::ephemeral(
+                "❌ Each time can only be offered once.".to_string(),
+            ));
+        }
+
+        let title = title
+            .as_deref()
+            .map(|t| state.validator.validate_meeting_title(t))
+            .transpose()?
+            .map(|t| t.value);
+        let poll = observability::timed(
+            Phase::Database,
+            state.db.create_poll(&Poll::new(
+                &user,
+                payload.team_id.clone(),
+                payload.channel_id.clone(),
+                title,
+                times,
+            )),
+        )
+        .await?;
+        info!(
+            "User {} started poll {:?} with {} times",
+            user.id,
+            poll.id,
+            poll.options.len()
+        );
+
+        Ok(polls::message(&poll, &[]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{connected_user, payload, test_state, RESPONSE_URL};
+    use crate::command_parser;
+    use crate::quota;
+    use crate::time::TestClock;
+    use crate::AppState;
+    use chrono::{DateTime, Utc};
+
+    /// State whose clock is a couple of days before the polled times.
+    async fn state() -> AppState {
+        let (mut state, _pool) = test_state().await;
+        state.clock = TestClock::new("2030-06-01T12:00:00Z".parse().unwrap());
+        state
+    }
+
+    #[tokio::test]
+    async fn test_posts_the_poll_in_the_channel() {
+        let state = state().await;
+        let user = connected_user(&state).await;
+        let payload = payload(
+            "poll \"Retro\" 2030-06-03 14:00, 2030-06-03 16:00",
+            RESPONSE_URL,
+        );
+        let command = command_parser::parse(payload.text.as_deref().unwrap()).unwrap();
+
+        let response = state
+            .commands
+            .dispatch(state.clone(), payload, command, false)
+            .await
+            .unwrap();
+
+        assert_eq!(response.response_type, "in_channel");
+        let blocks = response.blocks.as_ref().unwrap();
+        assert_eq!(blocks.len(), 5);
+        let poll_id = blocks[1].buttons()[0]
+            .value
+            .as_deref()
+            .and_then(|value| value.split_once(':'))
+            .map(|(poll_id, _)| poll_id.parse().unwrap())
+            .unwrap();
+        let poll = state.db.get_poll(poll_id).await.unwrap().unwrap();
+        assert_eq!(poll.user_id, user.id);
+        assert_eq!(poll.channel_id, "C012AB3CD");
+        assert_eq!(poll.title.as_deref(), Some("Retro"));
+        let options: Vec<DateTime<Utc>> = vec![
+            "2030-06-03T14:00:00Z".parse().unwrap(),
+            "2030-06-03T16:00:00Z".parse().unwrap(),
+        ];
+        assert_eq!(poll.options, options);
+    }
+
+    #[tokio::test]
+    async fn test_times_are_read_in_the_workspace_timezone() {
+        let state = state().await;
+        connected_user(&state).await;
+        state
+            .db
+            .set_team_setting("T012AB3C4", quota::TIMEZONE_KEY, "Europe/Warsaw")
+            .await
+            .unwrap();
+        let text = "poll 2030-06-03 14:00, 2030-06-03 16:00";
+
+        let response = state
+            .commands
+            .dispatch(
+                state.clone(),
+                payload(text, RESPONSE_URL),
+                command_parser::parse(text).unwrap(),
+                false,
+            )
+            .await
+            .unwrap();
+
+        let poll_id = response.blocks.as_ref().unwrap()[1].buttons()[0]
+            .value
+            .as_deref()
+            .and_then(|value| value.split_once(':'))
+            .map(|(poll_id, _)| poll_id.parse().unwrap())
+            .unwrap();
+        let poll = state.db.get_poll(poll_id).await.unwrap().unwrap();
+        let options: Vec<DateTime<Utc>> = vec![
+            "2030-06-03T12:00:00Z".parse().unwrap(),
+            "2030-06-03T14:00:00Z".parse().unwrap(),
+        ];
+        assert_eq!(poll.options, options);
+    }
+
+    #[tokio::test]
+    async fn test_refuses_past_and_repeated_times() {
+        let state = state().await;
+        connected_user(&state).await;
+        let run = |text: &str| {
+            let state = state.clone();
+            let payload = payload(text, RESPONSE_URL);
+            let command = command_parser::parse(text).unwrap();
+            async move {
+                state
+                    .commands
+                    .dispatch(state.clone(), payload, command, false)
+                    .await
+            }
+        };
+
+        let response = run("poll 2030-06-03 14:00, 2030-06-03 14:00")
+            .await
+            .unwrap();
+        assert_eq!(response.text, "❌ Each time can only be offered once.");
+
+        assert!(run("poll 2030-05-31 14:00, 2030-06-03 14:00")
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,764 @@
+//! The queue of meeting creations. `/meet` queues a [`CreateMeetingJob`] and
+//! a fixed pool of workers works through it, so a slow Google backs up a
+//! bounded queue rather than piling up a task per command. When the queue
+//! is full, commands are turned away.
+//!
+//! Past [`QueueConfig::busy_depth`] waiting jobs, a command that can't wait
+//! for its meeting tells the user how long it should take, estimated from
+//! the queue's depth and a moving average of how long jobs take, and the
+//! user hears again if it takes longer than that.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::{CancellationToken, DropGuard};
+use tracing::{error, info, warn, Instrument, Span};
+
+use super::create::{create_meeting, meeting_response, Creation, MeetingRequest, CREATION_FAILED};
+use super::InFlightGuard;
+use crate::config::QueueConfig;
+use crate::database::models::User;
+use crate::error::AppError;
+use crate::handlers::slack::{deliver, post_followup, SlackResponse, SlashCommandPayload};
+use crate::telemetry::metrics;
+use crate::AppState;
+
+pub const QUEUE_FULL: &str =
+    "🚦 I'm creating a lot of meetings right now. Please try again in a moment.";
+const CREATING: &str = "⏳ Creating your Google Meet…";
+const TIMED_OUT: &str =
+    "❌ Google took too long to create your meeting. Please try again in a moment.";
+const STILL_CREATING: &str =
+    "⏳ This is taking longer than expected, but your meeting is still on its way.";
+
+/// How much the latest job counts in the average job duration.
+const AVERAGE_WEIGHT: f64 = 0.2;
+/// The job duration assumed until one has been measured.
+const UNMEASURED_JOB_DURATION: Duration = Duration::from_secs(2);
+
+/// One meeting to create, with what the worker needs to answer: the
+/// command's payload carries the channel, the user's name and the
+/// `response_url`.
+pub struct CreateMeetingJob {
+    pub user: User,
+    pub payload: SlashCommandPayload,
+    pub request: MeetingRequest,
+    /// Held until the job is done, so the user can't start another meanwhile.
+    guard: Option<InFlightGuard>,
+    /// The command waiting for the result, until it acknowledged Slack.
+    reply: oneshot::Sender<Result<SlackResponse, AppError>>,
+    queued_at: Instant,
+    /// The command's span, so the job's logs keep its request id.
+    span: Span,
+    /// Tells whoever waits to follow up on the job that it is done.
+    done: DropGuard,
+}
+
+/// Exponentially weighted moving average of how long jobs take once a
+/// worker has them.
+#[derive(Clone, Default)]
+struct MovingAverage(Arc<Mutex<Option<Duration>>>);
+
+impl MovingAverage {
+    fn record(&self, sample: Duration) {
+        let mut average = self.0.lock().unwrap();
+        *average = Some(match *average {
+            Some(average) => average.mul_f64(1.0 - AVERAGE_WEIGHT) + sample.mul_f64(AVERAGE_WEIGHT),
+            None => sample,
+        });
+    }
+
+    fn get(&self) -> Option<Duration> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Handle to the queue, shared through `AppState`.
+#[derive(Clone)]
+pub struct MeetingQueue {
+    sender: mpsc::Sender<CreateMeetingJob>,
+    receiver: Arc<tokio::sync::Mutex<mpsc::Receiver<CreateMeetingJob>>>,
+    workers: Arc<Mutex<Vec<JoinHandle<()>>>>,
+    stop: CancellationToken,
+    config: QueueConfig,
+    durations: MovingAverage,
+    /// Posts the follow-ups of jobs taking longer than estimated.
+    http: reqwest::Client,
+}
+
+impl MeetingQueue {
+    pub fn new(config: QueueConfig, http: reqwest::Client) -> Self {
+        let (sender, receiver) = mpsc::channel(config.capacity);
+        Self {
+            sender,
+            receiver: Arc::new(tokio::sync::Mutex::new(receiver)),
+            workers: Arc::default(),
+            stop: CancellationToken::new(),
+            config,
+            durations: MovingAverage::default(),
+            http,
+        }
+    }
+
+    /// Starts the workers, which create meetings with `state`.
+    pub fn start(&self, state: AppState) {
+        let mut workers = self.workers.lock().unwrap();
+        for worker in 0..self.config.workers {
+            workers.push(tokio::spawn(self.clone().work(worker, state.clone())));
+        }
+        info!("Started {} meeting workers", self.config.workers);
+    }
+
+    /// Stops taking jobs and waits for the workers to finish the ones
+    /// already queued.
+    pub async fn drain(&self) {
+        self.stop.cancel();
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        for worker in workers {
+            if let Err(e) = worker.await {
+                error!("Meeting worker failed: {}", e);
+            }
+        }
+    }
+
+    /// Jobs waiting for a worker.
+    pub fn depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// The moving average of how long jobs take once a worker has them;
+    /// `None` before any job was done.
+    pub fn average_job_duration(&self) -> Option<Duration> {
+        self.durations.get()
+    }
+
+    /// How long until a job queued as the last of `depth` waiting ones is
+    /// done: the jobs being worked on finish, each worker works through
+    /// its share of those ahead, then the job itself, each taking the
+    /// average job duration.
+    pub fn estimated_wait(&self, depth: usize) -> Duration {
+        let average = self
+            .average_job_duration()
+            .unwrap_or(UNMEASURED_JOB_DURATION);
+        let ahead = depth.saturating_sub(1) / self.config.workers;
+        average * (ahead as u32 + 2)
+    }
+
+    /// Queues the meeting of `user` and waits up to `ack_deadline` for it.
+    /// A meeting that takes longer is acknowledged, and posted to the
+    /// command's `response_url` by the worker once it exists; when the
+    /// queue is busy, the acknowledgement estimates when.
+    pub async fn submit(
+        &self,
+        user: User,
+        payload: SlashCommandPayload,
+        request: MeetingRequest,
+        guard: Option<InFlightGuard>,
+        ack_deadline: Duration,
+    ) -> Result<SlackResponse, AppError> {
+        let (reply, result) = oneshot::channel();
+        let queued_at = Instant::now();
+        let response_url = payload.response_url.clone();
+        let done = CancellationToken::new();
+        let job = CreateMeetingJob {
+            user,
+            payload,
+            request,
+            guard,
+            reply,
+            queued_at,
+            span: Span::current(),
+            done: done.clone().drop_guard(),
+        };
+        match self.sender.try_send(job) {
+            Ok(()) => {}
+            Err(TrySendError::Full(job)) => {
+                warn!(
+                    "Meeting queue is full, turning away user {}",
+                    job.payload.user_id
+                );
+                metrics::record_rate_limit_block("queue_full");
+                return Ok(SlackResponse::ephemeral(QUEUE_FULL.to_string()));
+            }
+            Err(TrySendError::Closed(job)) => {
+                warn!(
+                    "Shutting down, turning away the meeting of user {}",
+                    job.payload.user_id
+                );
+                return Ok(SlackResponse::ephemeral(QUEUE_FULL.to_string()));
+            }
+        }
+        let depth = self.depth();
+        metrics::set_meeting_queue_depth(depth);
+        let estimate = (depth > self.config.busy_depth).then(|| self.estimated_wait(depth));
+
+        match tokio::time::timeout(ack_deadline, result).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(AppError::Internal(anyhow::anyhow!(
+                "meeting job ended without a result"
+            ))),
+            Err(_) => {
+                info!("Meeting creation outlasted the ack deadline, finishing in the background");
+                let Some(estimate) = estimate else {
+                    return Ok(SlackResponse::ephemeral(CREATING.to_string()));
+                };
+                info!(
+                    depth,
+                    "Meeting queue is busy, expecting the meeting in {:?}", estimate
+                );
+                if !response_url.is_empty() {
+                    tokio::spawn(
+                        follow_up_when_late(
+                            self.http.clone(),
+                            response_url,
+                            queued_at + estimate,
+                            done,
+                        )
+                        .in_current_span(),
+                    );
+                }
+                Ok(SlackResponse::ephemeral(format!(
+                    "⏳ High load — your meeting should be ready in ~{}.",
+                    format_wait(estimate)
+                )))
+            }
+        }
+    }
+
+    async fn work(self, worker: usize, state: AppState) {
+        while let Some(job) = self.next_job().await {
+            metrics::set_meeting_queue_depth(self.depth());
+            let span = job.span.clone();
+            run(&state, self.config.job_timeout, &self.durations, job)
+                .instrument(span)
+                .await;
+        }
+        info!(worker, "Meeting worker stopped");
+    }
+
+    /// The next job; `None` once stopped and the queue is empty.
+    async fn next_job(&self) -> Option<CreateMeetingJob> {
+        let mut receiver = self.receiver.lock().await;
+        tokio::select! {
+            biased;
+            job = receiver.recv() => job,
+            _ = self.stop.cancelled() => {
+                // Jobs already queued still get done
+                receiver.close();
+                receiver.recv().await
+            }
+        }
+    }
+}
+
+/// How one attempt at a job went.
+enum Attempt {
+    Finished(Result<Creation, AppError>),
+    TimedOut,
+}
+
+impl Attempt {
+    /// Whether another attempt may well succeed.
+    fn is_transient(&self) -> bool {
+        match self {
+            Attempt::TimedOut => true,
+            Attempt::Finished(Err(AppError::Google(e))) => e.is_transient(),
+            Attempt::Finished(_) => false,
+        }
+    }
+}
+
+async fn attempt(
+    state: &AppState,
+    timeout: Duration,
+    payload: &SlashCommandPayload,
+    user: &User,
+    request: &MeetingRequest,
+) -> Attempt {
+    let create = create_meeting(state, payload, user, request);
+    match tokio::time::timeout(timeout, create).await {
+        Ok(result) => Attempt::Finished(result),
+        Err(_) => Attempt::TimedOut,
+    }
+}
+
+/// The meeting an attempt stored before it timed out, so trying again
+/// doesn't create a second Meet space for the same command.
+async fn stored_meeting(state: &AppState, payload: &SlashCommandPayload) -> Option<Creation> {
+    match state.db.meeting_for_trigger(&payload.trigger_id).await {
+        Ok(meeting) => {
+            let meeting = meeting?;
+            info!(
+                "Meeting for trigger {} was stored before timing out, not retrying",
+                payload.trigger_id
+            );
+            Some(Creation::Created(
+                meeting_response(state, payload, Ok(meeting)).await,
+            ))
+        }
+        Err(e) => {
+            warn!(
+                "Failed to look up the meeting of trigger {}: {:#}",
+                payload.trigger_id, e
+            );
+            None
+        }
+    }
+}
+
+/// Tells the user at `response_url` their meeting is late, unless its job
+/// is `done` by `expected_at`.
+async fn follow_up_when_late(
+    http: reqwest::Client,
+    response_url: String,
+    expected_at: Instant,
+    done: CancellationToken,
+) {
+    tokio::select! {
+        _ = done.cancelled() => {}
+        _ = tokio::time::sleep_until(expected_at) => {
+            info!("Meeting is taking longer than estimated, following up");
+            let mut update = SlackResponse::ephemeral(STILL_CREATING.to_string());
+            update.replace_original = true;
+            post_followup(http, response_url, update).await;
+        }
+    }
+}
+
+/// `wait` rounded up, as `20s` or `3 min`.
+fn format_wait(wait: Duration) -> String {
+    let seconds = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    if seconds < 60 {
+        format!("{}s", seconds.max(1))
+    } else {
+        format!("{} min", seconds.div_ceil(60))
+    }
+}
+
+/// Creates the job's meeting, trying once more if the first attempt timed
+/// out or Google failed on its side, and delivers the result. An attempt
+/// that timed out after storing its meeting isn't tried again.
+async fn run(
+    state: &AppState,
+    timeout: Duration,
+    durations: &MovingAverage,
+    job: CreateMeetingJob,
+) {
+    let CreateMeetingJob {
+        user,
+        payload,
+        request,
+        guard,
+        reply,
+        queued_at,
+        done,
+        ..
+    } = job;
+
+    let started = Instant::now();
+    let mut outcome = attempt(state, timeout, &payload, &user, &request).await;
+    if matches!(outcome, Attempt::TimedOut) {
+        if let Some(creation) = stored_meeting(state, &payload).await {
+            outcome = Attempt::Finished(Ok(creation));
+        }
+    }
+    if outcome.is_transient() {
+        warn!(
+            "Creating a meeting for user {} failed, retrying once",
+            user.id
+        );
+        metrics::record_meeting_job_retry();
+        outcome = attempt(state, timeout, &payload, &user, &request).await;
+    }
+
+    let result = match outcome {
+        Attempt::Finished(result) => {
+            let label = match &result {
+                Ok(Creation::Created(_)) => "ok",
+                Ok(Creation::NotCreated(_)) | Err(_) => "failed",
+            };
+            metrics::record_meeting_job(label, queued_at.elapsed());
+            result.map(Creation::into_response)
+        }
+        Attempt::TimedOut => {
+            warn!(
+                "Creating a meeting for user {} timed out after {:?} twice",
+                user.id, timeout
+            );
+            metrics::record_meeting_job("timed_out", queued_at.elapsed());
+            Ok(SlackResponse::ephemeral(TIMED_OUT.to_string()))
+        }
+    };
+    durations.record(started.elapsed());
+    // Whatever happens next, the meeting is no longer late
+    drop(done);
+
+    // The command already acknowledged Slack when it stopped waiting
+    if let Err(result) = reply.send(result) {
+        let response = result.unwrap_or_else(|e| {
+            e.log();
+            SlackResponse::ephemeral(
+                e.slack_text()
+                    .unwrap_or_else(|| CREATION_FAILED.to_string()),
+            )
+        });
+        deliver(state, &payload, response).await;
+    }
+    drop(guard);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{connected_user, payload, test_state_with, RESPONSE_URL};
+    use super::*;
+    use crate::config::Config;
+    use crate::database::models::{MeetLinkKind, Meeting};
+    use crate::google::fake::{FakeGoogleApi, FAKE_MEETING_URI};
+    use crate::google::{CreatedMeeting, GoogleApiError};
+    use axum::{extract::State, response::Json, routing::post, Router};
+    use serde_json::Value;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A Slack `response_url` endpoint handing over what is posted to it.
+    async fn response_url() -> (String, mpsc::UnboundedReceiver<Value>) {
+        let (sender, followups) = mpsc::unbounded_channel();
+        let hooks = Router::new()
+            .route(
+                "/commands/1/2",
+                post(
+                    |State(sender): State<mpsc::UnboundedSender<Value>>,
+                     Json(body): Json<Value>| async move {
+                        sender.send(body).unwrap();
+                    },
+                ),
+            )
+            .with_state(sender);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/commands/1/2", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, hooks).await.unwrap() });
+        (url, followups)
+    }
+
+    async fn queue_state(google: FakeGoogleApi, queue: QueueConfig) -> (AppState, User) {
+        let mut config = Config::for_tests();
+        config.queue = queue;
+        let (state, _pool) = test_state_with(Arc::new(google), config).await;
+        let user = connected_user(&state).await;
+        (state, user)
+    }
+
+    /// Queues a meeting titled `title` and returns the command's reply.
+    async fn submit(state: &AppState, user: &User, title: &str, url: &str) -> SlackResponse {
+        let mut payload = payload(title, url);
+        payload.trigger_id = format!("trigger-{}", title);
+        state
+            .meeting_queue
+            .submit(
+                user.clone(),
+                payload,
+                MeetingRequest {
+                    title: Some(title.to_string()),
+                    ..MeetingRequest::default()
+                },
+                None,
+                Duration::from_millis(20),
+            )
+            .await
+            .unwrap()
+    }
+
+    fn one_worker(capacity: usize) -> QueueConfig {
+        QueueConfig {
+            workers: 1,
+            capacity,
+            job_timeout: Duration::from_secs(5),
+            ..QueueConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_estimated_wait_follows_depth_and_job_durations() {
+        let queue = MeetingQueue::new(
+            QueueConfig {
+                workers: 2,
+                ..QueueConfig::default()
+            },
+            reqwest::Client::new(),
+        );
+        assert_eq!(queue.average_job_duration(), None);
+        assert_eq!(queue.estimated_wait(1), UNMEASURED_JOB_DURATION * 2);
+
+        queue.durations.record(Duration::from_secs(1));
+        queue.durations.record(Duration::from_secs(6));
+        assert_eq!(queue.average_job_duration(), Some(Duration::from_secs(2)));
+        // Two workers get through the four ahead in two rounds
+        assert_eq!(queue.estimated_wait(1), Duration::from_secs(4));
+        assert_eq!(queue.estimated_wait(5), Duration::from_secs(8));
+        assert_eq!(queue.estimated_wait(6), Duration::from_secs(8));
+    }
+
+    #[test]
+    fn test_format_wait() {
+        assert_eq!(format_wait(Duration::from_millis(20)), "1s");
+        assert_eq!(format_wait(Duration::from_millis(19_200)), "20s");
+        assert_eq!(format_wait(Duration::from_secs(60)), "1 min");
+        assert_eq!(format_wait(Duration::from_secs(150)), "3 min");
+    }
+
+    #[tokio::test]
+    async fn test_a_busy_queue_estimates_the_wait_and_follows_up_when_late() {
+        let (state, user) = queue_state(
+            FakeGoogleApi::succeeding().with_delay(Duration::from_millis(200)),
+            QueueConfig {
+                busy_depth: 0,
+                ..one_worker(10)
+            },
+        )
+        .await;
+        state
+            .meeting_queue
+            .durations
+            .record(Duration::from_millis(10));
+        let (first_url, _) = response_url().await;
+        let (url, mut followups) = response_url().await;
+
+        // The worker has the first, so the second waits behind it
+        submit(&state, &user, "first", &first_url).await;
+        let response = submit(&state, &user, "second", &url).await;
+        assert_eq!(
+            response.text,
+            "⏳ High load — your meeting should be ready in ~1s."
+        );
+        assert_eq!(response.response_type, "ephemeral");
+
+        let update = followups.recv().await.unwrap();
+        assert_eq!(update["text"], STILL_CREATING);
+        assert_eq!(update["replace_original"], true);
+        let meeting = followups.recv().await.unwrap();
+        assert!(meeting["text"].as_str().unwrap().contains(FAKE_MEETING_URI));
+    }
+
+    #[tokio::test]
+    async fn test_a_quiet_queue_neither_estimates_nor_follows_up() {
+        let (state, user) = queue_state(
+            FakeGoogleApi::succeeding().with_delay(Duration::from_millis(200)),
+            one_worker(10),
+        )
+        .await;
+        state
+            .meeting_queue
+            .durations
+            .record(Duration::from_millis(10));
+        let (first_url, _) = response_url().await;
+        let (url, mut followups) = response_url().await;
+
+        submit(&state, &user, "first", &first_url).await;
+        let response = submit(&state, &user, "second", &url).await;
+        assert_eq!(response.text, CREATING);
+
+        // Late as it is, the meeting is the first thing posted
+        let meeting = followups.recv().await.unwrap();
+        assert!(meeting["text"].as_str().unwrap().contains(FAKE_MEETING_URI));
+    }
+
+    #[tokio::test]
+    async fn test_jobs_are_done_in_the_order_they_were_queued() {
+        let (state, user) = queue_state(
+            FakeGoogleApi::succeeding().with_delay(Duration::from_millis(30)),
+            one_worker(10),
+        )
+        .await;
+        let (url, mut followups) = response_url().await;
+
+        for title in ["first", "second", "third"] {
+            let response = submit(&state, &user, title, &url).await;
+            assert_eq!(response.text, CREATING);
+        }
+        for _ in 0..3 {
+            followups.recv().await.unwrap();
+        }
+
+        let mut titles: Vec<_> = state
+            .db
+            .get_user_meetings(user.id, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|meeting| (meeting.id, meeting.title.unwrap()))
+            .collect();
+        titles.sort();
+        let titles: Vec<_> = titles.into_iter().map(|(_, title)| title).collect();
+        assert_eq!(titles, ["first", "second", "third"]);
+    }
+
+    #[tokio::test]
+    async fn test_a_full_queue_turns_commands_away() {
+        let (state, user) = queue_state(
+            FakeGoogleApi::succeeding().with_delay(Duration::from_millis(300)),
+            one_worker(1),
+        )
+        .await;
+        let (url, mut followups) = response_url().await;
+
+        // One being created, one waiting, and no room for a third
+        assert_eq!(submit(&state, &user, "first", &url).await.text, CREATING);
+        assert_eq!(submit(&state, &user, "second", &url).await.text, CREATING);
+        assert_eq!(state.meeting_queue.depth(), 1);
+        let turned_away = submit(&state, &user, "third", &url).await;
+        assert_eq!(turned_away.text, QUEUE_FULL);
+        assert_eq!(turned_away.response_type, "ephemeral");
+
+        for _ in 0..2 {
+            let followup = followups.recv().await.unwrap();
+            assert!(followup["text"]
+                .as_str()
+                .unwrap()
+                .contains(FAKE_MEETING_URI));
+        }
+        assert_eq!(
+            state.db.get_user_meetings(user.id, 10).await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_drain_finishes_queued_jobs() {
+        let google = Arc::new(FakeGoogleApi::succeeding().with_delay(Duration::from_millis(50)));
+        let mut config = Config::for_tests();
+        config.queue = one_worker(10);
+        let (state, _pool) = test_state_with(google.clone(), config).await;
+        let user = connected_user(&state).await;
+        let (url, mut followups) = response_url().await;
+
+        for title in ["first", "second", "third"] {
+            submit(&state, &user, title, &url).await;
+        }
+        state.meeting_queue.drain().await;
+
+        assert_eq!(google.calls(), 3);
+        for _ in 0..3 {
+            followups.try_recv().expect("posted before the drain ended");
+        }
+        // Nothing is taken once the workers are gone
+        assert_eq!(submit(&state, &user, "late", &url).await.text, QUEUE_FULL);
+    }
+
+    #[tokio::test]
+    async fn test_transient_failures_are_retried_once() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let google = FakeGoogleApi::responding({
+            let calls = calls.clone();
+            move || {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    Err(GoogleApiError::Api {
+                        status: 503,
+                        message: "backend unavailable".to_string(),
+                    })
+                } else {
+                    Ok(CreatedMeeting {
+                        name: "spaces/abc".to_string(),
+                        meeting_uri: FAKE_MEETING_URI.to_string(),
+                    })
+                }
+            }
+        });
+        let (state, user) = queue_state(google, one_worker(10)).await;
+
+        let mut payload = payload("Standup", RESPONSE_URL);
+        payload.trigger_id = "trigger-retry".to_string();
+        let response = state
+            .meeting_queue
+            .submit(
+                user,
+                payload,
+                MeetingRequest::default(),
+                None,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert!(
+            response.text.contains(FAKE_MEETING_URI),
+            "{}",
+            response.text
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timed_out_jobs_whose_meeting_was_stored_are_not_retried() {
+        let google = Arc::new(FakeGoogleApi::succeeding().with_delay(Duration::from_millis(200)));
+        let mut config = Config::for_tests();
+        config.queue = QueueConfig {
+            job_timeout: Duration::from_millis(50),
+            ..one_worker(10)
+        };
+        let (state, _pool) = test_state_with(google.clone(), config).await;
+        let user = connected_user(&state).await;
+        // As if the attempt stored its meeting just as it ran out of time
+        state
+            .db
+            .create_meeting_for_trigger(
+                &Meeting::new(
+                    user.id,
+                    FAKE_MEETING_URI.to_string(),
+                    Some("Standup".to_string()),
+                    MeetLinkKind::Meet,
+                ),
+                "trigger-stored",
+            )
+            .await
+            .unwrap();
+
+        let mut payload = payload("Standup", RESPONSE_URL);
+        payload.trigger_id = "trigger-stored".to_string();
+        let response = state
+            .meeting_queue
+            .submit(
+                user,
+                payload,
+                MeetingRequest::default(),
+                None,
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert!(
+            response.text.contains(FAKE_MEETING_URI),
+            "{}",
+            response.text
+        );
+        assert_eq!(google.calls(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_lasting_failures_are_not_retried_forever() {
+        let google = Arc::new(FakeGoogleApi::failing(|| GoogleApiError::Api {
+            status: 500,
+            message: "internal".to_string(),
+        }));
+        let mut config = Config::for_tests();
+        config.queue = one_worker(10);
+        let (state, _pool) = test_state_with(google.clone(), config).await;
+        let user = connected_user(&state).await;
+
+        let result = state
+            .meeting_queue
+            .submit(
+                user,
+                payload("", RESPONSE_URL),
+                MeetingRequest::default(),
+                None,
+                Duration::from_secs(5),
+            )
+            .await;
+
+        assert!(matches!(result, Err(AppError::Google(_))));
+        assert_eq!(google.calls(), 2);
+    }
+}
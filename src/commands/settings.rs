@@ -0,0 +1,1616 @@
+//! `/meet set [team] <key> <value>`: preferences. Workspace settings, set
+//! with `team`, are for the Slack users listed in `ADMIN_SLACK_USERS`.
+
+use axum::async_trait;
+use std::collections::BTreeSet;
+use tracing::info;
+
+use super::{resolve_user, CommandContext, CommandHandler};
+use crate::announcement;
+use crate::audit::{self, AuditEvent};
+use crate::channel_policy::{self, ChannelPolicy};
+use crate::command_parser::{self, MeetCommand, SetScope};
+use crate::database::models::MeetingVisibility;
+use crate::error::AppError;
+use crate::features::Feature;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::personal_space;
+use crate::quota::{self, DailyLimit};
+use crate::recording;
+use crate::reminders::{self, ReminderLead};
+use crate::shared_account;
+use crate::slack::blocks;
+use crate::timezones;
+use crate::title_template::{self, DefaultTitleMode, TitleTemplate, TitleValues};
+use crate::working_hours::{self, WorkingHours};
+use crate::AppState;
+
+/// The user's own settings, as typed after `/meet set`.
+const USER_SETTINGS: &[&str] = &[
+    "visibility",
+    "reuse-personal-space",
+    "reminder",
+    "lend-account",
+];
+
+/// Workspace settings, as typed after `/meet set team`.
+const TEAM_SETTINGS: &[&str] = &[
+    "title-template",
+    "default-title",
+    "allow-channel",
+    "deny-channel",
+    "shared-account",
+    "announce-emoji",
+    "announce-prefix",
+    "announce-mention",
+    "auto-record",
+    "auto-transcribe",
+    "working-hours",
+    "daily-meeting-limit",
+    "timezone",
+];
+
+/// Example title text for showing what a template makes of it.
+const EXAMPLE_TEXT: &str = "Standup";
+
+pub struct SettingsHandler;
+
+#[async_trait]
+impl CommandHandler for SettingsHandler {
+    fn name(&self) -> &'static str {
+        "set"
+    }
+
+    fn needs_user(&self) -> bool {
+        false
+    }
+
+    fn feature(&self) -> Option<Feature> {
+        Some(Feature::Settings)
+    }
+
+    async fn handle(&self, ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let MeetCommand::Set { scope, key, value } = ctx.command else {
+            return Err(AppError::Internal(anyhow::anyhow!(
+                "set handler got /meet {}",
+                ctx.command.name()
+            )));
+        };
+
+        let key = key.replace('_', "-");
+        match (scope, key.as_str()) {
+            (SetScope::Team, key) if TEAM_SETTINGS.contains(&key) => {
+                let admins = &ctx.state.config.admin.slack_users;
+                if !admins.contains(&ctx.payload.user_id) {
+                    info!("Refused /meet set team to {}", ctx.payload.user_id);
+                    return Ok(SlackResponse::ephemeral(
+                        "🚫 Only the bot's admins can change workspace settings.".to_string(),
+                    ));
+                }
+                let value = unquote(&value);
+                match key {
+                    "title-template" => set_title_template(&ctx.state, &ctx.payload, value).await,
+                    "default-title" => set_default_title(&ctx.state, &ctx.payload, value).await,
+                    "shared-account" => set_shared_account(&ctx.state, &ctx.payload, value).await,
+                    "announce-emoji" | "announce-prefix" | "announce-mention" => {
+                        set_announcement(&ctx.state, &ctx.payload, key, value).await
+                    }
+                    "auto-record" | "auto-transcribe" => {
+                        set_artifacts(&ctx.state, &ctx.payload, key, value).await
+                    }
+                    "working-hours" => set_working_hours(&ctx.state, &ctx.payload, value).await,
+                    "daily-meeting-limit" => set_daily_limit(&ctx.state, &ctx.payload, value).await,
+                    "timezone" => set_timezone(&ctx.state, &ctx.payload, value).await,
+                    "allow-channel" => {
+                        set_channel_list(&ctx.state, &ctx.payload, ChannelList::Allowed, value)
+                            .await
+                    }
+                    _ => {
+                        set_channel_list(&ctx.state, &ctx.payload, ChannelList::Blocked, value)
+                            .await
+                    }
+                }
+            }
+            (SetScope::User, "visibility") => {
+                set_visibility(&ctx.state, &ctx.payload, unquote(&value)).await
+            }
+            (SetScope::User, "reuse-personal-space") => {
+                set_personal_space(&ctx.state, &ctx.payload, unquote(&value)).await
+            }
+            (SetScope::User, "reminder") => {
+                set_reminder(&ctx.state, &ctx.payload, unquote(&value)).await
+            }
+            (SetScope::User, "lend-account") => {
+                set_lend_account(&ctx.state, &ctx.payload, unquote(&value)).await
+            }
+            (scope, key) => {
+                let key = match scope {
+                    SetScope::Team => format!("team {}", key),
+                    SetScope::User => key.to_string(),
+                };
+                let known: Vec<String> = USER_SETTINGS
+                    .iter()
+                    .map(|setting| format!("`{}`", setting))
+                    .chain(
+                        TEAM_SETTINGS
+                            .iter()
+                            .map(|setting| format!("`team {}`", setting)),
+                    )
+                    .collect();
+                Ok(SlackResponse::ephemeral(format!(
+                    "❓ There's no setting `{}`. Settings: {}.",
+                    key,
+                    known.join(", ")
+                )))
+            }
+        }
+    }
+}
+
+/// `value` without one pair of surrounding quotes, straight or as Slack
+/// curls them.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    [('"', '"'), ('“', '”'), ('\'', '\'')]
+        .iter()
+        .find_map(|(open, close)| {
+            value
+                .strip_prefix(*open)
+                .and_then(|rest| rest.strip_suffix(*close))
+        })
+        .unwrap_or(value)
+}
+
+/// Shows who the user's meetings are shown to without a value, and
+/// otherwise has them shown to `value` from now on.
+async fn set_visibility(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{} set visibility channel|quiet`; `{} --quiet <title>` keeps a single meeting to yourself.",
+        payload.command, payload.command
+    );
+    let user = resolve_user(state, payload).await?;
+
+    if value.is_empty() {
+        let visibility = state
+            .db
+            .get_user_setting(user.id, MeetingVisibility::SETTING_KEY)
+            .await?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or_default();
+        return Ok(SlackResponse::ephemeral(format!(
+            "{}\n{}",
+            describe_visibility(visibility),
+            usage
+        )));
+    }
+
+    let Ok(visibility) = value.parse::<MeetingVisibility>() else {
+        return Ok(SlackResponse::ephemeral(format!(
+            "❌ `{}` isn't a visibility.\n{}",
+            value, usage
+        )));
+    };
+    state
+        .db
+        .set_user_setting(user.id, MeetingVisibility::SETTING_KEY, visibility.name())
+        .await?;
+    info!(
+        "{} set their meeting visibility to {}",
+        payload.user_id,
+        visibility.name()
+    );
+
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ {}",
+        describe_visibility(visibility)
+    )))
+}
+
+fn describe_visibility(visibility: MeetingVisibility) -> &'static str {
+    match visibility {
+        MeetingVisibility::Channel => "Your new meetings are posted in the channel.",
+        MeetingVisibility::Quiet => "Your new meetings are shown only to you.",
+    }
+}
+
+/// Shows whether `/meet` gives the user their standing room without a
+/// value, and otherwise turns it on or off. Turning it off keeps the space,
+/// so turning it on again brings back the same link.
+async fn set_personal_space(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{0} set reuse-personal-space on|off`; `{0} new` makes a one-off meeting either way.",
+        payload.command
+    );
+    let user = resolve_user(state, payload).await?;
+
+    let enabled = match value.to_ascii_lowercase().as_str() {
+        "" => personal_space::enabled(state, &user).await,
+        "on" => true,
+        "off" => false,
+        _ => {
+            return Ok(SlackResponse::ephemeral(format!(
+                "❌ `reuse-personal-space` is `on` or `off`.\n{}",
+                usage
+            )))
+        }
+    };
+    if !value.is_empty() {
+        state
+            .db
+            .set_user_setting(user.id, personal_space::SETTING_KEY, &enabled.to_string())
+            .await?;
+        info!(
+            "{} turned their standing room {}",
+            payload.user_id,
+            if enabled { "on" } else { "off" }
+        );
+    }
+
+    let current = if enabled {
+        format!(
+            "`{}` gives you the same standing room every time.",
+            payload.command
+        )
+    } else {
+        format!("`{}` creates a new meeting every time.", payload.command)
+    };
+    Ok(SlackResponse::ephemeral(format!(
+        "{} {}\n{}",
+        if value.is_empty() { "ℹ️" } else { "✅" },
+        current,
+        usage
+    )))
+}
+
+/// Shows how long before their scheduled meetings start the user's
+/// reminders are posted without a value, and otherwise sets it in minutes,
+/// `off` for none.
+async fn set_reminder(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{} set reminder <minutes>|off`, up to {} minutes.",
+        payload.command,
+        reminders::MAX_LEAD_MINUTES
+    );
+    let user = resolve_user(state, payload).await?;
+
+    let lead = if value.is_empty() {
+        let default = state
+            .config
+            .slack
+            .meeting_reminder
+            .and_then(|lead| chrono::Duration::from_std(lead).ok());
+        reminders::lead_for(&state.db, &user, default).await
+    } else {
+        let Ok(lead) = value.parse::<ReminderLead>() else {
+            return Ok(SlackResponse::ephemeral(format!(
+                "❌ `{}` isn't `off` or a number of minutes up to {}.\n{}",
+                value,
+                reminders::MAX_LEAD_MINUTES,
+                usage
+            )));
+        };
+        state
+            .db
+            .set_user_setting(user.id, reminders::SETTING_KEY, &lead.to_string())
+            .await?;
+        info!(
+            "{} set their reminders to {} minutes ahead",
+            payload.user_id, lead
+        );
+        lead.0
+    };
+
+    let current = match lead {
+        Some(lead) => format!(
+            "Your scheduled meetings get a reminder in the channel {} before they start.",
+            reminders::describe_lead(lead)
+        ),
+        None => "Your scheduled meetings get no reminder.".to_string(),
+    };
+    Ok(SlackResponse::ephemeral(format!(
+        "{} {}\n{}",
+        if value.is_empty() { "ℹ️" } else { "✅" },
+        current,
+        usage
+    )))
+}
+
+/// Shows whether the user lets the bot's admins make their Google account
+/// the workspace's shared one without a value, and otherwise turns that on
+/// or off. Turning it off while the workspace uses the account ends shared
+/// mode.
+async fn set_lend_account(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{} set lend-account on|off`.",
+        payload.command
+    );
+    let user = resolve_user(state, payload).await?;
+
+    let enabled = match value.to_ascii_lowercase().as_str() {
+        "" => shared_account::consents(&state.db, user.id).await?,
+        "on" => true,
+        "off" => false,
+        _ => {
+            return Ok(SlackResponse::ephemeral(format!(
+                "❌ `lend-account` is `on` or `off`.\n{}",
+                usage
+            )))
+        }
+    };
+
+    let mut stopped_sharing = false;
+    if !value.is_empty() {
+        state
+            .db
+            .set_user_setting(user.id, shared_account::CONSENT_KEY, &enabled.to_string())
+            .await?;
+        info!(
+            "{} {} lending their Google account",
+            payload.user_id,
+            if enabled { "allowed" } else { "stopped" }
+        );
+        let shared = shared_account::for_team(&state.db, &payload.team_id).await?;
+        if !enabled && shared.is_some_and(|account| account.slack_user_id == payload.user_id) {
+            shared_account::disable(&state.db, &payload.team_id).await?;
+            record_change(state, payload, shared_account::ACCOUNT_KEY, false).await;
+            stopped_sharing = true;
+        }
+    }
+
+    let current = if enabled {
+        "The bot's admins may have everyone's meetings in this workspace created with your Google account."
+    } else if stopped_sharing {
+        "Your Google account is only used for your own meetings again, and everyone creates meetings with their own."
+    } else {
+        "Your Google account is only used for your own meetings."
+    };
+    Ok(SlackResponse::ephemeral(format!(
+        "{} {}\n{}",
+        if value.is_empty() { "ℹ️" } else { "✅" },
+        current,
+        usage
+    )))
+}
+
+/// Shows the workspace's title template without a value, drops it with
+/// `off`, and otherwise sets it to `value` if that parses.
+async fn set_title_template(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Set one with `{} set team title-template <template>` using `{{channel}}`, `{{user}}`, `{{date}}` and `{{text}}`, or drop it with `off`.",
+        payload.command
+    );
+
+    if value.is_empty() {
+        let text = match title_template::for_team(&state.db, &payload.team_id).await? {
+            Some(template) => format!(
+                "Meetings in this workspace are titled `{}`, as in *{}*.\n{}",
+                template,
+                example(state, payload, &template).await,
+                usage
+            ),
+            None => format!("This workspace has no title template.\n{}", usage),
+        };
+        return Ok(SlackResponse::ephemeral(text));
+    }
+
+    if value.eq_ignore_ascii_case("off") {
+        state
+            .db
+            .delete_team_setting(&payload.team_id, title_template::SETTING_KEY)
+            .await?;
+        record_change(state, payload, title_template::SETTING_KEY, false).await;
+        return Ok(SlackResponse::ephemeral(
+            "✅ Meetings are titled as typed again.".to_string(),
+        ));
+    }
+
+    let template: TitleTemplate = match value.parse() {
+        Ok(template) => template,
+        Err(e) => return Ok(SlackResponse::ephemeral(format!("❌ {}.\n{}", e, usage))),
+    };
+    state
+        .db
+        .set_team_setting(
+            &payload.team_id,
+            title_template::SETTING_KEY,
+            &template.to_string(),
+        )
+        .await?;
+    record_change(state, payload, title_template::SETTING_KEY, true).await;
+
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ New meetings in this workspace will be titled like *{}*.",
+        example(state, payload, &template).await
+    )))
+}
+
+/// Shows how the workspace names untitled meetings without a value, and
+/// otherwise switches to the mode `value` names.
+async fn set_default_title(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{} set team default-title channel|generic`.",
+        payload.command
+    );
+    let today =
+        timezones::local_date(state, &payload.team_id, &payload.user_id, state.clock.now()).await;
+
+    if value.is_empty() {
+        let mode = title_template::default_title_mode(&state.db, &payload.team_id).await?;
+        return Ok(SlackResponse::ephemeral(format!(
+            "Meetings without a title are named `{}`, as in *{}*; private channels and direct messages always get *{}*.\n{}",
+            mode,
+            title_template::default_title(mode, &payload.channel_name, today),
+            title_template::default_title(DefaultTitleMode::Generic, "", today),
+            usage
+        )));
+    }
+
+    let Ok(mode) = value.parse::<DefaultTitleMode>() else {
+        return Ok(SlackResponse::ephemeral(format!(
+            "❌ `{}` isn't a default title.\n{}",
+            value, usage
+        )));
+    };
+    state
+        .db
+        .set_team_setting(
+            &payload.team_id,
+            title_template::DEFAULT_TITLE_MODE_KEY,
+            mode.name(),
+        )
+        .await?;
+    record_change(state, payload, title_template::DEFAULT_TITLE_MODE_KEY, true).await;
+
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ Meetings without a title will be named like *{}*.",
+        title_template::default_title(mode, &payload.channel_name, today)
+    )))
+}
+
+/// One of the lists of the workspace's [`ChannelPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelList {
+    Allowed,
+    Blocked,
+}
+
+impl ChannelList {
+    fn setting(self) -> &'static str {
+        match self {
+            ChannelList::Allowed => "allow-channel",
+            ChannelList::Blocked => "deny-channel",
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            ChannelList::Allowed => channel_policy::ALLOWED_KEY,
+            ChannelList::Blocked => channel_policy::BLOCKED_KEY,
+        }
+    }
+
+    fn of(self, policy: &mut ChannelPolicy) -> &mut BTreeSet<String> {
+        match self {
+            ChannelList::Allowed => &mut policy.allowed,
+            ChannelList::Blocked => &mut policy.blocked,
+        }
+    }
+}
+
+/// Adds the channel `value` links to `list`, or with `remove <channel>`
+/// takes it off; `clear` empties the list and no value shows the policy.
+async fn set_channel_list(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    list: ChannelList,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Add a channel with `{0} set team {1} #channel`, take it off with `{0} set team {1} remove #channel`, or empty the list with `{0} set team {1} clear`.",
+        payload.command,
+        list.setting()
+    );
+    let mut policy = channel_policy::for_team(&state.db, &payload.team_id).await?;
+
+    if value.is_empty() {
+        return Ok(SlackResponse::ephemeral(format!(
+            "{}\n{}",
+            describe_policy(&policy),
+            usage
+        )));
+    }
+
+    let (word, rest) = value.split_once(' ').unwrap_or((value, ""));
+    let (remove, channel) = match word.to_lowercase().as_str() {
+        "clear" if rest.trim().is_empty() => {
+            list.of(&mut policy).clear();
+            (false, None)
+        }
+        "remove" => (true, Some(rest.trim())),
+        _ => (false, Some(value)),
+    };
+    if let Some(channel) = channel {
+        let id = command_parser::channel_mention(channel).unwrap_or(channel);
+        if state.validator.validate_slack_channel_id(id).is_err() {
+            return Ok(SlackResponse::ephemeral(format!(
+                "❌ I can't tell which channel `{}` is. Pick it from Slack's suggestions as you type `#`.\n{}",
+                channel, usage
+            )));
+        }
+        if remove {
+            list.of(&mut policy).remove(id);
+        } else {
+            list.of(&mut policy).insert(id.to_string());
+        }
+    }
+
+    let channels = list.of(&mut policy);
+    channel_policy::save_list(&state.db, &payload.team_id, list.key(), channels).await?;
+    let set = !channels.is_empty();
+    record_change(state, payload, list.key(), set).await;
+
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ {}",
+        describe_policy(&policy)
+    )))
+}
+
+/// Where meetings can be created under `policy`, in a sentence or two.
+fn describe_policy(policy: &ChannelPolicy) -> String {
+    let mut text = if policy.allowed.is_empty() {
+        "Meetings can be created in any channel".to_string()
+    } else {
+        format!(
+            "Meetings can only be created in {}",
+            channel_policy::mentions(&policy.allowed)
+        )
+    };
+    if policy.blocked.is_empty() {
+        text.push('.');
+    } else {
+        text.push_str(&format!(
+            ", never in {}.",
+            channel_policy::mentions(&policy.blocked)
+        ));
+    }
+    text
+}
+
+/// Shows whose Google account the workspace's meetings are created with
+/// without a value, goes back to everyone's own with `off`, and otherwise
+/// shares the account of the user `value` mentions, if they agreed to it
+/// with `set lend-account`.
+async fn set_shared_account(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Share a member's Google account with `{0} set team shared-account @member`, or stop with `{0} set team shared-account off`.",
+        payload.command
+    );
+
+    if value.is_empty() {
+        let text = match shared_account::for_team(&state.db, &payload.team_id).await? {
+            Some(account) => format!(
+                "Meetings in this workspace are created with <@{}>'s Google account.",
+                account.slack_user_id
+            ),
+            None => "Everyone creates meetings with their own Google account.".to_string(),
+        };
+        return Ok(SlackResponse::ephemeral(format!("{}\n{}", text, usage)));
+    }
+
+    if value.eq_ignore_ascii_case("off") {
+        shared_account::disable(&state.db, &payload.team_id).await?;
+        record_change(state, payload, shared_account::ACCOUNT_KEY, false).await;
+        return Ok(SlackResponse::ephemeral(
+            "✅ Everyone creates meetings with their own Google account again.".to_string(),
+        ));
+    }
+
+    let slack_user_id = command_parser::user_mention(value).unwrap_or(value);
+    if state
+        .validator
+        .validate_slack_user_id(slack_user_id)
+        .is_err()
+    {
+        return Ok(SlackResponse::ephemeral(format!(
+            "❌ I can't tell who that is. Mention them with `@`.\n{}",
+            usage
+        )));
+    }
+    let owner = state
+        .db
+        .get_user_by_slack_id(slack_user_id)
+        .await?
+        .filter(|owner| owner.slack_team_id == payload.team_id);
+    let connected = match &owner {
+        Some(owner) => matches!(state.db.get_oauth_token(owner.id).await, Ok(Some(_))),
+        None => false,
+    };
+    let Some(owner) = owner.filter(|_| connected) else {
+        return Ok(SlackResponse::ephemeral(format!(
+            "❌ <@{}> hasn't connected a Google account. They can with `{} status`.",
+            slack_user_id, payload.command
+        )));
+    };
+    // Everyone's meetings would run on their token, so it's theirs to offer
+    if !shared_account::consents(&state.db, owner.id).await? {
+        return Ok(SlackResponse::ephemeral(format!(
+            "❌ <@{}> hasn't agreed to share their Google account. They can with `{} set lend-account on`.",
+            slack_user_id, payload.command
+        )));
+    }
+
+    shared_account::enable(&state.db, &payload.team_id, slack_user_id, &payload.user_id).await?;
+    record_change(state, payload, shared_account::ACCOUNT_KEY, true).await;
+
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ Meetings in this workspace will be created with <@{}>'s Google account. They still belong to whoever runs `{}`, and I'll tell you if the account stops working.",
+        slack_user_id, payload.command
+    )))
+}
+
+/// Shows how the workspace announces meetings without a value, puts the
+/// part `key` names back with `default`, and otherwise sets it to `value`.
+async fn set_announcement(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    key: &str,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{0} set team announce-emoji <emoji>`, `{0} set team announce-prefix <words>` or `{0} set team announce-mention on|off`; `default` undoes a change.",
+        payload.command
+    );
+
+    if value.is_empty() {
+        let style = announcement::for_team(&state.db, &payload.team_id).await?;
+        return Ok(SlackResponse::ephemeral(format!(
+            "New meetings are announced as “{}”.\n{}",
+            style.headline(&payload.user_id),
+            usage
+        )));
+    }
+
+    let setting_key = match key {
+        "announce-emoji" => announcement::EMOJI_KEY,
+        "announce-prefix" => announcement::PREFIX_KEY,
+        _ => announcement::MENTION_KEY,
+    };
+    if value.eq_ignore_ascii_case("default") {
+        state
+            .db
+            .delete_team_setting(&payload.team_id, setting_key)
+            .await?;
+        record_change(state, payload, setting_key, false).await;
+    } else {
+        let stored = match key {
+            "announce-emoji" => state.validator.validate_announcement_emoji(value),
+            "announce-prefix" => state.validator.validate_announcement_prefix(value),
+            _ => match value.to_ascii_lowercase().as_str() {
+                "on" => Ok("true".to_string()),
+                "off" => Ok("false".to_string()),
+                _ => {
+                    return Ok(SlackResponse::ephemeral(format!(
+                        "❌ `announce-mention` is `on` or `off`.\n{}",
+                        usage
+                    )))
+                }
+            },
+        };
+        let stored = match stored {
+            Ok(stored) => stored,
+            Err(e) => return Ok(SlackResponse::ephemeral(format!("❌ {}.\n{}", e, usage))),
+        };
+        state
+            .db
+            .set_team_setting(&payload.team_id, setting_key, &stored)
+            .await?;
+        record_change(state, payload, setting_key, true).await;
+    }
+
+    let style = announcement::for_team(&state.db, &payload.team_id).await?;
+    Ok(SlackResponse::ephemeral(format!(
+        "✅ New meetings will be announced as “{}”.",
+        style.headline(&payload.user_id)
+    )))
+}
+
+/// Shows whether the workspace's meetings record and transcribe themselves
+/// without a value, and otherwise turns what `key` names `on` or `off`.
+async fn set_artifacts(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    key: &str,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{0} set team auto-record on|off` or `{0} set team auto-transcribe on|off`; a single meeting can ask with `{0} --record` or `{0} --transcribe`.",
+        payload.command
+    );
+    let setting_key = match key {
+        "auto-record" => recording::RECORD_KEY,
+        _ => recording::TRANSCRIBE_KEY,
+    };
+
+    match value.to_ascii_lowercase().as_str() {
+        "" => {}
+        "on" => {
+            state
+                .db
+                .set_team_setting(&payload.team_id, setting_key, "true")
+                .await?;
+            record_change(state, payload, setting_key, true).await;
+        }
+        "off" => {
+            state
+                .db
+                .delete_team_setting(&payload.team_id, setting_key)
+                .await?;
+            record_change(state, payload, setting_key, false).await;
+        }
+        _ => {
+            return Ok(SlackResponse::ephemeral(format!(
+                "❌ `{}` is `on` or `off`.\n{}",
+                key, usage
+            )))
+        }
+    }
+
+    let artifacts = recording::for_team(&state.db, &payload.team_id).await?;
+    let current = match (artifacts.recording, artifacts.transcription) {
+        (true, true) => "New meetings are recorded and transcribed automatically.",
+        (true, false) => "New meetings are recorded automatically.",
+        (false, true) => "New meetings are transcribed automatically.",
+        (false, false) => "New meetings aren't recorded or transcribed unless asked to.",
+    };
+    Ok(SlackResponse::ephemeral(format!(
+        "{} {}\n{}",
+        if value.is_empty() { "ℹ️" } else { "✅" },
+        current,
+        usage
+    )))
+}
+
+/// Shows the workspace's working hours without a value, and otherwise sets
+/// them, or goes back to the default with `default`.
+async fn set_working_hours(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change them with `{} set team working-hours <start>-<end>|default`. Scheduling a meeting outside them for someone it mentions asks for confirmation first.",
+        payload.command
+    );
+
+    match value.to_ascii_lowercase().as_str() {
+        "" => {}
+        "default" => {
+            state
+                .db
+                .delete_team_setting(&payload.team_id, working_hours::SETTING_KEY)
+                .await?;
+            record_change(state, payload, working_hours::SETTING_KEY, false).await;
+        }
+        _ => {
+            let Ok(hours) = value.parse::<WorkingHours>() else {
+                return Ok(SlackResponse::ephemeral(format!(
+                    "❌ `{}` isn't a range of whole hours like `9-18`.\n{}",
+                    value, usage
+                )));
+            };
+            state
+                .db
+                .set_team_setting(
+                    &payload.team_id,
+                    working_hours::SETTING_KEY,
+                    &format!("{}-{}", hours.start, hours.end),
+                )
+                .await?;
+            record_change(state, payload, working_hours::SETTING_KEY, true).await;
+        }
+    }
+
+    let hours = working_hours::for_team(&state.db, &payload.team_id).await?;
+    Ok(SlackResponse::ephemeral(format!(
+        "{} Working hours are {}, in each attendee's own timezone.\n{}",
+        if value.is_empty() { "ℹ️" } else { "✅" },
+        hours,
+        usage
+    )))
+}
+
+async fn set_daily_limit(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{} set team daily-meeting-limit <number> [{}]|off`; with `{}` the bot's admins can go past it. Days start at midnight in the workspace's timezone, `{} set team timezone`.",
+        payload.command,
+        quota::EXEMPT_ADMINS,
+        quota::EXEMPT_ADMINS,
+        payload.command
+    );
+
+    match value.to_ascii_lowercase().as_str() {
+        "" => {}
+        "off" => {
+            state
+                .db
+                .delete_team_setting(&payload.team_id, quota::LIMIT_KEY)
+                .await?;
+            record_change(state, payload, quota::LIMIT_KEY, false).await;
+        }
+        _ => {
+            let Ok(limit) = value.parse::<DailyLimit>() else {
+                return Ok(SlackResponse::ephemeral(format!(
+                    "❌ `{}` isn't a number of meetings like `20`.\n{}",
+                    value, usage
+                )));
+            };
+            state
+                .db
+                .set_team_setting(&payload.team_id, quota::LIMIT_KEY, &limit.to_string())
+                .await?;
+            record_change(state, payload, quota::LIMIT_KEY, true).await;
+        }
+    }
+
+    let status = if value.is_empty() { "ℹ️" } else { "✅" };
+    let text = match quota::for_team(&state.db, &payload.team_id).await? {
+        None => format!(
+            "{} The workspace can create any number of meetings a day.\n{}",
+            status, usage
+        ),
+        Some(limit) => format!(
+            "{} The workspace can create {} meetings a day{}.\n{}",
+            status,
+            limit.meetings,
+            if limit.exempt_admins {
+                ", not counting the bot's admins"
+            } else {
+                ""
+            },
+            usage
+        ),
+    };
+    Ok(SlackResponse::ephemeral(text))
+}
+
+async fn set_timezone(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    let usage = format!(
+        "Change it with `{} set team timezone <Area/City>|default`, as in `Europe/Warsaw`. The workspace's days, for its daily meeting limit, start at midnight there.",
+        payload.command
+    );
+
+    match value {
+        "" => {}
+        "default" => {
+            state
+                .db
+                .delete_team_setting(&payload.team_id, quota::TIMEZONE_KEY)
+                .await?;
+            record_change(state, payload, quota::TIMEZONE_KEY, false).await;
+        }
+        _ => {
+            let Ok(tz) = value.parse::<chrono_tz::Tz>() else {
+                return Ok(SlackResponse::ephemeral(format!(
+                    "❌ `{}` isn't a timezone name like `Europe/Warsaw`.\n{}",
+                    value, usage
+                )));
+            };
+            state
+                .db
+                .set_team_setting(&payload.team_id, quota::TIMEZONE_KEY, tz.name())
+                .await?;
+            record_change(state, payload, quota::TIMEZONE_KEY, true).await;
+        }
+    }
+
+    let tz = quota::team_timezone(&state.db, &payload.team_id).await?;
+    Ok(SlackResponse::ephemeral(format!(
+        "{} The workspace's timezone is {}.\n{}",
+        if value.is_empty() { "ℹ️" } else { "✅" },
+        tz.name(),
+        usage
+    )))
+}
+
+/// What `template` makes of a meeting titled [`EXAMPLE_TEXT`] created now
+/// in the channel of `payload`.
+async fn example(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    template: &TitleTemplate,
+) -> String {
+    let date =
+        timezones::local_date(state, &payload.team_id, &payload.user_id, state.clock.now()).await;
+    let title = template.render(&TitleValues {
+        channel: &payload.channel_name,
+        user: &payload.user_name,
+        date,
+        text: Some(EXAMPLE_TEXT),
+    });
+    blocks::escape(&title.unwrap_or_default())
+}
+
+async fn record_change(state: &AppState, payload: &SlashCommandPayload, key: &str, set: bool) {
+    info!(
+        "{} {} setting {} of team {}",
+        payload.user_id,
+        if set { "set" } else { "dropped" },
+        key,
+        payload.team_id
+    );
+    audit::record(
+        &state.db,
+        AuditEvent::team_setting_changed(&payload.user_id, &payload.team_id, key, set),
+    )
+    .await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{payload, test_state_with, RESPONSE_URL};
+    use super::super::CommandRegistry;
+    use super::*;
+    use crate::command_parser;
+    use crate::config::Config;
+    use crate::database::models::OAuthToken;
+    use crate::features::FeatureFlags;
+    use crate::google::fake::FakeGoogleApi;
+    use std::sync::Arc;
+
+    async fn state(admin: bool) -> AppState {
+        let mut config = Config::for_tests();
+        config.features = FeatureFlags::new([Feature::Settings]);
+        if admin {
+            config.admin.slack_users = vec!["U012AB3CD".to_string()];
+        }
+        test_state_with(Arc::new(FakeGoogleApi::succeeding()), config)
+            .await
+            .0
+    }
+
+    async fn run(state: &AppState, text: &str) -> String {
+        let command = command_parser::parse(text).unwrap();
+        CommandRegistry::new()
+            .dispatch(state.clone(), payload(text, RESPONSE_URL), command, false)
+            .await
+            .unwrap()
+            .text
+    }
+
+    #[tokio::test]
+    async fn test_title_template_is_set_shown_and_dropped() {
+        let state = state(true).await;
+        let today = state.clock.now().format("%Y-%m-%d");
+
+        let text = run(
+            &state,
+            "set team title-template \"[#{channel}] {text} — {date}\"",
+        )
+        .await;
+        assert_eq!(
+            text,
+            format!(
+                "✅ New meetings in this workspace will be titled like *[#general] Standup — {}*.",
+                today
+            )
+        );
+        let text = run(&state, "set team title-template").await;
+        assert!(
+            text.starts_with(
+                "Meetings in this workspace are titled `[#{channel}] {text} — {date}`"
+            ),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "set team title-template off").await;
+        assert_eq!(text, "✅ Meetings are titled as typed again.");
+        assert!(title_template::for_team(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].event_type, "team_setting_changed");
+        assert_eq!(audit[0].detail["set"], false);
+        assert_eq!(audit[1].detail["key"], "title_template");
+    }
+
+    #[tokio::test]
+    async fn test_title_template_is_validated() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team title-template {channel} {title}").await;
+
+        assert!(
+            text.starts_with("❌ `{title}` isn't a placeholder"),
+            "{}",
+            text
+        );
+        assert!(title_template::for_team(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_only_admins_change_team_settings() {
+        let state = state(false).await;
+
+        let text = run(&state, "set team title-template {text}").await;
+
+        assert!(text.starts_with('🚫'), "{}", text);
+        let text = run(&state, "set color 6").await;
+        assert_eq!(
+            text,
+            "❓ There's no setting `color`. Settings: `visibility`, `reuse-personal-space`, `reminder`, `lend-account`, `team title-template`, `team default-title`, `team allow-channel`, `team deny-channel`, `team shared-account`, `team announce-emoji`, `team announce-prefix`, `team announce-mention`, `team auto-record`, `team auto-transcribe`, `team working-hours`, `team daily-meeting-limit`, `team timezone`."
+        );
+        let text = run(&state, "set team deny-channel <#C012AB3CD|general>").await;
+        assert!(text.starts_with('🚫'), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_default_title_mode_is_set() {
+        let state = state(true).await;
+        let today = state.clock.now().format("%b %-d");
+
+        let text = run(&state, "set team default-title").await;
+        assert!(
+            text.starts_with(&format!(
+                "Meetings without a title are named `channel`, as in *#general sync — {}*",
+                today
+            )),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "set team default_title generic").await;
+        assert_eq!(
+            text,
+            format!(
+                "✅ Meetings without a title will be named like *Meeting — {}*.",
+                today
+            )
+        );
+        assert_eq!(
+            title_template::default_title_mode(&state.db, "T012AB3C4")
+                .await
+                .unwrap(),
+            DefaultTitleMode::Generic
+        );
+
+        let text = run(&state, "set team default-title weekly").await;
+        assert!(
+            text.starts_with("❌ `weekly` isn't a default title."),
+            "{}",
+            text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_announcement_style_is_set() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team announce-emoji").await;
+        assert!(
+            text.starts_with(
+                "New meetings are announced as “🎥 Google Meet created by <@U012AB3CD>”."
+            ),
+            "{}",
+            text
+        );
+
+        run(&state, "set team announce-emoji :video_camera:").await;
+        let text = run(&state, "set team announce-prefix Video call started").await;
+        assert_eq!(
+            text,
+            "✅ New meetings will be announced as “:video_camera: Video call started by <@U012AB3CD>”."
+        );
+        let text = run(&state, "set team announce_mention off").await;
+        assert_eq!(
+            text,
+            "✅ New meetings will be announced as “:video_camera: Video call started”."
+        );
+
+        let text = run(&state, "set team announce-prefix *Loud* call").await;
+        assert!(
+            text.starts_with("❌ Announcement wording contains invalid characters."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team announce-emoji camera").await;
+        assert!(
+            text.starts_with("❌ Announcement emoji has an invalid format."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team announce-mention sometimes").await;
+        assert!(
+            text.starts_with("❌ `announce-mention` is `on` or `off`."),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "set team announce-emoji default").await;
+        assert_eq!(
+            text,
+            "✅ New meetings will be announced as “🎥 Video call started”."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recording_defaults_are_set() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team auto-record").await;
+        assert!(
+            text.starts_with("ℹ️ New meetings aren't recorded or transcribed unless asked to."),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "set team auto-record on").await;
+        assert!(
+            text.starts_with("✅ New meetings are recorded automatically."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team auto_transcribe ON").await;
+        assert!(
+            text.starts_with("✅ New meetings are recorded and transcribed automatically."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team auto-record always").await;
+        assert!(
+            text.starts_with("❌ `auto-record` is `on` or `off`."),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "set team auto-record off").await;
+        assert!(
+            text.starts_with("✅ New meetings are transcribed automatically."),
+            "{}",
+            text
+        );
+        assert_eq!(
+            recording::for_team(&state.db, "T012AB3C4").await.unwrap(),
+            crate::google::Artifacts {
+                recording: false,
+                transcription: true,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_working_hours_are_set() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team working-hours").await;
+        assert!(
+            text.starts_with("ℹ️ Working hours are 9:00–18:00"),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team working-hours 08:00-16:00").await;
+        assert!(
+            text.starts_with("✅ Working hours are 8:00–16:00"),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team working-hours 9-5").await;
+        assert!(
+            text.starts_with("❌ `9-5` isn't a range of whole hours"),
+            "{}",
+            text
+        );
+        assert_eq!(
+            working_hours::for_team(&state.db, "T012AB3C4")
+                .await
+                .unwrap(),
+            WorkingHours { start: 8, end: 16 }
+        );
+
+        let text = run(&state, "set team working-hours default").await;
+        assert!(
+            text.starts_with("✅ Working hours are 9:00–18:00"),
+            "{}",
+            text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_daily_limit_and_timezone_are_set() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team daily-meeting-limit").await;
+        assert!(
+            text.starts_with("ℹ️ The workspace can create any number of meetings a day."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team daily-meeting-limit 20 exempt-admins").await;
+        assert!(
+            text.starts_with(
+                "✅ The workspace can create 20 meetings a day, not counting the bot's admins."
+            ),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team daily-meeting-limit twenty").await;
+        assert!(
+            text.starts_with("❌ `twenty` isn't a number of meetings"),
+            "{}",
+            text
+        );
+        assert_eq!(
+            quota::for_team(&state.db, "T012AB3C4").await.unwrap(),
+            Some(DailyLimit {
+                meetings: 20,
+                exempt_admins: true
+            })
+        );
+        let text = run(&state, "set team daily-meeting-limit off").await;
+        assert!(
+            text.starts_with("✅ The workspace can create any number"),
+            "{}",
+            text
+        );
+
+        let text = run(&state, "set team timezone").await;
+        assert!(
+            text.starts_with("ℹ️ The workspace's timezone is UTC."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team timezone Asia/Tokyo").await;
+        assert!(
+            text.starts_with("✅ The workspace's timezone is Asia/Tokyo."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set team timezone Mars/Olympus_Mons").await;
+        assert!(
+            text.starts_with("❌ `Mars/Olympus_Mons` isn't a timezone"),
+            "{}",
+            text
+        );
+        assert_eq!(
+            quota::team_timezone(&state.db, "T012AB3C4").await.unwrap(),
+            chrono_tz::Asia::Tokyo
+        );
+    }
+
+    #[tokio::test]
+    async fn test_channel_lists_are_edited() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team allow-channel <#C012AB3CD|general>").await;
+        assert_eq!(text, "✅ Meetings can only be created in <#C012AB3CD>.");
+        run(&state, "set team allow-channel C098ZY7XW").await;
+        let text = run(&state, "set team deny_channel <#C012AB3CD>").await;
+        assert_eq!(
+            text,
+            "✅ Meetings can only be created in <#C012AB3CD>, <#C098ZY7XW>, never in <#C012AB3CD>."
+        );
+        let policy = channel_policy::for_team(&state.db, "T012AB3C4")
+            .await
+            .unwrap();
+        assert!(policy.check("C012AB3CD").is_err());
+        assert!(policy.check("C098ZY7XW").is_ok());
+
+        let text = run(&state, "set team allow-channel remove <#C098ZY7XW>").await;
+        assert_eq!(
+            text,
+            "✅ Meetings can only be created in <#C012AB3CD>, never in <#C012AB3CD>."
+        );
+        let text = run(&state, "set team allow-channel clear").await;
+        assert_eq!(
+            text,
+            "✅ Meetings can be created in any channel, never in <#C012AB3CD>."
+        );
+        let text = run(&state, "set team deny-channel").await;
+        assert!(
+            text.starts_with("Meetings can be created in any channel, never in <#C012AB3CD>.\n"),
+            "{}",
+            text
+        );
+
+        // Without escaping, Slack sends just the name
+        let text = run(&state, "set team deny-channel #general").await;
+        assert!(
+            text.starts_with("❌ I can't tell which channel `#general` is."),
+            "{}",
+            text
+        );
+
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 5);
+        assert_eq!(audit[0].detail["key"], "allowed_channels");
+        assert_eq!(audit[0].detail["set"], false);
+    }
+
+    #[tokio::test]
+    async fn test_visibility_is_a_user_setting() {
+        let state = state(false).await;
+
+        let text = run(&state, "set visibility").await;
+        assert!(
+            text.starts_with("Your new meetings are posted in the channel.\n"),
+            "{}",
+            text
+        );
+        let text = run(&state, "set visibility Quiet").await;
+        assert_eq!(text, "✅ Your new meetings are shown only to you.");
+        let user = state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            state
+                .db
+                .get_user_setting(user.id, MeetingVisibility::SETTING_KEY)
+                .await
+                .unwrap()
+                .as_deref(),
+            Some("quiet")
+        );
+
+        let text = run(&state, "set visibility secret").await;
+        assert!(
+            text.starts_with("❌ `secret` isn't a visibility."),
+            "{}",
+            text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_standing_room_is_a_user_setting() {
+        let state = state(false).await;
+
+        let text = run(&state, "set reuse-personal-space").await;
+        assert!(
+            text.starts_with("ℹ️ `/meet` creates a new meeting every time.\n"),
+            "{}",
+            text
+        );
+        let text = run(&state, "set reuse_personal_space ON").await;
+        assert!(
+            text.starts_with("✅ `/meet` gives you the same standing room every time.\n"),
+            "{}",
+            text
+        );
+        let user = state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(personal_space::enabled(&state, &user).await);
+
+        let text = run(&state, "set reuse-personal-space always").await;
+        assert!(
+            text.starts_with("❌ `reuse-personal-space` is `on` or `off`."),
+            "{}",
+            text
+        );
+        run(&state, "set reuse-personal-space off").await;
+        assert!(!personal_space::enabled(&state, &user).await);
+    }
+
+    #[tokio::test]
+    async fn test_reminder_lead_is_a_user_setting() {
+        let state = state(false).await;
+
+        let text = run(&state, "set reminder").await;
+        assert!(
+            text.starts_with(
+                "ℹ️ Your scheduled meetings get a reminder in the channel 10 minutes before they start.\n"
+            ),
+            "{}",
+            text
+        );
+        let text = run(&state, "set reminder 60").await;
+        assert!(
+            text.starts_with(
+                "✅ Your scheduled meetings get a reminder in the channel 1 hour before they start.\n"
+            ),
+            "{}",
+            text
+        );
+        let user = state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            reminders::lead_for(&state.db, &user, None).await,
+            Some(chrono::Duration::minutes(60))
+        );
+
+        let text = run(&state, "set reminder 1441").await;
+        assert!(
+            text.starts_with("❌ `1441` isn't `off` or a number of minutes up to 1440."),
+            "{}",
+            text
+        );
+        let text = run(&state, "set reminder off").await;
+        assert!(
+            text.starts_with("✅ Your scheduled meetings get no reminder.\n"),
+            "{}",
+            text
+        );
+        assert_eq!(
+            reminders::lead_for(&state.db, &user, Some(chrono::Duration::minutes(10))).await,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_shared_account_needs_a_connected_member() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team shared-account <@U0SHARED1|bot>").await;
+        assert_eq!(
+            text,
+            "❌ <@U0SHARED1> hasn't connected a Google account. They can with `/meet status`."
+        );
+
+        let owner = state
+            .db
+            .create_user("U0SHARED1", "T012AB3C4")
+            .await
+            .unwrap();
+        state
+            .db
+            .store_oauth_token(&OAuthToken::new(
+                owner.id,
+                "access".into(),
+                None,
+                None,
+                None,
+            ))
+            .await
+            .unwrap();
+        let text = run(&state, "set team shared-account <@U0SHARED1|bot>").await;
+        assert_eq!(
+            text,
+            "❌ <@U0SHARED1> hasn't agreed to share their Google account. They can with `/meet set lend-account on`."
+        );
+        assert!(shared_account::for_team(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+
+        state
+            .db
+            .set_user_setting(owner.id, shared_account::CONSENT_KEY, "true")
+            .await
+            .unwrap();
+        let text = run(&state, "set team shared-account <@U0SHARED1|bot>").await;
+        assert!(
+            text.starts_with("✅ Meetings in this workspace will be created with <@U0SHARED1>'s"),
+            "{}",
+            text
+        );
+        let account = shared_account::for_team(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.slack_user_id, "U0SHARED1");
+        assert_eq!(account.enabled_by.as_deref(), Some("U012AB3CD"));
+
+        let text = run(&state, "set team shared-account off").await;
+        assert_eq!(
+            text,
+            "✅ Everyone creates meetings with their own Google account again."
+        );
+        assert!(shared_account::for_team(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shared_account_input_isnt_echoed() {
+        let state = state(true).await;
+
+        let text = run(&state, "set team shared-account <script>").await;
+
+        assert!(
+            text.starts_with("❌ I can't tell who that is. Mention them with `@`.\n"),
+            "{}",
+            text
+        );
+        assert!(!text.contains("<script>"), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_taking_back_a_lent_account_ends_shared_mode() {
+        // Not an admin: lending is each member's own setting
+        let state = state(false).await;
+        let text = run(&state, "set lend-account").await;
+        assert_eq!(
+            text,
+            "ℹ️ Your Google account is only used for your own meetings.\nChange it with `/meet set lend-account on|off`."
+        );
+
+        let text = run(&state, "set lend-account on").await;
+        assert!(
+            text.starts_with("✅ The bot's admins may have everyone's meetings"),
+            "{}",
+            text
+        );
+        let user = state
+            .db
+            .get_user_by_slack_id("U012AB3CD")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(shared_account::consents(&state.db, user.id).await.unwrap());
+
+        shared_account::enable(&state.db, "T012AB3C4", "U012AB3CD", "U0ADMIN01")
+            .await
+            .unwrap();
+        let text = run(&state, "set lend-account off").await;
+        assert!(
+            text.starts_with("✅ Your Google account is only used for your own meetings again"),
+            "{}",
+            text
+        );
+        assert!(!shared_account::consents(&state.db, user.id).await.unwrap());
+        assert!(shared_account::for_team(&state.db, "T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("\"{channel} {text}\""), "{channel} {text}");
+        assert_eq!(unquote("“{text}”"), "{text}");
+        assert_eq!(unquote("{text}"), "{text}");
+        assert_eq!(unquote("\"{text}"), "\"{text}");
+    }
+}
@@ -0,0 +1,252 @@
+//! `/meet share-link [link] [--off]`: a short link to the user's latest
+//! meeting, or the one at a Meet link or code, for pasting where a Meet
+//! link is unwieldy. `--off` stops the link redirecting.
+
+use axum::async_trait;
+use tracing::info;
+
+use super::attendance::meeting_label;
+use super::{CommandContext, CommandHandler};
+use crate::command_parser::MeetCommand;
+use crate::database::models::{Meeting, User};
+use crate::error::AppError;
+use crate::handlers::slack::SlackResponse;
+use crate::observability::{self, Phase};
+use crate::short_links;
+use crate::AppState;
+
+pub struct ShareLinkHandler;
+
+#[async_trait]
+impl CommandHandler for ShareLinkHandler {
+    fn name(&self) -> &'static str {
+        "share-link"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        let (meeting, off) = match ctx.command {
+            MeetCommand::ShareLink { meeting, off } => (meeting, off),
+            _ => (None, false),
+        };
+        handle_share_link(ctx.state, user, meeting, off).await
+    }
+}
+
+async fn handle_share_link(
+    state: AppState,
+    user: User,
+    target: Option<String>,
+    off: bool,
+) -> Result<SlackResponse, AppError> {
+    let meeting = match find_meeting(&state, &user, target.as_deref()).await? {
+        Ok(meeting) => meeting,
+        Err(reply) => return Ok(SlackResponse::ephemeral(reply)),
+    };
+    let Some(meeting_id) = meeting.id else {
+        return Err(AppError::Internal(anyhow::anyhow!(
+            "stored meeting without an id"
+        )));
+    };
+    let label = meeting_label(&meeting);
+
+    if off {
+        let turned_off = observability::timed(
+            Phase::Database,
+            state.db.set_short_link_public(meeting_id, false),
+        )
+        .await?;
+        info!(
+            "User {} turned off the short link of meeting {}",
+            user.id, meeting_id
+        );
+        return Ok(SlackResponse::ephemeral(if turned_off {
+            format!("🔗 The short link to {} no longer works.", label)
+        } else {
+            format!("🔗 {} has no short link.", label)
+        }));
+    }
+
+    let slug = observability::timed(
+        Phase::Database,
+        short_links::publish(&state.db, meeting_id, short_links::generate),
+    )
+    .await?;
+    info!(
+        "User {} shared a short link to meeting {}",
+        user.id, meeting_id
+    );
+    Ok(SlackResponse::ephemeral(format!(
+        "🔗 Short link to {}: {}\nAnyone with it can join. Run this command with `--off` to stop it working.",
+        label,
+        short_links::url(state.config.google.public_base_url(), &slug)
+    )))
+}
+
+/// The user's latest meeting, or theirs at `target`; `Err` with the reply
+/// when there is none.
+async fn find_meeting(
+    state: &AppState,
+    user: &User,
+    target: Option<&str>,
+) -> Result<Result<Meeting, String>, AppError> {
+    let Some(target) = target else {
+        let meetings =
+            observability::timed(Phase::Database, state.db.get_user_meetings(user.id, 1)).await?;
+        return Ok(meetings
+            .into_iter()
+            .next()
+            .ok_or_else(|| "🔗 You have no meetings to share yet.".to_string()));
+    };
+
+    let link = if target.contains('/') {
+        target.to_string()
+    } else {
+        format!("https://meet.google.com/{}", target)
+    };
+    let Ok(link) = state.validator.validate_meet_link(&link) else {
+        return Ok(Err(
+            "❌ That isn't a Google Meet link or meeting code.".to_string()
+        ));
+    };
+    let meeting = observability::timed(
+        Phase::Database,
+        state.db.get_user_meeting_by_link(user.id, &link),
+    )
+    .await?;
+    Ok(meeting.ok_or_else(|| format!("🔗 You haven't created a meeting at {}.", link)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::test_state;
+    use super::*;
+    use crate::database::models::MeetLinkKind;
+
+    async fn create(state: &AppState, user: &User, code: &str) -> Meeting {
+        state
+            .db
+            .create_meeting(&Meeting::new(
+                user.id,
+                format!("https://meet.google.com/{}", code),
+                Some("Incident bridge".to_string()),
+                MeetLinkKind::Meet,
+            ))
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_shares_and_turns_off_a_link() {
+        let (state, _pool) = test_state().await;
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+        let meeting = create(&state, &user, "abc-defg-hij").await;
+        create(&state, &user, "xyz-abcd-efg").await;
+
+        let response = handle_share_link(
+            state.clone(),
+            user.clone(),
+            Some("abc-defg-hij".to_string()),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.response_type, "ephemeral");
+        let link = state
+            .db
+            .meeting_short_link(meeting.id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(link.public);
+        let url = short_links::url(state.config.google.public_base_url(), &link.slug);
+        assert!(
+            response.text.contains(&url) && response.text.contains("abc-defg-hij"),
+            "{}",
+            response.text
+        );
+
+        let response = handle_share_link(
+            state.clone(),
+            user,
+            Some("https://meet.google.com/abc-defg-hij".to_string()),
+            true,
+        )
+        .await
+        .unwrap();
+        assert!(
+            response.text.contains("no longer works"),
+            "{}",
+            response.text
+        );
+        assert_eq!(state.db.follow_short_link(&link.slug).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_defaults_to_the_latest_meeting() {
+        let (state, pool) = test_state().await;
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+
+        let response = handle_share_link(state.clone(), user.clone(), None, false)
+            .await
+            .unwrap();
+        assert_eq!(response.text, "🔗 You have no meetings to share yet.");
+
+        let older = create(&state, &user, "abc-defg-hij").await;
+        sqlx::query("UPDATE meetings SET created_at = '2020-01-01 00:00:00' WHERE id = ?")
+            .bind(older.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let latest = create(&state, &user, "xyz-abcd-efg").await;
+        handle_share_link(state.clone(), user.clone(), None, false)
+            .await
+            .unwrap();
+        assert!(state
+            .db
+            .meeting_short_link(latest.id.unwrap())
+            .await
+            .unwrap()
+            .is_some());
+
+        let response = handle_share_link(state, user, Some("aaa-bbbb-ccc".to_string()), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            response.text,
+            "🔗 You haven't created a meeting at https://meet.google.com/aaa-bbbb-ccc."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bad_links_arent_echoed() {
+        let (state, _pool) = test_state().await;
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+
+        let response = handle_share_link(
+            state,
+            user,
+            Some("https://evil.example/<!channel>".to_string()),
+            false,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            response.text,
+            "❌ That isn't a Google Meet link or meeting code."
+        );
+    }
+}
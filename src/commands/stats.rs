@@ -0,0 +1,188 @@
+//! `/meet stats`: a summary of the meetings the user created.
+
+use axum::async_trait;
+
+use super::{CommandContext, CommandHandler};
+use crate::error::AppError;
+use crate::handlers::slack::SlackResponse;
+use crate::observability::{self, Phase};
+use crate::{stats, timezones};
+
+pub struct StatsHandler;
+
+#[async_trait]
+impl CommandHandler for StatsHandler {
+    fn name(&self) -> &'static str {
+        "stats"
+    }
+
+    async fn handle(&self, mut ctx: CommandContext) -> Result<SlackResponse, AppError> {
+        let user = ctx.take_user()?;
+        // Weeks and days are counted where the user is: in the timezone set
+        // in their Slack profile, or else the workspace's.
+        let tz = timezones::of_user(&ctx.state, &ctx.payload.team_id, &ctx.payload.user_id).await?;
+        let now = ctx.state.clock.now().with_timezone(&tz);
+        let stats = observability::timed(
+            Phase::Database,
+            stats::usage_stats(&ctx.state.db, user.id, now),
+        )
+        .await?;
+
+        Ok(SlackResponse::usage_stats(&stats, &ctx.payload.command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{
+        connected_user, payload, test_state, Fixture, TestState, RESPONSE_URL,
+    };
+    use super::super::CommandRegistry;
+    use crate::command_parser;
+    use crate::database::models::{MeetLinkKind, Meeting};
+    use crate::slack::blocks;
+    use crate::time::TestClock;
+    use crate::{quota, AppState};
+    use sqlx::SqlitePool;
+
+    async fn stats(state: &crate::AppState) -> crate::handlers::slack::SlackResponse {
+        CommandRegistry::new()
+            .dispatch(
+                state.clone(),
+                payload("stats", RESPONSE_URL),
+                command_parser::parse("stats").unwrap(),
+                false,
+            )
+            .await
+            .unwrap()
+    }
+
+    /// Meetings of the connected user created at each of `created_at`, in UTC.
+    async fn meetings_at(state: &AppState, pool: &SqlitePool, created_at: &[&str]) {
+        let user = connected_user(state).await;
+        for created_at in created_at {
+            let meeting = state
+                .db
+                .create_meeting(&Meeting::new(
+                    user.id,
+                    "https://meet.google.com/abc-defg-hij".to_string(),
+                    None,
+                    MeetLinkKind::Meet,
+                ))
+                .await
+                .unwrap();
+            sqlx::query("UPDATE meetings SET created_at = ? WHERE id = ?")
+                .bind(created_at)
+                .bind(meeting.id)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_weeks_are_counted_in_the_users_timezone() {
+        let TestState {
+            mut state,
+            slack,
+            pool,
+            ..
+        } = Fixture::new().installed().build().await;
+        slack.set_timezone("U012AB3CD", "America/New_York");
+        // Monday 15 April, 10:00 in New York
+        state.clock = TestClock::new("2024-04-15T14:00:00Z".parse().unwrap());
+        // Sunday 23:30 and Monday 0:30 in New York, both Monday in UTC
+        meetings_at(
+            &state,
+            &pool,
+            &["2024-04-15 03:30:00", "2024-04-15 04:30:00"],
+        )
+        .await;
+
+        let response = stats(&state).await;
+
+        assert!(
+            response.text.starts_with(
+                "Your meeting stats: 1 this week, 2 this month, 2 in all. Longest streak: 2 days."
+            ),
+            "{}",
+            response.text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_the_workspace_timezone_is_used_otherwise() {
+        let (mut state, pool) = test_state().await;
+        state
+            .db
+            .set_team_setting("T012AB3C4", quota::TIMEZONE_KEY, "Asia/Tokyo")
+            .await
+            .unwrap();
+        // Monday 15 April, 9:30 in Tokyo
+        state.clock = TestClock::new("2024-04-15T00:30:00Z".parse().unwrap());
+        // Monday 0:30 in Tokyo, still Sunday in UTC
+        meetings_at(&state, &pool, &["2024-04-14 15:30:00"]).await;
+
+        let response = stats(&state).await;
+
+        assert!(
+            response
+                .text
+                .starts_with("Your meeting stats: 1 this week, 1 this month, 1 in all."),
+            "{}",
+            response.text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_users_without_meetings_are_told_so() {
+        let (state, _pool) = test_state().await;
+
+        let response = stats(&state).await;
+
+        assert_eq!(response.response_type, "ephemeral");
+        assert!(
+            response
+                .text
+                .starts_with("You haven't created any meetings yet"),
+            "{}",
+            response.text
+        );
+        assert!(response.blocks.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stats_are_shown_as_fields() {
+        let (state, _pool) = test_state().await;
+        let user = connected_user(&state).await;
+        for title in ["Standup", "Team standup", "Retro"] {
+            state
+                .db
+                .create_meeting(&Meeting::new(
+                    user.id,
+                    "https://meet.google.com/abc-defg-hij".to_string(),
+                    Some(title.to_string()),
+                    MeetLinkKind::Meet,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let response = stats(&state).await;
+
+        assert_eq!(response.response_type, "ephemeral");
+        assert_eq!(
+            response.text,
+            "Your meeting stats: 3 this week, 3 this month, 3 in all. Longest streak: 1 day. \
+             Top title words: standup (2), retro (1), team (1)."
+        );
+        let blocks = response.blocks.unwrap();
+        blocks::validate(&blocks).unwrap();
+        let json = serde_json::to_value(&blocks).unwrap();
+        assert_eq!(json[1]["fields"][2]["text"], "*All time*\n3");
+        assert_eq!(
+            json[2]["text"]["text"],
+            "*Most used title words*\nstandup (2) · retro (1) · team (1)"
+        );
+    }
+}
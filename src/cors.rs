@@ -0,0 +1,61 @@
+use axum::http::{header, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// CORS policy for the pages people open in a browser (the Google sign-in,
+/// exports, short links and calendar files), which other origins' pages may
+/// fetch too. Only `allowed_origins` get CORS headers back; with none
+/// configured, browsers refuse every cross-origin call.
+///
+/// Slack talks to us server-to-server, so its routes don't get this layer.
+pub fn browser_layer(allowed_origins: &[HeaderValue]) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::list(allowed_origins.iter().cloned()))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([header::CONTENT_TYPE])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn preflight(allowed: &[HeaderValue], origin: &str) -> Option<HeaderValue> {
+        let app = Router::new()
+            .route("/me", get(|| async { "settings" }))
+            .layer(browser_layer(allowed));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::OPTIONS)
+                    .uri("/me")
+                    .header(header::ORIGIN, origin)
+                    .header(header::ACCESS_CONTROL_REQUEST_METHOD, "GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        response
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn test_allows_configured_origin() {
+        let allowed = [HeaderValue::from_static("https://meet.example.com")];
+        assert_eq!(
+            preflight(&allowed, "https://meet.example.com").await,
+            Some(HeaderValue::from_static("https://meet.example.com"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejects_other_origins() {
+        let allowed = [HeaderValue::from_static("https://meet.example.com")];
+        assert_eq!(preflight(&allowed, "https://evil.example.com").await, None);
+        assert_eq!(preflight(&[], "https://meet.example.com").await, None);
+    }
+}
@@ -0,0 +1,148 @@
+use aes_gcm::{
+    aead::{generic_array::typenum::Unsigned, Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm,
+};
+use anyhow::anyhow;
+use chacha20poly1305::XChaCha20Poly1305;
+use std::str::FromStr;
+
+use super::CryptoError;
+
+/// The AEAD used for new ciphertexts. Every key works with both, and each
+/// ciphertext records which one produced it, so data written under either
+/// setting stays readable after `TOKEN_CIPHER` changes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CipherKind {
+    /// AES-256-GCM with a 96-bit random nonce.
+    #[default]
+    Aes256Gcm,
+    /// XChaCha20-Poly1305 with a 192-bit random nonce, which makes nonce
+    /// collisions a non-issue even at very high volume.
+    XChaCha20Poly1305,
+}
+
+impl CipherKind {
+    pub const ALL: [CipherKind; 2] = [CipherKind::Aes256Gcm, CipherKind::XChaCha20Poly1305];
+
+    /// Version prefix of ciphertexts made with this cipher:
+    /// `<prefix><key id>:<base64 nonce || ciphertext>`.
+    pub(super) fn prefix(self) -> &'static str {
+        match self {
+            CipherKind::Aes256Gcm => "v2:",
+            CipherKind::XChaCha20Poly1305 => "v2x:",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CipherKind::Aes256Gcm => "aes256gcm",
+            CipherKind::XChaCha20Poly1305 => "xchacha20",
+        }
+    }
+}
+
+impl FromStr for CipherKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "aes256gcm" | "aes-256-gcm" => Ok(CipherKind::Aes256Gcm),
+            "xchacha20" | "xchacha20poly1305" | "xchacha20-poly1305" => {
+                Ok(CipherKind::XChaCha20Poly1305)
+            }
+            _ => Err(anyhow!(
+                "TOKEN_CIPHER must be aes256gcm or xchacha20, got {:?}",
+                value
+            )),
+        }
+    }
+}
+
+/// One 32-byte key, set up for every supported cipher.
+#[derive(Clone)]
+pub(super) struct KeyCiphers {
+    aes: Aes256Gcm,
+    xchacha: XChaCha20Poly1305,
+}
+
+impl KeyCiphers {
+    pub(super) fn new(key: &[u8; 32]) -> Self {
+        Self {
+            aes: Aes256Gcm::new(key.into()),
+            xchacha: XChaCha20Poly1305::new(key.into()),
+        }
+    }
+
+    /// Encrypts with a fresh random nonce and returns `nonce || ciphertext`.
+    pub(super) fn seal(
+        &self,
+        kind: CipherKind,
+        plaintext: &str,
+        aad: &[u8],
+    ) -> Result<Vec<u8>, CryptoError> {
+        match kind {
+            CipherKind::Aes256Gcm => seal_with(&self.aes, plaintext, aad),
+            CipherKind::XChaCha20Poly1305 => seal_with(&self.xchacha, plaintext, aad),
+        }
+    }
+
+    pub(super) fn open(
+        &self,
+        kind: CipherKind,
+        combined: &[u8],
+        aad: &[u8],
+    ) -> Result<String, CryptoError> {
+        match kind {
+            CipherKind::Aes256Gcm => open_with(&self.aes, combined, aad),
+            CipherKind::XChaCha20Poly1305 => open_with(&self.xchacha, combined, aad),
+        }
+    }
+}
+
+fn seal_with<C: Aead + AeadCore>(
+    cipher: &C,
+    plaintext: &str,
+    aad: &[u8],
+) -> Result<Vec<u8>, CryptoError> {
+    let nonce = C::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext.as_bytes(),
+                aad,
+            },
+        )
+        .map_err(|_| CryptoError::EncryptionFailed)?;
+
+    let mut combined = nonce.to_vec();
+    combined.extend_from_slice(&ciphertext);
+    Ok(combined)
+}
+
+fn open_with<C: Aead + AeadCore>(
+    cipher: &C,
+    combined: &[u8],
+    aad: &[u8],
+) -> Result<String, CryptoError> {
+    // Anything shorter than a nonce and a tag can't be a ciphertext, even of
+    // an empty string, so don't hand it to the AEAD at all
+    let nonce_len = C::NonceSize::USIZE;
+    if combined.len() < nonce_len + C::TagSize::USIZE {
+        return Err(CryptoError::TooShort);
+    }
+
+    let (nonce_bytes, ciphertext) = combined.split_at(nonce_len);
+
+    let plaintext = cipher
+        .decrypt(
+            nonce_bytes.into(),
+            Payload {
+                msg: ciphertext,
+                aad,
+            },
+        )
+        .map_err(|_| CryptoError::DecryptionFailed)?;
+
+    String::from_utf8(plaintext).map_err(|_| CryptoError::NotUtf8)
+}
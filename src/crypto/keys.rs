@@ -0,0 +1,194 @@
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+
+/// A source of token encryption keys.
+///
+/// Providers return raw key material: either a single base64 key or a list
+/// of `id:key` entries (primary first) separated by commas or newlines.
+/// Implement this to fetch keys from a secret manager such as Vault or KMS.
+pub trait KeyProvider: Send + Sync {
+    /// Where the keys come from, for logs and error messages. Never includes
+    /// key material.
+    fn describe(&self) -> String;
+
+    fn load(&self) -> Result<String>;
+}
+
+/// Keys from an environment variable, as read when the configuration was
+/// loaded.
+pub struct EnvKeyProvider {
+    var: &'static str,
+    value: String,
+}
+
+impl EnvKeyProvider {
+    pub fn new(var: &'static str, value: String) -> Self {
+        Self { var, value }
+    }
+}
+
+impl KeyProvider for EnvKeyProvider {
+    fn describe(&self) -> String {
+        format!("environment variable {}", self.var)
+    }
+
+    fn load(&self) -> Result<String> {
+        Ok(self.value.clone())
+    }
+}
+
+/// Reads keys from a file, as delivered by Docker/Kubernetes secret mounts
+/// or systemd credentials. Surrounding whitespace is ignored.
+pub struct FileKeyProvider {
+    path: PathBuf,
+}
+
+impl FileKeyProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl KeyProvider for FileKeyProvider {
+    fn describe(&self) -> String {
+        format!("key file {}", self.path.display())
+    }
+
+    fn load(&self) -> Result<String> {
+        let contents = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("Failed to read key file {}", self.path.display()))?;
+
+        let contents = contents.trim();
+        if contents.is_empty() {
+            return Err(anyhow!("Key file {} is empty", self.path.display()));
+        }
+
+        Ok(contents.to_string())
+    }
+}
+
+/// Picks the key source from configuration, looked up through `var` (the
+/// process environment in production).
+///
+/// `TOKEN_ENCRYPTION_KEY_FILE` is used if set, and it is an error to also set
+/// `TOKEN_ENCRYPTION_KEYS` or `TOKEN_ENCRYPTION_KEY`, so there is never doubt
+/// about which key is in use. Otherwise `TOKEN_ENCRYPTION_KEYS` takes
+/// precedence over the single `TOKEN_ENCRYPTION_KEY`.
+pub fn provider_from_config(var: impl Fn(&str) -> Option<String>) -> Result<Box<dyn KeyProvider>> {
+    let file = var("TOKEN_ENCRYPTION_KEY_FILE").filter(|v| !v.trim().is_empty());
+    let keys = var("TOKEN_ENCRYPTION_KEYS");
+    let key = var("TOKEN_ENCRYPTION_KEY");
+
+    match (file, keys, key) {
+        (Some(_), Some(_), _) | (Some(_), _, Some(_)) => Err(anyhow!(
+            "TOKEN_ENCRYPTION_KEY_FILE cannot be combined with TOKEN_ENCRYPTION_KEYS or TOKEN_ENCRYPTION_KEY; set only one"
+        )),
+        (Some(path), None, None) => Ok(Box::new(FileKeyProvider::new(path.trim()))),
+        (None, Some(keys), _) => Ok(Box::new(EnvKeyProvider::new("TOKEN_ENCRYPTION_KEYS", keys))),
+        (None, None, Some(key)) => Ok(Box::new(EnvKeyProvider::new("TOKEN_ENCRYPTION_KEY", key))),
+        (None, None, None) => Err(anyhow!(
+            "No token encryption key configured: set TOKEN_ENCRYPTION_KEY_FILE, TOKEN_ENCRYPTION_KEYS or TOKEN_ENCRYPTION_KEY"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::TokenCrypto;
+    use std::collections::HashMap;
+    use std::io::Write;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn key_file(contents: &str) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_file_key_is_trimmed() {
+        let key = TokenCrypto::generate_key();
+        let file = key_file(&format!("  {}\n\n", key));
+
+        let provider = FileKeyProvider::new(file.path());
+        assert_eq!(provider.load().unwrap(), key);
+
+        let crypto = TokenCrypto::from_provider(&provider).unwrap();
+        let encrypted = crypto.encrypt_with_aad("token", b"aad").unwrap();
+        assert_eq!(
+            TokenCrypto::from_key(&key)
+                .unwrap()
+                .decrypt_with_aad(&encrypted, b"aad")
+                .unwrap(),
+            "token"
+        );
+    }
+
+    #[test]
+    fn test_file_with_labeled_keys() {
+        let file = key_file(&format!(
+            "k2:{}\nk1:{}\n",
+            TokenCrypto::generate_key(),
+            TokenCrypto::generate_key()
+        ));
+
+        let crypto = TokenCrypto::from_provider(&FileKeyProvider::new(file.path())).unwrap();
+        assert_eq!(crypto.primary_key_id(), "k2");
+    }
+
+    #[test]
+    fn test_missing_and_empty_files() {
+        let error = FileKeyProvider::new("/nonexistent/token.key")
+            .load()
+            .unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/token.key"));
+
+        let file = key_file(" \n");
+        let error = FileKeyProvider::new(file.path()).load().unwrap_err();
+        assert!(error.to_string().contains("is empty"));
+    }
+
+    #[test]
+    fn test_provider_precedence() {
+        let describe = |vars: &[(&str, &str)]| {
+            provider_from_config(lookup(vars))
+                .map(|p| p.describe())
+                .map_err(|e| e.to_string())
+        };
+
+        assert_eq!(
+            describe(&[("TOKEN_ENCRYPTION_KEY_FILE", "/run/secrets/key")]).unwrap(),
+            "key file /run/secrets/key"
+        );
+        assert_eq!(
+            describe(&[
+                ("TOKEN_ENCRYPTION_KEY", "a"),
+                ("TOKEN_ENCRYPTION_KEYS", "b")
+            ])
+            .unwrap(),
+            "environment variable TOKEN_ENCRYPTION_KEYS"
+        );
+        assert_eq!(
+            describe(&[("TOKEN_ENCRYPTION_KEY", "a")]).unwrap(),
+            "environment variable TOKEN_ENCRYPTION_KEY"
+        );
+
+        let error = describe(&[
+            ("TOKEN_ENCRYPTION_KEY_FILE", "/run/secrets/key"),
+            ("TOKEN_ENCRYPTION_KEY", "a"),
+        ])
+        .unwrap_err();
+        assert!(error.contains("set only one"));
+
+        let error = describe(&[]).unwrap_err();
+        assert!(error.contains("TOKEN_ENCRYPTION_KEY_FILE"));
+    }
+}
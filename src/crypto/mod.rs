@@ -0,0 +1,601 @@
+use aes_gcm::{aead::OsRng, Aes256Gcm, KeyInit};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use std::sync::Arc;
+
+mod cipher;
+pub mod keys;
+
+pub use cipher::CipherKind;
+use cipher::KeyCiphers;
+pub use keys::KeyProvider;
+
+/// Why a token could not be encrypted or decrypted. Callers decide from the
+/// variant whether the stored ciphertext is beyond repair.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CryptoError {
+    /// A version prefix without a well-formed `<key id>:` after it.
+    #[error("Invalid encrypted token format")]
+    InvalidFormat,
+
+    #[error("Encrypted token is not valid base64")]
+    InvalidBase64,
+
+    /// Shorter than a nonce plus an authentication tag.
+    #[error("Encrypted token too short")]
+    TooShort,
+
+    /// Longer than [`MAX_CIPHERTEXT_LEN`].
+    #[error("Encrypted token too large")]
+    TooLarge,
+
+    #[error("Decryption failed")]
+    DecryptionFailed,
+
+    #[error("Decrypted data is not valid UTF-8")]
+    NotUtf8,
+
+    #[error("Encryption key {key_id:?} is not configured")]
+    KeyUnavailable { key_id: String },
+
+    #[error("Encryption failed")]
+    EncryptionFailed,
+}
+
+/// Upper bound on an encoded ciphertext. Tokens are a few hundred bytes, so
+/// anything near this is garbage and is rejected before any decoding.
+pub const MAX_CIPHERTEXT_LEN: usize = 64 * 1024;
+
+/// Id given to the key loaded from the single-key `TOKEN_ENCRYPTION_KEY`.
+pub const DEFAULT_KEY_ID: &str = "default";
+
+#[derive(Clone)]
+struct KeyEntry {
+    id: String,
+    ciphers: KeyCiphers,
+}
+
+/// Encrypts tokens at rest. Holds one or more keys: new ciphertexts always
+/// use the primary (first) key, while older keys stay available for
+/// decryption until everything has been re-encrypted.
+///
+/// Ciphertexts look like `<version prefix><key id>:<base64 nonce ||
+/// ciphertext>`, where the prefix names the cipher (see [`CipherKind`]).
+/// Ciphertexts without a prefix predate key rotation; they are AES-256-GCM
+/// and are tried against every key.
+#[derive(Clone)]
+pub struct TokenCrypto {
+    keys: Arc<Vec<KeyEntry>>,
+    cipher: CipherKind,
+}
+
+impl TokenCrypto {
+    /// Loads keys from the configured source (see
+    /// [`keys::provider_from_config`] for the precedence rules) and the
+    /// cipher for new ciphertexts from `TOKEN_CIPHER`, reading settings
+    /// through `var`.
+    pub fn from_config(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let provider = keys::provider_from_config(&var)?;
+        let cipher = match var("TOKEN_CIPHER") {
+            Some(value) => value.parse()?,
+            None => CipherKind::default(),
+        };
+
+        Ok(Self::from_provider(provider.as_ref())?.with_cipher(cipher))
+    }
+
+    pub fn from_provider(provider: &dyn KeyProvider) -> Result<Self> {
+        let material = provider.load()?;
+        Self::from_key_material(&material)
+            .with_context(|| format!("Invalid encryption key in {}", provider.describe()))
+    }
+
+    /// Accepts either a single base64 key or `id:key` entries separated by
+    /// commas or newlines. Base64 never contains `:`, so the two can't be
+    /// confused.
+    pub fn from_key_material(material: &str) -> Result<Self> {
+        let material = material.trim();
+        if material.contains(':') {
+            Self::from_labeled_keys(&material.replace('\n', ","))
+        } else {
+            Self::from_key(material)
+        }
+    }
+
+    /// Builds a single-key instance from a base64 key.
+    pub fn from_key(key: &str) -> Result<Self> {
+        Self::from_keys(vec![(DEFAULT_KEY_ID.to_string(), key.to_string())])
+    }
+
+    /// Parses `id:key,id:key,...`; the first entry is the primary key.
+    pub fn from_labeled_keys(spec: &str) -> Result<Self> {
+        let keys = spec
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .split_once(':')
+                    .map(|(id, key)| (id.trim().to_string(), key.trim().to_string()))
+                    .ok_or_else(|| {
+                        anyhow!("TOKEN_ENCRYPTION_KEYS entries must look like <id>:<base64 key>")
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Self::from_keys(keys)
+    }
+
+    fn from_keys(keys: Vec<(String, String)>) -> Result<Self> {
+        if keys.is_empty() {
+            return Err(anyhow!("At least one encryption key is required"));
+        }
+
+        let mut entries: Vec<KeyEntry> = Vec::with_capacity(keys.len());
+        for (id, key) in keys {
+            if !is_valid_key_id(&id) {
+                return Err(anyhow!(
+                    "Invalid encryption key id {:?}: use 1-32 letters, digits, '-' or '_'",
+                    id
+                ));
+            }
+            if entries.iter().any(|entry| entry.id == id) {
+                return Err(anyhow!("Duplicate encryption key id {:?}", id));
+            }
+
+            entries.push(KeyEntry {
+                ciphers: parse_key(&id, &key)?,
+                id,
+            });
+        }
+
+        Ok(Self {
+            keys: Arc::new(entries),
+            cipher: CipherKind::default(),
+        })
+    }
+
+    /// Selects the cipher for new ciphertexts. Existing ciphertexts decrypt
+    /// with whichever cipher made them.
+    pub fn with_cipher(mut self, cipher: CipherKind) -> Self {
+        self.cipher = cipher;
+        self
+    }
+
+    /// Id of the key new ciphertexts are encrypted with.
+    pub fn primary_key_id(&self) -> &str {
+        &self.keys[0].id
+    }
+
+    /// Cipher new ciphertexts are encrypted with.
+    pub fn cipher(&self) -> CipherKind {
+        self.cipher
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, CryptoError> {
+        self.encrypt_with_aad(plaintext, &[])
+    }
+
+    pub fn decrypt(&self, encrypted: &str) -> Result<String, CryptoError> {
+        self.decrypt_with_aad(encrypted, &[])
+    }
+
+    /// Encrypts `plaintext` bound to `aad` (associated data): the ciphertext
+    /// only decrypts when the same `aad` is supplied, so it can't be moved
+    /// to a context with different associated data.
+    pub fn encrypt_with_aad(&self, plaintext: &str, aad: &[u8]) -> Result<String, CryptoError> {
+        let primary = &self.keys[0];
+        let sealed = primary.ciphers.seal(self.cipher, plaintext, aad)?;
+
+        let encrypted = format!(
+            "{}{}:{}",
+            self.cipher.prefix(),
+            primary.id,
+            general_purpose::STANDARD.encode(sealed)
+        );
+
+        // Never write something decrypt_with_aad would refuse to read
+        if encrypted.len() > MAX_CIPHERTEXT_LEN {
+            return Err(CryptoError::TooLarge);
+        }
+        Ok(encrypted)
+    }
+
+    /// Decrypts a ciphertext produced by [`Self::encrypt_with_aad`] with the
+    /// same `aad`. An empty `aad` matches ciphertexts made without one.
+    pub fn decrypt_with_aad(&self, encrypted: &str, aad: &[u8]) -> Result<String, CryptoError> {
+        if encrypted.len() > MAX_CIPHERTEXT_LEN {
+            return Err(CryptoError::TooLarge);
+        }
+
+        for kind in CipherKind::ALL {
+            let Some(rest) = encrypted.strip_prefix(kind.prefix()) else {
+                continue;
+            };
+            let (key_id, payload) = rest
+                .split_once(':')
+                .filter(|(key_id, _)| !key_id.is_empty())
+                .ok_or(CryptoError::InvalidFormat)?;
+
+            let key = self
+                .keys
+                .iter()
+                .find(|entry| entry.id == key_id)
+                .ok_or_else(|| CryptoError::KeyUnavailable {
+                    key_id: key_id.to_string(),
+                })?;
+
+            return key.ciphers.open(kind, &decode(payload)?, aad);
+        }
+
+        // Legacy ciphertext without a key id: try every key
+        let combined = decode(encrypted)?;
+        let mut last_error = None;
+        for key in self.keys.iter() {
+            match key.ciphers.open(CipherKind::Aes256Gcm, &combined, aad) {
+                Ok(plaintext) => return Ok(plaintext),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or(CryptoError::DecryptionFailed))
+    }
+
+    pub fn generate_key() -> String {
+        let key = Aes256Gcm::generate_key(OsRng);
+        general_purpose::STANDARD.encode(key)
+    }
+}
+
+fn is_valid_key_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 32
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+fn parse_key(id: &str, key: &str) -> Result<KeyCiphers> {
+    let key_bytes = general_purpose::STANDARD
+        .decode(key)
+        .map_err(|_| anyhow!("Invalid base64 in encryption key {:?}", id))?;
+
+    let key_bytes: [u8; 32] = key_bytes.try_into().map_err(|_| {
+        anyhow!(
+            "Encryption key {:?} must be 32 bytes when base64 decoded",
+            id
+        )
+    })?;
+
+    Ok(KeyCiphers::new(&key_bytes))
+}
+
+fn decode(payload: &str) -> Result<Vec<u8>, CryptoError> {
+    general_purpose::STANDARD
+        .decode(payload)
+        .map_err(|_| CryptoError::InvalidBase64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aes_gcm::aead::{Aead, AeadCore};
+
+    const TOKEN: &str = "ya29.a0AcM612xKwGxTUWg...test_token";
+
+    /// Encrypts the way TokenCrypto did before ciphertexts were versioned.
+    fn legacy_encrypt(key: &str, plaintext: &str) -> String {
+        let ciphers = parse_key("legacy", key).unwrap();
+        general_purpose::STANDARD
+            .encode(ciphers.seal(CipherKind::Aes256Gcm, plaintext, &[]).unwrap())
+    }
+
+    #[test]
+    fn test_encryption_roundtrip() {
+        let crypto = TokenCrypto::from_key_material(&TokenCrypto::generate_key()).unwrap();
+        let original = TOKEN;
+
+        let encrypted = crypto.encrypt(original).unwrap();
+        assert_ne!(encrypted, original);
+
+        let decrypted = crypto.decrypt(&encrypted).unwrap();
+        assert_eq!(decrypted, original);
+    }
+
+    #[test]
+    fn test_key_generation() {
+        let key = TokenCrypto::generate_key();
+        assert_eq!(key.len(), 44); // 32 bytes = 44 characters in base64
+
+        // Verify it can be decoded
+        let decoded = general_purpose::STANDARD.decode(&key).unwrap();
+        assert_eq!(decoded.len(), 32);
+    }
+
+    #[test]
+    fn test_ciphertexts_carry_the_primary_key_id() {
+        let spec = format!(
+            "k2:{},k1:{}",
+            TokenCrypto::generate_key(),
+            TokenCrypto::generate_key()
+        );
+        let crypto = TokenCrypto::from_labeled_keys(&spec).unwrap();
+
+        assert_eq!(crypto.primary_key_id(), "k2");
+        let encrypted = crypto.encrypt(TOKEN).unwrap();
+        assert!(encrypted.starts_with("v2:k2:"));
+        assert_eq!(crypto.decrypt(&encrypted).unwrap(), TOKEN);
+
+        let single = TokenCrypto::from_key(&TokenCrypto::generate_key()).unwrap();
+        assert!(single.encrypt(TOKEN).unwrap().starts_with("v2:default:"));
+    }
+
+    #[test]
+    fn test_legacy_ciphertexts_still_decrypt() {
+        let old_key = TokenCrypto::generate_key();
+        let legacy = legacy_encrypt(&old_key, TOKEN);
+
+        // Same key, loaded the old way
+        let crypto = TokenCrypto::from_key(&old_key).unwrap();
+        assert_eq!(crypto.decrypt(&legacy).unwrap(), TOKEN);
+
+        // After rotation the old key is no longer primary but still tried
+        let rotated = TokenCrypto::from_labeled_keys(&format!(
+            "new:{},old:{}",
+            TokenCrypto::generate_key(),
+            old_key
+        ))
+        .unwrap();
+        assert_eq!(rotated.decrypt(&legacy).unwrap(), TOKEN);
+        assert!(rotated.encrypt(TOKEN).unwrap().starts_with("v2:new:"));
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_versioned_ciphertexts_readable() {
+        let old_key = TokenCrypto::generate_key();
+        let before = TokenCrypto::from_labeled_keys(&format!("old:{}", old_key)).unwrap();
+        let encrypted = before.encrypt(TOKEN).unwrap();
+
+        let after = TokenCrypto::from_labeled_keys(&format!(
+            "new:{},old:{}",
+            TokenCrypto::generate_key(),
+            old_key
+        ))
+        .unwrap();
+        assert_eq!(after.decrypt(&encrypted).unwrap(), TOKEN);
+    }
+
+    #[test]
+    fn test_wrong_key_fails() {
+        let crypto = TokenCrypto::from_key(&TokenCrypto::generate_key()).unwrap();
+        let other = TokenCrypto::from_key(&TokenCrypto::generate_key()).unwrap();
+
+        let encrypted = crypto.encrypt(TOKEN).unwrap();
+        assert!(other.decrypt(&encrypted).is_err());
+
+        let legacy = legacy_encrypt(&TokenCrypto::generate_key(), TOKEN);
+        assert!(crypto.decrypt(&legacy).is_err());
+
+        let unknown_id =
+            TokenCrypto::from_labeled_keys(&format!("k9:{}", TokenCrypto::generate_key()))
+                .unwrap()
+                .encrypt(TOKEN)
+                .unwrap();
+        assert_eq!(
+            crypto.decrypt(&unknown_id),
+            Err(CryptoError::KeyUnavailable {
+                key_id: "k9".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_malformed_ciphertexts_are_rejected_by_shape() {
+        let crypto = TokenCrypto::from_key(&TokenCrypto::generate_key()).unwrap();
+        let encrypted = crypto.encrypt(TOKEN).unwrap();
+        let b64 = |bytes: &[u8]| general_purpose::STANDARD.encode(bytes);
+
+        let cases: Vec<(&str, String, CryptoError)> = vec![
+            ("empty", String::new(), CryptoError::TooShort),
+            (
+                "no key id separator",
+                "v2:no-separator".into(),
+                CryptoError::InvalidFormat,
+            ),
+            (
+                "empty key id",
+                format!("v2::{}", b64(&[0; 40])),
+                CryptoError::InvalidFormat,
+            ),
+            (
+                "xchacha without key id",
+                "v2x:".into(),
+                CryptoError::InvalidFormat,
+            ),
+            (
+                "payload not base64",
+                "v2:default:not base64!".into(),
+                CryptoError::InvalidBase64,
+            ),
+            (
+                "legacy not base64",
+                "%%%".into(),
+                CryptoError::InvalidBase64,
+            ),
+            ("empty payload", "v2:default:".into(), CryptoError::TooShort),
+            (
+                "partial nonce",
+                format!("v2:default:{}", b64(&[0; 3])),
+                CryptoError::TooShort,
+            ),
+            (
+                "nonce only",
+                format!("v2:default:{}", b64(&[0; 12])),
+                CryptoError::TooShort,
+            ),
+            (
+                "nonce and partial tag",
+                format!("v2:default:{}", b64(&[0; 27])),
+                CryptoError::TooShort,
+            ),
+            ("legacy nonce only", b64(&[0; 12]), CryptoError::TooShort),
+            (
+                "xchacha short nonce",
+                format!("v2x:default:{}", b64(&[0; 24])),
+                CryptoError::TooShort,
+            ),
+            (
+                "xchacha nonce and partial tag",
+                format!("v2x:default:{}", b64(&[0; 39])),
+                CryptoError::TooShort,
+            ),
+            (
+                "nonce and bogus tag",
+                format!("v2:default:{}", b64(&[0; 28])),
+                CryptoError::DecryptionFailed,
+            ),
+            (
+                "xchacha nonce and bogus tag",
+                format!("v2x:default:{}", b64(&[0; 40])),
+                CryptoError::DecryptionFailed,
+            ),
+            (
+                "tampered",
+                format!("{}AAAA", encrypted),
+                CryptoError::DecryptionFailed,
+            ),
+            (
+                "oversized",
+                format!("v2:default:{}", "A".repeat(MAX_CIPHERTEXT_LEN)),
+                CryptoError::TooLarge,
+            ),
+            (
+                "oversized legacy",
+                "A".repeat(MAX_CIPHERTEXT_LEN + 1),
+                CryptoError::TooLarge,
+            ),
+        ];
+
+        for (name, input, expected) in cases {
+            assert_eq!(crypto.decrypt(&input), Err(expected), "case: {}", name);
+        }
+    }
+
+    #[test]
+    fn test_oversized_plaintext_is_not_encrypted() {
+        let crypto = TokenCrypto::from_key(&TokenCrypto::generate_key()).unwrap();
+
+        assert_eq!(
+            crypto.encrypt(&"x".repeat(MAX_CIPHERTEXT_LEN)),
+            Err(CryptoError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn test_non_utf8_plaintext_is_reported() {
+        let key = TokenCrypto::generate_key();
+        let cipher =
+            Aes256Gcm::new_from_slice(&general_purpose::STANDARD.decode(&key).unwrap()).unwrap();
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let mut sealed = nonce.to_vec();
+        sealed.extend(cipher.encrypt(&nonce, &[0xff, 0xfe][..]).unwrap());
+        let encoded = format!("v2:default:{}", general_purpose::STANDARD.encode(sealed));
+        assert_eq!(
+            TokenCrypto::from_key(&key).unwrap().decrypt(&encoded),
+            Err(CryptoError::NotUtf8)
+        );
+    }
+
+    #[test]
+    fn test_invalid_key_specs_are_rejected() {
+        let key = TokenCrypto::generate_key();
+
+        assert!(TokenCrypto::from_labeled_keys("").is_err());
+        assert!(TokenCrypto::from_labeled_keys(&key).is_err());
+        assert!(TokenCrypto::from_labeled_keys(&format!("a:{},a:{}", key, key)).is_err());
+        assert!(TokenCrypto::from_labeled_keys(&format!("bad id:{}", key)).is_err());
+        assert!(TokenCrypto::from_labeled_keys("k1:dG9vc2hvcnQ=").is_err());
+        assert!(TokenCrypto::from_labeled_keys(&format!(" k1 : {} , k2:{} ", key, key)).is_ok());
+    }
+
+    #[test]
+    fn test_aad_binds_the_ciphertext() {
+        let crypto = TokenCrypto::from_key(&TokenCrypto::generate_key()).unwrap();
+
+        let encrypted = crypto.encrypt_with_aad(TOKEN, b"oauth:1").unwrap();
+        assert_eq!(
+            crypto.decrypt_with_aad(&encrypted, b"oauth:1").unwrap(),
+            TOKEN
+        );
+        assert!(crypto.decrypt_with_aad(&encrypted, b"oauth:2").is_err());
+        assert!(crypto.decrypt(&encrypted).is_err());
+
+        // No AAD is the same as empty AAD
+        let plain = crypto.encrypt(TOKEN).unwrap();
+        assert_eq!(crypto.decrypt_with_aad(&plain, &[]).unwrap(), TOKEN);
+        assert!(crypto.decrypt_with_aad(&plain, b"oauth:1").is_err());
+    }
+
+    #[test]
+    fn test_xchacha20_roundtrip() {
+        let crypto = TokenCrypto::from_key(&TokenCrypto::generate_key())
+            .unwrap()
+            .with_cipher(CipherKind::XChaCha20Poly1305);
+
+        let encrypted = crypto.encrypt_with_aad(TOKEN, b"oauth:1").unwrap();
+        assert!(encrypted.starts_with("v2x:default:"));
+        assert_eq!(
+            crypto.decrypt_with_aad(&encrypted, b"oauth:1").unwrap(),
+            TOKEN
+        );
+        assert_eq!(
+            crypto.decrypt_with_aad(&encrypted, b"oauth:2"),
+            Err(CryptoError::DecryptionFailed)
+        );
+        assert_eq!(
+            crypto.decrypt("v2x:default:AAAAAAAAAAAAAAAA"),
+            Err(CryptoError::TooShort)
+        );
+    }
+
+    #[test]
+    fn test_switching_ciphers_keeps_existing_data_readable() {
+        let key = TokenCrypto::generate_key();
+        let aes = TokenCrypto::from_key(&key).unwrap();
+        let xchacha = TokenCrypto::from_key(&key)
+            .unwrap()
+            .with_cipher(CipherKind::XChaCha20Poly1305);
+
+        let from_aes = aes.encrypt(TOKEN).unwrap();
+        let from_xchacha = xchacha.encrypt(TOKEN).unwrap();
+        assert!(from_aes.starts_with("v2:"));
+        assert!(from_xchacha.starts_with("v2x:"));
+
+        for crypto in [&aes, &xchacha] {
+            assert_eq!(crypto.decrypt(&from_aes).unwrap(), TOKEN);
+            assert_eq!(crypto.decrypt(&from_xchacha).unwrap(), TOKEN);
+        }
+
+        // Legacy unprefixed ciphertexts are always AES-GCM
+        assert_eq!(
+            xchacha.decrypt(&legacy_encrypt(&key, TOKEN)).unwrap(),
+            TOKEN
+        );
+    }
+
+    #[test]
+    fn test_cipher_names_parse() {
+        assert_eq!(
+            "aes256gcm".parse::<CipherKind>().unwrap(),
+            CipherKind::Aes256Gcm
+        );
+        assert_eq!(
+            " XChaCha20 ".parse::<CipherKind>().unwrap(),
+            CipherKind::XChaCha20Poly1305
+        );
+        assert!("rot13".parse::<CipherKind>().is_err());
+        for kind in CipherKind::ALL {
+            assert_eq!(kind.name().parse::<CipherKind>().unwrap(), kind);
+        }
+    }
+}
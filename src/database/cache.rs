@@ -0,0 +1,95 @@
+//! Short-lived copies of rows read on every command, so a `/meet` doesn't
+//! go to SQLite for the same user each time. Only rows without secrets are
+//! cached; tokens are always read (and decrypted) from the database.
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::telemetry::metrics;
+
+pub struct TtlCache<K, V> {
+    /// Label of the cache in metrics.
+    name: &'static str,
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<HashMap<K, (Instant, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(name: &'static str, ttl: Duration, capacity: usize) -> Self {
+        Self {
+            name,
+            ttl,
+            capacity,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The cached value for `key`, unless it is missing or older than the
+    /// TTL.
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        let value = match entries.get(key) {
+            Some((stored_at, value)) if stored_at.elapsed() < self.ttl => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        metrics::record_cache_lookup(self.name, value.is_some());
+        value
+    }
+
+    /// Caches `value` for `key`. When the cache is full, expired entries are
+    /// dropped first; if none are, the value simply isn't cached.
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            entries.retain(|_, (stored_at, _)| stored_at.elapsed() < self.ttl);
+            if entries.len() >= self.capacity {
+                return;
+            }
+        }
+        entries.insert(key, (Instant::now(), value));
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        self.entries.lock().unwrap().remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_entries_expire_after_the_ttl() {
+        let cache = TtlCache::new("test", Duration::from_secs(60), 10);
+        cache.insert("U012AB3CD", 1);
+        assert_eq!(cache.get(&"U012AB3CD"), Some(1));
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert_eq!(cache.get(&"U012AB3CD"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_full_cache_makes_room_from_expired_entries_only() {
+        let cache = TtlCache::new("test", Duration::from_secs(60), 2);
+        cache.insert("a", 1);
+        tokio::time::advance(Duration::from_secs(61)).await;
+        cache.insert("b", 2);
+
+        cache.insert("c", 3);
+        assert_eq!(cache.get(&"c"), Some(3), "took the expired entry's place");
+        cache.insert("d", 4);
+        assert_eq!(cache.get(&"d"), None, "nothing expired to make room");
+        assert_eq!(cache.get(&"b"), Some(2));
+
+        cache.invalidate(&"b");
+        assert_eq!(cache.get(&"b"), None);
+    }
+}
@@ -1,6 +1,9 @@
 use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+use crate::google::Artifacts;
+use crate::secret::SecretString;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: i64,
@@ -10,12 +13,14 @@ pub struct User {
     pub updated_at: NaiveDateTime,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A user's Google OAuth token, decrypted. The token values are
+/// [`SecretString`]s so they never show up in `Debug` output or logs.
+#[derive(Debug, Clone)]
 pub struct OAuthToken {
     pub id: Option<i64>,
     pub user_id: i64,
-    pub access_token: String,
-    pub refresh_token: Option<String>,
+    pub access_token: SecretString,
+    pub refresh_token: Option<SecretString>,
     pub expires_at: Option<NaiveDateTime>,
     pub scope: Option<String>,
     pub created_at: Option<NaiveDateTime>,
@@ -23,10 +28,13 @@ pub struct OAuthToken {
 }
 
 impl OAuthToken {
+    /// How long before it expires a token is refreshed.
+    pub const REFRESH_MARGIN: chrono::Duration = chrono::Duration::minutes(5);
+
     pub fn new(
         user_id: i64,
-        access_token: String,
-        refresh_token: Option<String>,
+        access_token: SecretString,
+        refresh_token: Option<SecretString>,
         expires_at: Option<DateTime<Utc>>,
         scope: Option<String>,
     ) -> Self {
@@ -43,40 +51,429 @@ impl OAuthToken {
     }
 
     pub fn is_expired(&self) -> bool {
+        self.is_expired_at(Utc::now())
+    }
+
+    pub fn is_expired_at(&self, now: DateTime<Utc>) -> bool {
         match self.expires_at {
-            Some(expires_at) => expires_at <= Utc::now().naive_utc(),
+            Some(expires_at) => expires_at <= now.naive_utc(),
             None => false,
         }
     }
 
     pub fn expires_soon(&self) -> bool {
+        self.expires_soon_at(Utc::now())
+    }
+
+    /// Whether the token expires within [`Self::REFRESH_MARGIN`] of `now`.
+    pub fn expires_soon_at(&self, now: DateTime<Utc>) -> bool {
         match self.expires_at {
-            Some(expires_at) => {
-                let five_minutes_from_now = (Utc::now() + chrono::Duration::minutes(5)).naive_utc();
-                expires_at <= five_minutes_from_now
-            }
+            Some(expires_at) => expires_at <= (now + Self::REFRESH_MARGIN).naive_utc(),
             None => false,
         }
     }
 }
 
+/// A Slack workspace the app is installed in, decrypted. The bot token and
+/// the incoming webhook URL both grant access to the workspace, so they are
+/// [`SecretString`]s and are encrypted at rest.
+#[derive(Debug, Clone)]
+pub struct SlackTeam {
+    pub id: Option<i64>,
+    pub slack_team_id: String,
+    pub team_name: Option<String>,
+    pub bot_user_id: Option<String>,
+    pub bot_token: SecretString,
+    pub incoming_webhook_url: Option<SecretString>,
+    pub created_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+}
+
+impl SlackTeam {
+    pub fn new(slack_team_id: String, bot_token: SecretString) -> Self {
+        Self {
+            id: None,
+            slack_team_id,
+            team_name: None,
+            bot_user_id: None,
+            bot_token,
+            incoming_webhook_url: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+}
+
+/// What kind of URL a meeting's `meet_link` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum MeetLinkKind {
+    /// A joinable `https://meet.google.com/...` URL.
+    Meet,
+    /// A calendar event page, stored when Google returned no conference data.
+    Calendar,
+}
+
+/// Who a new meeting's link is shown to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum MeetingVisibility {
+    /// Posted for the whole channel.
+    #[default]
+    Channel,
+    /// Shown only to the user who created it (`/meet --quiet`).
+    Quiet,
+}
+
+impl MeetingVisibility {
+    /// The `user_settings` key of the visibility a user's meetings get
+    /// without `--quiet`.
+    pub const SETTING_KEY: &'static str = "visibility";
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MeetingVisibility::Channel => "channel",
+            MeetingVisibility::Quiet => "quiet",
+        }
+    }
+}
+
+impl std::str::FromStr for MeetingVisibility {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "channel" => Ok(MeetingVisibility::Channel),
+            "quiet" => Ok(MeetingVisibility::Quiet),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Where a meeting was asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(rename_all = "lowercase")]
+pub enum MeetingSource {
+    /// `/meet`, its message shortcut or a mention of the bot.
+    #[default]
+    Slack,
+    /// `POST /api/v1/meetings`, with one of the workspace's API keys.
+    Api,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Meeting {
     pub id: Option<i64>,
     pub user_id: i64,
     pub meet_link: String,
     pub title: Option<String>,
+    pub link_kind: MeetLinkKind,
+    pub visibility: MeetingVisibility,
+    /// The user whose Google account created the meeting, when it was a
+    /// workspace's shared account rather than the meeting user's own.
+    pub created_with_account_user_id: Option<i64>,
+    /// The channel `/meet` was run in; unknown for older meetings.
+    pub channel_id: Option<String>,
+    /// The workspace the meeting was created in; for older meetings, its
+    /// user's workspace at the time the column was added.
+    pub slack_team_id: Option<String>,
+    /// The Meet space, `spaces/{id}`; unknown for older meetings.
+    pub space_name: Option<String>,
+    /// Whether Meet records the meeting by itself.
+    pub auto_recording: bool,
+    /// Whether Meet transcribes the meeting by itself.
+    pub auto_transcription: bool,
+    /// When the meeting was set to end; only known when `/meet` was given a
+    /// duration.
+    pub ends_at: Option<NaiveDateTime>,
+    pub source: MeetingSource,
     pub created_at: Option<NaiveDateTime>,
 }
 
 impl Meeting {
-    pub fn new(user_id: i64, meet_link: String, title: Option<String>) -> Self {
+    pub fn new(
+        user_id: i64,
+        meet_link: String,
+        title: Option<String>,
+        link_kind: MeetLinkKind,
+    ) -> Self {
         Self {
             id: None,
             user_id,
             meet_link,
             title,
+            link_kind,
+            visibility: MeetingVisibility::Channel,
+            created_with_account_user_id: None,
+            channel_id: None,
+            slack_team_id: None,
+            space_name: None,
+            auto_recording: false,
+            auto_transcription: false,
+            ends_at: None,
+            source: MeetingSource::Slack,
+            created_at: None,
+        }
+    }
+
+    /// How long after its end a meeting's link is still trusted to work;
+    /// Meet keeps letting people in for a while after a scheduled meeting.
+    pub const LINK_GRACE_PERIOD: chrono::Duration = chrono::Duration::hours(1);
+
+    /// The meeting, shown to `visibility`.
+    pub fn with_visibility(mut self, visibility: MeetingVisibility) -> Self {
+        self.visibility = visibility;
+        self
+    }
+
+    /// The meeting, created in `channel_id`.
+    pub fn with_channel(mut self, channel_id: &str) -> Self {
+        self.channel_id = Some(channel_id.to_string());
+        self
+    }
+
+    /// The meeting, created in the workspace `slack_team_id`.
+    pub fn with_team(mut self, slack_team_id: &str) -> Self {
+        self.slack_team_id = Some(slack_team_id.to_string());
+        self
+    }
+
+    /// The meeting, held in the Meet space `space_name`.
+    pub fn with_space(mut self, space_name: &str) -> Self {
+        self.space_name = Some(space_name.to_string());
+        self
+    }
+
+    /// The meeting, recorded or transcribed as `artifacts` asks.
+    pub fn with_artifacts(mut self, artifacts: Artifacts) -> Self {
+        self.auto_recording = artifacts.recording;
+        self.auto_transcription = artifacts.transcription;
+        self
+    }
+
+    /// What Meet generates for the meeting by itself.
+    pub fn artifacts(&self) -> Artifacts {
+        Artifacts {
+            recording: self.auto_recording,
+            transcription: self.auto_transcription,
+        }
+    }
+
+    /// The meeting, asked for from `source`.
+    pub fn with_source(mut self, source: MeetingSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// The meeting, set to end at `ends_at`.
+    pub fn with_end(mut self, ends_at: Option<DateTime<Utc>>) -> Self {
+        self.ends_at = ends_at.map(|ends_at| ends_at.naive_utc());
+        self
+    }
+
+    /// Whether the link has probably stopped accepting joins at `now`: the
+    /// meeting ended more than [`Self::LINK_GRACE_PERIOD`] ago. Meetings
+    /// without an end never do.
+    pub fn may_have_expired_at(&self, now: DateTime<Utc>) -> bool {
+        match self.ends_at {
+            Some(ends_at) => ends_at + Self::LINK_GRACE_PERIOD < now.naive_utc(),
+            None => false,
+        }
+    }
+}
+
+/// A message Slack will post later, as `chat.scheduleMessage` identifies it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduledReminder {
+    pub channel_id: String,
+    pub message_id: String,
+}
+
+/// The Meet space `/meet` returns to a user every time, while they have
+/// `reuse_personal_space` on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PersonalSpace {
+    pub meet_link: String,
+    /// Resource name, `spaces/{id}`.
+    pub space_name: String,
+}
+
+/// Where a meeting's announcement was posted, to edit it once the meeting
+/// is cancelled or ends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnnouncementLocation {
+    /// The pending response holding the `response_url` of the command
+    /// that announced the meeting, until it is used or expires.
+    pub response_id: Option<i64>,
+    /// The channel and `ts` of the announcement, when the bot posted it
+    /// with `chat.postMessage`.
+    pub message: Option<(String, String)>,
+}
+
+/// How many meetings a user created, for `/meet stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MeetingCounts {
+    pub this_week: i64,
+    pub this_month: i64,
+    pub all_time: i64,
+}
+
+/// A `/meet poll` asking a channel when to meet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Poll {
+    pub id: Option<i64>,
+    /// Who created the poll, the only one who can book a time.
+    pub user_id: i64,
+    /// The Slack id of its creator.
+    pub slack_user_id: String,
+    pub slack_team_id: String,
+    pub channel_id: String,
+    pub title: Option<String>,
+    /// The times offered, in the order given; votes refer to them by index.
+    pub options: Vec<DateTime<Utc>>,
+    /// The index of the booked time, which closes the poll.
+    pub booked_option: Option<usize>,
+    pub created_at: Option<NaiveDateTime>,
+}
+
+impl Poll {
+    pub fn new(
+        creator: &User,
+        slack_team_id: String,
+        channel_id: String,
+        title: Option<String>,
+        options: Vec<DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            id: None,
+            user_id: creator.id,
+            slack_user_id: creator.slack_user_id.clone(),
+            slack_team_id,
+            channel_id,
+            title,
+            options,
+            booked_option: None,
             created_at: None,
         }
     }
+
+    /// The booked time, once there is one.
+    pub fn booked(&self) -> Option<DateTime<Utc>> {
+        self.booked_option
+            .and_then(|option| self.options.get(option).copied())
+    }
+}
+
+/// A user's vote in a poll, for the option at `option`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PollVote {
+    pub slack_user_id: String,
+    pub option: usize,
+}
+
+/// A key for the REST API, without the key itself, of which only a hash is
+/// stored.
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub id: i64,
+    pub slack_team_id: String,
+    /// The start of the key, to tell keys apart.
+    pub prefix: String,
+    /// The Slack user who made the key, whose meetings it creates unless a
+    /// request names someone else.
+    pub created_by: String,
+    pub created_at: NaiveDateTime,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub revoked_at: Option<NaiveDateTime>,
+}
+
+/// A meeting's short link, `/m/{slug}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortLink {
+    pub meeting_id: i64,
+    pub slug: String,
+    /// Whether the link redirects; off, it answers like an unknown one.
+    pub public: bool,
+    /// How many times the link was followed while public.
+    pub click_count: i64,
+}
+
+/// A row of the audit log, as served by `GET /admin/audit`.
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub id: i64,
+    pub created_at: NaiveDateTime,
+    pub actor_slack_id: Option<String>,
+    pub slack_team_id: Option<String>,
+    pub event_type: String,
+    pub detail: serde_json::Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_oauth_token_debug_hides_secrets() {
+        let token = OAuthToken::new(
+            1,
+            "ya29.access-secret".into(),
+            Some("1//refresh-secret".into()),
+            None,
+            Some("scope".to_string()),
+        );
+
+        let debug = format!("{:?}", token);
+        assert!(!debug.contains("access-secret"));
+        assert!(!debug.contains("refresh-secret"));
+        assert!(debug.contains("REDACTED"));
+    }
+
+    #[test]
+    fn test_expiry_boundaries() {
+        let now = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let expiring_in = |duration: chrono::Duration| {
+            OAuthToken::new(1, "ya29.access".into(), None, Some(now + duration), None)
+        };
+
+        let in_five_minutes = expiring_in(chrono::Duration::minutes(5));
+        assert!(in_five_minutes.expires_soon_at(now));
+        assert!(!in_five_minutes.is_expired_at(now));
+
+        let just_after = expiring_in(chrono::Duration::minutes(5) + chrono::Duration::seconds(1));
+        assert!(!just_after.expires_soon_at(now));
+
+        let expiring_now = expiring_in(chrono::Duration::zero());
+        assert!(expiring_now.is_expired_at(now));
+        assert!(!expiring_now.is_expired_at(now - chrono::Duration::seconds(1)));
+
+        let no_expiry = OAuthToken::new(1, "ya29.access".into(), None, None, None);
+        assert!(!no_expiry.is_expired_at(now));
+        assert!(!no_expiry.expires_soon_at(now));
+    }
+
+    #[test]
+    fn test_meeting_link_grace_period() {
+        let ends_at = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let meeting = Meeting::new(
+            1,
+            "https://meet.google.com/abc-defg-hij".to_string(),
+            None,
+            MeetLinkKind::Meet,
+        )
+        .with_end(Some(ends_at));
+        let grace_ends = ends_at + Meeting::LINK_GRACE_PERIOD;
+
+        assert!(!meeting.may_have_expired_at(ends_at - chrono::Duration::minutes(30)));
+        assert!(!meeting.may_have_expired_at(ends_at));
+        assert!(!meeting.may_have_expired_at(grace_ends));
+        assert!(meeting.may_have_expired_at(grace_ends + chrono::Duration::seconds(1)));
+
+        let without_end = meeting.with_end(None);
+        assert!(!without_end.may_have_expired_at(grace_ends + chrono::Duration::days(30)));
+    }
 }
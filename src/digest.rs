@@ -0,0 +1,561 @@
+//! The weekly digest: a summary of a workspace's meetings over the past
+//! week, posted to a channel its admins picked with `/meet admin digest`.
+//!
+//! The schedule lives in `team_settings`. An hourly job posts every digest
+//! that has come due since the one recorded in `digest_last_sent`, so a
+//! digest missed while the bot was down goes out once it is back, and a
+//! restart never sends one twice.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc, Weekday};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+use crate::database::Database;
+use crate::slack::api::{ChatMessage, SlackApi};
+use crate::slack::blocks::{self, Block, Text};
+use crate::stats::{self, TeamActivity};
+
+/// How often the job looks for digests that are due.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub const DEFAULT_WEEKDAY: Weekday = Weekday::Mon;
+/// Hour of the day, in UTC, digests go out unless set otherwise.
+pub const DEFAULT_HOUR: u32 = 9;
+
+/// Prefix shared by every digest key of `team_settings`.
+const SETTING_PREFIX: &str = "digest_";
+const CHANNEL_KEY: &str = "digest_channel_id";
+const WEEKDAY_KEY: &str = "digest_weekday";
+const HOUR_KEY: &str = "digest_hour";
+/// The admin who set the digest up, told when it can't be posted.
+const CONFIGURED_BY_KEY: &str = "digest_configured_by";
+/// When the latest digest sent was due, in RFC 3339.
+const LAST_SENT_KEY: &str = "digest_last_sent";
+
+/// Where and when a workspace's digest is posted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigestSchedule {
+    pub channel_id: String,
+    pub weekday: Weekday,
+    /// Hour of the day in UTC.
+    pub hour: u32,
+    /// Slack user id of the admin who set the digest up.
+    pub configured_by: Option<String>,
+}
+
+impl DigestSchedule {
+    /// The schedule stored in a workspace's digest settings, if it has one.
+    /// Unreadable values fall back to the defaults.
+    fn from_settings(settings: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            channel_id: settings.get(CHANNEL_KEY)?.clone(),
+            weekday: settings
+                .get(WEEKDAY_KEY)
+                .and_then(|weekday| weekday.parse().ok())
+                .unwrap_or(DEFAULT_WEEKDAY),
+            hour: settings
+                .get(HOUR_KEY)
+                .and_then(|hour| hour.parse().ok())
+                .filter(|hour| *hour < 24)
+                .unwrap_or(DEFAULT_HOUR),
+            configured_by: settings.get(CONFIGURED_BY_KEY).cloned(),
+        })
+    }
+
+    /// The latest time at or before `now` a digest was due.
+    pub fn latest_due(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let days_back =
+            (7 + now.weekday().num_days_from_monday() - self.weekday.num_days_from_monday()) % 7;
+        let due = (now.date_naive() - Days::new(days_back.into()))
+            .and_hms_opt(self.hour, 0, 0)
+            .expect("hours are below 24")
+            .and_utc();
+        if due > now {
+            due - chrono::Duration::days(7)
+        } else {
+            due
+        }
+    }
+
+    /// The first time after `now` a digest is due.
+    pub fn next_due(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        self.latest_due(now) + chrono::Duration::days(7)
+    }
+
+    /// When the digest goes out, e.g. "every Monday at 09:00 UTC".
+    pub fn describe(&self) -> String {
+        format!(
+            "every {} at {:02}:00 UTC",
+            weekday_name(self.weekday),
+            self.hour
+        )
+    }
+}
+
+async fn settings(db: &Database, slack_team_id: &str) -> Result<HashMap<String, String>> {
+    Ok(db
+        .team_settings_with_prefix(slack_team_id, SETTING_PREFIX)
+        .await?
+        .into_iter()
+        .collect())
+}
+
+/// The workspace's digest schedule, if it has a digest.
+pub async fn schedule(db: &Database, slack_team_id: &str) -> Result<Option<DigestSchedule>> {
+    Ok(DigestSchedule::from_settings(
+        &settings(db, slack_team_id).await?,
+    ))
+}
+
+/// Sets up the workspace's digest, or changes it. Counts as sent at `now`,
+/// so the first digest is the next one due rather than one already past.
+pub async fn configure(
+    db: &Database,
+    slack_team_id: &str,
+    schedule: &DigestSchedule,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    db.set_team_setting(slack_team_id, CHANNEL_KEY, &schedule.channel_id)
+        .await?;
+    db.set_team_setting(slack_team_id, WEEKDAY_KEY, &schedule.weekday.to_string())
+        .await?;
+    db.set_team_setting(slack_team_id, HOUR_KEY, &schedule.hour.to_string())
+        .await?;
+    match &schedule.configured_by {
+        Some(user_id) => {
+            db.set_team_setting(slack_team_id, CONFIGURED_BY_KEY, user_id)
+                .await?
+        }
+        None => {
+            db.delete_team_setting(slack_team_id, CONFIGURED_BY_KEY)
+                .await?
+        }
+    }
+    db.set_team_setting(slack_team_id, LAST_SENT_KEY, &now.to_rfc3339())
+        .await
+}
+
+/// Stops the workspace's digest.
+pub async fn disable(db: &Database, slack_team_id: &str) -> Result<()> {
+    for key in [
+        CHANNEL_KEY,
+        WEEKDAY_KEY,
+        HOUR_KEY,
+        CONFIGURED_BY_KEY,
+        LAST_SENT_KEY,
+    ] {
+        db.delete_team_setting(slack_team_id, key).await?;
+    }
+    Ok(())
+}
+
+/// Posts every digest due by `now` that hasn't been sent, pointing admins
+/// at `command` to change it. A workspace whose digest fails doesn't hold
+/// up the others; it is tried again on the next run.
+pub async fn send_due(
+    db: &Database,
+    slack: &dyn SlackApi,
+    command: &str,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let mut failed = 0;
+    for (slack_team_id, _) in db.teams_with_setting(CHANNEL_KEY).await? {
+        if let Err(e) = send_if_due(db, slack, command, &slack_team_id, now).await {
+            error!(
+                "Failed to send the weekly digest of team {}: {:#}",
+                slack_team_id, e
+            );
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        anyhow::bail!("{} weekly digests failed", failed);
+    }
+    Ok(())
+}
+
+async fn send_if_due(
+    db: &Database,
+    slack: &dyn SlackApi,
+    command: &str,
+    slack_team_id: &str,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let settings = settings(db, slack_team_id).await?;
+    let Some(schedule) = DigestSchedule::from_settings(&settings) else {
+        return Ok(());
+    };
+    let due = schedule.latest_due(now);
+    let last_sent = settings
+        .get(LAST_SENT_KEY)
+        .and_then(|sent| DateTime::parse_from_rfc3339(sent).ok());
+    if last_sent.is_some_and(|sent| sent >= due) {
+        return Ok(());
+    }
+
+    let team = db
+        .get_slack_team(slack_team_id)
+        .await?
+        .ok_or_else(|| anyhow!("no bot token is stored for the workspace"))?;
+    // The seven whole days before the one the digest is due on
+    let last_day = due.date_naive() - Days::new(1);
+    let first_day = due.date_naive() - Days::new(7);
+    let activity = stats::team_activity(
+        db,
+        slack_team_id,
+        first_day.and_time(NaiveTime::MIN),
+        due.date_naive().and_time(NaiveTime::MIN),
+    )
+    .await?;
+
+    let message = digest_message(&schedule, command, first_day, last_day, &activity);
+    match slack.post_message(&team.bot_token, &message).await {
+        Ok(()) => info!(
+            "Sent the weekly digest of team {} to {}",
+            slack_team_id, schedule.channel_id
+        ),
+        Err(e) if e.is_not_in_channel() => {
+            warn!(
+                "Can't post the weekly digest of team {} to {}: {}",
+                slack_team_id, schedule.channel_id, e
+            );
+            // Counted as sent anyway, so the admin hears about it once a week
+            // rather than every hour
+            notify_not_in_channel(slack, &team.bot_token, &schedule, command).await;
+        }
+        Err(e) => return Err(e.into()),
+    }
+
+    db.set_team_setting(slack_team_id, LAST_SENT_KEY, &due.to_rfc3339())
+        .await
+}
+
+/// Tells the admin who set the digest up that the bot isn't in its channel.
+async fn notify_not_in_channel(
+    slack: &dyn SlackApi,
+    bot_token: &crate::secret::SecretString,
+    schedule: &DigestSchedule,
+    command: &str,
+) {
+    let Some(admin) = &schedule.configured_by else {
+        warn!("Nobody to tell that the weekly digest couldn't be posted");
+        return;
+    };
+    let message = ChatMessage {
+        channel: admin.clone(),
+        text: format!(
+            "⚠️ I couldn't post this week's meeting digest to <#{}> because I'm not in that channel. \
+             Invite me there, or pick another channel with `{} admin digest`.",
+            schedule.channel_id, command
+        ),
+        blocks: Vec::new(),
+        thread: None,
+    };
+    if let Err(e) = slack.post_message(bot_token, &message).await {
+        warn!("Couldn't tell {} about the weekly digest: {}", admin, e);
+    }
+}
+
+/// The digest of `activity` from `first_day` to `last_day`, for the channel
+/// of `schedule`, made with `command`.
+pub fn digest_message(
+    schedule: &DigestSchedule,
+    command: &str,
+    first_day: NaiveDate,
+    last_day: NaiveDate,
+    activity: &TeamActivity,
+) -> ChatMessage {
+    let period = format!(
+        "{} – {}",
+        first_day.format("%-d %b"),
+        last_day.format("%-d %b")
+    );
+    let footer = Block::context(vec![Text::mrkdwn(format!(
+        "Posted {}. Change it with `{} admin digest`.",
+        schedule.describe(),
+        command
+    ))]);
+
+    if activity.meetings == 0 {
+        let text = format!(
+            "📅 Weekly meeting digest, {}: no meetings were created with {}.",
+            period, command
+        );
+        return ChatMessage {
+            channel: schedule.channel_id.clone(),
+            blocks: vec![Block::section(Text::mrkdwn(text.clone())), footer],
+            text,
+            thread: None,
+        };
+    }
+
+    let meetings = match activity.meetings {
+        1 => "1 meeting".to_string(),
+        count => format!("{} meetings", count),
+    };
+    let mut text = format!("📅 Weekly meeting digest, {}: {}.", period, meetings);
+    let mut fields = vec![Text::mrkdwn(format!(
+        "*Meetings created*\n{}",
+        activity.meetings
+    ))];
+    if let Some((day, count)) = activity.busiest_day {
+        let busiest = format!(
+            "{} {} ({})",
+            weekday_name(day.weekday()),
+            day.format("%-d %b"),
+            count
+        );
+        text.push_str(&format!(" Busiest day: {}.", busiest));
+        fields.push(Text::mrkdwn(format!("*Busiest day*\n{}", busiest)));
+    }
+    let mut message_blocks = vec![
+        Block::section(Text::mrkdwn(format!(
+            "*📅 Weekly meeting digest* · {}",
+            blocks::escape(&period)
+        ))),
+        Block::fields(fields),
+    ];
+    if !activity.top_creators.is_empty() {
+        let creators: Vec<String> = activity
+            .top_creators
+            .iter()
+            .map(|(user_id, count)| format!("<@{}> ({})", user_id, count))
+            .collect();
+        text.push_str(&format!(" Top creators: {}.", creators.join(", ")));
+        message_blocks.push(Block::section(Text::mrkdwn(format!(
+            "*Top creators*\n{}",
+            creators.join(" · ")
+        ))));
+    }
+    message_blocks.push(footer);
+
+    ChatMessage {
+        channel: schedule.channel_id.clone(),
+        text,
+        blocks: message_blocks,
+        thread: None,
+    }
+}
+
+pub fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::SlackTeam;
+    use crate::database::test_db;
+    use crate::slack::commands::MEET;
+    use crate::slack::fake::FakeSlackApi;
+    use crate::time::{Clock, TestClock};
+    use chrono::TimeZone;
+    use sqlx::SqlitePool;
+
+    const TEAM: &str = "T012AB3C4";
+    const CHANNEL: &str = "C012AB3CD";
+    const ADMIN: &str = "U012AB3CD";
+
+    /// A workspace with the bot installed and meetings on Tuesday 5 March
+    /// 2024, with no digest set up yet.
+    async fn workspace_db() -> (Database, SqlitePool) {
+        let (db, pool) = test_db().await;
+        db.store_slack_team(&SlackTeam::new(TEAM.to_string(), "xoxb-bot".into()))
+            .await
+            .unwrap();
+        let user = db.create_user(ADMIN, TEAM).await.unwrap();
+        for created_at in ["2024-03-05 09:00:00", "2024-03-05 15:00:00"] {
+            sqlx::query(
+                "INSERT INTO meetings (user_id, meet_link, created_at) VALUES (?1, 'https://meet.google.com/abc-defg-hij', ?2)",
+            )
+            .bind(user.id)
+            .bind(created_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        (db, pool)
+    }
+
+    fn schedule() -> DigestSchedule {
+        DigestSchedule {
+            channel_id: CHANNEL.to_string(),
+            weekday: Weekday::Mon,
+            hour: 9,
+            configured_by: Some(ADMIN.to_string()),
+        }
+    }
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339).unwrap().to_utc()
+    }
+
+    #[test]
+    fn test_latest_due() {
+        let schedule = schedule();
+
+        // Monday 11 March 2024
+        assert_eq!(
+            schedule.latest_due(at("2024-03-11T09:00:00Z")),
+            at("2024-03-11T09:00:00Z")
+        );
+        assert_eq!(
+            schedule.latest_due(at("2024-03-11T08:59:59Z")),
+            at("2024-03-04T09:00:00Z")
+        );
+        assert_eq!(
+            schedule.latest_due(at("2024-03-17T23:00:00Z")),
+            at("2024-03-11T09:00:00Z")
+        );
+        let friday = DigestSchedule {
+            weekday: Weekday::Fri,
+            hour: 16,
+            ..schedule
+        };
+        assert_eq!(
+            friday.latest_due(at("2024-03-11T09:00:00Z")),
+            at("2024-03-08T16:00:00Z")
+        );
+        assert_eq!(
+            friday.next_due(at("2024-03-11T09:00:00Z")),
+            at("2024-03-15T16:00:00Z")
+        );
+        assert_eq!(friday.describe(), "every Friday at 16:00 UTC");
+    }
+
+    #[tokio::test]
+    async fn test_digest_goes_out_once_when_due() {
+        let (db, _pool) = workspace_db().await;
+        let slack = FakeSlackApi::new();
+        // Set up on Thursday 7 March; the first digest is Monday's
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2024, 3, 7, 12, 0, 0).unwrap());
+        configure(&db, TEAM, &schedule(), clock.now())
+            .await
+            .unwrap();
+
+        send_due(&db, &slack, MEET, clock.now()).await.unwrap();
+        assert!(slack.posted().is_empty());
+
+        clock.set(at("2024-03-11T08:59:00Z"));
+        send_due(&db, &slack, MEET, clock.now()).await.unwrap();
+        assert!(slack.posted().is_empty());
+
+        // The hourly runs after it's due, including after a restart, which
+        // starts over from what the database says
+        for minutes in [1, 61, 121] {
+            clock.set(at("2024-03-11T08:59:00Z") + chrono::Duration::minutes(minutes));
+            send_due(&db, &slack, MEET, clock.now()).await.unwrap();
+        }
+        let posted = slack.posted();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].channel, CHANNEL);
+        assert_eq!(
+            posted[0].text,
+            "📅 Weekly meeting digest, 4 Mar – 10 Mar: 2 meetings. Busiest day: Tuesday 5 Mar (2). \
+             Top creators: <@U012AB3CD> (2)."
+        );
+        blocks::validate(&posted[0].blocks).unwrap();
+
+        clock.set(at("2024-03-18T09:00:00Z"));
+        send_due(&db, &slack, MEET, clock.now()).await.unwrap();
+        let posted = slack.posted();
+        assert_eq!(posted.len(), 2);
+        assert!(posted[1].text.contains("11 Mar – 17 Mar: no meetings"));
+    }
+
+    #[tokio::test]
+    async fn test_missed_digests_are_sent_once_when_back() {
+        let (db, _pool) = workspace_db().await;
+        let slack = FakeSlackApi::new();
+        configure(&db, TEAM, &schedule(), at("2024-03-01T12:00:00Z"))
+            .await
+            .unwrap();
+
+        // Down over two Mondays; only the latest digest goes out
+        send_due(&db, &slack, MEET, at("2024-03-13T10:00:00Z"))
+            .await
+            .unwrap();
+        send_due(&db, &slack, MEET, at("2024-03-13T11:00:00Z"))
+            .await
+            .unwrap();
+
+        let posted = slack.posted();
+        assert_eq!(posted.len(), 1);
+        assert!(posted[0].text.contains("4 Mar – 10 Mar"));
+    }
+
+    #[tokio::test]
+    async fn test_admin_hears_when_the_bot_is_not_in_the_channel() {
+        let (db, _pool) = workspace_db().await;
+        let slack = FakeSlackApi::new();
+        slack.fail_channel(CHANNEL, "not_in_channel");
+        configure(&db, TEAM, &schedule(), at("2024-03-07T12:00:00Z"))
+            .await
+            .unwrap();
+
+        send_due(&db, &slack, MEET, at("2024-03-11T09:00:00Z"))
+            .await
+            .unwrap();
+        send_due(&db, &slack, MEET, at("2024-03-11T10:00:00Z"))
+            .await
+            .unwrap();
+
+        let posted = slack.posted();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].channel, ADMIN);
+        assert!(posted[0].text.contains("not in that channel"));
+    }
+
+    #[tokio::test]
+    async fn test_other_failures_are_retried_on_the_next_run() {
+        let (db, _pool) = workspace_db().await;
+        let slack = FakeSlackApi::new();
+        slack.fail_channel(CHANNEL, "ratelimited");
+        configure(&db, TEAM, &schedule(), at("2024-03-07T12:00:00Z"))
+            .await
+            .unwrap();
+
+        assert!(send_due(&db, &slack, MEET, at("2024-03-11T09:00:00Z"))
+            .await
+            .is_err());
+
+        let slack = FakeSlackApi::new();
+        send_due(&db, &slack, MEET, at("2024-03-11T10:00:00Z"))
+            .await
+            .unwrap();
+        assert_eq!(slack.posted().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_disabled_digests_are_not_sent() {
+        let (db, _pool) = workspace_db().await;
+        let slack = FakeSlackApi::new();
+        configure(&db, TEAM, &schedule(), at("2024-03-07T12:00:00Z"))
+            .await
+            .unwrap();
+        assert_eq!(schedule_of(&db).await, Some(schedule()));
+
+        disable(&db, TEAM).await.unwrap();
+        send_due(&db, &slack, MEET, at("2024-03-11T09:00:00Z"))
+            .await
+            .unwrap();
+
+        assert!(slack.posted().is_empty());
+        assert_eq!(schedule_of(&db).await, None);
+    }
+
+    async fn schedule_of(db: &Database) -> Option<DigestSchedule> {
+        super::schedule(db, TEAM).await.unwrap()
+    }
+}
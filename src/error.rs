@@ -0,0 +1,486 @@
+//! The error handlers return, and how it is shown to whoever called.
+//!
+//! [`AppError`] turns into a response carrying its status and an
+//! [`ErrorReply`]; the [`reply`] middleware then shapes that into an
+//! ephemeral message for slash commands, since Slack only shows a generic
+//! failure for error statuses, and into a JSON body with the request id for
+//! everything else.
+
+use axum::{
+    extract::Request,
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use serde_json::json;
+use thiserror::Error;
+use tracing::{error, warn};
+
+use crate::crypto::CryptoError;
+use crate::google::GoogleApiError;
+use crate::handlers::slack::SlackResponse;
+use crate::request_id::RequestId;
+use crate::slack::SlackVerificationError;
+use crate::validation::ValidationError;
+
+const COMMANDS_PATH: &str = "/slack/commands";
+
+pub const INTERNAL_ERROR_TEXT: &str = "❌ Something went wrong. Please try again.";
+
+/// Which rate limit a request ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimit {
+    /// The user sent too many requests.
+    User,
+    /// The bot as a whole is taking too many.
+    Global,
+    /// The workspace created as many meetings today as its admins allow.
+    Team,
+}
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    /// A request body or query that couldn't be parsed.
+    #[error("Malformed request: {0}")]
+    BadRequest(String),
+    /// A request body over what its route accepts.
+    #[error("Request body too large")]
+    PayloadTooLarge,
+    /// Missing or wrong credentials for an endpoint of our own.
+    #[error("Missing or invalid credentials")]
+    Unauthorized,
+    /// Nothing, or nothing public, at a link of our own.
+    #[error("Not found")]
+    NotFound,
+    #[error("Rate limited ({0:?})")]
+    RateLimited(RateLimit),
+    /// The Slack user a request acts for has no usable Google account.
+    #[error("{0} hasn't connected a Google account to the bot")]
+    NotConnected(String),
+    #[error("Database error: {0}")]
+    Db(#[from] sqlx::Error),
+    #[error(transparent)]
+    Google(#[from] GoogleApiError),
+    #[error(transparent)]
+    Crypto(#[from] CryptoError),
+    #[error(transparent)]
+    SlackVerification(#[from] SlackVerificationError),
+    #[error(transparent)]
+    Internal(anyhow::Error),
+}
+
+/// Sorts an error from the database or other `anyhow`-returning code into
+/// the variant for its cause, so `?` keeps the right status.
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        let e = match e.downcast::<CryptoError>() {
+            Ok(e) => return Self::Crypto(e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<sqlx::Error>() {
+            Ok(e) => return Self::Db(e),
+            Err(e) => e,
+        };
+        let e = match e.downcast::<GoogleApiError>() {
+            Ok(e) => return Self::Google(e),
+            Err(e) => e,
+        };
+        match e.downcast::<ValidationError>() {
+            Ok(e) => Self::Validation(e),
+            Err(e) => Self::Internal(e),
+        }
+    }
+}
+
+impl AppError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::Validation(_) | AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
+            AppError::NotFound => StatusCode::NOT_FOUND,
+            AppError::RateLimited(RateLimit::User) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::RateLimited(RateLimit::Global) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::RateLimited(RateLimit::Team) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::NotConnected(_) => StatusCode::CONFLICT,
+            AppError::Google(GoogleApiError::Unauthorized) => StatusCode::UNAUTHORIZED,
+            AppError::Google(GoogleApiError::QuotaExceeded) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::Google(
+                GoogleApiError::MissingScope | GoogleApiError::ArtifactsUnavailable,
+            ) => StatusCode::FORBIDDEN,
+            AppError::Google(_) => StatusCode::BAD_GATEWAY,
+            AppError::SlackVerification(
+                SlackVerificationError::MissingSignature
+                | SlackVerificationError::MissingTimestamp
+                | SlackVerificationError::RequestTooOld
+                | SlackVerificationError::TimestampInFuture
+                | SlackVerificationError::SignatureMismatch,
+            ) => StatusCode::UNAUTHORIZED,
+            AppError::SlackVerification(_) => StatusCode::BAD_REQUEST,
+            AppError::Db(_) | AppError::Crypto(_) | AppError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// Stable identifier for the `error` field of JSON replies.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Validation(_) => "invalid_input",
+            AppError::BadRequest(_) => "bad_request",
+            AppError::PayloadTooLarge => "payload_too_large",
+            AppError::Unauthorized => "unauthorized",
+            AppError::NotFound => "not_found",
+            AppError::RateLimited(RateLimit::User) => "rate_limited",
+            AppError::RateLimited(RateLimit::Global) => "overloaded",
+            AppError::RateLimited(RateLimit::Team) => "daily_limit_reached",
+            AppError::NotConnected(_) => "google_not_connected",
+            AppError::Db(_) => "database_error",
+            AppError::Google(GoogleApiError::Unauthorized) => "google_unauthorized",
+            AppError::Google(GoogleApiError::QuotaExceeded) => "google_quota_exceeded",
+            AppError::Google(GoogleApiError::MissingScope) => "google_missing_scope",
+            AppError::Google(GoogleApiError::ArtifactsUnavailable) => {
+                "google_artifacts_unavailable"
+            }
+            AppError::Google(_) => "google_error",
+            AppError::Crypto(_) => "crypto_error",
+            AppError::SlackVerification(_) => "invalid_slack_request",
+            AppError::Internal(_) => "internal_error",
+        }
+    }
+
+    /// The reply to a slash command that failed this way. `None` for
+    /// requests that didn't come from Slack, which get the error status.
+    pub fn slack_text(&self) -> Option<String> {
+        let text = match self {
+            AppError::Validation(e) => format!("❌ {}", e),
+            AppError::BadRequest(_) => "❌ I couldn't read that request.".to_string(),
+            AppError::RateLimited(RateLimit::User) => {
+                "⏱️ Please slow down! You're sending commands too quickly.".to_string()
+            }
+            AppError::RateLimited(RateLimit::Global) => {
+                "🚫 Service temporarily unavailable due to high load. Please try again later."
+                    .to_string()
+            }
+            AppError::RateLimited(RateLimit::Team) => {
+                "🚫 Your workspace reached today's meeting limit. Please try again tomorrow, or ask the bot's admins to raise it."
+                    .to_string()
+            }
+            AppError::NotConnected(_) => {
+                "❌ Connect your Google account first. Run this command with `status` to connect."
+                    .to_string()
+            }
+            AppError::Db(_) => "❌ Sorry, there was a database error.".to_string(),
+            AppError::Google(GoogleApiError::Unauthorized) => {
+                "❌ Google no longer accepts your sign-in. Run this command with `status` to connect again."
+                    .to_string()
+            }
+            AppError::Google(GoogleApiError::QuotaExceeded) => {
+                "❌ Google isn't accepting new meetings from the bot right now. Please try again in a few minutes."
+                    .to_string()
+            }
+            AppError::Google(GoogleApiError::MissingScope) => {
+                "❌ You didn't give the bot permission for that when you connected Google. Run this command with `logout`, then connect again and allow it."
+                    .to_string()
+            }
+            AppError::Google(GoogleApiError::ArtifactsUnavailable) => {
+                "❌ Google won't record or transcribe meetings for your account. That needs a Google Workspace edition with Meet recording, turned on by your Workspace admin. Leave out `--record` and `--transcribe`, or ask the bot's admins to turn off `auto-record` and `auto-transcribe` for the workspace."
+                    .to_string()
+            }
+            AppError::Google(_) => {
+                "❌ Failed to create Google Meet link. Please try again.".to_string()
+            }
+            AppError::Crypto(_) => {
+                "❌ Sorry, there was an error checking your authentication.".to_string()
+            }
+            AppError::Internal(_) => INTERNAL_ERROR_TEXT.to_string(),
+            AppError::Unauthorized
+            | AppError::NotFound
+            | AppError::PayloadTooLarge
+            | AppError::SlackVerification(_) => return None,
+        };
+        Some(text)
+    }
+
+    /// Whether the request was at fault rather than the bot.
+    pub fn is_client_error(&self) -> bool {
+        self.status().is_client_error()
+    }
+
+    /// Logs the error at the level its cause deserves; done for every
+    /// error turned into a response.
+    pub fn log(&self) {
+        match self {
+            AppError::Db(e) => error!(tags.error_kind = "database", "Database error: {}", e),
+            AppError::Crypto(e) => error!(tags.error_kind = "crypto", "Crypto error: {}", e),
+            AppError::Google(
+                GoogleApiError::Unauthorized
+                | GoogleApiError::QuotaExceeded
+                | GoogleApiError::ArtifactsUnavailable
+                | GoogleApiError::MissingScope,
+            ) => {
+                warn!("{}", self)
+            }
+            AppError::Google(e) => error!(tags.error_kind = "google_api", "{}", e),
+            AppError::Internal(e) => error!(tags.error_kind = "internal", "{:#}", e),
+            _ => warn!("Rejected request: {}", self),
+        }
+    }
+}
+
+/// What [`reply`] needs to describe a failed request.
+#[derive(Debug, Clone, Serialize)]
+struct ErrorReply {
+    error: &'static str,
+    /// Why the request was refused; only given for client errors, whose
+    /// messages never carry anything internal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip)]
+    slack_text: Option<String>,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        self.log();
+        let status = self.status();
+        error_response(
+            status,
+            ErrorReply {
+                error: self.code(),
+                message: status.is_client_error().then(|| self.to_string()),
+                slack_text: self.slack_text(),
+            },
+        )
+    }
+}
+
+/// The response for a failure nothing more is known about, such as a
+/// caught panic.
+pub fn internal_error_response() -> Response {
+    error_response(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorReply {
+            error: "internal_error",
+            message: None,
+            slack_text: Some(INTERNAL_ERROR_TEXT.to_string()),
+        },
+    )
+}
+
+fn error_response(status: StatusCode, reply: ErrorReply) -> Response {
+    let mut response = (status, Json(&reply)).into_response();
+    response.extensions_mut().insert(reply);
+    response
+}
+
+/// Middleware shaping error responses for the caller: slash commands get an
+/// ephemeral reply with a support reference, and everything else a JSON
+/// body carrying the request id.
+pub async fn reply(request_id: RequestId, request: Request, next: Next) -> Response {
+    let is_command = request.uri().path() == COMMANDS_PATH;
+    let response = next.run(request).await;
+    let Some(reply) = response.extensions().get::<ErrorReply>().cloned() else {
+        return response;
+    };
+
+    match reply.slack_text {
+        Some(text) if is_command => {
+            Json(SlackResponse::ephemeral(text).with_error_ref(&request_id)).into_response()
+        }
+        _ => (
+            response.status(),
+            Json(json!({
+                "error": reply.error,
+                "message": reply.message,
+                "request_id": request_id.as_str(),
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_id::{self, REQUEST_ID_HEADER};
+    use axum::{body::Body, middleware, routing::post, Router};
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    type MakeError = fn() -> AppError;
+
+    /// Sends `error` back from a handler at `path`, through [`reply`].
+    async fn send(path: &str, error: MakeError) -> (StatusCode, String, Value) {
+        let app = Router::new()
+            .route(path, post(move || async move { Err::<(), _>(error()) }))
+            .layer(middleware::from_fn(reply))
+            .layer(middleware::from_fn(request_id::propagate));
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(path)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let status = response.status();
+        let request_id = response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, request_id, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_each_variant_gets_its_status_and_code() {
+        let cases: [(MakeError, StatusCode, &str); 15] = [
+            (
+                || ValidationError::UnknownCommand.into(),
+                StatusCode::BAD_REQUEST,
+                "invalid_input",
+            ),
+            (
+                || AppError::BadRequest("not a form".into()),
+                StatusCode::BAD_REQUEST,
+                "bad_request",
+            ),
+            (
+                || AppError::PayloadTooLarge,
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+            ),
+            (
+                || AppError::Unauthorized,
+                StatusCode::UNAUTHORIZED,
+                "unauthorized",
+            ),
+            (|| AppError::NotFound, StatusCode::NOT_FOUND, "not_found"),
+            (
+                || AppError::RateLimited(RateLimit::User),
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limited",
+            ),
+            (
+                || AppError::RateLimited(RateLimit::Team),
+                StatusCode::TOO_MANY_REQUESTS,
+                "daily_limit_reached",
+            ),
+            (
+                || AppError::NotConnected("U012AB3CD".into()),
+                StatusCode::CONFLICT,
+                "google_not_connected",
+            ),
+            (
+                || sqlx::Error::PoolTimedOut.into(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "database_error",
+            ),
+            (
+                || GoogleApiError::QuotaExceeded.into(),
+                StatusCode::SERVICE_UNAVAILABLE,
+                "google_quota_exceeded",
+            ),
+            (
+                || GoogleApiError::MissingScope.into(),
+                StatusCode::FORBIDDEN,
+                "google_missing_scope",
+            ),
+            (
+                || GoogleApiError::ArtifactsUnavailable.into(),
+                StatusCode::FORBIDDEN,
+                "google_artifacts_unavailable",
+            ),
+            (
+                || CryptoError::DecryptionFailed.into(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "crypto_error",
+            ),
+            (
+                || SlackVerificationError::SignatureMismatch.into(),
+                StatusCode::UNAUTHORIZED,
+                "invalid_slack_request",
+            ),
+            (
+                || AppError::Internal(anyhow::anyhow!("bug")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "internal_error",
+            ),
+        ];
+
+        for (error, expected_status, expected_code) in cases {
+            let (status, request_id, body) = send("/api/thing", error).await;
+            assert_eq!(status, expected_status, "{}", expected_code);
+            assert_eq!(body["error"], expected_code);
+            assert_eq!(body["request_id"], request_id);
+            // Only client errors say why; server errors could leak internals
+            assert_eq!(
+                body["message"].is_string(),
+                expected_status.is_client_error(),
+                "{}",
+                expected_code
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slash_commands_get_an_ephemeral_reply() {
+        let (status, request_id, body) =
+            send(COMMANDS_PATH, || sqlx::Error::PoolTimedOut.into()).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["response_type"], "ephemeral");
+        assert_eq!(
+            body["text"],
+            format!(
+                "❌ Sorry, there was a database error. (ref: {})",
+                &request_id[..6]
+            )
+        );
+
+        let (status, _, body) =
+            send(COMMANDS_PATH, || AppError::RateLimited(RateLimit::User)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body["text"].as_str().unwrap().starts_with("⏱️"));
+    }
+
+    #[tokio::test]
+    async fn test_bad_signatures_on_commands_keep_their_status() {
+        let (status, _, body) = send(COMMANDS_PATH, || {
+            SlackVerificationError::RequestTooOld.into()
+        })
+        .await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"], "invalid_slack_request");
+    }
+
+    #[test]
+    fn test_anyhow_errors_are_sorted_by_cause() {
+        use anyhow::Context;
+
+        let crypto: anyhow::Result<()> =
+            Err(CryptoError::DecryptionFailed).context("Failed to load token");
+        assert!(matches!(
+            AppError::from(crypto.unwrap_err()),
+            AppError::Crypto(CryptoError::DecryptionFailed)
+        ));
+        assert!(matches!(
+            AppError::from(anyhow::Error::new(sqlx::Error::RowNotFound)),
+            AppError::Db(_)
+        ));
+        assert!(matches!(
+            AppError::from(anyhow::anyhow!("something else")),
+            AppError::Internal(_)
+        ));
+    }
+}
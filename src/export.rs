@@ -0,0 +1,236 @@
+//! A copy of everything the bot keeps about a user, for `/meet
+//! export-my-data`: their user row, preferences and meetings. Tokens are
+//! never part of it; the export only says whether a Google account is
+//! connected.
+//!
+//! Without a bot token to send the file with, the user gets a download link
+//! instead. Its token carries the Slack user id and an expiry, signed with
+//! HMAC-SHA256 under the Slack signing secret, so nothing is stored for it
+//! and the export is built fresh when the link is opened.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+use crate::database::models::{Meeting, User};
+use crate::database::Database;
+use crate::secret::SecretString;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How long a download link works.
+pub const LINK_TTL: Duration = Duration::minutes(15);
+
+#[derive(Debug, Serialize)]
+pub struct UserExport {
+    pub exported_at: DateTime<Utc>,
+    pub user: User,
+    pub google_account_connected: bool,
+    pub preferences: BTreeMap<String, String>,
+    /// Newest first.
+    pub meetings: Vec<Meeting>,
+}
+
+impl UserExport {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("exports serialize")
+    }
+}
+
+/// What the bot keeps about `user`, as of `now`.
+pub async fn gather(db: &Database, user: &User, now: DateTime<Utc>) -> Result<UserExport> {
+    Ok(UserExport {
+        exported_at: now,
+        user: user.clone(),
+        google_account_connected: db.get_oauth_token(user.id).await?.is_some(),
+        preferences: db.user_settings(user.id).await?.into_iter().collect(),
+        meetings: db.get_user_meetings(user.id, i64::MAX).await?,
+    })
+}
+
+/// Name of the export file of `slack_user_id`.
+pub fn filename(slack_user_id: &str) -> String {
+    format!("google-meet-bot-{}.json", slack_user_id)
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ExportLinkError {
+    #[error("malformed export link token")]
+    Malformed,
+    #[error("export link signature doesn't match")]
+    BadSignature,
+    #[error("export link expired")]
+    Expired,
+}
+
+/// A download link token for the export of `slack_user_id`, working until
+/// `expires_at`.
+pub fn sign_link_token(
+    secret: &SecretString,
+    slack_user_id: &str,
+    expires_at: DateTime<Utc>,
+) -> String {
+    let expires_at = expires_at.timestamp();
+    let signature = hex::encode(
+        link_mac(secret, slack_user_id, expires_at)
+            .finalize()
+            .into_bytes(),
+    );
+    format!("{}.{}.{}", slack_user_id, expires_at, signature)
+}
+
+/// The Slack user id a download link token was signed for, if it is
+/// untouched and hasn't expired at `now`.
+pub fn verify_link_token(
+    secret: &SecretString,
+    token: &str,
+    now: DateTime<Utc>,
+) -> Result<String, ExportLinkError> {
+    let mut parts = token.split('.');
+    let (Some(slack_user_id), Some(expires_at), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(ExportLinkError::Malformed);
+    };
+    let expires_at: i64 = expires_at.parse().map_err(|_| ExportLinkError::Malformed)?;
+    let signature = hex::decode(signature).map_err(|_| ExportLinkError::Malformed)?;
+
+    link_mac(secret, slack_user_id, expires_at)
+        .verify_slice(&signature)
+        .map_err(|_| ExportLinkError::BadSignature)?;
+    if now.timestamp() >= expires_at {
+        return Err(ExportLinkError::Expired);
+    }
+    Ok(slack_user_id.to_string())
+}
+
+fn link_mac(secret: &SecretString, slack_user_id: &str, expires_at: i64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.expose().as_bytes()).expect("HMAC takes any key size");
+    // Prefixed so the signature can't be mistaken for one over a Slack
+    // request, which uses the same secret
+    mac.update(format!("export:{}:{}", slack_user_id, expires_at).as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::{connected_user, test_state};
+    use crate::database::models::MeetLinkKind;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_export_has_no_secrets() {
+        let (state, _pool) = test_state().await;
+        let user = connected_user(&state).await;
+        state
+            .db
+            .set_user_setting(user.id, "timezone", "Europe/Warsaw")
+            .await
+            .unwrap();
+        state
+            .db
+            .create_meeting(&Meeting::new(
+                user.id,
+                "https://meet.google.com/abc-defg-hij".to_string(),
+                Some("Standup".to_string()),
+                MeetLinkKind::Meet,
+            ))
+            .await
+            .unwrap();
+
+        let export = gather(&state.db, &user, at("2024-03-05T10:00:00Z"))
+            .await
+            .unwrap();
+        let json = export.to_json();
+
+        assert!(export.google_account_connected);
+        assert_eq!(export.preferences["timezone"], "Europe/Warsaw");
+        assert_eq!(export.meetings.len(), 1);
+        assert!(
+            json.contains("https://meet.google.com/abc-defg-hij"),
+            "{}",
+            json
+        );
+        assert!(
+            json.contains("\"slack_user_id\": \"U012AB3CD\""),
+            "{}",
+            json
+        );
+        let token = state.db.get_oauth_token(user.id).await.unwrap().unwrap();
+        assert!(!json.contains(token.access_token.expose()), "{}", json);
+        assert!(!json.contains("token"), "{}", json);
+    }
+
+    #[test]
+    fn test_link_token_round_trip() {
+        let secret = "signing-secret".into();
+        let token = sign_link_token(&secret, "U012AB3CD", at("2024-03-05T10:15:00Z"));
+
+        assert!(token.starts_with("U012AB3CD.1709633700."), "{}", token);
+        assert_eq!(
+            verify_link_token(&secret, &token, at("2024-03-05T10:14:59Z")),
+            Ok("U012AB3CD".to_string())
+        );
+    }
+
+    #[test]
+    fn test_link_token_expires() {
+        let secret = "signing-secret".into();
+        let token = sign_link_token(&secret, "U012AB3CD", at("2024-03-05T10:15:00Z"));
+
+        assert_eq!(
+            verify_link_token(&secret, &token, at("2024-03-05T10:15:00Z")),
+            Err(ExportLinkError::Expired)
+        );
+    }
+
+    #[test]
+    fn test_tampered_link_tokens_are_rejected() {
+        let secret = "signing-secret".into();
+        let now = at("2024-03-05T10:00:00Z");
+        let token = sign_link_token(&secret, "U012AB3CD", at("2024-03-05T10:15:00Z"));
+        let signature = token.rsplit('.').next().unwrap();
+
+        let cases = [
+            (
+                format!("U098ZY7XW.1709633700.{}", signature),
+                ExportLinkError::BadSignature,
+            ),
+            (
+                format!("U012AB3CD.1809633700.{}", signature),
+                ExportLinkError::BadSignature,
+            ),
+            (format!("{}00", token), ExportLinkError::BadSignature),
+            (
+                "U012AB3CD.1709633700".to_string(),
+                ExportLinkError::Malformed,
+            ),
+            (format!("{}.x", token), ExportLinkError::Malformed),
+            (
+                "U012AB3CD.soon.abcd".to_string(),
+                ExportLinkError::Malformed,
+            ),
+        ];
+        for (tampered, expected) in cases {
+            assert_eq!(
+                verify_link_token(&secret, &tampered, now),
+                Err(expected),
+                "{}",
+                tampered
+            );
+        }
+        assert_eq!(
+            verify_link_token(&"another-secret".into(), &token, now),
+            Err(ExportLinkError::BadSignature)
+        );
+    }
+}
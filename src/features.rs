@@ -0,0 +1,281 @@
+//! Feature flags gating experimental subcommands, so they can be tried with
+//! a few workspaces before everyone gets them. `FEATURE_FLAGS` turns flags on
+//! everywhere; a workspace's overrides in `team_settings`, set with
+//! `/meet admin flags`, win over it.
+
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::database::Database;
+
+/// Prefix of the `team_settings` keys holding flag overrides, followed by
+/// the flag name. Values are `on` or `off`.
+pub const TEAM_SETTING_PREFIX: &str = "feature.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    /// `/meet cancel` and `/meet end`.
+    Cancel,
+    /// `/meet set`.
+    Settings,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 2] = [Feature::Cancel, Feature::Settings];
+
+    /// Name used in `FEATURE_FLAGS` and `/meet admin flags`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Cancel => "cancel",
+            Feature::Settings => "settings",
+        }
+    }
+
+    /// What the feature does, naming `command` as the workspace's `/meet`.
+    pub fn description(self, command: &str) -> String {
+        match self {
+            Feature::Cancel => format!(
+                "Cancel meetings with `{0} cancel` and end their calls with `{0} end`",
+                command
+            ),
+            Feature::Settings => format!("Change preferences with `{} set`", command),
+        }
+    }
+
+    fn team_setting_key(self) -> String {
+        format!("{}{}", TEAM_SETTING_PREFIX, self.name())
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("unknown feature flag {0:?}")]
+pub struct UnknownFeature(pub String);
+
+impl FromStr for Feature {
+    type Err = UnknownFeature;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.name().eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| UnknownFeature(name.trim().to_string()))
+    }
+}
+
+/// Whether a flag is on for a workspace, and why.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlagState {
+    pub enabled: bool,
+    /// The workspace's override, if it has one.
+    pub team_override: Option<bool>,
+}
+
+/// The flags turned on for every workspace.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeatureFlags {
+    global: BTreeSet<Feature>,
+}
+
+impl FeatureFlags {
+    pub fn new(global: impl IntoIterator<Item = Feature>) -> Self {
+        Self {
+            global: global.into_iter().collect(),
+        }
+    }
+
+    /// Parses a comma-separated list of flag names, as in `FEATURE_FLAGS`.
+    pub fn parse(list: &str) -> Result<Self, UnknownFeature> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect::<Result<BTreeSet<_>, _>>()
+            .map(|global| Self { global })
+    }
+
+    pub fn is_globally_enabled(&self, feature: Feature) -> bool {
+        self.global.contains(&feature)
+    }
+
+    /// Whether `feature` is on for the workspace `slack_team_id`.
+    pub async fn is_enabled(
+        &self,
+        db: &Database,
+        feature: Feature,
+        slack_team_id: &str,
+    ) -> Result<bool> {
+        let value = db
+            .get_team_setting(slack_team_id, &feature.team_setting_key())
+            .await?;
+        let team_override = value.and_then(|value| parse_override(feature.name(), &value));
+        Ok(team_override.unwrap_or_else(|| self.is_globally_enabled(feature)))
+    }
+
+    /// Every flag with its state in the workspace `slack_team_id`.
+    pub async fn states(
+        &self,
+        db: &Database,
+        slack_team_id: &str,
+    ) -> Result<Vec<(Feature, FlagState)>> {
+        let overrides = db
+            .team_settings_with_prefix(slack_team_id, TEAM_SETTING_PREFIX)
+            .await?;
+        let mut states = Vec::new();
+        for feature in Feature::ALL {
+            let team_override = overrides
+                .iter()
+                .find(|(key, _)| *key == feature.team_setting_key())
+                .and_then(|(_, value)| parse_override(feature.name(), value));
+            let enabled = team_override.unwrap_or_else(|| self.is_globally_enabled(feature));
+            states.push((
+                feature,
+                FlagState {
+                    enabled,
+                    team_override,
+                },
+            ));
+        }
+        Ok(states)
+    }
+
+    /// Overrides `feature` for the workspace; `None` drops the override so
+    /// the global setting applies again.
+    pub async fn set_override(
+        db: &Database,
+        feature: Feature,
+        slack_team_id: &str,
+        enabled: Option<bool>,
+    ) -> Result<()> {
+        let key = feature.team_setting_key();
+        match enabled {
+            Some(enabled) => {
+                db.set_team_setting(slack_team_id, &key, if enabled { "on" } else { "off" })
+                    .await
+            }
+            None => db.delete_team_setting(slack_team_id, &key).await,
+        }
+    }
+}
+
+/// A stored override; anything but `on` or `off` is ignored so the global
+/// setting applies.
+fn parse_override(flag: &str, value: &str) -> Option<bool> {
+    match value {
+        "on" => Some(true),
+        "off" => Some(false),
+        _ => {
+            warn!("Ignoring override {:?} of feature flag {}", value, flag);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_db;
+
+    #[test]
+    fn test_parse_flag_list() {
+        assert_eq!(
+            FeatureFlags::parse(" Cancel, ,settings ").unwrap(),
+            FeatureFlags::new([Feature::Cancel, Feature::Settings])
+        );
+        assert_eq!(FeatureFlags::parse("").unwrap(), FeatureFlags::default());
+        assert_eq!(
+            FeatureFlags::parse("cancel,modal"),
+            Err(UnknownFeature("modal".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_global_flags_apply_to_every_team() {
+        let (db, _pool) = test_db().await;
+        let flags = FeatureFlags::new([Feature::Cancel]);
+
+        assert!(flags
+            .is_enabled(&db, Feature::Cancel, "T012AB3C4")
+            .await
+            .unwrap());
+        assert!(!flags
+            .is_enabled(&db, Feature::Settings, "T012AB3C4")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_team_overrides_win_over_global_flags() {
+        let (db, _pool) = test_db().await;
+        let flags = FeatureFlags::new([Feature::Cancel]);
+        FeatureFlags::set_override(&db, Feature::Cancel, "T012AB3C4", Some(false))
+            .await
+            .unwrap();
+        FeatureFlags::set_override(&db, Feature::Settings, "T012AB3C4", Some(true))
+            .await
+            .unwrap();
+
+        for (feature, pilot, other) in [
+            (Feature::Cancel, false, true),
+            (Feature::Settings, true, false),
+        ] {
+            assert_eq!(
+                flags.is_enabled(&db, feature, "T012AB3C4").await.unwrap(),
+                pilot
+            );
+            assert_eq!(
+                flags.is_enabled(&db, feature, "T098ZY7XW").await.unwrap(),
+                other
+            );
+        }
+        assert_eq!(
+            flags.states(&db, "T012AB3C4").await.unwrap(),
+            vec![
+                (
+                    Feature::Cancel,
+                    FlagState {
+                        enabled: false,
+                        team_override: Some(false)
+                    }
+                ),
+                (
+                    Feature::Settings,
+                    FlagState {
+                        enabled: true,
+                        team_override: Some(true)
+                    }
+                ),
+            ]
+        );
+
+        FeatureFlags::set_override(&db, Feature::Cancel, "T012AB3C4", None)
+            .await
+            .unwrap();
+        assert!(flags
+            .is_enabled(&db, Feature::Cancel, "T012AB3C4")
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_override_values_fall_back_to_the_global_setting() {
+        let (db, _pool) = test_db().await;
+        db.set_team_setting("T012AB3C4", "feature.cancel", "yes please")
+            .await
+            .unwrap();
+
+        assert!(!FeatureFlags::default()
+            .is_enabled(&db, Feature::Cancel, "T012AB3C4")
+            .await
+            .unwrap());
+    }
+}
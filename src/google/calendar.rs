@@ -0,0 +1,100 @@
+//! Links opening a new Google Calendar event filled in for a meeting, so
+//! people can add a scheduled meeting to their own calendars. The bot
+//! creates Meet spaces, not events, so there is no event of its own to
+//! link to.
+
+use chrono::{DateTime, Duration, Utc};
+use url::Url;
+
+use crate::text;
+
+/// Where Google Calendar opens a new event from query parameters.
+const NEW_EVENT_URL: &str = "https://calendar.google.com/calendar/render";
+
+/// How long an event without an end lasts, as Google Calendar's default.
+pub const DEFAULT_EVENT_LENGTH: Duration = Duration::hours(1);
+
+/// What the event is called when the meeting has no title.
+pub const UNTITLED: &str = "Google Meet";
+
+/// Most chars of a title put in a link, which keeps links of titles full
+/// of emoji well within what Slack takes for a button URL.
+const MAX_TITLE: usize = 150;
+
+/// A link opening a new event for the meeting at `meet_link` from
+/// `starts_at` until `ends_at`, or for an hour without an end.
+pub fn add_to_calendar_link(
+    title: Option<&str>,
+    meet_link: &str,
+    starts_at: DateTime<Utc>,
+    ends_at: Option<DateTime<Utc>>,
+) -> String {
+    let ends_at = ends_at.unwrap_or(starts_at + DEFAULT_EVENT_LENGTH);
+    let dates = format!("{}/{}", calendar_time(starts_at), calendar_time(ends_at));
+    let title = text::truncate_chars(title.unwrap_or(UNTITLED), MAX_TITLE);
+    Url::parse_with_params(
+        NEW_EVENT_URL,
+        [
+            ("action", "TEMPLATE"),
+            ("text", &title),
+            ("dates", &dates),
+            ("details", meet_link),
+            ("location", meet_link),
+        ],
+    )
+    .expect("the new event URL is valid")
+    .to_string()
+}
+
+/// `at` the way the `dates` parameter takes it.
+fn calendar_time(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_fills_in_the_event() {
+        let starts_at = "2024-03-05T07:00:00Z".parse().unwrap();
+        let link = add_to_calendar_link(
+            Some("Q&A: 🇵🇱 sync"),
+            "https://meet.google.com/abc-defg-hij",
+            starts_at,
+            Some("2024-03-05T07:45:00Z".parse().unwrap()),
+        );
+
+        let url = Url::parse(&link).unwrap();
+        assert_eq!(url.host_str(), Some("calendar.google.com"));
+        let params: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+        assert_eq!(param("action"), Some("TEMPLATE"));
+        assert_eq!(param("text"), Some("Q&A: 🇵🇱 sync"));
+        assert_eq!(param("dates"), Some("20240305T070000Z/20240305T074500Z"));
+        assert_eq!(
+            param("location"),
+            Some("https://meet.google.com/abc-defg-hij")
+        );
+
+        // Without an end or a title
+        let link = add_to_calendar_link(
+            None,
+            "https://meet.google.com/abc-defg-hij",
+            starts_at,
+            None,
+        );
+        let url = Url::parse(&link).unwrap();
+        assert!(url
+            .query_pairs()
+            .any(|(key, value)| key == "dates" && value == "20240305T070000Z/20240305T080000Z"));
+        assert!(url
+            .query_pairs()
+            .any(|(key, value)| key == "text" && value == "Google Meet"));
+    }
+}
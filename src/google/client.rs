@@ -0,0 +1,1185 @@
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::time::Instant;
+use tracing::{warn, Instrument, Span};
+
+use super::{
+    ConferenceArtifact, ConferenceRecord, CreatedMeeting, GoogleApi, GoogleApiError,
+    MeetingOptions, Participant,
+};
+use crate::http_client::{self, Route};
+use crate::observability::{self, Phase};
+use crate::secret::SecretString;
+use crate::telemetry::{metrics, otel};
+
+const MEET_BASE_URL: &str = "https://meet.googleapis.com";
+
+/// Longest part of an error response kept in [`GoogleApiError::Api`].
+const MAX_ERROR_MESSAGE_LEN: usize = 500;
+
+/// Most pages read from one list call, so a misbehaving page token can't
+/// keep the bot fetching forever.
+const MAX_PAGES: usize = 20;
+
+/// Items asked for per page of participants; the API's maximum is 250.
+const PARTICIPANTS_PAGE_SIZE: &str = "100";
+
+#[derive(Debug, Serialize)]
+struct CreateSpaceRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    config: Option<SpaceConfig>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SpaceConfig {
+    access_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artifact_config: Option<ArtifactConfig>,
+}
+
+/// Unset parts keep the space's defaults, which are off.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    recording_config: Option<RecordingConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    transcription_config: Option<TranscriptionConfig>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordingConfig {
+    auto_recording_generation: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscriptionConfig {
+    auto_transcription_generation: &'static str,
+}
+
+impl CreateSpaceRequest {
+    fn new(options: &MeetingOptions) -> Self {
+        let artifacts = options.artifacts;
+        Self {
+            config: Some(SpaceConfig {
+                access_type: if options.open_access {
+                    "OPEN" // Anyone with the link can join
+                } else {
+                    "TRUSTED"
+                }
+                .to_string(),
+                artifact_config: artifacts.any().then(|| ArtifactConfig {
+                    recording_config: artifacts.recording.then_some(RecordingConfig {
+                        auto_recording_generation: "ON",
+                    }),
+                    transcription_config: artifacts.transcription.then_some(TranscriptionConfig {
+                        auto_transcription_generation: "ON",
+                    }),
+                }),
+            }),
+        }
+    }
+}
+
+/// A member added to a space. Members are only in the API's `v2beta`
+/// version so far.
+#[derive(Debug, Serialize)]
+struct MemberRequest<'a> {
+    email: &'a str,
+    role: &'static str,
+}
+
+#[derive(Debug, Deserialize)]
+struct Space {
+    name: String,
+    #[serde(rename = "meetingUri")]
+    meeting_uri: String,
+}
+
+/// A page of a list call.
+trait Page: DeserializeOwned {
+    type Item;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>);
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConferenceRecordPage {
+    #[serde(default)]
+    conference_records: Vec<ConferenceRecordResource>,
+    next_page_token: Option<String>,
+}
+
+impl Page for ConferenceRecordPage {
+    type Item = ConferenceRecordResource;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.conference_records, self.next_page_token)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ConferenceRecordResource {
+    name: String,
+    start_time: DateTime<Utc>,
+    end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ParticipantPage {
+    #[serde(default)]
+    participants: Vec<ParticipantResource>,
+    next_page_token: Option<String>,
+}
+
+impl Page for ParticipantPage {
+    type Item = ParticipantResource;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.participants, self.next_page_token)
+    }
+}
+
+/// A participant is exactly one of a signed-in, anonymous or phone user.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ParticipantResource {
+    earliest_start_time: DateTime<Utc>,
+    latest_end_time: Option<DateTime<Utc>>,
+    signedin_user: Option<ParticipantUser>,
+    anonymous_user: Option<ParticipantUser>,
+    phone_user: Option<ParticipantUser>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ParticipantUser {
+    display_name: Option<String>,
+}
+
+impl From<ParticipantResource> for Participant {
+    fn from(resource: ParticipantResource) -> Self {
+        let display_name = [
+            resource.signedin_user,
+            resource.anonymous_user,
+            resource.phone_user,
+        ]
+        .into_iter()
+        .flatten()
+        .find_map(|user| user.display_name)
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "Unknown participant".to_string());
+        Participant {
+            display_name,
+            earliest_start_time: resource.earliest_start_time,
+            latest_end_time: resource.latest_end_time,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RecordingPage {
+    #[serde(default)]
+    recordings: Vec<ArtifactResource>,
+    next_page_token: Option<String>,
+}
+
+impl Page for RecordingPage {
+    type Item = ArtifactResource;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.recordings, self.next_page_token)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TranscriptPage {
+    #[serde(default)]
+    transcripts: Vec<ArtifactResource>,
+    next_page_token: Option<String>,
+}
+
+impl Page for TranscriptPage {
+    type Item = ArtifactResource;
+
+    fn into_parts(self) -> (Vec<Self::Item>, Option<String>) {
+        (self.transcripts, self.next_page_token)
+    }
+}
+
+/// A recording keeps its file in `driveDestination`, a transcript its
+/// document in `docsDestination`. Either is only there to open once the
+/// state is `FILE_GENERATED`; before that the call is still being recorded
+/// (`STARTED`) or the file processed (`ENDED`).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactResource {
+    name: String,
+    #[serde(default)]
+    state: String,
+    start_time: DateTime<Utc>,
+    drive_destination: Option<ArtifactDestination>,
+    docs_destination: Option<ArtifactDestination>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ArtifactDestination {
+    export_uri: Option<String>,
+}
+
+impl From<ArtifactResource> for ConferenceArtifact {
+    fn from(resource: ArtifactResource) -> Self {
+        let link = if resource.state == "FILE_GENERATED" {
+            resource
+                .drive_destination
+                .or(resource.docs_destination)
+                .and_then(|destination| destination.export_uri)
+        } else {
+            None
+        };
+        ConferenceArtifact {
+            name: resource.name,
+            start_time: resource.start_time,
+            link,
+        }
+    }
+}
+
+/// The real Google APIs, sharing one connection pool.
+#[derive(Debug, Clone)]
+pub struct GoogleClient {
+    http: Client,
+    meet_base_url: String,
+}
+
+impl GoogleClient {
+    pub fn new(http: Client) -> Self {
+        Self::with_meet_base_url(http, MEET_BASE_URL)
+    }
+
+    pub fn with_meet_base_url(http: Client, meet_base_url: &str) -> Self {
+        Self {
+            http,
+            meet_base_url: meet_base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    async fn request_meet_space(
+        &self,
+        access_token: &SecretString,
+        options: &MeetingOptions,
+    ) -> Result<CreatedMeeting, GoogleApiError> {
+        let space_request = CreateSpaceRequest::new(options);
+
+        let url = format!("{}/v2/spaces", self.meet_base_url);
+        let span = otel::client_span!("google.create_space", "POST", url.as_str());
+        let request = self
+            .http
+            .post(&url)
+            .headers(otel::trace_headers(&span))
+            .bearer_auth(access_token.expose())
+            .header("Content-Type", "application/json")
+            .json(&space_request);
+        let response = http_client::send(Route::google("/v2/spaces"), request)
+            .instrument(span.clone())
+            .await?;
+        otel::record_status(&span, response.status());
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            return Err(match classify_error(status, body) {
+                // Google refuses the whole space when the account can't
+                // record or transcribe, with a plain permission error
+                GoogleApiError::Api { status: 403, .. } if options.artifacts.any() => {
+                    GoogleApiError::ArtifactsUnavailable
+                }
+                error => error,
+            });
+        }
+
+        let space: Space = response.json().await?;
+        Ok(CreatedMeeting {
+            name: space.name,
+            meeting_uri: space.meeting_uri,
+        })
+    }
+
+    async fn request_space(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<Option<CreatedMeeting>, GoogleApiError> {
+        let url = format!("{}/v2/{}", self.meet_base_url, space_name);
+        let span = otel::client_span!("google.get_space", "GET", url.as_str());
+        let request = self
+            .http
+            .get(&url)
+            .headers(otel::trace_headers(&span))
+            .bearer_auth(access_token.expose());
+        let response = http_client::send(Route::google("/v2/spaces/{space}"), request)
+            .instrument(span.clone())
+            .await?;
+        otel::record_status(&span, response.status());
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            let body = response.text().await?;
+            return Err(classify_error(status, body));
+        }
+
+        let space: Space = response.json().await?;
+        Ok(Some(CreatedMeeting {
+            name: space.name,
+            meeting_uri: space.meeting_uri,
+        }))
+    }
+
+    async fn request_space_member(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+        email: &str,
+    ) -> Result<(), GoogleApiError> {
+        let url = format!("{}/v2beta/{}/members", self.meet_base_url, space_name);
+        let span = otel::client_span!("google.create_member", "POST", url.as_str());
+        let request = self
+            .http
+            .post(&url)
+            .headers(otel::trace_headers(&span))
+            .bearer_auth(access_token.expose())
+            .json(&MemberRequest {
+                email,
+                role: "COHOST",
+            });
+        let response = http_client::send(Route::google("/v2beta/spaces/{space}/members"), request)
+            .instrument(span.clone())
+            .await?;
+        otel::record_status(&span, response.status());
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            return Err(match classify_error(status, body) {
+                // An address that isn't a Google account, or is one outside
+                // the organization, is an invalid argument or not found
+                GoogleApiError::Api {
+                    status: 400 | 404, ..
+                } => GoogleApiError::MemberRejected {
+                    email: email.to_string(),
+                },
+                error => error,
+            });
+        }
+        Ok(())
+    }
+
+    async fn request_end_conference(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<bool, GoogleApiError> {
+        let url = format!(
+            "{}/v2/{}:endActiveConference",
+            self.meet_base_url, space_name
+        );
+        let span = otel::client_span!("google.end_active_conference", "POST", url.as_str());
+        let request = self
+            .http
+            .post(&url)
+            .headers(otel::trace_headers(&span))
+            .bearer_auth(access_token.expose())
+            .json(&serde_json::json!({}));
+        let response = http_client::send(
+            Route::google("/v2/spaces/{space}:endActiveConference"),
+            request,
+        )
+        .instrument(span.clone())
+        .await?;
+        otel::record_status(&span, response.status());
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await?;
+            // Google refuses to end a call in a space where none is going on
+            if status == StatusCode::BAD_REQUEST && body.contains("FAILED_PRECONDITION") {
+                return Ok(false);
+            }
+            return Err(classify_error(status, body));
+        }
+        Ok(true)
+    }
+
+    /// Every item of the list call at `path`, following page tokens for up
+    /// to [`MAX_PAGES`] pages. `route` is the template of `path`.
+    async fn list_all<P: Page>(
+        &self,
+        span: fn(&str) -> Span,
+        route: Route,
+        access_token: &SecretString,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<P::Item>, GoogleApiError> {
+        let url = format!("{}/v2/{}", self.meet_base_url, path);
+        let mut items = Vec::new();
+        let mut page_token: Option<String> = None;
+
+        for _ in 0..MAX_PAGES {
+            let span = span(&url);
+            let mut request = self
+                .http
+                .get(&url)
+                .headers(otel::trace_headers(&span))
+                .bearer_auth(access_token.expose())
+                .query(query);
+            if let Some(page_token) = &page_token {
+                request = request.query(&[("pageToken", page_token)]);
+            }
+            let response = http_client::send(route, request)
+                .instrument(span.clone())
+                .await?;
+            otel::record_status(&span, response.status());
+
+            let status = response.status();
+            if !status.is_success() {
+                let body = response.text().await?;
+                return Err(classify_error(status, body));
+            }
+
+            let (page, next_page_token) = response.json::<P>().await?.into_parts();
+            items.extend(page);
+            match next_page_token.filter(|token| !token.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => return Ok(items),
+            }
+        }
+
+        warn!(
+            "{} still had pages after {}, using the first ones",
+            path, MAX_PAGES
+        );
+        Ok(items)
+    }
+}
+
+/// Runs the call to Google `operation`, recording how it went and how long
+/// it took.
+async fn timed<T>(
+    operation: &'static str,
+    call: impl Future<Output = Result<T, GoogleApiError>>,
+) -> Result<T, GoogleApiError> {
+    let started = Instant::now();
+    let result = call.await;
+    let elapsed = started.elapsed();
+    metrics::record_google_api_call(
+        operation,
+        if result.is_ok() { "ok" } else { "error" },
+        elapsed,
+    );
+    observability::record_phase(Phase::Google, elapsed);
+    result
+}
+
+#[async_trait]
+impl GoogleApi for GoogleClient {
+    async fn create_meeting(
+        &self,
+        access_token: &SecretString,
+        options: &MeetingOptions,
+    ) -> Result<CreatedMeeting, GoogleApiError> {
+        timed(
+            "create_space",
+            self.request_meet_space(access_token, options),
+        )
+        .await
+    }
+
+    async fn get_space(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<Option<CreatedMeeting>, GoogleApiError> {
+        timed("get_space", self.request_space(access_token, space_name)).await
+    }
+
+    async fn add_space_member(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+        email: &str,
+    ) -> Result<(), GoogleApiError> {
+        timed(
+            "create_member",
+            self.request_space_member(access_token, space_name, email),
+        )
+        .await
+    }
+
+    async fn end_active_conference(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<bool, GoogleApiError> {
+        timed(
+            "end_active_conference",
+            self.request_end_conference(access_token, space_name),
+        )
+        .await
+    }
+
+    async fn list_conference_records(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<Vec<ConferenceRecord>, GoogleApiError> {
+        let filter = format!("space.name = \"{}\"", space_name);
+        let records = timed(
+            "list_conference_records",
+            self.list_all::<ConferenceRecordPage>(
+                |url| otel::client_span!("google.list_conference_records", "GET", url),
+                Route::google("/v2/conferenceRecords"),
+                access_token,
+                "conferenceRecords",
+                &[("filter", &filter)],
+            ),
+        )
+        .await?;
+
+        let mut records: Vec<ConferenceRecord> = records
+            .into_iter()
+            .map(|record| ConferenceRecord {
+                name: record.name,
+                start_time: record.start_time,
+                end_time: record.end_time,
+            })
+            .collect();
+        records.sort_by_key(|record| std::cmp::Reverse(record.start_time));
+        Ok(records)
+    }
+
+    async fn list_participants(
+        &self,
+        access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<Participant>, GoogleApiError> {
+        let participants = timed(
+            "list_participants",
+            self.list_all::<ParticipantPage>(
+                |url| otel::client_span!("google.list_participants", "GET", url),
+                Route::google("/v2/conferenceRecords/{record}/participants"),
+                access_token,
+                &format!("{}/participants", conference_record),
+                &[("pageSize", PARTICIPANTS_PAGE_SIZE)],
+            ),
+        )
+        .await?;
+
+        Ok(participants.into_iter().map(Participant::from).collect())
+    }
+
+    async fn list_recordings(
+        &self,
+        access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<ConferenceArtifact>, GoogleApiError> {
+        let recordings = timed(
+            "list_recordings",
+            self.list_all::<RecordingPage>(
+                |url| otel::client_span!("google.list_recordings", "GET", url),
+                Route::google("/v2/conferenceRecords/{record}/recordings"),
+                access_token,
+                &format!("{}/recordings", conference_record),
+                &[],
+            ),
+        )
+        .await?;
+
+        Ok(by_start_time(recordings))
+    }
+
+    async fn list_transcripts(
+        &self,
+        access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<ConferenceArtifact>, GoogleApiError> {
+        let transcripts = timed(
+            "list_transcripts",
+            self.list_all::<TranscriptPage>(
+                |url| otel::client_span!("google.list_transcripts", "GET", url),
+                Route::google("/v2/conferenceRecords/{record}/transcripts"),
+                access_token,
+                &format!("{}/transcripts", conference_record),
+                &[],
+            ),
+        )
+        .await?;
+
+        Ok(by_start_time(transcripts))
+    }
+}
+
+fn by_start_time(resources: Vec<ArtifactResource>) -> Vec<ConferenceArtifact> {
+    let mut artifacts: Vec<ConferenceArtifact> = resources
+        .into_iter()
+        .map(ConferenceArtifact::from)
+        .collect();
+    artifacts.sort_by_key(|artifact| artifact.start_time);
+    artifacts
+}
+
+/// Google reports exhausted quotas as 429, or as 403 with a rate-limit
+/// reason in the body.
+fn classify_error(status: StatusCode, body: String) -> GoogleApiError {
+    match status {
+        StatusCode::UNAUTHORIZED => GoogleApiError::Unauthorized,
+        StatusCode::TOO_MANY_REQUESTS => GoogleApiError::QuotaExceeded,
+        StatusCode::FORBIDDEN
+            if ["ACCESS_TOKEN_SCOPE_INSUFFICIENT", "insufficientPermissions"]
+                .iter()
+                .any(|reason| body.contains(reason)) =>
+        {
+            GoogleApiError::MissingScope
+        }
+        StatusCode::FORBIDDEN
+            if ["RESOURCE_EXHAUSTED", "rateLimitExceeded", "quotaExceeded"]
+                .iter()
+                .any(|reason| body.contains(reason)) =>
+        {
+            GoogleApiError::QuotaExceeded
+        }
+        _ => {
+            let mut end = body.len().min(MAX_ERROR_MESSAGE_LEN);
+            while !body.is_char_boundary(end) {
+                end -= 1;
+            }
+            GoogleApiError::Api {
+                status: status.as_u16(),
+                message: body[..end].to_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::google::Artifacts;
+    use serde_json::json;
+    use wiremock::matchers::{
+        bearer_token, body_json, method, path, query_param, query_param_is_missing,
+    };
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn test_conference_records_are_filtered_by_space() {
+        let meet = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/conferenceRecords"))
+            .and(query_param("filter", "space.name = \"spaces/abc\""))
+            .and(bearer_token("ya29.access"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "conferenceRecords": [
+                    {
+                        "name": "conferenceRecords/old",
+                        "startTime": "2024-03-04T10:00:00.123Z",
+                        "endTime": "2024-03-04T10:20:00Z",
+                        "space": "spaces/abc"
+                    },
+                    {
+                        "name": "conferenceRecords/now",
+                        "startTime": "2024-03-05T10:00:00Z",
+                        "space": "spaces/abc"
+                    }
+                ]
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+
+        let records = client
+            .list_conference_records(&"ya29.access".into(), "spaces/abc")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            records
+                .iter()
+                .map(|record| (record.name.as_str(), record.end_time.is_some()))
+                .collect::<Vec<_>>(),
+            [
+                ("conferenceRecords/now", false),
+                ("conferenceRecords/old", true)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_participants_are_read_from_every_page() {
+        let meet = MockServer::start().await;
+        let participants = "/v2/conferenceRecords/abc/participants";
+        Mock::given(method("GET"))
+            .and(path(participants))
+            .and(query_param_is_missing("pageToken"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "participants": [{
+                    "name": "conferenceRecords/abc/participants/1",
+                    "earliestStartTime": "2024-03-05T10:00:00Z",
+                    "latestEndTime": "2024-03-05T10:30:00Z",
+                    "signedinUser": { "user": "users/1", "displayName": "Alice" }
+                }],
+                "nextPageToken": "page-2"
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(participants))
+            .and(query_param("pageToken", "page-2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "participants": [
+                    {
+                        "name": "conferenceRecords/abc/participants/2",
+                        "earliestStartTime": "2024-03-05T10:05:00Z",
+                        "phoneUser": { "displayName": "+48 ••• ••• 123" }
+                    },
+                    {
+                        "name": "conferenceRecords/abc/participants/3",
+                        "earliestStartTime": "2024-03-05T10:06:00Z",
+                        "latestEndTime": "2024-03-05T10:07:00Z",
+                        "anonymousUser": {}
+                    }
+                ]
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+
+        let participants = client
+            .list_participants(&"ya29.access".into(), "conferenceRecords/abc")
+            .await
+            .unwrap();
+
+        let names: Vec<&str> = participants
+            .iter()
+            .map(|participant| participant.display_name.as_str())
+            .collect();
+        assert_eq!(names, ["Alice", "+48 ••• ••• 123", "Unknown participant"]);
+        assert_eq!(participants[1].latest_end_time, None);
+    }
+
+    #[tokio::test]
+    async fn test_missing_scope_is_recognized() {
+        let meet = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "error": {
+                    "code": 403,
+                    "status": "PERMISSION_DENIED",
+                    "details": [{ "reason": "ACCESS_TOKEN_SCOPE_INSUFFICIENT" }]
+                }
+            })))
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+
+        let error = client
+            .list_conference_records(&"ya29.access".into(), "spaces/abc")
+            .await
+            .unwrap_err();
+
+        assert!(matches!(error, GoogleApiError::MissingScope), "{:?}", error);
+    }
+
+    #[test]
+    fn test_classifies_error_responses() {
+        assert!(matches!(
+            classify_error(StatusCode::UNAUTHORIZED, String::new()),
+            GoogleApiError::Unauthorized
+        ));
+        assert!(matches!(
+            classify_error(StatusCode::TOO_MANY_REQUESTS, String::new()),
+            GoogleApiError::QuotaExceeded
+        ));
+        assert!(matches!(
+            classify_error(
+                StatusCode::FORBIDDEN,
+                r#"{"error": {"code": 403, "status": "RESOURCE_EXHAUSTED"}}"#.to_string()
+            ),
+            GoogleApiError::QuotaExceeded
+        ));
+
+        match classify_error(StatusCode::FORBIDDEN, "é".repeat(400)) {
+            GoogleApiError::Api { status, message } => {
+                assert_eq!(status, 403);
+                assert!(message.len() <= MAX_ERROR_MESSAGE_LEN);
+            }
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_artifacts_are_requested_in_the_space_config() {
+        let plain = serde_json::to_value(CreateSpaceRequest::new(&MeetingOptions::default()));
+        assert_eq!(
+            plain.unwrap(),
+            json!({ "config": { "accessType": "OPEN" } })
+        );
+
+        let options = MeetingOptions {
+            artifacts: Artifacts {
+                recording: true,
+                transcription: false,
+            },
+            ..MeetingOptions::default()
+        };
+        assert_eq!(
+            serde_json::to_value(CreateSpaceRequest::new(&options)).unwrap(),
+            json!({
+                "config": {
+                    "accessType": "OPEN",
+                    "artifactConfig": {
+                        "recordingConfig": { "autoRecordingGeneration": "ON" }
+                    }
+                }
+            })
+        );
+
+        let options = MeetingOptions {
+            artifacts: Artifacts {
+                recording: true,
+                transcription: true,
+            },
+            ..MeetingOptions::default()
+        };
+        assert_eq!(
+            serde_json::to_value(CreateSpaceRequest::new(&options)).unwrap()["config"]
+                ["artifactConfig"],
+            json!({
+                "recordingConfig": { "autoRecordingGeneration": "ON" },
+                "transcriptionConfig": { "autoTranscriptionGeneration": "ON" }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_refused_artifacts_are_recognized() {
+        let meet = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/spaces"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "error": {
+                    "code": 403,
+                    "message": "The caller does not have permission",
+                    "status": "PERMISSION_DENIED"
+                }
+            })))
+            .expect(2)
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+        let options = MeetingOptions {
+            artifacts: Artifacts {
+                recording: false,
+                transcription: true,
+            },
+            ..MeetingOptions::default()
+        };
+
+        let error = client
+            .create_meeting(&"ya29.access".into(), &options)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, GoogleApiError::ArtifactsUnavailable),
+            "{:?}",
+            error
+        );
+
+        // Without artifacts it's an ordinary refusal
+        let error = client
+            .create_meeting(&"ya29.access".into(), &MeetingOptions::default())
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(error, GoogleApiError::Api { status: 403, .. }),
+            "{:?}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recordings_are_linked_once_generated() {
+        let meet = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/conferenceRecords/abc/recordings"))
+            .and(bearer_token("ya29.access"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "recordings": [
+                    {
+                        "name": "conferenceRecords/abc/recordings/2",
+                        "state": "ENDED",
+                        "startTime": "2024-03-05T10:20:00Z",
+                        "driveDestination": { "file": "2" }
+                    },
+                    {
+                        "name": "conferenceRecords/abc/recordings/1",
+                        "state": "FILE_GENERATED",
+                        "startTime": "2024-03-05T10:00:00Z",
+                        "endTime": "2024-03-05T10:15:00Z",
+                        "driveDestination": {
+                            "file": "1",
+                            "exportUri": "https://drive.google.com/file/d/1/view"
+                        }
+                    }
+                ]
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+
+        let recordings = client
+            .list_recordings(&"ya29.access".into(), "conferenceRecords/abc")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            recordings
+                .iter()
+                .map(|recording| (recording.name.as_str(), recording.link.as_deref()))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    "conferenceRecords/abc/recordings/1",
+                    Some("https://drive.google.com/file/d/1/view")
+                ),
+                ("conferenceRecords/abc/recordings/2", None)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transcripts_are_read_from_every_page() {
+        let meet = MockServer::start().await;
+        let transcripts = "/v2/conferenceRecords/abc/transcripts";
+        Mock::given(method("GET"))
+            .and(path(transcripts))
+            .and(query_param_is_missing("pageToken"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "transcripts": [{
+                    "name": "conferenceRecords/abc/transcripts/1",
+                    "state": "FILE_GENERATED",
+                    "startTime": "2024-03-05T10:00:00Z",
+                    "docsDestination": {
+                        "document": "1",
+                        "exportUri": "https://docs.google.com/document/d/1/edit"
+                    }
+                }],
+                "nextPageToken": "page-2"
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(transcripts))
+            .and(query_param("pageToken", "page-2"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "transcripts": [{
+                    "name": "conferenceRecords/abc/transcripts/2",
+                    "state": "STARTED",
+                    "startTime": "2024-03-05T10:20:00Z"
+                }]
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/conferenceRecords/none/transcripts"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+
+        let transcripts = client
+            .list_transcripts(&"ya29.access".into(), "conferenceRecords/abc")
+            .await
+            .unwrap();
+        assert_eq!(
+            transcripts[0].link.as_deref(),
+            Some("https://docs.google.com/document/d/1/edit")
+        );
+        assert_eq!(transcripts[1].link, None);
+
+        // Google leaves the list out when there's nothing in it
+        let transcripts = client
+            .list_transcripts(&"ya29.access".into(), "conferenceRecords/none")
+            .await
+            .unwrap();
+        assert!(transcripts.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_artifact_lookups_recognize_a_missing_scope() {
+        let meet = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(403).set_body_json(json!({
+                "error": {
+                    "code": 403,
+                    "status": "PERMISSION_DENIED",
+                    "details": [{ "reason": "ACCESS_TOKEN_SCOPE_INSUFFICIENT" }]
+                }
+            })))
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+
+        let error = client
+            .list_recordings(&"ya29.access".into(), "conferenceRecords/abc")
+            .await
+            .unwrap_err();
+        assert!(matches!(error, GoogleApiError::MissingScope), "{:?}", error);
+    }
+
+    #[tokio::test]
+    async fn test_cohosts_are_added_as_members() {
+        let meet = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2beta/spaces/abc/members"))
+            .and(bearer_token("ya29.access"))
+            .and(body_json(
+                json!({ "email": "ann@example.com", "role": "COHOST" }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "spaces/abc/members/1",
+                "email": "ann@example.com",
+                "role": "COHOST"
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v2beta/spaces/abc/members"))
+            .and(body_json(
+                json!({ "email": "joe@example.org", "role": "COHOST" }),
+            ))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": {
+                    "code": 400,
+                    "message": "Invalid member email.",
+                    "status": "INVALID_ARGUMENT"
+                }
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+        let token = "ya29.access".into();
+
+        client
+            .add_space_member(&token, "spaces/abc", "ann@example.com")
+            .await
+            .unwrap();
+        let error = client
+            .add_space_member(&token, "spaces/abc", "joe@example.org")
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(&error, GoogleApiError::MemberRejected { email } if email == "joe@example.org"),
+            "{:?}",
+            error
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deleted_spaces_are_recognized() {
+        let meet = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/v2/spaces/abc"))
+            .and(bearer_token("ya29.access"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "name": "spaces/abc",
+                "meetingUri": "https://meet.google.com/abc-defg-hij",
+                "meetingCode": "abc-defg-hij"
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/v2/spaces/gone"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(json!({
+                "error": {
+                    "code": 404,
+                    "message": "Requested entity was not found.",
+                    "status": "NOT_FOUND"
+                }
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+        let token = "ya29.access".into();
+
+        let space = client.get_space(&token, "spaces/abc").await.unwrap();
+        assert_eq!(
+            space,
+            Some(CreatedMeeting {
+                name: "spaces/abc".to_string(),
+                meeting_uri: "https://meet.google.com/abc-defg-hij".to_string(),
+            })
+        );
+        assert_eq!(client.get_space(&token, "spaces/gone").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_ending_a_space_without_a_call_is_recognized() {
+        let meet = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/v2/spaces/abc:endActiveConference"))
+            .and(bearer_token("ya29.access"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({})))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/v2/spaces/idle:endActiveConference"))
+            .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+                "error": {
+                    "code": 400,
+                    "message": "No active conference.",
+                    "status": "FAILED_PRECONDITION"
+                }
+            })))
+            .expect(1)
+            .mount(&meet)
+            .await;
+        let client = GoogleClient::with_meet_base_url(Client::new(), &meet.uri());
+        let token = "ya29.access".into();
+
+        assert!(client
+            .end_active_conference(&token, "spaces/abc")
+            .await
+            .unwrap());
+        assert!(!client
+            .end_active_conference(&token, "spaces/idle")
+            .await
+            .unwrap());
+    }
+}
@@ -0,0 +1,294 @@
+//! Stand-in for Google in tests, and in load tests of a running instance
+//! (`GOOGLE_FAKE`).
+
+use axum::async_trait;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{
+    ConferenceArtifact, ConferenceRecord, CreatedMeeting, GoogleApi, GoogleApiError,
+    MeetingOptions, Participant,
+};
+use crate::secret::SecretString;
+
+pub const FAKE_MEETING_URI: &str = "https://meet.google.com/abc-defg-hij";
+
+type Respond = dyn Fn() -> Result<CreatedMeeting, GoogleApiError> + Send + Sync;
+
+/// A [`GoogleApi`] answering every call the way a test set it up to.
+pub struct FakeGoogleApi {
+    respond: Box<Respond>,
+    delay: Duration,
+    calls: AtomicUsize,
+    last_request: Mutex<Option<MeetingOptions>>,
+    /// Conferences by space name, each with its participants.
+    conferences: Vec<(String, ConferenceRecord, Vec<Participant>)>,
+    /// Recordings and transcripts by conference record name.
+    recordings: Vec<(String, ConferenceArtifact)>,
+    transcripts: Vec<(String, ConferenceArtifact)>,
+    conference_error: Option<fn() -> GoogleApiError>,
+    /// Emails refused as members, and the members added so far as
+    /// `(space, email)`.
+    rejected_members: Vec<String>,
+    members: Mutex<Vec<(String, String)>>,
+    /// The spaces whose call was ended, oldest first.
+    ended: Mutex<Vec<String>>,
+    /// The spaces created and not deleted since.
+    spaces: Mutex<Vec<CreatedMeeting>>,
+}
+
+impl FakeGoogleApi {
+    /// Creates every meeting at [`FAKE_MEETING_URI`].
+    pub fn succeeding() -> Self {
+        Self::responding(|| {
+            Ok(CreatedMeeting {
+                name: "spaces/abc".to_string(),
+                meeting_uri: FAKE_MEETING_URI.to_string(),
+            })
+        })
+    }
+
+    /// Fails every call with what `error` returns.
+    pub fn failing(error: impl Fn() -> GoogleApiError + Send + Sync + 'static) -> Self {
+        Self::responding(move || Err(error()))
+    }
+
+    pub fn responding(
+        respond: impl Fn() -> Result<CreatedMeeting, GoogleApiError> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            respond: Box::new(respond),
+            delay: Duration::ZERO,
+            calls: AtomicUsize::new(0),
+            last_request: Mutex::new(None),
+            conferences: Vec::new(),
+            recordings: Vec::new(),
+            transcripts: Vec::new(),
+            conference_error: None,
+            rejected_members: Vec::new(),
+            members: Mutex::new(Vec::new()),
+            ended: Mutex::new(Vec::new()),
+            spaces: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Adds a conference held in `space_name`, joined by `participants`.
+    pub fn with_conference(
+        mut self,
+        space_name: &str,
+        record: ConferenceRecord,
+        participants: Vec<Participant>,
+    ) -> Self {
+        self.conferences
+            .push((space_name.to_string(), record, participants));
+        self
+    }
+
+    /// Adds a recording of the conference `conference_record`.
+    pub fn with_recording(
+        mut self,
+        conference_record: &str,
+        recording: ConferenceArtifact,
+    ) -> Self {
+        self.recordings
+            .push((conference_record.to_string(), recording));
+        self
+    }
+
+    /// Adds a transcript of the conference `conference_record`.
+    pub fn with_transcript(
+        mut self,
+        conference_record: &str,
+        transcript: ConferenceArtifact,
+    ) -> Self {
+        self.transcripts
+            .push((conference_record.to_string(), transcript));
+        self
+    }
+
+    /// Fails every conference lookup with what `error` returns.
+    pub fn failing_conferences(mut self, error: fn() -> GoogleApiError) -> Self {
+        self.conference_error = Some(error);
+        self
+    }
+
+    /// Refuses `email` as a member of any space.
+    pub fn rejecting_member(mut self, email: &str) -> Self {
+        self.rejected_members.push(email.to_string());
+        self
+    }
+
+    /// The members added so far as `(space, email)`, oldest first.
+    pub fn members(&self) -> Vec<(String, String)> {
+        self.members.lock().unwrap().clone()
+    }
+
+    /// The spaces whose call was ended so far, oldest first.
+    pub fn ended(&self) -> Vec<String> {
+        self.ended.lock().unwrap().clone()
+    }
+
+    /// Deletes the space `space_name`, as its owner can at Google.
+    pub fn delete_space(&self, space_name: &str) {
+        self.spaces
+            .lock()
+            .unwrap()
+            .retain(|space| space.name != space_name);
+    }
+
+    /// Makes every call take `delay` before answering, like a slow Google.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Calls made so far.
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+
+    /// The options of the latest call.
+    pub fn last_request(&self) -> Option<MeetingOptions> {
+        self.last_request.lock().unwrap().clone()
+    }
+
+    fn artifacts(
+        &self,
+        artifacts: &[(String, ConferenceArtifact)],
+        conference_record: &str,
+    ) -> Result<Vec<ConferenceArtifact>, GoogleApiError> {
+        if let Some(error) = self.conference_error {
+            return Err(error());
+        }
+        Ok(artifacts
+            .iter()
+            .filter(|(record, _)| record == conference_record)
+            .map(|(_, artifact)| artifact.clone())
+            .collect())
+    }
+}
+
+#[async_trait]
+impl GoogleApi for FakeGoogleApi {
+    async fn create_meeting(
+        &self,
+        _access_token: &SecretString,
+        options: &MeetingOptions,
+    ) -> Result<CreatedMeeting, GoogleApiError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        *self.last_request.lock().unwrap() = Some(options.clone());
+        tokio::time::sleep(self.delay).await;
+        let created = (self.respond)()?;
+        self.spaces.lock().unwrap().push(created.clone());
+        Ok(created)
+    }
+
+    async fn get_space(
+        &self,
+        _access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<Option<CreatedMeeting>, GoogleApiError> {
+        Ok(self
+            .spaces
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|space| space.name == space_name)
+            .cloned())
+    }
+
+    async fn add_space_member(
+        &self,
+        _access_token: &SecretString,
+        space_name: &str,
+        email: &str,
+    ) -> Result<(), GoogleApiError> {
+        if self
+            .rejected_members
+            .iter()
+            .any(|rejected| rejected == email)
+        {
+            return Err(GoogleApiError::MemberRejected {
+                email: email.to_string(),
+            });
+        }
+        self.members
+            .lock()
+            .unwrap()
+            .push((space_name.to_string(), email.to_string()));
+        Ok(())
+    }
+
+    /// A call is going on in a space with a conference that has no end
+    /// time, until it is ended.
+    async fn end_active_conference(
+        &self,
+        _access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<bool, GoogleApiError> {
+        if let Some(error) = self.conference_error {
+            return Err(error());
+        }
+        let mut ended = self.ended.lock().unwrap();
+        let live = self
+            .conferences
+            .iter()
+            .any(|(space, record, _)| space == space_name && record.end_time.is_none());
+        if !live || ended.iter().any(|space| space == space_name) {
+            return Ok(false);
+        }
+        ended.push(space_name.to_string());
+        Ok(true)
+    }
+
+    async fn list_conference_records(
+        &self,
+        _access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<Vec<ConferenceRecord>, GoogleApiError> {
+        if let Some(error) = self.conference_error {
+            return Err(error());
+        }
+        let mut records: Vec<ConferenceRecord> = self
+            .conferences
+            .iter()
+            .filter(|(space, _, _)| space == space_name)
+            .map(|(_, record, _)| record.clone())
+            .collect();
+        records.sort_by_key(|record| std::cmp::Reverse(record.start_time));
+        Ok(records)
+    }
+
+    async fn list_participants(
+        &self,
+        _access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<Participant>, GoogleApiError> {
+        if let Some(error) = self.conference_error {
+            return Err(error());
+        }
+        Ok(self
+            .conferences
+            .iter()
+            .find(|(_, record, _)| record.name == conference_record)
+            .map(|(_, _, participants)| participants.clone())
+            .unwrap_or_default())
+    }
+
+    async fn list_recordings(
+        &self,
+        _access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<ConferenceArtifact>, GoogleApiError> {
+        self.artifacts(&self.recordings, conference_record)
+    }
+
+    async fn list_transcripts(
+        &self,
+        _access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<ConferenceArtifact>, GoogleApiError> {
+        self.artifacts(&self.transcripts, conference_record)
+    }
+}
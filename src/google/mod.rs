@@ -0,0 +1,198 @@
+//! Google APIs the bot calls, behind [`GoogleApi`] so the command handlers
+//! can be tested without the network.
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use thiserror::Error;
+
+use crate::secret::SecretString;
+
+pub mod calendar;
+mod client;
+pub mod fake;
+
+pub use client::GoogleClient;
+
+/// How a new meeting is set up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MeetingOptions {
+    /// Let anyone with the link join without knocking; otherwise only
+    /// people in the creator's organization can.
+    pub open_access: bool,
+    pub artifacts: Artifacts,
+}
+
+impl Default for MeetingOptions {
+    fn default() -> Self {
+        Self {
+            open_access: true,
+            artifacts: Artifacts::default(),
+        }
+    }
+}
+
+/// What Meet generates by itself once a meeting starts. Only Google
+/// Workspace editions that include Meet recording allow either.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Artifacts {
+    pub recording: bool,
+    pub transcription: bool,
+}
+
+impl Artifacts {
+    pub fn any(self) -> bool {
+        self.recording || self.transcription
+    }
+
+    /// Everything either of `self` and `other` generates.
+    pub fn union(self, other: Artifacts) -> Artifacts {
+        Artifacts {
+            recording: self.recording || other.recording,
+            transcription: self.transcription || other.transcription,
+        }
+    }
+}
+
+/// A meeting space Google created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedMeeting {
+    /// Resource name, `spaces/{id}`.
+    pub name: String,
+    pub meeting_uri: String,
+}
+
+/// One call held in a Meet space. A space is reused each time its link is
+/// joined after everyone left, so it can have many.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConferenceRecord {
+    /// Resource name, `conferenceRecords/{id}`.
+    pub name: String,
+    pub start_time: DateTime<Utc>,
+    /// Unset while the call is still going on.
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// Someone who joined a conference, however many times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Participant {
+    pub display_name: String,
+    pub earliest_start_time: DateTime<Utc>,
+    /// Unset while they are still in the call.
+    pub latest_end_time: Option<DateTime<Utc>>,
+}
+
+/// A recording or transcript Meet made of a conference.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConferenceArtifact {
+    /// Resource name, `conferenceRecords/{id}/recordings/{id}` or
+    /// `conferenceRecords/{id}/transcripts/{id}`.
+    pub name: String,
+    pub start_time: DateTime<Utc>,
+    /// Where the Drive file or Docs document opens; unset until Google has
+    /// generated it, which takes a while after the call.
+    pub link: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum GoogleApiError {
+    /// The access token was rejected; the user has to connect again.
+    #[error("Google rejected the access token")]
+    Unauthorized,
+    #[error("Google API quota exceeded")]
+    QuotaExceeded,
+    /// Recording or transcription was asked for, and the account's
+    /// Workspace edition or its admin doesn't allow it.
+    #[error("Google doesn't allow recording or transcription for this account")]
+    ArtifactsUnavailable,
+    /// Google wouldn't make the email a member of the space, as when it
+    /// isn't a Google account in the space owner's organization.
+    #[error("Google refused {email} as a member of the space")]
+    MemberRejected { email: String },
+    /// The user didn't grant the scope the call needs.
+    #[error("Google access token lacks a required scope")]
+    MissingScope,
+    #[error("Google API returned {status}: {message}")]
+    Api { status: u16, message: String },
+    #[error("Request to Google failed: {0}")]
+    Network(#[from] reqwest::Error),
+}
+
+impl GoogleApiError {
+    /// Whether the same call may well succeed if made again: the request
+    /// never got an answer, or Google failed on its side.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            GoogleApiError::Network(_) => true,
+            GoogleApiError::Api { status, .. } => *status >= 500,
+            GoogleApiError::Unauthorized
+            | GoogleApiError::QuotaExceeded
+            | GoogleApiError::ArtifactsUnavailable
+            | GoogleApiError::MemberRejected { .. }
+            | GoogleApiError::MissingScope => false,
+        }
+    }
+}
+
+#[async_trait]
+pub trait GoogleApi: Send + Sync {
+    /// Creates a Meet space on behalf of the user `access_token` belongs to.
+    async fn create_meeting(
+        &self,
+        access_token: &SecretString,
+        options: &MeetingOptions,
+    ) -> Result<CreatedMeeting, GoogleApiError>;
+
+    /// The Meet space `space_name`, or `None` when it no longer exists, as
+    /// when its owner deleted it at Google.
+    async fn get_space(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<Option<CreatedMeeting>, GoogleApiError>;
+
+    /// Makes the Google account `email` a co-host of the Meet space
+    /// `space_name`, so they can admit people and manage the call.
+    async fn add_space_member(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+        email: &str,
+    ) -> Result<(), GoogleApiError>;
+
+    /// Ends the call going on in the Meet space `space_name`, removing
+    /// everyone from it. Returns `false` when no call was going on.
+    async fn end_active_conference(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<bool, GoogleApiError>;
+
+    /// The calls held in the Meet space `space_name`, newest first. Needs
+    /// the `meetings.space.readonly` scope.
+    async fn list_conference_records(
+        &self,
+        access_token: &SecretString,
+        space_name: &str,
+    ) -> Result<Vec<ConferenceRecord>, GoogleApiError>;
+
+    /// Everyone who joined the conference `conference_record`.
+    async fn list_participants(
+        &self,
+        access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<Participant>, GoogleApiError>;
+
+    /// The recordings of the conference `conference_record`, oldest first.
+    async fn list_recordings(
+        &self,
+        access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<ConferenceArtifact>, GoogleApiError>;
+
+    /// The transcripts of the conference `conference_record`, oldest first.
+    async fn list_transcripts(
+        &self,
+        access_token: &SecretString,
+        conference_record: &str,
+    ) -> Result<Vec<ConferenceArtifact>, GoogleApiError>;
+}
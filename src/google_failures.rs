@@ -0,0 +1,319 @@
+//! Recent hard Google failures of each user. When Google keeps refusing an
+//! account the same way, as one suspended or with Meet turned off, `/meet`
+//! tells the user what is wrong for a while instead of asking Google again
+//! each time. A meeting created or Google connected again clears it.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use crate::config::RateLimitConfig;
+use crate::google::GoogleApiError;
+
+/// How long a user's failures are remembered at least, as with rate limits.
+const CLEANUP_THRESHOLD: chrono::Duration = chrono::Duration::hours(1);
+
+/// A kind of failure that asking Google again won't change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureClass {
+    /// The user didn't grant the scope creating meetings needs.
+    MissingScope,
+    /// Google refused the request with this 4xx status.
+    Refused { status: u16 },
+}
+
+impl FailureClass {
+    /// The class of `error`; `None` for failures that may well go away by
+    /// themselves, or that depend on what was asked for rather than on the
+    /// account.
+    pub fn of(error: &GoogleApiError) -> Option<Self> {
+        match error {
+            GoogleApiError::MissingScope => Some(FailureClass::MissingScope),
+            // 408 and 409 are about the request, not the account
+            GoogleApiError::Api { status, .. }
+                if (400..500).contains(status) && !matches!(status, 408 | 409) =>
+            {
+                Some(FailureClass::Refused { status: *status })
+            }
+            // Unauthorized already asks the user to connect again, quotas
+            // are the bot's, and the rest depend on the meeting
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct FailureHistory {
+    class: FailureClass,
+    /// Failures of `class` in a row.
+    count: u32,
+    last_seen: DateTime<Utc>,
+    /// What Google said the last time, if it said it in words.
+    reason: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct GoogleFailures {
+    threshold: u32,
+    cooldown: chrono::Duration,
+    users: Arc<RwLock<HashMap<String, FailureHistory>>>,
+}
+
+impl GoogleFailures {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            threshold: config.google_failure_threshold,
+            cooldown: chrono::Duration::from_std(config.google_failure_cooldown)
+                .unwrap_or(chrono::Duration::MAX),
+            users: Arc::new(RwLock::new(HashMap::new())),
+        }
+    }
+
+    /// What to tell the Slack user `user_id` instead of asking Google, while
+    /// their last failures are too many alike and too recent at `now`.
+    pub async fn short_circuit(&self, user_id: &str, now: DateTime<Utc>) -> Option<String> {
+        if self.threshold == 0 {
+            return None;
+        }
+        let users = self.users.read().await;
+        let history = users.get(user_id)?;
+        let retry_at = history.last_seen + self.cooldown;
+        if history.count < self.threshold || now >= retry_at {
+            return None;
+        }
+        Some(message(history, retry_at - now))
+    }
+
+    /// Counts `error` against `user_id`, unless asking again may well go
+    /// differently.
+    pub async fn record_failure(&self, user_id: &str, error: &GoogleApiError, now: DateTime<Utc>) {
+        let Some(class) = FailureClass::of(error) else {
+            return;
+        };
+        let mut users = self.users.write().await;
+        let history = users
+            .entry(user_id.to_string())
+            .and_modify(|history| {
+                if history.class == class {
+                    history.count += 1;
+                } else {
+                    history.class = class;
+                    history.count = 1;
+                }
+            })
+            .or_insert(FailureHistory {
+                class,
+                count: 1,
+                last_seen: now,
+                reason: None,
+            });
+        history.last_seen = now;
+        history.reason = reason(error);
+
+        if self.threshold > 0 && history.count == self.threshold {
+            warn!(
+                "Google failed {} times in a row for {} ({:?}), not asking again for {} minutes",
+                history.count,
+                user_id,
+                class,
+                self.cooldown.num_minutes()
+            );
+        }
+    }
+
+    /// Forgets the failures of `user_id`, whose account works, or was just
+    /// connected again.
+    pub async fn reset(&self, user_id: &str) {
+        self.users.write().await.remove(user_id);
+    }
+
+    pub async fn cleanup_old_entries(&self, now: DateTime<Utc>) {
+        let threshold = self.cooldown.max(CLEANUP_THRESHOLD);
+        self.users
+            .write()
+            .await
+            .retain(|_, history| now - history.last_seen < threshold);
+    }
+}
+
+/// The message of a Google API error body, as in
+/// `{"error": {"message": "..."}}`.
+fn reason(error: &GoogleApiError) -> Option<String> {
+    let GoogleApiError::Api { message, .. } = error else {
+        return None;
+    };
+    let body: serde_json::Value = serde_json::from_str(message).ok()?;
+    let message = body["error"]["message"].as_str()?.trim();
+    (!message.is_empty()).then(|| message.to_string())
+}
+
+fn message(history: &FailureHistory, wait: chrono::Duration) -> String {
+    let minutes = (wait.num_seconds() + 59) / 60;
+    let retry = match minutes {
+        1 => "in a minute".to_string(),
+        minutes => format!("in {} minutes", minutes),
+    };
+    let said = match &history.reason {
+        Some(reason) => format!(" Google says: “{}”", reason),
+        None => String::new(),
+    };
+    match history.class {
+        FailureClass::MissingScope => format!(
+            "🚫 You didn't give the bot permission to create meetings when you connected Google. Run this command with `logout`, then connect again and allow it; otherwise I'll ask Google again {}.",
+            retry
+        ),
+        FailureClass::Refused { status: 403 } => format!(
+            "🚫 Google keeps refusing to create meetings for your account.{} Your account may be suspended, or Meet turned off for it; your Google Workspace admin can tell. I'll ask Google again {}, or as soon as you run this command with `logout` and connect again.",
+            said, retry
+        ),
+        FailureClass::Refused { .. } => format!(
+            "🚫 Google keeps rejecting meetings for your account.{} I'll ask Google again {}, or as soon as you run this command with `logout` and connect again.",
+            said, retry
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::time::Duration;
+
+    fn failures() -> GoogleFailures {
+        GoogleFailures::new(&RateLimitConfig {
+            google_failure_threshold: 3,
+            google_failure_cooldown: Duration::from_secs(10 * 60),
+            ..RateLimitConfig::default()
+        })
+    }
+
+    fn denied() -> GoogleApiError {
+        GoogleApiError::Api {
+            status: 403,
+            message: r#"{"error": {"code": 403, "message": "Meet is turned off for this user.", "status": "PERMISSION_DENIED"}}"#.to_string(),
+        }
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 5, 9, 0, 0).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_short_circuits_after_the_threshold() {
+        let failures = failures();
+        let now = start();
+        for _ in 0..2 {
+            failures.record_failure("U012AB3CD", &denied(), now).await;
+            assert_eq!(failures.short_circuit("U012AB3CD", now).await, None);
+        }
+
+        failures.record_failure("U012AB3CD", &denied(), now).await;
+        let message = failures.short_circuit("U012AB3CD", now).await.unwrap();
+        assert_eq!(
+            message,
+            "🚫 Google keeps refusing to create meetings for your account. Google says: “Meet is turned off for this user.” Your account may be suspended, or Meet turned off for it; your Google Workspace admin can tell. I'll ask Google again in 10 minutes, or as soon as you run this command with `logout` and connect again."
+        );
+        // Only for that user
+        assert_eq!(failures.short_circuit("U0OTHER01", now).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_only_identical_hard_failures_count() {
+        let failures = failures();
+        let now = start();
+        failures.record_failure("U012AB3CD", &denied(), now).await;
+        failures
+            .record_failure("U012AB3CD", &GoogleApiError::MissingScope, now)
+            .await;
+        failures
+            .record_failure("U012AB3CD", &GoogleApiError::MissingScope, now)
+            .await;
+        assert_eq!(failures.short_circuit("U012AB3CD", now).await, None);
+
+        // Failures that may go away by themselves leave the count alone
+        for error in [
+            GoogleApiError::Api {
+                status: 503,
+                message: "backend unavailable".to_string(),
+            },
+            GoogleApiError::QuotaExceeded,
+            GoogleApiError::Unauthorized,
+        ] {
+            failures.record_failure("U012AB3CD", &error, now).await;
+        }
+        assert_eq!(failures.short_circuit("U012AB3CD", now).await, None);
+
+        failures
+            .record_failure("U012AB3CD", &GoogleApiError::MissingScope, now)
+            .await;
+        let message = failures.short_circuit("U012AB3CD", now).await.unwrap();
+        assert!(message.contains("`logout`"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn test_cooldown_expires() {
+        let failures = failures();
+        let now = start();
+        for _ in 0..3 {
+            failures.record_failure("U012AB3CD", &denied(), now).await;
+        }
+
+        let later = now + chrono::Duration::seconds(9 * 60 + 30);
+        let message = failures.short_circuit("U012AB3CD", later).await.unwrap();
+        assert!(message.contains("in a minute"), "{}", message);
+
+        let later = now + chrono::Duration::minutes(10);
+        assert_eq!(failures.short_circuit("U012AB3CD", later).await, None);
+        // Failing once more after the cooldown starts another
+        failures.record_failure("U012AB3CD", &denied(), later).await;
+        assert!(failures.short_circuit("U012AB3CD", later).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_reset_on_success() {
+        let failures = failures();
+        let now = start();
+        for _ in 0..3 {
+            failures.record_failure("U012AB3CD", &denied(), now).await;
+        }
+        assert!(failures.short_circuit("U012AB3CD", now).await.is_some());
+
+        failures.reset("U012AB3CD").await;
+        assert_eq!(failures.short_circuit("U012AB3CD", now).await, None);
+        failures.record_failure("U012AB3CD", &denied(), now).await;
+        assert_eq!(failures.short_circuit("U012AB3CD", now).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_forgets_old_failures() {
+        let failures = failures();
+        let now = start();
+        failures.record_failure("U012AB3CD", &denied(), now).await;
+        failures
+            .record_failure("U0OTHER01", &denied(), now + chrono::Duration::minutes(30))
+            .await;
+
+        failures
+            .cleanup_old_entries(now + chrono::Duration::minutes(61))
+            .await;
+
+        let users = failures.users.read().await;
+        assert!(!users.contains_key("U012AB3CD"));
+        assert!(users.contains_key("U0OTHER01"));
+    }
+
+    #[tokio::test]
+    async fn test_a_zero_threshold_turns_it_off() {
+        let failures = GoogleFailures::new(&RateLimitConfig {
+            google_failure_threshold: 0,
+            ..RateLimitConfig::default()
+        });
+        let now = start();
+        for _ in 0..5 {
+            failures.record_failure("U012AB3CD", &denied(), now).await;
+        }
+        assert_eq!(failures.short_circuit("U012AB3CD", now).await, None);
+    }
+}
@@ -0,0 +1,650 @@
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::warn;
+
+use super::auth::render_page;
+use crate::audit::{self, AuditEvent, AuditEventType};
+use crate::background::{JobRegistry, JobStatus};
+use crate::database::{models::AuditRecord, Database};
+use crate::error::AppError;
+use crate::secret::SecretString;
+use crate::telemetry::logging::{LogFilter, LogFilterError};
+use crate::time::SharedClock;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+/// How long a changed log filter stays in effect unless the request says
+/// otherwise.
+const DEFAULT_LOG_FILTER_MINUTES: u64 = 30;
+
+/// Days of meetings the dashboard shows, today included.
+const DASHBOARD_DAYS: u64 = 14;
+
+/// Authentication failures the dashboard lists.
+const DASHBOARD_AUTH_FAILURES: i64 = 20;
+
+const AUTH_FAILURE_EVENTS: [AuditEventType; 2] = [
+    AuditEventType::TokenRefreshFailed,
+    AuditEventType::SignatureRejected,
+];
+
+/// Width in pixels of the busiest day's bar, as wide as the chart column of
+/// `admin_dashboard.html`.
+const BAR_WIDTH: i64 = 200;
+
+/// Sent with a rejected dashboard request so browsers ask for credentials.
+const BASIC_CHALLENGE: &str = r#"Basic realm="meet-slack-bot admin", charset="UTF-8""#;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Clone)]
+struct AdminState {
+    db: Database,
+    token: SecretString,
+    log_filter: LogFilter,
+    jobs: JobRegistry,
+    clock: SharedClock,
+}
+
+#[derive(Debug, Deserialize)]
+struct AuditQuery {
+    /// Only entries with a lower id; pass the previous page's `next_before`.
+    before: Option<i64>,
+    limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogLevelRequest {
+    /// `RUST_LOG`-style directives, e.g. `meet_slack_bot::google=trace,info`.
+    filter: String,
+    /// Minutes until the configured filter is restored; `0` keeps the new
+    /// filter until the next change or restart.
+    revert_after_minutes: Option<u64>,
+}
+
+/// Router serving the admin endpoints, all of which require
+/// `Authorization: Bearer <token>` or, for browsers, basic auth with the
+/// token as the password.
+pub fn router(
+    db: Database,
+    token: SecretString,
+    log_filter: LogFilter,
+    jobs: JobRegistry,
+    clock: SharedClock,
+) -> Router {
+    Router::new()
+        .route("/admin", get(dashboard))
+        .route("/admin/audit", get(audit_log))
+        .route("/admin/log-level", get(log_level).put(set_log_level))
+        .route("/admin/jobs", get(background_jobs))
+        .with_state(AdminState {
+            db,
+            token,
+            log_filter,
+            jobs,
+            clock,
+        })
+}
+
+impl AdminState {
+    fn authorize(&self, headers: &HeaderMap, endpoint: &str) -> Result<(), AppError> {
+        if self.token.matches_bearer(headers) || self.token.matches_basic_password(headers) {
+            Ok(())
+        } else {
+            warn!("Rejected {} request without valid credentials", endpoint);
+            Err(AppError::Unauthorized)
+        }
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin_dashboard.html")]
+struct DashboardPage {
+    generated_at: String,
+    users: i64,
+    connected_users: i64,
+    days: Vec<DayRow>,
+    auth_failures: Vec<AuthFailureRow>,
+    jobs: Vec<JobRow>,
+}
+
+struct DayRow {
+    day: NaiveDate,
+    meetings: i64,
+    bar_width: i64,
+}
+
+struct AuthFailureRow {
+    at: String,
+    event_type: String,
+    /// Who or where the failure came from: the Slack user, or the address
+    /// of a request with a bad signature.
+    source: String,
+    reason: String,
+}
+
+struct JobRow {
+    name: &'static str,
+    /// `ok`, `failing`, `stopped`, or `waiting` before the first run.
+    health: &'static str,
+    runs: u64,
+    failures: u64,
+    last_run: String,
+    last_error: String,
+}
+
+/// An HTML overview for operators: totals, meetings per day, recent
+/// authentication failures and background job health. Shows nothing from
+/// the audit log beyond identifiers and reason codes, and no tokens.
+async fn dashboard(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if state.authorize(&headers, "/admin").is_err() {
+        // Not the usual JSON error, which would drop the challenge header
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, BASIC_CHALLENGE)],
+            AppError::Unauthorized.to_string(),
+        )
+            .into_response());
+    }
+
+    let now = state.clock.now();
+    let today = now.date_naive();
+    let first_day = today - chrono::Days::new(DASHBOARD_DAYS - 1);
+    let event_types = AUTH_FAILURE_EVENTS.map(|event_type| event_type.as_str());
+    let page = DashboardPage {
+        generated_at: now.format(TIME_FORMAT).to_string(),
+        users: state.db.count_users().await?,
+        connected_users: state.db.count_connected_users().await?,
+        days: daily_meetings(
+            state
+                .db
+                .meetings_per_day(first_day.and_time(NaiveTime::MIN))
+                .await?,
+            first_day,
+            today,
+        ),
+        auth_failures: state
+            .db
+            .audit_log_of_types(&event_types, DASHBOARD_AUTH_FAILURES)
+            .await?
+            .iter()
+            .map(auth_failure_row)
+            .collect(),
+        jobs: state
+            .jobs
+            .snapshot()
+            .into_iter()
+            .map(|(name, status)| job_row(name, &status))
+            .collect(),
+    };
+
+    Ok(Html(render_page(&page)).into_response())
+}
+
+/// A row for every day from `first_day` to `today`, including those without
+/// meetings, with bars scaled to the busiest day.
+fn daily_meetings(
+    counts: Vec<(NaiveDate, i64)>,
+    first_day: NaiveDate,
+    today: NaiveDate,
+) -> Vec<DayRow> {
+    let counts: HashMap<NaiveDate, i64> = counts.into_iter().collect();
+    let busiest = counts.values().copied().max().unwrap_or(0).max(1);
+    first_day
+        .iter_days()
+        .take_while(|day| *day <= today)
+        .map(|day| {
+            let meetings = counts.get(&day).copied().unwrap_or(0);
+            DayRow {
+                day,
+                meetings,
+                bar_width: meetings * BAR_WIDTH / busiest,
+            }
+        })
+        .collect()
+}
+
+fn auth_failure_row(record: &AuditRecord) -> AuthFailureRow {
+    let detail = |key: &str| record.detail[key].as_str().map(str::to_string);
+    AuthFailureRow {
+        at: record.created_at.format(TIME_FORMAT).to_string(),
+        event_type: record.event_type.clone(),
+        source: record
+            .actor_slack_id
+            .clone()
+            .or_else(|| detail("ip"))
+            .unwrap_or_default(),
+        reason: detail("reason").unwrap_or_default(),
+    }
+}
+
+fn job_row(name: &'static str, status: &JobStatus) -> JobRow {
+    let health = if !status.active {
+        "stopped"
+    } else if status.failing {
+        "failing"
+    } else if status.runs == 0 {
+        "waiting"
+    } else {
+        "ok"
+    };
+    let format = |at: Option<DateTime<Utc>>| {
+        at.map(|at| at.format(TIME_FORMAT).to_string())
+            .unwrap_or_default()
+    };
+    JobRow {
+        name,
+        health,
+        runs: status.runs,
+        failures: status.failures,
+        last_run: format(status.last_run),
+        last_error: status.last_error.clone().unwrap_or_default(),
+    }
+}
+
+/// Every background job with its last run and last error.
+async fn background_jobs(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    state.authorize(&headers, "/admin/jobs")?;
+
+    Ok(Json(json!({ "jobs": state.jobs.snapshot() })))
+}
+
+async fn log_level(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+) -> Result<Json<Value>, AppError> {
+    state.authorize(&headers, "/admin/log-level")?;
+
+    Ok(Json(json!({ "filter": state.log_filter.current() })))
+}
+
+async fn set_log_level(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Json(request): Json<LogLevelRequest>,
+) -> Result<Json<Value>, AppError> {
+    state.authorize(&headers, "/admin/log-level")?;
+
+    let minutes = request
+        .revert_after_minutes
+        .unwrap_or(DEFAULT_LOG_FILTER_MINUTES);
+    let revert_after = (minutes > 0).then(|| Duration::from_secs(minutes * 60));
+    state
+        .log_filter
+        .set(&request.filter, revert_after)
+        .map_err(|e| match e {
+            LogFilterError::Invalid(_) => AppError::BadRequest(e.to_string()),
+            e => AppError::Internal(e.into()),
+        })?;
+    audit::record(
+        &state.db,
+        AuditEvent::log_filter_changed(&request.filter, (minutes > 0).then_some(minutes)),
+    )
+    .await;
+
+    Ok(Json(json!({
+        "filter": state.log_filter.current(),
+        "revert_after_minutes": (minutes > 0).then_some(minutes),
+    })))
+}
+
+async fn audit_log(
+    State(state): State<AdminState>,
+    headers: HeaderMap,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Value>, AppError> {
+    state.authorize(&headers, "/admin/audit")?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let entries = state.db.audit_log_page(query.before, limit).await?;
+    audit::record(&state.db, AuditEvent::audit_log_viewed(query.before, limit)).await;
+
+    let next_before = (entries.len() as i64 == limit)
+        .then(|| entries.last().map(|entry| entry.id))
+        .flatten();
+    Ok(Json(
+        json!({ "entries": entries, "next_before": next_before }),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::oauth::OAuthError;
+    use crate::database::models::OAuthToken;
+    use crate::database::test_db;
+    use crate::time::{SystemClock, TestClock};
+    use axum::{body::Body, http::Request};
+    use base64::{engine::general_purpose, Engine as _};
+    use chrono::TimeZone;
+    use tower::ServiceExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Sends a GET with the given `Authorization` header, returning the
+    /// response with its body.
+    async fn send(
+        db: &Database,
+        clock: SharedClock,
+        uri: &str,
+        authorization: Option<String>,
+    ) -> (Response, String) {
+        let mut request = Request::builder().uri(uri);
+        if let Some(authorization) = authorization {
+            request = request.header("authorization", authorization);
+        }
+        let (_layer, log_filter) = LogFilter::new("info").unwrap();
+        let response = router(
+            db.clone(),
+            "admin-token".into(),
+            log_filter,
+            JobRegistry::new(),
+            clock,
+        )
+        .oneshot(request.body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+        let (parts, body) = response.into_parts();
+        let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+        (
+            Response::from_parts(parts, Body::empty()),
+            String::from_utf8(body.to_vec()).unwrap(),
+        )
+    }
+
+    async fn get(db: &Database, uri: &str, token: Option<&str>) -> (StatusCode, Value) {
+        let authorization = token.map(|token| format!("Bearer {}", token));
+        let (response, body) = send(db, SystemClock::shared(), uri, authorization).await;
+        (
+            response.status(),
+            serde_json::from_str(&body).unwrap_or(Value::Null),
+        )
+    }
+
+    fn basic(credentials: &str) -> Option<String> {
+        Some(format!(
+            "Basic {}",
+            general_purpose::STANDARD.encode(credentials)
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_requires_the_admin_token() {
+        let (db, _pool) = test_db().await;
+
+        assert_eq!(
+            get(&db, "/admin/audit", None).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get(&db, "/admin/audit", Some("wrong")).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            get(&db, "/admin/jobs", None).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+        assert!(db.audit_log_page(None, 10).await.unwrap().is_empty());
+
+        let (status, body) = get(&db, "/admin/jobs", Some("admin-token")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["jobs"], json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_pages_through_the_log_and_audits_the_view() {
+        let (db, _pool) = test_db().await;
+        let user = db.create_user("U012AB3CD", "T012AB3C4").await.unwrap();
+        for _ in 0..3 {
+            audit::record(&db, AuditEvent::google_disconnected(&user, "logout")).await;
+        }
+
+        let (status, page) = get(&db, "/admin/audit?limit=2", Some("admin-token")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(page["entries"].as_array().unwrap().len(), 2);
+        assert_eq!(page["entries"][0]["event_type"], "google_disconnected");
+        assert_eq!(page["entries"][0]["actor_slack_id"], "U012AB3CD");
+
+        let next = page["next_before"].as_i64().unwrap();
+        let (_, rest) = get(
+            &db,
+            &format!("/admin/audit?limit=2&before={}", next),
+            Some("admin-token"),
+        )
+        .await;
+        assert_eq!(rest["entries"].as_array().unwrap().len(), 1);
+        assert!(rest["next_before"].is_null());
+
+        let (_, latest) = get(&db, "/admin/audit?limit=1", Some("admin-token")).await;
+        assert_eq!(latest["entries"][0]["event_type"], "audit_log_viewed");
+        assert_eq!(latest["entries"][0]["detail"]["limit"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_log_level_can_be_changed_with_the_token() {
+        let (db, _pool) = test_db().await;
+        // The filter only takes changes while its layer is alive
+        let (layer, log_filter) = LogFilter::new("info").unwrap();
+        let _subscriber = tracing_subscriber::registry().with(layer);
+        let app = router(
+            db.clone(),
+            "admin-token".into(),
+            log_filter.clone(),
+            JobRegistry::new(),
+            SystemClock::shared(),
+        );
+
+        let put = |token: &'static str, body: &'static str| {
+            app.clone().oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/admin/log-level")
+                    .header("authorization", format!("Bearer {}", token))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+
+        let response = put("wrong", r#"{"filter": "debug"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = put("admin-token", r#"{"filter": "meet_slack_bot=loud"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(log_filter.current(), "info");
+
+        let response = put(
+            "admin-token",
+            r#"{"filter": "meet_slack_bot::google=trace,info", "revert_after_minutes": 5}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(log_filter.current(), "meet_slack_bot::google=trace,info");
+
+        let audit = db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 1);
+        assert_eq!(audit[0].event_type, "log_filter_changed");
+        assert_eq!(audit[0].detail["revert_after_minutes"], 5);
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_asks_browsers_for_credentials() {
+        let (db, _pool) = test_db().await;
+
+        for authorization in [None, basic("admin:wrong"), basic("admin-token")] {
+            let (response, body) = send(&db, SystemClock::shared(), "/admin", authorization).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                response.headers()[header::WWW_AUTHENTICATE],
+                BASIC_CHALLENGE
+            );
+            assert_eq!(body, "Missing or invalid credentials");
+        }
+
+        for authorization in [
+            basic("admin:admin-token"),
+            Some("Bearer admin-token".to_string()),
+        ] {
+            let (response, _) = send(&db, SystemClock::shared(), "/admin", authorization).await;
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dashboard_shows_totals_without_token_material() {
+        let (db, pool) = test_db().await;
+        let connected = db.create_user("U012AB3CD", "T012AB3C4").await.unwrap();
+        db.create_user("U098ZY7XW", "T012AB3C4").await.unwrap();
+        db.store_oauth_token(&OAuthToken::new(
+            connected.id,
+            "ya29.ACCESS".into(),
+            Some("1//REFRESH".into()),
+            None,
+            None,
+        ))
+        .await
+        .unwrap();
+        for created_at in [
+            "2024-03-14 09:00:00",
+            "2024-03-14 17:30:00",
+            "2024-03-10 12:00:00",
+            // Before the fourteen days shown
+            "2024-02-29 12:00:00",
+        ] {
+            sqlx::query(
+                "INSERT INTO meetings (user_id, meet_link, created_at) VALUES (?1, 'https://meet.google.com/abc-defg-hij', ?2)",
+            )
+            .bind(connected.id)
+            .bind(created_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        audit::record(
+            &db,
+            AuditEvent::token_refresh_failed(
+                &connected,
+                &OAuthError::RefreshFailed("invalid_grant for 1//REFRESH".into()),
+            ),
+        )
+        .await;
+        audit::record(
+            &db,
+            AuditEvent::signature_rejected("bad_signature", Some([203, 0, 113, 7].into()), None),
+        )
+        .await;
+        audit::record(&db, AuditEvent::google_disconnected(&connected, "logout")).await;
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2024, 3, 14, 18, 0, 0).unwrap());
+
+        let (response, page) = send(&db, clock, "/admin", basic("ops:admin-token")).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(
+            page.contains(r#"<div class="value">2</div>Users"#),
+            "{}",
+            page
+        );
+        assert!(
+            page.contains(r#"<div class="value">1</div>Connected Google accounts"#),
+            "{}",
+            page
+        );
+        // Fourteen days ending today, the busiest with the full bar
+        assert_eq!(page.matches("<rect ").count(), 14);
+        assert!(!page.contains("2024-02-29"), "{}", page);
+        assert!(page.contains("<td>2024-03-01</td>"), "{}", page);
+        assert!(page.contains(
+            r#"<td>2024-03-14</td>
+                <td>2</td>
+                <td><svg width="200" height="12"><rect width="200""#
+        ));
+        assert!(page.contains(
+            r#"<td>2024-03-10</td>
+                <td>1</td>
+                <td><svg width="200" height="12"><rect width="100""#
+        ));
+        // Auth failures only, newest first
+        let signature = page.find("signature_rejected").unwrap();
+        let refresh = page.find("token_refresh_failed").unwrap();
+        assert!(signature < refresh);
+        assert!(page.contains("203.0.113.7"));
+        assert!(page.contains("refresh_failed"));
+        assert!(!page.contains("google_disconnected"));
+        assert!(page.contains("No jobs running."));
+        for secret in ["admin-token", "ya29", "REFRESH", "invalid_grant"] {
+            assert!(!page.contains(secret), "{} shown", secret);
+        }
+    }
+
+    #[test]
+    fn test_job_health() {
+        let status = JobStatus {
+            interval_secs: 60,
+            active: true,
+            runs: 0,
+            failures: 0,
+            failing: false,
+            last_run: None,
+            last_success: None,
+            last_error: None,
+            last_error_at: None,
+        };
+        assert_eq!(job_row("prune", &status).health, "waiting");
+
+        let ran = JobStatus {
+            runs: 3,
+            failures: 1,
+            last_run: Some(Utc.with_ymd_and_hms(2024, 3, 14, 18, 0, 0).unwrap()),
+            ..status.clone()
+        };
+        let row = job_row("prune", &ran);
+        assert_eq!(row.health, "ok");
+        assert_eq!(row.last_run, "2024-03-14 18:00:00");
+        assert_eq!(
+            job_row(
+                "prune",
+                &JobStatus {
+                    failing: true,
+                    ..ran.clone()
+                }
+            )
+            .health,
+            "failing"
+        );
+        assert_eq!(
+            job_row(
+                "prune",
+                &JobStatus {
+                    active: false,
+                    failing: true,
+                    ..ran
+                }
+            )
+            .health,
+            "stopped"
+        );
+    }
+}
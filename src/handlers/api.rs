@@ -0,0 +1,200 @@
+//! The REST API, for tools creating meetings without going through Slack,
+//! such as incident tooling. Requests carry one of the workspace's
+//! [API keys](crate::api_keys) as `Authorization: Bearer <key>` and create
+//! the meeting the way `/meet` does, with the same Google accounts.
+
+use axum::{
+    body::Bytes,
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info, instrument, warn};
+use uuid::Uuid;
+
+use super::slack::SlashCommandPayload;
+use crate::api_keys;
+use crate::commands::{create_detached_meeting, team_artifacts, MeetingRequest};
+use crate::database::models::{ApiKey, MeetingSource};
+use crate::error::{AppError, RateLimit};
+use crate::observability::{self, Phase};
+use crate::quota;
+use crate::slack::commands as slack_commands;
+use crate::AppState;
+
+/// Where meetings are created; also the rate limiter's name for it.
+pub const MEETINGS_PATH: &str = "/api/v1/meetings";
+
+/// Largest request body accepted; a meeting request is a few fields.
+pub const MAX_BODY_BYTES: usize = 16 * 1024;
+
+/// The body of `POST /api/v1/meetings`.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CreateMeetingBody {
+    /// Whose meeting it is; the admin who made the key when left out. They
+    /// have to be in the key's workspace and have connected Google.
+    slack_user_id: Option<String>,
+    title: Option<String>,
+    duration_minutes: Option<i64>,
+    /// When the meeting starts, in RFC 3339; now when left out.
+    start_time: Option<DateTime<Utc>>,
+}
+
+/// What `POST /api/v1/meetings` answers with once the meeting exists.
+#[derive(Debug, Serialize)]
+pub struct CreatedMeetingBody {
+    /// The bot's id for the meeting; missing when it couldn't be stored,
+    /// though the link works.
+    meeting_id: Option<i64>,
+    meet_link: String,
+    /// Google's name for the Meet space, `spaces/{id}`.
+    event_id: Option<String>,
+    title: Option<String>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// `POST /api/v1/meetings`: creates a meeting for the key's workspace and
+/// answers with its link. The key is checked before the body is read.
+#[instrument(skip_all)]
+pub async fn create_meeting(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<CreatedMeetingBody>), AppError> {
+    let key = authenticate(&state, &headers).await?;
+    check_rate_limits(&state, &key).await?;
+    let body: CreateMeetingBody = serde_json::from_slice(&body)
+        .map_err(|e| AppError::BadRequest(format!("unparsable meeting request: {}", e)))?;
+
+    let slack_user_id = body.slack_user_id.unwrap_or_else(|| key.created_by.clone());
+    state.validator.validate_slack_user_id(&slack_user_id)?;
+    let user = match observability::timed(
+        Phase::Database,
+        state.db.get_user_by_slack_id(&slack_user_id),
+    )
+    .await?
+    {
+        // A key only acts for its own workspace
+        Some(user) if user.slack_team_id == key.slack_team_id => user,
+        _ => return Err(AppError::NotConnected(slack_user_id)),
+    };
+    if quota::reached(&state, &key.slack_team_id, &slack_user_id).await {
+        return Err(AppError::RateLimited(RateLimit::Team));
+    }
+
+    let now = state.clock.now();
+    let title = body
+        .title
+        .as_deref()
+        .map(|t| state.validator.validate_meeting_title(t))
+        .transpose()?
+        .map(|t| t.value);
+    if let Some(start_time) = body.start_time {
+        state.validator.validate_meeting_time(start_time, now)?;
+    }
+    let duration = match body.duration_minutes {
+        Some(minutes) if minutes <= 0 => {
+            return Err(AppError::BadRequest(
+                "duration_minutes must be positive".to_string(),
+            ))
+        }
+        Some(minutes) => {
+            // Too many minutes for a `Duration` are too long a meeting too
+            let duration = chrono::Duration::try_minutes(minutes).unwrap_or(chrono::Duration::MAX);
+            state.validator.validate_meeting_duration(duration)?;
+            Some(duration)
+        }
+        None => None,
+    };
+
+    let request = MeetingRequest {
+        title,
+        starts_at: body.start_time,
+        ends_at: duration.map(|duration| body.start_time.unwrap_or(now) + duration),
+        artifacts: team_artifacts(&state, &key.slack_team_id).await,
+        source: MeetingSource::Api,
+        ..MeetingRequest::default()
+    };
+    let payload = SlashCommandPayload {
+        team_id: key.slack_team_id.clone(),
+        enterprise_id: None,
+        channel_id: String::new(),
+        channel_name: String::new(),
+        user_id: slack_user_id.clone(),
+        user_name: String::new(),
+        command: state
+            .config
+            .slack
+            .commands
+            .name_for(slack_commands::MEET)
+            .to_string(),
+        text: None,
+        response_url: String::new(),
+        // Unique per request, so the trigger dedup never hands back
+        // another request's meeting
+        trigger_id: format!("api-{}", Uuid::new_v4()),
+        thread_ts: None,
+    };
+
+    let Some(meeting) = create_detached_meeting(&state, &payload, &user, &request).await? else {
+        return Err(AppError::NotConnected(slack_user_id));
+    };
+    info!(
+        "API key {} created meeting {:?} for {}",
+        key.id, meeting.id, slack_user_id
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreatedMeetingBody {
+            meeting_id: meeting.id,
+            meet_link: meeting.meet_link,
+            event_id: meeting.space_name,
+            title: meeting.title,
+            start_time: request.starts_at,
+            end_time: request.ends_at,
+        }),
+    ))
+}
+
+/// The key sent as `Authorization: Bearer <key>`, if it is one that works.
+async fn authenticate(state: &AppState, headers: &HeaderMap) -> Result<ApiKey, AppError> {
+    let Some(key) = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        warn!("Rejected API request without a key");
+        return Err(AppError::Unauthorized);
+    };
+
+    match api_keys::authenticate(&state.db, key.trim(), state.clock.now()).await? {
+        Some(key) => Ok(key),
+        None => {
+            warn!("Rejected API request with an unknown or revoked key");
+            Err(AppError::Unauthorized)
+        }
+    }
+}
+
+/// Each key has its own limit, and all of them share the endpoint's.
+async fn check_rate_limits(state: &AppState, key: &ApiKey) -> Result<(), AppError> {
+    if let Err(e) = state
+        .rate_limiter
+        .check_user_limit(&format!("api_key:{}", key.id), MEETINGS_PATH)
+        .await
+    {
+        warn!("Rate limit exceeded for API key {}: {}", key.id, e);
+        return Err(AppError::RateLimited(RateLimit::User));
+    }
+
+    if let Err(e) = state.rate_limiter.check_endpoint_limit(MEETINGS_PATH).await {
+        error!("Global rate limit exceeded for the API: {}", e);
+        return Err(AppError::RateLimited(RateLimit::Global));
+    }
+    Ok(())
+}
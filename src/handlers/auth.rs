@@ -1,40 +1,83 @@
+use anyhow::Context;
+use askama::Template;
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
     response::{Html, Redirect},
 };
+use chrono::{DateTime, Utc};
 use oauth2::{
-    basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl,
+    basic::{BasicClient, BasicTokenResponse},
+    AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken, RedirectUrl, RequestTokenError,
     Scope, TokenResponse, TokenUrl,
 };
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
 use tracing::{error, info, instrument, warn};
 
-use crate::{database::models::OAuthToken, validation::InputValidator, AppState};
+use crate::{
+    audit::{self, AuditEvent},
+    auth::oauth::REQUESTED_SCOPES,
+    database::{models::OAuthToken, Database},
+    error::{AppError, RateLimit},
+    http_client::oauth2_http_client,
+    secret::{redact, redact_url},
+    slack::commands as slack_commands,
+    telemetry::metrics,
+    AppState,
+};
+
+/// How long a user has to finish signing in with Google.
+pub const OAUTH_STATE_TTL: chrono::Duration = chrono::Duration::minutes(10);
 
 #[derive(Debug, Deserialize)]
 pub struct AuthQuery {
     pub user_id: String,
 }
 
-#[derive(Debug, Deserialize)]
+/// Query string Google redirects back with. On success it carries `code`
+/// and `state`; when the user denies access or Google rejects the request it
+/// carries `error` and usually `error_description` instead.
+#[derive(Deserialize)]
 pub struct CallbackQuery {
-    pub code: String,
-    pub state: String,
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+    pub error_description: Option<String>,
+}
+
+// `code` can be exchanged for a token and `state` carries the CSRF nonce,
+// so neither may show up in the span `#[instrument]` records
+impl fmt::Debug for CallbackQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallbackQuery")
+            .field("code", &self.code.as_deref().map(redact))
+            .field("state", &self.state.as_deref().map(redact))
+            .field("error", &self.error)
+            .field("error_description", &self.error_description)
+            .finish()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "auth_error.html")]
+struct ErrorPage<'a> {
+    message: &'a str,
+    request_id: Option<&'a str>,
 }
 
+#[derive(Template)]
+#[template(path = "auth_success.html")]
+struct SuccessPage;
+
 #[instrument(skip(state))]
 pub async fn initiate_google_oauth(
     State(state): State<AppState>,
     Query(query): Query<AuthQuery>,
-) -> Result<Redirect, StatusCode> {
+) -> Result<Redirect, AppError> {
     info!("Initiating Google OAuth for user: {}", query.user_id);
 
-    let validator = InputValidator::new();
-    if let Err(e) = validator.validate_slack_user_id(&query.user_id) {
-        warn!("Invalid user ID in OAuth request: {}", e);
-        return Err(StatusCode::BAD_REQUEST);
-    }
+    state.validator.validate_slack_user_id(&query.user_id)?;
 
     if let Err(e) = state
         .rate_limiter
@@ -45,7 +88,7 @@ pub async fn initiate_google_oauth(
             "Rate limit exceeded for user {} on OAuth: {}",
             query.user_id, e
         );
-        return Err(StatusCode::TOO_MANY_REQUESTS);
+        return Err(AppError::RateLimited(RateLimit::User));
     }
 
     if let Err(e) = state
@@ -54,28 +97,37 @@ pub async fn initiate_google_oauth(
         .await
     {
         error!("Global rate limit exceeded for OAuth: {}", e);
-        return Err(StatusCode::SERVICE_UNAVAILABLE);
+        return Err(AppError::RateLimited(RateLimit::Global));
     }
 
     let client = create_oauth_client(&state)?;
 
-    // Create a cryptographically secure state parameter
-    use base64::{engine::general_purpose, Engine as _};
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    let random_bytes: [u8; 32] = rng.gen();
-    let random_state = general_purpose::URL_SAFE_NO_PAD.encode(&random_bytes);
-    let state_param = format!("user:{}:{}", query.user_id, random_state);
-    let csrf_token = CsrfToken::new(state_param);
+    let oauth_state = generate_oauth_state(&query.user_id);
+    state
+        .db
+        .record_oauth_state(
+            &hash_oauth_state(&oauth_state),
+            &query.user_id,
+            state.clock.now().naive_utc(),
+        )
+        .await?;
+    let csrf_token = CsrfToken::new(oauth_state);
 
+    // Offline access with a fresh consent, so Google hands out a refresh
+    // token even to a user who connected before
     let (auth_url, _) = client
         .authorize_url(|| csrf_token.clone())
-        .add_scope(Scope::new(
-            "https://www.googleapis.com/auth/meetings.space.created".to_string(),
-        ))
+        .add_scopes(
+            REQUESTED_SCOPES
+                .iter()
+                .map(|scope| Scope::new(scope.to_string())),
+        )
+        .add_extra_param("access_type", "offline")
+        .add_extra_param("prompt", "consent")
         .url();
 
-    info!("Redirecting to Google OAuth: {}", auth_url);
+    metrics::record_oauth_flow("started");
+    info!(auth_url = %redact_url(&auth_url), "Redirecting to Google OAuth");
     Ok(Redirect::temporary(auth_url.as_str()))
 }
 
@@ -83,41 +135,40 @@ pub async fn initiate_google_oauth(
 pub async fn handle_google_callback(
     State(state): State<AppState>,
     Query(query): Query<CallbackQuery>,
-) -> Result<Html<String>, StatusCode> {
+) -> Result<Html<String>, AppError> {
     info!("Handling Google OAuth callback");
 
-    // Validate OAuth parameters
-    let validator = InputValidator::new();
-    if let Err(e) = validator.validate_oauth_code(&query.code) {
-        warn!("Invalid OAuth code: {}", e);
-        return Ok(Html(create_error_page("Invalid authorization code")));
+    if let Some(error) = &query.error {
+        warn!("Google returned an OAuth error: {}", error);
+        metrics::record_oauth_flow("denied");
+        return Ok(Html(create_google_error_page(
+            error,
+            query.error_description.as_deref(),
+        )));
     }
 
-    if let Err(e) = validator.validate_oauth_state(&query.state) {
-        warn!("Invalid OAuth state: {}", e);
-        return Ok(Html(create_error_page("Invalid authentication state")));
+    let (Some(code), Some(oauth_state)) = (query.code, query.state) else {
+        warn!("OAuth callback is missing code or state");
+        return Ok(Html(create_error_page(
+            "The authorization response was incomplete.",
+        )));
+    };
+
+    // Validate OAuth parameters
+    let validator = &state.validator;
+    if let Err(e) = validator.validate_oauth_code(&code) {
+        warn!("Invalid OAuth code: {}", e);
+        return Ok(Html(create_error_page(&e.to_string())));
     }
 
-    // Parse and validate state parameter to extract user_id
-    let user_id = if query.state.starts_with("user:") {
-        let parts: Vec<&str> = query.state.splitn(3, ':').collect();
-        if parts.len() >= 2 {
-            parts[1]
-        } else {
-            error!("Invalid state parameter format: {}", query.state);
-            return Ok(Html(create_error_page("Invalid authentication state")));
+    let user_id = match validator.validate_oauth_state(&oauth_state) {
+        Ok(user_id) => user_id,
+        Err(e) => {
+            warn!("Invalid OAuth state: {}", e);
+            return Ok(Html(create_error_page(&e.to_string())));
         }
-    } else {
-        error!("Invalid state parameter format: {}", query.state);
-        return Ok(Html(create_error_page("Invalid authentication state")));
     };
 
-    // Validate extracted user ID
-    if let Err(e) = validator.validate_slack_user_id(user_id) {
-        warn!("Invalid user ID in OAuth callback: {}", e);
-        return Ok(Html(create_error_page("Invalid user ID")));
-    }
-
     // Apply rate limiting
     if let Err(e) = state
         .rate_limiter
@@ -144,13 +195,36 @@ pub async fn handle_google_callback(
         )));
     }
 
+    // The state has to be one handed out here, to this user, recently
+    let issued = state
+        .db
+        .take_oauth_state(&hash_oauth_state(&oauth_state))
+        .await;
+    match issued {
+        Ok(Some((issued_to, issued_at)))
+            if issued_to == user_id
+                && state.clock.now().naive_utc() - issued_at <= OAUTH_STATE_TTL => {}
+        Ok(_) => {
+            warn!("OAuth state was not issued to this user, was used already or expired");
+            metrics::record_oauth_flow("rejected");
+            return Ok(Html(create_error_page(&format!(
+                "This sign-in link has expired or was already used. Run {} again to get a new one.",
+                state.config.slack.commands.name_for(slack_commands::MEET)
+            ))));
+        }
+        Err(e) => {
+            error!("Failed to look up OAuth state: {}", e);
+            return Ok(Html(create_error_page("Database error")));
+        }
+    }
+
     info!("Processing OAuth callback for user: {}", user_id);
 
     // Exchange authorization code for access token
     let client = create_oauth_client(&state)?;
     let token_result = client
-        .exchange_code(AuthorizationCode::new(query.code))
-        .request_async(oauth2::reqwest::async_http_client)
+        .exchange_code(AuthorizationCode::new(code))
+        .request_async(|request| oauth2_http_client(&state.http, request))
         .await;
 
     match token_result {
@@ -172,108 +246,288 @@ pub async fn handle_google_callback(
 
             // Calculate expiration time
             let expires_at = token.expires_in().map(|duration| {
-                chrono::Utc::now() + chrono::Duration::seconds(duration.as_secs() as i64)
+                state.clock.now() + chrono::Duration::seconds(duration.as_secs() as i64)
             });
 
             // Store OAuth token
             let oauth_token = OAuthToken::new(
                 user.id,
-                token.access_token().secret().to_string(),
-                token.refresh_token().map(|t| t.secret().to_string()),
+                token.access_token().secret().clone().into(),
+                token.refresh_token().map(|t| t.secret().clone().into()),
                 expires_at,
-                Some("https://www.googleapis.com/auth/meetings.space.created".to_string()),
+                Some(granted_scopes(&token)),
             );
 
             match state.db.store_oauth_token(&oauth_token).await {
                 Ok(_) => {
                     info!("OAuth token stored successfully for user: {}", user_id);
+                    state.google_failures.reset(user_id).await;
+                    metrics::record_oauth_flow("completed");
+                    audit::record(
+                        &state.db,
+                        AuditEvent::google_connected(&user, oauth_token.scope.as_deref()),
+                    )
+                    .await;
                     Ok(Html(create_success_page()))
                 }
                 Err(e) => {
                     error!("Failed to store OAuth token: {}", e);
+                    metrics::record_oauth_flow("failed");
                     Ok(Html(create_error_page("Failed to store authentication")))
                 }
             }
         }
+        Err(RequestTokenError::ServerResponse(response)) => {
+            error!("Google rejected the OAuth code exchange: {:?}", response);
+            metrics::record_oauth_flow("failed");
+            Ok(Html(create_google_error_page(
+                response.error().as_ref(),
+                response.error_description().map(String::as_str),
+            )))
+        }
         Err(e) => {
             error!("Failed to exchange OAuth code: {}", e);
+            metrics::record_oauth_flow("failed");
             Ok(Html(create_error_page("Authentication failed")))
         }
     }
 }
 
-pub fn create_oauth_client(state: &AppState) -> Result<BasicClient, StatusCode> {
+/// Creates a cryptographically secure state parameter of the form
+/// `user:<slack user id>:<base64url nonce>`.
+pub fn generate_oauth_state(slack_user_id: &str) -> String {
+    format!("user:{}:{}", slack_user_id, oauth_nonce())
+}
+
+/// 32 random bytes, base64url-encoded.
+pub(super) fn oauth_nonce() -> String {
+    use base64::{engine::general_purpose, Engine as _};
+    use rand::Rng;
+
+    let random_bytes: [u8; 32] = rand::thread_rng().gen();
+    general_purpose::URL_SAFE_NO_PAD.encode(random_bytes)
+}
+
+/// How an OAuth state is stored: hex SHA-256, so the table alone doesn't
+/// let anyone complete a sign-in.
+pub(super) fn hash_oauth_state(oauth_state: &str) -> String {
+    hex::encode(Sha256::digest(oauth_state.as_bytes()))
+}
+
+/// The scopes the user granted, or all we asked for if Google didn't list them.
+fn granted_scopes(token: &BasicTokenResponse) -> String {
+    match token.scopes() {
+        Some(scopes) => scopes
+            .iter()
+            .map(|scope| scope.as_str())
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => REQUESTED_SCOPES.join(" "),
+    }
+}
+
+/// Forgets OAuth states older than [`OAUTH_STATE_TTL`] at `now`, which can
+/// no longer be used.
+pub async fn prune_oauth_states(db: &Database, now: DateTime<Utc>) -> anyhow::Result<()> {
+    let pruned = db
+        .prune_oauth_states((now - OAUTH_STATE_TTL).naive_utc())
+        .await?;
+    if pruned > 0 {
+        info!("Pruned {} unused OAuth states", pruned);
+    }
+    Ok(())
+}
+
+pub fn create_oauth_client(state: &AppState) -> Result<BasicClient, AppError> {
     let client = BasicClient::new(
-        ClientId::new(state.google_client_id.clone()),
-        Some(ClientSecret::new(state.google_client_secret.clone())),
-        AuthUrl::new("https://accounts.google.com/o/oauth2/v2/auth".to_string()).map_err(|e| {
-            error!("Invalid auth URL: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?,
-        Some(
-            TokenUrl::new("https://www.googleapis.com/oauth2/v4/token".to_string()).map_err(
-                |e| {
-                    error!("Invalid token URL: {}", e);
-                    StatusCode::INTERNAL_SERVER_ERROR
-                },
-            )?,
-        ),
+        ClientId::new(state.config.google.client_id.clone()),
+        Some(ClientSecret::new(
+            state.config.google.client_secret.expose().to_string(),
+        )),
+        AuthUrl::new(state.config.google.auth_url.clone()).context("Invalid auth URL")?,
+        Some(TokenUrl::new(state.config.google.token_url.clone()).context("Invalid token URL")?),
     )
     .set_redirect_uri(
-        RedirectUrl::new(state.google_redirect_uri.clone()).map_err(|e| {
-            error!("Invalid redirect URI: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?,
+        RedirectUrl::new(state.config.google.redirect_uri.clone())
+            .context("Invalid redirect URI")?,
     );
 
     Ok(client)
 }
 
 fn create_success_page() -> String {
-    r#"
-    <!DOCTYPE html>
-    <html>
-    <head>
-        <title>Authentication Successful</title>
-        <style>
-            body { font-family: Arial, sans-serif; text-align: center; margin: 50px; }
-            .success { color: #28a745; }
-            .container { max-width: 500px; margin: 0 auto; }
-        </style>
-    </head>
-    <body>
-        <div class="container">
-            <h1 class="success">✅ Authentication Successful!</h1>
-            <p>You've successfully connected your Google account to the Slack bot.</p>
-            <p>You can now close this window and return to Slack to use the <code>/meet</code> command.</p>
-        </div>
-    </body>
-    </html>
-    "#.to_string()
+    render_page(&SuccessPage)
 }
 
-fn create_error_page(error_message: &str) -> String {
-    format!(
-        r#"
-        <!DOCTYPE html>
-        <html>
-        <head>
-            <title>Authentication Error</title>
-            <style>
-                body {{ font-family: Arial, sans-serif; text-align: center; margin: 50px; }}
-                .error {{ color: #dc3545; }}
-                .container {{ max-width: 500px; margin: 0 auto; }}
-            </style>
-        </head>
-        <body>
-            <div class="container">
-                <h1 class="error">❌ Authentication Error</h1>
-                <p>{}</p>
-                <p>Please try again or contact support if the problem persists.</p>
-            </div>
-        </body>
-        </html>
-        "#,
-        error_message
-    )
+pub(super) fn create_error_page(error_message: &str) -> String {
+    render_page(&ErrorPage {
+        message: error_message,
+        request_id: None,
+    })
+}
+
+/// Renders an error reported by Google. `error_description` is
+/// attacker-influenceable, so it goes through the same escaping template as
+/// every other message.
+fn create_google_error_page(error: &str, error_description: Option<&str>) -> String {
+    match (error, error_description) {
+        (_, Some(description)) => create_error_page(description),
+        ("access_denied", None) => create_error_page("Access to your Google account was denied."),
+        _ => create_error_page("Google could not complete the sign-in."),
+    }
+}
+
+pub(super) fn render_page(page: &impl Template) -> String {
+    page.render().unwrap_or_else(|e| {
+        error!("Failed to render page: {}", e);
+        "Something went wrong. Please try again.".to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validation::InputValidator;
+    use axum::response::IntoResponse;
+
+    #[test]
+    fn test_generated_state_passes_callback_validation() {
+        let validator = InputValidator::default();
+
+        for user_id in ["U1234567890", "UABCDEFGHIJ"] {
+            let state = generate_oauth_state(user_id);
+            assert_eq!(validator.validate_oauth_state(&state), Ok(user_id));
+        }
+    }
+
+    #[test]
+    fn test_error_page_escapes_message() {
+        let page = create_error_page(r#"<script>alert("x")</script> & 'quotes'"#);
+
+        assert!(!page.contains("<script>"));
+        assert!(page.contains("&lt;script&gt;"));
+        assert!(page.contains("&quot;x&quot;"));
+        assert!(page.contains("&amp;"));
+        assert!(!page.contains("'quotes'"));
+        assert!(!page.contains("Request ID"));
+    }
+
+    #[test]
+    fn test_error_page_shows_request_id() {
+        let page = render_page(&ErrorPage {
+            message: "Authentication failed",
+            request_id: Some("req-<123>"),
+        });
+
+        assert!(page.contains("Request ID"));
+        assert!(page.contains("req-&lt;123&gt;"));
+    }
+
+    #[test]
+    fn test_google_error_description_is_escaped() {
+        let page =
+            create_google_error_page("invalid_request", Some("<img src=x onerror=alert(1)>"));
+
+        assert!(!page.contains("<img"));
+        assert!(page.contains("&lt;img src=x onerror=alert(1)&gt;"));
+
+        let page = create_google_error_page("access_denied", None);
+        assert!(page.contains("Access to your Google account was denied."));
+    }
+
+    #[test]
+    fn test_generated_states_are_unique() {
+        assert_ne!(
+            generate_oauth_state("U1234567890"),
+            generate_oauth_state("U1234567890")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oauth_logs_never_contain_codes_or_state() {
+        use crate::background::JobRegistry;
+        use crate::config::Config;
+        use crate::database::test_db;
+        use crate::google::fake::FakeGoogleApi;
+        use crate::observability::ErrorRateMonitor;
+        use crate::rate_limiter::RateLimiter;
+        use crate::telemetry::logging::{tests::capture, LogFormat};
+        use std::sync::Arc;
+
+        let (db, _pool) = test_db().await;
+        let state = AppState {
+            db,
+            rate_limiter: RateLimiter::new(),
+            google_failures: crate::google_failures::GoogleFailures::new(&Default::default()),
+            validator: Arc::new(InputValidator::default()),
+            config: Arc::new(Config::for_tests()),
+            error_rate: Arc::new(ErrorRateMonitor::new(&Default::default())),
+            jobs: JobRegistry::new(),
+            google: Arc::new(FakeGoogleApi::succeeding()),
+            slack: Arc::new(crate::slack::fake::FakeSlackApi::new()),
+            http: reqwest::Client::new(),
+            commands: Arc::new(crate::commands::CommandRegistry::new()),
+            in_flight: Arc::new(crate::commands::InFlightCommands::new(Default::default())),
+            meeting_queue: crate::commands::MeetingQueue::new(
+                Default::default(),
+                reqwest::Client::new(),
+            ),
+            clock: crate::time::SystemClock::shared(),
+            metrics: crate::telemetry::metrics::install(),
+            log_filter: crate::telemetry::logging::LogFilter::new("info").unwrap().1,
+        };
+
+        for format in [LogFormat::Pretty, LogFormat::Json] {
+            let (subscriber, logs) = capture(format);
+            let _guard = tracing::subscriber::set_default(subscriber);
+
+            let redirect = initiate_google_oauth(
+                State(state.clone()),
+                Query(AuthQuery {
+                    user_id: "U012AB3CD".to_string(),
+                }),
+            )
+            .await
+            .unwrap();
+            let location = redirect.into_response().headers()["location"]
+                .to_str()
+                .unwrap()
+                .to_string();
+            let issued_state = url::Url::parse(&location)
+                .unwrap()
+                .query_pairs()
+                .find(|(name, _)| name == "state")
+                .unwrap()
+                .1
+                .into_owned();
+
+            // A code that passes validation paired with a forged state, so
+            // the callback logs and bails out before calling Google
+            let nonce = issued_state.rsplit(':').next().unwrap();
+            let _ = handle_google_callback(
+                State(state.clone()),
+                Query(CallbackQuery {
+                    code: Some("4/0AcodeSECRETmarker".to_string()),
+                    state: Some(format!("user:not-a-user:{}", nonce)),
+                    error: None,
+                    error_description: None,
+                }),
+            )
+            .await
+            .unwrap();
+
+            let output = logs.contents();
+            assert!(output.contains("Redirecting to Google OAuth"), "{}", output);
+            assert!(output.contains("Invalid OAuth state"), "{}", output);
+            for marker in [nonce, "SECRETmarker", "client-secret"] {
+                assert!(
+                    !output.contains(marker),
+                    "{:?} leaked into {:?} logs:\n{}",
+                    marker,
+                    format,
+                    output
+                );
+            }
+        }
+    }
 }
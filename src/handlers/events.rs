@@ -0,0 +1,170 @@
+use axum::{extract::State, response::Json};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::{info, instrument, warn};
+
+use crate::error::AppError;
+use crate::handlers::slack::{run_detached, SlashCommandPayload};
+use crate::slack::commands as slack_commands;
+use crate::slack::VerifiedSlackBody;
+use crate::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventEnvelope {
+    UrlVerification {
+        challenge: String,
+    },
+    EventCallback {
+        team_id: String,
+        event: Value,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+/// Someone mentioned the bot, as in `@Meet standup 30m`.
+#[derive(Debug, Deserialize)]
+struct AppMention {
+    user: String,
+    text: String,
+    channel: String,
+    ts: String,
+    /// Set when the mention is a reply in a thread.
+    thread_ts: Option<String>,
+    /// Set when another bot posted the mention.
+    bot_id: Option<String>,
+}
+
+impl AppMention {
+    /// The mention as `/meet` with the text after the bot's handle,
+    /// replying in its thread if it is in one. Mentions have no response
+    /// URL, and their channel and timestamp make the trigger id, so Slack's
+    /// retries of the event get the meeting already created.
+    fn into_payload(self, team_id: String, command: &str) -> SlashCommandPayload {
+        let text = strip_leading_mention(&self.text).to_string();
+        SlashCommandPayload {
+            team_id,
+            enterprise_id: None,
+            trigger_id: format!("mention:{}:{}", self.channel, self.ts),
+            channel_id: self.channel,
+            channel_name: String::new(),
+            user_name: self.user.clone(),
+            user_id: self.user,
+            command: command.to_string(),
+            text: (!text.is_empty()).then_some(text),
+            response_url: String::new(),
+            thread_ts: self.thread_ts,
+        }
+    }
+}
+
+/// `text` without the `<@U123>` mention of the bot it starts with.
+fn strip_leading_mention(text: &str) -> &str {
+    let text = text.trim_start();
+    match text
+        .strip_prefix("<@")
+        .and_then(|rest| rest.split_once('>'))
+    {
+        Some((_, rest)) => rest.trim(),
+        None => text.trim(),
+    }
+}
+
+/// Entry point for the Slack Events API.
+///
+/// Answers the one-time `url_verification` handshake and acknowledges event
+/// callbacks. An `app_mention` runs the text after the bot's handle as
+/// `/meet` in the background, replying in the mention's thread when it is
+/// in one; other event types are dispatched as features need them.
+#[instrument(skip(state, verified))]
+pub async fn handle_event(
+    State(state): State<AppState>,
+    verified: VerifiedSlackBody,
+) -> Result<Json<Value>, AppError> {
+    let envelope: EventEnvelope = serde_json::from_str(&verified.body)
+        .map_err(|e| AppError::BadRequest(format!("unparsable Slack event: {}", e)))?;
+
+    match envelope {
+        EventEnvelope::UrlVerification { challenge } => {
+            info!("Answering Slack URL verification challenge");
+            Ok(Json(json!({ "challenge": challenge })))
+        }
+        EventEnvelope::EventCallback { team_id, event } => {
+            let kind = event
+                .get("type")
+                .and_then(|t| t.as_str())
+                .unwrap_or("unknown");
+            info!("Received event {} for team {}", kind, team_id);
+            if kind == "app_mention" {
+                handle_mention(state, team_id, event);
+            }
+            Ok(Json(json!({})))
+        }
+        EventEnvelope::Unknown => {
+            warn!("Ignoring unsupported Slack event envelope");
+            Ok(Json(json!({})))
+        }
+    }
+}
+
+/// Slack retries events that aren't acknowledged with a 200, so a mention
+/// that can't be run is only logged.
+fn handle_mention(state: AppState, team_id: String, event: Value) {
+    let mention: AppMention = match serde_json::from_value(event) {
+        Ok(mention) => mention,
+        Err(e) => {
+            warn!("Ignoring unparsable app_mention: {}", e);
+            return;
+        }
+    };
+    if mention.bot_id.is_some() {
+        info!("Ignoring a mention posted by a bot");
+        return;
+    }
+    let command = state.config.slack.commands.name_for(slack_commands::MEET);
+    let payload = mention.into_payload(team_id, command);
+    if let Err(e) = run_detached(state, payload) {
+        warn!("Ignoring app_mention: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mention(text: &str, thread_ts: Option<&str>) -> AppMention {
+        serde_json::from_value(json!({
+            "type": "app_mention",
+            "user": "U012AB3CD",
+            "text": text,
+            "ts": "1709628700.000300",
+            "channel": "C012AB3CD",
+            "thread_ts": thread_ts,
+            "event_ts": "1709628700.000300",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_mention_text_becomes_the_command() {
+        let payload = mention("<@U0BOT0001> standup 30m", None)
+            .into_payload("T012AB3C4".to_string(), "/meet");
+
+        assert_eq!(payload.text.as_deref(), Some("standup 30m"));
+        assert_eq!(payload.trigger_id, "mention:C012AB3CD:1709628700.000300");
+        assert_eq!(payload.response_url, "");
+        assert_eq!(payload.thread_ts, None);
+
+        let payload = mention("<@U0BOT0001>", None).into_payload("T012AB3C4".to_string(), "/meet");
+        assert_eq!(payload.text, None);
+    }
+
+    #[test]
+    fn test_mention_in_a_thread_replies_there() {
+        let payload = mention("<@U0BOT0001> bridge", Some("1709628600.000200"))
+            .into_payload("T012AB3C4".to_string(), "/meet");
+
+        assert_eq!(payload.thread_ts.as_deref(), Some("1709628600.000200"));
+    }
+}
@@ -0,0 +1,107 @@
+//! `GET /export/:token`: the download link `/meet export-my-data` hands out
+//! when it can't send the file in Slack.
+
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use tracing::{info, warn};
+
+use crate::{error::AppError, export, AppState};
+
+/// Answers with the export of the user the link was signed for. Tampered,
+/// expired and malformed links all get the same 401, as does the link of a
+/// user the bot no longer knows.
+pub async fn download(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Response, AppError> {
+    let slack_user_id = export::verify_link_token(
+        &state.config.slack.signing_secret,
+        &token,
+        state.clock.now(),
+    )
+    .map_err(|e| {
+        warn!("Refused data export download: {}", e);
+        AppError::Unauthorized
+    })?;
+    let Some(user) = state.db.get_user_by_slack_id(&slack_user_id).await? else {
+        warn!(
+            "Refused data export download of unknown user {}",
+            slack_user_id
+        );
+        return Err(AppError::Unauthorized);
+    };
+
+    let export = export::gather(&state.db, &user, state.clock.now()).await?;
+    info!("Served data export to user {}", user.id);
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/json".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    export::filename(&user.slack_user_id)
+                ),
+            ),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        export.to_json(),
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::{connected_user, test_state};
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_download_serves_the_export() {
+        let (state, _pool) = test_state().await;
+        connected_user(&state).await;
+        let token = export::sign_link_token(
+            &state.config.slack.signing_secret,
+            "U012AB3CD",
+            state.clock.now() + export::LINK_TTL,
+        );
+
+        let response = download(State(state), Path(token)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"google-meet-bot-U012AB3CD.json\""
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let export: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(export["user"]["slack_user_id"], "U012AB3CD");
+        assert_eq!(export["google_account_connected"], true);
+    }
+
+    #[tokio::test]
+    async fn test_download_refuses_expired_and_tampered_links() {
+        let (state, _pool) = test_state().await;
+        connected_user(&state).await;
+        let secret = &state.config.slack.signing_secret;
+        let expired = export::sign_link_token(
+            secret,
+            "U012AB3CD",
+            state.clock.now() - chrono::Duration::seconds(1),
+        );
+        let tampered =
+            export::sign_link_token(secret, "U012AB3CD", state.clock.now() + export::LINK_TTL)
+                .replacen("U012AB3CD", "U098ZY7XW", 1);
+
+        for token in [expired, tampered] {
+            let error = download(State(state.clone()), Path(token))
+                .await
+                .unwrap_err();
+            assert!(matches!(error, AppError::Unauthorized), "{:?}", error);
+        }
+    }
+}
@@ -0,0 +1,309 @@
+use axum::{extract::State, http::StatusCode, response::Json, routing::get, Router};
+use serde_json::{json, Map, Value};
+use std::future::Future;
+use std::time::Duration;
+use tracing::{instrument, warn};
+
+use crate::background::JobRegistry;
+use crate::build_info;
+use crate::database::Database;
+
+/// Any single readiness check taking longer than this counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Host whose resolution `/ready` checks when Google checks are enabled.
+const GOOGLE_TOKEN_HOST: &str = "oauth2.googleapis.com:443";
+
+#[derive(Clone)]
+struct HealthState {
+    db: Database,
+    check_google: bool,
+    jobs: JobRegistry,
+}
+
+/// Router serving `/health`, a liveness check that only proves the process
+/// answers, `/ready`, which checks the database, recent writes to it,
+/// migrations and encryption keys, plus DNS for Google's token endpoint with `check_google`, and
+/// answers 503 until they all pass, and `/version`.
+///
+/// `/ready` also lists the background jobs in `jobs`, and fails once any of
+/// them has stopped; a job whose last run failed is reported but doesn't
+/// take the instance out of rotation. `migrations` lists the versions still
+/// to apply and those applied by a newer version of the bot.
+pub fn router(db: Database, check_google: bool, jobs: JobRegistry) -> Router {
+    Router::new()
+        .route("/health", get(health_check))
+        .route("/ready", get(readiness_check))
+        .route("/version", get(version))
+        .with_state(HealthState {
+            db,
+            check_google,
+            jobs,
+        })
+}
+
+#[instrument]
+async fn health_check() -> Json<Value> {
+    Json(json!({
+        "status": "healthy",
+        "service": "meet-slack-bot",
+        "version": build_info::VERSION,
+        "git_sha": build_info::GIT_SHA,
+        "timestamp": chrono::Utc::now().to_rfc3339()
+    }))
+}
+
+/// Which build is running: crate version, commit and build time.
+async fn version() -> Json<Value> {
+    Json(build_info::as_json())
+}
+
+#[instrument(skip(state))]
+async fn readiness_check(State(state): State<HealthState>) -> (StatusCode, Json<Value>) {
+    let mut checks = Map::new();
+    let mut failing = Vec::new();
+
+    let mut record = |name: &'static str, result: anyhow::Result<()>| {
+        let status = match result {
+            Ok(()) => "ok",
+            Err(e) => {
+                warn!("Readiness check {} failed: {}", name, e);
+                failing.push(name);
+                "failed"
+            }
+        };
+        checks.insert(name.to_string(), json!(status));
+    };
+
+    record("database", with_timeout(state.db.ping()).await);
+    record("database_writes", state.db.check_writes());
+    let migrations = with_timeout(state.db.migration_status()).await;
+    record(
+        "migrations",
+        match &migrations {
+            Ok(status) => match status.pending().as_slice() {
+                [] => Ok(()),
+                pending => Err(anyhow::anyhow!("not applied: {:?}", pending)),
+            },
+            Err(e) => Err(anyhow::anyhow!("{:#}", e)),
+        },
+    );
+    let migrations = migrations.ok().map(|status| {
+        json!({
+            "applied": status.applied.len(),
+            "available": status.available.len(),
+            "pending": status.pending(),
+            "unknown": status.unknown(),
+        })
+    });
+    record("encryption", state.db.check_encryption());
+    if state.check_google {
+        record(
+            "google_dns",
+            with_timeout(async {
+                let mut addresses = tokio::net::lookup_host(GOOGLE_TOKEN_HOST).await?;
+                anyhow::ensure!(addresses.next().is_some(), "no addresses");
+                Ok(())
+            })
+            .await,
+        );
+    }
+
+    let jobs = state.jobs.snapshot();
+    let stopped: Vec<_> = jobs
+        .iter()
+        .filter(|(_, status)| !status.active)
+        .map(|(name, _)| *name)
+        .collect();
+    record(
+        "background_jobs",
+        match stopped.as_slice() {
+            [] => Ok(()),
+            stopped => Err(anyhow::anyhow!("stopped: {}", stopped.join(", "))),
+        },
+    );
+
+    if failing.is_empty() {
+        (
+            StatusCode::OK,
+            Json(
+                json!({ "status": "ready", "checks": checks, "jobs": jobs, "migrations": migrations }),
+            ),
+        )
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(
+                json!({ "status": "not_ready", "failing": failing, "checks": checks, "jobs": jobs, "migrations": migrations }),
+            ),
+        )
+    }
+}
+
+async fn with_timeout<T>(check: impl Future<Output = anyhow::Result<T>>) -> anyhow::Result<T> {
+    tokio::time::timeout(CHECK_TIMEOUT, check)
+        .await
+        .map_err(|_| anyhow::anyhow!("timed out after {:?}", CHECK_TIMEOUT))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{test_db, test_db_unmigrated};
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    async fn get(app: Router, uri: &str) -> (StatusCode, Value) {
+        let response = app
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_ready_once_migrated() {
+        let (db, _pool) = test_db().await;
+
+        let (status, body) = get(router(db, false, JobRegistry::new()), "/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ready");
+        assert_eq!(body["checks"]["database"], "ok");
+        assert_eq!(body["checks"]["database_writes"], "ok");
+        assert_eq!(body["checks"]["migrations"], "ok");
+        assert_eq!(body["checks"]["encryption"], "ok");
+        assert!(body["checks"].get("google_dns").is_none());
+        assert_eq!(body["checks"]["background_jobs"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_once_a_job_stops() {
+        let (db, _pool) = test_db().await;
+        let jobs = JobRegistry::new();
+        let shutdown = tokio_util::sync::CancellationToken::new();
+        let handle = jobs.spawn_periodic(
+            "cleanup",
+            Duration::from_secs(60),
+            shutdown.clone(),
+            Duration::ZERO,
+            || async { anyhow::bail!("database is locked") },
+        );
+
+        let app = router(db, false, jobs);
+        let (status, body) = get(app.clone(), "/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["jobs"]["cleanup"]["active"], true);
+
+        shutdown.cancel();
+        handle.await.unwrap();
+        let (status, body) = get(app, "/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["failing"], json!(["background_jobs"]));
+        assert_eq!(body["jobs"]["cleanup"]["active"], false);
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_before_migrations() {
+        let (db, _pool) = test_db_unmigrated().await;
+
+        let (status, body) = get(router(db, false, JobRegistry::new()), "/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["failing"], json!(["migrations"]));
+        assert_eq!(body["checks"]["database"], "ok");
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_while_a_migration_is_pending() {
+        let (db, _pool) = test_db_unmigrated().await;
+        db.migrate_all_but_last().await.unwrap();
+        let newest = *db
+            .migration_status()
+            .await
+            .unwrap()
+            .available
+            .last()
+            .unwrap();
+
+        let app = router(db.clone(), false, JobRegistry::new());
+        let (status, body) = get(app.clone(), "/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["failing"], json!(["migrations"]));
+        assert_eq!(body["migrations"]["pending"], json!([newest]));
+        assert_eq!(
+            body["migrations"]["applied"],
+            body["migrations"]["available"].as_u64().unwrap() - 1
+        );
+
+        db.migrate().await.unwrap();
+        let (status, body) = get(app, "/ready").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["migrations"]["pending"], json!([]));
+        assert_eq!(body["migrations"]["unknown"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_with_broken_database() {
+        let (db, pool) = test_db_unmigrated().await;
+        db.migrate().await.unwrap();
+        pool.close().await;
+
+        let app = router(db, false, JobRegistry::new());
+        let (status, body) = get(app.clone(), "/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "not_ready");
+        assert_eq!(body["failing"], json!(["database", "migrations"]));
+        assert_eq!(body["checks"]["database_writes"], "ok");
+        assert_eq!(body["checks"]["encryption"], "ok");
+
+        // Liveness doesn't depend on the database
+        let (status, body) = get(app, "/health").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "healthy");
+    }
+
+    #[tokio::test]
+    async fn test_not_ready_while_writes_fail() {
+        let (db, pool) = test_db_unmigrated().await;
+        db.migrate().await.unwrap();
+        // Reads still work, like a database on a volume gone read-only
+        sqlx::query("PRAGMA query_only = ON")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(db.create_user("U012AB3CD", "T012AB3C4").await.is_err());
+
+        let app = router(db.clone(), false, JobRegistry::new());
+        let (status, body) = get(app.clone(), "/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["failing"], json!(["database_writes"]));
+        assert_eq!(body["checks"]["database"], "ok");
+
+        sqlx::query("PRAGMA query_only = OFF")
+            .execute(&pool)
+            .await
+            .unwrap();
+        db.create_user("U012AB3CD", "T012AB3C4").await.unwrap();
+        let (status, _) = get(app, "/ready").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_version_reports_the_build() {
+        let (db, _pool) = test_db_unmigrated().await;
+        let app = router(db, false, JobRegistry::new());
+
+        let (status, body) = get(app.clone(), "/version").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(body["git_sha"], option_env!("GIT_SHA").unwrap_or("unknown"));
+        assert_eq!(body["build_time"], build_info::build_time());
+
+        let (_, health) = get(app, "/health").await;
+        assert_eq!(health["version"], body["version"]);
+        assert_eq!(health["git_sha"], body["git_sha"]);
+    }
+}
@@ -0,0 +1,149 @@
+//! `GET /ics/:token`: the calendar file of a scheduled meeting, linked
+//! from its announcement.
+
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use tracing::{info, warn};
+
+use crate::database::models::Meeting;
+use crate::{error::AppError, ics, AppState};
+
+/// Answers with the file of the meeting the link was signed for. Tampered
+/// and malformed links get a 401, and links to meetings since deleted a
+/// 404.
+pub async fn download(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<Response, AppError> {
+    let (meeting_id, starts_at) = ics::verify_token(&state.config.slack.signing_secret, &token)
+        .map_err(|e| {
+            warn!("Refused calendar file download: {}", e);
+            AppError::Unauthorized
+        })?;
+    let Some(meeting) = state.db.get_meeting(meeting_id).await? else {
+        return Err(AppError::NotFound);
+    };
+
+    let organizer = organizer_email(&state, &meeting).await;
+    let mut event = ics::Event::new(
+        &meeting,
+        starts_at,
+        state.config.google.public_base_url(),
+        state.clock.now(),
+    );
+    event.organizer = organizer.as_deref();
+    info!("Served the calendar file of meeting {}", meeting_id);
+    Ok((
+        [
+            (header::CONTENT_TYPE, ics::CONTENT_TYPE.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", ics::filename(meeting_id)),
+            ),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+        event.to_ics(),
+    )
+        .into_response())
+}
+
+/// The Slack email of whoever created `meeting`. Without the bot installed
+/// in their workspace, or with Slack keeping it to itself, the file has no
+/// organizer.
+async fn organizer_email(state: &AppState, meeting: &Meeting) -> Option<String> {
+    let team_id = meeting.slack_team_id.as_deref()?;
+    let lookup = async {
+        let Some(team) = state.db.get_slack_team(team_id).await? else {
+            return anyhow::Ok(None);
+        };
+        let Some(user) = state.db.get_user(meeting.user_id).await? else {
+            return Ok(None);
+        };
+        Ok(state
+            .slack
+            .user_email(&team.bot_token, &user.slack_user_id)
+            .await?)
+    };
+    lookup.await.unwrap_or_else(|e| {
+        warn!(
+            "Failed to look up the organizer of meeting {:?}: {:#}",
+            meeting.id, e
+        );
+        None
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::{connected_user, test_state, Fixture, TestState};
+    use crate::database::models::MeetLinkKind;
+
+    use axum::body::to_bytes;
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn test_download_serves_the_meetings_file() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        slack.set_email("U012AB3CD", "alice@example.com");
+        let user = connected_user(&state).await;
+        let meeting = state
+            .db
+            .create_meeting(
+                &Meeting::new(
+                    user.id,
+                    "https://meet.google.com/abc-defg-hij".to_string(),
+                    Some("Partner sync".to_string()),
+                    MeetLinkKind::Meet,
+                )
+                .with_team("T012AB3C4"),
+            )
+            .await
+            .unwrap();
+        let meeting_id = meeting.id.unwrap();
+        let starts_at = "2024-03-05T07:00:00Z".parse().unwrap();
+        let token = ics::sign_token(&state.config.slack.signing_secret, meeting_id, starts_at);
+
+        let response = download(State(state), Path(token)).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], ics::CONTENT_TYPE);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            format!("attachment; filename=\"meeting-{}.ics\"", meeting_id)
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("\r\nSUMMARY:Partner sync\r\n"), "{}", body);
+        assert!(
+            body.contains("\r\nDTSTART:20240305T070000Z\r\n"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("\r\nORGANIZER:mailto:alice@example.com\r\n"),
+            "{}",
+            body
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_refuses_tampered_links_and_unknown_meetings() {
+        let (state, _pool) = test_state().await;
+        let secret = &state.config.slack.signing_secret;
+        let starts_at = "2024-03-05T07:00:00Z".parse().unwrap();
+        let tampered = ics::sign_token(secret, 7, starts_at).replacen('7', "8", 1);
+
+        let error = download(State(state.clone()), Path(tampered))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, AppError::Unauthorized), "{:?}", error);
+
+        let unknown = ics::sign_token(secret, 7, starts_at);
+        let error = download(State(state), Path(unknown)).await.unwrap_err();
+        assert!(matches!(error, AppError::NotFound), "{:?}", error);
+    }
+}
@@ -0,0 +1,445 @@
+//! Installing the app into a workspace with Slack's OAuth flow, which is
+//! where each workspace's bot token comes from.
+
+use askama::Template;
+use axum::{
+    extract::{Query, State},
+    response::{Html, Redirect},
+};
+use serde::Deserialize;
+use std::fmt;
+use tracing::{error, info, instrument, warn};
+
+use super::auth::{create_error_page, hash_oauth_state, oauth_nonce, render_page, OAUTH_STATE_TTL};
+use crate::{
+    audit::{self, AuditEvent},
+    config::SlackInstallConfig,
+    database::models::SlackTeam,
+    error::{AppError, RateLimit},
+    secret::redact,
+    AppState,
+};
+
+pub const INSTALL_PATH: &str = "/slack/install";
+pub const CALLBACK_PATH: &str = "/slack/oauth/callback";
+
+const AUTHORIZE_URL: &str = "https://slack.com/oauth/v2/authorize";
+
+/// What the bot's token is used for: slash commands, posting and
+/// scheduling messages, uploading QR codes and exports, reading people's
+/// timezones and emails, and hearing mentions.
+pub const BOT_SCOPES: &[&str] = &[
+    "commands",
+    "chat:write",
+    "files:write",
+    "users:read",
+    "users:read.email",
+    "app_mentions:read",
+];
+
+/// Who install states are recorded as issued to. Nobody is signed in when
+/// an install starts, and no Slack user id looks like this.
+const INSTALL_STATE_OWNER: &str = "install";
+
+/// Query string Slack redirects back with: `code` and `state`, or `error`
+/// when the installer cancelled.
+#[derive(Deserialize)]
+pub struct InstallCallbackQuery {
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub error: Option<String>,
+}
+
+// Like the Google callback's, `code` and `state` stay out of spans
+impl fmt::Debug for InstallCallbackQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstallCallbackQuery")
+            .field("code", &self.code.as_deref().map(redact))
+            .field("state", &self.state.as_deref().map(redact))
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "slack_installed.html")]
+struct InstalledPage<'a> {
+    team: &'a str,
+}
+
+/// Sends whoever opens the install page to Slack to pick a workspace and
+/// approve [`BOT_SCOPES`].
+#[instrument(skip(state))]
+pub async fn begin(State(state): State<AppState>) -> Result<Redirect, AppError> {
+    let install = install_config(&state)?;
+
+    if let Err(e) = state.rate_limiter.check_endpoint_limit(INSTALL_PATH).await {
+        error!("Global rate limit exceeded for Slack installs: {}", e);
+        return Err(AppError::RateLimited(RateLimit::Global));
+    }
+
+    let oauth_state = format!("{}:{}", INSTALL_STATE_OWNER, oauth_nonce());
+    state
+        .db
+        .record_oauth_state(
+            &hash_oauth_state(&oauth_state),
+            INSTALL_STATE_OWNER,
+            state.clock.now().naive_utc(),
+        )
+        .await?;
+
+    let authorize_url = url::Url::parse_with_params(
+        AUTHORIZE_URL,
+        [
+            ("client_id", install.client_id.as_str()),
+            ("scope", &BOT_SCOPES.join(",")),
+            ("redirect_uri", &redirect_uri(&state)),
+            ("state", &oauth_state),
+        ],
+    )
+    .map_err(anyhow::Error::from)?;
+
+    info!("Redirecting to Slack to install the app");
+    Ok(Redirect::temporary(authorize_url.as_str()))
+}
+
+/// Stores the bot token of the workspace Slack sent the installer back
+/// from.
+#[instrument(skip(state))]
+pub async fn finish(
+    State(state): State<AppState>,
+    Query(query): Query<InstallCallbackQuery>,
+) -> Result<Html<String>, AppError> {
+    let install = install_config(&state)?;
+
+    if let Some(error) = &query.error {
+        warn!("Slack returned an install error: {}", error);
+        return Ok(Html(create_error_page("The app wasn't installed.")));
+    }
+
+    let (Some(code), Some(oauth_state)) = (query.code, query.state) else {
+        warn!("Install callback is missing code or state");
+        return Ok(Html(create_error_page(
+            "The installation response was incomplete.",
+        )));
+    };
+
+    if let Err(e) = state.validator.validate_oauth_code(&code) {
+        warn!("Invalid install code: {}", e);
+        return Ok(Html(create_error_page(&e.to_string())));
+    }
+
+    if let Err(e) = state.rate_limiter.check_endpoint_limit(CALLBACK_PATH).await {
+        error!("Global rate limit exceeded for the install callback: {}", e);
+        return Ok(Html(create_error_page(
+            "Service temporarily unavailable. Please try again later.",
+        )));
+    }
+
+    // As with Google sign-ins, the state must be one handed out here,
+    // recently, and not used before
+    match state
+        .db
+        .take_oauth_state(&hash_oauth_state(&oauth_state))
+        .await
+    {
+        Ok(Some((issued_to, issued_at)))
+            if issued_to == INSTALL_STATE_OWNER
+                && state.clock.now().naive_utc() - issued_at <= OAUTH_STATE_TTL => {}
+        Ok(_) => {
+            warn!("Install state was not issued here, was used already or expired");
+            return Ok(Html(create_error_page(&format!(
+                "This install link has expired or was already used. Open {} again.",
+                INSTALL_PATH
+            ))));
+        }
+        Err(e) => {
+            error!("Failed to look up install state: {}", e);
+            return Ok(Html(create_error_page("Database error")));
+        }
+    }
+
+    let installation = match state
+        .slack
+        .oauth_access(
+            &install.client_id,
+            &install.client_secret,
+            &code,
+            &redirect_uri(&state),
+        )
+        .await
+    {
+        Ok(installation) => installation,
+        Err(e) => {
+            error!("Slack rejected the install code exchange: {}", e);
+            return Ok(Html(create_error_page(
+                "Slack could not complete the installation.",
+            )));
+        }
+    };
+
+    if let Err(e) = state
+        .validator
+        .validate_slack_team_id(&installation.team_id)
+    {
+        error!("Slack installed the app into an odd team id: {}", e);
+        return Ok(Html(create_error_page(
+            "Slack could not complete the installation.",
+        )));
+    }
+
+    // The webhook is stored and posted to later, so it has to be Slack's
+    if let Some(url) = &installation.incoming_webhook_url {
+        if let Err(e) = state.validator.validate_url(url.expose()) {
+            error!(
+                "Slack handed out an incoming webhook we won't post to: {}",
+                e
+            );
+            return Ok(Html(create_error_page(
+                "Slack could not complete the installation.",
+            )));
+        }
+    }
+
+    let team = SlackTeam {
+        team_name: installation.team_name.clone(),
+        bot_user_id: installation.bot_user_id.clone(),
+        incoming_webhook_url: installation.incoming_webhook_url.clone(),
+        ..SlackTeam::new(installation.team_id.clone(), installation.bot_token.clone())
+    };
+    if let Err(e) = state.db.store_slack_team(&team).await {
+        error!("Failed to store the bot token: {}", e);
+        return Ok(Html(create_error_page("Failed to store the installation")));
+    }
+
+    info!("Installed into team {}", installation.team_id);
+    audit::record(
+        &state.db,
+        AuditEvent::slack_installed(
+            installation.installed_by.as_deref(),
+            &installation.team_id,
+            installation.scope.as_deref(),
+        ),
+    )
+    .await;
+    Ok(Html(render_page(&InstalledPage {
+        team: installation
+            .team_name
+            .as_deref()
+            .unwrap_or("your workspace"),
+    })))
+}
+
+fn install_config(state: &AppState) -> Result<&SlackInstallConfig, AppError> {
+    state
+        .config
+        .slack
+        .install
+        .as_ref()
+        .ok_or(AppError::NotFound)
+}
+
+/// Where Slack sends installers back to; the same on both legs of the
+/// flow, as Slack checks.
+fn redirect_uri(state: &AppState) -> String {
+    format!("{}{}", state.config.google.public_base_url(), CALLBACK_PATH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::Fixture;
+    use crate::config::Config;
+    use crate::slack::api::Installation;
+    use crate::slack::fake::FakeSlackApi;
+    use axum::response::IntoResponse;
+    use std::sync::Arc;
+
+    fn installation() -> Installation {
+        Installation {
+            team_id: "T012AB3C4".to_string(),
+            team_name: Some("Acme <Corp>".to_string()),
+            bot_user_id: Some("U0BOTUSER1".to_string()),
+            bot_token: "xoxb-installed".into(),
+            scope: Some(BOT_SCOPES.join(",")),
+            installed_by: Some("U012AB3CD".to_string()),
+            incoming_webhook_url: None,
+        }
+    }
+
+    /// The `state` of the Slack URL `begin` redirects to.
+    async fn started(state: &AppState) -> String {
+        let redirect = begin(State(state.clone())).await.unwrap();
+        let location = redirect.into_response().headers()["location"]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let url = url::Url::parse(&location).unwrap();
+        assert_eq!(url.host_str(), Some("slack.com"));
+        let param = |name: &str| {
+            url.query_pairs()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.into_owned())
+                .unwrap()
+        };
+        assert_eq!(param("client_id"), "slack-client-id");
+        assert_eq!(param("scope"), BOT_SCOPES.join(","));
+        assert_eq!(
+            param("redirect_uri"),
+            "https://bot.example.com/slack/oauth/callback"
+        );
+        param("state")
+    }
+
+    async fn callback(state: &AppState, code: &str, oauth_state: &str) -> String {
+        finish(
+            State(state.clone()),
+            Query(InstallCallbackQuery {
+                code: Some(code.to_string()),
+                state: Some(oauth_state.to_string()),
+                error: None,
+            }),
+        )
+        .await
+        .unwrap()
+        .0
+    }
+
+    #[tokio::test]
+    async fn test_install_stores_the_bot_token() {
+        let slack = FakeSlackApi::new();
+        slack.set_install("1234.5678.abcd", installation());
+        let state = Fixture::new().slack(Arc::new(slack)).build().await.state;
+
+        let oauth_state = started(&state).await;
+        let page = callback(&state, "1234.5678.abcd", &oauth_state).await;
+
+        assert!(page.contains("Installed"), "{}", page);
+        assert!(page.contains("Acme &lt;Corp&gt;"), "{}", page);
+        let team = state.db.get_slack_team("T012AB3C4").await.unwrap().unwrap();
+        assert_eq!(team.bot_token.expose(), "xoxb-installed");
+        assert_eq!(team.bot_user_id.as_deref(), Some("U0BOTUSER1"));
+        let audit = state.db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit[0].event_type, "slack_installed");
+        assert_eq!(audit[0].actor_slack_id.as_deref(), Some("U012AB3CD"));
+    }
+
+    #[tokio::test]
+    async fn test_install_states_are_single_use_and_unforgeable() {
+        let slack = FakeSlackApi::new();
+        slack.set_install("1234.5678.abcd", installation());
+        slack.set_install("1234.5678.efgh", installation());
+        let state = Fixture::new().slack(Arc::new(slack)).build().await.state;
+
+        let forged = format!("{}:{}", INSTALL_STATE_OWNER, oauth_nonce());
+        let page = callback(&state, "1234.5678.abcd", &forged).await;
+        assert!(page.contains("expired or was already used"), "{}", page);
+
+        let oauth_state = started(&state).await;
+        callback(&state, "1234.5678.abcd", &oauth_state).await;
+        state
+            .db
+            .store_slack_team(&SlackTeam::new("T012AB3C4".to_string(), "xoxb-kept".into()))
+            .await
+            .unwrap();
+        let page = callback(&state, "1234.5678.efgh", &oauth_state).await;
+        assert!(page.contains("expired or was already used"), "{}", page);
+
+        let team = state.db.get_slack_team("T012AB3C4").await.unwrap().unwrap();
+        assert_eq!(team.bot_token.expose(), "xoxb-kept");
+    }
+
+    #[tokio::test]
+    async fn test_sign_in_states_dont_install() {
+        let slack = FakeSlackApi::new();
+        slack.set_install("1234.5678.abcd", installation());
+        let state = Fixture::new().slack(Arc::new(slack)).build().await.state;
+        let sign_in = super::super::auth::generate_oauth_state("U012AB3CD");
+        state
+            .db
+            .record_oauth_state(
+                &hash_oauth_state(&sign_in),
+                "U012AB3CD",
+                state.clock.now().naive_utc(),
+            )
+            .await
+            .unwrap();
+
+        let page = callback(&state, "1234.5678.abcd", &sign_in).await;
+
+        assert!(page.contains("expired or was already used"), "{}", page);
+        assert!(state
+            .db
+            .get_slack_team("T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_incoming_webhooks_must_be_on_an_allowed_host() {
+        let slack = FakeSlackApi::new();
+        slack.set_install(
+            "1234.5678.abcd",
+            Installation {
+                incoming_webhook_url: Some("https://hooks.slack.com/services/T0/B0/x".into()),
+                ..installation()
+            },
+        );
+        slack.set_install(
+            "1234.5678.efgh",
+            Installation {
+                team_id: "T0OTHER0001".to_string(),
+                incoming_webhook_url: Some("https://hooks.slack.com.evil.example/x".into()),
+                ..installation()
+            },
+        );
+        let state = Fixture::new().slack(Arc::new(slack)).build().await.state;
+
+        let oauth_state = started(&state).await;
+        callback(&state, "1234.5678.abcd", &oauth_state).await;
+        let team = state.db.get_slack_team("T012AB3C4").await.unwrap().unwrap();
+        assert_eq!(
+            team.incoming_webhook_url.unwrap().expose(),
+            "https://hooks.slack.com/services/T0/B0/x"
+        );
+
+        let oauth_state = started(&state).await;
+        let page = callback(&state, "1234.5678.efgh", &oauth_state).await;
+        assert!(page.contains("could not complete"), "{}", page);
+        assert!(state
+            .db
+            .get_slack_team("T0OTHER0001")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejected_codes_store_nothing() {
+        let state = Fixture::new().build().await.state;
+
+        let oauth_state = started(&state).await;
+        let page = callback(&state, "1234.5678.wrong", &oauth_state).await;
+
+        assert!(page.contains("could not complete"), "{}", page);
+        assert!(state
+            .db
+            .get_slack_team("T012AB3C4")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_no_install_pages_without_client_credentials() {
+        let mut config = Config::for_tests();
+        config.slack.install = None;
+        let state = Fixture::new().config(config).build().await.state;
+
+        assert!(matches!(
+            begin(State(state.clone())).await,
+            Err(AppError::NotFound)
+        ));
+    }
+}
@@ -0,0 +1,265 @@
+use axum::{extract::State, http::StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{info, instrument, warn, Instrument};
+
+use crate::error::AppError;
+use crate::handlers::slack::{run_detached, SlashCommandPayload};
+use crate::polls;
+use crate::slack::commands as slack_commands;
+use crate::slack::VerifiedSlackBody;
+use crate::working_hours;
+use crate::AppState;
+
+/// Callback id of the message shortcut creating a meeting, as set up in the
+/// Slack app.
+pub const CREATE_MEETING_SHORTCUT: &str = "create_meeting";
+
+#[derive(Debug, Deserialize)]
+struct InteractionForm {
+    payload: String,
+}
+
+/// A message shortcut, run from a message's menu.
+#[derive(Debug, Deserialize)]
+struct MessageAction {
+    callback_id: String,
+    trigger_id: String,
+    response_url: String,
+    team: Team,
+    enterprise: Option<Enterprise>,
+    channel: Channel,
+    user: ActionUser,
+    message: ShortcutMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct Team {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Enterprise {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Channel {
+    id: String,
+    #[serde(default)]
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActionUser {
+    id: String,
+    #[serde(default, alias = "username")]
+    name: String,
+}
+
+/// Clicks on buttons of the bot's messages. Link buttons are clicked too,
+/// but have no value for the bot to act on.
+#[derive(Debug, Deserialize)]
+struct BlockActions {
+    trigger_id: String,
+    #[serde(default)]
+    response_url: String,
+    team: Team,
+    enterprise: Option<Enterprise>,
+    channel: Channel,
+    user: ActionUser,
+    actions: Vec<BlockAction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockAction {
+    action_id: String,
+    value: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShortcutMessage {
+    /// Set when the message is in a thread, or starts one.
+    thread_ts: Option<String>,
+}
+
+impl MessageAction {
+    /// The shortcut as `/meet` without text, replying in the message's
+    /// thread if it is in one.
+    fn into_payload(self, command: &str) -> SlashCommandPayload {
+        SlashCommandPayload {
+            team_id: self.team.id,
+            enterprise_id: self.enterprise.map(|e| e.id),
+            channel_id: self.channel.id,
+            channel_name: self.channel.name,
+            user_id: self.user.id,
+            user_name: self.user.name,
+            command: command.to_string(),
+            text: None,
+            response_url: self.response_url,
+            trigger_id: self.trigger_id,
+            thread_ts: self.message.thread_ts,
+        }
+    }
+}
+
+impl BlockActions {
+    /// The click as `/meet` without text run by whoever clicked, answering
+    /// to the message's response URL.
+    fn into_payload(self, command: &str) -> (SlashCommandPayload, Vec<BlockAction>) {
+        let payload = SlashCommandPayload {
+            team_id: self.team.id,
+            enterprise_id: self.enterprise.map(|e| e.id),
+            channel_id: self.channel.id,
+            channel_name: self.channel.name,
+            user_id: self.user.id,
+            user_name: self.user.name,
+            command: command.to_string(),
+            text: None,
+            response_url: self.response_url,
+            trigger_id: self.trigger_id,
+            thread_ts: None,
+        };
+        (payload, self.actions)
+    }
+}
+
+/// Entry point for Slack interactivity (buttons, shortcuts, modals).
+///
+/// Slack posts the interaction as a JSON document in the `payload` form field
+/// and only needs a fast 200 to consider it delivered. The
+/// [meeting shortcut](CREATE_MEETING_SHORTCUT) creates a meeting in the
+/// background, announced in the message's thread when it has one. Clicks
+/// on a poll's buttons are [counted](polls::handle_click), and a meeting
+/// outside working hours is
+/// [created once confirmed](working_hours::schedule_anyway), in the
+/// background too.
+#[instrument(skip(state, verified))]
+pub async fn handle_interaction(
+    State(state): State<AppState>,
+    verified: VerifiedSlackBody,
+) -> Result<StatusCode, AppError> {
+    let form: InteractionForm = serde_urlencoded::from_str(&verified.body)
+        .map_err(|e| AppError::BadRequest(format!("unparsable interaction form: {}", e)))?;
+    let payload: Value = serde_json::from_str(&form.payload)
+        .map_err(|e| AppError::BadRequest(format!("unparsable interaction payload: {}", e)))?;
+
+    let kind = payload
+        .get("type")
+        .and_then(|t| t.as_str())
+        .unwrap_or("unknown");
+    info!("Received {} interaction", kind);
+    let command = state.config.slack.commands.name_for(slack_commands::MEET);
+    match kind {
+        "message_action" => {}
+        "block_actions" => {
+            let actions: BlockActions = serde_json::from_value(payload)
+                .map_err(|e| AppError::BadRequest(format!("unparsable block actions: {}", e)))?;
+            let (payload, actions) = actions.into_payload(command);
+            for action in actions {
+                // Link buttons have no value and need nothing done
+                let Some(value) = action.value else {
+                    continue;
+                };
+                if action.action_id == working_hours::SCHEDULE_ANYWAY_ACTION {
+                    let task =
+                        working_hours::schedule_anyway(state.clone(), payload.clone(), value);
+                    tokio::spawn(task.in_current_span());
+                } else {
+                    let task = polls::handle_click(
+                        state.clone(),
+                        payload.clone(),
+                        action.action_id,
+                        value,
+                    );
+                    tokio::spawn(task.in_current_span());
+                }
+            }
+            return Ok(StatusCode::OK);
+        }
+        _ => return Ok(StatusCode::OK),
+    }
+
+    let action: MessageAction = serde_json::from_value(payload)
+        .map_err(|e| AppError::BadRequest(format!("unparsable message shortcut: {}", e)))?;
+    if action.callback_id != CREATE_MEETING_SHORTCUT {
+        warn!("Ignoring unknown message shortcut {}", action.callback_id);
+        return Ok(StatusCode::OK);
+    }
+    let payload = action.into_payload(command);
+    run_detached(state, payload)?;
+
+    Ok(StatusCode::OK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn action(message: Value) -> MessageAction {
+        serde_json::from_value(json!({
+            "type": "message_action",
+            "callback_id": CREATE_MEETING_SHORTCUT,
+            "trigger_id": "13345224609.738474920.8088930838d88f008e0",
+            "response_url": "https://hooks.slack.com/app/T012AB3C4/1/abc",
+            "team": { "id": "T012AB3C4", "domain": "acme" },
+            "channel": { "id": "C012AB3CD", "name": "general" },
+            "user": { "id": "U012AB3CD", "name": "alice" },
+            "message": message,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_shortcut_in_a_thread_replies_there() {
+        let payload = action(json!({
+            "ts": "1709628700.000300",
+            "thread_ts": "1709628600.000200",
+            "text": "what's the bridge?",
+        }))
+        .into_payload("/meet");
+
+        assert_eq!(payload.thread_ts.as_deref(), Some("1709628600.000200"));
+        assert_eq!(payload.channel_id, "C012AB3CD");
+        assert_eq!(payload.command, "/meet");
+        assert_eq!(payload.text, None);
+    }
+
+    #[test]
+    fn test_button_clicks_run_as_whoever_clicked() {
+        let actions: BlockActions = serde_json::from_value(json!({
+            "type": "block_actions",
+            "trigger_id": "13345224609.738474920.8088930838d88f008e0",
+            "response_url": "https://hooks.slack.com/actions/T012AB3C4/1/abc",
+            "team": { "id": "T012AB3C4", "domain": "acme" },
+            "channel": { "id": "C012AB3CD", "name": "general" },
+            "user": { "id": "U098ZY7XW", "username": "bob" },
+            "actions": [
+                { "action_id": polls::VOTE_ACTION, "value": "7:1", "type": "button" },
+                { "action_id": "open_meeting", "type": "button" },
+            ],
+        }))
+        .unwrap();
+
+        let (payload, actions) = actions.into_payload("/meet");
+        assert_eq!(payload.user_id, "U098ZY7XW");
+        assert_eq!(payload.user_name, "bob");
+        assert_eq!(
+            payload.response_url,
+            "https://hooks.slack.com/actions/T012AB3C4/1/abc"
+        );
+        assert_eq!(payload.text, None);
+        assert_eq!(actions[0].action_id, polls::VOTE_ACTION);
+        assert_eq!(actions[0].value.as_deref(), Some("7:1"));
+        assert_eq!(actions[1].value, None);
+    }
+
+    #[test]
+    fn test_shortcut_outside_threads_has_no_thread() {
+        let payload =
+            action(json!({ "ts": "1709628700.000300", "text": "standup?" })).into_payload("/meet");
+
+        assert_eq!(payload.thread_ts, None);
+    }
+}
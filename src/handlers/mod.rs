@@ -1,2 +1,11 @@
+pub mod admin;
+pub mod api;
 pub mod auth;
+pub mod events;
+pub mod export;
+pub mod health;
+pub mod ics;
+pub mod install;
+pub mod interactions;
+pub mod short_links;
 pub mod slack;
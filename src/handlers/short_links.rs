@@ -0,0 +1,101 @@
+//! `GET /m/:slug`: the short links `/meet share-link` hands out, redirecting
+//! to the meeting while the link is public.
+
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use tracing::info;
+
+use crate::{error::AppError, short_links, AppState};
+
+/// Redirects to the Meet link of the meeting, counting the click. Private,
+/// unknown and malformed slugs all get the same 404, so a private link
+/// can't be told apart from none.
+pub async fn follow(
+    State(state): State<AppState>,
+    Path(slug): Path<String>,
+) -> Result<Response, AppError> {
+    if !short_links::is_well_formed(&slug) {
+        return Err(AppError::NotFound);
+    }
+    let Some(meet_link) = state.db.follow_short_link(&slug).await? else {
+        return Err(AppError::NotFound);
+    };
+
+    info!("Followed short link {}", slug);
+    Ok((
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, meet_link),
+            (header::CACHE_CONTROL, "no-store".to_string()),
+        ],
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::test_state;
+    use crate::database::models::{MeetLinkKind, Meeting};
+
+    async fn shared_meeting(state: &AppState) -> (i64, String) {
+        let user = state
+            .db
+            .create_user("U0000A001", "T012AB3C4")
+            .await
+            .unwrap();
+        let meeting = state
+            .db
+            .create_meeting(&Meeting::new(
+                user.id,
+                "https://meet.google.com/abc-defg-hij".into(),
+                None,
+                MeetLinkKind::Meet,
+            ))
+            .await
+            .unwrap();
+        let id = meeting.id.unwrap();
+        let slug = short_links::publish(&state.db, id, short_links::generate)
+            .await
+            .unwrap();
+        (id, slug)
+    }
+
+    #[tokio::test]
+    async fn test_public_links_redirect_and_count_clicks() {
+        let (state, _pool) = test_state().await;
+        let (id, slug) = shared_meeting(&state).await;
+
+        for _ in 0..2 {
+            let response = follow(State(state.clone()), Path(slug.clone()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::FOUND);
+            assert_eq!(
+                response.headers()[header::LOCATION],
+                "https://meet.google.com/abc-defg-hij"
+            );
+        }
+        let link = state.db.meeting_short_link(id).await.unwrap().unwrap();
+        assert_eq!(link.click_count, 2);
+    }
+
+    #[tokio::test]
+    async fn test_private_and_unknown_links_are_not_found() {
+        let (state, _pool) = test_state().await;
+        let (id, slug) = shared_meeting(&state).await;
+        state.db.set_short_link_public(id, false).await.unwrap();
+
+        for slug in [slug, "ZZZZZZZZZZ".to_string(), id.to_string()] {
+            let error = follow(State(state.clone()), Path(slug.clone()))
+                .await
+                .unwrap_err();
+            assert!(matches!(error, AppError::NotFound), "{}: {:?}", slug, error);
+        }
+        let link = state.db.meeting_short_link(id).await.unwrap().unwrap();
+        assert_eq!(link.click_count, 0);
+    }
+}
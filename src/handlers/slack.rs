@@ -1,69 +1,120 @@
-use axum::{
-    extract::State,
-    http::{HeaderMap, StatusCode},
-    response::Json,
-};
+use axum::{extract::State, response::Json};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tracing::{error, info, instrument, warn};
+use std::time::Instant;
+use tracing::{error, info, instrument, warn, Instrument};
 
-use crate::auth::oauth::{is_token_valid, refresh_token_if_needed};
-use crate::handlers::auth::create_oauth_client;
-use crate::utils::{verify_slack_request, SlackVerificationError};
-use crate::validation::InputValidator;
+use crate::announcement::AnnouncementStyle;
+use crate::command_parser;
+use crate::database::models::{MeetLinkKind, Meeting, MeetingVisibility};
+use crate::error::{AppError, RateLimit, INTERNAL_ERROR_TEXT};
+use crate::google::calendar;
+use crate::http_client;
+use crate::observability::{self, Phase};
+use crate::recording;
+use crate::reminders;
+use crate::request_id::RequestId;
+use crate::slack::api::{ChatMessage, ThreadReply};
+use crate::slack::blocks::{self, Block, BlockError, Button, ButtonStyle, Text};
+use crate::slack::commands as slack_commands;
+use crate::slack::VerifiedSlackBody;
+use crate::stats::UsageStats;
+use crate::telemetry::{error_reporting, metrics, otel};
 use crate::AppState;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize)]
 pub struct SlashCommandPayload {
-    pub token: String,
     pub team_id: String,
-    pub team_domain: String,
+    /// Set when the workspace is part of an Enterprise Grid organization.
+    pub enterprise_id: Option<String>,
     pub channel_id: String,
     pub channel_name: String,
     pub user_id: String,
     pub user_name: String,
     pub command: String,
     pub text: Option<String>,
+    /// Empty for commands from a mention, which come without one.
     pub response_url: String,
     pub trigger_id: String,
+    /// The thread replies go to. Slash commands can't know the thread they
+    /// were typed in, so only message shortcuts and mentions set it.
+    #[serde(skip)]
+    pub thread_ts: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct SlackResponse {
     pub response_type: String,
+    /// The whole message without blocks; with them, what notifications
+    /// and clients that can't show blocks fall back to.
     pub text: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub attachments: Option<Vec<SlackAttachment>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub blocks: Option<Vec<Value>>,
+    pub blocks: Option<Vec<Block>>,
+    /// Whether the message replaces the one whose button was clicked, when
+    /// sent to the response URL of an interaction.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub replace_original: bool,
 }
 
-#[derive(Debug, Serialize)]
-pub struct SlackAttachment {
-    pub color: String,
-    pub title: String,
-    pub text: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub actions: Option<Vec<SlackAction>>,
-}
+/// The rate limits every `/meet` run is counted against, wherever it came
+/// from.
+const COMMANDS_ENDPOINT: &str = "/slack/commands";
 
-#[derive(Debug, Serialize)]
-pub struct SlackAction {
-    pub name: String,
-    pub text: String,
-    #[serde(rename = "type")]
-    pub action_type: String,
-    pub url: String,
-    pub style: String,
+/// Action id of the button sending a user to connect Google.
+pub const CONNECT_GOOGLE_ACTION: &str = "connect_google";
+
+/// Said under a quiet meeting's link, which only its creator sees.
+const QUIET_NOTE: &str = "🤫 Only you can see this link. Share it with whoever should join.";
+
+/// Starts a new meeting at Google in one click, offered next to stored
+/// links that have probably stopped working.
+pub const FRESH_MEETING_URL: &str = "https://meet.google.com/new";
+
+/// Action id of the button opening a new Google Calendar event for a
+/// scheduled meeting.
+pub const ADD_TO_CALENDAR_ACTION: &str = "add_to_calendar";
+
+/// Action id of the button downloading a scheduled meeting's calendar
+/// file, for calendars other than Google's.
+pub const DOWNLOAD_ICS_ACTION: &str = "download_ics";
+
+/// Action id of the button starting a fresh meeting.
+pub const FRESH_MEETING_ACTION: &str = "fresh_meeting";
+
+/// A stored meeting's link as shown at `now`, with `label` after it. A link
+/// past its meeting's end is flagged and comes with a button starting a
+/// fresh meeting.
+pub fn stored_meeting_link(
+    meeting: &Meeting,
+    label: &str,
+    now: DateTime<Utc>,
+) -> (String, Option<Button>) {
+    let title = meeting.title.as_deref().unwrap_or("Untitled meeting");
+    let link = format!("<{}|{}>{}", meeting.meet_link, blocks::title(title), label);
+    if !meeting.may_have_expired_at(now) {
+        return (link, None);
+    }
+    (
+        format!("{} (may have expired)", link),
+        Some(Button::link(
+            FRESH_MEETING_ACTION,
+            "Create a fresh one",
+            FRESH_MEETING_URL,
+        )),
+    )
 }
 
 impl SlackResponse {
+    pub fn builder() -> SlackResponseBuilder {
+        SlackResponseBuilder::default()
+    }
+
     pub fn ephemeral(text: String) -> Self {
         Self {
             response_type: "ephemeral".to_string(),
             text,
-            attachments: None,
             blocks: None,
+            replace_original: false,
         }
     }
 
@@ -71,336 +122,925 @@ impl SlackResponse {
         Self {
             response_type: "in_channel".to_string(),
             text,
-            attachments: None,
             blocks: None,
+            replace_original: false,
         }
     }
 
     pub fn with_auth_prompt(auth_url: String) -> Self {
-        let attachment = SlackAttachment {
-            color: "warning".to_string(),
-            title: "Authentication Required".to_string(),
-            text: "You need to authenticate with Google to create Meet links.".to_string(),
-            actions: Some(vec![SlackAction {
-                name: "auth".to_string(),
-                text: "Authenticate with Google".to_string(),
-                action_type: "button".to_string(),
-                url: auth_url,
-                style: "primary".to_string(),
-            }]),
-        };
+        Self::builder()
+            .ephemeral()
+            .text("🔐 Authentication needed to create Google Meet links")
+            .block(Block::section(Text::mrkdwn(
+                "*Authentication required*\nYou need to authenticate with Google to create Meet links.",
+            )))
+            .block(Block::actions(vec![Button::link(
+                CONNECT_GOOGLE_ACTION,
+                "Authenticate with Google",
+                auth_url,
+            )
+            .style(ButtonStyle::Primary)]))
+            .build_or_text()
+    }
 
-        Self {
-            response_type: "ephemeral".to_string(),
-            text: "🔐 Authentication needed to create Google Meet links".to_string(),
-            attachments: Some(vec![attachment]),
-            blocks: None,
-        }
+    /// Whether this asks the user to connect their Google account.
+    pub fn is_auth_prompt(&self) -> bool {
+        self.blocks.iter().flatten().any(|block| {
+            block
+                .buttons()
+                .iter()
+                .any(|button| button.action_id == CONNECT_GOOGLE_ACTION)
+        })
     }
-}
 
-#[instrument(skip(state, headers, body))]
-pub async fn handle_slash_command(
-    State(state): State<AppState>,
-    headers: HeaderMap,
-    body: String,
-) -> Result<Json<SlackResponse>, StatusCode> {
-    info!("Received slash command");
-
-    let signature = headers
-        .get("x-slack-signature")
-        .and_then(|v| v.to_str().ok())
-        .ok_or_else(|| {
-            warn!("Missing or invalid X-Slack-Signature header");
-            StatusCode::UNAUTHORIZED
-        })?;
-
-    let timestamp = headers
-        .get("x-slack-request-timestamp")
-        .and_then(|v| v.to_str().ok())
-        .ok_or_else(|| {
-            warn!("Missing or invalid X-Slack-Request-Timestamp header");
-            StatusCode::UNAUTHORIZED
-        })?;
-
-    if let Err(e) = verify_slack_request(&state.slack_signing_secret, signature, timestamp, &body) {
-        match e {
-            SlackVerificationError::RequestTooOld => {
-                warn!("Slack request verification failed: request too old");
-                return Err(StatusCode::UNAUTHORIZED);
-            }
-            SlackVerificationError::SignatureMismatch => {
-                warn!("Slack request verification failed: signature mismatch");
-                return Err(StatusCode::UNAUTHORIZED);
+    /// Shares a new meeting in the channel in the workspace's `style`,
+    /// crediting `creator`; a quiet meeting is shown to its creator alone.
+    pub fn meeting_created(creator: &str, meeting: &Meeting, style: &AnnouncementStyle) -> Self {
+        Self::meeting_announcement(creator, meeting, style, None, None, Vec::new())
+    }
+
+    /// Shares a meeting starting at `starts_at` in the channel, saying when
+    /// its reminder will be posted if one was scheduled `reminder` ahead,
+    /// with a button adding it to Google Calendar and, given `ics_url`, one
+    /// downloading its calendar file for other calendars. `local_times` is
+    /// the start in the timezones of the people it's for, as
+    /// [`crate::timezones::start_times`] tells it.
+    pub fn meeting_scheduled(
+        creator: &str,
+        meeting: &Meeting,
+        style: &AnnouncementStyle,
+        starts_at: DateTime<Utc>,
+        reminder: Option<chrono::Duration>,
+        ics_url: Option<&str>,
+        local_times: Option<&str>,
+    ) -> Self {
+        let mut when = format!("🕘 Starts {}.", blocks::date(starts_at));
+        if let Some(lead) = reminder {
+            when.push_str(&format!(
+                " I'll post a reminder here {} before.",
+                reminders::describe_lead(lead)
+            ));
+        }
+        // A calendar link is already an event to add
+        let mut calendar_buttons = Vec::new();
+        if meeting.link_kind == MeetLinkKind::Meet {
+            calendar_buttons.push(Button::link(
+                ADD_TO_CALENDAR_ACTION,
+                "Add to calendar",
+                calendar::add_to_calendar_link(
+                    meeting.title.as_deref(),
+                    &meeting.meet_link,
+                    starts_at,
+                    meeting.ends_at.map(|ends_at| ends_at.and_utc()),
+                ),
+            ));
+            if let Some(ics_url) = ics_url {
+                calendar_buttons.push(Button::link(DOWNLOAD_ICS_ACTION, "Download .ics", ics_url));
             }
-            _ => {
-                error!("Slack request verification failed: {}", e);
-                return Err(StatusCode::BAD_REQUEST);
+        }
+        Self::meeting_announcement(
+            creator,
+            meeting,
+            style,
+            Some(when),
+            local_times,
+            calendar_buttons,
+        )
+    }
+
+    fn meeting_announcement(
+        creator: &str,
+        meeting: &Meeting,
+        style: &AnnouncementStyle,
+        when: Option<String>,
+        local_times: Option<&str>,
+        calendar_buttons: Vec<Button>,
+    ) -> Self {
+        let (headline, button) = match meeting.link_kind {
+            MeetLinkKind::Meet => (style.headline(creator), "Join meeting"),
+            // Says what went wrong, so it keeps its own wording
+            MeetLinkKind::Calendar => {
+                let created = if style.mention_creator {
+                    format!("📅 Calendar event created by <@{}>", creator)
+                } else {
+                    "📅 Calendar event created".to_string()
+                };
+                (
+                    format!("{} (no Meet link was attached)", created),
+                    "Open event",
+                )
             }
+        };
+        let text = format!("{}: {}", headline, meeting.meet_link);
+        let headline = match &meeting.title {
+            Some(title) => format!("*{}*\n{}", blocks::title(title), headline),
+            None => headline,
+        };
+        let (text, headline) = match when {
+            Some(when) => (
+                format!("{}\n{}", text, when),
+                format!("{}\n{}", headline, when),
+            ),
+            None => (text, headline),
+        };
+        let (text, headline) = match recording::notice(meeting.artifacts()) {
+            Some(notice) => (
+                format!("{}\n{}", text, notice),
+                format!("{}\n{}", headline, notice),
+            ),
+            None => (text, headline),
+        };
+
+        let mut builder = Self::builder().text(text).block(Block::section_with_button(
+            Text::mrkdwn(headline),
+            Button::link("open_meeting", button, &meeting.meet_link).style(ButtonStyle::Primary),
+        ));
+        if let Some(local_times) = local_times {
+            builder = builder.block(Block::context(vec![Text::mrkdwn(format!(
+                "🌍 {}",
+                local_times
+            ))]));
+        }
+        if !calendar_buttons.is_empty() {
+            builder = builder.block(Block::actions(calendar_buttons));
+        }
+        match meeting.visibility {
+            MeetingVisibility::Channel => builder
+                .in_channel()
+                .block(Block::context(vec![Text::mrkdwn(&meeting.meet_link)])),
+            MeetingVisibility::Quiet => builder.ephemeral().block(Block::context(vec![
+                Text::mrkdwn(&meeting.meet_link),
+                Text::mrkdwn(QUIET_NOTE),
+            ])),
         }
+        .build_or_text()
     }
 
-    info!("Slack signature verification successful");
+    /// A user's recent meetings, as given, for `/meet list` at `now`.
+    pub fn meeting_list(meetings: &[Meeting], now: DateTime<Utc>) -> Self {
+        if meetings.is_empty() {
+            return Self::ephemeral("You haven't created any meetings yet.".to_string());
+        }
 
-    let payload: SlashCommandPayload = serde_urlencoded::from_str(&body).map_err(|e| {
-        error!("Failed to parse form data: {}", e);
-        StatusCode::BAD_REQUEST
-    })?;
+        let mut lines = Vec::new();
+        let mut builder = Self::builder()
+            .ephemeral()
+            .block(Block::section(Text::mrkdwn("*Your recent meetings*")));
+        for meeting in meetings {
+            let label = match (meeting.link_kind, meeting.visibility) {
+                (MeetLinkKind::Meet, MeetingVisibility::Channel) => "",
+                (MeetLinkKind::Meet, MeetingVisibility::Quiet) => " (quiet)",
+                (MeetLinkKind::Calendar, MeetingVisibility::Channel) => " (calendar event only)",
+                (MeetLinkKind::Calendar, MeetingVisibility::Quiet) => {
+                    " (quiet, calendar event only)"
+                }
+            };
+            let created_at = meeting
+                .created_at
+                .map(|created_at| created_at.format("%Y-%m-%d %H:%M UTC").to_string());
 
-    let validator = InputValidator::new();
+            let (link, fresh_meeting) = stored_meeting_link(meeting, label, now);
+            lines.push(match &created_at {
+                Some(created_at) => format!("• {} — {}", link, created_at),
+                None => format!("• {}", link),
+            });
+            let text = Text::mrkdwn(match &created_at {
+                Some(created_at) => format!("{}\n{}", link, created_at),
+                None => link,
+            });
+            builder = builder.block(match fresh_meeting {
+                Some(button) => Block::section_with_button(text, button),
+                None => Block::section(text),
+            });
+        }
 
-    if let Err(e) = validator.validate_slack_command(&payload.command) {
-        warn!("Invalid command: {}", e);
-        return Ok(Json(SlackResponse::ephemeral(
-            "❌ Invalid command".to_string(),
-        )));
+        builder
+            .text(format!("Your recent meetings:\n{}", lines.join("\n")))
+            .build_or_text()
     }
 
-    if let Err(e) = validator.validate_slack_user_id(&payload.user_id) {
-        warn!("Invalid user ID: {}", e);
-        return Err(StatusCode::BAD_REQUEST);
+    /// The latest meeting of a channel for `/meet join` at `now`, shown to
+    /// the user asking, or posted in the channel crediting `shared_by`.
+    pub fn channel_meeting(meeting: &Meeting, now: DateTime<Utc>, shared_by: Option<&str>) -> Self {
+        let (link, fresh_meeting) = stored_meeting_link(meeting, "", now);
+        let mut text = match shared_by {
+            Some(user) => format!(
+                "🔗 <@{}> shared this channel's latest meeting: {}",
+                user, link
+            ),
+            None => format!("🔗 This channel's latest meeting: {}", link),
+        };
+        if let Some(created_at) = meeting.created_at {
+            text.push_str(&format!("\nCreated {}", blocks::date(created_at.and_utc())));
+        }
+
+        let builder = Self::builder().text(text.clone());
+        let builder = match shared_by {
+            Some(_) => builder.in_channel(),
+            None => builder.ephemeral(),
+        };
+        builder
+            .block(match fresh_meeting {
+                Some(button) => Block::section_with_button(Text::mrkdwn(text), button),
+                None => Block::section(Text::mrkdwn(text)),
+            })
+            .build_or_text()
     }
 
-    if let Err(e) = validator.validate_slack_team_id(&payload.team_id) {
-        warn!("Invalid team ID: {}", e);
-        return Err(StatusCode::BAD_REQUEST);
+    /// A user's meeting stats, for `/meet stats` run as `command`.
+    pub fn usage_stats(stats: &UsageStats, command: &str) -> Self {
+        if stats.counts.all_time == 0 {
+            return Self::ephemeral(format!(
+                "You haven't created any meetings yet, so there are no stats to show. Run `{}` to start one.",
+                command
+            ));
+        }
+
+        let streak = match stats.longest_streak {
+            1 => "1 day".to_string(),
+            days => format!("{} days", days),
+        };
+        let mut text = format!(
+            "Your meeting stats: {} this week, {} this month, {} in all. Longest streak: {}.",
+            stats.counts.this_week, stats.counts.this_month, stats.counts.all_time, streak
+        );
+        let mut builder = Self::builder()
+            .ephemeral()
+            .block(Block::section(Text::mrkdwn("*Your meeting stats*")))
+            .block(Block::fields(vec![
+                Text::mrkdwn(format!("*This week*\n{}", stats.counts.this_week)),
+                Text::mrkdwn(format!("*This month*\n{}", stats.counts.this_month)),
+                Text::mrkdwn(format!("*All time*\n{}", stats.counts.all_time)),
+                Text::mrkdwn(format!("*Longest streak*\n{}", streak)),
+            ]));
+
+        if !stats.top_words.is_empty() {
+            let words: Vec<String> = stats
+                .top_words
+                .iter()
+                .map(|(word, count)| format!("{} ({})", blocks::escape(word), count))
+                .collect();
+            text.push_str(&format!(" Top title words: {}.", words.join(", ")));
+            builder = builder.block(Block::section(Text::mrkdwn(format!(
+                "*Most used title words*\n{}",
+                words.join(" · ")
+            ))));
+        }
+
+        builder.text(text).build_or_text()
     }
 
-    if let Err(e) = validator.validate_slack_channel_id(&payload.channel_id) {
-        warn!("Invalid channel ID: {}", e);
-        return Err(StatusCode::BAD_REQUEST);
+    /// Appends a support reference to replies reporting a failure (those
+    /// starting with ❌), so a user's report can be matched to the logs.
+    pub fn with_error_ref(mut self, request_id: &RequestId) -> Self {
+        if self.text.starts_with('❌') {
+            self.text = format!("{} (ref: {})", self.text, request_id.short());
+        }
+        self
     }
+}
 
-    if let Err(e) = validator.validate_url(&payload.response_url) {
-        warn!("Invalid response URL: {}", e);
-        return Err(StatusCode::BAD_REQUEST);
+/// Composes a [`SlackResponse`] with blocks. Messages are ephemeral unless
+/// made [`in_channel`](Self::in_channel).
+#[derive(Debug, Default)]
+pub struct SlackResponseBuilder {
+    in_channel: bool,
+    replace_original: bool,
+    text: String,
+    blocks: Vec<Block>,
+}
+
+impl SlackResponseBuilder {
+    /// Shown only to the user who ran the command.
+    pub fn ephemeral(mut self) -> Self {
+        self.in_channel = false;
+        self
+    }
+
+    /// Posted for the whole channel to see.
+    pub fn in_channel(mut self) -> Self {
+        self.in_channel = true;
+        self
     }
 
-    if let Some(ref text) = payload.text {
-        if let Err(e) = validator.validate_text_input(text, "command text") {
-            warn!("Invalid command text: {}", e);
-            return Ok(Json(SlackResponse::ephemeral(
-                "❌ Invalid command text. Please check for special characters.".to_string(),
-            )));
+    /// Replaces the message with the clicked button, rather than posting a
+    /// new one.
+    pub fn replace_original(mut self) -> Self {
+        self.replace_original = true;
+        self
+    }
+
+    /// The fallback text, required once there are blocks.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.text = text.into();
+        self
+    }
+
+    pub fn block(mut self, block: Block) -> Self {
+        self.blocks.push(block);
+        self
+    }
+
+    /// The response, unless its blocks break Slack's limits.
+    pub fn build(self) -> Result<SlackResponse, BlockError> {
+        if !self.blocks.is_empty() {
+            if self.text.trim().is_empty() {
+                return Err(BlockError::MissingFallbackText);
+            }
+            blocks::validate(&self.blocks)?;
         }
+
+        Ok(SlackResponse {
+            response_type: if self.in_channel {
+                "in_channel"
+            } else {
+                "ephemeral"
+            }
+            .to_string(),
+            text: self.text,
+            blocks: (!self.blocks.is_empty()).then_some(self.blocks),
+            replace_original: self.replace_original,
+        })
+    }
+
+    /// Like [`build`](Self::build), but blocks breaking Slack's limits are
+    /// dropped, logged, and the fallback text sent alone, so the user still
+    /// gets an answer.
+    pub fn build_or_text(self) -> SlackResponse {
+        let in_channel = self.in_channel;
+        let replace_original = self.replace_original;
+        let text = self.text.clone();
+        self.build().unwrap_or_else(|e| {
+            error!(
+                tags.error_kind = "invalid_blocks",
+                "Dropping message blocks: {}", e
+            );
+            let mut response = if in_channel {
+                SlackResponse::in_channel(text)
+            } else {
+                SlackResponse::ephemeral(text)
+            };
+            response.replace_original = replace_original;
+            response
+        })
     }
+}
 
+#[instrument(skip(state, request_id, verified), fields(request_id = %request_id))]
+pub async fn handle_slash_command(
+    State(state): State<AppState>,
+    request_id: RequestId,
+    verified: VerifiedSlackBody,
+) -> Result<Json<SlackResponse>, AppError> {
+    let started = Instant::now();
+    let verification_time = verified.verification_time;
+    let slow_request_threshold = state.config.observability.slow_request_threshold;
+    let error_rate = state.error_rate.clone();
+
+    let (result, phases) = observability::with_phase_timing(async {
+        observability::record_phase(Phase::Verification, verification_time);
+        process_slash_command(state, verified).await
+    })
+    .await;
+    metrics::record_handler_latency("slash_command", started.elapsed());
+
+    observability::warn_if_slow(
+        "slash command",
+        verification_time + started.elapsed(),
+        &phases,
+        slow_request_threshold,
+    );
+    error_rate.record(matches!(command_outcome(&result), "error" | "failed"));
+
+    result.map(|response| Json(response.with_error_ref(&request_id)))
+}
+
+async fn process_slash_command(
+    state: AppState,
+    verified: VerifiedSlackBody,
+) -> Result<SlackResponse, AppError> {
+    info!(
+        "Received slash command (request timestamp {})",
+        verified.timestamp
+    );
+
+    let body = verified.body;
+
+    let mut payload: SlashCommandPayload = serde_urlencoded::from_str(&body)
+        .map_err(|e| AppError::BadRequest(format!("unparsable slash command: {}", e)))?;
+
+    state.validator.validate_slack_command(&payload.command)?;
+    // Slash commands always have a response URL
+    state
+        .validator
+        .validate_response_url(&payload.response_url)?;
+    validate_ids(&state, &payload)?;
+    let mut text_was_modified = sanitize_text(&state, &mut payload)?;
+    check_rate_limits(&state, &payload).await?;
+
+    info!("Parsed command: {}", payload.command);
+
+    let commands = &state.config.slack.commands;
+    match commands.canonical(&payload.command) {
+        Some(canonical) => {
+            if let Some(subcommand) = slack_commands::shortcut_subcommand(canonical) {
+                // Run as the subcommand of the workspace's `/meet`, so
+                // replies name a command users can run
+                payload.command = commands.name_for(slack_commands::MEET).to_string();
+                payload.text = Some(subcommand.to_string());
+                text_was_modified = false;
+            }
+            handle_meet_command(state, payload, text_was_modified).await
+        }
+        None => {
+            error!("Unknown command: {}", payload.command);
+            Ok(SlackResponse::ephemeral("Unknown command".to_string()))
+        }
+    }
+}
+
+/// Checks the ids and `response_url` of a command. A command from a mention
+/// has no response URL to check.
+fn validate_ids(state: &AppState, payload: &SlashCommandPayload) -> Result<(), AppError> {
+    let validator = &state.validator;
+    validator.validate_slack_user_id(&payload.user_id)?;
+    validator.validate_slack_team_id(&payload.team_id)?;
+    error_reporting::set_team(&payload.team_id);
+    validator.validate_slack_channel_id(&payload.channel_id)?;
+    if let Some(enterprise_id) = payload.enterprise_id.as_deref().filter(|id| !id.is_empty()) {
+        validator.validate_slack_enterprise_id(enterprise_id)?;
+    }
+    if !payload.response_url.is_empty() {
+        validator.validate_response_url(&payload.response_url)?;
+    }
+    Ok(())
+}
+
+/// Strips unsupported characters from the command text, returning whether
+/// any were removed.
+fn sanitize_text(state: &AppState, payload: &mut SlashCommandPayload) -> Result<bool, AppError> {
+    let Some(text) = payload.text.take() else {
+        return Ok(false);
+    };
+    let sanitized = state.validator.validate_text_input(&text, "Command text")?;
+    if sanitized.was_modified {
+        info!(
+            "Removed {} unsupported characters from command text",
+            sanitized.removed.len()
+        );
+    }
+    payload.text = Some(sanitized.value);
+    Ok(sanitized.was_modified)
+}
+
+async fn check_rate_limits(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+) -> Result<(), AppError> {
     if let Err(e) = state
         .rate_limiter
-        .check_user_limit(&payload.user_id, "/slack/commands")
+        .check_user_limit(&payload.user_id, COMMANDS_ENDPOINT)
         .await
     {
         warn!("Rate limit exceeded for user {}: {}", payload.user_id, e);
-        return Ok(Json(SlackResponse::ephemeral(
-            "⏱️ Please slow down! You're sending commands too quickly.".to_string(),
-        )));
+        return Err(AppError::RateLimited(RateLimit::User));
     }
 
     if let Err(e) = state
         .rate_limiter
-        .check_endpoint_limit("/slack/commands")
+        .check_endpoint_limit(COMMANDS_ENDPOINT)
         .await
     {
         error!("Global rate limit exceeded: {}", e);
-        return Ok(Json(SlackResponse::ephemeral(
-            "🚫 Service temporarily unavailable due to high load. Please try again later."
-                .to_string(),
-        )));
+        return Err(AppError::RateLimited(RateLimit::Global));
     }
+    Ok(())
+}
 
-    info!("Parsed command: {}", payload.command);
+/// Runs `/meet` text that came from a message shortcut or a mention. Slack
+/// only waits for those to be acknowledged, so after the ids are checked
+/// the command runs in the background and its reply is
+/// [delivered](deliver) once it is ready. They count against the same rate
+/// limits as slash commands.
+pub(crate) fn run_detached(state: AppState, payload: SlashCommandPayload) -> Result<(), AppError> {
+    validate_ids(&state, &payload)?;
+    let task = async move {
+        let reply_to = payload.clone();
+        let response = run_detached_command(state.clone(), payload)
+            .await
+            .unwrap_or_else(|e| {
+                e.log();
+                SlackResponse::ephemeral(
+                    e.slack_text()
+                        .unwrap_or_else(|| INTERNAL_ERROR_TEXT.to_string()),
+                )
+            });
+        deliver(&state, &reply_to, response).await;
+    };
+    tokio::spawn(task.in_current_span());
+    Ok(())
+}
 
-    match payload.command.as_str() {
-        "/meet" => handle_meet_command(state, payload).await,
-        _ => {
-            error!("Unknown command: {}", payload.command);
-            Ok(Json(SlackResponse::ephemeral(
-                "Unknown command".to_string(),
-            )))
-        }
-    }
+async fn run_detached_command(
+    state: AppState,
+    mut payload: SlashCommandPayload,
+) -> Result<SlackResponse, AppError> {
+    let text_was_modified = sanitize_text(&state, &mut payload)?;
+    check_rate_limits(&state, &payload).await?;
+    handle_meet_command(state, payload, text_was_modified).await
 }
 
-#[instrument(skip(state))]
+// The payload carries the command's text and response URL, so only who
+// sent it goes on the span
+#[instrument(
+    skip(state, payload),
+    fields(user_id = %payload.user_id, team_id = %payload.team_id)
+)]
 async fn handle_meet_command(
     state: AppState,
     payload: SlashCommandPayload,
-) -> Result<Json<SlackResponse>, StatusCode> {
+    text_was_modified: bool,
+) -> Result<SlackResponse, AppError> {
     info!("Handling /meet command for user: {}", payload.user_id);
 
-    let user = match state.db.get_user_by_slack_id(&payload.user_id).await {
-        Ok(Some(user)) => user,
-        Ok(None) => {
-            match state
-                .db
-                .create_user(&payload.user_id, &payload.team_id)
-                .await
-            {
-                Ok(user) => user,
-                Err(e) => {
-                    error!("Failed to create user: {}", e);
-                    return Ok(Json(SlackResponse::ephemeral(
-                        "❌ Sorry, there was an error processing your request.".to_string(),
-                    )));
-                }
-            }
-        }
+    let command = match command_parser::parse(payload.text.as_deref().unwrap_or_default()) {
+        Ok(command) => command,
         Err(e) => {
-            error!("Database error: {}", e);
-            return Ok(Json(SlackResponse::ephemeral(
-                "❌ Sorry, there was a database error.".to_string(),
-            )));
+            warn!("Failed to parse /meet command text: {:?}", e);
+            metrics::record_slash_command("invalid", "rejected");
+            return Ok(SlackResponse::ephemeral(format!("❌ {}", e)));
         }
     };
 
-    match state.db.get_oauth_token(user.id).await {
-        Ok(Some(mut token)) => {
-            if token.is_expired() || token.expires_soon() {
-                info!(
-                    "Token expired or expiring soon for user {}, attempting refresh",
-                    user.id
-                );
-
-                let client = match create_oauth_client(&state) {
-                    Ok(client) => client,
-                    Err(_) => {
-                        error!("Failed to create OAuth client for token refresh");
-                        return Ok(Json(SlackResponse::ephemeral(
-                            "❌ Authentication system error. Please try again.".to_string(),
-                        )));
-                    }
-                };
+    let name = command.name();
+    let result = state
+        .commands
+        .clone()
+        .dispatch(state, payload, command, text_was_modified)
+        .await;
+    metrics::record_slash_command(name, command_outcome(&result));
+    result
+}
 
-                match refresh_token_if_needed(&client, &token).await {
-                    Ok(Some(refreshed_token)) => {
-                        info!("Successfully refreshed token for user {}", user.id);
-
-                        if let Err(e) = state.db.store_oauth_token(&refreshed_token).await {
-                            error!("Failed to store refreshed token: {}", e);
-                            return Ok(Json(SlackResponse::ephemeral(
-                                "❌ Failed to update authentication. Please re-authenticate."
-                                    .to_string(),
-                            )));
-                        }
-
-                        token = refreshed_token;
-                    }
-                    Ok(None) => {}
-                    Err(e) => {
-                        warn!("Failed to refresh token for user {}: {}", user.id, e);
-                        let auth_url = format!(
-                            "{}/auth/google?user_id={}",
-                            state
-                                .google_redirect_uri
-                                .trim_end_matches("/auth/google/callback"),
-                            payload.user_id
-                        );
-
-                        return Ok(Json(SlackResponse::with_auth_prompt(auth_url)));
-                    }
-                }
-            }
+/// Classifies a command's response for metrics. Replies to requests the bot
+/// could not carry out all start with ❌; input the bot refused is
+/// `rejected` rather than a failure of the bot.
+fn command_outcome(result: &Result<SlackResponse, AppError>) -> &'static str {
+    match result {
+        Err(e) if e.is_client_error() => "rejected",
+        Err(_) => "error",
+        Ok(response) if response.is_auth_prompt() => "auth_required",
+        Ok(response) if response.text.starts_with('❌') => "failed",
+        Ok(_) => "ok",
+    }
+}
 
-            if !is_token_valid(&token) {
-                warn!(
-                    "Token invalid or missing required scopes for user {}",
-                    user.id
-                );
-                let auth_url = format!(
-                    "{}/auth/google?user_id={}",
-                    state
-                        .google_redirect_uri
-                        .trim_end_matches("/auth/google/callback"),
-                    payload.user_id
-                );
-
-                return Ok(Json(SlackResponse::with_auth_prompt(auth_url)));
-            }
+/// Sends an extra message for the command in the background, for notes
+/// that don't fit in the immediate response (for example an ephemeral note
+/// next to an in-channel reply).
+pub(crate) fn send_followup(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    message: SlackResponse,
+) {
+    let (state, payload) = (state.clone(), payload.clone());
+    // Stays in the request's span, and so keeps its request id, after the
+    // handler has returned
+    tokio::spawn(async move { deliver(&state, &payload, message).await }.in_current_span());
+}
 
-            match create_meet_link(&state, &token, &payload).await {
-                Ok(meet_link) => {
-                    let meeting = crate::database::models::Meeting::new(
-                        user.id,
-                        meet_link.clone(),
-                        payload.text.clone(),
-                    );
-
-                    if let Err(e) = state.db.create_meeting(&meeting).await {
-                        error!("Failed to store meeting: {}", e);
-                    }
-
-                    Ok(Json(SlackResponse::in_channel(format!(
-                        "🎥 Google Meet created by <@{}>: {}",
-                        payload.user_name, meet_link
-                    ))))
-                }
-                Err(e) => {
-                    error!("Failed to create Meet link: {}", e);
-                    Ok(Json(SlackResponse::ephemeral(
-                        "❌ Failed to create Google Meet link. Please try again.".to_string(),
-                    )))
-                }
-            }
-        }
-        Ok(None) => {
-            let auth_url = format!(
-                "{}/auth/google?user_id={}",
-                state
-                    .google_redirect_uri
-                    .trim_end_matches("/auth/google/callback"),
-                payload.user_id
-            );
+/// Posts `message` where the command's later replies go: its
+/// `response_url`, or for a command from a thread, or from a mention that
+/// has no response URL, the channel through the workspace's bot token.
+/// Replies to a thread stay in it, and are also shown in the channel with
+/// `SLACK_THREAD_BROADCAST`. Without a bot token, the response URL is the
+/// fallback.
+pub(crate) async fn deliver(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    message: SlackResponse,
+) {
+    let has_response_url = !payload.response_url.is_empty();
+    if payload.thread_ts.is_none() && has_response_url {
+        return post_followup(state.http.clone(), payload.response_url.clone(), message).await;
+    }
 
-            Ok(Json(SlackResponse::with_auth_prompt(auth_url)))
-        }
-        Err(e) => {
-            let error_message = e.to_string();
-            
-            if error_message.contains("Invalid encrypted token format") 
-                || error_message.contains("Decryption failed") 
-                || error_message.contains("Encrypted token too short") {
-                
-                warn!("Token decryption failed for user {}: {}. Prompting for re-authentication.", user.id, e);
-                
-                if let Err(delete_err) = state.db.delete_oauth_token(user.id).await {
-                    warn!("Failed to delete invalid token: {}", delete_err);
-                }
-                
-                let auth_url = format!(
-                    "{}/auth/google?user_id={}",
-                    state
-                        .google_redirect_uri
-                        .trim_end_matches("/auth/google/callback"),
-                    payload.user_id
-                );
-
-                Ok(Json(SlackResponse::with_auth_prompt(auth_url)))
-            } else {
-                error!("Failed to get OAuth token: {}", e);
-                Ok(Json(SlackResponse::ephemeral(
-                    "❌ Sorry, there was an error checking your authentication.".to_string(),
-                )))
-            }
+    match post_with_bot_token(state, payload, &message).await {
+        Ok(()) => {}
+        Err(e) if has_response_url => {
+            warn!(
+                "Failed to reply in the thread, using the response URL: {:#}",
+                e
+            );
+            post_followup(state.http.clone(), payload.response_url.clone(), message).await;
         }
+        Err(e) => warn!(
+            "Failed to reply to {} in channel {}: {:#}",
+            payload.user_id, payload.channel_id, e
+        ),
     }
 }
 
-async fn create_meet_link(
+async fn post_with_bot_token(
     state: &AppState,
-    token: &crate::database::models::OAuthToken,
     payload: &SlashCommandPayload,
-) -> anyhow::Result<String> {
-    let title = match &payload.text {
-        Some(text) if !text.trim().is_empty() => Some(text.trim().to_string()),
-        _ => None,
+    message: &SlackResponse,
+) -> anyhow::Result<()> {
+    let team = state
+        .db
+        .get_slack_team(&payload.team_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("no bot token stored for team {}", payload.team_id))?;
+    let in_channel = message.response_type == "in_channel";
+    let chat = ChatMessage {
+        channel: payload.channel_id.clone(),
+        text: message.text.clone(),
+        blocks: message.blocks.clone().unwrap_or_default(),
+        thread: payload.thread_ts.clone().map(|thread_ts| ThreadReply {
+            thread_ts,
+            reply_broadcast: in_channel && state.config.slack.thread_broadcast,
+        }),
     };
+    if in_channel {
+        state.slack.post_message(&team.bot_token, &chat).await?;
+    } else {
+        state
+            .slack
+            .post_ephemeral(&team.bot_token, &chat, &payload.user_id)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Posts `message` to a command's `response_url`, logging a failure.
+pub(crate) async fn post_followup(
+    http: reqwest::Client,
+    response_url: String,
+    message: SlackResponse,
+) {
+    // response_url embeds a one-time secret, so only the host goes on the span
+    let span = otel::client_span!("slack.response_url", "POST", "https://hooks.slack.com");
+    let request = http
+        .post(&response_url)
+        .headers(otel::trace_headers(&span))
+        .json(&message);
+    let result = http_client::send(http_client::RESPONSE_URL, request)
+        .instrument(span.clone())
+        .await
+        .and_then(|response| {
+            otel::record_status(&span, response.status());
+            response.error_for_status()
+        });
+
+    if let Err(e) = result {
+        warn!("Failed to send follow-up message: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::test_state;
+    use axum::http::StatusCode;
+    use serde_json::Value;
+
+    #[test]
+    fn test_payload_accepts_enterprise_grid_fields() {
+        let body = "token=x&team_id=T012AB3C4&team_domain=acme&enterprise_id=E0KH6HD1H&enterprise_name=Acme%20Corp&channel_id=C012AB3CD&channel_name=general&user_id=W012A3CDE&user_name=alice&command=%2Fmeet&text=standup&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1%2F2&trigger_id=1.2.3";
+        let payload: SlashCommandPayload = serde_urlencoded::from_str(body).unwrap();
+
+        assert_eq!(payload.enterprise_id.as_deref(), Some("E0KH6HD1H"));
+        assert_eq!(payload.user_id, "W012A3CDE");
+    }
+
+    #[test]
+    fn test_payload_without_enterprise_fields() {
+        let body = "token=x&team_id=T012AB3C4&team_domain=acme&channel_id=C012AB3CD&channel_name=general&user_id=U012AB3CD&user_name=alice&command=%2Fmeet&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1%2F2&trigger_id=1.2.3";
+        let payload: SlashCommandPayload = serde_urlencoded::from_str(body).unwrap();
 
-    let meet_link =
-        crate::google::create_meet_space(&token.access_token).await?;
+        assert!(payload.enterprise_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_command_text_and_response_url_stay_out_of_the_logs() {
+        use crate::telemetry::logging::{tests::capture, LogFormat};
+
+        let (state, _pool) = test_state().await;
+        let (subscriber, logs) = capture(LogFormat::Json);
+        let _default = tracing::subscriber::set_default(subscriber);
+
+        let payload = crate::commands::testing::payload(
+            "list SECRET-topic",
+            "https://hooks.slack.com/commands/1/SECRET",
+        );
+        handle_meet_command(state, payload, false).await.unwrap();
+
+        let logs = logs.contents();
+        assert!(logs.contains("U012AB3CD"), "{}", logs);
+        assert!(!logs.contains("SECRET"), "{}", logs);
+    }
 
-    let meeting = crate::database::models::Meeting::new(token.user_id, meet_link.clone(), title);
+    #[tokio::test]
+    async fn test_error_reply_carries_the_request_id_ref() {
+        use crate::request_id::{self, REQUEST_ID_HEADER};
+        use axum::{body::Body, http::Request, middleware, routing::post, Router};
+        use hmac::{Hmac, Mac};
+        use sha2::Sha256;
+        use tower::ServiceExt;
+
+        let (state, _pool) = test_state().await;
+        let app = Router::new()
+            .route("/slack/commands", post(handle_slash_command))
+            .with_state(state)
+            .layer(middleware::from_fn(request_id::propagate));
+
+        let body = "token=x&team_id=T012AB3C4&team_domain=acme&channel_id=C012AB3CD&channel_name=general&user_id=U012AB3CD&user_name=alice&command=%2Fmeet&text=list%20abc&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1%2F2&trigger_id=1.2.3";
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+        let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/slack/commands")
+                    .header("x-slack-signature", signature)
+                    .header("x-slack-request-timestamp", timestamp)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let request_id = response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let reply: Value = serde_json::from_slice(&bytes).unwrap();
+        let text = reply["text"].as_str().unwrap();
+
+        assert!(text.starts_with('❌'), "{}", text);
+        assert!(
+            text.ends_with(&format!("(ref: {})", &request_id[..6])),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_messages_slack_would_drop() {
+        let button = |i: usize| Button::link(format!("b{}", i), "Open", "https://example.com");
+
+        let no_fallback = SlackResponse::builder()
+            .block(Block::section(Text::mrkdwn("Hi")))
+            .build();
+        assert_eq!(no_fallback.unwrap_err(), BlockError::MissingFallbackText);
+
+        let crowded = || {
+            SlackResponse::builder()
+                .in_channel()
+                .text("Pick one")
+                .block(Block::actions((0..26).map(button).collect()))
+        };
+        assert!(matches!(
+            crowded().build(),
+            Err(BlockError::TooManyElements { count: 26, .. })
+        ));
+        // Sent without the blocks rather than not at all
+        let fallback = crowded().build_or_text();
+        assert_eq!(fallback.response_type, "in_channel");
+        assert_eq!(fallback.text, "Pick one");
+        assert!(fallback.blocks.is_none());
+
+        let plain = SlackResponse::builder().text("Hi").build().unwrap();
+        assert_eq!(plain.response_type, "ephemeral");
+        assert!(plain.blocks.is_none());
+    }
 
-    state.db.create_meeting(&meeting).await?;
+    #[test]
+    fn test_only_failures_get_a_ref() {
+        let request_id = RequestId::generate();
 
-    Ok(meet_link)
+        let ok = SlackResponse::ephemeral("✅ Your Google account is connected.".to_string())
+            .with_error_ref(&request_id);
+        assert!(!ok.text.contains("ref:"));
+
+        let failed = SlackResponse::ephemeral("❌ Sorry, there was a database error.".to_string())
+            .with_error_ref(&request_id);
+        assert_eq!(
+            failed.text,
+            format!(
+                "❌ Sorry, there was a database error. (ref: {})",
+                request_id.short()
+            )
+        );
+    }
+
+    mod threads {
+        use super::*;
+        use crate::commands::testing::{connected_user, payload, Fixture, TestState};
+        use crate::config::Config;
+
+        const THREAD: &str = "1709628600.000200";
+
+        /// `/meet <text>` from a mention, in `thread_ts` if given.
+        fn mention(text: &str, thread_ts: Option<&str>) -> SlashCommandPayload {
+            let mut payload = payload(text, "");
+            payload.thread_ts = thread_ts.map(str::to_string);
+            payload
+        }
+
+        #[tokio::test]
+        async fn test_meeting_from_a_thread_is_announced_there() {
+            let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+            connected_user(&state).await;
+            let payload = mention("Incident bridge", Some(THREAD));
+
+            let response = run_detached_command(state.clone(), payload.clone())
+                .await
+                .unwrap();
+            deliver(&state, &payload, response).await;
+
+            let posted = slack.posted();
+            assert_eq!(posted.len(), 1);
+            assert_eq!(posted[0].channel, "C012AB3CD");
+            assert!(
+                posted[0].text.contains("meet.google.com"),
+                "{}",
+                posted[0].text
+            );
+            assert_eq!(
+                posted[0].thread,
+                Some(ThreadReply {
+                    thread_ts: THREAD.to_string(),
+                    reply_broadcast: false,
+                })
+            );
+        }
+
+        #[tokio::test]
+        async fn test_thread_is_only_set_when_given() {
+            let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+
+            deliver(
+                &state,
+                &mention("", None),
+                SlackResponse::in_channel("Top level".to_string()),
+            )
+            .await;
+            deliver(
+                &state,
+                &mention("", Some(THREAD)),
+                SlackResponse::ephemeral("Only you".to_string()),
+            )
+            .await;
+
+            let posted = slack.posted();
+            assert_eq!(posted.len(), 1);
+            assert_eq!(posted[0].thread, None);
+            let ephemeral = slack.ephemeral();
+            assert_eq!(ephemeral.len(), 1);
+            assert_eq!(ephemeral[0].0, "U012AB3CD");
+            assert_eq!(
+                ephemeral[0].1.thread.as_ref().map(|t| t.thread_ts.as_str()),
+                Some(THREAD)
+            );
+        }
+
+        #[tokio::test]
+        async fn test_replies_are_broadcast_when_configured() {
+            let mut config = Config::for_tests();
+            config.slack.thread_broadcast = true;
+            let TestState { state, slack, .. } =
+                Fixture::new().config(config).installed().build().await;
+
+            deliver(
+                &state,
+                &mention("", Some(THREAD)),
+                SlackResponse::in_channel("Join here".to_string()),
+            )
+            .await;
+            deliver(
+                &state,
+                &mention("", Some(THREAD)),
+                SlackResponse::ephemeral("Only you".to_string()),
+            )
+            .await;
+
+            assert!(slack.posted()[0].thread.as_ref().unwrap().reply_broadcast);
+            assert!(
+                !slack.ephemeral()[0]
+                    .1
+                    .thread
+                    .as_ref()
+                    .unwrap()
+                    .reply_broadcast
+            );
+        }
+    }
 }
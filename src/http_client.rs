@@ -0,0 +1,358 @@
+//! The HTTP client every outbound call shares: Google's APIs, OAuth token
+//! exchanges and Slack response URLs. Calls go out through [`send`], which
+//! logs and measures each one.
+
+use oauth2::{HttpRequest, HttpResponse};
+use reqwest::header::{self, HeaderMap};
+use reqwest::{redirect, Client, NoProxy, Proxy, RequestBuilder, Response};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+use url::Url;
+
+use crate::config::{OutboundConfig, DEFAULT_OUTBOUND_SLOW_CALL};
+use crate::secret::redact;
+use crate::telemetry::metrics;
+
+/// [`OutboundConfig::slow_call_threshold`] of the last client built, in
+/// milliseconds; every client is built from the same configuration.
+static SLOW_CALL_MS: AtomicU64 = AtomicU64::new(DEFAULT_OUTBOUND_SLOW_CALL.as_millis() as u64);
+
+/// What an outbound call is, for its logs and metrics: the service called
+/// and the template of the path, such as `/v2/spaces/{space}`. The path
+/// itself would give a metric series per meeting, and a response URL's
+/// holds a secret.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Route {
+    pub service: &'static str,
+    pub path: &'static str,
+}
+
+/// A command's response URL, whose path is a one-time secret.
+pub const RESPONSE_URL: Route = Route::slack("/{response_url}");
+
+impl Route {
+    pub const fn google(path: &'static str) -> Self {
+        Self {
+            service: "google",
+            path,
+        }
+    }
+
+    pub const fn slack(path: &'static str) -> Self {
+        Self {
+            service: "slack",
+            path,
+        }
+    }
+}
+
+/// Error of [`oauth2_http_client`], the one oauth2's own reqwest client uses.
+pub type OAuth2HttpError = oauth2::reqwest::Error<reqwest::Error>;
+
+/// Builds the shared client. Proxies come from `HTTPS_PROXY`, `HTTP_PROXY`
+/// and `NO_PROXY` unless `OUTBOUND_PROXY_URL` is set, in which case every
+/// request goes through it except for hosts in `NO_PROXY`. Credentials in a
+/// proxy URL are sent as basic auth.
+///
+/// Redirects aren't followed: none of the APIs called use them, and
+/// following them from the token exchange would open it up to SSRF.
+pub fn build(config: &OutboundConfig) -> anyhow::Result<Client> {
+    let mut builder = Client::builder().redirect(redirect::Policy::none());
+    if let Some(proxy_url) = &config.proxy_url {
+        builder = builder.proxy(Proxy::all(proxy_url.expose())?.no_proxy(NoProxy::from_env()));
+    }
+    SLOW_CALL_MS.store(
+        config.slow_call_threshold.as_millis() as u64,
+        Ordering::Relaxed,
+    );
+    Ok(builder.build()?)
+}
+
+/// Sends `request` with the client that built it, recording its method,
+/// status and latency under `route` in `outbound_request_duration_seconds`
+/// and the logs. Calls slower than `OUTBOUND_SLOW_CALL_MS` and those that
+/// get no response are logged as warnings. Authorization headers and
+/// token-like query parameters never reach the logs.
+pub async fn send(route: Route, request: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    let method = request.method().clone();
+    let url = logged_url(route, request.url());
+    debug!(
+        service = route.service,
+        %method,
+        %url,
+        headers = %redact_headers(request.headers()),
+        "Outbound request"
+    );
+
+    let started = Instant::now();
+    let result = client.execute(request).await;
+    let elapsed = started.elapsed();
+    let status = match &result {
+        Ok(response) => response.status().as_str().to_string(),
+        Err(e) if e.is_timeout() => "timeout".to_string(),
+        Err(_) => "error".to_string(),
+    };
+    metrics::record_outbound_request(route.service, route.path, method.as_str(), &status, elapsed);
+
+    let elapsed_ms = elapsed.as_millis() as u64;
+    let slow_call = Duration::from_millis(SLOW_CALL_MS.load(Ordering::Relaxed));
+    match &result {
+        Err(e) => warn!(
+            service = route.service,
+            %method,
+            %url,
+            elapsed_ms,
+            "Outbound request failed: {}",
+            describe(e)
+        ),
+        Ok(_) if elapsed >= slow_call => warn!(
+            service = route.service,
+            %method,
+            %url,
+            %status,
+            elapsed_ms,
+            "Slow outbound request"
+        ),
+        Ok(_) => debug!(
+            service = route.service,
+            %method,
+            %url,
+            %status,
+            elapsed_ms,
+            "Outbound response"
+        ),
+    }
+    result
+}
+
+/// `url` as [`send`] logs it: its path replaced by the template of
+/// `route`, and the values of token-like query parameters redacted.
+fn logged_url(route: Route, url: &Url) -> String {
+    let mut logged = format!(
+        "{}://{}{}",
+        url.scheme(),
+        url.host_str().unwrap_or_default(),
+        route.path
+    );
+    let query: Vec<String> = url
+        .query_pairs()
+        .map(|(name, value)| {
+            if is_sensitive(&name) {
+                format!("{}={}", name, redact(&value))
+            } else {
+                format!("{}={}", name, value)
+            }
+        })
+        .collect();
+    if !query.is_empty() {
+        logged.push('?');
+        logged.push_str(&query.join("&"));
+    }
+    logged
+}
+
+/// `headers` for the logs, with the values of credentials redacted.
+fn redact_headers(headers: &HeaderMap) -> String {
+    let mut logged: Vec<String> = headers
+        .iter()
+        .map(|(name, value)| {
+            let credential = name == header::AUTHORIZATION
+                || name == header::PROXY_AUTHORIZATION
+                || name == header::COOKIE
+                || is_sensitive(name.as_str());
+            if credential {
+                format!("{}: {}", name, redact(value.to_str().unwrap_or_default()))
+            } else {
+                format!("{}: {}", name, value.to_str().unwrap_or("<binary>"))
+            }
+        })
+        .collect();
+    logged.sort();
+    logged.join(", ")
+}
+
+/// What went wrong with a request that got no response, without its URL.
+fn describe(error: &reqwest::Error) -> String {
+    match std::error::Error::source(error) {
+        Some(source) => source.to_string(),
+        None if error.is_timeout() => "timed out".to_string(),
+        None => "no response".to_string(),
+    }
+}
+
+/// Whether a query parameter or header of this name carries a credential.
+fn is_sensitive(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["token", "secret", "key", "code", "signature", "password"]
+        .iter()
+        .any(|part| name.contains(part))
+}
+
+/// Sends oauth2's requests through `http`, so token exchanges and refreshes
+/// use the same proxy settings as everything else. Pass it to
+/// `request_async` as `|request| oauth2_http_client(&http, request)`.
+pub async fn oauth2_http_client(
+    http: &Client,
+    request: HttpRequest,
+) -> Result<HttpResponse, OAuth2HttpError> {
+    let mut builder = http
+        .request(request.method, request.url.as_str())
+        .body(request.body);
+    for (name, value) in &request.headers {
+        builder = builder.header(name.as_str(), value.as_bytes());
+    }
+
+    let response = send(Route::google("/token"), builder)
+        .await
+        .map_err(OAuth2HttpError::Reqwest)?;
+    let status_code = response.status();
+    let headers = response.headers().to_owned();
+    let body = response.bytes().await.map_err(OAuth2HttpError::Reqwest)?;
+    Ok(HttpResponse {
+        status_code,
+        headers,
+        body: body.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::State, http::Request, response::Json, Router};
+    use oauth2::http::{HeaderMap, Method};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    /// What a forward proxy saw of one request.
+    #[derive(Debug, Clone)]
+    struct Proxied {
+        uri: String,
+        proxy_authorization: Option<String>,
+    }
+
+    /// A forward proxy that answers every request itself, recording it.
+    async fn proxy() -> (String, Arc<Mutex<Vec<Proxied>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let app = Router::new()
+            .fallback(
+                |State(seen): State<Arc<Mutex<Vec<Proxied>>>>,
+                 request: Request<axum::body::Body>| async move {
+                    seen.lock().unwrap().push(Proxied {
+                        uri: request.uri().to_string(),
+                        proxy_authorization: request
+                            .headers()
+                            .get("proxy-authorization")
+                            .map(|value| value.to_str().unwrap().to_string()),
+                    });
+                    Json(json!({ "access_token": "ya29.access", "token_type": "Bearer" }))
+                },
+            )
+            .with_state(seen.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", address), seen)
+    }
+
+    #[tokio::test]
+    async fn test_token_exchange_goes_through_the_configured_proxy() {
+        let (proxy_address, seen) = proxy().await;
+        let config = OutboundConfig {
+            proxy_url: Some(
+                proxy_address
+                    .replace("http://", "http://bot:hunter2@")
+                    .into(),
+            ),
+            ..OutboundConfig::default()
+        };
+        let http = build(&config).unwrap();
+
+        let response = oauth2_http_client(
+            &http,
+            HttpRequest {
+                url: "http://oauth2.example.com/token".parse().unwrap(),
+                method: Method::POST,
+                headers: HeaderMap::new(),
+                body: b"grant_type=refresh_token".to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.status_code, 200);
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        // A proxied plain HTTP request names the whole target URL
+        assert_eq!(seen[0].uri, "http://oauth2.example.com/token");
+        assert_eq!(
+            seen[0].proxy_authorization.as_deref(),
+            // base64 of bot:hunter2
+            Some("Basic Ym90Omh1bnRlcjI=")
+        );
+    }
+
+    #[test]
+    fn test_logs_leave_credentials_out() {
+        let url = Url::parse(
+            "https://hooks.slack.com/commands/T012AB3C4/1234/s3cr3t?access_token=ya29.secret&pageSize=100&pageToken=next",
+        )
+        .unwrap();
+        assert_eq!(
+            logged_url(RESPONSE_URL, &url),
+            "https://hooks.slack.com/{response_url}?access_token=[REDACTED 11 bytes]&pageSize=100&pageToken=[REDACTED 4 bytes]"
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, "Bearer ya29.secret".parse().unwrap());
+        headers.insert(header::PROXY_AUTHORIZATION, "Basic Ym90".parse().unwrap());
+        headers.insert("x-goog-api-key", "AIza-secret".parse().unwrap());
+        headers.insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+        let logged = redact_headers(&headers);
+        assert_eq!(
+            logged,
+            "authorization: [REDACTED 18 bytes], content-type: application/json, \
+             proxy-authorization: [REDACTED 10 bytes], x-goog-api-key: [REDACTED 11 bytes]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_labelled_by_route_not_url() {
+        use wiremock::matchers::method;
+        use wiremock::{Mock, MockServer, ResponseTemplate};
+
+        let handle = metrics::install();
+        let google = MockServer::start().await;
+        Mock::given(method("GET"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&google)
+            .await;
+        let route = Route::google("/v2/spaces/{space}/outbound-test");
+        for space in ["spaces/jQCFfuBOdN5z", "spaces/xK9aPq2LmW7e"] {
+            let request = Client::new()
+                .get(format!("{}/v2/{}/outbound-test", google.uri(), space))
+                .bearer_auth("ya29.secret")
+                .query(&[("pageToken", space)]);
+            let response = send(route, request).await.unwrap();
+            assert_eq!(response.status(), 404);
+        }
+
+        let rendered = handle.render();
+        let series: Vec<&str> = rendered
+            .lines()
+            .filter(|line| {
+                line.starts_with("outbound_request_duration_seconds_count")
+                    && line.contains("outbound-test")
+            })
+            .collect();
+        assert_eq!(
+            series,
+            [
+                r#"outbound_request_duration_seconds_count{service="google",route="/v2/spaces/{space}/outbound-test",method="GET",status="404"} 2"#
+            ]
+        );
+        assert!(!rendered.contains("jQCFfuBOdN5z"), "{}", rendered);
+    }
+}
@@ -0,0 +1,383 @@
+//! iCalendar files (RFC 5545) of scheduled meetings, for attendees whose
+//! calendars aren't Google's, like Outlook users at partner companies. The
+//! announcement of a scheduled meeting links to `/ics/<token>`, whose
+//! token carries the meeting id and start, signed with HMAC-SHA256 under
+//! the Slack signing secret as the data export's links are; nothing is
+//! stored for it, and the file is built when the link is opened.
+
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use thiserror::Error;
+use url::Url;
+
+use crate::database::models::Meeting;
+use crate::google::calendar::{DEFAULT_EVENT_LENGTH, UNTITLED};
+use crate::secret::SecretString;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Where the files are served.
+pub const PATH_PREFIX: &str = "/ics/";
+
+pub const CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+
+const PRODUCT_ID: &str = "-//google-meet-slackbot//Meeting//EN";
+
+/// Longest a content line may be, in octets, without its line break.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// The domain of event UIDs when the bot's address has none.
+const FALLBACK_DOMAIN: &str = "google-meet-slackbot";
+
+/// One meeting, as the `VEVENT` of a calendar file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event<'a> {
+    /// Unique across calendars, as `meeting-<id>@<bot's domain>`, so
+    /// importing the file again updates the event instead of adding one.
+    pub uid: String,
+    pub starts_at: DateTime<Utc>,
+    /// An hour after the start when the meeting has no end.
+    pub ends_at: DateTime<Utc>,
+    pub title: &'a str,
+    pub meet_link: &'a str,
+    /// Email of whoever created the meeting, when Slack shares it.
+    pub organizer: Option<&'a str>,
+    /// When the file was made.
+    pub stamp: DateTime<Utc>,
+}
+
+impl<'a> Event<'a> {
+    /// The event of `meeting` starting at `starts_at`, for the bot at
+    /// `base_url`.
+    pub fn new(
+        meeting: &'a Meeting,
+        starts_at: DateTime<Utc>,
+        base_url: &str,
+        stamp: DateTime<Utc>,
+    ) -> Self {
+        let domain = Url::parse(base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_string))
+            .unwrap_or_else(|| FALLBACK_DOMAIN.to_string());
+        Self {
+            uid: format!("meeting-{}@{}", meeting.id.unwrap_or_default(), domain),
+            starts_at,
+            ends_at: meeting
+                .ends_at
+                .map(|ends_at| ends_at.and_utc())
+                .unwrap_or(starts_at + DEFAULT_EVENT_LENGTH),
+            title: meeting.title.as_deref().unwrap_or(UNTITLED),
+            meet_link: &meeting.meet_link,
+            organizer: None,
+            stamp,
+        }
+    }
+
+    /// The calendar file holding just this event, with CRLF line breaks
+    /// and long lines folded.
+    pub fn to_ics(&self) -> String {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:{}", PRODUCT_ID),
+            "CALSCALE:GREGORIAN".to_string(),
+            "METHOD:PUBLISH".to_string(),
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}", escape(&self.uid)),
+            format!("DTSTAMP:{}", utc_time(self.stamp)),
+            format!("DTSTART:{}", utc_time(self.starts_at)),
+            format!("DTEND:{}", utc_time(self.ends_at)),
+            format!("SUMMARY:{}", escape(self.title)),
+            format!(
+                "DESCRIPTION:{}",
+                escape(&format!("Join with Google Meet: {}", self.meet_link))
+            ),
+            format!("LOCATION:{}", escape(self.meet_link)),
+            format!("URL:{}", self.meet_link),
+        ];
+        if let Some(email) = self.organizer {
+            lines.push(format!("ORGANIZER:mailto:{}", email));
+        }
+        lines.push("END:VEVENT".to_string());
+        lines.push("END:VCALENDAR".to_string());
+
+        lines.iter().map(|line| fold(line)).collect()
+    }
+}
+
+/// `text` as a `TEXT` value: backslashes, semicolons and commas escaped,
+/// and line breaks as `\n`.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\r' => {
+                if chars.peek() != Some(&'\n') {
+                    escaped.push_str("\\n");
+                }
+            }
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `line` ended by CRLF, and broken into lines of at most
+/// [`MAX_LINE_OCTETS`] octets, each after the first starting with the
+/// space that marks it as a continuation. Lines are only broken between
+/// chars, so no UTF-8 sequence is split.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + line.len() / MAX_LINE_OCTETS * 3 + 2);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
+
+/// `at` as a UTC `DATE-TIME`.
+fn utc_time(at: DateTime<Utc>) -> String {
+    at.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Name of the file of the meeting `meeting_id`.
+pub fn filename(meeting_id: i64) -> String {
+    format!("meeting-{}.ics", meeting_id)
+}
+
+/// Where the file signed into `token` is served by the bot at `base_url`.
+pub fn url(base_url: &str, token: &str) -> String {
+    format!("{}{}{}", base_url, PATH_PREFIX, token)
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum IcsLinkError {
+    #[error("malformed calendar file token")]
+    Malformed,
+    #[error("calendar file signature doesn't match")]
+    BadSignature,
+}
+
+/// A token for the file of the meeting `meeting_id` starting at
+/// `starts_at`. It doesn't expire, as the announcement holding it stays.
+pub fn sign_token(secret: &SecretString, meeting_id: i64, starts_at: DateTime<Utc>) -> String {
+    let starts_at = starts_at.timestamp();
+    let signature = hex::encode(
+        token_mac(secret, meeting_id, starts_at)
+            .finalize()
+            .into_bytes(),
+    );
+    format!("{}.{}.{}", meeting_id, starts_at, signature)
+}
+
+/// The meeting id and start a token was signed for, if it is untouched.
+pub fn verify_token(
+    secret: &SecretString,
+    token: &str,
+) -> Result<(i64, DateTime<Utc>), IcsLinkError> {
+    let mut parts = token.split('.');
+    let (Some(meeting_id), Some(starts_at), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(IcsLinkError::Malformed);
+    };
+    let meeting_id: i64 = meeting_id.parse().map_err(|_| IcsLinkError::Malformed)?;
+    let starts_at: i64 = starts_at.parse().map_err(|_| IcsLinkError::Malformed)?;
+    let signature = hex::decode(signature).map_err(|_| IcsLinkError::Malformed)?;
+
+    token_mac(secret, meeting_id, starts_at)
+        .verify_slice(&signature)
+        .map_err(|_| IcsLinkError::BadSignature)?;
+    let starts_at = Utc
+        .timestamp_opt(starts_at, 0)
+        .single()
+        .ok_or(IcsLinkError::Malformed)?;
+    Ok((meeting_id, starts_at))
+}
+
+fn token_mac(secret: &SecretString, meeting_id: i64, starts_at: i64) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.expose().as_bytes()).expect("HMAC takes any key size");
+    // Prefixed so the signature can't be mistaken for an export link's or
+    // a Slack request's, which use the same secret
+    mac.update(format!("ics:{}:{}", meeting_id, starts_at).as_bytes());
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::MeetLinkKind;
+    use ical::parser::ical::component::IcalCalendar;
+    use ical::IcalParser;
+    use std::io::BufReader;
+
+    const LINK: &str = "https://meet.google.com/abc-defg-hij";
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn meeting(title: &str) -> Meeting {
+        let mut meeting = Meeting::new(
+            1,
+            LINK.to_string(),
+            Some(title.to_string()),
+            MeetLinkKind::Meet,
+        );
+        meeting.id = Some(42);
+        meeting
+    }
+
+    fn parse(ics: &str) -> IcalCalendar {
+        let mut calendars = IcalParser::new(BufReader::new(ics.as_bytes()));
+        let calendar = calendars.next().unwrap().unwrap();
+        assert!(calendars.next().is_none());
+        calendar
+    }
+
+    fn property(calendar: &IcalCalendar, name: &str) -> Option<String> {
+        calendar.events[0]
+            .properties
+            .iter()
+            .find(|property| property.name == name)
+            .and_then(|property| property.value.clone())
+    }
+
+    #[test]
+    fn test_event_parses_back() {
+        let meeting = meeting("Standup");
+        let mut event = Event::new(
+            &meeting,
+            at("2024-03-05T07:00:00Z"),
+            "https://bot.example.com",
+            at("2024-03-04T12:30:00Z"),
+        );
+        event.organizer = Some("alice@example.com");
+
+        let ics = event.to_ics();
+        let calendar = parse(&ics);
+
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(calendar.events.len(), 1);
+        assert_eq!(
+            property(&calendar, "UID").as_deref(),
+            Some("meeting-42@bot.example.com")
+        );
+        assert_eq!(
+            property(&calendar, "DTSTART").as_deref(),
+            Some("20240305T070000Z")
+        );
+        // Without an end it lasts an hour
+        assert_eq!(
+            property(&calendar, "DTEND").as_deref(),
+            Some("20240305T080000Z")
+        );
+        assert_eq!(
+            property(&calendar, "DTSTAMP").as_deref(),
+            Some("20240304T123000Z")
+        );
+        assert_eq!(property(&calendar, "SUMMARY").as_deref(), Some("Standup"));
+        assert_eq!(
+            property(&calendar, "DESCRIPTION").unwrap(),
+            format!("Join with Google Meet: {}", LINK)
+        );
+        assert_eq!(
+            property(&calendar, "ORGANIZER").as_deref(),
+            Some("mailto:alice@example.com")
+        );
+    }
+
+    #[test]
+    fn test_text_is_escaped() {
+        let meeting = meeting("Q1, Q2; review \\ plan\nwith Ann");
+        let event = Event::new(
+            &meeting,
+            at("2024-03-05T07:00:00Z"),
+            "https://bot.example.com",
+            at("2024-03-04T12:30:00Z"),
+        );
+
+        let calendar = parse(&event.to_ics());
+
+        assert_eq!(
+            property(&calendar, "SUMMARY").as_deref(),
+            Some("Q1\\, Q2\\; review \\\\ plan\\nwith Ann")
+        );
+        assert_eq!(escape("a\r\nb\rc"), "a\\nb\\nc");
+    }
+
+    #[test]
+    fn test_long_lines_are_folded_at_75_octets() {
+        let title = "Zażółć gęślą jaźń 🇵🇱 ".repeat(8);
+        let meeting = meeting(title.trim());
+        let event = Event::new(
+            &meeting,
+            at("2024-03-05T07:00:00Z"),
+            "https://bot.example.com",
+            at("2024-03-04T12:30:00Z"),
+        );
+
+        let ics = event.to_ics();
+
+        for line in ics.split_terminator("\r\n") {
+            assert!(line.len() <= MAX_LINE_OCTETS, "{:?}", line);
+        }
+        assert!(ics.contains("\r\n "), "nothing was folded: {}", ics);
+        // Unfolded, the title is whole again
+        let calendar = parse(&ics);
+        assert_eq!(
+            property(&calendar, "SUMMARY").as_deref(),
+            Some(title.trim())
+        );
+    }
+
+    #[test]
+    fn test_token_round_trip() {
+        let secret = "signing-secret".into();
+        let starts_at = at("2024-03-05T07:00:00Z");
+        let token = sign_token(&secret, 42, starts_at);
+
+        assert_eq!(verify_token(&secret, &token), Ok((42, starts_at)));
+        assert_eq!(
+            verify_token(&"other-secret".into(), &token),
+            Err(IcsLinkError::BadSignature)
+        );
+    }
+
+    #[test]
+    fn test_tampered_tokens_are_rejected() {
+        let secret = "signing-secret".into();
+        let token = sign_token(&secret, 42, at("2024-03-05T07:00:00Z"));
+        let signature = token.rsplit('.').next().unwrap();
+
+        assert_eq!(
+            verify_token(&secret, &format!("43.1709622000.{}", signature)),
+            Err(IcsLinkError::BadSignature)
+        );
+        assert_eq!(
+            verify_token(&secret, &format!("42.1709625600.{}", signature)),
+            Err(IcsLinkError::BadSignature)
+        );
+        for malformed in ["", "42", "42.x.00", "42.1709622000.zz", "a.b.c.d"] {
+            assert_eq!(
+                verify_token(&secret, malformed),
+                Err(IcsLinkError::Malformed),
+                "{}",
+                malformed
+            );
+        }
+    }
+}
@@ -0,0 +1,668 @@
+//! Slack bot creating Google Meet links. `main.rs` reads the config and
+//! calls [`run`]; the library holds everything else so integration tests
+//! can build the same router.
+
+use axum::{
+    extract::FromRef,
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use metrics_exporter_prometheus::PrometheusHandle;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tower_http::limit::RequestBodyLimitLayer;
+use tracing::{error, info, warn};
+
+pub mod announcement;
+pub mod api_keys;
+pub mod audit;
+pub mod auth;
+pub mod background;
+pub mod build_info;
+pub mod catch_panic;
+pub mod channel_policy;
+pub mod command_parser;
+pub mod commands;
+pub mod config;
+pub mod cors;
+pub mod crypto;
+pub mod database;
+pub mod digest;
+pub mod error;
+pub mod export;
+pub mod features;
+pub mod google;
+pub mod google_failures;
+pub mod handlers;
+pub mod http_client;
+pub mod ics;
+pub mod listener;
+pub mod meeting_end;
+pub mod models;
+pub mod observability;
+pub mod personal_space;
+pub mod polls;
+pub mod qr;
+pub mod quota;
+pub mod rate_limiter;
+pub mod recording;
+pub mod reminders;
+pub mod request_id;
+pub mod secret;
+pub mod shared_account;
+pub mod short_links;
+pub mod shutdown;
+pub mod slack;
+pub mod stats;
+pub mod telemetry;
+pub mod text;
+pub mod time;
+pub mod timezones;
+pub mod title_template;
+pub mod validation;
+pub mod working_hours;
+
+use background::JobRegistry;
+use commands::{CommandRegistry, InFlightCommands, MeetingQueue};
+use config::Config;
+use database::{Database, KeyCheck};
+use google::{GoogleApi, GoogleClient};
+use google_failures::GoogleFailures;
+use observability::ErrorRateMonitor;
+use rate_limiter::RateLimiter;
+use slack::api::{SlackApi, SlackClient};
+use slack::{guard, SlackVerifier};
+use telemetry::logging::LogFilter;
+use time::{SharedClock, SystemClock};
+use validation::InputValidator;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Database,
+    pub rate_limiter: RateLimiter,
+    /// Users whose meetings Google keeps refusing, beside the rate limits.
+    pub google_failures: GoogleFailures,
+    pub validator: Arc<InputValidator>,
+    pub config: Arc<Config>,
+    pub google: Arc<dyn GoogleApi>,
+    /// Slack's Web API, for messages sent other than as a command's reply.
+    pub slack: Arc<dyn SlackApi>,
+    /// Shared by every outbound call, with the proxy settings applied.
+    pub http: reqwest::Client,
+    /// Handlers for the `/meet` subcommands.
+    pub commands: Arc<CommandRegistry>,
+    /// Users creating a meeting right now, so a double submit makes one.
+    pub in_flight: Arc<InFlightCommands>,
+    /// Meeting creations waiting for a worker; the workers are started with
+    /// [`MeetingQueue::start`].
+    pub meeting_queue: MeetingQueue,
+    /// Where handlers and jobs read the time, so tests can set it.
+    pub clock: SharedClock,
+    pub error_rate: Arc<ErrorRateMonitor>,
+    /// Periodic background jobs and how their last runs went.
+    pub jobs: JobRegistry,
+    pub metrics: PrometheusHandle,
+    pub log_filter: LogFilter,
+}
+
+impl AppState {
+    /// Opens and migrates the database, checks the encryption key against
+    /// it, and sets up everything else the handlers share.
+    pub async fn from_config(config: Arc<Config>, log_filter: LogFilter) -> anyhow::Result<Self> {
+        let crypto = config.encryption.clone();
+        info!(
+            "Token encryption ready (primary key id: {}, cipher: {})",
+            crypto.primary_key_id(),
+            crypto.cipher().name()
+        );
+
+        let metrics = telemetry::metrics::install();
+        let http = http_client::build(&config.outbound)?;
+        let google: Arc<dyn GoogleApi> = match config.google.fake_latency {
+            Some(latency) => {
+                warn!(
+                    "GOOGLE_FAKE is set: meetings are made up locally after {:?}, not created at Google",
+                    latency
+                );
+                Arc::new(google::fake::FakeGoogleApi::succeeding().with_delay(latency))
+            }
+            None => Arc::new(GoogleClient::new(http.clone())),
+        };
+
+        let db = Database::new(&config.database.url, crypto).await?;
+        db.migrate().await?;
+
+        match db
+            .verify_encryption_key(config.database.allow_key_mismatch)
+            .await?
+        {
+            KeyCheck::Initialized => info!("Stored encryption key canary for future startups"),
+            KeyCheck::Matched => info!("Encryption key matches existing data"),
+            KeyCheck::MismatchAllowed(e) => error!(
+                "Encryption key does not match existing data ({}) — all stored tokens \
+                 will be unusable and users must re-authenticate. Starting anyway because \
+                 ALLOW_KEY_MISMATCH=true.",
+                e
+            ),
+        }
+
+        Ok(Self {
+            db,
+            rate_limiter: RateLimiter::with_config(config.rate_limit),
+            google_failures: GoogleFailures::new(&config.rate_limit),
+            validator: Arc::new(InputValidator::with_config(config.validation.clone())),
+            google,
+            slack: Arc::new(SlackClient::new(http.clone())),
+            http: http.clone(),
+            commands: Arc::new(CommandRegistry::new()),
+            in_flight: Arc::new(InFlightCommands::new(config.rate_limit.create_cooldown)),
+            meeting_queue: MeetingQueue::new(config.queue, http),
+            clock: SystemClock::shared(),
+            error_rate: Arc::new(ErrorRateMonitor::new(&config.observability)),
+            jobs: JobRegistry::new(),
+            metrics,
+            log_filter,
+            config,
+        })
+    }
+}
+
+/// Applies the pending migrations to the database of `config` and closes
+/// it, for `--migrate-only`: an init container can migrate before any
+/// instance of a new version takes traffic.
+pub async fn migrate_only(config: &Config) -> anyhow::Result<()> {
+    let db = Database::new(&config.database.url, config.encryption.clone()).await?;
+    let status = db.migration_status().await?;
+    let pending = status.pending();
+    if pending.is_empty() {
+        info!(
+            "Database is up to date, {} migrations applied",
+            status.applied.len()
+        );
+    } else {
+        info!("Applying migrations {:?}", pending);
+    }
+    let migrated = db.migrate().await;
+    db.close().await;
+    migrated?;
+    info!("Migrations done");
+    Ok(())
+}
+
+/// Serves the app of `config` until a shutdown signal, then lets queued
+/// meetings and background jobs finish and closes the database.
+pub async fn run(config: Arc<Config>, log_filter: LogFilter) -> anyhow::Result<()> {
+    let state = AppState::from_config(config.clone(), log_filter).await?;
+    let background = CancellationToken::new();
+    let jobs = spawn_background_jobs(&state, &background);
+
+    let meeting_queue = state.meeting_queue.clone();
+    meeting_queue.start(state.clone());
+
+    let db = state.db.clone();
+    let app = app(state);
+
+    let listener = listener::Listener::bind(&config.server).await?;
+    shutdown::serve(
+        listener,
+        app,
+        shutdown::signal(),
+        background,
+        config.server.shutdown_timeout,
+    )
+    .await?;
+
+    // Requests are done, so nothing more is queued; meetings already
+    // queued get created and posted before the database closes
+    info!(
+        "Finishing {} queued meeting creations",
+        meeting_queue.depth()
+    );
+    if tokio::time::timeout(config.server.shutdown_timeout, meeting_queue.drain())
+        .await
+        .is_err()
+    {
+        warn!(
+            "Queued meetings still being created after {}s; abandoning them",
+            config.server.shutdown_timeout.as_secs()
+        );
+    }
+
+    for (name, job) in jobs {
+        if let Err(e) = job.await {
+            error!("{} task failed: {}", name, e);
+        }
+    }
+    info!("Closing database connections");
+    db.close().await;
+    info!("Shutdown complete");
+    Ok(())
+}
+
+/// Starts the periodic jobs, which stop once `stop` is cancelled: pruning
+/// the rate limiters, pruning expired records, and sending the weekly
+/// digests. Returns each with its name for the logs.
+fn spawn_background_jobs(
+    state: &AppState,
+    stop: &CancellationToken,
+) -> Vec<(&'static str, JoinHandle<()>)> {
+    let rate_limiter = state.rate_limiter.clone();
+    let google_failures = state.google_failures.clone();
+    let cleanup_clock = state.clock.clone();
+    let in_flight = state.in_flight.clone();
+    let cleanup_task = state.jobs.spawn_periodic(
+        "rate_limiter_cleanup",
+        rate_limiter::CLEANUP_INTERVAL,
+        stop.clone(),
+        Duration::from_secs(30),
+        move || {
+            let rate_limiter = rate_limiter.clone();
+            let google_failures = google_failures.clone();
+            let now = cleanup_clock.now();
+            in_flight.prune();
+            async move {
+                rate_limiter.cleanup_old_entries().await;
+                google_failures.cleanup_old_entries(now).await;
+                Ok(())
+            }
+        },
+    );
+
+    let retention_db = state.db.clone();
+    let retention_clock = state.clock.clone();
+    let audit_retention_days = state.config.admin.audit_retention_days;
+    let retention_task = state.jobs.spawn_periodic(
+        "retention",
+        audit::PRUNE_INTERVAL,
+        stop.clone(),
+        Duration::from_secs(5 * 60),
+        move || {
+            let db = retention_db.clone();
+            let now = retention_clock.now();
+            async move {
+                audit::prune_expired(&db, audit_retention_days, now).await?;
+                commands::prune_request_dedup(&db, now).await?;
+                handlers::auth::prune_oauth_states(&db, now).await?;
+                meeting_end::expire_pending_responses(&db, now).await
+            }
+        },
+    );
+
+    let digest_db = state.db.clone();
+    let digest_slack = state.slack.clone();
+    let digest_clock = state.clock.clone();
+    let digest_command = state
+        .config
+        .slack
+        .commands
+        .name_for(slack::commands::MEET)
+        .to_string();
+    let digest_task = state.jobs.spawn_periodic(
+        "weekly_digest",
+        digest::CHECK_INTERVAL,
+        stop.clone(),
+        Duration::from_secs(5 * 60),
+        move || {
+            let db = digest_db.clone();
+            let slack = digest_slack.clone();
+            let now = digest_clock.now();
+            let command = digest_command.clone();
+            async move { digest::send_due(&db, slack.as_ref(), &command, now).await }
+        },
+    );
+
+    vec![
+        ("Rate limiter cleanup", cleanup_task),
+        ("Retention", retention_task),
+        ("Weekly digest", digest_task),
+    ]
+}
+
+impl FromRef<AppState> for SlackVerifier {
+    fn from_ref(state: &AppState) -> Self {
+        SlackVerifier {
+            signing_secret: state.config.slack.signing_secret.clone(),
+            config: state.config.slack.verification,
+            audit: Some(state.db.clone()),
+            clock: state.clock.clone(),
+        }
+    }
+}
+
+/// Assembles every route group and the request-wide middleware.
+pub fn app(state: AppState) -> Router {
+    // Size and content-type checks run before the body is buffered or its
+    // signature verified. Load beyond the concurrency limit is shed, and a
+    // stalled Google call or database lock can't hold a request forever.
+    let slack_routes = Router::new()
+        .route(
+            "/slack/commands",
+            post(handlers::slack::handle_slash_command)
+                .route_layer(middleware::from_fn(guard::require_form_content_type)),
+        )
+        .route(
+            "/slack/interactions",
+            post(handlers::interactions::handle_interaction)
+                .route_layer(middleware::from_fn(guard::require_form_content_type)),
+        )
+        .route(
+            "/slack/events",
+            post(handlers::events::handle_event)
+                .route_layer(middleware::from_fn(guard::require_json_content_type)),
+        )
+        .layer(RequestBodyLimitLayer::new(
+            state.config.slack.max_body_bytes,
+        ));
+    let slack_routes = guard::with_backpressure(
+        slack_routes,
+        state.config.slack.request_timeout,
+        state.config.slack.max_concurrent_requests,
+    );
+
+    // The REST API, for tools creating meetings with the API keys admins
+    // make with `/meet admin api-key create`
+    let api_routes = Router::new()
+        .route(
+            handlers::api::MEETINGS_PATH,
+            post(handlers::api::create_meeting)
+                .route_layer(middleware::from_fn(guard::require_json_content_type)),
+        )
+        .layer(RequestBodyLimitLayer::new(handlers::api::MAX_BODY_BYTES));
+
+    // Pages people open in a browser: the Google sign-in, the Slack
+    // install, exports, short links and calendar files. They are the only routes that answer CORS
+    // requests, and only for CORS_ALLOWED_ORIGINS.
+    let browser_routes = Router::new()
+        .route("/auth/google", get(handlers::auth::initiate_google_oauth))
+        .route(
+            "/auth/google/callback",
+            get(handlers::auth::handle_google_callback),
+        )
+        .route(
+            handlers::install::INSTALL_PATH,
+            get(handlers::install::begin),
+        )
+        .route(
+            handlers::install::CALLBACK_PATH,
+            get(handlers::install::finish),
+        )
+        .route("/export/:token", get(handlers::export::download))
+        .route("/m/:slug", get(handlers::short_links::follow))
+        .route("/ics/:token", get(handlers::ics::download))
+        .layer(cors::browser_layer(
+            &state.config.server.cors_allowed_origins,
+        ));
+
+    // Scraped by Prometheus; protect it with METRICS_TOKEN when the port is
+    // reachable from outside the cluster.
+    let metrics_routes = telemetry::metrics::router(
+        state.metrics.clone(),
+        state.db.clone(),
+        state.config.telemetry.metrics_token.clone(),
+    );
+
+    // /ready can also check that Google's token endpoint resolves, for
+    // deployments where DNS or egress trouble should keep traffic away
+    let health_routes = handlers::health::router(
+        state.db.clone(),
+        state.config.server.ready_check_google,
+        state.jobs.clone(),
+    );
+
+    // Admin endpoints only exist when ADMIN_TOKEN is set
+    let admin_routes = match &state.config.admin.token {
+        Some(token) => handlers::admin::router(
+            state.db.clone(),
+            token.clone(),
+            state.log_filter.clone(),
+            state.jobs.clone(),
+            state.clock.clone(),
+        ),
+        None => Router::new(),
+    };
+
+    Router::new()
+        .merge(slack_routes)
+        .merge(api_routes)
+        .merge(browser_routes)
+        .with_state(state)
+        .merge(health_routes)
+        .merge(metrics_routes)
+        .merge(admin_routes)
+        .layer(catch_panic::layer())
+        .layer(middleware::from_fn(error::reply))
+        .layer(middleware::from_fn(
+            telemetry::error_reporting::request_scope,
+        ))
+        .layer(telemetry::otel::http_trace_layer())
+        .layer(middleware::from_fn(request_id::propagate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Method, Request, StatusCode},
+        response::Response,
+    };
+    use tower::ServiceExt;
+
+    async fn test_app() -> Router {
+        test_app_with(Config::for_tests()).await
+    }
+
+    async fn test_app_with(config: Config) -> Router {
+        test_app_and_db(config).await.0
+    }
+
+    /// The router together with the database behind it, for tests that
+    /// check what a request did or didn't write.
+    async fn test_app_and_db(config: Config) -> (Router, Database) {
+        let (db, _pool) = database::test_db().await;
+
+        let (_layer, log_filter) = LogFilter::new("info").unwrap();
+        let state = AppState {
+            db,
+            rate_limiter: RateLimiter::new(),
+            google_failures: GoogleFailures::new(&config.rate_limit),
+            validator: Arc::new(InputValidator::default()),
+            error_rate: Arc::new(ErrorRateMonitor::new(&config.observability)),
+            jobs: JobRegistry::new(),
+            google: Arc::new(google::fake::FakeGoogleApi::succeeding()),
+            slack: Arc::new(slack::fake::FakeSlackApi::new()),
+            http: reqwest::Client::new(),
+            commands: Arc::new(CommandRegistry::new()),
+            in_flight: Arc::new(InFlightCommands::new(config.rate_limit.create_cooldown)),
+            meeting_queue: MeetingQueue::new(config.queue, reqwest::Client::new()),
+            clock: time::SystemClock::shared(),
+            metrics: telemetry::metrics::install(),
+            log_filter,
+            config: Arc::new(config),
+        };
+        (app(state.clone()), state.db)
+    }
+
+    async fn preflight(uri: &str, method: &str) -> Response {
+        preflight_from(test_app().await, uri, method, "https://evil.example.com").await
+    }
+
+    async fn preflight_from(app: Router, uri: &str, method: &str, origin: &str) -> Response {
+        app.oneshot(
+            Request::builder()
+                .method(Method::OPTIONS)
+                .uri(uri)
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, method)
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cross_origin_preflight_is_rejected() {
+        for (uri, method) in [("/slack/commands", "POST"), ("/health", "GET")] {
+            let response = preflight(uri, method).await;
+            assert!(
+                !response
+                    .headers()
+                    .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+                "{} answered a CORS preflight",
+                uri
+            );
+            assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_browser_pages_answer_allowed_origins() {
+        let mut config = Config::for_tests();
+        config.server.cors_allowed_origins =
+            vec![header::HeaderValue::from_static("https://meet.example.com")];
+        let app = test_app_with(config).await;
+
+        for uri in [
+            "/auth/google",
+            "/auth/google/callback",
+            "/export/abc",
+            "/m/standup",
+            "/ics/abc",
+        ] {
+            let allowed = preflight_from(app.clone(), uri, "GET", "https://meet.example.com").await;
+            assert_eq!(
+                allowed.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+                Some(&header::HeaderValue::from_static(
+                    "https://meet.example.com"
+                )),
+                "{}",
+                uri
+            );
+
+            let other = preflight_from(app.clone(), uri, "GET", "https://evil.example.com").await;
+            assert!(
+                !other
+                    .headers()
+                    .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN),
+                "{} answered another origin",
+                uri
+            );
+        }
+    }
+
+    /// A slash command POST signed with the test signing secret. The builder
+    /// adds no `Content-Length`, so the body is only measured as it's read.
+    fn signed_command(body: &str) -> axum::http::request::Builder {
+        use hmac::{Hmac, Mac};
+        use sha2::Sha256;
+
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+        let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+
+        Request::builder()
+            .method(Method::POST)
+            .uri("/slack/commands")
+            .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header("x-slack-signature", signature)
+            .header("x-slack-request-timestamp", timestamp)
+    }
+
+    fn meet_command(user_id: &str, text: &str) -> String {
+        format!(
+            "team_id=T012AB3C4&channel_id=C012AB3CD&channel_name=general&user_id={}&user_name=alice&command=%2Fmeet&text={}&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1%2F2&trigger_id=1.2.3",
+            user_id, text
+        )
+    }
+
+    #[tokio::test]
+    async fn test_slack_command_post_is_unaffected() {
+        let body = "token=x&team_id=T012AB3C4&team_domain=acme&channel_id=C012AB3CD&channel_name=general&user_id=U012AB3CD&user_name=alice&command=%2Fmeet-help&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1%2F2&trigger_id=1.2.3";
+
+        let response = test_app()
+            .await
+            .oneshot(
+                signed_command(body)
+                    .header(header::ORIGIN, "https://evil.example.com")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_slack_body_is_refused_before_anything_runs() {
+        let mut config = Config::for_tests();
+        config.slack.max_body_bytes = 512;
+        let (app, db) = test_app_and_db(config).await;
+
+        // A command inside the limit gets through and creates its user.
+        let body = meet_command("U0000FITS", "list");
+        assert!(body.len() < 512);
+        let response = app
+            .clone()
+            .oneshot(signed_command(&body).body(Body::from(body)).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(db
+            .get_user_by_slack_id("U0000FITS")
+            .await
+            .unwrap()
+            .is_some());
+        let audited = db.audit_log_page(None, 100).await.unwrap().len();
+
+        // Validly signed but too long, once announced by its length and once
+        // only found out while reading.
+        let body = meet_command("U0000LONG", &format!("list+{}", "x".repeat(1024)));
+        let declared = signed_command(&body)
+            .header(header::CONTENT_LENGTH, body.len())
+            .body(Body::from(body.clone()))
+            .unwrap();
+        let undeclared = signed_command(&body).body(Body::from(body)).unwrap();
+        for request in [declared, undeclared] {
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        assert!(db
+            .get_user_by_slack_id("U0000LONG")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(db.count_users().await.unwrap(), 1);
+        assert_eq!(db.audit_log_page(None, 100).await.unwrap().len(), audited);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_only_applies_what_is_pending() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bot.db");
+        let mut config = Config::for_tests();
+        let db = Database::new(
+            &format!("sqlite:{}?mode=rwc", path.display()),
+            config.encryption.clone(),
+        )
+        .await
+        .unwrap();
+        db.migrate_all_but_last().await.unwrap();
+        assert_eq!(db.migration_status().await.unwrap().pending().len(), 1);
+
+        config.database.url = format!("sqlite:{}", path.display());
+        migrate_only(&config).await.unwrap();
+        assert!(db.migration_status().await.unwrap().pending().is_empty());
+        // Running it again finds nothing to do
+        migrate_only(&config).await.unwrap();
+    }
+}
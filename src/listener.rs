@@ -0,0 +1,394 @@
+use anyhow::{bail, Context};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use std::future::{Future, IntoFuture};
+use std::io;
+use std::net::SocketAddr;
+#[cfg(unix)]
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio_util::sync::CancellationToken;
+use tower::Service;
+use tracing::{debug, error, info, warn};
+
+use crate::config::{Listen, ServerConfig};
+
+/// A bound socket, ready to serve the app.
+pub enum Listener {
+    Tcp(TcpListener),
+    Tls {
+        listener: std::net::TcpListener,
+        config: RustlsConfig,
+        cert_path: PathBuf,
+        key_path: PathBuf,
+    },
+    #[cfg(unix)]
+    Unix {
+        listener: UnixListener,
+        path: PathBuf,
+    },
+}
+
+impl Listener {
+    /// Binds what `config.listen` asks for. A stale socket file left by a
+    /// previous run is replaced; any other file at that path is an error.
+    pub async fn bind(config: &ServerConfig) -> anyhow::Result<Self> {
+        let addr = format!("{}:{}", config.host, config.port);
+        match &config.listen {
+            Listen::Tcp => {
+                let listener = TcpListener::bind(&addr)
+                    .await
+                    .with_context(|| format!("Failed to bind {}", addr))?;
+                info!("Starting server on http://{}", listener.local_addr()?);
+                Ok(Self::Tcp(listener))
+            }
+            Listen::Tls {
+                cert_path,
+                key_path,
+            } => {
+                let tls = RustlsConfig::from_pem_file(cert_path, key_path)
+                    .await
+                    .with_context(|| {
+                        format!(
+                            "Failed to load the TLS certificate {} or key {}",
+                            cert_path.display(),
+                            key_path.display()
+                        )
+                    })?;
+                let listener = std::net::TcpListener::bind(&addr)
+                    .with_context(|| format!("Failed to bind {}", addr))?;
+                listener.set_nonblocking(true)?;
+                info!("Starting server on https://{}", listener.local_addr()?);
+                Ok(Self::Tls {
+                    listener,
+                    config: tls,
+                    cert_path: cert_path.clone(),
+                    key_path: key_path.clone(),
+                })
+            }
+            #[cfg(not(unix))]
+            Listen::Unix { .. } => bail!("LISTEN_UNIX_SOCKET is only supported on Unix"),
+            #[cfg(unix)]
+            Listen::Unix { path } => {
+                remove_stale_socket(path)?;
+                let listener = UnixListener::bind(path)
+                    .with_context(|| format!("Failed to bind {}", path.display()))?;
+                info!("Starting server on unix:{}", path.display());
+                Ok(Self::Unix {
+                    listener,
+                    path: path.clone(),
+                })
+            }
+        }
+    }
+
+    /// Serves `app` until `shutdown` is cancelled, then stops accepting
+    /// connections and resolves once those in flight have finished.
+    pub fn serve(
+        self,
+        app: Router,
+        shutdown: CancellationToken,
+    ) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send>> {
+        match self {
+            Self::Tcp(listener) => Box::pin(
+                axum::serve(
+                    listener,
+                    app.into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown.cancelled_owned())
+                .into_future(),
+            ),
+            Self::Tls {
+                listener,
+                config,
+                cert_path,
+                key_path,
+            } => {
+                tokio::spawn(reload_on_sighup(
+                    config.clone(),
+                    cert_path,
+                    key_path,
+                    shutdown.clone(),
+                ));
+
+                let handle = axum_server::Handle::new();
+                let on_shutdown = handle.clone();
+                tokio::spawn(async move {
+                    shutdown.cancelled().await;
+                    on_shutdown.graceful_shutdown(None);
+                });
+                Box::pin(
+                    axum_server::from_tcp_rustls(listener, config)
+                        .handle(handle)
+                        .serve(app.into_make_service_with_connect_info::<SocketAddr>()),
+                )
+            }
+            #[cfg(unix)]
+            Self::Unix { listener, path } => Box::pin(serve_unix(listener, path, app, shutdown)),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> anyhow::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display())),
+        Ok(_) => bail!(
+            "LISTEN_UNIX_SOCKET {} exists and is not a socket",
+            path.display()
+        ),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| format!("Failed to inspect {}", path.display())),
+    }
+}
+
+/// axum's `serve` only takes TCP listeners, so Unix connections are driven
+/// by hyper directly. Requests carry no `ConnectInfo`.
+#[cfg(unix)]
+async fn serve_unix(
+    listener: UnixListener,
+    path: PathBuf,
+    app: Router,
+    shutdown: CancellationToken,
+) -> io::Result<()> {
+    let graceful = GracefulShutdown::new();
+    let builder = auto::Builder::new(TokioExecutor::new());
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Failed to accept a connection on the Unix socket: {}", e);
+                    continue;
+                }
+            },
+            _ = shutdown.cancelled() => break,
+        };
+
+        let app = app.clone();
+        let service = hyper::service::service_fn(move |request| app.clone().call(request));
+        let connection = builder
+            .serve_connection(TokioIo::new(stream), service)
+            .into_owned();
+        let connection = graceful.watch(connection);
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!("Unix socket connection ended with an error: {}", e);
+            }
+        });
+    }
+
+    drop(listener);
+    if let Err(e) = std::fs::remove_file(&path) {
+        warn!("Failed to remove socket {}: {}", path.display(), e);
+    }
+    graceful.shutdown().await;
+    Ok(())
+}
+
+/// Reads the certificate and key again whenever the process gets SIGHUP,
+/// so a renewed certificate is picked up without a restart. A certificate
+/// that fails to load is logged and the current one kept.
+#[cfg(unix)]
+async fn reload_on_sighup(
+    config: RustlsConfig,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    shutdown: CancellationToken,
+) {
+    let mut hangups = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(e) => {
+            error!(
+                "Failed to install the SIGHUP handler; TLS certificates won't be reloaded: {}",
+                e
+            );
+            return;
+        }
+    };
+
+    loop {
+        tokio::select! {
+            _ = hangups.recv() => {
+                match reload_certificate(&config, &cert_path, &key_path).await {
+                    Ok(()) => info!("Reloaded TLS certificate from {}", cert_path.display()),
+                    Err(e) => error!("Failed to reload the TLS certificate, keeping the current one: {:#}", e),
+                }
+            }
+            _ = shutdown.cancelled() => break,
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn reload_on_sighup(_: RustlsConfig, _: PathBuf, _: PathBuf, _: CancellationToken) {}
+
+async fn reload_certificate(
+    config: &RustlsConfig,
+    cert_path: &Path,
+    key_path: &Path,
+) -> anyhow::Result<()> {
+    config
+        .reload_from_pem_file(cert_path, key_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to load {} or {}",
+                cert_path.display(),
+                key_path.display()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use axum::routing::get;
+    use rustls::pki_types::ServerName;
+    use std::sync::Arc;
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    #[cfg(unix)]
+    use tokio::net::UnixStream;
+
+    fn app() -> Router {
+        Router::new().route("/health", get(|| async { "healthy" }))
+    }
+
+    fn server_config(listen: Listen) -> ServerConfig {
+        ServerConfig {
+            port: 0,
+            listen,
+            ..Config::for_tests().server
+        }
+    }
+
+    async fn get_health(mut stream: impl AsyncRead + AsyncWrite + Unpin) -> String {
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    /// Writes a self-signed certificate for `localhost`, returning it in DER
+    /// form for the client to trust.
+    fn write_certificate(dir: &Path) -> rustls::pki_types::CertificateDer<'static> {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        std::fs::write(dir.join("cert.pem"), certified.cert.pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), certified.key_pair.serialize_pem()).unwrap();
+        certified.cert.der().clone()
+    }
+
+    async fn handshake(
+        addr: SocketAddr,
+        trusted: rustls::pki_types::CertificateDer<'static>,
+    ) -> io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(trusted).unwrap();
+        let client = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        let stream = TcpStream::connect(addr).await?;
+        tokio_rustls::TlsConnector::from(Arc::new(client))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_serves_on_a_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meetbot.sock");
+        // A socket left behind by a previous run doesn't stop startup
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+
+        let listener = Listener::bind(&server_config(Listen::Unix { path: path.clone() }))
+            .await
+            .unwrap();
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(listener.serve(app(), shutdown.clone()));
+
+        let response = get_health(UnixStream::connect(&path).await.unwrap()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("healthy"));
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_refuses_to_replace_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("meetbot.sock");
+        std::fs::write(&path, "not a socket").unwrap();
+
+        let error = match Listener::bind(&server_config(Listen::Unix { path })).await {
+            Err(error) => error,
+            Ok(_) => panic!("bound over a regular file"),
+        };
+        assert!(error.to_string().contains("is not a socket"));
+    }
+
+    #[tokio::test]
+    async fn test_serves_https_and_reloads_the_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = write_certificate(dir.path());
+        let cert_path = dir.path().join("cert.pem");
+        let key_path = dir.path().join("key.pem");
+
+        let listener = Listener::bind(&server_config(Listen::Tls {
+            cert_path: cert_path.clone(),
+            key_path: key_path.clone(),
+        }))
+        .await
+        .unwrap();
+        let Listener::Tls {
+            listener: socket,
+            config,
+            ..
+        } = &listener
+        else {
+            panic!("expected a TLS listener");
+        };
+        let addr = socket.local_addr().unwrap();
+        let config = config.clone();
+        let shutdown = CancellationToken::new();
+        let server = tokio::spawn(listener.serve(app(), shutdown.clone()));
+
+        let response = get_health(handshake(addr, first.clone()).await.unwrap()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+        // A renewed certificate is served once reloaded
+        let second = write_certificate(dir.path());
+        reload_certificate(&config, &cert_path, &key_path)
+            .await
+            .unwrap();
+        assert!(handshake(addr, first).await.is_err());
+        let response = get_health(handshake(addr, second).await.unwrap()).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+
+        // A broken one is rejected and the current one kept
+        std::fs::write(&cert_path, "garbage").unwrap();
+        assert!(reload_certificate(&config, &cert_path, &key_path)
+            .await
+            .is_err());
+
+        shutdown.cancel();
+        server.await.unwrap().unwrap();
+    }
+}
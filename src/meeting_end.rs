@@ -0,0 +1,391 @@
+//! Editing a meeting's announcement once the meeting is cancelled or has
+//! ended, so the channel isn't left with a link that looks live: the link
+//! is struck through and the message says who stopped it and when.
+//!
+//! A message the bot posted itself is edited with `chat.update`; one that
+//! was a command's reply only through its response URL, which Slack takes
+//! for 30 minutes. That URL is kept encrypted in `pending_responses` until
+//! then and used at most once; past that the channel gets a follow-up
+//! message instead.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use tracing::{info, warn};
+
+use crate::database::models::Meeting;
+use crate::database::Database;
+use crate::handlers::slack::{SlackResponse, SlashCommandPayload};
+use crate::http_client;
+use crate::secret::SecretString;
+use crate::slack::api::ChatMessage;
+use crate::slack::blocks;
+use crate::AppState;
+
+/// How long Slack takes messages at a command's response URL.
+pub const RESPONSE_URL_LIFETIME: Duration = Duration::minutes(30);
+
+/// How a meeting stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ending {
+    Cancelled,
+    Ended,
+}
+
+impl Ending {
+    fn emoji(self) -> &'static str {
+        match self {
+            Ending::Cancelled => "🚫",
+            Ending::Ended => "⏹️",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            Ending::Cancelled => "cancelled",
+            Ending::Ended => "ended",
+        }
+    }
+
+    /// [`Self::verb`] starting a sentence.
+    fn headline(self) -> &'static str {
+        match self {
+            Ending::Cancelled => "Cancelled",
+            Ending::Ended => "Ended",
+        }
+    }
+}
+
+/// How the channel was told.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    /// The bot's own announcement was edited with `chat.update`.
+    Edited,
+    /// The command's reply was replaced through its response URL.
+    Replaced,
+    /// A new message was posted, as the announcement couldn't be edited.
+    FollowedUp,
+    /// The meeting was never announced in a channel.
+    NotAnnounced,
+}
+
+/// Records where the command in `payload` announced `meeting`, if it did
+/// so through its response URL; failing to is only logged, and leaves the
+/// announcement as it is when the meeting stops.
+pub async fn record_announcement(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    meeting: &Meeting,
+) {
+    // Replies to threads are posted with the bot token, which the response
+    // URL can't edit
+    if payload.response_url.is_empty() || payload.thread_ts.is_some() {
+        return;
+    }
+    let Some(meeting_id) = meeting.id else {
+        return;
+    };
+    let response_url = SecretString::from(payload.response_url.as_str());
+    let expires_at = (state.clock.now() + RESPONSE_URL_LIFETIME).naive_utc();
+    let recorded = async {
+        let response_id = state
+            .db
+            .store_pending_response(&response_url, expires_at)
+            .await?;
+        state
+            .db
+            .set_announcement_response(meeting_id, response_id)
+            .await
+    };
+    if let Err(e) = recorded.await {
+        warn!(
+            "Failed to record the announcement of meeting {}: {:#}",
+            meeting_id, e
+        );
+    }
+}
+
+/// Tells the channel `meeting` was stopped by `by`, a Slack user id, as
+/// this module describes.
+pub async fn update_announcement(
+    state: &AppState,
+    meeting: &Meeting,
+    ending: Ending,
+    by: &str,
+) -> Result<Update> {
+    let meeting_id = meeting.id.context("meeting isn't stored")?;
+    let Some(location) = state.db.meeting_announcement(meeting_id).await? else {
+        return Ok(Update::NotAnnounced);
+    };
+    let now = state.clock.now();
+    let label = match &meeting.title {
+        Some(title) => format!("<{}|{}>", meeting.meet_link, blocks::title(title)),
+        None => format!("<{}>", meeting.meet_link),
+    };
+    let stopped = format!(
+        "{} {} by <@{}> {}.",
+        ending.emoji(),
+        ending.headline(),
+        by,
+        blocks::date(now)
+    );
+    let replacement = format!("~{}~\n{}", label, stopped);
+
+    let team = match &meeting.slack_team_id {
+        Some(team_id) => state.db.get_slack_team(team_id).await?,
+        None => None,
+    };
+
+    if let (Some((channel, ts)), Some(team)) = (&location.message, &team) {
+        let message = chat_message(channel, replacement.clone());
+        match state
+            .slack
+            .update_message(&team.bot_token, &message, ts)
+            .await
+        {
+            Ok(()) => {
+                info!("Edited the announcement of meeting {}", meeting_id);
+                return Ok(Update::Edited);
+            }
+            Err(e) => warn!(
+                "Failed to edit the announcement of meeting {}: {}",
+                meeting_id, e
+            ),
+        }
+    }
+
+    let response_url = match location.response_id {
+        Some(response_id) => state
+            .db
+            .claim_pending_response(response_id, now.naive_utc())
+            .await
+            .unwrap_or_else(|e| {
+                warn!(
+                    "Failed to look up the response URL of meeting {}: {:#}",
+                    meeting_id, e
+                );
+                None
+            }),
+        None => None,
+    };
+    if let Some(response_url) = response_url {
+        let mut reply = SlackResponse::in_channel(replacement);
+        reply.replace_original = true;
+        match replace(state, response_url.expose(), &reply).await {
+            Ok(()) => {
+                info!("Replaced the announcement of meeting {}", meeting_id);
+                return Ok(Update::Replaced);
+            }
+            Err(e) => warn!(
+                "Failed to replace the announcement of meeting {}: {:#}",
+                meeting_id, e
+            ),
+        }
+    }
+
+    let channel = location
+        .message
+        .as_ref()
+        .map(|(channel, _)| channel.as_str())
+        .or(meeting.channel_id.as_deref());
+    let (Some(channel), Some(team)) = (channel, &team) else {
+        bail!(
+            "no way left to tell the channel meeting {} was {}",
+            meeting_id,
+            ending.verb()
+        );
+    };
+    let message = chat_message(
+        channel,
+        format!(
+            "{} {} was {} by <@{}> {}; its link no longer works.",
+            ending.emoji(),
+            label,
+            ending.verb(),
+            by,
+            blocks::date(now)
+        ),
+    );
+    state.slack.post_message(&team.bot_token, &message).await?;
+    info!("Followed up on the announcement of meeting {}", meeting_id);
+    Ok(Update::FollowedUp)
+}
+
+/// Deletes the response URLs Slack no longer takes by `now`.
+pub async fn expire_pending_responses(db: &Database, now: DateTime<Utc>) -> Result<()> {
+    let expired = db.expire_pending_responses(now.naive_utc()).await?;
+    if expired > 0 {
+        info!("Expired {} unused response URLs", expired);
+    }
+    Ok(())
+}
+
+async fn replace(state: &AppState, response_url: &str, reply: &SlackResponse) -> Result<()> {
+    let request = state.http.post(response_url).json(reply);
+    http_client::send(http_client::RESPONSE_URL, request)
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+fn chat_message(channel: &str, text: String) -> ChatMessage {
+    ChatMessage {
+        channel: channel.to_string(),
+        text,
+        blocks: Vec::new(),
+        thread: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::{payload, test_state, Fixture, TestState};
+    use crate::database::models::MeetLinkKind;
+
+    use crate::time::TestClock;
+    use serde_json::json;
+
+    use wiremock::matchers::{body_partial_json, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    const LINK: &str = "https://meet.google.com/abc-defg-hij";
+
+    async fn announced(state: &AppState) -> Meeting {
+        let user = state
+            .db
+            .create_user("U012AB3CD", "T012AB3C4")
+            .await
+            .unwrap();
+        state
+            .db
+            .create_meeting(
+                &Meeting::new(
+                    user.id,
+                    LINK.to_string(),
+                    Some("Standup".to_string()),
+                    MeetLinkKind::Meet,
+                )
+                .with_team("T012AB3C4")
+                .with_channel("C012AB3CD"),
+            )
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bot_messages_are_edited() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        let meeting = announced(&state).await;
+        state
+            .db
+            .set_announcement_message(meeting.id.unwrap(), "C012AB3CD", "1709628600.000200")
+            .await
+            .unwrap();
+
+        let update = update_announcement(&state, &meeting, Ending::Cancelled, "U098ZY7XW")
+            .await
+            .unwrap();
+
+        assert_eq!(update, Update::Edited);
+        let updated = slack.updated();
+        assert_eq!(updated.len(), 1);
+        assert_eq!(updated[0].0, "1709628600.000200");
+        assert_eq!(updated[0].1.channel, "C012AB3CD");
+        assert!(
+            updated[0].1.text.starts_with(&format!(
+                "~<{}|Standup>~\n🚫 Cancelled by <@U098ZY7XW> ",
+                LINK
+            )),
+            "{}",
+            updated[0].1.text
+        );
+        assert!(slack.posted().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_command_replies_are_replaced_through_the_response_url() {
+        let slack_hooks = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/commands/1/2"))
+            .and(body_partial_json(json!({
+                "replace_original": true,
+                "response_type": "in_channel",
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&slack_hooks)
+            .await;
+        let (state, pool) = test_state().await;
+        let meeting = announced(&state).await;
+        let response_url = format!("{}/commands/1/2", slack_hooks.uri());
+        record_announcement(&state, &payload("Standup", &response_url), &meeting).await;
+        let stored: String = sqlx::query_scalar("SELECT response_url FROM pending_responses")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(!stored.contains("/commands/1/2"), "{}", stored);
+
+        let update = update_announcement(&state, &meeting, Ending::Ended, "U012AB3CD")
+            .await
+            .unwrap();
+
+        assert_eq!(update, Update::Replaced);
+        // Used up, so a second stop can't post through it again
+        let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM pending_responses")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_response_urls_get_a_follow_up() {
+        let TestState {
+            mut state, slack, ..
+        } = Fixture::new().installed().build().await;
+        let clock = TestClock::new(state.clock.now());
+        state.clock = clock.clone();
+        let meeting = announced(&state).await;
+        record_announcement(
+            &state,
+            &payload("Standup", "https://hooks.slack.com/commands/1/2"),
+            &meeting,
+        )
+        .await;
+        clock.advance(RESPONSE_URL_LIFETIME);
+
+        let update = update_announcement(&state, &meeting, Ending::Cancelled, "U012AB3CD")
+            .await
+            .unwrap();
+
+        assert_eq!(update, Update::FollowedUp);
+        let posted = slack.posted();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].channel, "C012AB3CD");
+        assert!(
+            posted[0].text.starts_with(&format!(
+                "🚫 <{}|Standup> was cancelled by <@U012AB3CD> ",
+                LINK
+            )),
+            "{}",
+            posted[0].text
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unannounced_meetings_are_left_alone() {
+        let TestState { state, slack, .. } = Fixture::new().installed().build().await;
+        let meeting = announced(&state).await;
+        // Replies in threads aren't at the response URL
+        let mut in_thread = payload("Standup", "https://hooks.slack.com/commands/1/2");
+        in_thread.thread_ts = Some("1709628600.000200".to_string());
+        record_announcement(&state, &in_thread, &meeting).await;
+
+        let update = update_announcement(&state, &meeting, Ending::Ended, "U012AB3CD")
+            .await
+            .unwrap();
+
+        assert_eq!(update, Update::NotAnnounced);
+        assert!(slack.posted().is_empty() && slack.updated().is_empty());
+    }
+}
@@ -0,0 +1,354 @@
+//! Slow-request logging and a rolling error rate for slash commands.
+//!
+//! Time spent in each phase of a command is collected in a task-local while
+//! the command runs, so code deep in the call chain can report it without
+//! the timings being passed around. Outside [`with_phase_timing`] the
+//! reports are ignored.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+use crate::config::ObservabilityConfig;
+
+/// Width of the buckets the error-rate window is kept in.
+const BUCKET_WIDTH: Duration = Duration::from_secs(1);
+
+tokio::task_local! {
+    static PHASES: RefCell<PhaseTimings>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Checking the Slack signature, before the handler runs.
+    Verification,
+    Database,
+    /// Refreshing the user's Google token.
+    Refresh,
+    /// Calls to Google APIs other than the token refresh.
+    Google,
+}
+
+/// Time a request spent in each [`Phase`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PhaseTimings {
+    pub verification: Duration,
+    pub database: Duration,
+    pub refresh: Duration,
+    pub google: Duration,
+}
+
+impl PhaseTimings {
+    fn add(&mut self, phase: Phase, elapsed: Duration) {
+        let slot = match phase {
+            Phase::Verification => &mut self.verification,
+            Phase::Database => &mut self.database,
+            Phase::Refresh => &mut self.refresh,
+            Phase::Google => &mut self.google,
+        };
+        *slot += elapsed;
+    }
+
+    fn accounted(&self) -> Duration {
+        self.verification + self.database + self.refresh + self.google
+    }
+}
+
+/// Runs `request`, collecting what [`record_phase`] and [`timed`] report
+/// while it does.
+pub async fn with_phase_timing<F: Future>(request: F) -> (F::Output, PhaseTimings) {
+    PHASES
+        .scope(RefCell::new(PhaseTimings::default()), async {
+            let output = request.await;
+            (output, PHASES.with(|phases| *phases.borrow()))
+        })
+        .await
+}
+
+/// Adds `elapsed` to the current request's `phase`.
+pub fn record_phase(phase: Phase, elapsed: Duration) {
+    // Outside a timed request there is nothing to add to
+    let _ = PHASES.try_with(|phases| phases.borrow_mut().add(phase, elapsed));
+}
+
+/// Awaits `future`, counting the time it takes towards `phase`.
+pub async fn timed<F: Future>(phase: Phase, future: F) -> F::Output {
+    let started = Instant::now();
+    let output = future.await;
+    record_phase(phase, started.elapsed());
+    output
+}
+
+/// Logs a warning with the phase breakdown when `total` is over
+/// `threshold`, returning whether it did.
+pub fn warn_if_slow(
+    operation: &str,
+    total: Duration,
+    phases: &PhaseTimings,
+    threshold: Duration,
+) -> bool {
+    if total <= threshold {
+        return false;
+    }
+
+    warn!(
+        operation,
+        total_ms = total.as_millis() as u64,
+        verification_ms = phases.verification.as_millis() as u64,
+        database_ms = phases.database.as_millis() as u64,
+        refresh_ms = phases.refresh.as_millis() as u64,
+        google_ms = phases.google.as_millis() as u64,
+        other_ms = total.saturating_sub(phases.accounted()).as_millis() as u64,
+        "Slow {} took {}ms (threshold {}ms)",
+        operation,
+        total.as_millis(),
+        threshold.as_millis()
+    );
+    true
+}
+
+/// Requests and failures over the error-rate window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorRateSummary {
+    pub requests: u32,
+    pub failures: u32,
+}
+
+impl ErrorRateSummary {
+    pub fn ratio(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            f64::from(self.failures) / f64::from(self.requests)
+        }
+    }
+}
+
+/// The error rate crossing the threshold, in either direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorRateChange {
+    Exceeded(ErrorRateSummary),
+    Recovered(ErrorRateSummary),
+}
+
+#[derive(Debug)]
+struct Bucket {
+    start: Instant,
+    requests: u32,
+    failures: u32,
+}
+
+#[derive(Debug, Default)]
+struct Window {
+    buckets: VecDeque<Bucket>,
+    exceeded: bool,
+}
+
+/// Failure ratio of slash commands over the last few minutes.
+///
+/// Logs an `error!` summary once when the ratio goes over the threshold,
+/// and an `info!` once it is back under, rather than on every request in
+/// between.
+#[derive(Debug)]
+pub struct ErrorRateMonitor {
+    window: Duration,
+    threshold: f64,
+    min_requests: u32,
+    state: Mutex<Window>,
+}
+
+impl ErrorRateMonitor {
+    pub fn new(config: &ObservabilityConfig) -> Self {
+        Self {
+            window: config.error_rate_window,
+            threshold: config.error_rate_threshold,
+            min_requests: config.error_rate_min_requests,
+            state: Mutex::new(Window::default()),
+        }
+    }
+
+    /// Counts one finished request.
+    pub fn record(&self, failed: bool) {
+        match self.record_at(Instant::now(), failed) {
+            Some(ErrorRateChange::Exceeded(summary)) => error!(
+                tags.error_kind = "error_rate",
+                requests = summary.requests,
+                failures = summary.failures,
+                "{} of the last {} slash commands failed ({:.0}%) in the last {} minutes",
+                summary.failures,
+                summary.requests,
+                summary.ratio() * 100.0,
+                self.window.as_secs() / 60
+            ),
+            Some(ErrorRateChange::Recovered(summary)) => info!(
+                requests = summary.requests,
+                failures = summary.failures,
+                "Slash command error rate is back to {:.0}%",
+                summary.ratio() * 100.0
+            ),
+            None => {}
+        }
+    }
+
+    /// Counts a request finished at `now`, returning how the error rate
+    /// changed because of it, if it crossed the threshold.
+    pub fn record_at(&self, now: Instant, failed: bool) -> Option<ErrorRateChange> {
+        let mut state = self.state.lock().unwrap();
+
+        while let Some(oldest) = state.buckets.front() {
+            if now.saturating_duration_since(oldest.start) < self.window {
+                break;
+            }
+            state.buckets.pop_front();
+        }
+
+        match state.buckets.back_mut() {
+            Some(bucket) if now.saturating_duration_since(bucket.start) < BUCKET_WIDTH => {
+                bucket.requests += 1;
+                bucket.failures += u32::from(failed);
+            }
+            _ => state.buckets.push_back(Bucket {
+                start: now,
+                requests: 1,
+                failures: u32::from(failed),
+            }),
+        }
+
+        let summary = state.buckets.iter().fold(
+            ErrorRateSummary {
+                requests: 0,
+                failures: 0,
+            },
+            |sum, b| ErrorRateSummary {
+                requests: sum.requests + b.requests,
+                failures: sum.failures + b.failures,
+            },
+        );
+
+        let over = summary.requests >= self.min_requests && summary.ratio() >= self.threshold;
+        if over && !state.exceeded {
+            state.exceeded = true;
+            Some(ErrorRateChange::Exceeded(summary))
+        } else if !over && state.exceeded && summary.ratio() < self.threshold {
+            state.exceeded = false;
+            Some(ErrorRateChange::Recovered(summary))
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor() -> ErrorRateMonitor {
+        ErrorRateMonitor::new(&ObservabilityConfig {
+            error_rate_window: Duration::from_secs(5 * 60),
+            error_rate_threshold: 0.5,
+            error_rate_min_requests: 4,
+            ..ObservabilityConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_error_rate_alerts_once_when_crossed() {
+        let monitor = monitor();
+        let start = Instant::now();
+
+        // Below the minimum sample, even all failures aren't reported
+        for i in 0..3 {
+            assert_eq!(
+                monitor.record_at(start + Duration::from_secs(i), true),
+                None
+            );
+        }
+        assert_eq!(
+            monitor.record_at(start + Duration::from_secs(3), false),
+            Some(ErrorRateChange::Exceeded(ErrorRateSummary {
+                requests: 4,
+                failures: 3
+            }))
+        );
+        assert_eq!(
+            monitor.record_at(start + Duration::from_secs(4), true),
+            None
+        );
+
+        for i in 5..8 {
+            assert_eq!(
+                monitor.record_at(start + Duration::from_secs(i), false),
+                None
+            );
+        }
+        assert_eq!(
+            monitor.record_at(start + Duration::from_secs(8), false),
+            Some(ErrorRateChange::Recovered(ErrorRateSummary {
+                requests: 9,
+                failures: 4
+            }))
+        );
+    }
+
+    #[test]
+    fn test_old_failures_leave_the_window() {
+        let monitor = monitor();
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            monitor.record_at(start, true);
+        }
+        let later = start + Duration::from_secs(5 * 60);
+        for i in 0..4 {
+            assert_eq!(
+                monitor.record_at(later + Duration::from_millis(i), false),
+                None
+            );
+        }
+        // Only the recent successes count
+        assert_eq!(monitor.record_at(later, true), None);
+    }
+
+    #[test]
+    fn test_warns_only_over_threshold() {
+        let phases = PhaseTimings {
+            google: Duration::from_millis(1500),
+            ..PhaseTimings::default()
+        };
+        let threshold = Duration::from_millis(2000);
+
+        assert!(!warn_if_slow(
+            "test",
+            Duration::from_millis(1900),
+            &phases,
+            threshold
+        ));
+        assert!(warn_if_slow(
+            "test",
+            Duration::from_millis(2100),
+            &phases,
+            threshold
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_phases_are_collected_inside_the_scope() {
+        record_phase(Phase::Google, Duration::from_secs(1));
+
+        let ((), phases) = with_phase_timing(async {
+            record_phase(Phase::Verification, Duration::from_millis(5));
+            record_phase(Phase::Database, Duration::from_millis(10));
+            record_phase(Phase::Database, Duration::from_millis(15));
+            timed(Phase::Google, async {}).await;
+        })
+        .await;
+
+        assert_eq!(phases.verification, Duration::from_millis(5));
+        assert_eq!(phases.database, Duration::from_millis(25));
+        assert_eq!(phases.refresh, Duration::ZERO);
+        assert!(phases.google < Duration::from_secs(1));
+    }
+}
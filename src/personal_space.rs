@@ -0,0 +1,120 @@
+//! Standing rooms: with `reuse_personal_space` on, `/meet` gives a user the
+//! same Meet space every time instead of a new one, so colleagues can keep
+//! the link. The space is created the first time, and again when its owner
+//! deleted it at Google; `/meet new` makes a one-off meeting instead.
+
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::database::models::{OAuthToken, PersonalSpace, User};
+use crate::google::{CreatedMeeting, MeetingOptions};
+use crate::observability::{self, Phase};
+use crate::AppState;
+
+/// `true` in `user_settings` when the user reuses their space.
+pub const SETTING_KEY: &str = "reuse_personal_space";
+
+/// The flag, or subcommand, making a one-off meeting.
+pub const FLAG: &str = "new";
+
+/// How the user's standing space came about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Standing {
+    /// The space they had was still there.
+    Reused,
+    /// It is their first.
+    Created,
+    /// The one they had was deleted at Google.
+    Recreated,
+}
+
+impl Standing {
+    /// The note telling the user the link is their standing room.
+    pub fn note(self, command: &str) -> String {
+        match self {
+            Standing::Reused => format!(
+                "🏠 This is your standing room, the same link every time. `{} new` makes a one-off meeting.",
+                command
+            ),
+            Standing::Created => format!(
+                "🏠 This is your standing room: `{0}` gives you this link from now on, and `{0} new` makes a one-off meeting.",
+                command
+            ),
+            Standing::Recreated => format!(
+                "🏠 Your standing room was deleted at Google, so this is a new one with a new link. `{}` gives you this link from now on.",
+                command
+            ),
+        }
+    }
+}
+
+/// Whether `user` turned `reuse_personal_space` on. Failing to look it up
+/// makes a new meeting as usual.
+pub async fn enabled(state: &AppState, user: &User) -> bool {
+    let setting = observability::timed(
+        Phase::Database,
+        state.db.get_user_setting(user.id, SETTING_KEY),
+    )
+    .await;
+    match setting {
+        Ok(value) => value.as_deref() == Some("true"),
+        Err(e) => {
+            warn!(
+                "Failed to look up whether user {} reuses their space: {:#}",
+                user.id, e
+            );
+            false
+        }
+    }
+}
+
+/// The standing space of `user`: the one they have while Google still has
+/// it, and otherwise a new one created with `token` and `options`. A new
+/// space that can't be stored is still returned; the next `/meet` makes
+/// another.
+pub async fn space(
+    state: &AppState,
+    token: &OAuthToken,
+    user: &User,
+    options: &MeetingOptions,
+) -> Result<(CreatedMeeting, Standing)> {
+    let stored = observability::timed(Phase::Database, state.db.personal_space(user.id)).await?;
+    let standing = match stored {
+        Some(stored) => {
+            let current = state
+                .google
+                .get_space(&token.access_token, &stored.space_name)
+                .await?;
+            if let Some(space) = current {
+                return Ok((space, Standing::Reused));
+            }
+            info!(
+                "Standing space {} of user {} was deleted, creating another",
+                stored.space_name, user.id
+            );
+            Standing::Recreated
+        }
+        None => Standing::Created,
+    };
+
+    let created = state
+        .google
+        .create_meeting(&token.access_token, options)
+        .await?;
+    let space = PersonalSpace {
+        meet_link: created.meeting_uri.clone(),
+        space_name: created.name.clone(),
+    };
+    if let Err(e) = observability::timed(
+        Phase::Database,
+        state.db.set_personal_space(user.id, &space),
+    )
+    .await
+    {
+        warn!(
+            "Failed to store the standing space {} of user {}: {:#}",
+            created.name, user.id, e
+        );
+    }
+    Ok((created, standing))
+}
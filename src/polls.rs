@@ -0,0 +1,504 @@
+//! Scheduling polls from `/meet poll`. The poll's message has a button per
+//! offered time; every vote replaces the message through the click's
+//! response URL, so the tally stays live, and the poll's creator books the
+//! winning time with a button that runs `/meet` for it.
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use tracing::{info, warn};
+
+use crate::database::models::{Poll, PollVote};
+use crate::error::{AppError, INTERNAL_ERROR_TEXT};
+use crate::handlers::slack::{post_followup, run_detached, SlackResponse, SlashCommandPayload};
+use crate::observability::{self, Phase};
+use crate::slack::blocks::{self, Block, Button, ButtonStyle, Text};
+use crate::timezones;
+use crate::AppState;
+
+/// Action id of the button voting for a time; its value is
+/// `{poll_id}:{option}`.
+pub const VOTE_ACTION: &str = "poll_vote";
+
+/// Action id of the button booking the winning time; its value is the
+/// poll's id.
+pub const BOOK_ACTION: &str = "poll_book";
+
+/// Most voters named under a time; the rest are only counted.
+const MAX_NAMED_VOTERS: usize = 20;
+
+/// The option of `poll` with the most votes, the earliest time on a tie;
+/// `None` before anyone has voted.
+pub fn winner(poll: &Poll, votes: &[PollVote]) -> Option<usize> {
+    let tally = tally(poll, votes);
+    (0..poll.options.len())
+        .filter(|&option| tally[option] > 0)
+        .max_by(|&a, &b| {
+            tally[a]
+                .cmp(&tally[b])
+                .then(poll.options[b].cmp(&poll.options[a]))
+        })
+}
+
+/// Votes per option of `poll`.
+fn tally(poll: &Poll, votes: &[PollVote]) -> Vec<usize> {
+    let mut tally = vec![0; poll.options.len()];
+    for vote in votes {
+        if let Some(count) = tally.get_mut(vote.option) {
+            *count += 1;
+        }
+    }
+    tally
+}
+
+/// The message showing `poll` with `votes`: buttons to vote and book while
+/// it is open, and the booked time once it isn't.
+pub fn message(poll: &Poll, votes: &[PollVote]) -> SlackResponse {
+    let id = poll.id.unwrap_or_default();
+    let title = poll.title.as_deref().map(blocks::title);
+    let booked = poll.booked();
+
+    let headline = match (booked, &title) {
+        (None, Some(title)) => format!("🗳️ *{}*: when should we meet?", title),
+        (None, None) => "🗳️ When should we meet?".to_string(),
+        (Some(at), Some(title)) => format!("✅ *{}* is booked for {}.", title, blocks::date(at)),
+        (Some(at), None) => format!("✅ Booked for {}.", blocks::date(at)),
+    };
+    let asked = match booked {
+        None => format!(
+            "<@{}> is asking. Vote for the time that suits you best; voting again changes your vote.",
+            poll.slack_user_id
+        ),
+        Some(_) => format!(
+            "<@{}> booked the time with the most votes.",
+            poll.slack_user_id
+        ),
+    };
+
+    let mut builder = SlackResponse::builder()
+        .in_channel()
+        .text(headline.clone())
+        .block(Block::section(Text::mrkdwn(format!(
+            "{}\n{}",
+            headline, asked
+        ))));
+    for (option, at) in poll.options.iter().enumerate() {
+        let voters: Vec<&str> = votes
+            .iter()
+            .filter(|vote| vote.option == option)
+            .map(|vote| vote.slack_user_id.as_str())
+            .collect();
+        let mark = if poll.booked_option == Some(option) {
+            "✅ "
+        } else {
+            ""
+        };
+        let text = Text::mrkdwn(format!(
+            "{}*{}*\n{}",
+            mark,
+            blocks::date(*at),
+            voters_line(&voters)
+        ));
+        builder = builder.block(match booked {
+            None => Block::section_with_button(
+                text,
+                Button::action(VOTE_ACTION, "Vote", format!("{}:{}", id, option)),
+            ),
+            Some(_) => Block::section(text),
+        });
+    }
+    if booked.is_none() {
+        builder = builder
+            .block(Block::actions(vec![Button::action(
+                BOOK_ACTION,
+                "Book winning time",
+                id.to_string(),
+            )
+            .style(ButtonStyle::Primary)]))
+            .block(Block::context(vec![Text::mrkdwn(format!(
+                "Only <@{}> can book. A tie goes to the earliest time.",
+                poll.slack_user_id
+            ))]));
+    }
+    builder.build_or_text()
+}
+
+/// The count and names of those who voted for a time.
+fn voters_line(voters: &[&str]) -> String {
+    if voters.is_empty() {
+        return "No votes".to_string();
+    }
+    let mut names: Vec<String> = voters
+        .iter()
+        .take(MAX_NAMED_VOTERS)
+        .map(|user_id| format!("<@{}>", user_id))
+        .collect();
+    if voters.len() > MAX_NAMED_VOTERS {
+        names.push(format!("and {} more", voters.len() - MAX_NAMED_VOTERS));
+    }
+    format!("`{}` {}", voters.len(), names.join(" "))
+}
+
+/// Handles a click on the poll button `action_id` carrying `value`, by
+/// `payload.user_id`. The answer goes to the response URL of the poll's
+/// message; booking then runs `/meet` for the booked time as the creator.
+pub async fn handle_click(
+    state: AppState,
+    payload: SlashCommandPayload,
+    action_id: String,
+    value: String,
+) {
+    let clicked = match action_id.as_str() {
+        VOTE_ACTION => vote(&state, &payload, &value)
+            .await
+            .map(|reply| (reply, None)),
+        BOOK_ACTION => book(&state, &payload, &value).await,
+        _ => {
+            warn!("Ignoring unknown button {}", action_id);
+            return;
+        }
+    };
+    let (reply, create) = clicked.unwrap_or_else(|e| {
+        e.log();
+        let text = e
+            .slack_text()
+            .unwrap_or_else(|| INTERNAL_ERROR_TEXT.to_string());
+        (SlackResponse::ephemeral(text), None)
+    });
+
+    post_followup(state.http.clone(), payload.response_url.clone(), reply).await;
+    if let Some(create) = create {
+        if let Err(e) = run_detached(state, create) {
+            e.log();
+        }
+    }
+}
+
+/// Records a vote, valued `{poll_id}:{option}`, and answers with the
+/// poll's message with the new tally, replacing the old one.
+async fn vote(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<SlackResponse, AppError> {
+    state.validator.validate_slack_user_id(&payload.user_id)?;
+    let (poll_id, option) = value
+        .split_once(':')
+        .and_then(|(poll_id, option)| Some((poll_id.parse().ok()?, option.parse().ok()?)))
+        .ok_or_else(|| AppError::BadRequest(format!("malformed poll vote {}", value)))?;
+    let Some(poll) = find_poll(state, poll_id).await? else {
+        return Ok(gone());
+    };
+    if option >= poll.options.len() {
+        return Err(AppError::BadRequest(format!(
+            "vote for option {} of poll {} with {} options",
+            option,
+            poll_id,
+            poll.options.len()
+        )));
+    }
+
+    let recorded = observability::timed(
+        Phase::Database,
+        state.db.record_poll_vote(poll_id, &payload.user_id, option),
+    )
+    .await?;
+    if !recorded {
+        return Ok(closed(&poll));
+    }
+    info!("User {} voted in poll {}", payload.user_id, poll_id);
+    current_message(state, &poll).await
+}
+
+/// Books the winning time of the poll valued `value`, if the creator
+/// clicked: answers with the closed poll's message and the `/meet` run
+/// creating the meeting. Anyone else is told only the creator can.
+async fn book(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    value: &str,
+) -> Result<(SlackResponse, Option<SlashCommandPayload>), AppError> {
+    let poll_id = value
+        .parse()
+        .map_err(|_| AppError::BadRequest(format!("malformed poll booking {}", value)))?;
+    let Some(mut poll) = find_poll(state, poll_id).await? else {
+        return Ok((gone(), None));
+    };
+    if payload.user_id != poll.slack_user_id {
+        return Ok((
+            SlackResponse::ephemeral(format!(
+                "🗳️ Only <@{}>, who started the poll, can book a time.",
+                poll.slack_user_id
+            )),
+            None,
+        ));
+    }
+    if poll.booked_option.is_some() {
+        return Ok((closed(&poll), None));
+    }
+
+    let votes = observability::timed(Phase::Database, state.db.poll_votes(poll_id)).await?;
+    let Some(option) = winner(&poll, &votes) else {
+        return Ok((
+            SlackResponse::ephemeral(
+                "🗳️ Nobody has voted yet, so there's no time to book.".to_string(),
+            ),
+            None,
+        ));
+    };
+    let booked = observability::timed(Phase::Database, state.db.book_poll(poll_id, option)).await?;
+    if !booked {
+        // Booked by a click a moment ago
+        return Ok((closed(&poll), None));
+    }
+    poll.booked_option = Some(option);
+    info!("Poll {} booked option {}", poll_id, option);
+
+    // Only the creator books, so `/meet` reads the time where they are
+    let tz = timezones::of_user(state, &payload.team_id, &payload.user_id).await?;
+    let mut create = payload.clone();
+    create.text = Some(create_text(
+        poll.title.as_deref(),
+        poll.options[option].with_timezone(&tz),
+    ));
+    let mut reply = message(&poll, &votes);
+    reply.replace_original = true;
+    Ok((reply, Some(create)))
+}
+
+async fn find_poll(state: &AppState, poll_id: i64) -> Result<Option<Poll>, AppError> {
+    Ok(observability::timed(Phase::Database, state.db.get_poll(poll_id)).await?)
+}
+
+/// The poll's message with the votes it has now, replacing the old one.
+async fn current_message(state: &AppState, poll: &Poll) -> Result<SlackResponse, AppError> {
+    let poll_id = poll.id.unwrap_or_default();
+    let votes = observability::timed(Phase::Database, state.db.poll_votes(poll_id)).await?;
+    let mut reply = message(poll, &votes);
+    reply.replace_original = true;
+    Ok(reply)
+}
+
+fn gone() -> SlackResponse {
+    SlackResponse::ephemeral("🗳️ That poll no longer exists.".to_string())
+}
+
+fn closed(poll: &Poll) -> SlackResponse {
+    SlackResponse::ephemeral(match poll.booked() {
+        Some(at) => format!(
+            "🗳️ Voting is closed: the poll was booked for {}.",
+            blocks::date(at)
+        ),
+        None => "🗳️ Voting is closed.".to_string(),
+    })
+}
+
+/// The `/meet` text creating the meeting booked for `at`, told in the
+/// timezone `/meet` reads it in.
+fn create_text(title: Option<&str>, at: DateTime<Tz>) -> String {
+    let when = at.format("--at %Y-%m-%d %H:%M");
+    match title {
+        // The poll's title came from one kind of quotes, so the other one
+        // holds it
+        Some(title) if title.contains('"') => format!("“{}” {}", title, when),
+        Some(title) => format!("\"{}\" {}", title, when),
+        None => when.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::{connected_user, payload, test_state, RESPONSE_URL};
+    use crate::database::models::User;
+    use chrono::Utc;
+
+    fn vote_of(slack_user_id: &str, option: usize) -> PollVote {
+        PollVote {
+            slack_user_id: slack_user_id.to_string(),
+            option,
+        }
+    }
+
+    /// State with a poll by U012AB3CD offering 14:00 and 16:00.
+    async fn state() -> (AppState, User, Poll) {
+        let (state, _pool) = test_state().await;
+        let user = connected_user(&state).await;
+        let poll = state
+            .db
+            .create_poll(&Poll::new(
+                &user,
+                "T012AB3C4".to_string(),
+                "C012AB3CD".to_string(),
+                Some("Retro".to_string()),
+                vec![
+                    "2030-06-03T14:00:00Z".parse().unwrap(),
+                    "2030-06-03T16:00:00Z".parse().unwrap(),
+                ],
+            ))
+            .await
+            .unwrap();
+        (state, user, poll)
+    }
+
+    /// A click's payload from `user_id`.
+    fn click(user_id: &str) -> SlashCommandPayload {
+        SlashCommandPayload {
+            user_id: user_id.to_string(),
+            text: None,
+            ..payload("", RESPONSE_URL)
+        }
+    }
+
+    #[test]
+    fn test_winner_has_most_votes_and_is_earliest_on_a_tie() {
+        let mut poll = Poll {
+            id: Some(1),
+            user_id: 1,
+            slack_user_id: "U012AB3CD".to_string(),
+            slack_team_id: "T012AB3C4".to_string(),
+            channel_id: "C012AB3CD".to_string(),
+            title: None,
+            // Not in order, as given
+            options: vec![
+                "2030-06-04T10:00:00Z".parse().unwrap(),
+                "2030-06-03T16:00:00Z".parse().unwrap(),
+                "2030-06-03T14:00:00Z".parse().unwrap(),
+            ],
+            booked_option: None,
+            created_at: None,
+        };
+
+        assert_eq!(winner(&poll, &[]), None);
+        let votes = [vote_of("U1", 0), vote_of("U2", 0), vote_of("U3", 1)];
+        assert_eq!(winner(&poll, &votes), Some(0));
+        let votes = [vote_of("U1", 0), vote_of("U2", 1), vote_of("U3", 2)];
+        assert_eq!(winner(&poll, &votes), Some(2));
+
+        poll.booked_option = Some(2);
+        assert_eq!(poll.booked(), Some(poll.options[2]));
+    }
+
+    #[test]
+    fn test_booked_time_is_told_where_the_creator_is() {
+        let at: DateTime<Utc> = "2030-06-03T14:00:00Z".parse().unwrap();
+        assert_eq!(
+            create_text(Some("Retro"), at.with_timezone(&Tz::Europe__Warsaw)),
+            "\"Retro\" --at 2030-06-03 16:00"
+        );
+        assert_eq!(
+            create_text(None, at.with_timezone(&Tz::America__New_York)),
+            "--at 2030-06-03 10:00"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_votes_are_one_per_user_and_changeable() {
+        let (state, _user, poll) = state().await;
+        let id = poll.id.unwrap();
+
+        vote(&state, &click("U0000B0B1"), &format!("{}:0", id))
+            .await
+            .unwrap();
+        // Clicking again keeps one vote
+        vote(&state, &click("U0000B0B1"), &format!("{}:0", id))
+            .await
+            .unwrap();
+        let reply = vote(&state, &click("U0000A001"), &format!("{}:0", id))
+            .await
+            .unwrap();
+        assert!(reply.replace_original);
+        assert!(reply.text.contains("*Retro*: when should we meet?"));
+        let tallied = serde_json::to_string(&reply.blocks).unwrap();
+        assert!(
+            tallied.contains("`2` <@U0000B0B1> <@U0000A001>")
+                || tallied.contains("`2` <@U0000A001> <@U0000B0B1>"),
+            "{}",
+            tallied
+        );
+
+        // Voting for another time moves the vote
+        let reply = vote(&state, &click("U0000B0B1"), &format!("{}:1", id))
+            .await
+            .unwrap();
+        let tallied = serde_json::to_string(&reply.blocks).unwrap();
+        assert!(tallied.contains("`1` <@U0000A001>"), "{}", tallied);
+        assert!(tallied.contains("`1` <@U0000B0B1>"), "{}", tallied);
+        let mut votes = state.db.poll_votes(id).await.unwrap();
+        votes.sort_by(|a, b| a.slack_user_id.cmp(&b.slack_user_id));
+        assert_eq!(votes, [vote_of("U0000A001", 0), vote_of("U0000B0B1", 1)]);
+
+        assert!(vote(&state, &click("U0000B0B1"), &format!("{}:2", id))
+            .await
+            .is_err());
+        let reply = vote(&state, &click("U0000B0B1"), &format!("{}:0", id + 1))
+            .await
+            .unwrap();
+        assert_eq!(reply.text, "🗳️ That poll no longer exists.");
+    }
+
+    #[tokio::test]
+    async fn test_only_the_creator_books_the_winner() {
+        let (state, _user, poll) = state().await;
+        let id = poll.id.unwrap();
+
+        let (reply, create) = book(&state, &click("U012AB3CD"), &id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            reply.text,
+            "🗳️ Nobody has voted yet, so there's no time to book."
+        );
+        assert!(create.is_none());
+
+        vote(&state, &click("U0000B0B1"), &format!("{}:1", id))
+            .await
+            .unwrap();
+        let (reply, create) = book(&state, &click("U0000B0B1"), &id.to_string())
+            .await
+            .unwrap();
+        assert_eq!(
+            reply.text,
+            "🗳️ Only <@U012AB3CD>, who started the poll, can book a time."
+        );
+        assert!(create.is_none());
+
+        let (reply, create) = book(&state, &click("U012AB3CD"), &id.to_string())
+            .await
+            .unwrap();
+        assert!(reply.replace_original);
+        assert_eq!(reply.response_type, "in_channel");
+        assert!(reply
+            .text
+            .starts_with("✅ *Retro* is booked for <!date^1906732800^"));
+        let buttons: Vec<&Button> = reply
+            .blocks
+            .iter()
+            .flatten()
+            .flat_map(Block::buttons)
+            .collect();
+        assert!(buttons.is_empty());
+        let create = create.unwrap();
+        assert_eq!(create.user_id, "U012AB3CD");
+        assert_eq!(
+            create.text.as_deref(),
+            Some("\"Retro\" --at 2030-06-03 16:00")
+        );
+        assert!(matches!(
+            crate::command_parser::parse(create.text.as_deref().unwrap()),
+            Ok(crate::command_parser::MeetCommand::Create { title: Some(title), start: Some(_), .. })
+                if title == "Retro"
+        ));
+
+        // Booked once; votes are closed from then on
+        let (reply, create) = book(&state, &click("U012AB3CD"), &id.to_string())
+            .await
+            .unwrap();
+        assert!(reply.text.starts_with("🗳️ Voting is closed"));
+        assert!(create.is_none());
+        let reply = vote(&state, &click("U0000A001"), &format!("{}:0", id))
+            .await
+            .unwrap();
+        assert!(!reply.replace_original);
+        assert!(reply.text.starts_with("🗳️ Voting is closed"));
+    }
+}
@@ -0,0 +1,221 @@
+//! QR codes of meeting links, posted with `/meet --qr` for channels shown
+//! on conference-room displays, so people can join from their phones.
+//!
+//! A slash command's reply doesn't tell the bot which message it became,
+//! so the code goes in the thread the command was run in, or otherwise
+//! into the channel right after the announcement.
+
+use anyhow::{Context, Result};
+use image::{ImageFormat, Luma};
+use qrcode::QrCode;
+use std::io::Cursor;
+use tracing::{info, warn};
+
+use crate::database::models::{Meeting, MeetingVisibility};
+use crate::handlers::slack::{deliver, SlackResponse, SlashCommandPayload};
+use crate::slack::api::ImageUpload;
+use crate::AppState;
+
+/// The flag asking for a QR code.
+pub const FLAG: &str = "qr";
+
+/// Pixels on each side of a module, the code's smallest square. A Meet
+/// link needs 29 modules across, so the image is a few hundred pixels
+/// wide: enough for a phone to read it off a screen across the room.
+const MODULE_PIXELS: u32 = 10;
+
+const FILENAME: &str = "meeting-qr.png";
+
+const QUIET_NOTE: &str =
+    "📷 QR codes are only posted for meetings shared in the channel, so I didn't post one.";
+const MISSING_SCOPE_NOTE: &str = "📷 I couldn't post a QR code: the bot needs the `files:write` scope, which your Slack admins can add by reinstalling it. Join with the link instead.";
+const NOT_INSTALLED_NOTE: &str = "📷 I couldn't post a QR code: the bot isn't installed in this workspace, so it can't upload files. Join with the link instead.";
+const UPLOAD_FAILED_NOTE: &str =
+    "📷 I couldn't post a QR code for this meeting. Join with the link instead.";
+
+/// A PNG of the QR code of `text`, black on white with the quiet zone
+/// around it that scanners need.
+pub fn png(text: &str) -> Result<Vec<u8>> {
+    let code = QrCode::new(text.as_bytes()).context("text too long for a QR code")?;
+    let image = code
+        .render::<Luma<u8>>()
+        .module_dimensions(MODULE_PIXELS, MODULE_PIXELS)
+        .build();
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .context("failed to encode the QR code")?;
+    Ok(bytes)
+}
+
+/// Posts the QR code of `meeting`'s link where the command was run. When
+/// that can't be done, the user is told so in a note only they see, and
+/// the announcement's link is all there is.
+pub async fn share(state: &AppState, payload: &SlashCommandPayload, meeting: &Meeting) {
+    if let Err(note) = upload(state, payload, meeting).await {
+        deliver(state, payload, SlackResponse::ephemeral(note.to_string())).await;
+    }
+}
+
+/// Uploads the QR code, or gives the note saying why it wasn't.
+async fn upload(
+    state: &AppState,
+    payload: &SlashCommandPayload,
+    meeting: &Meeting,
+) -> Result<(), &'static str> {
+    // Posting it would share a quiet meeting with the channel
+    if meeting.visibility == MeetingVisibility::Quiet {
+        return Err(QUIET_NOTE);
+    }
+    let team = match state.db.get_slack_team(&payload.team_id).await {
+        Ok(Some(team)) => team,
+        Ok(None) => return Err(NOT_INSTALLED_NOTE),
+        Err(e) => {
+            warn!(
+                "Failed to look up the bot token of team {}: {:#}",
+                payload.team_id, e
+            );
+            return Err(UPLOAD_FAILED_NOTE);
+        }
+    };
+    let bytes = png(&meeting.meet_link).map_err(|e| {
+        warn!(
+            "Failed to make the QR code of {}: {:#}",
+            meeting.meet_link, e
+        );
+        UPLOAD_FAILED_NOTE
+    })?;
+
+    let image = ImageUpload {
+        channel_id: payload.channel_id.clone(),
+        thread_ts: payload.thread_ts.clone(),
+        filename: FILENAME.to_string(),
+        title: meeting
+            .title
+            .clone()
+            .unwrap_or_else(|| "Google Meet".to_string()),
+        initial_comment: format!("📷 Scan to join {}", meeting.meet_link),
+        bytes,
+    };
+    match state.slack.upload_image(&team.bot_token, &image).await {
+        Ok(()) => {
+            info!("Posted the QR code of {}", meeting.meet_link);
+            Ok(())
+        }
+        Err(e) if e.is_missing_scope() => {
+            warn!(
+                "Team {} hasn't given the bot the files:write scope",
+                payload.team_id
+            );
+            Err(MISSING_SCOPE_NOTE)
+        }
+        Err(e) => {
+            warn!(
+                "Failed to post the QR code in channel {}: {}",
+                payload.channel_id, e
+            );
+            Err(UPLOAD_FAILED_NOTE)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::{payload, Fixture, TestState, RESPONSE_URL};
+    use crate::database::models::MeetLinkKind;
+    use crate::slack::fake::FakeSlackApi;
+    use qrcode::Color;
+    use std::sync::Arc;
+
+    const LINK: &str = "https://meet.google.com/abc-defg-hij";
+
+    #[test]
+    fn test_png_decodes_back_to_the_code() {
+        let bytes = png(LINK).unwrap();
+        let image = image::load_from_memory_with_format(&bytes, ImageFormat::Png)
+            .unwrap()
+            .to_luma8();
+
+        let code = QrCode::new(LINK).unwrap();
+        let quiet_zone = 4;
+        let side = (code.width() as u32 + 2 * quiet_zone) * MODULE_PIXELS;
+        assert_eq!(image.dimensions(), (side, side));
+        // The centre pixel of every module has the module's colour
+        for y in 0..code.width() {
+            for x in 0..code.width() {
+                let centre = |i: usize| (i as u32 + quiet_zone) * MODULE_PIXELS + MODULE_PIXELS / 2;
+                let dark = image.get_pixel(centre(x), centre(y))[0] < 128;
+                assert_eq!(dark, code[(x, y)] == Color::Dark, "module ({}, {})", x, y);
+            }
+        }
+        // The quiet zone is white
+        assert_eq!(image.get_pixel(0, 0)[0], 255);
+    }
+
+    fn meeting() -> Meeting {
+        Meeting::new(
+            1,
+            LINK.to_string(),
+            Some("Room 4 standup".to_string()),
+            MeetLinkKind::Meet,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_code_is_uploaded_where_the_command_ran() {
+        let slack = Arc::new(FakeSlackApi::new());
+        let state = Fixture::new()
+            .slack(slack.clone())
+            .installed()
+            .build()
+            .await
+            .state;
+        let mut payload = payload("--qr Room 4 standup", RESPONSE_URL);
+        payload.thread_ts = Some("1709628600.000200".to_string());
+
+        share(&state, &payload, &meeting()).await;
+
+        let images = slack.images();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].channel_id, "C012AB3CD");
+        assert_eq!(images[0].thread_ts.as_deref(), Some("1709628600.000200"));
+        assert_eq!(images[0].title, "Room 4 standup");
+        assert!(images[0].initial_comment.contains(LINK));
+        assert_eq!(images[0].bytes, png(LINK).unwrap());
+        assert!(slack.ephemeral().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_missing_scope_falls_back_to_a_note() {
+        let slack = Arc::new(FakeSlackApi::new());
+        slack.fail_channel("C012AB3CD", "missing_scope");
+        let state = Fixture::new()
+            .slack(slack.clone())
+            .installed()
+            .build()
+            .await
+            .state;
+        let payload = payload("--qr", RESPONSE_URL);
+
+        assert_eq!(
+            upload(&state, &payload, &meeting()).await,
+            Err(MISSING_SCOPE_NOTE)
+        );
+        assert!(slack.images().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_no_code_for_quiet_or_uninstalled() {
+        let TestState { state, slack, .. } = Fixture::new().build().await;
+        let payload = payload("--qr", RESPONSE_URL);
+
+        assert_eq!(
+            upload(&state, &payload, &meeting()).await,
+            Err(NOT_INSTALLED_NOTE)
+        );
+        let quiet = meeting().with_visibility(MeetingVisibility::Quiet);
+        assert_eq!(upload(&state, &payload, &quiet).await, Err(QUIET_NOTE));
+        assert!(slack.images().is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+//! A cap on the meetings a workspace creates each day, set by the bot's
+//! admins with `/meet set team daily-meeting-limit`. Days start at midnight
+//! in the workspace's timezone (`/meet set team timezone`, UTC by default);
+//! without a limit a workspace creates as many meetings as it likes.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, LocalResult, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::database::Database;
+use crate::observability::{self, Phase};
+use crate::telemetry::metrics;
+use crate::AppState;
+
+/// The workspace's daily limit in `team_settings`, as [`DailyLimit`]
+/// shows it.
+pub const LIMIT_KEY: &str = "daily_meeting_limit";
+
+/// The workspace's IANA timezone in `team_settings`, where its days start.
+pub const TIMEZONE_KEY: &str = "timezone";
+
+/// Word after the number exempting the bot's admins from the limit.
+pub const EXEMPT_ADMINS: &str = "exempt-admins";
+
+/// How many meetings a workspace may create each day.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DailyLimit {
+    pub meetings: u32,
+    /// Whether the bot's admins may create meetings past it.
+    pub exempt_admins: bool,
+}
+
+/// Reads `20`, or `20 exempt-admins`.
+impl FromStr for DailyLimit {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+        let meetings = words.next().ok_or(())?.parse().map_err(|_| ())?;
+        let exempt_admins = match words.next() {
+            None => false,
+            Some(word) if word.eq_ignore_ascii_case(EXEMPT_ADMINS) => true,
+            Some(_) => return Err(()),
+        };
+        if words.next().is_some() {
+            return Err(());
+        }
+        Ok(Self {
+            meetings,
+            exempt_admins,
+        })
+    }
+}
+
+impl fmt::Display for DailyLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.meetings)?;
+        if self.exempt_admins {
+            write!(f, " {}", EXEMPT_ADMINS)?;
+        }
+        Ok(())
+    }
+}
+
+/// The daily limit of `slack_team_id`; `None` without a setting or with
+/// one that no longer parses.
+pub async fn for_team(db: &Database, slack_team_id: &str) -> Result<Option<DailyLimit>> {
+    Ok(db
+        .get_team_setting(slack_team_id, LIMIT_KEY)
+        .await?
+        .and_then(|value| value.parse().ok()))
+}
+
+/// The timezone of `slack_team_id`; UTC without a setting or with one that
+/// no longer parses.
+pub async fn team_timezone(db: &Database, slack_team_id: &str) -> Result<Tz> {
+    Ok(db
+        .get_team_setting(slack_team_id, TIMEZONE_KEY)
+        .await?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(Tz::UTC))
+}
+
+/// When the day `now` is in started in `tz`.
+pub fn day_start(now: DateTime<Utc>, tz: Tz) -> DateTime<Utc> {
+    midnight(now.with_timezone(&tz).date_naive(), tz)
+}
+
+/// When `day` starts in `tz`. On the few days a timezone skips midnight
+/// for summer time, the day starts at its first hour.
+pub fn midnight(day: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    let midnight = day.and_time(NaiveTime::MIN);
+    let start = match tz.from_local_datetime(&midnight) {
+        LocalResult::Single(start) | LocalResult::Ambiguous(start, _) => start,
+        LocalResult::None => tz
+            .from_local_datetime(&(midnight + Duration::hours(1)))
+            .earliest()
+            .unwrap_or_else(|| tz.from_utc_datetime(&midnight)),
+    };
+    start.with_timezone(&Utc)
+}
+
+/// Whether `slack_user_id` may not create another meeting in
+/// `slack_team_id` today. A limit that can't be looked up holds no one
+/// back.
+pub async fn reached(state: &AppState, slack_team_id: &str, slack_user_id: &str) -> bool {
+    match observability::timed(
+        Phase::Database,
+        count_against(state, slack_team_id, slack_user_id),
+    )
+    .await
+    {
+        Ok(Some((today, limit))) if today >= i64::from(limit.meetings) => {
+            info!(
+                "Team {} reached its daily limit of {} meetings, refusing {}",
+                slack_team_id, limit.meetings, slack_user_id
+            );
+            metrics::record_rate_limit_block("team_daily");
+            true
+        }
+        Ok(_) => false,
+        Err(e) => {
+            warn!(
+                "Failed to check the daily meeting limit of team {}: {:#}",
+                slack_team_id, e
+            );
+            false
+        }
+    }
+}
+
+/// The workspace's meetings so far today and its limit, when it has one
+/// that applies to `slack_user_id`.
+async fn count_against(
+    state: &AppState,
+    slack_team_id: &str,
+    slack_user_id: &str,
+) -> Result<Option<(i64, DailyLimit)>> {
+    let Some(limit) = for_team(&state.db, slack_team_id).await? else {
+        return Ok(None);
+    };
+    if limit.exempt_admins
+        && state
+            .config
+            .admin
+            .slack_users
+            .iter()
+            .any(|admin| admin == slack_user_id)
+    {
+        return Ok(None);
+    }
+    let tz = team_timezone(&state.db, slack_team_id).await?;
+    let since = day_start(state.clock.now(), tz);
+    let today = state
+        .db
+        .count_team_meetings_since(slack_team_id, since.naive_utc())
+        .await?;
+    Ok(Some((today, limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::test_state;
+    use crate::database::models::{MeetLinkKind, Meeting};
+    use chrono_tz::{America, Asia};
+
+    fn at(utc: &str) -> DateTime<Utc> {
+        utc.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parses_and_shows_limits() {
+        assert_eq!(
+            "20".parse(),
+            Ok(DailyLimit {
+                meetings: 20,
+                exempt_admins: false
+            })
+        );
+        let exempt: DailyLimit = "5 Exempt-Admins".parse().unwrap();
+        assert!(exempt.exempt_admins);
+        assert_eq!(exempt.to_string(), "5 exempt-admins");
+        for bad in ["", "-1", "five", "5 admins", "5 exempt-admins now"] {
+            assert_eq!(bad.parse::<DailyLimit>(), Err(()), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_days_start_at_midnight_where_the_workspace_is() {
+        assert_eq!(
+            day_start(at("2030-06-04T23:59:59Z"), Tz::UTC),
+            at("2030-06-04T00:00:00Z")
+        );
+        assert_eq!(
+            day_start(at("2030-06-05T00:00:00Z"), Tz::UTC),
+            at("2030-06-05T00:00:00Z")
+        );
+
+        // Tokyo is UTC+9: 14:59 UTC is 23:59 there, 15:00 the next day
+        assert_eq!(
+            day_start(at("2030-06-04T14:59:00Z"), Asia::Tokyo),
+            at("2030-06-03T15:00:00Z")
+        );
+        assert_eq!(
+            day_start(at("2030-06-04T15:00:00Z"), Asia::Tokyo),
+            at("2030-06-04T15:00:00Z")
+        );
+
+        // Chile's summer time starts at midnight on 8 September 2024, so
+        // that day starts at 1:00 (UTC-3)
+        assert_eq!(
+            day_start(at("2024-09-08T15:00:00Z"), America::Santiago),
+            at("2024-09-08T04:00:00Z")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_counts_the_workspace_meetings_of_the_day() {
+        let (state, pool) = test_state().await;
+        let user = state
+            .db
+            .create_user("U0000A001", "T012AB3C4")
+            .await
+            .unwrap();
+        let other = state
+            .db
+            .create_user("U0000B0B1", "T0OTHER01")
+            .await
+            .unwrap();
+        for (user, team, created_at) in [
+            (&user, "T012AB3C4", "2030-06-04 14:59:59"),
+            (&user, "T012AB3C4", "2030-06-04 15:00:00"),
+            (&user, "T012AB3C4", "2030-06-04 20:00:00"),
+            (&other, "T0OTHER01", "2030-06-04 20:00:00"),
+        ] {
+            let meeting = state
+                .db
+                .create_meeting(
+                    &Meeting::new(
+                        user.id,
+                        "https://meet.google.com/abc-defg-hij".into(),
+                        None,
+                        MeetLinkKind::Meet,
+                    )
+                    .with_team(team),
+                )
+                .await
+                .unwrap();
+            sqlx::query("UPDATE meetings SET created_at = ? WHERE id = ?")
+                .bind(created_at)
+                .bind(meeting.id)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let count = |since: &str| {
+            let db = state.db.clone();
+            let since = at(since).naive_utc();
+            async move {
+                db.count_team_meetings_since("T012AB3C4", since)
+                    .await
+                    .unwrap()
+            }
+        };
+        assert_eq!(count("2030-06-04T00:00:00Z").await, 3);
+        // Midnight in Tokyo leaves the meeting a second before it out
+        assert_eq!(count("2030-06-04T15:00:00Z").await, 2);
+        assert_eq!(count("2030-06-05T00:00:00Z").await, 0);
+    }
+}
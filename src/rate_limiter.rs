@@ -1,13 +1,21 @@
 use anyhow::{bail, Result};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+use crate::config::RateLimitConfig;
+use crate::telemetry::metrics;
+
+/// How often entries for idle users are dropped.
+pub const CLEANUP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
 #[derive(Clone)]
 pub struct RateLimiter {
+    config: RateLimitConfig,
     user_limits: Arc<RwLock<HashMap<String, UserRateLimit>>>,
-    endpoint_limits: Arc<RwLock<HashMap<String, EndpointRateLimit>>>,
+    endpoint_limits: Arc<EndpointLimits>,
 }
 
 #[derive(Debug, Clone)]
@@ -18,10 +26,93 @@ struct UserRateLimit {
     backoff_duration: Duration,
 }
 
-#[derive(Debug, Clone)]
+/// Global per-endpoint limits. The known endpoints live in fixed slots so the
+/// hot path never takes a lock; anything else gets a lazily-created entry.
+struct EndpointLimits {
+    epoch: Instant,
+    slack_commands: EndpointRateLimit,
+    auth_google: EndpointRateLimit,
+    auth_google_callback: EndpointRateLimit,
+    other: RwLock<HashMap<String, Arc<EndpointRateLimit>>>,
+}
+
+#[derive(Debug)]
 struct EndpointRateLimit {
-    request_count: u32,
-    window_start: Instant,
+    max_requests: u32,
+    window: Duration,
+    /// The start of the current window, in milliseconds since
+    /// `EndpointLimits::epoch`, above [`COUNT_BITS`] bits of the requests
+    /// counted in it. Kept in one word so a window reset and the counting
+    /// can't interleave. No request counted means no window yet.
+    state: AtomicU64,
+}
+
+/// Bits of [`EndpointRateLimit::state`] holding the count, leaving 40 bits
+/// of milliseconds, about 34 years.
+const COUNT_BITS: u32 = 24;
+const MAX_COUNT: u64 = (1 << COUNT_BITS) - 1;
+
+impl EndpointRateLimit {
+    fn new(max_requests: u32, window: Duration) -> Self {
+        Self {
+            max_requests: max_requests.min(MAX_COUNT as u32),
+            window,
+            state: AtomicU64::new(0),
+        }
+    }
+
+    fn for_endpoint(endpoint: &str) -> Self {
+        let (max_requests, window_duration) = match endpoint {
+            "/slack/commands" => (1000, Duration::from_secs(60)),
+            "/auth/google" => (200, Duration::from_secs(60)),
+            "/auth/google/callback" => (500, Duration::from_secs(60)),
+            _ => (5000, Duration::from_secs(60)),
+        };
+        Self::new(max_requests, window_duration)
+    }
+
+    /// Counts a request at `now_ms`, returning false when the window is full.
+    /// A window starts with the first request after the last one ended, not
+    /// on a fixed grid, so the first requests after startup get a whole
+    /// window too.
+    fn try_acquire(&self, now_ms: u64) -> bool {
+        let window_ms = self.window.as_millis() as u64;
+
+        self.state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                let (mut window_start, mut count) = (state >> COUNT_BITS, state & MAX_COUNT);
+                if count == 0 || now_ms.saturating_sub(window_start) >= window_ms {
+                    window_start = now_ms;
+                    count = 0;
+                }
+                (count < u64::from(self.max_requests))
+                    .then(|| (window_start << COUNT_BITS) | (count + 1))
+            })
+            .is_ok()
+    }
+
+    fn window_start_ms(&self) -> u64 {
+        self.state.load(Ordering::Acquire) >> COUNT_BITS
+    }
+}
+
+impl EndpointLimits {
+    fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            epoch: Instant::now(),
+            slack_commands: EndpointRateLimit::new(
+                config.global_commands_per_minute,
+                Duration::from_secs(60),
+            ),
+            auth_google: EndpointRateLimit::for_endpoint("/auth/google"),
+            auth_google_callback: EndpointRateLimit::for_endpoint("/auth/google/callback"),
+            other: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
 }
 
 impl Default for RateLimiter {
@@ -32,9 +123,14 @@ impl Default for RateLimiter {
 
 impl RateLimiter {
     pub fn new() -> Self {
+        Self::with_config(RateLimitConfig::default())
+    }
+
+    pub fn with_config(config: RateLimitConfig) -> Self {
         Self {
+            config,
             user_limits: Arc::new(RwLock::new(HashMap::new())),
-            endpoint_limits: Arc::new(RwLock::new(HashMap::new())),
+            endpoint_limits: Arc::new(EndpointLimits::new(&config)),
         }
     }
 
@@ -42,9 +138,16 @@ impl RateLimiter {
         let now = Instant::now();
 
         let (max_requests, window_duration) = match endpoint {
-            "/slack/commands" => (10, Duration::from_secs(60)),
+            "/slack/commands" => (
+                self.config.user_commands_per_minute,
+                Duration::from_secs(60),
+            ),
             "/auth/google" => (5, Duration::from_secs(300)),
             "/auth/google/callback" => (10, Duration::from_secs(300)),
+            "/api/v1/meetings" => (
+                self.config.api_key_requests_per_minute,
+                Duration::from_secs(60),
+            ),
             _ => (100, Duration::from_secs(60)),
         };
 
@@ -60,6 +163,7 @@ impl RateLimiter {
 
         if let Some(last_blocked) = user_limit.last_blocked {
             if now.duration_since(last_blocked) < user_limit.backoff_duration {
+                metrics::record_rate_limit_block("user");
                 bail!(
                     "User {} is in backoff period for {} seconds",
                     user_id,
@@ -80,6 +184,7 @@ impl RateLimiter {
                 Duration::from_secs(15 * 60),
             );
 
+            metrics::record_rate_limit_block("user");
             bail!(
                 "Rate limit exceeded for user {}: {} requests in {} seconds",
                 user_id,
@@ -93,38 +198,45 @@ impl RateLimiter {
     }
 
     pub async fn check_endpoint_limit(&self, endpoint: &str) -> Result<()> {
-        let now = Instant::now();
+        let limits = &self.endpoint_limits;
+        let now_ms = limits.now_ms();
 
-        let (max_requests, window_duration) = match endpoint {
-            "/slack/commands" => (1000, Duration::from_secs(60)),
-            "/auth/google" => (200, Duration::from_secs(60)),
-            "/auth/google/callback" => (500, Duration::from_secs(60)),
-            _ => (5000, Duration::from_secs(60)),
+        let fixed = match endpoint {
+            "/slack/commands" => Some(&limits.slack_commands),
+            "/auth/google" => Some(&limits.auth_google),
+            "/auth/google/callback" => Some(&limits.auth_google_callback),
+            _ => None,
         };
 
-        let mut endpoint_limits = self.endpoint_limits.write().await;
-        let endpoint_limit = endpoint_limits
-            .entry(endpoint.to_string())
-            .or_insert_with(|| EndpointRateLimit {
-                request_count: 0,
-                window_start: now,
-            });
-
-        if now.duration_since(endpoint_limit.window_start) >= window_duration {
-            endpoint_limit.request_count = 0;
-            endpoint_limit.window_start = now;
-        }
+        let dynamic;
+        let endpoint_limit = match fixed {
+            Some(limit) => limit,
+            None => {
+                let existing = limits.other.read().await.get(endpoint).cloned();
+                dynamic = match existing {
+                    Some(limit) => limit,
+                    None => limits
+                        .other
+                        .write()
+                        .await
+                        .entry(endpoint.to_string())
+                        .or_insert_with(|| Arc::new(EndpointRateLimit::for_endpoint(endpoint)))
+                        .clone(),
+                };
+                dynamic.as_ref()
+            }
+        };
 
-        if endpoint_limit.request_count >= max_requests {
+        if !endpoint_limit.try_acquire(now_ms) {
+            metrics::record_rate_limit_block("endpoint");
             bail!(
                 "Global rate limit exceeded for endpoint {}: {} requests in {} seconds",
                 endpoint,
-                max_requests,
-                window_duration.as_secs()
+                endpoint_limit.max_requests,
+                endpoint_limit.window.as_secs()
             );
         }
 
-        endpoint_limit.request_count += 1;
         Ok(())
     }
 
@@ -139,29 +251,19 @@ impl RateLimiter {
                 .retain(|_, limit| now.duration_since(limit.window_start) < cleanup_threshold);
         }
 
-        // Clean up endpoint limits
+        // Clean up lazily-created endpoint limits; the fixed slots are permanent
         {
-            let mut endpoint_limits = self.endpoint_limits.write().await;
-            endpoint_limits
-                .retain(|_, limit| now.duration_since(limit.window_start) < cleanup_threshold);
+            let now_ms = self.endpoint_limits.now_ms();
+            let threshold_ms = cleanup_threshold.as_millis() as u64;
+            let mut other = self.endpoint_limits.other.write().await;
+            other.retain(|_, limit| now_ms.saturating_sub(limit.window_start_ms()) < threshold_ms);
         }
     }
 }
 
-/// Background task to periodically clean up old rate limit entries
-pub async fn start_cleanup_task(rate_limiter: RateLimiter) {
-    let mut interval = tokio::time::interval(Duration::from_secs(10 * 60)); // 10 minutes
-
-    loop {
-        interval.tick().await;
-        rate_limiter.cleanup_old_entries().await;
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::time::sleep;
 
     #[tokio::test]
     async fn test_user_rate_limiting() {
@@ -186,4 +288,108 @@ mod tests {
         // Test global endpoint limiting
         assert!(rate_limiter.check_endpoint_limit(endpoint).await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_endpoint_limit_blocks_at_max() {
+        let rate_limiter = RateLimiter::new();
+        let endpoint = "/auth/google";
+
+        for _ in 0..200 {
+            assert!(rate_limiter.check_endpoint_limit(endpoint).await.is_ok());
+        }
+        assert!(rate_limiter.check_endpoint_limit(endpoint).await.is_err());
+
+        // Other endpoints have their own budget
+        assert!(rate_limiter
+            .check_endpoint_limit("/auth/google/callback")
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_endpoint_gets_lazy_entry() {
+        let rate_limiter = RateLimiter::new();
+
+        assert!(rate_limiter.check_endpoint_limit("/custom").await.is_ok());
+        assert!(rate_limiter.check_endpoint_limit("/custom").await.is_ok());
+
+        let other = rate_limiter.endpoint_limits.other.read().await;
+        let limit = other.get("/custom").expect("entry should be created");
+        assert_eq!(limit.state.load(Ordering::Acquire) & MAX_COUNT, 2);
+        assert_eq!(limit.max_requests, 5000);
+    }
+
+    #[test]
+    fn test_endpoint_window_resets() {
+        let limit = EndpointRateLimit::new(2, Duration::from_millis(100));
+
+        assert!(limit.try_acquire(0));
+        assert!(limit.try_acquire(50));
+        assert!(!limit.try_acquire(99));
+
+        // Exactly one window later the counter starts over
+        assert!(limit.try_acquire(100));
+        assert!(limit.try_acquire(150));
+        assert!(!limit.try_acquire(199));
+    }
+
+    #[test]
+    fn test_first_window_starts_at_the_first_request() {
+        let limit = EndpointRateLimit::new(2, Duration::from_millis(100));
+
+        // A burst either side of where a window anchored at startup would
+        // end still shares one window
+        assert!(limit.try_acquire(90));
+        assert!(limit.try_acquire(110));
+        assert!(!limit.try_acquire(189));
+
+        assert!(limit.try_acquire(190));
+    }
+
+    #[test]
+    fn test_requests_racing_a_window_reset_are_counted_exactly() {
+        for _ in 0..100 {
+            let limit = Arc::new(EndpointRateLimit::new(50, Duration::from_millis(100)));
+            assert!(limit.try_acquire(0));
+
+            // Every thread sees the window expired at once, so several try
+            // to start the next one while others already count in it
+            let allowed: usize = std::thread::scope(|scope| {
+                let threads: Vec<_> = (0..8)
+                    .map(|_| {
+                        let limit = limit.clone();
+                        scope.spawn(move || (0..20).filter(|_| limit.try_acquire(100)).count())
+                    })
+                    .collect();
+                threads.into_iter().map(|t| t.join().unwrap()).sum()
+            });
+            assert_eq!(allowed, 50);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_endpoint_requests_are_counted_exactly() {
+        let rate_limiter = RateLimiter::new();
+        let endpoint = "/slack/commands";
+
+        let mut handles = Vec::new();
+        for _ in 0..8 {
+            let limiter = rate_limiter.clone();
+            handles.push(tokio::spawn(async move {
+                let mut allowed = 0;
+                for _ in 0..200 {
+                    if limiter.check_endpoint_limit(endpoint).await.is_ok() {
+                        allowed += 1;
+                    }
+                }
+                allowed
+            }));
+        }
+
+        let mut total = 0;
+        for handle in handles {
+            total += handle.await.unwrap();
+        }
+        assert_eq!(total, 1000);
+    }
 }
@@ -0,0 +1,75 @@
+//! Meetings that record or transcribe themselves: `/meet --record` and
+//! `--transcribe` for one meeting, and `/meet set team auto-record` and
+//! `auto-transcribe` for every meeting of a workspace. Meet starts either
+//! as soon as the meeting does, so announcements say so.
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::database::Database;
+use crate::google::Artifacts;
+
+/// Prefix shared by the workspace defaults in `team_settings`.
+const SETTING_PREFIX: &str = "auto_";
+/// `true` when the workspace's meetings are recorded.
+pub const RECORD_KEY: &str = "auto_record";
+/// `true` when the workspace's meetings are transcribed.
+pub const TRANSCRIBE_KEY: &str = "auto_transcribe";
+
+/// What the meetings of `slack_team_id` generate unless `/meet` asks for
+/// more; nothing without settings.
+pub async fn for_team(db: &Database, slack_team_id: &str) -> Result<Artifacts> {
+    let settings: HashMap<String, String> = db
+        .team_settings_with_prefix(slack_team_id, SETTING_PREFIX)
+        .await?
+        .into_iter()
+        .collect();
+    Ok(Artifacts {
+        recording: settings.get(RECORD_KEY).map(String::as_str) == Some("true"),
+        transcription: settings.get(TRANSCRIBE_KEY).map(String::as_str) == Some("true"),
+    })
+}
+
+/// The line telling a meeting's participants what Meet will generate.
+pub fn notice(artifacts: Artifacts) -> Option<&'static str> {
+    match (artifacts.recording, artifacts.transcription) {
+        (true, true) => Some("🔴 This meeting is recorded and transcribed automatically."),
+        (true, false) => Some("🔴 This meeting is recorded automatically."),
+        (false, true) => Some("📝 This meeting is transcribed automatically."),
+        (false, false) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::test_state;
+
+    #[tokio::test]
+    async fn test_team_defaults() {
+        let (state, _pool) = test_state().await;
+        assert_eq!(
+            for_team(&state.db, "T012AB3C4").await.unwrap(),
+            Artifacts::default()
+        );
+
+        state
+            .db
+            .set_team_setting("T012AB3C4", TRANSCRIBE_KEY, "true")
+            .await
+            .unwrap();
+        state
+            .db
+            .set_team_setting("T012AB3C4", RECORD_KEY, "false")
+            .await
+            .unwrap();
+
+        assert_eq!(
+            for_team(&state.db, "T012AB3C4").await.unwrap(),
+            Artifacts {
+                recording: false,
+                transcription: true,
+            }
+        );
+    }
+}
@@ -0,0 +1,435 @@
+//! Reminders posted in the channel shortly before a scheduled meeting
+//! starts. Slack holds them until then (`chat.scheduleMessage`), so nothing
+//! here runs when they are due; the scheduled message is recorded on the
+//! meeting so cancelling it can take the reminder back. How long before the
+//! start is each user's `reminder_minutes` setting, or else
+//! `MEETING_REMINDER_MINUTES`.
+
+use anyhow::Result;
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use std::fmt;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::database::models::{Meeting, ScheduledReminder, User};
+use crate::database::Database;
+use crate::handlers::slack::SlashCommandPayload;
+use crate::slack::api::{ChatMessage, SlackApi, SlackApiError};
+use crate::slack::blocks;
+
+/// The user's lead time in `user_settings`, in whole minutes; `0` for no
+/// reminders.
+pub const SETTING_KEY: &str = "reminder_minutes";
+
+/// Longest lead time; reminders are for the run-up to a meeting, not its
+/// day.
+pub const MAX_LEAD_MINUTES: u64 = 24 * 60;
+
+/// Furthest ahead Slack schedules messages.
+pub const MAX_SCHEDULE_AHEAD_DAYS: i64 = 120;
+
+/// Reminders due sooner than this are skipped: Slack refuses times that
+/// passed by the time it gets the request, and the meeting was just
+/// announced anyway.
+const MIN_SCHEDULE_AHEAD_MINUTES: i64 = 1;
+
+/// When the reminder `lead` before a meeting starting at `starts_at` is
+/// posted, in UTC as Slack takes it; `None` when that is too soon after
+/// `now` or too far ahead for Slack.
+pub fn reminder_time<Tz: TimeZone>(
+    starts_at: &DateTime<Tz>,
+    lead: Duration,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let post_at = starts_at.with_timezone(&Utc) - lead;
+    if post_at < now + Duration::minutes(MIN_SCHEDULE_AHEAD_MINUTES)
+        || post_at > now + Duration::days(MAX_SCHEDULE_AHEAD_DAYS)
+    {
+        return None;
+    }
+    Some(post_at)
+}
+
+/// How long before their meetings start `user` is reminded: their own
+/// setting, or `default` when they have none or it can't be read. `None`
+/// means no reminders.
+pub async fn lead_for(db: &Database, user: &User, default: Option<Duration>) -> Option<Duration> {
+    let setting = match db.get_user_setting(user.id, SETTING_KEY).await {
+        Ok(setting) => setting,
+        Err(e) => {
+            warn!(
+                "Failed to read the reminder setting of user {}: {:#}",
+                user.id, e
+            );
+            None
+        }
+    };
+    match setting.map(|minutes| minutes.parse::<ReminderLead>()) {
+        Some(Ok(ReminderLead(lead))) => lead,
+        Some(Err(())) => {
+            warn!(
+                "Ignoring the unreadable reminder setting of user {}",
+                user.id
+            );
+            default
+        }
+        None => default,
+    }
+}
+
+/// A user's lead time; `None` when they turned reminders off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReminderLead(pub Option<Duration>);
+
+/// Reads whole minutes up to [`MAX_LEAD_MINUTES`], with `0` or `off` for
+/// none.
+impl FromStr for ReminderLead {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("off") {
+            return Ok(Self(None));
+        }
+        match s.parse::<u64>().map_err(|_| ())? {
+            0 => Ok(Self(None)),
+            minutes if minutes <= MAX_LEAD_MINUTES => {
+                Ok(Self(Some(Duration::minutes(minutes as i64))))
+            }
+            _ => Err(()),
+        }
+    }
+}
+
+/// Minutes as stored in `user_settings`.
+impl fmt::Display for ReminderLead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.map_or(0, |lead| lead.num_minutes()))
+    }
+}
+
+/// `lead` in words, as "10 minutes" or "1 hour".
+pub fn describe_lead(lead: Duration) -> String {
+    let minutes = lead.num_minutes();
+    match (minutes / 60, minutes % 60) {
+        (1, 0) => "1 hour".to_string(),
+        (hours, 0) if hours > 1 => format!("{} hours", hours),
+        _ if minutes == 1 => "1 minute".to_string(),
+        _ => format!("{} minutes", minutes),
+    }
+}
+
+/// The reminder for `meeting`, created by the Slack user `creator_id`,
+/// posted in `channel` `lead` before it starts.
+pub fn reminder_message(
+    channel: &str,
+    creator_id: &str,
+    meeting: &Meeting,
+    lead: Duration,
+) -> ChatMessage {
+    let what = match &meeting.title {
+        Some(title) => format!("*{}*", blocks::title(title)),
+        None => format!("<@{}>'s meeting", creator_id),
+    };
+    ChatMessage {
+        channel: channel.to_string(),
+        text: format!(
+            "⏰ {} starts in {}: {}",
+            what,
+            describe_lead(lead),
+            meeting.meet_link
+        ),
+        blocks: Vec::new(),
+        thread: None,
+    }
+}
+
+/// Schedules the reminder for `meeting`, created by `payload` to start at
+/// `starts_at`, in the channel the command came from, and records it on
+/// the meeting. Returns whether one was scheduled: none is when it would be
+/// too soon or too far ahead, the workspace has no bot token stored, or
+/// the bot isn't in the channel.
+pub async fn schedule(
+    db: &Database,
+    slack: &dyn SlackApi,
+    payload: &SlashCommandPayload,
+    meeting: &Meeting,
+    starts_at: DateTime<Utc>,
+    lead: Duration,
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    let Some(post_at) = reminder_time(&starts_at, lead, now) else {
+        info!("Meeting starts too soon or too far ahead for a reminder");
+        return Ok(false);
+    };
+    // Without an id the reminder couldn't be cancelled with the meeting
+    let Some(meeting_id) = meeting.id else {
+        warn!("Meeting was not stored, skipping its reminder");
+        return Ok(false);
+    };
+    let Some(team) = db.get_slack_team(&payload.team_id).await? else {
+        info!(
+            "No bot token is stored for workspace {}, skipping the reminder",
+            payload.team_id
+        );
+        return Ok(false);
+    };
+
+    let message = reminder_message(&payload.channel_id, &payload.user_id, meeting, lead);
+    let message_id = match slack
+        .schedule_message(&team.bot_token, &message, post_at)
+        .await
+    {
+        Ok(id) => id,
+        Err(e) if e.is_not_in_channel() => {
+            info!(
+                "Bot is not in channel {}, skipping the reminder",
+                payload.channel_id
+            );
+            return Ok(false);
+        }
+        Err(e) => return Err(e.into()),
+    };
+    info!("Scheduled reminder {} for {}", message_id, post_at);
+
+    db.set_meeting_reminder(
+        meeting_id,
+        &ScheduledReminder {
+            channel_id: payload.channel_id.clone(),
+            message_id,
+        },
+    )
+    .await?;
+    Ok(true)
+}
+
+/// Deletes the reminder of the meeting `meeting_id` in the workspace
+/// `slack_team_id`, for when the meeting is cancelled. Returns whether it
+/// had one; a reminder Slack already posted counts as deleted.
+pub async fn cancel(
+    db: &Database,
+    slack: &dyn SlackApi,
+    slack_team_id: &str,
+    meeting_id: i64,
+) -> Result<bool> {
+    let Some(reminder) = db.meeting_reminder(meeting_id).await? else {
+        return Ok(false);
+    };
+    let Some(team) = db.get_slack_team(slack_team_id).await? else {
+        anyhow::bail!("no bot token is stored for workspace {}", slack_team_id);
+    };
+
+    match slack
+        .delete_scheduled_message(&team.bot_token, &reminder.channel_id, &reminder.message_id)
+        .await
+    {
+        Ok(()) => {}
+        Err(SlackApiError::Api(code)) if code == "invalid_scheduled_message_id" => {
+            info!("Reminder {} was already posted", reminder.message_id);
+        }
+        Err(e) => return Err(e.into()),
+    }
+    db.clear_meeting_reminder(meeting_id).await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_parser::{self, MeetCommand};
+    use crate::commands::testing;
+    use crate::database::models::{MeetLinkKind, SlackTeam};
+    use crate::database::test_db;
+    use crate::slack::fake::FakeSlackApi;
+    use chrono::FixedOffset;
+
+    const LINK: &str = "https://meet.google.com/abc-defg-hij";
+
+    /// A database with the workspace's bot token and a meeting of U012AB3CD.
+    async fn db_with_meeting(title: Option<&str>) -> (Database, Meeting) {
+        let (db, _pool) = test_db().await;
+        db.store_slack_team(&SlackTeam::new("T012AB3C4".to_string(), "xoxb-bot".into()))
+            .await
+            .unwrap();
+        let user = db.create_user("U012AB3CD", "T012AB3C4").await.unwrap();
+        let meeting = db
+            .create_meeting(&Meeting::new(
+                user.id,
+                LINK.to_string(),
+                title.map(str::to_string),
+                MeetLinkKind::Meet,
+            ))
+            .await
+            .unwrap();
+        (db, meeting)
+    }
+
+    fn payload() -> SlashCommandPayload {
+        testing::payload(
+            "standup tomorrow 09:00",
+            "https://hooks.slack.com/commands/1",
+        )
+    }
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    /// The start of `/meet <text>` read in the offset `minutes` east of UTC,
+    /// at `now`.
+    fn start_in(minutes: i32, text: &str, now: DateTime<Utc>) -> DateTime<FixedOffset> {
+        let offset = FixedOffset::east_opt(minutes * 60).unwrap();
+        let MeetCommand::Create {
+            start: Some(start), ..
+        } = command_parser::parse(text).unwrap()
+        else {
+            panic!("expected a start time in {:?}", text);
+        };
+        start.resolve(&now.with_timezone(&offset)).unwrap()
+    }
+
+    #[test]
+    fn test_reminder_time_is_in_utc() {
+        let now = utc("2024-03-04T20:00:00Z");
+        let lead = Duration::minutes(10);
+
+        // 09:00 tomorrow in UTC+2 is 07:00 UTC
+        let start = start_in(120, "standup tomorrow 09:00", now);
+        assert_eq!(
+            reminder_time(&start, lead, now),
+            Some(utc("2024-03-05T06:50:00Z"))
+        );
+        // 09:00 tomorrow in UTC-5 is 14:00 UTC
+        let start = start_in(-300, "standup tomorrow 09:00", now);
+        assert_eq!(
+            reminder_time(&start, lead, now),
+            Some(utc("2024-03-05T13:50:00Z"))
+        );
+        // In UTC+5:30 it is already 01:30 on the 5th, so tomorrow is the 6th
+        let start = start_in(330, "standup tomorrow 09:00", now);
+        assert_eq!(
+            reminder_time(&start, lead, now),
+            Some(utc("2024-03-06T03:20:00Z"))
+        );
+    }
+
+    #[test]
+    fn test_reminders_too_soon_or_too_far_ahead_are_skipped() {
+        let now = utc("2024-03-04T09:00:00Z");
+        let lead = Duration::minutes(10);
+
+        // The reminder time has passed
+        assert_eq!(reminder_time(&utc("2024-03-04T09:05:00Z"), lead, now), None);
+        // Less than a minute away
+        assert_eq!(reminder_time(&utc("2024-03-04T09:10:30Z"), lead, now), None);
+        assert_eq!(
+            reminder_time(&utc("2024-03-04T09:11:00Z"), lead, now),
+            Some(utc("2024-03-04T09:01:00Z"))
+        );
+        // Beyond what Slack schedules
+        assert_eq!(reminder_time(&(now + Duration::days(121)), lead, now), None);
+    }
+
+    #[test]
+    fn test_describe_lead() {
+        assert_eq!(describe_lead(Duration::minutes(1)), "1 minute");
+        assert_eq!(describe_lead(Duration::minutes(10)), "10 minutes");
+        assert_eq!(describe_lead(Duration::minutes(60)), "1 hour");
+        assert_eq!(describe_lead(Duration::minutes(90)), "90 minutes");
+        assert_eq!(describe_lead(Duration::minutes(120)), "2 hours");
+    }
+
+    #[tokio::test]
+    async fn test_schedules_and_cancels_the_reminder() {
+        let (db, meeting) = db_with_meeting(Some("Standup <daily>")).await;
+        let slack = FakeSlackApi::new();
+        let now = utc("2024-03-04T20:00:00Z");
+        let starts_at = start_in(120, "standup tomorrow 09:00", now).to_utc();
+
+        let scheduled = schedule(
+            &db,
+            &slack,
+            &payload(),
+            &meeting,
+            starts_at,
+            Duration::minutes(15),
+            now,
+        )
+        .await
+        .unwrap();
+
+        assert!(scheduled);
+        let posts = slack.scheduled();
+        assert_eq!(posts.len(), 1);
+        assert_eq!(posts[0].post_at, utc("2024-03-05T06:45:00Z"));
+        assert_eq!(
+            posts[0].message.text,
+            format!("⏰ *Standup &lt;daily&gt;* starts in 15 minutes: {}", LINK)
+        );
+        let meeting_id = meeting.id.unwrap();
+        assert_eq!(
+            db.meeting_reminder(meeting_id).await.unwrap(),
+            Some(ScheduledReminder {
+                channel_id: "C012AB3CD".to_string(),
+                message_id: posts[0].id.clone(),
+            })
+        );
+
+        assert!(cancel(&db, &slack, "T012AB3C4", meeting_id).await.unwrap());
+        assert!(slack.scheduled().is_empty());
+        assert_eq!(db.meeting_reminder(meeting_id).await.unwrap(), None);
+        // Nothing left to cancel
+        assert!(!cancel(&db, &slack, "T012AB3C4", meeting_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_no_reminder_without_bot_or_time_for_it() {
+        let (db, meeting) = db_with_meeting(None).await;
+        let slack = FakeSlackApi::new();
+        let now = utc("2024-03-04T09:00:00Z");
+        let lead = Duration::minutes(10);
+
+        // Starting within the lead
+        let soon = utc("2024-03-04T09:05:00Z");
+        assert!(
+            !schedule(&db, &slack, &payload(), &meeting, soon, lead, now)
+                .await
+                .unwrap()
+        );
+
+        // The bot was never invited to the channel
+        let later = utc("2024-03-04T15:00:00Z");
+        slack.fail_channel("C012AB3CD", "not_in_channel");
+        assert!(
+            !schedule(&db, &slack, &payload(), &meeting, later, lead, now)
+                .await
+                .unwrap()
+        );
+
+        // No bot token for the workspace
+        let mut other = payload();
+        other.team_id = "T098ZY7XW".to_string();
+        assert!(!schedule(&db, &slack, &other, &meeting, later, lead, now)
+            .await
+            .unwrap());
+
+        assert!(slack.scheduled().is_empty());
+        assert_eq!(
+            db.meeting_reminder(meeting.id.unwrap()).await.unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_reminder_without_title_names_the_creator() {
+        let meeting = Meeting::new(1, LINK.to_string(), None, MeetLinkKind::Meet);
+
+        let message = reminder_message("C012AB3CD", "U012AB3CD", &meeting, Duration::minutes(5));
+
+        assert_eq!(message.channel, "C012AB3CD");
+        assert_eq!(
+            message.text,
+            format!("⏰ <@U012AB3CD>'s meeting starts in 5 minutes: {}", LINK)
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request},
+    http::{request::Parts, HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::convert::Infallible;
+use std::fmt;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest caller-supplied id that is kept; anything longer is replaced.
+const MAX_LEN: usize = 64;
+
+/// Characters shown to users in "ref: …" notes.
+const SHORT_LEN: usize = 6;
+
+/// Identifies one HTTP request across the logs, the `X-Request-Id`
+/// response header and the reference shown in error messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        Self(uuid::Uuid::new_v4().simple().to_string())
+    }
+
+    /// Keeps an id from an upstream proxy if it's short and made of
+    /// characters that are safe in a header and a log line.
+    fn from_header(value: &HeaderValue) -> Option<Self> {
+        Self::parse(value.to_str().ok()?)
+    }
+
+    /// An id given as text, if it's short and made of characters that are
+    /// safe in a header and a log line.
+    pub fn parse(value: &str) -> Option<Self> {
+        let acceptable = !value.is_empty()
+            && value.len() <= MAX_LEN
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+        acceptable.then(|| Self(value.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Leading characters of the id, short enough to read out to support
+    /// and still unique enough to grep the logs for.
+    pub fn short(&self) -> &str {
+        &self.0[..self.0.len().min(SHORT_LEN)]
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Reads the id [`propagate`] stored on the request. Requests that didn't
+/// go through the middleware get a fresh one, so handlers can always ask
+/// for it.
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for RequestId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_else(RequestId::generate))
+    }
+}
+
+/// Middleware that takes the request id from `X-Request-Id` or makes one
+/// up, stores it in the request extensions for the trace span and handlers,
+/// and echoes it in the response's `X-Request-Id` header.
+///
+/// Must sit outside the trace layer so the request span can record it.
+pub async fn propagate(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(RequestId::from_header)
+        .unwrap_or_else(RequestId::generate);
+    request.extensions_mut().insert(request_id.clone());
+
+    let mut response = next.run(request).await;
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::get, Router};
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        Router::new()
+            .route(
+                "/",
+                get(|request_id: RequestId| async move { request_id.to_string() }),
+            )
+            .layer(middleware::from_fn(propagate))
+    }
+
+    async fn send(header: Option<&str>) -> (String, String) {
+        let mut request = Request::builder().uri("/");
+        if let Some(value) = header {
+            request = request.header(&REQUEST_ID_HEADER, value);
+        }
+        let response = app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let header = response.headers()[&REQUEST_ID_HEADER]
+            .to_str()
+            .unwrap()
+            .to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (header, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_generates_id_when_absent() {
+        let (header, seen_by_handler) = send(None).await;
+        assert_eq!(header.len(), 32);
+        assert_eq!(header, seen_by_handler);
+
+        let (other, _) = send(None).await;
+        assert_ne!(header, other);
+    }
+
+    #[tokio::test]
+    async fn test_propagates_upstream_id() {
+        let (header, seen_by_handler) = send(Some("edge-7f3a.42")).await;
+        assert_eq!(header, "edge-7f3a.42");
+        assert_eq!(seen_by_handler, "edge-7f3a.42");
+    }
+
+    #[tokio::test]
+    async fn test_replaces_unsafe_upstream_id() {
+        for value in ["", "has space", "semi;colon", &"a".repeat(MAX_LEN + 1)] {
+            let (header, _) = send(Some(value)).await;
+            assert_ne!(header, value);
+            assert_eq!(header.len(), 32);
+        }
+    }
+
+    #[test]
+    fn test_short_form() {
+        assert_eq!(RequestId("a1b2c3d4e5".to_string()).short(), "a1b2c3");
+        assert_eq!(RequestId("abc".to_string()).short(), "abc");
+    }
+}
@@ -0,0 +1,174 @@
+use axum::http::{header, HeaderMap};
+use base64::{engine::general_purpose, Engine as _};
+use std::fmt;
+use url::Url;
+use zeroize::Zeroizing;
+
+/// A string that holds a secret such as an OAuth token or the Slack signing
+/// secret.
+///
+/// `Debug` never prints the value and the memory is zeroed on drop. Call
+/// [`SecretString::expose`] only at the points that need the actual bytes
+/// (an Authorization header, an HMAC key, encryption).
+#[derive(Clone, Default)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether `headers` carry `Authorization: Bearer <this secret>`,
+    /// compared in constant time.
+    pub fn matches_bearer(&self, headers: &HeaderMap) -> bool {
+        headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| constant_time_eq(token.as_bytes(), self.expose().as_bytes()))
+    }
+
+    /// Whether `headers` carry HTTP basic credentials whose password is this
+    /// secret, whatever the username, compared in constant time.
+    pub fn matches_basic_password(&self, headers: &HeaderMap) -> bool {
+        let Some(encoded) = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Basic "))
+        else {
+            return false;
+        };
+        let Ok(decoded) = general_purpose::STANDARD.decode(encoded.trim()) else {
+            return false;
+        };
+        let decoded = Zeroizing::new(decoded);
+        decoded
+            .iter()
+            .position(|&byte| byte == b':')
+            .is_some_and(|colon| constant_time_eq(&decoded[colon + 1..], self.expose().as_bytes()))
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+/// Log-field stand-in for a value that must never reach the logs, such as
+/// a token or an authorization code: `token = %redact(&token)`. Only the
+/// length is shown, which is enough to tell an empty value from a real one.
+pub fn redact(value: &str) -> Redacted {
+    Redacted(value.len())
+}
+
+pub struct Redacted(usize);
+
+impl fmt::Display for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[REDACTED {} bytes]", self.0)
+    }
+}
+
+impl fmt::Debug for Redacted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// A URL fit for the logs: the query keeps its parameter names but loses
+/// every value, so an OAuth URL shows which parameters it carried without
+/// its `state` or `code`. Any fragment is dropped.
+pub fn redact_url(url: &Url) -> String {
+    let mut redacted = url.clone();
+    redacted.set_fragment(None);
+    let names: Vec<String> = url
+        .query_pairs()
+        .map(|(name, _)| name.into_owned())
+        .collect();
+    if names.is_empty() {
+        return redacted.to_string();
+    }
+
+    redacted.set_query(None);
+    redacted
+        .query_pairs_mut()
+        .extend_pairs(names.iter().map(|name| (name.as_str(), "REDACTED")));
+    redacted.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debug_is_redacted() {
+        let secret = SecretString::from("ya29.super-secret");
+
+        assert_eq!(format!("{:?}", secret), "SecretString([REDACTED])");
+        assert!(!format!("{:?}", Some(secret.clone())).contains("super-secret"));
+        assert_eq!(secret.expose(), "ya29.super-secret");
+    }
+
+    #[test]
+    fn test_basic_credentials_match_on_the_password() {
+        let secret = SecretString::from("admin-token");
+        let basic = |credentials: &str| {
+            let mut headers = HeaderMap::new();
+            let value = format!("Basic {}", general_purpose::STANDARD.encode(credentials));
+            headers.insert(header::AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert!(secret.matches_basic_password(&basic("admin:admin-token")));
+        assert!(secret.matches_basic_password(&basic(":admin-token")));
+        assert!(!secret.matches_basic_password(&basic("admin:wrong")));
+        assert!(!secret.matches_basic_password(&basic("admin-token")));
+        assert!(!secret.matches_basic_password(&basic("admin-token:")));
+        assert!(!secret.matches_basic_password(&HeaderMap::new()));
+    }
+
+    #[test]
+    fn test_redact_shows_only_the_length() {
+        assert_eq!(
+            redact("ya29.super-secret").to_string(),
+            "[REDACTED 17 bytes]"
+        );
+    }
+
+    #[test]
+    fn test_redact_url_keeps_parameter_names_only() {
+        let url = Url::parse(
+            "https://accounts.google.com/o/oauth2/v2/auth?response_type=code&state=user%3AU1%3Anonce123#frag",
+        )
+        .unwrap();
+
+        assert_eq!(
+            redact_url(&url),
+            "https://accounts.google.com/o/oauth2/v2/auth?response_type=REDACTED&state=REDACTED"
+        );
+
+        let plain = Url::parse("https://meet.googleapis.com/v2/spaces").unwrap();
+        assert_eq!(redact_url(&plain), "https://meet.googleapis.com/v2/spaces");
+    }
+}
@@ -0,0 +1,231 @@
+//! Shared account mode: a workspace whose admins picked one member's Google
+//! account with `/meet set team shared-account` creates every member's
+//! meetings with that account's token, so only one person has to connect
+//! Google. Meetings still belong to whoever ran `/meet`. Admins can only
+//! pick a member who agreed to it with `/meet set lend-account on`, and
+//! the mode ends when that member takes it back.
+//!
+//! When the shared token can't be used, `/meet` falls back to the member's
+//! own account and the admin who set the mode up hears about it once, until
+//! the shared token works again.
+
+use anyhow::Result;
+use std::collections::HashMap;
+use tracing::{info, warn};
+
+use crate::database::Database;
+use crate::slack::api::{ChatMessage, SlackApi};
+
+/// Prefix shared by every key of the mode in `team_settings`.
+const SETTING_PREFIX: &str = "shared_account_";
+/// Slack user id of the member whose Google account is shared.
+pub const ACCOUNT_KEY: &str = "shared_account_user_id";
+/// The admin who turned the mode on, told when the shared token fails.
+const ENABLED_BY_KEY: &str = "shared_account_enabled_by";
+/// Set once the admin was told the shared token fails, so they are told
+/// once rather than on every `/meet`.
+const FAILING_KEY: &str = "shared_account_failing";
+/// The user setting with which a member lets admins share their Google
+/// account with the workspace.
+pub const CONSENT_KEY: &str = "lend_google_account";
+
+/// A workspace's shared account.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SharedAccount {
+    /// Slack user id of the member whose Google account is used.
+    pub slack_user_id: String,
+    /// Slack user id of the admin who turned the mode on.
+    pub enabled_by: Option<String>,
+    /// Whether the admin was told the shared token fails.
+    pub failing: bool,
+}
+
+impl SharedAccount {
+    fn from_settings(settings: &HashMap<String, String>) -> Option<Self> {
+        Some(Self {
+            slack_user_id: settings.get(ACCOUNT_KEY)?.clone(),
+            enabled_by: settings.get(ENABLED_BY_KEY).cloned(),
+            failing: settings.contains_key(FAILING_KEY),
+        })
+    }
+}
+
+/// The workspace's shared account, if it has one.
+pub async fn for_team(db: &Database, slack_team_id: &str) -> Result<Option<SharedAccount>> {
+    let settings: HashMap<String, String> = db
+        .team_settings_with_prefix(slack_team_id, SETTING_PREFIX)
+        .await?
+        .into_iter()
+        .collect();
+    Ok(SharedAccount::from_settings(&settings))
+}
+
+/// Whether the user with id `user_id` agreed to their Google account being
+/// shared with the workspace.
+pub async fn consents(db: &Database, user_id: i64) -> Result<bool> {
+    Ok(db.get_user_setting(user_id, CONSENT_KEY).await?.as_deref() == Some("true"))
+}
+
+/// Has the workspace create meetings with the Google account of
+/// `slack_user_id`, as `admin` asked.
+pub async fn enable(
+    db: &Database,
+    slack_team_id: &str,
+    slack_user_id: &str,
+    admin: &str,
+) -> Result<()> {
+    db.set_team_setting(slack_team_id, ACCOUNT_KEY, slack_user_id)
+        .await?;
+    db.set_team_setting(slack_team_id, ENABLED_BY_KEY, admin)
+        .await?;
+    db.delete_team_setting(slack_team_id, FAILING_KEY).await
+}
+
+/// Goes back to every member using their own Google account.
+pub async fn disable(db: &Database, slack_team_id: &str) -> Result<()> {
+    for key in [ACCOUNT_KEY, ENABLED_BY_KEY, FAILING_KEY] {
+        db.delete_team_setting(slack_team_id, key).await?;
+    }
+    Ok(())
+}
+
+/// Tells the admin who turned the mode on that the shared token can't be
+/// used because of `problem`, pointing them at `command`, unless they were
+/// told already. Failing to is only logged.
+pub async fn report_failure(
+    db: &Database,
+    slack: &dyn SlackApi,
+    command: &str,
+    slack_team_id: &str,
+    account: &SharedAccount,
+    problem: &str,
+) {
+    if account.failing {
+        return;
+    }
+    if let Err(e) = notify_admin(db, slack, command, slack_team_id, account, problem).await {
+        warn!(
+            "Couldn't tell the admins of team {} about their shared account: {:#}",
+            slack_team_id, e
+        );
+    }
+}
+
+async fn notify_admin(
+    db: &Database,
+    slack: &dyn SlackApi,
+    command: &str,
+    slack_team_id: &str,
+    account: &SharedAccount,
+    problem: &str,
+) -> Result<()> {
+    // Marked first, so a notification that fails isn't retried on every
+    // command either
+    db.set_team_setting(slack_team_id, FAILING_KEY, "true")
+        .await?;
+    let Some(admin) = &account.enabled_by else {
+        warn!(
+            "Nobody to tell that the shared account of team {} fails",
+            slack_team_id
+        );
+        return Ok(());
+    };
+    let Some(team) = db.get_slack_team(slack_team_id).await? else {
+        warn!(
+            "No bot token for team {}, can't tell {} about the shared account",
+            slack_team_id, admin
+        );
+        return Ok(());
+    };
+    let message = ChatMessage {
+        channel: admin.clone(),
+        text: format!(
+            "⚠️ I couldn't create meetings with <@{}>'s shared Google account: {}. \
+             Until it works again, members use their own accounts. \
+             <@{}> can reconnect with `{} status`, or you can pick another account with `{} set team shared-account`.",
+            account.slack_user_id, problem, account.slack_user_id, command, command
+        ),
+        blocks: Vec::new(),
+        thread: None,
+    };
+    slack.post_message(&team.bot_token, &message).await?;
+    info!(
+        "Told {} that the shared account of team {} fails",
+        admin, slack_team_id
+    );
+    Ok(())
+}
+
+/// Notes that the shared token works, so the admin hears about the next
+/// failure.
+pub async fn report_working(db: &Database, slack_team_id: &str, account: &SharedAccount) {
+    if !account.failing {
+        return;
+    }
+    info!("The shared account of team {} works again", slack_team_id);
+    if let Err(e) = db.delete_team_setting(slack_team_id, FAILING_KEY).await {
+        warn!(
+            "Failed to clear the shared account failure of team {}: {:#}",
+            slack_team_id, e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::models::SlackTeam;
+    use crate::database::test_db;
+    use crate::slack::fake::FakeSlackApi;
+
+    #[tokio::test]
+    async fn test_admin_is_told_once_per_failure() {
+        let (db, _pool) = test_db().await;
+        let slack = FakeSlackApi::new();
+        db.store_slack_team(&SlackTeam::new("T012AB3C4".to_string(), "xoxb-bot".into()))
+            .await
+            .unwrap();
+        enable(&db, "T012AB3C4", "U0SHARED1", "U0ADMIN01")
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let account = for_team(&db, "T012AB3C4").await.unwrap().unwrap();
+            report_failure(
+                &db,
+                &slack,
+                "/gmeet",
+                "T012AB3C4",
+                &account,
+                "it isn't connected",
+            )
+            .await;
+        }
+        let posted = slack.posted();
+        assert_eq!(posted.len(), 1);
+        assert_eq!(posted[0].channel, "U0ADMIN01");
+        assert!(posted[0]
+            .text
+            .contains("<@U0SHARED1>'s shared Google account: it isn't connected"));
+        assert!(posted[0].text.contains("`/gmeet set team shared-account`"));
+
+        // Working again, the next failure is reported
+        let account = for_team(&db, "T012AB3C4").await.unwrap().unwrap();
+        report_working(&db, "T012AB3C4", &account).await;
+        let account = for_team(&db, "T012AB3C4").await.unwrap().unwrap();
+        assert!(!account.failing);
+        report_failure(
+            &db,
+            &slack,
+            "/meet",
+            "T012AB3C4",
+            &account,
+            "Google rejected it",
+        )
+        .await;
+        assert_eq!(slack.posted().len(), 2);
+
+        disable(&db, "T012AB3C4").await.unwrap();
+        assert!(for_team(&db, "T012AB3C4").await.unwrap().is_none());
+    }
+}
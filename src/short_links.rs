@@ -0,0 +1,152 @@
+//! Short links to meetings, `{base}/m/{slug}`, for places where a Meet link
+//! is unwieldy, such as status pages. `/meet share-link` makes one; slugs
+//! are random rather than the meeting's id, so public links can't be found
+//! by counting up from one.
+
+use anyhow::{bail, Result};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+use crate::database::Database;
+
+/// Where short links are served, with the slug after it.
+pub const PATH_PREFIX: &str = "/m/";
+
+/// Characters in a slug; 62 possibilities each.
+const SLUG_LEN: usize = 10;
+
+/// Slugs tried before giving up, should each one already be taken.
+const MAX_ATTEMPTS: usize = 5;
+
+/// Makes the short link of meeting `meeting_id` public, giving it a slug
+/// from `new_slug` unless it had one, and returns the slug. A link turned
+/// off and on again keeps its slug.
+pub async fn publish(
+    db: &Database,
+    meeting_id: i64,
+    mut new_slug: impl FnMut() -> String,
+) -> Result<String> {
+    for _ in 0..MAX_ATTEMPTS {
+        if let Some(link) = db.meeting_short_link(meeting_id).await? {
+            if !link.public {
+                db.set_short_link_public(meeting_id, true).await?;
+            }
+            return Ok(link.slug);
+        }
+        let slug = new_slug();
+        if db.create_short_link(meeting_id, &slug).await? {
+            return Ok(slug);
+        }
+    }
+    bail!(
+        "no free short link slug for meeting {} in {} attempts",
+        meeting_id,
+        MAX_ATTEMPTS
+    )
+}
+
+/// A new random slug.
+pub fn generate() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SLUG_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Whether `slug` could be one [`generate`] made, checked before looking it
+/// up.
+pub fn is_well_formed(slug: &str) -> bool {
+    slug.len() == SLUG_LEN && slug.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// The short link with `slug` on the bot at `base_url`.
+pub fn url(base_url: &str, slug: &str) -> String {
+    format!("{}{}{}", base_url, PATH_PREFIX, slug)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::testing::test_state;
+    use crate::database::models::{MeetLinkKind, Meeting};
+
+    async fn meeting(db: &Database, code: &str) -> i64 {
+        let user = db.create_user("U0000A001", "T012AB3C4").await.unwrap();
+        db.create_meeting(&Meeting::new(
+            user.id,
+            format!("https://meet.google.com/{}", code),
+            None,
+            MeetLinkKind::Meet,
+        ))
+        .await
+        .unwrap()
+        .id
+        .unwrap()
+    }
+
+    #[test]
+    fn test_slugs_are_random_and_checked() {
+        let slug = generate();
+        assert!(is_well_formed(&slug), "{}", slug);
+        assert_ne!(generate(), slug);
+        for bad in ["42", "abc/defghi", "abcdefghij1", "abcdefghi-"] {
+            assert!(!is_well_formed(bad), "{}", bad);
+        }
+        assert_eq!(
+            url("https://bot.example.com", "AbCdE12345"),
+            "https://bot.example.com/m/AbCdE12345"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_taken_slugs_are_skipped() {
+        let (state, _pool) = test_state().await;
+        let first = meeting(&state.db, "abc-defg-hij").await;
+        let second = meeting(&state.db, "xyz-abcd-efg").await;
+        assert_eq!(
+            publish(&state.db, first, || "AAAAAAAAAA".to_string())
+                .await
+                .unwrap(),
+            "AAAAAAAAAA"
+        );
+
+        let mut slugs = vec!["BBBBBBBBBB", "AAAAAAAAAA"];
+        let slug = publish(&state.db, second, || slugs.pop().unwrap().to_string())
+            .await
+            .unwrap();
+        assert_eq!(slug, "BBBBBBBBBB");
+        assert_eq!(
+            state.db.follow_short_link("AAAAAAAAAA").await.unwrap(),
+            Some("https://meet.google.com/abc-defg-hij".to_string())
+        );
+
+        // Every slug taken is an error rather than a link to another meeting
+        let third = meeting(&state.db, "ghi-jklm-nop").await;
+        assert!(publish(&state.db, third, || "AAAAAAAAAA".to_string())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_republishing_keeps_the_slug() {
+        let (state, _pool) = test_state().await;
+        let id = meeting(&state.db, "abc-defg-hij").await;
+        let slug = publish(&state.db, id, generate).await.unwrap();
+
+        assert!(state.db.set_short_link_public(id, false).await.unwrap());
+        assert_eq!(state.db.follow_short_link(&slug).await.unwrap(), None);
+        assert_eq!(publish(&state.db, id, generate).await.unwrap(), slug);
+        assert!(state.db.follow_short_link(&slug).await.unwrap().is_some());
+        assert_eq!(
+            state
+                .db
+                .meeting_short_link(id)
+                .await
+                .unwrap()
+                .unwrap()
+                .click_count,
+            1
+        );
+    }
+}
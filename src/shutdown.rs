@@ -0,0 +1,190 @@
+use axum::Router;
+use std::future::Future;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+
+use crate::listener::Listener;
+
+/// Resolves on SIGTERM (what orchestrators send on deploy) or Ctrl-C.
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install the Ctrl-C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install the SIGTERM handler")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Received Ctrl-C"),
+        _ = terminate => info!("Received SIGTERM"),
+    }
+}
+
+/// Serves `app` until `signal` resolves, then stops accepting connections,
+/// cancels `background` so periodic tasks wind down, and waits up to
+/// `drain_timeout` for in-flight requests to finish.
+///
+/// A meeting creation cut off halfway can leave a calendar event that was
+/// never announced in Slack, so requests get the chance to complete; the
+/// timeout only keeps a stuck request from holding up a deploy forever.
+pub async fn serve(
+    listener: Listener,
+    app: Router,
+    signal: impl Future<Output = ()>,
+    background: CancellationToken,
+    drain_timeout: Duration,
+) -> anyhow::Result<()> {
+    let mut server = listener.serve(app, background.clone());
+
+    tokio::select! {
+        result = &mut server => return Ok(result?),
+        _ = signal => {}
+    }
+
+    info!(
+        "Shutting down: no longer accepting connections, draining in-flight requests (up to {}s)",
+        drain_timeout.as_secs()
+    );
+    background.cancel();
+
+    match tokio::time::timeout(drain_timeout, server).await {
+        Ok(result) => {
+            result?;
+            info!("All in-flight requests finished");
+        }
+        Err(_) => warn!(
+            "In-flight requests still running after {}s; abandoning them",
+            drain_timeout.as_secs()
+        ),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use std::sync::Arc;
+    use tokio::net::TcpListener;
+    use tokio::sync::{oneshot, Notify};
+
+    /// Starts `app` on a random port; returns its address, the trigger for
+    /// the shutdown signal and the server task.
+    async fn start(
+        app: Router,
+        drain_timeout: Duration,
+    ) -> (
+        String,
+        oneshot::Sender<()>,
+        tokio::task::JoinHandle<anyhow::Result<()>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = format!("http://{}", listener.local_addr().unwrap());
+        let (trigger, signal) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            Listener::Tcp(listener),
+            app,
+            async {
+                let _ = signal.await;
+            },
+            CancellationToken::new(),
+            drain_timeout,
+        ));
+        (addr, trigger, server)
+    }
+
+    /// A handler that stalls, like a slow call to Google, until released.
+    fn stalling_app(entered: Arc<Notify>, release: Arc<Notify>) -> Router {
+        Router::new().route(
+            "/slow",
+            get(move || async move {
+                entered.notify_one();
+                release.notified().await;
+                "meeting created"
+            }),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_request_completes_before_exit() {
+        let entered = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let (addr, trigger, server) = start(
+            stalling_app(entered.clone(), release.clone()),
+            Duration::from_secs(5),
+        )
+        .await;
+
+        let request = tokio::spawn(reqwest::get(format!("{}/slow", addr)));
+        entered.notified().await;
+
+        trigger.send(()).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(
+            !server.is_finished(),
+            "server exited with a request in flight"
+        );
+
+        release.notify_one();
+        let response = request.await.unwrap().unwrap();
+        assert_eq!(response.text().await.unwrap(), "meeting created");
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server exits once drained")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_drain_is_bounded_by_the_timeout() {
+        let entered = Arc::new(Notify::new());
+        let (addr, trigger, server) = start(
+            stalling_app(entered.clone(), Arc::new(Notify::new())),
+            Duration::from_millis(100),
+        )
+        .await;
+
+        let _request = tokio::spawn(reqwest::get(format!("{}/slow", addr)));
+        entered.notified().await;
+        trigger.send(()).unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .expect("server gives up on the stuck request")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancels_background_tasks() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let background = CancellationToken::new();
+        let task = tokio::spawn(background.clone().cancelled_owned());
+
+        serve(
+            Listener::Tcp(listener),
+            Router::new(),
+            async {},
+            background,
+            Duration::from_secs(1),
+        )
+        .await
+        .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(1), task)
+            .await
+            .expect("background task was cancelled")
+            .unwrap();
+    }
+}
@@ -0,0 +1,481 @@
+//! Typed Block Kit blocks for rich messages, serializing to Slack's schema.
+//! Slack rejects a message breaking any of its limits as a whole, so
+//! [`validate`] checks them before a message is sent; the response builder
+//! runs it on every message with blocks.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::text;
+
+/// Most blocks Slack accepts in one message.
+pub const MAX_BLOCKS: usize = 50;
+const MAX_SECTION_TEXT: usize = 3000;
+const MAX_SECTION_FIELDS: usize = 10;
+const MAX_FIELD_TEXT: usize = 2000;
+const MAX_CONTEXT_ELEMENTS: usize = 10;
+const MAX_ACTIONS_ELEMENTS: usize = 25;
+const MAX_BUTTON_TEXT: usize = 75;
+const MAX_BUTTON_URL: usize = 3000;
+const MAX_BUTTON_VALUE: usize = 2000;
+const MAX_ACTION_ID: usize = 255;
+/// Longest title shown in a message, as Slack caps header and plain text;
+/// a title is one part of a message that has to fit the others as well.
+const MAX_SHOWN_TITLE: usize = 150;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BlockError {
+    #[error("A message can have at most {MAX_BLOCKS} blocks, got {0}")]
+    TooManyBlocks(usize),
+    #[error("A {block} block can have at most {max} elements, got {count}")]
+    TooManyElements {
+        block: &'static str,
+        count: usize,
+        max: usize,
+    },
+    #[error("{field} can be at most {max} characters, got {len}")]
+    TextTooLong {
+        field: &'static str,
+        len: usize,
+        max: usize,
+    },
+    #[error("A section needs text or fields")]
+    EmptySection,
+    #[error("Button text must be plain text")]
+    ButtonTextNotPlain,
+    #[error("A message with blocks needs fallback text for notifications")]
+    MissingFallbackText,
+}
+
+/// A text object; most places take either kind.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Text {
+    Mrkdwn { text: String },
+    PlainText { text: String, emoji: bool },
+}
+
+impl Text {
+    pub fn mrkdwn(text: impl Into<String>) -> Self {
+        Text::Mrkdwn { text: text.into() }
+    }
+
+    /// Plain text, with emoji shortcodes like `:wave:` rendered.
+    pub fn plain(text: impl Into<String>) -> Self {
+        Text::PlainText {
+            text: text.into(),
+            emoji: true,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Text::Mrkdwn { text } | Text::PlainText { text, .. } => text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ButtonStyle {
+    Primary,
+    Danger,
+}
+
+/// A button opening `url`, or one the bot acts on with `value`. Slack sends
+/// an interaction when either is clicked, which the interactions endpoint
+/// acknowledges.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Button {
+    pub text: Text,
+    pub action_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<ButtonStyle>,
+}
+
+impl Button {
+    pub fn link(
+        action_id: impl Into<String>,
+        text: impl Into<String>,
+        url: impl Into<String>,
+    ) -> Self {
+        Self {
+            text: Text::plain(text),
+            action_id: action_id.into(),
+            url: Some(url.into()),
+            value: None,
+            style: None,
+        }
+    }
+
+    /// A button handled by the bot, which gets `value` back with the click.
+    pub fn action(
+        action_id: impl Into<String>,
+        text: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Self {
+        Self {
+            text: Text::plain(text),
+            action_id: action_id.into(),
+            url: None,
+            value: Some(value.into()),
+            style: None,
+        }
+    }
+
+    pub fn style(mut self, style: ButtonStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+}
+
+/// An interactive element of an actions block or section accessory.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Element {
+    Button(Button),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Section {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<Text>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<Text>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accessory: Option<Element>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Context {
+    pub elements: Vec<Text>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Actions {
+    pub elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Block {
+    Section(Section),
+    Context(Context),
+    Actions(Actions),
+    Divider,
+}
+
+impl Block {
+    pub fn section(text: Text) -> Self {
+        Block::Section(Section {
+            text: Some(text),
+            fields: Vec::new(),
+            accessory: None,
+        })
+    }
+
+    /// A section with `button` at its side.
+    pub fn section_with_button(text: Text, button: Button) -> Self {
+        Block::Section(Section {
+            text: Some(text),
+            fields: Vec::new(),
+            accessory: Some(Element::Button(button)),
+        })
+    }
+
+    /// A section laying `fields` out in two columns.
+    pub fn fields(fields: Vec<Text>) -> Self {
+        Block::Section(Section {
+            text: None,
+            fields,
+            accessory: None,
+        })
+    }
+
+    pub fn context(elements: Vec<Text>) -> Self {
+        Block::Context(Context { elements })
+    }
+
+    pub fn actions(buttons: Vec<Button>) -> Self {
+        Block::Actions(Actions {
+            elements: buttons.into_iter().map(Element::Button).collect(),
+        })
+    }
+
+    /// Buttons in this block, wherever they sit.
+    pub fn buttons(&self) -> Vec<&Button> {
+        let elements: Vec<&Element> = match self {
+            Block::Section(section) => section.accessory.iter().collect(),
+            Block::Actions(actions) => actions.elements.iter().collect(),
+            Block::Context(_) | Block::Divider => Vec::new(),
+        };
+        elements
+            .into_iter()
+            .map(|Element::Button(button)| button)
+            .collect()
+    }
+}
+
+/// Escapes the characters mrkdwn gives meaning to, for user-supplied text
+/// such as meeting titles.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// A meeting or poll title for mrkdwn: escaped, and shortened between
+/// graphemes to what a message shows of a title.
+pub fn title(title: &str) -> String {
+    escape(&text::truncate_chars(title, MAX_SHOWN_TITLE))
+}
+
+/// `at` as mrkdwn Slack shows in each reader's own timezone, as "Tomorrow
+/// at 9:00 AM"; clients that can't get the time in UTC.
+pub fn date(at: DateTime<Utc>) -> String {
+    format!(
+        "<!date^{}^{{date_short_pretty}} at {{time}}|{}>",
+        at.timestamp(),
+        at.format("%a %-d %b %Y %H:%M UTC")
+    )
+}
+
+/// Checks `blocks` against Slack's limits for a message.
+pub fn validate(blocks: &[Block]) -> Result<(), BlockError> {
+    if blocks.len() > MAX_BLOCKS {
+        return Err(BlockError::TooManyBlocks(blocks.len()));
+    }
+
+    for block in blocks {
+        match block {
+            Block::Section(section) => {
+                if section.text.is_none() && section.fields.is_empty() {
+                    return Err(BlockError::EmptySection);
+                }
+                if let Some(text) = &section.text {
+                    check_length("Section text", text.as_str(), MAX_SECTION_TEXT)?;
+                }
+                check_count("section", section.fields.len(), MAX_SECTION_FIELDS)?;
+                for field in &section.fields {
+                    check_length("Section field", field.as_str(), MAX_FIELD_TEXT)?;
+                }
+            }
+            Block::Context(context) => {
+                check_count("context", context.elements.len(), MAX_CONTEXT_ELEMENTS)?
+            }
+            Block::Actions(actions) => {
+                check_count("actions", actions.elements.len(), MAX_ACTIONS_ELEMENTS)?
+            }
+            Block::Divider => {}
+        }
+
+        for button in block.buttons() {
+            if !matches!(button.text, Text::PlainText { .. }) {
+                return Err(BlockError::ButtonTextNotPlain);
+            }
+            check_length("Button text", button.text.as_str(), MAX_BUTTON_TEXT)?;
+            if let Some(url) = &button.url {
+                check_length("Button URL", url, MAX_BUTTON_URL)?;
+            }
+            if let Some(value) = &button.value {
+                check_length("Button value", value, MAX_BUTTON_VALUE)?;
+            }
+            check_length("Action id", &button.action_id, MAX_ACTION_ID)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_count(block: &'static str, count: usize, max: usize) -> Result<(), BlockError> {
+    if count > max {
+        return Err(BlockError::TooManyElements { block, count, max });
+    }
+    Ok(())
+}
+
+fn check_length(field: &'static str, text: &str, max: usize) -> Result<(), BlockError> {
+    let len = text.chars().count();
+    if len > max {
+        return Err(BlockError::TextTooLong { field, len, max });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_blocks_serialize_to_slacks_schema() {
+        // As given in Slack's Block Kit reference
+        let blocks = vec![
+            Block::section_with_button(
+                Text::mrkdwn("*Weekly sync* is starting"),
+                Button::link("join", "Join", "https://meet.google.com/abc-defg-hij")
+                    .style(ButtonStyle::Primary),
+            ),
+            Block::fields(vec![Text::mrkdwn("*When*\nNow"), Text::plain("Room 1")]),
+            Block::Divider,
+            Block::context(vec![Text::mrkdwn("Created by <@U012AB3CD>")]),
+            Block::actions(vec![
+                Button::link("docs", "Docs", "https://example.com/docs"),
+                Button::action("vote", "Vote", "7:1"),
+            ]),
+        ];
+
+        assert_eq!(
+            serde_json::to_value(&blocks).unwrap(),
+            json!([
+                {
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": "*Weekly sync* is starting" },
+                    "accessory": {
+                        "type": "button",
+                        "text": { "type": "plain_text", "text": "Join", "emoji": true },
+                        "action_id": "join",
+                        "url": "https://meet.google.com/abc-defg-hij",
+                        "style": "primary"
+                    }
+                },
+                {
+                    "type": "section",
+                    "fields": [
+                        { "type": "mrkdwn", "text": "*When*\nNow" },
+                        { "type": "plain_text", "text": "Room 1", "emoji": true }
+                    ]
+                },
+                { "type": "divider" },
+                {
+                    "type": "context",
+                    "elements": [{ "type": "mrkdwn", "text": "Created by <@U012AB3CD>" }]
+                },
+                {
+                    "type": "actions",
+                    "elements": [
+                        {
+                            "type": "button",
+                            "text": { "type": "plain_text", "text": "Docs", "emoji": true },
+                            "action_id": "docs",
+                            "url": "https://example.com/docs"
+                        },
+                        {
+                            "type": "button",
+                            "text": { "type": "plain_text", "text": "Vote", "emoji": true },
+                            "action_id": "vote",
+                            "value": "7:1"
+                        }
+                    ]
+                }
+            ])
+        );
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(
+            escape("Q&A <@U012AB3CD> > notes"),
+            "Q&amp;A &lt;@U012AB3CD&gt; &gt; notes"
+        );
+    }
+
+    #[test]
+    fn test_long_titles_are_shortened_between_graphemes() {
+        assert_eq!(title("Q&A"), "Q&amp;A");
+
+        // 100 flags are 200 chars; 74 of them and the ellipsis fit in 150
+        let flags = title(&"🇵🇱".repeat(100));
+        assert_eq!(flags, format!("{}…", "🇵🇱".repeat(74)));
+        assert!(flags.chars().count() <= MAX_SHOWN_TITLE);
+    }
+
+    #[test]
+    fn test_date() {
+        let at = "2024-03-05T07:00:00Z".parse().unwrap();
+
+        assert_eq!(
+            date(at),
+            "<!date^1709622000^{date_short_pretty} at {time}|Tue 5 Mar 2024 07:00 UTC>"
+        );
+    }
+
+    #[test]
+    fn test_validate_enforces_slacks_limits() {
+        let button = |i: usize| Button::link(format!("b{}", i), "Open", "https://example.com");
+
+        let cases = [
+            (vec![Block::Divider; 51], BlockError::TooManyBlocks(51)),
+            (
+                vec![Block::actions((0..26).map(button).collect())],
+                BlockError::TooManyElements {
+                    block: "actions",
+                    count: 26,
+                    max: 25,
+                },
+            ),
+            (
+                vec![Block::context(vec![Text::plain("x"); 11])],
+                BlockError::TooManyElements {
+                    block: "context",
+                    count: 11,
+                    max: 10,
+                },
+            ),
+            (
+                vec![Block::section(Text::mrkdwn("é".repeat(3001)))],
+                BlockError::TextTooLong {
+                    field: "Section text",
+                    len: 3001,
+                    max: 3000,
+                },
+            ),
+            (vec![Block::fields(Vec::new())], BlockError::EmptySection),
+            (
+                vec![Block::actions(vec![Button {
+                    text: Text::mrkdwn("*Open*"),
+                    ..button(0)
+                }])],
+                BlockError::ButtonTextNotPlain,
+            ),
+            (
+                vec![Block::section_with_button(
+                    Text::plain("Hi"),
+                    Button::link("b", "x".repeat(76), "https://example.com"),
+                )],
+                BlockError::TextTooLong {
+                    field: "Button text",
+                    len: 76,
+                    max: 75,
+                },
+            ),
+            (
+                vec![Block::actions(vec![Button::action(
+                    "b",
+                    "Vote",
+                    "1".repeat(2001),
+                )])],
+                BlockError::TextTooLong {
+                    field: "Button value",
+                    len: 2001,
+                    max: 2000,
+                },
+            ),
+        ];
+        for (blocks, expected) in cases {
+            assert_eq!(validate(&blocks), Err(expected));
+        }
+
+        assert_eq!(
+            validate(&[
+                Block::actions((0..25).map(button).collect()),
+                Block::section(Text::mrkdwn("é".repeat(3000))),
+            ]),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,188 @@
+//! The slash commands the bot answers to. Workspaces where another app
+//! already owns `/meet` register ours under other names and map them onto
+//! the canonical ones with `SLACK_COMMANDS`, e.g.
+//! `/gmeet=/meet,/gmeet-help=/meet-help`.
+
+use thiserror::Error;
+
+pub const MEET: &str = "/meet";
+/// Shortcut for `/meet status`.
+pub const MEET_AUTH: &str = "/meet-auth";
+/// Shortcut for `/meet help`.
+pub const MEET_HELP: &str = "/meet-help";
+
+const CANONICAL: [&str; 3] = [MEET, MEET_AUTH, MEET_HELP];
+
+/// The registered command names, each mapped to the canonical command it
+/// stands for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlashCommands {
+    aliases: Vec<(String, &'static str)>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SlashCommandsError {
+    #[error("{0:?} must look like /alias=/command")]
+    Malformed(String),
+
+    #[error("{alias:?} maps to unknown command {canonical:?}; known commands are /meet, /meet-auth and /meet-help")]
+    UnknownCommand { alias: String, canonical: String },
+
+    #[error("{0:?} is mapped more than once")]
+    Duplicate(String),
+
+    #[error("no command is mapped to /meet")]
+    MissingMeet,
+}
+
+impl Default for SlashCommands {
+    fn default() -> Self {
+        Self {
+            aliases: CANONICAL
+                .iter()
+                .map(|command| (command.to_string(), *command))
+                .collect(),
+        }
+    }
+}
+
+impl SlashCommands {
+    /// Parses comma-separated `alias=canonical` pairs. Only the listed
+    /// names are answered to, so a canonical name still in use has to be
+    /// mapped to itself.
+    pub fn parse(value: &str) -> Result<Self, SlashCommandsError> {
+        let mut aliases: Vec<(String, &'static str)> = Vec::new();
+        for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (alias, canonical) = entry
+                .split_once('=')
+                .map(|(alias, canonical)| (alias.trim(), canonical.trim()))
+                .filter(|(alias, _)| is_command_name(alias))
+                .ok_or_else(|| SlashCommandsError::Malformed(entry.to_string()))?;
+            let canonical = CANONICAL
+                .into_iter()
+                .find(|known| known.eq_ignore_ascii_case(canonical))
+                .ok_or_else(|| SlashCommandsError::UnknownCommand {
+                    alias: alias.to_string(),
+                    canonical: canonical.to_string(),
+                })?;
+            let alias = alias.to_ascii_lowercase();
+            if aliases.iter().any(|(known, _)| *known == alias) {
+                return Err(SlashCommandsError::Duplicate(alias));
+            }
+            aliases.push((alias, canonical));
+        }
+
+        if !aliases.iter().any(|(_, canonical)| *canonical == MEET) {
+            return Err(SlashCommandsError::MissingMeet);
+        }
+        Ok(Self { aliases })
+    }
+
+    /// Every registered name, in the order they were configured.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.aliases.iter().map(|(alias, _)| alias.as_str())
+    }
+
+    /// The canonical command `name` stands for, if it is registered.
+    pub fn canonical(&self, name: &str) -> Option<&'static str> {
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias == name)
+            .map(|(_, canonical)| *canonical)
+    }
+
+    /// The name `canonical` is registered under in this workspace: the
+    /// first alias configured for it.
+    pub fn name_for<'a>(&'a self, canonical: &'a str) -> &'a str {
+        self.aliases
+            .iter()
+            .find(|(_, known)| *known == canonical)
+            .map_or(canonical, |(alias, _)| alias.as_str())
+    }
+}
+
+/// The `/meet` subcommand a shortcut command runs.
+pub fn shortcut_subcommand(canonical: &str) -> Option<&'static str> {
+    match canonical {
+        MEET_AUTH => Some("status"),
+        MEET_HELP => Some("help"),
+        _ => None,
+    }
+}
+
+fn is_command_name(name: &str) -> bool {
+    name.len() > 1
+        && name.starts_with('/')
+        && name[1..]
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_answers_to_the_canonical_names() {
+        let commands = SlashCommands::default();
+
+        assert_eq!(
+            commands.names().collect::<Vec<_>>(),
+            ["/meet", "/meet-auth", "/meet-help"]
+        );
+        assert_eq!(commands.canonical("/meet-help"), Some(MEET_HELP));
+        assert_eq!(commands.canonical("/gmeet"), None);
+        assert_eq!(commands.name_for(MEET), "/meet");
+    }
+
+    #[test]
+    fn test_parse_remapped_commands() {
+        let commands =
+            SlashCommands::parse(" /gmeet=/meet, /VC=/meet ,/gmeet-help=/meet-help").unwrap();
+
+        assert_eq!(
+            commands.names().collect::<Vec<_>>(),
+            ["/gmeet", "/vc", "/gmeet-help"]
+        );
+        assert_eq!(commands.canonical("/vc"), Some(MEET));
+        assert_eq!(commands.canonical("/gmeet-help"), Some(MEET_HELP));
+        assert_eq!(commands.canonical("/meet"), None);
+        assert_eq!(commands.name_for(MEET), "/gmeet");
+        assert_eq!(commands.name_for(MEET_AUTH), MEET_AUTH);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            (
+                "/gmeet",
+                SlashCommandsError::Malformed("/gmeet".to_string()),
+            ),
+            (
+                "gmeet=/meet",
+                SlashCommandsError::Malformed("gmeet=/meet".to_string()),
+            ),
+            (
+                "/g meet=/meet",
+                SlashCommandsError::Malformed("/g meet=/meet".to_string()),
+            ),
+            (
+                "/gmeet=/meet,/standup=/standup",
+                SlashCommandsError::UnknownCommand {
+                    alias: "/standup".to_string(),
+                    canonical: "/standup".to_string(),
+                },
+            ),
+            (
+                "/gmeet=/meet,/GMEET=/meet-help",
+                SlashCommandsError::Duplicate("/gmeet".to_string()),
+            ),
+            ("/gmeet-help=/meet-help", SlashCommandsError::MissingMeet),
+            ("", SlashCommandsError::MissingMeet),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(SlashCommands::parse(value), Err(expected), "{}", value);
+        }
+    }
+}
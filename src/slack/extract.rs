@@ -0,0 +1,337 @@
+use axum::{
+    async_trait,
+    extract::{
+        rejection::{FailedToBufferBody, StringRejection},
+        ConnectInfo, FromRef, FromRequest, Request,
+    },
+    http::HeaderMap,
+};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+use super::verification::{verify_slack_request_at, SlackVerificationError, VerificationConfig};
+use crate::audit::{self, AuditEvent};
+use crate::database::Database;
+use crate::error::AppError;
+use crate::secret::SecretString;
+use crate::time::SharedClock;
+
+/// Everything needed to verify a Slack request, extractable from the
+/// application state.
+#[derive(Clone)]
+pub struct SlackVerifier {
+    pub signing_secret: SecretString,
+    pub config: VerificationConfig,
+    /// Where rejected requests are recorded, when auditing is wanted.
+    pub audit: Option<Database>,
+    /// What a request's timestamp is checked against.
+    pub clock: SharedClock,
+}
+
+impl SlackVerifier {
+    /// Records a rejected request with the address it came from.
+    async fn audit_rejection(
+        &self,
+        reason: &'static str,
+        ip: Option<SocketAddr>,
+        headers: &HeaderMap,
+    ) {
+        if let Some(db) = &self.audit {
+            let forwarded_for = header_value(headers, "x-forwarded-for");
+            let event = AuditEvent::signature_rejected(
+                reason,
+                ip.map(|addr| addr.ip()),
+                forwarded_for.as_deref(),
+            );
+            audit::record(db, event).await;
+        }
+    }
+}
+
+/// A request body whose Slack signature has been verified.
+///
+/// Reads the raw body and checks it against the `X-Slack-Signature` and
+/// `X-Slack-Request-Timestamp` headers before the handler sees it. Missing
+/// headers, stale timestamps, and signature mismatches are rejected with
+/// `401 Unauthorized`; malformed input is rejected with `400 Bad Request`,
+/// and a body running past the route's size limit, which only shows once
+/// it is read when it came without a `Content-Length`, with `413 Payload
+/// Too Large`.
+#[derive(Debug)]
+pub struct VerifiedSlackBody {
+    pub body: String,
+    pub timestamp: u64,
+    /// Time spent reading the body and checking the signature.
+    pub verification_time: Duration,
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for VerifiedSlackBody
+where
+    S: Send + Sync,
+    SlackVerifier: FromRef<S>,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let started = Instant::now();
+        let verifier = SlackVerifier::from_ref(state);
+        let ip = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| *addr);
+        let headers = req.headers().clone();
+
+        let headers_present = header_value(&headers, "x-slack-signature")
+            .ok_or(SlackVerificationError::MissingSignature)
+            .and_then(|signature| {
+                header_value(&headers, "x-slack-request-timestamp")
+                    .map(|timestamp| (signature, timestamp))
+                    .ok_or(SlackVerificationError::MissingTimestamp)
+            });
+        let (signature, timestamp) = match headers_present {
+            Ok(present) => present,
+            Err(e) => {
+                verifier.audit_rejection(e.reason(), ip, &headers).await;
+                return Err(e.into());
+            }
+        };
+
+        let body = String::from_request(req, state)
+            .await
+            .map_err(|e| match e {
+                StringRejection::FailedToBufferBody(FailedToBufferBody::LengthLimitError(_)) => {
+                    AppError::PayloadTooLarge
+                }
+                e => AppError::BadRequest(format!("unreadable Slack request body: {}", e)),
+            })?;
+
+        if let Err(e) = verify_slack_request_at(
+            &verifier.config,
+            verifier.clock.unix_seconds(),
+            verifier.signing_secret.expose(),
+            &signature,
+            &timestamp,
+            &body,
+        ) {
+            verifier.audit_rejection(e.reason(), ip, &headers).await;
+            if !matches!(
+                e,
+                SlackVerificationError::RequestTooOld
+                    | SlackVerificationError::TimestampInFuture
+                    | SlackVerificationError::SignatureMismatch
+            ) {
+                error!("Slack request verification failed: {}", e);
+            }
+            return Err(e.into());
+        }
+
+        info!("Slack signature verification successful");
+
+        // verify_slack_request_at has already checked that the timestamp parses
+        let timestamp = timestamp
+            .parse()
+            .map_err(|_| SlackVerificationError::InvalidTimestamp)?;
+
+        Ok(Self {
+            body,
+            timestamp,
+            verification_time: started.elapsed(),
+        })
+    }
+}
+
+fn header_value(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::{Clock, SystemClock, TestClock};
+    use axum::{body::Body, http::StatusCode, routing::post, Router};
+    use chrono::{TimeZone, Utc};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use tower::ServiceExt;
+
+    const SECRET: &str = "test_signing_secret";
+
+    fn app() -> Router {
+        app_with_clock(SystemClock::shared())
+    }
+
+    fn app_with_clock(clock: SharedClock) -> Router {
+        Router::new()
+            .route(
+                "/",
+                post(|verified: VerifiedSlackBody| async move { verified.body }),
+            )
+            .with_state(SlackVerifier {
+                signing_secret: SECRET.into(),
+                config: VerificationConfig::default(),
+                audit: None,
+                clock,
+            })
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn sign(timestamp: u64, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+        format!("v0={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn request(signature: Option<&str>, timestamp: Option<u64>, body: &str) -> Request {
+        let mut builder = Request::builder().method("POST").uri("/");
+        if let Some(signature) = signature {
+            builder = builder.header("x-slack-signature", signature);
+        }
+        if let Some(timestamp) = timestamp {
+            builder = builder.header("x-slack-request-timestamp", timestamp.to_string());
+        }
+        builder.body(Body::from(body.to_string())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_valid_request_passes_body_through() {
+        let ts = now();
+        let body = "command=%2Fmeet&text=standup";
+        let response = app()
+            .oneshot(request(Some(&sign(ts, body)), Some(ts), body))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(bytes, body.as_bytes());
+    }
+
+    #[tokio::test]
+    async fn test_missing_signature_header() {
+        let response = app()
+            .oneshot(request(None, Some(now()), "body"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_missing_timestamp_header() {
+        let ts = now();
+        let response = app()
+            .oneshot(request(Some(&sign(ts, "body")), None, "body"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_stale_timestamp() {
+        let ts = now() - 10 * 60;
+        let response = app()
+            .oneshot(request(Some(&sign(ts, "body")), Some(ts), "body"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_max_age_is_checked_against_the_clock() {
+        let clock = TestClock::new(Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap());
+        let app = app_with_clock(clock.clone());
+        let ts = clock.unix_seconds();
+        let body = "command=%2Fmeet";
+        let signed = || request(Some(&sign(ts, body)), Some(ts), body);
+
+        // Aged exactly the 300s allowed
+        clock.advance(chrono::Duration::seconds(300));
+        let response = app.clone().oneshot(signed()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        clock.advance(chrono::Duration::seconds(1));
+        let response = app.oneshot(signed()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_tampered_body() {
+        let ts = now();
+        let response = app()
+            .oneshot(request(Some(&sign(ts, "text=a")), Some(ts), "text=b"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_signature() {
+        let response = app()
+            .oneshot(request(Some("not-a-signature"), Some(now()), "body"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_rejections_are_audited_with_the_caller_address() {
+        let (db, _pool) = crate::database::test_db().await;
+
+        let app = Router::new()
+            .route(
+                "/",
+                post(|verified: VerifiedSlackBody| async move { verified.body }),
+            )
+            .with_state(SlackVerifier {
+                signing_secret: SECRET.into(),
+                config: VerificationConfig::default(),
+                audit: Some(db.clone()),
+                clock: SystemClock::shared(),
+            });
+
+        let ts = now();
+        let mut forged = request(Some(&sign(ts, "text=a")), Some(ts), "text=b");
+        forged
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([203, 0, 113, 9], 40000))));
+        forged
+            .headers_mut()
+            .insert("x-forwarded-for", "198.51.100.7".parse().unwrap());
+        let response = app.clone().oneshot(forged).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = app
+            .clone()
+            .oneshot(request(None, Some(ts), "body"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Valid requests leave no trace
+        let body = "command=%2Fmeet";
+        app.oneshot(request(Some(&sign(ts, body)), Some(ts), body))
+            .await
+            .unwrap();
+
+        let audit = db.audit_log_page(None, 10).await.unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].detail["reason"], "missing_signature");
+        assert_eq!(audit[1].event_type, "signature_rejected");
+        assert_eq!(audit[1].detail["reason"], "signature_mismatch");
+        assert_eq!(audit[1].detail["ip"], "203.0.113.9");
+        assert_eq!(audit[1].detail["forwarded_for"], "198.51.100.7");
+    }
+}
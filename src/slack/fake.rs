@@ -0,0 +1,239 @@
+//! Stand-in for the Slack Web API in tests.
+
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::api::{ChatMessage, FileUpload, ImageUpload, Installation, SlackApi, SlackApiError};
+use crate::secret::SecretString;
+
+/// A message Slack was asked to post later.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScheduledPost {
+    pub id: String,
+    pub message: ChatMessage,
+    pub post_at: DateTime<Utc>,
+}
+
+/// A [`SlackApi`] recording every message, and failing those to the
+/// channels a test chose with the error code it gave.
+#[derive(Debug, Default)]
+pub struct FakeSlackApi {
+    failures: Mutex<HashMap<String, String>>,
+    posted: Mutex<Vec<ChatMessage>>,
+    ephemeral: Mutex<Vec<(String, ChatMessage)>>,
+    updated: Mutex<Vec<(String, ChatMessage)>>,
+    scheduled: Mutex<Vec<ScheduledPost>>,
+    scheduled_count: Mutex<usize>,
+    uploaded: Mutex<Vec<FileUpload>>,
+    images: Mutex<Vec<ImageUpload>>,
+    emails: Mutex<HashMap<String, String>>,
+    timezones: Mutex<HashMap<String, String>>,
+    installs: Mutex<HashMap<String, Installation>>,
+}
+
+impl FakeSlackApi {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes posts to `channel` fail with the Slack error `code`.
+    pub fn fail_channel(&self, channel: &str, code: &str) {
+        self.failures
+            .lock()
+            .unwrap()
+            .insert(channel.to_string(), code.to_string());
+    }
+
+    /// The messages posted so far, oldest first; failed posts aren't among
+    /// them.
+    pub fn posted(&self) -> Vec<ChatMessage> {
+        self.posted.lock().unwrap().clone()
+    }
+
+    /// The ephemeral messages posted so far with the user each was for,
+    /// oldest first.
+    pub fn ephemeral(&self) -> Vec<(String, ChatMessage)> {
+        self.ephemeral.lock().unwrap().clone()
+    }
+
+    /// The messages updated so far with the `ts` of each, oldest first.
+    pub fn updated(&self) -> Vec<(String, ChatMessage)> {
+        self.updated.lock().unwrap().clone()
+    }
+
+    /// The messages scheduled and not deleted, oldest first.
+    pub fn scheduled(&self) -> Vec<ScheduledPost> {
+        self.scheduled.lock().unwrap().clone()
+    }
+
+    /// The files uploaded so far, oldest first.
+    pub fn uploaded(&self) -> Vec<FileUpload> {
+        self.uploaded.lock().unwrap().clone()
+    }
+
+    /// The images uploaded so far, oldest first.
+    pub fn images(&self) -> Vec<ImageUpload> {
+        self.images.lock().unwrap().clone()
+    }
+
+    /// Gives `user` the profile email `email`; others have none.
+    pub fn set_email(&self, user: &str, email: &str) {
+        self.emails
+            .lock()
+            .unwrap()
+            .insert(user.to_string(), email.to_string());
+    }
+
+    /// Gives `user` the timezone `tz`; others have none.
+    pub fn set_timezone(&self, user: &str, tz: &str) {
+        self.timezones
+            .lock()
+            .unwrap()
+            .insert(user.to_string(), tz.to_string());
+    }
+
+    /// Makes `oauth.v2.access` hand out `install` for `code`; other codes
+    /// are `invalid_code`.
+    pub fn set_install(&self, code: &str, install: Installation) {
+        self.installs
+            .lock()
+            .unwrap()
+            .insert(code.to_string(), install);
+    }
+
+    fn check_channel(&self, channel: &str) -> Result<(), SlackApiError> {
+        match self.failures.lock().unwrap().get(channel) {
+            Some(code) => Err(SlackApiError::Api(code.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl SlackApi for FakeSlackApi {
+    async fn post_message(
+        &self,
+        _bot_token: &SecretString,
+        message: &ChatMessage,
+    ) -> Result<(), SlackApiError> {
+        self.check_channel(&message.channel)?;
+        self.posted.lock().unwrap().push(message.clone());
+        Ok(())
+    }
+
+    async fn post_ephemeral(
+        &self,
+        _bot_token: &SecretString,
+        message: &ChatMessage,
+        user: &str,
+    ) -> Result<(), SlackApiError> {
+        self.check_channel(&message.channel)?;
+        self.ephemeral
+            .lock()
+            .unwrap()
+            .push((user.to_string(), message.clone()));
+        Ok(())
+    }
+
+    async fn update_message(
+        &self,
+        _bot_token: &SecretString,
+        message: &ChatMessage,
+        ts: &str,
+    ) -> Result<(), SlackApiError> {
+        self.check_channel(&message.channel)?;
+        self.updated
+            .lock()
+            .unwrap()
+            .push((ts.to_string(), message.clone()));
+        Ok(())
+    }
+
+    async fn schedule_message(
+        &self,
+        _bot_token: &SecretString,
+        message: &ChatMessage,
+        post_at: DateTime<Utc>,
+    ) -> Result<String, SlackApiError> {
+        self.check_channel(&message.channel)?;
+        let mut count = self.scheduled_count.lock().unwrap();
+        *count += 1;
+        let id = format!("Q{:09}", *count);
+        self.scheduled.lock().unwrap().push(ScheduledPost {
+            id: id.clone(),
+            message: message.clone(),
+            post_at,
+        });
+        Ok(id)
+    }
+
+    async fn delete_scheduled_message(
+        &self,
+        _bot_token: &SecretString,
+        channel: &str,
+        scheduled_message_id: &str,
+    ) -> Result<(), SlackApiError> {
+        self.check_channel(channel)?;
+        let mut scheduled = self.scheduled.lock().unwrap();
+        let before = scheduled.len();
+        scheduled.retain(|post| post.id != scheduled_message_id || post.message.channel != channel);
+        if scheduled.len() == before {
+            return Err(SlackApiError::Api(
+                "invalid_scheduled_message_id".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    async fn upload_file(
+        &self,
+        _bot_token: &SecretString,
+        file: &FileUpload,
+    ) -> Result<(), SlackApiError> {
+        self.check_channel(&file.channels)?;
+        self.uploaded.lock().unwrap().push(file.clone());
+        Ok(())
+    }
+
+    async fn upload_image(
+        &self,
+        _bot_token: &SecretString,
+        image: &ImageUpload,
+    ) -> Result<(), SlackApiError> {
+        self.check_channel(&image.channel_id)?;
+        self.images.lock().unwrap().push(image.clone());
+        Ok(())
+    }
+
+    async fn user_email(
+        &self,
+        _bot_token: &SecretString,
+        user: &str,
+    ) -> Result<Option<String>, SlackApiError> {
+        Ok(self.emails.lock().unwrap().get(user).cloned())
+    }
+
+    async fn user_timezone(
+        &self,
+        _bot_token: &SecretString,
+        user: &str,
+    ) -> Result<Option<String>, SlackApiError> {
+        Ok(self.timezones.lock().unwrap().get(user).cloned())
+    }
+
+    async fn oauth_access(
+        &self,
+        _client_id: &str,
+        _client_secret: &SecretString,
+        code: &str,
+        _redirect_uri: &str,
+    ) -> Result<Installation, SlackApiError> {
+        self.installs
+            .lock()
+            .unwrap()
+            .remove(code)
+            .ok_or_else(|| SlackApiError::Api("invalid_code".to_string()))
+    }
+}
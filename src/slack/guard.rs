@@ -0,0 +1,290 @@
+use axum::{
+    error_handling::HandleErrorLayer,
+    extract::Request,
+    http::{header::CONTENT_TYPE, HeaderMap, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+    Router,
+};
+use std::time::Duration;
+use tower::{limit::GlobalConcurrencyLimitLayer, BoxError, ServiceBuilder};
+use tracing::warn;
+
+use crate::handlers::slack::SlackResponse;
+use crate::request_id::RequestId;
+
+/// Default cap on Slack request bodies. Slash commands and interaction
+/// payloads are a few KB at most.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Default time a Slack request gets before it is abandoned, well inside the
+/// window after which Slack gives up and retries.
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// Default number of Slack requests handled at once; more are turned away.
+pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 64;
+
+/// How long Slack waits for a slash command's response before showing the
+/// user an error.
+pub const SLACK_RESPONSE_DEADLINE: Duration = Duration::from_secs(3);
+
+/// Default time a command waits on slow work before acknowledging, leaving
+/// room for the response to reach Slack.
+pub const DEFAULT_ACK_DEADLINE: Duration = Duration::from_millis(2500);
+
+const COMMANDS_PATH: &str = "/slack/commands";
+
+pub const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
+pub const JSON_CONTENT_TYPE: &str = "application/json";
+
+/// Rejects requests that are not form-encoded with `415 Unsupported Media
+/// Type` before the body is read or its signature checked.
+pub async fn require_form_content_type(req: Request, next: Next) -> Response {
+    require_content_type(FORM_CONTENT_TYPE, req, next).await
+}
+
+/// Rejects requests that are not JSON with `415 Unsupported Media Type`.
+pub async fn require_json_content_type(req: Request, next: Next) -> Response {
+    require_content_type(JSON_CONTENT_TYPE, req, next).await
+}
+
+async fn require_content_type(expected: &str, req: Request, next: Next) -> Response {
+    if !has_content_type(req.headers(), expected) {
+        warn!(
+            "Rejecting Slack request with content type {:?}, expected {}",
+            req.headers().get(CONTENT_TYPE),
+            expected
+        );
+        return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+    }
+
+    next.run(req).await
+}
+
+/// Applies backpressure to `router`: at most `max_concurrent` requests run
+/// at once across all its routes, further requests are shed immediately
+/// rather than queued, and each request is abandoned after `timeout`.
+///
+/// Shed and timed-out requests get a 503, except slash commands, which get
+/// an ephemeral reply telling the user to try again since Slack shows
+/// nothing useful for an error status.
+pub fn with_backpressure<S>(
+    router: Router<S>,
+    timeout: Duration,
+    max_concurrent: usize,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    router.layer(
+        ServiceBuilder::new()
+            .layer(HandleErrorLayer::new(overload_response))
+            .load_shed()
+            .layer(GlobalConcurrencyLimitLayer::new(max_concurrent))
+            .timeout(timeout),
+    )
+}
+
+async fn overload_response(uri: Uri, request_id: RequestId, error: BoxError) -> Response {
+    let timed_out = error.is::<tower::timeout::error::Elapsed>();
+    if timed_out {
+        warn!("Slack request to {} timed out", uri.path());
+    } else {
+        warn!("Shedding Slack request to {}: {}", uri.path(), error);
+    }
+
+    if uri.path() != COMMANDS_PATH {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+    let text = if timed_out {
+        "❌ That took too long. Please try again in a moment."
+    } else {
+        "❌ The bot is busy right now. Please try again in a moment."
+    };
+    Json(SlackResponse::ephemeral(text.to_string()).with_error_ref(&request_id)).into_response()
+}
+
+fn has_content_type(headers: &HeaderMap, expected: &str) -> bool {
+    headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case(expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, middleware, routing::post, Router};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tower::ServiceExt;
+    use tower_http::limit::RequestBodyLimitLayer;
+
+    fn app(calls: Arc<AtomicUsize>) -> Router {
+        Router::new()
+            .route(
+                "/",
+                post(move |body: String| async move {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    body
+                })
+                .route_layer(middleware::from_fn(require_form_content_type)),
+            )
+            .layer(RequestBodyLimitLayer::new(DEFAULT_MAX_BODY_BYTES))
+    }
+
+    fn request(content_type: &str, body: Vec<u8>) -> Request {
+        Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(CONTENT_TYPE, content_type)
+            .header("content-length", body.len())
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_form_body_reaches_handler() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let response = app(calls.clone())
+            .oneshot(request(
+                "application/x-www-form-urlencoded; charset=utf-8",
+                b"command=%2Fmeet".to_vec(),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_body_is_rejected() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let response = app(calls.clone())
+            .oneshot(request(
+                FORM_CONTENT_TYPE,
+                vec![b'a'; DEFAULT_MAX_BODY_BYTES + 1],
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test]
+    async fn test_json_body_is_rejected() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let response = app(calls.clone())
+            .oneshot(request(
+                JSON_CONTENT_TYPE,
+                b"{\"command\":\"/meet\"}".to_vec(),
+            ))
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+
+    /// Slack-like routes whose handlers wait for `release` before replying.
+    fn stalling_app(release: Arc<tokio::sync::Notify>, timeout: Duration) -> Router {
+        let stall = move || {
+            let release = release.clone();
+            async move {
+                release.notified().await;
+                "done"
+            }
+        };
+        with_backpressure(
+            Router::new()
+                .route(COMMANDS_PATH, post(stall.clone()))
+                .route("/slack/events", post(stall)),
+            timeout,
+            1,
+        )
+    }
+
+    async fn send(app: Router, uri: &str) -> (StatusCode, String) {
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_stalled_command_times_out_with_ephemeral_reply() {
+        let app = stalling_app(Arc::default(), Duration::from_millis(50));
+
+        let (status, body) = send(app, COMMANDS_PATH).await;
+        assert_eq!(status, StatusCode::OK);
+        let reply: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(reply["response_type"], "ephemeral");
+        let text = reply["text"].as_str().unwrap();
+        assert!(text.starts_with("❌ That took too long"), "{}", text);
+        assert!(text.contains("(ref: "), "{}", text);
+    }
+
+    #[tokio::test]
+    async fn test_stalled_event_times_out_with_503() {
+        let app = stalling_app(Arc::default(), Duration::from_millis(50));
+
+        let (status, _) = send(app, "/slack/events").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_requests_over_the_limit_are_shed() {
+        let release = Arc::new(tokio::sync::Notify::new());
+        let app = stalling_app(release.clone(), Duration::from_secs(5));
+
+        let first = tokio::spawn(send(app.clone(), COMMANDS_PATH));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        // The limit is shared across routes
+        let (status, _) = send(app.clone(), "/slack/events").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        let (status, body) = send(app.clone(), COMMANDS_PATH).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("busy right now"), "{}", body);
+
+        release.notify_one();
+        assert_eq!(first.await.unwrap(), (StatusCode::OK, "done".to_string()));
+
+        // Once the first request is done there is room again
+        release.notify_one();
+        assert_eq!(
+            send(app, "/slack/events").await,
+            (StatusCode::OK, "done".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_missing_content_type_is_rejected() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .body(Body::from("command=%2Fmeet"))
+            .unwrap();
+        let response = app(calls.clone()).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(calls.load(Ordering::SeqCst), 0);
+    }
+}
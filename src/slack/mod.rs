@@ -0,0 +1,10 @@
+pub mod api;
+pub mod blocks;
+pub mod commands;
+pub mod extract;
+pub mod fake;
+pub mod guard;
+pub mod verification;
+
+pub use extract::{SlackVerifier, VerifiedSlackBody};
+pub use verification::{verify_slack_request, SlackVerificationError, VerificationConfig};
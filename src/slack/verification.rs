@@ -0,0 +1,436 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Replay-protection window applied to Slack request timestamps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerificationConfig {
+    /// Maximum age of a request before it is treated as a replay.
+    pub max_age_seconds: u64,
+    /// How far ahead of our clock a request timestamp may be before it is rejected.
+    pub max_future_skew_seconds: u64,
+}
+
+impl Default for VerificationConfig {
+    fn default() -> Self {
+        Self {
+            max_age_seconds: 5 * 60,
+            max_future_skew_seconds: 60,
+        }
+    }
+}
+
+/// Verify that a Slack request is authentic using the signing secret
+///
+/// Slack sends a signature in the `X-Slack-Signature` header and a timestamp
+/// in the `X-Slack-Request-Timestamp` header. We verify the request by:
+/// 1. Checking that the timestamp is within the replay window in `config`
+///    (by default no more than 5 minutes old or a minute in the future)
+/// 2. Computing HMAC-SHA256 of "v0:{timestamp}:{body}" using the signing secret
+/// 3. Comparing our computed signature with the one from Slack in constant time
+pub fn verify_slack_request(
+    config: &VerificationConfig,
+    signing_secret: &str,
+    signature: &str,
+    timestamp: &str,
+    body: &str,
+) -> Result<(), SlackVerificationError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| SlackVerificationError::SystemTimeError)?
+        .as_secs();
+
+    verify_slack_request_at(config, now, signing_secret, signature, timestamp, body)
+}
+
+/// Same as [`verify_slack_request`], with the current Unix time supplied by the
+/// caller.
+pub fn verify_slack_request_at(
+    config: &VerificationConfig,
+    now: u64,
+    signing_secret: &str,
+    signature: &str,
+    timestamp: &str,
+    body: &str,
+) -> Result<(), SlackVerificationError> {
+    // Parse the timestamp
+    let request_timestamp = timestamp
+        .parse::<u64>()
+        .map_err(|_| SlackVerificationError::InvalidTimestamp)?;
+
+    // Check if the request is too old (replay attack protection)
+    if now.saturating_sub(request_timestamp) > config.max_age_seconds {
+        warn!(
+            "Request timestamp is too old: {} vs {}",
+            request_timestamp, now
+        );
+        return Err(SlackVerificationError::RequestTooOld);
+    }
+
+    if request_timestamp.saturating_sub(now) > config.max_future_skew_seconds {
+        warn!(
+            "Request timestamp is in the future: {} vs {}",
+            request_timestamp, now
+        );
+        return Err(SlackVerificationError::TimestampInFuture);
+    }
+
+    // Parse the signature (should start with "v0=")
+    let provided_signature = signature
+        .strip_prefix("v0=")
+        .ok_or(SlackVerificationError::InvalidSignatureFormat)?;
+    let expected_signature_bytes = hex::decode(provided_signature)
+        .map_err(|_| SlackVerificationError::InvalidSignatureFormat)?;
+
+    // Create the basestring: v0:timestamp:body
+    let basestring = format!("v0:{}:{}", timestamp, body);
+    debug!(
+        "Verifying signature for basestring length: {}",
+        basestring.len()
+    );
+
+    // Compute the expected signature and compare using constant-time comparison
+    let mut mac = HmacSha256::new_from_slice(signing_secret.as_bytes())
+        .map_err(|_| SlackVerificationError::InvalidSecret)?;
+    mac.update(basestring.as_bytes());
+
+    if mac.verify_slice(&expected_signature_bytes).is_ok() {
+        debug!("Slack signature verification successful");
+        Ok(())
+    } else {
+        warn!("Slack signature verification failed");
+        Err(SlackVerificationError::SignatureMismatch)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SlackVerificationError {
+    #[error("Missing X-Slack-Signature header")]
+    MissingSignature,
+
+    #[error("Missing X-Slack-Request-Timestamp header")]
+    MissingTimestamp,
+
+    #[error("Invalid timestamp format")]
+    InvalidTimestamp,
+
+    #[error("Request is too old (possible replay attack)")]
+    RequestTooOld,
+
+    #[error("Request timestamp is too far in the future")]
+    TimestampInFuture,
+
+    #[error("Invalid signature format")]
+    InvalidSignatureFormat,
+
+    #[error("Invalid signing secret")]
+    InvalidSecret,
+
+    #[error("Signature mismatch")]
+    SignatureMismatch,
+
+    #[error("System time error")]
+    SystemTimeError,
+}
+
+impl SlackVerificationError {
+    /// Stable identifier for the audit log.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            SlackVerificationError::MissingSignature => "missing_signature",
+            SlackVerificationError::MissingTimestamp => "missing_timestamp",
+            SlackVerificationError::InvalidTimestamp => "invalid_timestamp",
+            SlackVerificationError::RequestTooOld => "request_too_old",
+            SlackVerificationError::TimestampInFuture => "timestamp_in_future",
+            SlackVerificationError::InvalidSignatureFormat => "invalid_signature_format",
+            SlackVerificationError::InvalidSecret => "invalid_secret",
+            SlackVerificationError::SignatureMismatch => "signature_mismatch",
+            SlackVerificationError::SystemTimeError => "system_time_error",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slack_signature_verification() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        // Use current timestamp to avoid RequestTooOld error
+        let current_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let timestamp = current_timestamp.to_string();
+
+        let signing_secret = "8f742231b10e8888abcd99yyyzzz85a5";
+        let body = "token=xyzz0WbapA4vBCDEFasx0q6G&team_id=T1DC2JH3J&team_domain=testteamnow&channel_id=G8PSS9T3V&channel_name=foobar&user_id=U2CERLKJA&user_name=roadrunner&command=%2Fwebhook-collect&text=&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1234%2F5678&trigger_id=2142974.1.R043K93V9AL";
+
+        // Create a valid signature for our test
+        let basestring = format!("v0:{}:{}", timestamp, body);
+        let mut mac = HmacSha256::new_from_slice(signing_secret.as_bytes()).unwrap();
+        mac.update(basestring.as_bytes());
+        let computed_signature = mac.finalize().into_bytes();
+        let expected_signature = format!("v0={}", hex::encode(computed_signature));
+
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            signing_secret,
+            &expected_signature,
+            &timestamp,
+            body,
+        );
+        assert!(
+            result.is_ok(),
+            "Signature verification should succeed: {:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_invalid_signature() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let current_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let timestamp = current_timestamp.to_string();
+
+        let signing_secret = "8f742231b10e8888abcd99yyyzzz85a5";
+        let body = "different body";
+        let wrong_signature = "v0=a2114d57b48eac39b9ad189dd8316235a7b4a8d21a10bd27519666489c69b503";
+
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            signing_secret,
+            wrong_signature,
+            &timestamp,
+            body,
+        );
+        assert!(matches!(
+            result,
+            Err(SlackVerificationError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_old_timestamp() {
+        let signing_secret = "test_secret";
+        let old_timestamp = "1000000000"; // Very old timestamp
+        let body = "test body";
+        let signature = "v0=invalid";
+
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            signing_secret,
+            signature,
+            old_timestamp,
+            body,
+        );
+        assert!(matches!(result, Err(SlackVerificationError::RequestTooOld)));
+    }
+
+    #[test]
+    fn test_future_timestamp() {
+        let current_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        let signing_secret = "test_secret";
+        let body = "test body";
+
+        // Well beyond the skew allowance
+        let far_future = (current_timestamp + 10 * 60).to_string();
+        let signature = sign(signing_secret, &far_future, body);
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            signing_secret,
+            &signature,
+            &far_future,
+            body,
+        );
+        assert!(matches!(
+            result,
+            Err(SlackVerificationError::TimestampInFuture)
+        ));
+
+        // A few seconds of clock drift is tolerated
+        let near_future = (current_timestamp + 5).to_string();
+        let signature = sign(signing_secret, &near_future, body);
+        assert!(verify_slack_request(
+            &VerificationConfig::default(),
+            signing_secret,
+            &signature,
+            &near_future,
+            body
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_invalid_timestamp() {
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            "test_secret",
+            "v0=00",
+            "not-a-number",
+            "body",
+        );
+        assert!(matches!(
+            result,
+            Err(SlackVerificationError::InvalidTimestamp)
+        ));
+    }
+
+    #[test]
+    fn test_tampered_body() {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            .to_string();
+
+        let signing_secret = "test_secret";
+        let signature = sign(signing_secret, &timestamp, "text=hello");
+
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            signing_secret,
+            &signature,
+            &timestamp,
+            "text=hellO",
+        );
+        assert!(matches!(
+            result,
+            Err(SlackVerificationError::SignatureMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_non_hex_signature() {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            .to_string();
+
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            "test_secret",
+            "v0=zzzz",
+            &timestamp,
+            "body",
+        );
+        assert!(matches!(
+            result,
+            Err(SlackVerificationError::InvalidSignatureFormat)
+        ));
+    }
+
+    #[test]
+    fn test_max_age_boundary() {
+        let config = VerificationConfig::default();
+        let now = 1_700_000_000;
+        let secret = "test_secret";
+        let body = "test body";
+
+        let at_limit = (now - config.max_age_seconds).to_string();
+        let signature = sign(secret, &at_limit, body);
+        assert!(verify_slack_request_at(&config, now, secret, &signature, &at_limit, body).is_ok());
+
+        let past_limit = (now - config.max_age_seconds - 1).to_string();
+        let signature = sign(secret, &past_limit, body);
+        assert!(matches!(
+            verify_slack_request_at(&config, now, secret, &signature, &past_limit, body),
+            Err(SlackVerificationError::RequestTooOld)
+        ));
+    }
+
+    #[test]
+    fn test_future_skew_boundary() {
+        let config = VerificationConfig {
+            max_age_seconds: 60,
+            max_future_skew_seconds: 10,
+        };
+        let now = 1_700_000_000;
+        let secret = "test_secret";
+        let body = "test body";
+
+        let slightly_ahead = (now + 3).to_string();
+        let signature = sign(secret, &slightly_ahead, body);
+        assert!(
+            verify_slack_request_at(&config, now, secret, &signature, &slightly_ahead, body)
+                .is_ok()
+        );
+
+        let at_limit = (now + 10).to_string();
+        let signature = sign(secret, &at_limit, body);
+        assert!(verify_slack_request_at(&config, now, secret, &signature, &at_limit, body).is_ok());
+
+        let past_limit = (now + 11).to_string();
+        let signature = sign(secret, &past_limit, body);
+        assert!(matches!(
+            verify_slack_request_at(&config, now, secret, &signature, &past_limit, body),
+            Err(SlackVerificationError::TimestampInFuture)
+        ));
+    }
+
+    #[test]
+    fn test_tightened_max_age() {
+        let config = VerificationConfig {
+            max_age_seconds: 30,
+            ..VerificationConfig::default()
+        };
+        let now = 1_700_000_000;
+        let secret = "test_secret";
+        let body = "test body";
+
+        let timestamp = (now - 31).to_string();
+        let signature = sign(secret, &timestamp, body);
+        assert!(matches!(
+            verify_slack_request_at(&config, now, secret, &signature, &timestamp, body),
+            Err(SlackVerificationError::RequestTooOld)
+        ));
+    }
+
+    fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+        let basestring = format!("v0:{}:{}", timestamp, body);
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(basestring.as_bytes());
+        format!("v0={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    #[test]
+    fn test_invalid_signature_format() {
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let current_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let timestamp = current_timestamp.to_string();
+
+        let signing_secret = "test_secret";
+        let body = "test body";
+        let invalid_signature = "invalid_format";
+
+        let result = verify_slack_request(
+            &VerificationConfig::default(),
+            signing_secret,
+            invalid_signature,
+            &timestamp,
+            body,
+        );
+        assert!(matches!(
+            result,
+            Err(SlackVerificationError::InvalidSignatureFormat)
+        ));
+    }
+}
@@ -0,0 +1,374 @@
+//! A user's meeting statistics for `/meet stats`: meetings created this
+//! week, this month and in all, the words their titles use most, and their
+//! longest run of days with meetings. Weeks start on Monday; weeks, months
+//! and days are those of the timezone the stats are asked for, with each
+//! of their starts at the offset it has then.
+//!
+//! Also a workspace's activity over a period, for the weekly digest.
+
+use anyhow::Result;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+
+use crate::database::models::MeetingCounts;
+use crate::database::Database;
+use crate::quota;
+
+/// Title words shown.
+pub const TOP_WORDS: usize = 5;
+
+/// Creators a workspace's activity names.
+pub const TOP_CREATORS: usize = 3;
+
+/// Words too common in titles to tell anything.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "at", "for", "in", "of", "on", "or", "the", "to", "with",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct UsageStats {
+    pub counts: MeetingCounts,
+    /// The most used title words with how many titles have them, most used
+    /// first.
+    pub top_words: Vec<(String, usize)>,
+    /// Most consecutive days with at least one meeting.
+    pub longest_streak: usize,
+}
+
+/// What a workspace did between two times.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct TeamActivity {
+    pub meetings: i64,
+    /// Slack user ids of whoever created the most meetings, with how many,
+    /// most first.
+    pub top_creators: Vec<(String, i64)>,
+    /// The UTC day with the most meetings, the earliest of a tie, and how
+    /// many it had.
+    pub busiest_day: Option<(NaiveDate, i64)>,
+}
+
+/// The activity of the workspace `slack_team_id` from `from` until just
+/// before `until`.
+pub async fn team_activity(
+    db: &Database,
+    slack_team_id: &str,
+    from: NaiveDateTime,
+    until: NaiveDateTime,
+) -> Result<TeamActivity> {
+    let mut creators = db.team_meeting_creators(slack_team_id, from, until).await?;
+    let meetings = creators.iter().map(|(_, meetings)| meetings).sum();
+    creators.truncate(TOP_CREATORS);
+    let days = db.team_meetings_per_day(slack_team_id, from, until).await?;
+    let busiest_day = days
+        .into_iter()
+        .reduce(|busiest, day| if day.1 > busiest.1 { day } else { busiest });
+
+    Ok(TeamActivity {
+        meetings,
+        top_creators: creators,
+        busiest_day,
+    })
+}
+
+/// The stats of `user_id` as of `now`, in the timezone of `now`.
+pub async fn usage_stats(db: &Database, user_id: i64, now: DateTime<Tz>) -> Result<UsageStats> {
+    let counts = db
+        .count_user_meetings(user_id, week_start(&now), month_start(&now))
+        .await?;
+    if counts.all_time == 0 {
+        return Ok(UsageStats::default());
+    }
+
+    let titles = db.user_meeting_titles(user_id).await?;
+    let tz = now.timezone();
+    let mut days: Vec<NaiveDate> = db
+        .user_meeting_times(user_id)
+        .await?
+        .iter()
+        .map(|created_at| tz.from_utc_datetime(created_at).date_naive())
+        .collect();
+    days.dedup();
+
+    Ok(UsageStats {
+        counts,
+        top_words: top_words(&titles, TOP_WORDS),
+        longest_streak: longest_streak(&days),
+    })
+}
+
+/// Midnight starting the Monday of `now`'s week, in UTC as meetings are
+/// stored.
+fn week_start(now: &DateTime<Tz>) -> NaiveDateTime {
+    let monday = now.date_naive() - chrono::Days::new(now.weekday().num_days_from_monday().into());
+    quota::midnight(monday, now.timezone()).naive_utc()
+}
+
+/// Midnight starting the first of `now`'s month, in UTC.
+fn month_start(now: &DateTime<Tz>) -> NaiveDateTime {
+    let first = now
+        .date_naive()
+        .with_day(1)
+        .expect("every month has a first");
+    quota::midnight(first, now.timezone()).naive_utc()
+}
+
+/// The `limit` words found in most titles, ties broken alphabetically.
+/// Words are compared lowercased, counted once per title, and skipped when
+/// a single character or a [stop word](STOP_WORDS).
+fn top_words(titles: &[String], limit: usize) -> Vec<(String, usize)> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for title in titles {
+        let mut words: Vec<String> = title
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| word.chars().count() > 1)
+            .map(str::to_lowercase)
+            .filter(|word| !STOP_WORDS.contains(&word.as_str()))
+            .collect();
+        words.sort();
+        words.dedup();
+        for word in words {
+            *counts.entry(word).or_default() += 1;
+        }
+    }
+
+    let mut words: Vec<(String, usize)> = counts.into_iter().collect();
+    words.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    words.truncate(limit);
+    words
+}
+
+/// The longest run of consecutive days in `days`, which are sorted and
+/// distinct.
+fn longest_streak(days: &[NaiveDate]) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for &day in days {
+        current = match previous {
+            Some(previous) if previous.succ_opt() == Some(day) => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        previous = Some(day);
+    }
+    longest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_db;
+
+    const USER_ID: i64 = 1;
+
+    /// A database with user 1 and meetings created at the given UTC times.
+    async fn db_with_meetings(meetings: &[(&str, Option<&str>)]) -> Database {
+        let meetings: Vec<_> = meetings
+            .iter()
+            .map(|(created_at, title)| (USER_ID, *created_at, *title))
+            .collect();
+        db_with_users_meetings(&meetings).await
+    }
+
+    /// A database with users 1 and 2 in T012AB3C4, user 3 in T098ZY7XW,
+    /// and meetings created by them at the given UTC times.
+    async fn db_with_users_meetings(meetings: &[(i64, &str, Option<&str>)]) -> Database {
+        let (db, pool) = test_db().await;
+        db.create_user("U012AB3CD", "T012AB3C4").await.unwrap();
+        db.create_user("U098ZY7XW", "T012AB3C4").await.unwrap();
+        db.create_user("U055XX5XX", "T098ZY7XW").await.unwrap();
+        for (user_id, created_at, title) in meetings {
+            sqlx::query(
+                "INSERT INTO meetings (user_id, meet_link, title, created_at) VALUES (?1, 'https://meet.google.com/abc-defg-hij', ?2, ?3)",
+            )
+            .bind(user_id)
+            .bind(*title)
+            .bind(*created_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+        }
+        db
+    }
+
+    /// `local` in the timezone `tz`.
+    fn at(tz: Tz, local: &str) -> DateTime<Tz> {
+        tz.from_local_datetime(&local.parse().unwrap()).unwrap()
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_weeks_and_months_follow_the_timezone() {
+        // Monday 4 March 2024 at 00:30 in Johannesburg (UTC+2), still
+        // Sunday in UTC
+        let now = at(Tz::Africa__Johannesburg, "2024-03-04T00:30:00");
+        let db = db_with_meetings(&[
+            // Sunday 23:59:59 in UTC+2: last week, but this month
+            ("2024-03-03 21:59:59", None),
+            // Monday 00:00 in UTC+2: this week
+            ("2024-03-03 22:00:00", None),
+            // 1 March 00:30 in UTC+2, still February in UTC: this month
+            ("2024-02-29 22:30:00", None),
+            // 29 February 23:59 in UTC+2: last month
+            ("2024-02-29 21:59:00", None),
+        ])
+        .await;
+
+        let stats = usage_stats(&db, USER_ID, now).await.unwrap();
+
+        assert_eq!(
+            stats.counts,
+            MeetingCounts {
+                this_week: 1,
+                this_month: 3,
+                all_time: 4,
+            }
+        );
+
+        // The same meetings in UTC, where it is still Sunday
+        let stats = usage_stats(&db, USER_ID, at(Tz::UTC, "2024-03-03T22:30:00"))
+            .await
+            .unwrap();
+        assert_eq!(stats.counts.this_week, 4);
+        assert_eq!(stats.counts.this_month, 2);
+    }
+
+    #[tokio::test]
+    async fn test_streaks_count_local_days() {
+        let db = db_with_meetings(&[
+            // 1, 2 and 3 March in UTC+2, but 29 February and 2 March in UTC
+            ("2024-02-29 22:30:00", None),
+            ("2024-03-02 09:00:00", None),
+            ("2024-03-02 23:00:00", None),
+            ("2024-03-10 12:00:00", None),
+        ])
+        .await;
+
+        let local = usage_stats(
+            &db,
+            USER_ID,
+            at(Tz::Africa__Johannesburg, "2024-03-11T12:00:00"),
+        )
+        .await
+        .unwrap();
+        assert_eq!(local.longest_streak, 3);
+        let utc = usage_stats(&db, USER_ID, at(Tz::UTC, "2024-03-11T12:00:00"))
+            .await
+            .unwrap();
+        assert_eq!(utc.longest_streak, 1);
+    }
+
+    #[tokio::test]
+    async fn test_boundaries_across_summer_time_keep_their_own_offset() {
+        // New York moved to summer time (UTC-4) on 10 March 2024, so the
+        // month, and the days before that, start at UTC-5
+        let now = at(Tz::America__New_York, "2024-03-20T12:00:00");
+        let db = db_with_meetings(&[
+            // 29 February 23:30 in New York: last month
+            ("2024-03-01 04:30:00", None),
+            // 1 March 00:30 in New York: this month
+            ("2024-03-01 05:30:00", None),
+            // 28 February 23:30 and 29 February 09:00, making three days in
+            // a row with the two above
+            ("2024-02-29 04:30:00", None),
+            ("2024-02-29 14:00:00", None),
+        ])
+        .await;
+
+        let stats = usage_stats(&db, USER_ID, now).await.unwrap();
+
+        assert_eq!(stats.counts.this_month, 1);
+        assert_eq!(stats.longest_streak, 3);
+    }
+
+    #[tokio::test]
+    async fn test_no_meetings_means_empty_stats() {
+        let db = db_with_meetings(&[]).await;
+
+        let stats = usage_stats(&db, USER_ID, at(Tz::UTC, "2024-03-11T12:00:00"))
+            .await
+            .unwrap();
+
+        assert_eq!(stats, UsageStats::default());
+    }
+
+    #[tokio::test]
+    async fn test_team_activity_counts_the_period_only() {
+        let db = db_with_users_meetings(&[
+            (1, "2024-03-04 09:00:00", None),
+            (2, "2024-03-05 09:00:00", None),
+            (1, "2024-03-05 15:00:00", None),
+            (1, "2024-03-07 10:00:00", None),
+            (2, "2024-03-07 11:00:00", None),
+            // Another workspace
+            (3, "2024-03-05 12:00:00", None),
+            // Outside the week
+            (1, "2024-03-03 23:59:59", None),
+            (2, "2024-03-11 00:00:00", None),
+        ])
+        .await;
+        let from = "2024-03-04T00:00:00".parse().unwrap();
+        let until = "2024-03-11T00:00:00".parse().unwrap();
+
+        let activity = team_activity(&db, "T012AB3C4", from, until).await.unwrap();
+
+        assert_eq!(
+            activity,
+            TeamActivity {
+                meetings: 5,
+                top_creators: vec![("U012AB3CD".to_string(), 3), ("U098ZY7XW".to_string(), 2)],
+                // Tied with the 7th
+                busiest_day: Some((day("2024-03-05"), 2)),
+            }
+        );
+        assert_eq!(
+            team_activity(&db, "T0NOBODY", from, until).await.unwrap(),
+            TeamActivity::default()
+        );
+    }
+
+    #[test]
+    fn test_top_words() {
+        let titles: Vec<String> = [
+            "Standup",
+            "Team standup with design",
+            "STANDUP standup",
+            "Design review",
+            "Sprint planning — team",
+            "1:1 with Alex",
+        ]
+        .iter()
+        .map(|title| title.to_string())
+        .collect();
+
+        assert_eq!(
+            top_words(&titles, 3),
+            vec![
+                ("standup".to_string(), 3),
+                ("design".to_string(), 2),
+                ("team".to_string(), 2),
+            ]
+        );
+        assert!(top_words(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_longest_streak() {
+        assert_eq!(longest_streak(&[]), 0);
+        assert_eq!(longest_streak(&[day("2024-03-01")]), 1);
+        assert_eq!(
+            longest_streak(&[
+                day("2024-02-28"),
+                day("2024-02-29"),
+                day("2024-03-01"),
+                day("2024-03-05"),
+                day("2024-03-06"),
+            ]),
+            3
+        );
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::anyhow;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::task::JoinHandle;
+use tracing::{info, Subscriber};
+use tracing_subscriber::{
+    filter::ParseError, fmt::MakeWriter, registry::LookupSpan, reload, EnvFilter, Layer, Registry,
+};
+
+/// Output format of the log layer, chosen with `LOG_FORMAT`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines, for a terminal.
+    #[default]
+    Pretty,
+    /// One JSON object per event with the event's fields flattened into
+    /// the top level and its enclosing spans listed under `spans`, for log
+    /// pipelines.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "pretty" | "text" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(anyhow!(
+                "LOG_FORMAT must be json or pretty, got {:?}",
+                value
+            )),
+        }
+    }
+}
+
+/// The fmt layer for `format`, writing to stdout.
+pub fn fmt_layer<S>(format: LogFormat) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fmt_layer_with_writer(format, std::io::stdout)
+}
+
+fn fmt_layer_with_writer<S, W>(format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer().with_writer(writer);
+    match format {
+        LogFormat::Pretty => layer.boxed(),
+        LogFormat::Json => layer
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(true)
+            .boxed(),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LogFilterError {
+    #[error("invalid filter: {0}")]
+    Invalid(#[from] ParseError),
+
+    #[error("failed to swap the log filter: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+/// Handle for changing the log filter of the running process, to get
+/// `debug` logs from one module without a restart.
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    default: Arc<str>,
+    revert: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl LogFilter {
+    /// A filter layer starting from `default` directives, to be installed
+    /// directly on the registry, and the handle controlling it.
+    pub fn new(
+        default: &str,
+    ) -> Result<(reload::Layer<EnvFilter, Registry>, Self), LogFilterError> {
+        let (layer, handle) = reload::Layer::new(EnvFilter::try_new(default)?);
+        let filter = Self {
+            handle,
+            default: default.into(),
+            revert: Arc::default(),
+        };
+        Ok((layer, filter))
+    }
+
+    /// Directives currently in effect.
+    pub fn current(&self) -> String {
+        self.handle
+            .with_current(ToString::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Switches to `directives`. With `revert_after`, the configured
+    /// filter comes back by itself once that much time has passed, so trace
+    /// logging can't be left on by accident. A later change replaces any
+    /// pending revert.
+    pub fn set(
+        &self,
+        directives: &str,
+        revert_after: Option<Duration>,
+    ) -> Result<(), LogFilterError> {
+        let filter = EnvFilter::try_new(directives)?;
+        let mut revert = self.revert.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(pending) = revert.take() {
+            pending.abort();
+        }
+        self.handle.reload(filter)?;
+        info!("Log filter changed to {:?}", directives);
+
+        if let Some(delay) = revert_after {
+            let this = self.clone();
+            *revert = Some(tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                match EnvFilter::try_new(&*this.default).map(|filter| this.handle.reload(filter)) {
+                    Ok(Ok(())) => info!("Log filter reverted to {:?}", &*this.default),
+                    _ => tracing::error!("Failed to revert the log filter"),
+                }
+            }));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    /// Collects everything the fmt layer writes.
+    #[derive(Clone, Default)]
+    pub(crate) struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl CapturedLogs {
+        pub(crate) fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for CapturedLogs {
+        type Writer = CapturedLogs;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    /// A subscriber logging everything in `format` into the returned buffer.
+    pub(crate) fn capture(format: LogFormat) -> (impl Subscriber + Send + Sync, CapturedLogs) {
+        let logs = CapturedLogs::default();
+        let subscriber =
+            tracing_subscriber::registry().with(fmt_layer_with_writer(format, logs.clone()));
+        (subscriber, logs)
+    }
+
+    #[test]
+    fn test_parse_log_format() {
+        assert_eq!("json".parse::<LogFormat>().unwrap(), LogFormat::Json);
+        assert_eq!(" Pretty ".parse::<LogFormat>().unwrap(), LogFormat::Pretty);
+        assert!("xml".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn test_json_events_have_flattened_fields() {
+        let (subscriber, logs) = capture(LogFormat::Json);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(user_id = "U012AB3CD", "Processing OAuth callback");
+        });
+
+        let line: serde_json::Value =
+            serde_json::from_str(logs.contents().lines().next().unwrap()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "Processing OAuth callback");
+        assert_eq!(line["user_id"], "U012AB3CD");
+    }
+
+    #[tokio::test]
+    async fn test_filter_changes_at_runtime_and_reverts() {
+        let logs = CapturedLogs::default();
+        let (filter_layer, filter) = LogFilter::new("info").unwrap();
+        let subscriber = tracing_subscriber::registry()
+            .with(filter_layer)
+            .with(fmt_layer_with_writer(LogFormat::Pretty, logs.clone()));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        tracing::debug!(target: "meet_slack_bot::google", "before the change");
+        filter
+            .set(
+                "meet_slack_bot::google=debug,info",
+                Some(Duration::from_millis(50)),
+            )
+            .unwrap();
+        assert_eq!(filter.current(), "meet_slack_bot::google=debug,info");
+        tracing::debug!(target: "meet_slack_bot::google", "while debugging");
+        tracing::debug!(target: "meet_slack_bot::database", "other module");
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(filter.current(), "info");
+        tracing::debug!(target: "meet_slack_bot::google", "after the revert");
+
+        let output = logs.contents();
+        assert!(output.contains("while debugging"), "{}", output);
+        assert!(!output.contains("before the change"), "{}", output);
+        assert!(!output.contains("other module"), "{}", output);
+        assert!(!output.contains("after the revert"), "{}", output);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_filter_is_rejected() {
+        let (_layer, filter) = LogFilter::new("info").unwrap();
+
+        assert!(matches!(
+            filter.set("meet_slack_bot=loud", None),
+            Err(LogFilterError::Invalid(_))
+        ));
+        assert_eq!(filter.current(), "info");
+    }
+}
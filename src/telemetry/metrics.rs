@@ -0,0 +1,260 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::build_info;
+use crate::database::Database;
+use crate::secret::SecretString;
+
+/// Latency buckets, in seconds, for every `*_seconds` histogram.
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Installs the global Prometheus recorder. Safe to call more than once;
+/// every call returns a handle to the same recorder.
+pub fn install() -> PrometheusHandle {
+    HANDLE
+        .get_or_init(|| {
+            let handle = PrometheusBuilder::new()
+                .set_buckets_for_metric(Matcher::Suffix("_seconds".to_string()), LATENCY_BUCKETS)
+                .expect("latency buckets are not empty")
+                .install_recorder()
+                .expect("no other metrics recorder is installed");
+            gauge!(
+                "build_info",
+                "version" => build_info::VERSION,
+                "git_sha" => build_info::GIT_SHA
+            )
+            .set(1.0);
+            handle
+        })
+        .clone()
+}
+
+#[derive(Clone)]
+struct MetricsState {
+    handle: PrometheusHandle,
+    db: Database,
+    bearer_token: Option<SecretString>,
+}
+
+/// Router serving `/metrics`. With `bearer_token` set, scrapes must send
+/// `Authorization: Bearer <token>`.
+pub fn router(
+    handle: PrometheusHandle,
+    db: Database,
+    bearer_token: Option<SecretString>,
+) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(MetricsState {
+            handle,
+            db,
+            bearer_token,
+        })
+}
+
+async fn metrics_handler(State(state): State<MetricsState>, headers: HeaderMap) -> Response {
+    if let Some(expected) = &state.bearer_token {
+        if !expected.matches_bearer(&headers) {
+            warn!("Rejected /metrics scrape without a valid bearer token");
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+    }
+
+    let (size, idle) = state.db.pool_stats();
+    gauge!("db_pool_connections").set(size as f64);
+    gauge!("db_pool_idle_connections").set(idle as f64);
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.handle.render(),
+    )
+        .into_response()
+}
+
+/// A `/meet` subcommand finished; `outcome` is one of `ok`, `failed`,
+/// `auth_required`, `rejected` or `error`.
+pub fn record_slash_command(command: &'static str, outcome: &'static str) {
+    counter!("slash_commands_total", "command" => command, "outcome" => outcome).increment(1);
+}
+
+pub fn record_meeting_created(link_kind: &'static str) {
+    counter!("meetings_created_total", "link_kind" => link_kind).increment(1);
+}
+
+/// A step of the Google OAuth flow: `started`, `denied`, `completed` or
+/// `failed`.
+pub fn record_oauth_flow(stage: &'static str) {
+    counter!("oauth_flows_total", "stage" => stage).increment(1);
+}
+
+/// A request was turned away by the rate limiter; `scope` is `user`,
+/// `endpoint`, `in_flight` for a user already creating a meeting, or
+/// `queue_full` when the meeting queue had no room.
+pub fn record_rate_limit_block(scope: &'static str) {
+    counter!("rate_limit_blocks_total", "scope" => scope).increment(1);
+}
+
+/// Meeting creations waiting for a worker.
+pub fn set_meeting_queue_depth(depth: usize) {
+    gauge!("meeting_queue_depth").set(depth as f64);
+}
+
+/// A queued meeting creation finished, `elapsed` after it was queued;
+/// `outcome` is `ok`, `failed` or `timed_out`.
+pub fn record_meeting_job(outcome: &'static str, elapsed: Duration) {
+    histogram!("meeting_job_duration_seconds", "outcome" => outcome).record(elapsed.as_secs_f64());
+}
+
+/// A meeting creation failed in a way worth one more try.
+pub fn record_meeting_job_retry() {
+    counter!("meeting_job_retries_total").increment(1);
+}
+
+pub fn record_google_api_call(operation: &'static str, outcome: &'static str, elapsed: Duration) {
+    histogram!(
+        "google_api_request_duration_seconds",
+        "operation" => operation,
+        "outcome" => outcome
+    )
+    .record(elapsed.as_secs_f64());
+}
+
+pub fn record_slack_api_call(method: &'static str, outcome: &'static str, elapsed: Duration) {
+    histogram!(
+        "slack_api_request_duration_seconds",
+        "method" => method,
+        "outcome" => outcome
+    )
+    .record(elapsed.as_secs_f64());
+}
+
+/// An outbound request to `service` finished; `route` is the template of
+/// its path, and `status` the response's status code, `timeout` or `error`.
+pub fn record_outbound_request(
+    service: &'static str,
+    route: &'static str,
+    method: &str,
+    status: &str,
+    elapsed: Duration,
+) {
+    histogram!(
+        "outbound_request_duration_seconds",
+        "service" => service,
+        "route" => route,
+        "method" => method.to_string(),
+        "status" => status.to_string()
+    )
+    .record(elapsed.as_secs_f64());
+}
+
+pub fn record_handler_latency(handler: &'static str, elapsed: Duration) {
+    histogram!("handler_duration_seconds", "handler" => handler).record(elapsed.as_secs_f64());
+}
+
+/// A read served from (`hit`) or past (`miss`) one of the in-memory
+/// caches in front of the database and Slack's `users.info`.
+pub fn record_cache_lookup(cache: &'static str, hit: bool) {
+    let outcome = if hit { "hit" } else { "miss" };
+    counter!("cache_lookups_total", "cache" => cache, "outcome" => outcome).increment(1);
+}
+
+/// A reply went out without everything it normally involves, such as a
+/// meeting link delivered although storing the meeting failed.
+pub fn record_degraded_response(reason: &'static str) {
+    counter!("degraded_responses_total", "reason" => reason).increment(1);
+}
+
+/// A handler panicked and the panic was turned into an error reply.
+pub fn record_handler_panic() {
+    counter!("handler_panics_total").increment(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::test_db;
+    use crate::rate_limiter::RateLimiter;
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    async fn scrape(app: Router, authorization: Option<&str>) -> (StatusCode, String) {
+        let mut request = Request::builder().uri("/metrics");
+        if let Some(value) = authorization {
+            request = request.header(header::AUTHORIZATION, value);
+        }
+
+        let response = app
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_scrape_after_traffic() {
+        let handle = install();
+        let app = router(handle, test_db().await.0, None);
+
+        record_slash_command("create", "ok");
+        record_meeting_created("meet");
+        record_oauth_flow("started");
+        record_google_api_call("create_space", "ok", Duration::from_millis(120));
+        record_handler_latency("slash_command", Duration::from_millis(30));
+
+        let limiter = RateLimiter::new();
+        for _ in 0..20 {
+            let _ = limiter
+                .check_user_limit("U0METRICS01", "/slack/commands")
+                .await;
+        }
+
+        let (status, body) = scrape(app, None).await;
+        assert_eq!(status, StatusCode::OK);
+        for expected in [
+            r#"slash_commands_total{command="create",outcome="ok"}"#,
+            r#"meetings_created_total{link_kind="meet"}"#,
+            r#"oauth_flows_total{stage="started"}"#,
+            r#"rate_limit_blocks_total{scope="user"}"#,
+            "google_api_request_duration_seconds_bucket",
+            r#"handler_duration_seconds_count{handler="slash_command"}"#,
+            "db_pool_connections",
+            "db_pool_idle_connections",
+            &format!(r#"build_info{{version="{}""#, build_info::VERSION),
+        ] {
+            assert!(body.contains(expected), "missing {} in\n{}", expected, body);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scrape_requires_the_configured_token() {
+        let app = router(install(), test_db().await.0, Some("s3cret".into()));
+
+        let (status, _) = scrape(app.clone(), None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = scrape(app.clone(), Some("Bearer wrong")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _) = scrape(app, Some("Bearer s3cret")).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
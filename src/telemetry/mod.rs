@@ -0,0 +1,75 @@
+//! Operational visibility: logging, error reporting, Prometheus metrics and
+//! OpenTelemetry tracing.
+
+pub mod error_reporting;
+pub mod logging;
+pub mod metrics;
+pub mod otel;
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::TracerProvider;
+use sentry::ClientInitGuard;
+use tracing::{error, info};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::build_info;
+use crate::config::Config;
+use logging::LogFilter;
+
+/// Logging, tracing and error reporting as set up for the process; kept
+/// until it exits.
+pub struct Telemetry {
+    /// Changes the log filter at runtime.
+    pub log_filter: LogFilter,
+    tracer_provider: Option<TracerProvider>,
+    /// Flushes pending error reports when dropped.
+    _sentry: Option<ClientInitGuard>,
+}
+
+impl Telemetry {
+    /// Installs the global subscriber: the log filter and format of
+    /// `config`, plus traces and error reports when they are configured.
+    /// Logs which build is running.
+    pub fn init(config: &Config) -> anyhow::Result<Self> {
+        let tracer_provider = otel::init_tracer_provider(&config.telemetry)?;
+        let sentry = error_reporting::init(&config.telemetry);
+
+        let (filter_layer, log_filter) = LogFilter::new(&config.logging.filter)?;
+        tracing_subscriber::registry()
+            .with(filter_layer)
+            .with(logging::fmt_layer(config.logging.format))
+            .with(tracer_provider.as_ref().map(|provider| {
+                tracing_opentelemetry::layer().with_tracer(provider.tracer("meet-slack-bot"))
+            }))
+            .with(sentry.as_ref().map(|_| error_reporting::layer()))
+            .init();
+
+        info!(
+            "meet-slack-bot {} (commit {}, built {})",
+            build_info::VERSION,
+            build_info::GIT_SHA,
+            build_info::build_time()
+        );
+        if tracer_provider.is_some() {
+            info!("Exporting traces over OTLP");
+        }
+        if sentry.is_some() {
+            info!("Reporting errors to Sentry");
+        }
+
+        Ok(Self {
+            log_filter,
+            tracer_provider,
+            _sentry: sentry,
+        })
+    }
+
+    /// Flushes the traces not exported yet.
+    pub fn shutdown(self) {
+        if let Some(provider) = self.tracer_provider {
+            if let Err(e) = provider.shutdown() {
+                error!("Failed to flush traces: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,228 @@
+use axum::{body::Body, extract::MatchedPath, http::Request};
+use opentelemetry::{global, propagation::Extractor, propagation::Injector, KeyValue};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator, runtime, trace::TracerProvider, Resource,
+};
+use tower_http::{
+    classify::{ServerErrorsAsFailures, SharedClassifier},
+    trace::TraceLayer,
+};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::config::TelemetryConfig;
+use crate::request_id::RequestId;
+
+/// Builds an OTLP/HTTP trace pipeline when an OTLP endpoint is configured,
+/// and installs the W3C trace-context propagator so outgoing requests carry
+/// `traceparent`. Returns `None`, changing nothing, otherwise.
+///
+/// Like the standard `OTEL_EXPORTER_OTLP_ENDPOINT`, the endpoint is the
+/// collector's base URL; spans go to `/v1/traces` under it.
+pub fn init_tracer_provider(config: &TelemetryConfig) -> anyhow::Result<Option<TracerProvider>> {
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()?;
+    let service_name = config.service_name.clone();
+
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new_with_defaults([KeyValue::new(
+            "service.name",
+            service_name,
+        )]))
+        .build();
+
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    Ok(Some(provider))
+}
+
+type MakeRequestSpan = fn(&Request<Body>) -> Span;
+
+/// Span layer for the HTTP server: one root span per request, named after
+/// the matched route rather than the raw path, continuing any trace the
+/// caller sent in `traceparent`.
+pub fn http_trace_layer() -> TraceLayer<SharedClassifier<ServerErrorsAsFailures>, MakeRequestSpan> {
+    TraceLayer::new_for_http().make_span_with(make_request_span as MakeRequestSpan)
+}
+
+fn make_request_span(request: &Request<Body>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(MatchedPath::as_str)
+        .unwrap_or("unmatched");
+    let request_id = request
+        .extensions()
+        .get::<RequestId>()
+        .map(RequestId::as_str)
+        .unwrap_or_default();
+
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri().path(),
+        version = ?request.version(),
+        route,
+        request_id,
+        otel.name = %format!("{} {}", request.method(), route),
+        otel.kind = "server",
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&RequestHeaders(request.headers()))
+    });
+    span.set_parent(parent);
+    span
+}
+
+/// Span for an outgoing HTTP call. Record the status with
+/// [`record_status`] once the response arrives.
+macro_rules! client_span {
+    ($name:expr, $method:expr, $url:expr) => {
+        tracing::info_span!(
+            $name,
+            otel.kind = "client",
+            http.request.method = $method,
+            url.full = $url,
+            http.response.status_code = tracing::field::Empty,
+            otel.status_code = tracing::field::Empty,
+        )
+    };
+}
+pub(crate) use client_span;
+
+/// Records the response status of a span made with [`client_span!`].
+pub fn record_status(span: &Span, status: reqwest::StatusCode) {
+    span.record("http.response.status_code", status.as_u16());
+    if status.is_server_error() || status.is_client_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+/// Headers carrying `span`'s trace context (`traceparent`, `tracestate`),
+/// to attach to an outgoing request. Empty unless tracing export is on.
+pub fn trace_headers(span: &Span) -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let context = span.context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut OutgoingHeaders(&mut headers))
+    });
+    headers
+}
+
+struct OutgoingHeaders<'a>(&'a mut reqwest::header::HeaderMap);
+
+impl Injector for OutgoingHeaders<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+            reqwest::header::HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+struct RequestHeaders<'a>(&'a axum::http::HeaderMap);
+
+impl Extractor for RequestHeaders<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{routing::get, Router};
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+    use tower::ServiceExt;
+    use tracing::Instrument;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn test_disabled_without_endpoint() {
+        assert!(init_tracer_provider(&TelemetryConfig::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_request_and_client_spans_are_exported() {
+        let exporter = InMemorySpanExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let _guard = tracing::subscriber::set_default(subscriber);
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        async fn handler() -> String {
+            let span = client_span!("google.create_space", "POST", "https://example.com");
+            let headers = trace_headers(&span);
+            async { record_status(&span, reqwest::StatusCode::OK) }
+                .instrument(span.clone())
+                .await;
+            headers
+                .get("traceparent")
+                .map(|value| value.to_str().unwrap().to_string())
+                .unwrap_or_default()
+        }
+
+        let app = Router::new()
+            .route("/meetings/:id", get(handler))
+            .layer(http_trace_layer());
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/meetings/42")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let traceparent = String::from_utf8(body.to_vec()).unwrap();
+
+        provider.force_flush();
+        let spans = exporter.get_finished_spans().unwrap();
+
+        let server = spans
+            .iter()
+            .find(|span| span.name == "GET /meetings/:id")
+            .expect("server span");
+        assert!(server
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "route" && kv.value.as_str() == "/meetings/:id"));
+
+        let client = spans
+            .iter()
+            .find(|span| span.name == "google.create_space")
+            .expect("client span");
+        assert_eq!(client.parent_span_id, server.span_context.span_id());
+        assert!(client
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "http.response.status_code"));
+
+        // The propagated header names the client span's trace
+        assert!(traceparent.starts_with("00-"));
+        assert!(traceparent.contains(&client.span_context.trace_id().to_string()));
+    }
+}
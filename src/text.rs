@@ -0,0 +1,117 @@
+//! Measuring and shortening user-supplied text, such as meeting titles, by
+//! what people see as characters: grapheme clusters, so a flag, a family
+//! emoji or a letter with an accent counts once and is never cut in half.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What shortened text ends in.
+const ELLIPSIS: char = '…';
+
+/// How long `text` looks: its grapheme clusters.
+pub fn grapheme_len(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// `text` shortened to at most `max` graphemes, ending in `…` when it was
+/// cut.
+pub fn truncate_graphemes(text: &str, max: usize) -> String {
+    truncate_by(text, max, |_| 1)
+}
+
+/// `text` shortened to at most `max` chars, as Slack counts its limits,
+/// cutting only between graphemes and ending in `…` when it was cut.
+pub fn truncate_chars(text: &str, max: usize) -> String {
+    truncate_by(text, max, |grapheme| grapheme.chars().count())
+}
+
+/// Keeps whole graphemes of `text` while their `len` with the ellipsis
+/// fits in `max`. Spaces before the ellipsis go.
+fn truncate_by(text: &str, max: usize, len: impl Fn(&str) -> usize) -> String {
+    let total: usize = text.graphemes(true).map(&len).sum();
+    if total <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+
+    let budget = max - 1;
+    let mut kept = 0;
+    let mut end = 0;
+    for (start, grapheme) in text.grapheme_indices(true) {
+        kept += len(grapheme);
+        if kept > budget {
+            break;
+        }
+        end = start + grapheme.len();
+    }
+
+    let mut truncated = text[..end].trim_end().to_string();
+    truncated.push(ELLIPSIS);
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLAG: &str = "🇵🇱";
+    const FAMILY: &str = "👨‍👩‍👧‍👦";
+    const E_ACUTE: &str = "e\u{0301}";
+
+    #[test]
+    fn test_graphemes_count_once() {
+        assert_eq!(grapheme_len(""), 0);
+        assert_eq!(grapheme_len("Standup"), 7);
+        assert_eq!(grapheme_len(FLAG), 1);
+        assert_eq!(FLAG.chars().count(), 2);
+        assert_eq!(grapheme_len(FAMILY), 1);
+        assert_eq!(FAMILY.chars().count(), 7);
+        assert_eq!(grapheme_len(E_ACUTE), 1);
+        assert_eq!(grapheme_len(&format!("Retro {}{}", FLAG, FAMILY)), 8);
+    }
+
+    #[test]
+    fn test_truncates_to_whole_graphemes() {
+        assert_eq!(truncate_graphemes("Standup", 7), "Standup");
+        assert_eq!(truncate_graphemes("Standup", 6), "Stand…");
+        assert_eq!(truncate_graphemes("Standup", 1), "…");
+        assert_eq!(truncate_graphemes("Standup", 0), "");
+
+        let flags = FLAG.repeat(5);
+        assert_eq!(truncate_graphemes(&flags, 5), flags);
+        assert_eq!(
+            truncate_graphemes(&flags, 4),
+            format!("{}…", FLAG.repeat(3))
+        );
+
+        let families = FAMILY.repeat(3);
+        assert_eq!(truncate_graphemes(&families, 2), format!("{}…", FAMILY));
+
+        let accents = format!("caf{}caf{}", E_ACUTE, E_ACUTE);
+        assert_eq!(truncate_graphemes(&accents, 5), format!("caf{}…", E_ACUTE));
+    }
+
+    #[test]
+    fn test_truncates_to_a_char_limit_between_graphemes() {
+        // A family is seven chars: it fits whole or not at all
+        let title = format!("ab{}cd", FAMILY);
+        assert_eq!(truncate_chars(&title, 11), title);
+        assert_eq!(truncate_chars(&title, 10), format!("ab{}…", FAMILY));
+        assert_eq!(truncate_chars(&title, 9), "ab…");
+
+        // Half a flag is a different flag, or none
+        assert_eq!(truncate_chars(&FLAG.repeat(3), 4), format!("{}…", FLAG));
+
+        // The combining accent stays with its letter
+        let accents = format!("{}{}", E_ACUTE, E_ACUTE);
+        assert_eq!(truncate_chars(&accents, 3), format!("{}…", E_ACUTE));
+        assert_eq!(truncate_chars(&accents, 2), "…");
+    }
+
+    #[test]
+    fn test_no_space_before_the_ellipsis() {
+        assert_eq!(truncate_graphemes("Team sync", 6), "Team…");
+        assert_eq!(truncate_chars("Team sync", 6), "Team…");
+    }
+}
@@ -0,0 +1,83 @@
+//! The current time, behind [`Clock`] so that token expiry, request age and
+//! retention can be tested at their exact boundaries instead of by sleeping.
+//! The application reads [`SystemClock`] through `AppState::clock`; tests
+//! swap in a [`TestClock`] and move it by hand.
+
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, Mutex};
+
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Seconds since the Unix epoch, as Slack's request timestamps count.
+    fn unix_seconds(&self) -> u64 {
+        self.now().timestamp().max(0) as u64
+    }
+}
+
+/// A clock shared by everything reading the time.
+pub type SharedClock = Arc<dyn Clock>;
+
+/// The operating system's clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl SystemClock {
+    pub fn shared() -> SharedClock {
+        Arc::new(SystemClock)
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock standing still until it is set or advanced.
+#[derive(Debug)]
+pub struct TestClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl TestClock {
+    pub fn new(now: DateTime<Utc>) -> Arc<Self> {
+        Arc::new(Self {
+            now: Mutex::new(now),
+        })
+    }
+
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    pub fn advance(&self, by: chrono::Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+impl Clock for TestClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_clock_moves_only_when_told() {
+        let start = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let clock = TestClock::new(start);
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.unix_seconds(), 1_709_294_400);
+
+        clock.advance(chrono::Duration::seconds(90));
+        assert_eq!(clock.now(), start + chrono::Duration::seconds(90));
+
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+}
@@ -0,0 +1,240 @@
+//! The Slack timezones of the people a meeting mentions, and a scheduled
+//! meeting's start told in each of them ("15:00 CET · 9:00 EST · 19:30
+//! IST"), so distributed teams don't have to ask whose 3pm it is. Times
+//! users type are read where they are, as [`of_user`] tells.
+
+use chrono::{DateTime, NaiveDate, Offset, Utc};
+use chrono_tz::Tz;
+use tracing::{info, warn};
+
+use crate::observability::{self, Phase};
+use crate::quota;
+use crate::AppState;
+
+/// Most timezones an announcement shows the start in.
+pub const MAX_SHOWN: usize = 4;
+
+/// The timezones the Slack users `user_ids` set, by user, looked up with
+/// the bot token of `team_id`. Users without one, and every user when the
+/// workspace has no bot token or a lookup fails, are left out.
+pub async fn of_users(state: &AppState, team_id: &str, user_ids: &[&str]) -> Vec<(String, Tz)> {
+    if user_ids.is_empty() {
+        return Vec::new();
+    }
+    let team = match observability::timed(Phase::Database, state.db.get_slack_team(team_id)).await {
+        Ok(Some(team)) => team,
+        Ok(None) => {
+            info!(
+                "No bot token for team {}, not looking up timezones",
+                team_id
+            );
+            return Vec::new();
+        }
+        Err(e) => {
+            warn!("Failed to look up team {}: {:#}", team_id, e);
+            return Vec::new();
+        }
+    };
+
+    let mut timezones = Vec::with_capacity(user_ids.len());
+    for user_id in user_ids {
+        match state.slack.user_timezone(&team.bot_token, user_id).await {
+            Ok(Some(name)) => match name.parse::<Tz>() {
+                Ok(tz) => timezones.push((user_id.to_string(), tz)),
+                Err(_) => warn!("Unknown timezone {} of {}", name, user_id),
+            },
+            Ok(None) => {}
+            Err(e) => warn!("Failed to look up the timezone of {}: {}", user_id, e),
+        }
+    }
+    timezones
+}
+
+/// Where the Slack user `user_id` of `team_id` is: the timezone set in
+/// their Slack profile, or else the workspace's.
+pub async fn of_user(state: &AppState, team_id: &str, user_id: &str) -> anyhow::Result<Tz> {
+    match of_users(state, team_id, &[user_id]).await.pop() {
+        Some((_, tz)) => Ok(tz),
+        None => quota::team_timezone(&state.db, team_id).await,
+    }
+}
+
+/// The date `at` falls on where the Slack user `user_id` of `team_id` is,
+/// as [`of_user`] tells; the UTC date when that can't be looked up.
+pub async fn local_date(
+    state: &AppState,
+    team_id: &str,
+    user_id: &str,
+    at: DateTime<Utc>,
+) -> NaiveDate {
+    match of_user(state, team_id, user_id).await {
+        Ok(tz) => at.with_timezone(&tz).date_naive(),
+        Err(e) => {
+            warn!("Failed to look up where {} is: {:#}", user_id, e);
+            at.date_naive()
+        }
+    }
+}
+
+/// `starts_at` in each of `timezones`, in their order and without
+/// repeating a time already shown, for up to [`MAX_SHOWN`] of them. `None`
+/// when that leaves fewer than two, as Slack already shows every reader
+/// the start in their own timezone.
+pub fn start_times(starts_at: DateTime<Utc>, timezones: &[Tz]) -> Option<String> {
+    let mut shown: Vec<(String, String, i32)> = Vec::new();
+    for tz in timezones {
+        let local = starts_at.with_timezone(tz);
+        let time = local.format("%-H:%M").to_string();
+        let abbreviation = local.format("%Z").to_string();
+        let offset = local.offset().fix().local_minus_utc();
+        if shown
+            .iter()
+            .any(|(_, name, seconds)| *name == abbreviation && *seconds == offset)
+        {
+            continue;
+        }
+        if shown.len() == MAX_SHOWN {
+            break;
+        }
+        shown.push((time, abbreviation, offset));
+    }
+    if shown.len() < 2 {
+        return None;
+    }
+
+    let labels: Vec<String> = shown
+        .iter()
+        .map(|(time, abbreviation, offset)| {
+            // The same letters meaning different offsets, as IST for India
+            // and Israel, would say nothing
+            let ambiguous = shown
+                .iter()
+                .any(|(_, other, seconds)| other == abbreviation && seconds != offset);
+            let name = if is_abbreviation(abbreviation) && !ambiguous {
+                abbreviation.clone()
+            } else {
+                utc_offset(*offset)
+            };
+            format!("{} {}", time, name)
+        })
+        .collect();
+    Some(labels.join(" · "))
+}
+
+/// Whether the timezone database names the offset with letters, as `CET`;
+/// many zones only have a number such as `+0545`.
+fn is_abbreviation(abbreviation: &str) -> bool {
+    !abbreviation.is_empty() && abbreviation.chars().all(|c| c.is_ascii_alphabetic())
+}
+
+/// `seconds` east of UTC, as `UTC+5:45` or `UTC-3`.
+fn utc_offset(seconds: i32) -> String {
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let minutes = seconds.abs() / 60;
+    match minutes % 60 {
+        0 => format!("UTC{}{}", sign, minutes / 60),
+        rest => format!("UTC{}{}:{:02}", sign, minutes / 60, rest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zones(names: &[&str]) -> Vec<Tz> {
+        names.iter().map(|name| name.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_start_times() {
+        let cases: &[(&str, &[&str], Option<&str>)] = &[
+            (
+                "2024-01-16T14:00:00Z",
+                &["Europe/Warsaw", "America/New_York", "Asia/Kolkata"],
+                Some("15:00 CET · 9:00 EST · 19:30 IST"),
+            ),
+            // Half- and quarter-hour offsets, one without letters
+            (
+                "2024-01-16T14:00:00Z",
+                &["Australia/Adelaide", "Asia/Kathmandu"],
+                Some("0:30 ACDT · 19:45 UTC+5:45"),
+            ),
+            // The US moved its clocks on 10 March, Europe not until the 31st
+            (
+                "2024-03-11T14:00:00Z",
+                &["Europe/Warsaw", "America/New_York"],
+                Some("15:00 CET · 10:00 EDT"),
+            ),
+            (
+                "2024-03-31T14:00:00Z",
+                &["Europe/Warsaw", "America/New_York"],
+                Some("16:00 CEST · 10:00 EDT"),
+            ),
+            // The night Europe moves its clocks back, before and after
+            (
+                "2024-10-27T00:30:00Z",
+                &["Europe/London", "Europe/Warsaw"],
+                Some("1:30 BST · 2:30 CEST"),
+            ),
+            (
+                "2024-10-27T01:30:00Z",
+                &["Europe/London", "Europe/Warsaw"],
+                Some("1:30 GMT · 2:30 CET"),
+            ),
+            // The same letters for different offsets fall back to numbers
+            (
+                "2024-01-16T14:00:00Z",
+                &["Asia/Kolkata", "Asia/Jerusalem"],
+                Some("19:30 UTC+5:30 · 16:00 UTC+2"),
+            ),
+            (
+                "2024-01-16T14:00:00Z",
+                &["America/Sao_Paulo", "UTC"],
+                Some("11:00 UTC-3 · 14:00 UTC"),
+            ),
+            // Zones agreeing on the time are shown once
+            (
+                "2024-01-16T14:00:00Z",
+                &["Europe/Warsaw", "Europe/Berlin", "America/Chicago"],
+                Some("15:00 CET · 8:00 CST"),
+            ),
+            (
+                "2024-01-16T14:00:00Z",
+                &["Europe/Warsaw", "Europe/Berlin"],
+                None,
+            ),
+            ("2024-01-16T14:00:00Z", &[], None),
+            // At most four
+            (
+                "2024-01-16T14:00:00Z",
+                &[
+                    "Europe/Warsaw",
+                    "America/New_York",
+                    "Asia/Tokyo",
+                    "Europe/London",
+                    "America/Los_Angeles",
+                ],
+                Some("15:00 CET · 9:00 EST · 23:00 JST · 14:00 GMT"),
+            ),
+        ];
+
+        for (starts_at, names, expected) in cases {
+            let starts_at: DateTime<Utc> = starts_at.parse().unwrap();
+            assert_eq!(
+                start_times(starts_at, &zones(names)).as_deref(),
+                *expected,
+                "{} in {:?}",
+                starts_at,
+                names
+            );
+        }
+    }
+
+    #[test]
+    fn test_utc_offsets() {
+        assert_eq!(utc_offset(0), "UTC+0");
+        assert_eq!(utc_offset(-3 * 3600), "UTC-3");
+        assert_eq!(utc_offset(-(3 * 3600 + 1800)), "UTC-3:30");
+        assert_eq!(utc_offset(5 * 3600 + 45 * 60), "UTC+5:45");
+    }
+}
@@ -0,0 +1,470 @@
+//! Workspace title templates, set with `/meet set team title-template`, so
+//! a team's meetings get consistent names such as
+//! "[#general] Standup — 2025-06-03". A template mixes text with the
+//! placeholders `{channel}`, `{user}`, `{date}` and `{text}`; `{{` and
+//! `}}` stand for literal braces.
+//!
+//! A placeholder with nothing to fill in, as `{text}` when the user typed
+//! no title, is dropped along with the separator leading up to it, and an
+//! empty pair of brackets around it goes too, so the title reads as if the
+//! template never had it.
+//!
+//! Without a template, meetings the user gave no title are named after
+//! their channel and day, or generically if the workspace prefers
+//! (`/meet set team default-title`).
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+use tracing::warn;
+
+use crate::database::Database;
+use crate::text;
+use crate::validation::MAX_MEETING_TITLE_LENGTH;
+
+/// The `team_settings` key templates are stored under.
+pub const SETTING_KEY: &str = "title_template";
+
+/// The `team_settings` key of how untitled meetings are named.
+pub const DEFAULT_TITLE_MODE_KEY: &str = "default_title_mode";
+
+/// The `channel_name` Slack sends for private channels the bot may not
+/// name and for direct messages.
+const HIDDEN_CHANNEL_NAMES: &[&str] = &["privategroup", "directmessage"];
+
+/// Characters that only separate parts of a title, dropped with an empty
+/// placeholder.
+const SEPARATORS: &[char] = &['-', '–', '—', '|', ':', '·', '•', ',', '/'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    Channel,
+    User,
+    Date,
+    Text,
+}
+
+impl Placeholder {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "channel" => Some(Placeholder::Channel),
+            "user" => Some(Placeholder::User),
+            "date" => Some(Placeholder::Date),
+            "text" => Some(Placeholder::Text),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("The template is empty")]
+    Empty,
+    #[error("Templates can be at most {max} characters")]
+    TooLong { max: usize },
+    #[error(
+        "`{{{0}}}` isn't a placeholder; use `{{channel}}`, `{{user}}`, `{{date}}` or `{{text}}`"
+    )]
+    UnknownPlaceholder(String),
+    #[error("A `{{` is never closed; write `{{{{` for a literal brace")]
+    Unclosed,
+    #[error("A `}}` closes nothing; write `}}}}` for a literal brace")]
+    UnmatchedClose,
+}
+
+/// A parsed title template, checked to only use known placeholders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TitleTemplate {
+    source: String,
+    pieces: Vec<Piece>,
+}
+
+/// What a template's placeholders are filled in with.
+#[derive(Debug, Clone, Copy)]
+pub struct TitleValues<'a> {
+    /// The channel's name, without `#`.
+    pub channel: &'a str,
+    pub user: &'a str,
+    /// The day the meeting starts, as `2025-06-03`.
+    pub date: NaiveDate,
+    /// The title the user typed, if any.
+    pub text: Option<&'a str>,
+}
+
+impl TitleValues<'_> {
+    fn get(&self, placeholder: Placeholder) -> String {
+        match placeholder {
+            Placeholder::Channel => self.channel.trim().to_string(),
+            Placeholder::User => self.user.trim().to_string(),
+            Placeholder::Date => self.date.format("%Y-%m-%d").to_string(),
+            Placeholder::Text => self.text.unwrap_or_default().trim().to_string(),
+        }
+    }
+}
+
+impl FromStr for TitleTemplate {
+    type Err = TemplateError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let source = source.trim();
+        if source.is_empty() {
+            return Err(TemplateError::Empty);
+        }
+        if text::grapheme_len(source) > MAX_MEETING_TITLE_LENGTH {
+            return Err(TemplateError::TooLong {
+                max: MAX_MEETING_TITLE_LENGTH,
+            });
+        }
+
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some('{') | None => return Err(TemplateError::Unclosed),
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    let placeholder = Placeholder::parse(&name).ok_or_else(|| {
+                        TemplateError::UnknownPlaceholder(name.trim().to_string())
+                    })?;
+                    if !literal.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(Piece::Placeholder(placeholder));
+                }
+                '}' => return Err(TemplateError::UnmatchedClose),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            pieces.push(Piece::Literal(literal));
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            pieces,
+        })
+    }
+}
+
+impl fmt::Display for TitleTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl TitleTemplate {
+    /// The title for `values`; `None` when nothing is left of it, as for
+    /// `{text}` alone without a typed title.
+    pub fn render(&self, values: &TitleValues) -> Option<String> {
+        let mut title = String::new();
+        // Set by an empty placeholder with nothing before it, so the
+        // separator after it goes instead
+        let mut drop_leading = false;
+        // Characters of the next literal already accounted for
+        let mut skip = 0;
+
+        for (i, piece) in self.pieces.iter().enumerate() {
+            let value = match piece {
+                Piece::Literal(text) => {
+                    let mut text: &str = &text[skip..];
+                    skip = 0;
+                    if drop_leading {
+                        text = text.trim_start_matches(is_separator);
+                    }
+                    drop_leading &= text.is_empty();
+                    title.push_str(text);
+                    continue;
+                }
+                Piece::Placeholder(placeholder) => values.get(*placeholder),
+            };
+            if !value.is_empty() {
+                title.push_str(&value);
+                drop_leading = false;
+                continue;
+            }
+
+            // Brackets around nothing go with it
+            let close = match title.chars().last() {
+                Some('(') => Some(')'),
+                Some('[') => Some(']'),
+                _ => None,
+            };
+            if let (Some(close), Some(Piece::Literal(next))) = (close, self.pieces.get(i + 1)) {
+                if next.starts_with(close) {
+                    title.pop();
+                    skip = 1;
+                }
+            }
+            let kept = title.trim_end_matches(is_separator).len();
+            title.truncate(kept);
+            drop_leading = title.is_empty();
+        }
+
+        let title = title.trim();
+        (!title.is_empty()).then(|| title.to_string())
+    }
+}
+
+fn is_separator(c: char) -> bool {
+    c.is_whitespace() || SEPARATORS.contains(&c)
+}
+
+/// How a meeting the user gave no title is named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DefaultTitleMode {
+    /// After the channel and day, as "#general sync — Jun 3".
+    #[default]
+    Channel,
+    /// After the day alone, as "Meeting — Jun 3".
+    Generic,
+}
+
+impl DefaultTitleMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DefaultTitleMode::Channel => "channel",
+            DefaultTitleMode::Generic => "generic",
+        }
+    }
+}
+
+impl fmt::Display for DefaultTitleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for DefaultTitleMode {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "channel" => Ok(DefaultTitleMode::Channel),
+            "generic" => Ok(DefaultTitleMode::Generic),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The title of a meeting created without one in the channel
+/// `channel_name` for `date`. Private channels and direct messages get the
+/// generic title whatever `mode` says, so their names don't end up in
+/// calendars. A channel name too long for a title is shortened, keeping
+/// the date.
+pub fn default_title(mode: DefaultTitleMode, channel_name: &str, date: NaiveDate) -> String {
+    let day = date.format("%b %-d");
+    match (mode, public_channel(channel_name)) {
+        (DefaultTitleMode::Channel, Some(channel)) => {
+            let rest = format!(" sync — {}", day);
+            let room = MAX_MEETING_TITLE_LENGTH - 1 - text::grapheme_len(&rest);
+            format!("#{}{}", text::truncate_graphemes(channel, room), rest)
+        }
+        _ => format!("Meeting — {}", day),
+    }
+}
+
+/// `channel_name` if it is a channel's own name. Slack sends placeholders
+/// for private channels and direct messages, and names group direct
+/// messages after their members.
+fn public_channel(channel_name: &str) -> Option<&str> {
+    let name = channel_name.trim().trim_start_matches('#');
+    if name.is_empty() || HIDDEN_CHANNEL_NAMES.contains(&name) || name.starts_with("mpdm-") {
+        return None;
+    }
+    Some(name)
+}
+
+/// How the workspace `slack_team_id` names untitled meetings; a stored
+/// value that doesn't parse counts as the default.
+pub async fn default_title_mode(db: &Database, slack_team_id: &str) -> Result<DefaultTitleMode> {
+    let mode = db
+        .get_team_setting(slack_team_id, DEFAULT_TITLE_MODE_KEY)
+        .await?
+        .and_then(|mode| mode.parse().ok())
+        .unwrap_or_default();
+    Ok(mode)
+}
+
+/// The template set for the workspace `slack_team_id`, if any. One that no
+/// longer parses, as after a placeholder was renamed, is ignored.
+pub async fn for_team(db: &Database, slack_team_id: &str) -> Result<Option<TitleTemplate>> {
+    let Some(source) = db.get_team_setting(slack_team_id, SETTING_KEY).await? else {
+        return Ok(None);
+    };
+    match source.parse() {
+        Ok(template) => Ok(Some(template)),
+        Err(e) => {
+            warn!(
+                "Ignoring the title template of team {}: {}",
+                slack_team_id, e
+            );
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(template: &str, text: Option<&str>) -> Option<String> {
+        let template: TitleTemplate = template.parse().unwrap();
+        template.render(&TitleValues {
+            channel: "general",
+            user: "alice",
+            date: NaiveDate::from_ymd_opt(2025, 6, 3).unwrap(),
+            text,
+        })
+    }
+
+    #[test]
+    fn test_render() {
+        let cases: &[(&str, Option<&str>, Option<&str>)] = &[
+            (
+                "[#{channel}] {text} — {date}",
+                Some("Standup"),
+                Some("[#general] Standup — 2025-06-03"),
+            ),
+            (
+                "[#{channel}] {text} — {date}",
+                None,
+                Some("[#general] — 2025-06-03"),
+            ),
+            ("{text} — {date}", Some("Retro"), Some("Retro — 2025-06-03")),
+            ("{text} — {date}", None, Some("2025-06-03")),
+            ("{date} — {text}", None, Some("2025-06-03")),
+            ("{date} | {text} | {user}", None, Some("2025-06-03 | alice")),
+            ("Sync ({text})", None, Some("Sync")),
+            ("Sync [{text}] with {user}", None, Some("Sync with alice")),
+            (
+                "Sync [{text}] with {user}",
+                Some("Q3"),
+                Some("Sync [Q3] with alice"),
+            ),
+            ("{text}", None, None),
+            ("{text}", Some("  Standup  "), Some("Standup")),
+            ("{{team}} {text}", Some("Standup"), Some("{team} Standup")),
+            ("{{{text}}}", Some("Standup"), Some("{Standup}")),
+            ("{ USER }'s meeting", None, Some("alice's meeting")),
+            ("Planning", Some("ignored"), Some("Planning")),
+        ];
+
+        for (template, text, expected) in cases {
+            assert_eq!(
+                render(template, *text).as_deref(),
+                *expected,
+                "{:?} with {:?}",
+                template,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_title() {
+        let day = NaiveDate::from_ymd_opt(2025, 6, 3).unwrap();
+        let cases = [
+            (
+                "general",
+                DefaultTitleMode::Channel,
+                "#general sync — Jun 3",
+            ),
+            ("general", DefaultTitleMode::Generic, "Meeting — Jun 3"),
+            ("privategroup", DefaultTitleMode::Channel, "Meeting — Jun 3"),
+            (
+                "directmessage",
+                DefaultTitleMode::Channel,
+                "Meeting — Jun 3",
+            ),
+            (
+                "mpdm-alice--bob--carol-1",
+                DefaultTitleMode::Channel,
+                "Meeting — Jun 3",
+            ),
+            ("", DefaultTitleMode::Channel, "Meeting — Jun 3"),
+            (
+                "directmessage",
+                DefaultTitleMode::Generic,
+                "Meeting — Jun 3",
+            ),
+        ];
+
+        for (channel, mode, expected) in cases {
+            assert_eq!(
+                default_title(mode, channel, day),
+                expected,
+                "{} in {:?}",
+                mode,
+                channel
+            );
+        }
+
+        let long = "ąę".repeat(150);
+        let title = default_title(DefaultTitleMode::Channel, &long, day);
+        assert_eq!(text::grapheme_len(&title), MAX_MEETING_TITLE_LENGTH);
+        assert!(title.starts_with("#ąę"), "{}", title);
+        assert!(title.ends_with("… sync — Jun 3"), "{}", title);
+    }
+
+    #[test]
+    fn test_default_title_mode_parses() {
+        assert_eq!("channel".parse(), Ok(DefaultTitleMode::Channel));
+        assert_eq!(" Generic ".parse(), Ok(DefaultTitleMode::Generic));
+        assert_eq!("meet".parse::<DefaultTitleMode>(), Err(()));
+    }
+
+    #[test]
+    fn test_only_known_placeholders_parse() {
+        assert_eq!(
+            "{channel} {title}".parse::<TitleTemplate>(),
+            Err(TemplateError::UnknownPlaceholder("title".to_string()))
+        );
+        assert_eq!(
+            "{channel".parse::<TitleTemplate>(),
+            Err(TemplateError::Unclosed)
+        );
+        assert_eq!(
+            "{chan{nel}".parse::<TitleTemplate>(),
+            Err(TemplateError::Unclosed)
+        );
+        assert_eq!(
+            "channel}".parse::<TitleTemplate>(),
+            Err(TemplateError::UnmatchedClose)
+        );
+        assert_eq!("  ".parse::<TitleTemplate>(), Err(TemplateError::Empty));
+        assert_eq!(
+            "x".repeat(201).parse::<TitleTemplate>(),
+            Err(TemplateError::TooLong { max: 200 })
+        );
+        assert_eq!(
+            "{{channel}} {text}"
+                .parse::<TitleTemplate>()
+                .unwrap()
+                .to_string(),
+            "{{channel}} {text}"
+        );
+    }
+}
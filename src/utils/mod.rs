@@ -1,3 +0,0 @@
-pub mod slack_verification;
-
-pub use slack_verification::{verify_slack_request, SlackVerificationError};
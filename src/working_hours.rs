@@ -0,0 +1,293 @@
+//! Warnings for meetings scheduled outside their attendees' working hours.
+//! `/meet` with a start time looks up the Slack timezone of everyone it
+//! mentions; when the start falls outside the workspace's working hours
+//! (`/meet set team working-hours`, 9–18 by default) for any of them, the
+//! creator is asked to confirm before the meeting is created.
+
+use anyhow::Result;
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::database::Database;
+use crate::handlers::slack::{post_followup, run_detached, SlackResponse, SlashCommandPayload};
+use crate::slack::blocks::{self, Block, Button, ButtonStyle, Text};
+use crate::AppState;
+
+/// The workspace's working hours in `team_settings`, as `start-end`.
+pub const SETTING_KEY: &str = "working_hours";
+
+/// `/meet` flag creating the meeting without checking working hours, which
+/// the confirmation's button adds.
+pub const OUTSIDE_HOURS_FLAG: &str = "outside-hours";
+
+/// Action id of the button creating the meeting anyway; its value is the
+/// `/meet` text to run.
+pub const SCHEDULE_ANYWAY_ACTION: &str = "schedule_anyway";
+
+/// Whole local hours from `start` until `end`, the same for every
+/// attendee in their own timezone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorkingHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl WorkingHours {
+    /// Whether `at` is within the hours where it is.
+    pub fn contain(self, at: &DateTime<Tz>) -> bool {
+        (self.start..self.end).contains(&at.hour())
+    }
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        Self { start: 9, end: 18 }
+    }
+}
+
+/// Reads `9-18`, and `09:00-18:00` as Slack users tend to type it.
+impl FromStr for WorkingHours {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hour = |part: &str| -> Result<u32, ()> {
+            let part = part.trim();
+            let part = part.strip_suffix(":00").unwrap_or(part);
+            part.parse().map_err(|_| ())
+        };
+        let (start, end) = s.split_once(['-', '–']).ok_or(())?;
+        let (start, end) = (hour(start)?, hour(end)?);
+        if start >= end || end > 24 {
+            return Err(());
+        }
+        Ok(Self { start, end })
+    }
+}
+
+impl fmt::Display for WorkingHours {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:00–{}:00", self.start, self.end)
+    }
+}
+
+/// An attendee for whom a meeting starts outside working hours.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutsideHours {
+    pub user_id: String,
+    /// When the meeting starts where they are.
+    pub local_start: DateTime<Tz>,
+}
+
+/// The attendees, Slack user ids with their timezones, for whom a meeting
+/// at `start` is outside `hours`, in the order given.
+pub fn outside_hours(
+    start: DateTime<Utc>,
+    hours: WorkingHours,
+    attendees: &[(String, Tz)],
+) -> Vec<OutsideHours> {
+    attendees
+        .iter()
+        .map(|(user_id, tz)| OutsideHours {
+            user_id: user_id.clone(),
+            local_start: start.with_timezone(tz),
+        })
+        .filter(|attendee| !hours.contain(&attendee.local_start))
+        .collect()
+}
+
+/// The working hours of `slack_team_id`; the default without a setting or
+/// with one that no longer parses.
+pub async fn for_team(db: &Database, slack_team_id: &str) -> Result<WorkingHours> {
+    Ok(db
+        .get_team_setting(slack_team_id, SETTING_KEY)
+        .await?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default())
+}
+
+/// Asks the creator to confirm a meeting at `start` that is outside
+/// `hours` for `outside`. Confirming runs `/meet text` again with
+/// [`OUTSIDE_HOURS_FLAG`].
+pub fn confirmation(
+    command: &str,
+    text: &str,
+    start: DateTime<Utc>,
+    hours: WorkingHours,
+    outside: &[OutsideHours],
+) -> SlackResponse {
+    let who: Vec<String> = outside
+        .iter()
+        .map(|attendee| format!("<@{}>", attendee.user_id))
+        .collect();
+    let lines: Vec<String> = outside
+        .iter()
+        .map(|attendee| {
+            format!(
+                "• <@{}>: {} ({})",
+                attendee.user_id,
+                attendee.local_start.format("%a %H:%M"),
+                attendee.local_start.timezone().name()
+            )
+        })
+        .collect();
+    let anyway = format!("{} --{}", text, OUTSIDE_HOURS_FLAG);
+
+    SlackResponse::builder()
+        .ephemeral()
+        .text(format!(
+            "⚠️ {} is outside working hours for {}. The meeting wasn't created; run `{} {}` to create it anyway.",
+            blocks::date(start),
+            who.join(", "),
+            command,
+            anyway
+        ))
+        .block(Block::section(Text::mrkdwn(format!(
+            "⚠️ *{}* is outside working hours ({}) for:\n{}",
+            blocks::date(start),
+            hours,
+            lines.join("\n")
+        ))))
+        .block(Block::actions(vec![Button::action(
+            SCHEDULE_ANYWAY_ACTION,
+            "Schedule anyway",
+            anyway,
+        )
+        .style(ButtonStyle::Primary)]))
+        .block(Block::context(vec![Text::mrkdwn(format!(
+            "The meeting hasn't been created yet. Run `{}` again with another time, or add `--{}` to skip this check.",
+            command, OUTSIDE_HOURS_FLAG
+        ))]))
+        .build_or_text()
+}
+
+/// Creates the meeting the creator confirmed with the button valued `text`,
+/// in place of the confirmation.
+pub async fn schedule_anyway(state: AppState, mut payload: SlashCommandPayload, text: String) {
+    let mut reply = SlackResponse::ephemeral("⏳ Scheduling it anyway…".to_string());
+    reply.replace_original = true;
+    post_followup(state.http.clone(), payload.response_url.clone(), reply).await;
+
+    payload.text = Some(text);
+    if let Err(e) = run_detached(state, payload) {
+        e.log();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::{America, Asia, Europe, Pacific};
+
+    fn at(utc: &str) -> DateTime<Utc> {
+        utc.parse().unwrap()
+    }
+
+    fn outside(utc: &str, hours: WorkingHours, tz: Tz) -> bool {
+        !outside_hours(at(utc), hours, &[("U0000A001".to_string(), tz)]).is_empty()
+    }
+
+    #[test]
+    fn test_parses_and_shows_working_hours() {
+        assert_eq!("9-18".parse(), Ok(WorkingHours::default()));
+        assert_eq!(
+            "08:00 – 16:00".parse(),
+            Ok(WorkingHours { start: 8, end: 16 })
+        );
+        assert_eq!("0-24".parse(), Ok(WorkingHours { start: 0, end: 24 }));
+        for bad in ["18-9", "9-9", "9-25", "9", "9:30-18", "nine-five", ""] {
+            assert_eq!(bad.parse::<WorkingHours>(), Err(()), "{}", bad);
+        }
+        assert_eq!(WorkingHours::default().to_string(), "9:00–18:00");
+    }
+
+    #[test]
+    fn test_start_and_end_bounds() {
+        let hours = WorkingHours::default();
+        // Warsaw is UTC+1 in winter
+        assert!(outside("2024-03-05T07:59:00Z", hours, Europe::Warsaw));
+        assert!(!outside("2024-03-05T08:00:00Z", hours, Europe::Warsaw));
+        assert!(!outside("2024-03-05T16:59:00Z", hours, Europe::Warsaw));
+        assert!(outside("2024-03-05T17:00:00Z", hours, Europe::Warsaw));
+
+        let always = WorkingHours { start: 0, end: 24 };
+        assert!(!outside("2024-03-05T23:59:00Z", always, Europe::Warsaw));
+    }
+
+    #[test]
+    fn test_follows_daylight_saving_time() {
+        let hours = WorkingHours::default();
+        // New York moves to UTC-4 on 10 March 2024: 13:00 UTC is 8:00 the
+        // Friday before and 9:00 the Monday after
+        assert!(outside("2024-03-08T13:00:00Z", hours, America::New_York));
+        assert!(!outside("2024-03-11T13:00:00Z", hours, America::New_York));
+
+        // London goes back to UTC on 27 October 2024: 17:30 UTC is 18:30
+        // the Friday before and 17:30 the Monday after
+        assert!(outside("2024-10-25T17:30:00Z", hours, Europe::London));
+        assert!(!outside("2024-10-28T17:30:00Z", hours, Europe::London));
+
+        // Auckland's summer time ends on 7 April 2024, from UTC+13 to +12:
+        // 20:30 UTC is 9:30 the Friday before and 8:30 the Monday after
+        assert!(!outside("2024-04-04T20:30:00Z", hours, Pacific::Auckland));
+        assert!(outside("2024-04-07T20:30:00Z", hours, Pacific::Auckland));
+    }
+
+    #[test]
+    fn test_half_hour_offsets_and_other_days() {
+        let hours = WorkingHours::default();
+        // India is UTC+5:30
+        assert!(outside("2024-03-05T03:15:00Z", hours, Asia::Kolkata));
+        assert!(!outside("2024-03-05T03:30:00Z", hours, Asia::Kolkata));
+
+        // 22:00 UTC on Tuesday is already 7:00 on Wednesday in Tokyo
+        let late = outside_hours(
+            at("2024-03-05T22:00:00Z"),
+            hours,
+            &[
+                ("U0000A001".to_string(), Asia::Tokyo),
+                ("U0000B0B1".to_string(), America::Los_Angeles),
+            ],
+        );
+        assert_eq!(late.len(), 1);
+        assert_eq!(late[0].user_id, "U0000A001");
+        assert_eq!(
+            late[0].local_start.format("%a %H:%M").to_string(),
+            "Wed 07:00"
+        );
+    }
+
+    #[test]
+    fn test_confirmation_offers_to_schedule_anyway() {
+        let start = at("2024-03-05T22:00:00Z");
+        let outside = outside_hours(
+            start,
+            WorkingHours::default(),
+            &[("U0000A001".to_string(), Asia::Tokyo)],
+        );
+
+        let reply = confirmation(
+            "/meet",
+            "Sync <@U0000A001> 22:00",
+            start,
+            WorkingHours::default(),
+            &outside,
+        );
+
+        assert_eq!(reply.response_type, "ephemeral");
+        let blocks = reply.blocks.unwrap();
+        assert!(
+            matches!(&blocks[0], Block::Section(section)
+                if section.text.as_ref().unwrap().as_str().ends_with("(9:00–18:00) for:\n• <@U0000A001>: Wed 07:00 (Asia/Tokyo)")),
+            "{:?}",
+            blocks[0]
+        );
+        let button = blocks[1].buttons()[0];
+        assert_eq!(button.action_id, SCHEDULE_ANYWAY_ACTION);
+        assert_eq!(
+            button.value.as_deref(),
+            Some("Sync <@U0000A001> 22:00 --outside-hours")
+        );
+    }
+}
@@ -0,0 +1,21 @@
+//! State shared by the integration tests: the app as `main` builds it,
+//! with an in-memory database and its meeting queue running.
+
+use meet_slack_bot::{
+    config::Config, google::fake::FakeGoogleApi, telemetry::logging::LogFilter, AppState,
+};
+use std::sync::Arc;
+
+/// The state of the app run with `config`. Google is `google` when given,
+/// and otherwise the client `config` points at.
+pub async fn test_state(config: Config, google: Option<FakeGoogleApi>) -> AppState {
+    let (_layer, log_filter) = LogFilter::new("info").unwrap();
+    let mut state = AppState::from_config(Arc::new(config), log_filter)
+        .await
+        .unwrap();
+    if let Some(google) = google {
+        state.google = Arc::new(google);
+    }
+    state.meeting_queue.start(state.clone());
+    state
+}
@@ -0,0 +1,159 @@
+//! Drives the whole router the way Slack does: signed form posts in,
+//! JSON replies out, with an in-memory database and a fake Google.
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use common::test_state;
+use hmac::{Hmac, Mac};
+use meet_slack_bot::{
+    app,
+    config::Config,
+    database::models::OAuthToken,
+    google::fake::{FakeGoogleApi, FAKE_MEETING_URI},
+    slack::commands::SlashCommands,
+};
+use serde_json::Value;
+use sha2::Sha256;
+use tower::ServiceExt;
+
+/// Matches `Config::for_tests`.
+const SIGNING_SECRET: &[u8] = b"secret";
+
+/// Sends `/meet <text>` as user U012AB3CD and returns the JSON reply.
+async fn slash_command(router: &Router, text: &str) -> Value {
+    command(router, "/meet", text).await
+}
+
+/// Sends `<command> <text>` as user U012AB3CD and returns the JSON reply.
+async fn command(router: &Router, command: &str, text: &str) -> Value {
+    let body = format!(
+        "token=x&team_id=T012AB3C4&team_domain=acme&channel_id=C012AB3CD&channel_name=general&user_id=U012AB3CD&user_name=alice&{}&response_url=https%3A%2F%2Fhooks.slack.com%2Fcommands%2F1%2F2&trigger_id=1.2.3",
+        serde_urlencoded::to_string([("command", command), ("text", text)]).unwrap()
+    );
+    let timestamp = chrono::Utc::now().timestamp().to_string();
+    let mut mac = Hmac::<Sha256>::new_from_slice(SIGNING_SECRET).unwrap();
+    mac.update(format!("v0:{}:{}", timestamp, body).as_bytes());
+    let signature = format!("v0={}", hex::encode(mac.finalize().into_bytes()));
+
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/slack/commands")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .header("x-slack-signature", signature)
+                .header("x-slack-request-timestamp", timestamp)
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&bytes).unwrap()
+}
+
+#[tokio::test]
+async fn test_slash_command_flow() {
+    let state = test_state(Config::for_tests(), Some(FakeGoogleApi::succeeding())).await;
+    let router = app(state.clone());
+
+    // A new user is asked to connect Google first
+    let reply = slash_command(&router, "Standup").await;
+    assert_eq!(
+        reply["blocks"][1]["elements"][0]["action_id"],
+        "connect_google"
+    );
+
+    // Stands in for the OAuth callback, which needs Google's token endpoint
+    let user = state
+        .db
+        .get_user_by_slack_id("U012AB3CD")
+        .await
+        .unwrap()
+        .expect("the first command registers the user");
+    state
+        .db
+        .store_oauth_token(&OAuthToken::new(
+            user.id,
+            "ya29.access".into(),
+            Some("1//refresh".into()),
+            Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+            Some("https://www.googleapis.com/auth/meetings.space.created".into()),
+        ))
+        .await
+        .unwrap();
+
+    let reply = slash_command(&router, "Standup").await;
+    assert_eq!(reply["response_type"], "in_channel");
+    assert!(
+        reply["text"].as_str().unwrap().contains(FAKE_MEETING_URI),
+        "{}",
+        reply
+    );
+
+    let reply = slash_command(&router, "list").await;
+    assert!(
+        reply["text"].as_str().unwrap().contains(FAKE_MEETING_URI),
+        "{}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn test_remapped_commands() {
+    let mut config = Config::for_tests();
+    config.slack.commands = SlashCommands::parse("/gmeet=/meet,/gmeet-help=/meet-help").unwrap();
+    config.validation.allowed_commands = vec!["/gmeet".to_string(), "/gmeet-help".to_string()];
+    let router = app(test_state(config, Some(FakeGoogleApi::succeeding())).await);
+
+    // Both the alias and the help shortcut point users at `/gmeet`
+    for (name, text) in [("/gmeet", "help"), ("/gmeet-help", "")] {
+        let reply = command(&router, name, text).await;
+        let text = reply["text"].as_str().unwrap();
+        assert!(text.contains("`/gmeet list [n]`"), "{}: {}", name, text);
+        assert!(!text.contains("/meet"), "{}: {}", name, text);
+    }
+
+    let reply = command(&router, "/gmeet", "Standup").await;
+    assert_eq!(
+        reply["blocks"][1]["elements"][0]["action_id"],
+        "connect_google"
+    );
+
+    // The canonical name belongs to another app in this workspace
+    let reply = command(&router, "/meet", "help").await;
+    assert!(
+        reply["text"].as_str().unwrap().starts_with('❌'),
+        "{}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn test_unsigned_command_is_rejected() {
+    let router = app(test_state(Config::for_tests(), Some(FakeGoogleApi::succeeding())).await);
+
+    let response = router
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/slack/commands")
+                .header(header::CONTENT_TYPE, "application/x-www-form-urlencoded")
+                .body(Body::from("command=%2Fmeet&text=Standup"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
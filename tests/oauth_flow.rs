@@ -0,0 +1,323 @@
+//! The Google OAuth flow against a mock of Google's OAuth endpoints: the
+//! redirect to the consent screen, the callback exchanging the code, and
+//! refreshing a stored token. Runs with an in-memory database and the
+//! fresh encryption key of `Config::for_tests`.
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use meet_slack_bot::{
+    app,
+    auth::oauth::{refresh_token_if_needed, OAuthError, REQUESTED_SCOPES},
+    config::Config,
+    database::models::OAuthToken,
+    handlers::auth::create_oauth_client,
+    AppState,
+};
+use serde_json::json;
+use tower::ServiceExt;
+use url::Url;
+use wiremock::matchers::{body_string_contains, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const USER_ID: &str = "U012AB3CD";
+/// What Google grants in these tests: the user left out the optional
+/// read-only scope.
+const SCOPE: &str = "https://www.googleapis.com/auth/meetings.space.created";
+const CODE: &str = "4/0AcodeFROMgoogle";
+
+/// App state whose OAuth client talks to `google`, with [`USER_ID`]
+/// registered as by their first `/meet`.
+async fn test_state(google: &MockServer) -> AppState {
+    let mut config = Config::for_tests();
+    config.google.auth_url = format!("{}/o/oauth2/v2/auth", google.uri());
+    config.google.token_url = format!("{}/token", google.uri());
+    let state = common::test_state(config, None).await;
+    state.db.create_user(USER_ID, "T012AB3C4").await.unwrap();
+    state
+}
+
+async fn get(router: &Router, uri: &str) -> (StatusCode, Option<String>, String) {
+    let response = router
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let location = response
+        .headers()
+        .get(header::LOCATION)
+        .map(|value| value.to_str().unwrap().to_string());
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, location, String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+/// Starts the flow for [`USER_ID`] and returns the consent screen URL.
+async fn initiate(router: &Router) -> Url {
+    let (status, location, _) = get(router, &format!("/auth/google?user_id={}", USER_ID)).await;
+    assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+    Url::parse(&location.expect("a redirect to Google")).unwrap()
+}
+
+fn query_param(url: &Url, name: &str) -> Option<String> {
+    url.query_pairs()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn callback_uri(params: &[(&str, &str)]) -> String {
+    format!(
+        "/auth/google/callback?{}",
+        serde_urlencoded::to_string(params).unwrap()
+    )
+}
+
+/// Google's token endpoint answering a code exchange, once.
+async fn mock_code_exchange(google: &MockServer) {
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .and(body_string_contains("grant_type=authorization_code"))
+        // Form-encoded, so the slash of the code is escaped
+        .and(body_string_contains("code=4%2F0AcodeFROMgoogle"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "ya29.fromGOOGLE",
+            "refresh_token": "1//refreshFROMgoogle",
+            "expires_in": 3599,
+            "scope": SCOPE,
+            "token_type": "Bearer"
+        })))
+        .expect(1)
+        .mount(google)
+        .await;
+}
+
+async fn assert_no_token(state: &AppState) {
+    let user = state
+        .db
+        .get_user_by_slack_id(USER_ID)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state.db.get_oauth_token(user.id).await.unwrap().is_none());
+}
+
+fn stored_token(expires_in: chrono::Duration) -> OAuthToken {
+    let mut token = OAuthToken::new(
+        1,
+        "ya29.old".into(),
+        Some("1//refreshSTORED".into()),
+        Some(chrono::Utc::now() + expires_in),
+        Some(SCOPE.to_string()),
+    );
+    token.id = Some(7);
+    token
+}
+
+#[tokio::test]
+async fn test_initiate_redirects_to_google_with_a_recorded_state() {
+    let google = MockServer::start().await;
+    mock_code_exchange(&google).await;
+    let router = app(test_state(&google).await);
+
+    let consent = initiate(&router).await;
+
+    assert!(
+        consent
+            .as_str()
+            .starts_with(&format!("{}/o/oauth2/v2/auth?", google.uri())),
+        "{}",
+        consent
+    );
+    assert_eq!(query_param(&consent, "response_type").unwrap(), "code");
+    assert_eq!(query_param(&consent, "client_id").unwrap(), "client-id");
+    assert_eq!(
+        query_param(&consent, "redirect_uri").unwrap(),
+        "https://bot.example.com/auth/google/callback"
+    );
+    assert_eq!(
+        query_param(&consent, "scope").unwrap(),
+        REQUESTED_SCOPES.join(" ")
+    );
+    assert_eq!(query_param(&consent, "access_type").unwrap(), "offline");
+    assert_eq!(query_param(&consent, "prompt").unwrap(), "consent");
+    let state = query_param(&consent, "state").unwrap();
+    assert!(
+        state.starts_with(&format!("user:{}:", USER_ID)),
+        "{}",
+        state
+    );
+
+    // The state was recorded: accepted once, then never again
+    let uri = callback_uri(&[("code", CODE), ("state", &state)]);
+    let (status, _, page) = get(&router, &uri).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("Authentication Successful"), "{}", page);
+    let (_, _, page) = get(&router, &uri).await;
+    assert!(page.contains("expired or was already used"), "{}", page);
+}
+
+#[tokio::test]
+async fn test_callback_stores_the_token_encrypted() {
+    let google = MockServer::start().await;
+    mock_code_exchange(&google).await;
+    let state = test_state(&google).await;
+    let router = app(state.clone());
+
+    let oauth_state = query_param(&initiate(&router).await, "state").unwrap();
+    let (status, _, page) = get(
+        &router,
+        &callback_uri(&[("code", CODE), ("state", &oauth_state)]),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(page.contains("Authentication Successful"), "{}", page);
+
+    let user = state
+        .db
+        .get_user_by_slack_id(USER_ID)
+        .await
+        .unwrap()
+        .unwrap();
+    // Read back through the database's decryption with the test key
+    let token = state.db.get_oauth_token(user.id).await.unwrap().unwrap();
+    assert_eq!(token.access_token.expose(), "ya29.fromGOOGLE");
+    assert_eq!(
+        token.refresh_token.as_ref().unwrap().expose(),
+        "1//refreshFROMgoogle"
+    );
+    assert!(!token.is_expired());
+    // Only what was granted, not everything asked for
+    assert_eq!(token.scope.as_deref(), Some(SCOPE));
+
+    let audit = state.db.audit_log_page(None, 10).await.unwrap();
+    assert_eq!(audit[0].event_type, "google_connected");
+}
+
+#[tokio::test]
+async fn test_denied_consent_shows_why_without_calling_google() {
+    let google = MockServer::start().await;
+    Mock::given(path("/token"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&google)
+        .await;
+    let state = test_state(&google).await;
+    let router = app(state.clone());
+
+    let oauth_state = query_param(&initiate(&router).await, "state").unwrap();
+    let (status, _, page) = get(
+        &router,
+        &callback_uri(&[("error", "access_denied"), ("state", &oauth_state)]),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(
+        page.contains("Access to your Google account was denied."),
+        "{}",
+        page
+    );
+    assert_no_token(&state).await;
+}
+
+#[tokio::test]
+async fn test_forged_state_is_rejected() {
+    let google = MockServer::start().await;
+    Mock::given(path("/token"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(0)
+        .mount(&google)
+        .await;
+    let state = test_state(&google).await;
+    let router = app(state.clone());
+
+    // Well-formed, but never handed out
+    let issued = query_param(&initiate(&router).await, "state").unwrap();
+    let forged = format!("user:{}:{}", USER_ID, "A".repeat(43));
+    // Nor does a state issued to one user work for another
+    let other_user = issued.replacen(USER_ID, "U098ZY7XW", 1);
+
+    for oauth_state in [forged, other_user] {
+        let (status, _, page) = get(
+            &router,
+            &callback_uri(&[("code", CODE), ("state", &oauth_state)]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains("expired or was already used"), "{}", page);
+    }
+    assert_no_token(&state).await;
+}
+
+#[tokio::test]
+async fn test_refresh_replaces_a_token_about_to_expire() {
+    let google = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .and(body_string_contains("grant_type=refresh_token"))
+        .and(body_string_contains("refreshSTORED"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "access_token": "ya29.REFRESHED",
+            "expires_in": 3599,
+            "token_type": "Bearer"
+        })))
+        .expect(1)
+        .mount(&google)
+        .await;
+    let state = test_state(&google).await;
+    let client = create_oauth_client(&state).unwrap();
+    let now = chrono::Utc::now();
+
+    // Not due yet, so Google isn't asked
+    let fresh = stored_token(chrono::Duration::hours(1));
+    let refreshed = refresh_token_if_needed(&client, &state.http, &fresh, now)
+        .await
+        .unwrap();
+    assert!(refreshed.is_none());
+
+    let expiring = stored_token(chrono::Duration::minutes(1));
+    let refreshed = refresh_token_if_needed(&client, &state.http, &expiring, now)
+        .await
+        .unwrap()
+        .expect("a token expiring within five minutes is refreshed");
+    assert_eq!(refreshed.access_token.expose(), "ya29.REFRESHED");
+    // Google didn't send a new refresh token, so the old one is kept
+    assert_eq!(
+        refreshed.refresh_token.as_ref().unwrap().expose(),
+        "1//refreshSTORED"
+    );
+    assert_eq!(refreshed.id, Some(7));
+    assert!(!refreshed.expires_soon_at(now));
+}
+
+#[tokio::test]
+async fn test_revoked_refresh_token_fails_the_refresh() {
+    let google = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/token"))
+        .respond_with(ResponseTemplate::new(400).set_body_json(json!({
+            "error": "invalid_grant",
+            "error_description": "Token has been expired or revoked."
+        })))
+        .expect(1)
+        .mount(&google)
+        .await;
+    let state = test_state(&google).await;
+    let client = create_oauth_client(&state).unwrap();
+
+    let expiring = stored_token(chrono::Duration::minutes(1));
+    let result = refresh_token_if_needed(&client, &state.http, &expiring, chrono::Utc::now()).await;
+
+    match result {
+        Err(OAuthError::RefreshFailed(message)) => {
+            assert!(!message.contains("refreshSTORED"), "{}", message)
+        }
+        other => panic!("expected a failed refresh, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,262 @@
+//! Drives `POST /api/v1/meetings` the way incident tooling would: JSON in
+//! with an API key, JSON out, with an in-memory database and a fake Google.
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+    Router,
+};
+use common::test_state;
+use meet_slack_bot::{
+    api_keys, app,
+    config::Config,
+    database::models::{MeetingSource, OAuthToken},
+    google::fake::{FakeGoogleApi, FAKE_MEETING_URI},
+    AppState,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+/// Registers `slack_user_id` in workspace T012AB3C4 with a Google token, as
+/// the OAuth callback would.
+async fn connect(state: &AppState, slack_user_id: &str) {
+    let user = state
+        .db
+        .create_user(slack_user_id, "T012AB3C4")
+        .await
+        .unwrap();
+    state
+        .db
+        .store_oauth_token(&OAuthToken::new(
+            user.id,
+            "ya29.access".into(),
+            Some("1//refresh".into()),
+            Some(chrono::Utc::now() + chrono::Duration::hours(1)),
+            Some("https://www.googleapis.com/auth/meetings.space.created".into()),
+        ))
+        .await
+        .unwrap();
+}
+
+/// A key for T012AB3C4 made by U012AB3CD, with its number.
+async fn api_key(state: &AppState) -> (i64, String) {
+    let (stored, key) = api_keys::create(&state.db, "T012AB3C4", "U012AB3CD", chrono::Utc::now())
+        .await
+        .unwrap();
+    (stored.id, key.expose().to_string())
+}
+
+/// Posts `body` to the meetings endpoint with `key`, if any.
+async fn create_meeting(router: &Router, key: Option<&str>, body: Value) -> (StatusCode, Value) {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri("/api/v1/meetings")
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(key) = key {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", key));
+    }
+    let response = router
+        .clone()
+        .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        .await
+        .unwrap();
+
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[tokio::test]
+async fn test_requests_without_a_working_key_are_refused() {
+    let state = test_state(Config::for_tests(), Some(FakeGoogleApi::succeeding())).await;
+    connect(&state, "U012AB3CD").await;
+    let (_, key) = api_key(&state).await;
+    let router = app(state.clone());
+    let body = json!({ "title": "Incident 42" });
+
+    for key in [None, Some("msb_made-up"), Some(&key[..key.len() - 1])] {
+        let (status, reply) = create_meeting(&router, key, body.clone()).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{:?}", key);
+        assert_eq!(reply["error"], "unauthorized");
+    }
+    // Nor does a key work as any other kind of credentials
+    let response = router
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/api/v1/meetings")
+                .header(header::CONTENT_TYPE, "application/json")
+                .header(header::AUTHORIZATION, format!("Basic {}", key))
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let user = state
+        .db
+        .get_user_by_slack_id("U012AB3CD")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(state
+        .db
+        .get_user_meetings(user.id, 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_meeting_is_created_and_recorded_as_from_the_api() {
+    let state = test_state(Config::for_tests(), Some(FakeGoogleApi::succeeding())).await;
+    connect(&state, "U012AB3CD").await;
+    connect(&state, "U0000B0B1").await;
+    let (_, key) = api_key(&state).await;
+    let router = app(state.clone());
+
+    let start = chrono::Utc::now() + chrono::Duration::hours(2);
+    let (status, reply) = create_meeting(
+        &router,
+        Some(&key),
+        json!({
+            "slack_user_id": "U0000B0B1",
+            "title": "Incident 42",
+            "duration_minutes": 45,
+            "start_time": start.to_rfc3339(),
+        }),
+    )
+    .await;
+
+    assert_eq!(status, StatusCode::CREATED, "{}", reply);
+    assert_eq!(reply["meet_link"], FAKE_MEETING_URI);
+    assert_eq!(reply["event_id"], "spaces/abc");
+    assert_eq!(reply["title"], "Incident 42");
+    let end: chrono::DateTime<chrono::Utc> =
+        serde_json::from_value(reply["end_time"].clone()).unwrap();
+    assert_eq!(end - start, chrono::Duration::minutes(45));
+
+    let bob = state
+        .db
+        .get_user_by_slack_id("U0000B0B1")
+        .await
+        .unwrap()
+        .unwrap();
+    let meetings = state.db.get_user_meetings(bob.id, 10).await.unwrap();
+    assert_eq!(meetings.len(), 1);
+    assert_eq!(Some(meetings[0].id.unwrap()), reply["meeting_id"].as_i64());
+    assert_eq!(meetings[0].source, MeetingSource::Api);
+    assert_eq!(meetings[0].channel_id, None);
+
+    // Without a user the meeting is the key's maker's
+    let (status, reply) = create_meeting(&router, Some(&key), json!({})).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", reply);
+    assert!(
+        reply["title"].as_str().unwrap().starts_with("Meeting — "),
+        "{}",
+        reply
+    );
+}
+
+#[tokio::test]
+async fn test_revoked_keys_stop_working() {
+    let state = test_state(Config::for_tests(), Some(FakeGoogleApi::succeeding())).await;
+    connect(&state, "U012AB3CD").await;
+    let (id, key) = api_key(&state).await;
+    let router = app(state.clone());
+
+    let (status, _) = create_meeting(&router, Some(&key), json!({})).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    assert!(state
+        .db
+        .revoke_api_key("T012AB3C4", id, chrono::Utc::now().naive_utc())
+        .await
+        .unwrap());
+    let (status, reply) = create_meeting(&router, Some(&key), json!({})).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(reply["error"], "unauthorized");
+}
+
+#[tokio::test]
+async fn test_bad_requests_are_explained() {
+    let state = test_state(Config::for_tests(), Some(FakeGoogleApi::succeeding())).await;
+    connect(&state, "U012AB3CD").await;
+    state
+        .db
+        .create_user("U0NOGOOGLE", "T012AB3C4")
+        .await
+        .unwrap();
+    connect(&state, "U0ELSEWHERE").await;
+    state
+        .db
+        .create_user("U0ELSEWHERE", "T0OTHER01")
+        .await
+        .unwrap();
+    let (_, key) = api_key(&state).await;
+    let router = app(state.clone());
+
+    let (status, reply) = create_meeting(&router, Some(&key), json!({ "titel": "typo" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(reply["error"], "bad_request");
+
+    let (status, reply) =
+        create_meeting(&router, Some(&key), json!({ "duration_minutes": 1 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(reply["error"], "invalid_input");
+
+    // Held to the same longest meeting as `/meet`, however far past it
+    for minutes in [481, i64::MAX] {
+        let (status, reply) =
+            create_meeting(&router, Some(&key), json!({ "duration_minutes": minutes })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", minutes);
+        assert_eq!(reply["error"], "invalid_input");
+    }
+    for minutes in [0, -30, i64::MIN] {
+        let (status, reply) =
+            create_meeting(&router, Some(&key), json!({ "duration_minutes": minutes })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", minutes);
+        assert_eq!(reply["error"], "bad_request");
+    }
+
+    // Someone without Google, or in another workspace, can't be given a
+    // meeting
+    for slack_user_id in ["U0NOGOOGLE", "U0ELSEWHERE", "U0UNKNOWN1"] {
+        let (status, reply) = create_meeting(
+            &router,
+            Some(&key),
+            json!({ "slack_user_id": slack_user_id }),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{}", slack_user_id);
+        assert_eq!(reply["error"], "google_not_connected");
+    }
+}
+
+#[tokio::test]
+async fn test_each_key_is_rate_limited() {
+    let mut config = Config::for_tests();
+    config.rate_limit.api_key_requests_per_minute = 2;
+    let state = test_state(config, Some(FakeGoogleApi::succeeding())).await;
+    connect(&state, "U012AB3CD").await;
+    let (_, first) = api_key(&state).await;
+    let (_, second) = api_key(&state).await;
+    let router = app(state.clone());
+
+    for _ in 0..2 {
+        let (status, _) = create_meeting(&router, Some(&first), json!({})).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+    let (status, reply) = create_meeting(&router, Some(&first), json!({})).await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(reply["error"], "rate_limited");
+
+    let (status, _) = create_meeting(&router, Some(&second), json!({})).await;
+    assert_eq!(status, StatusCode::CREATED);
+}
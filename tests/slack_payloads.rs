@@ -0,0 +1,349 @@
+//! Pins the JSON of every message the bot sends to Slack. Slack drops
+//! malformed messages without saying why, so any change to these shows up
+//! as a snapshot diff to review (`cargo insta review`).
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc, Weekday};
+use insta::assert_json_snapshot;
+use meet_slack_bot::{
+    announcement::AnnouncementStyle,
+    database::models::{
+        MeetLinkKind, Meeting, MeetingCounts, MeetingSource, MeetingVisibility, Poll, PollVote,
+    },
+    digest::{digest_message, DigestSchedule},
+    error::{AppError, RateLimit},
+    google::Artifacts,
+    handlers::slack::SlackResponse,
+    polls,
+    request_id::RequestId,
+    stats::{TeamActivity, UsageStats},
+};
+
+const MEET_LINK: &str = "https://meet.google.com/abc-defg-hij";
+
+fn meeting(title: Option<&str>, link_kind: MeetLinkKind, day: u32) -> Meeting {
+    Meeting {
+        id: Some(day.into()),
+        user_id: 1,
+        meet_link: MEET_LINK.to_string(),
+        title: title.map(str::to_string),
+        link_kind,
+        visibility: MeetingVisibility::Channel,
+        created_with_account_user_id: None,
+        channel_id: None,
+        slack_team_id: None,
+        space_name: None,
+        auto_recording: false,
+        auto_transcription: false,
+        ends_at: None,
+        source: MeetingSource::Slack,
+        created_at: NaiveDate::from_ymd_opt(2024, 3, day)
+            .and_then(|date| date.and_hms_opt(9, 30, 0)),
+    }
+}
+
+fn request_id() -> RequestId {
+    RequestId::parse("4f1c2a9e0b7d4c3a8e6f5d4c3b2a1908").unwrap()
+}
+
+#[test]
+fn ephemeral_error() {
+    let text = AppError::Db(sqlx::Error::PoolClosed).slack_text().unwrap();
+    assert_json_snapshot!(SlackResponse::ephemeral(text).with_error_ref(&request_id()));
+}
+
+#[test]
+fn meeting_created() {
+    let meeting = meeting(Some("Standup"), MeetLinkKind::Meet, 1);
+    assert_json_snapshot!(SlackResponse::meeting_created(
+        "alice",
+        &meeting,
+        &AnnouncementStyle::default()
+    ));
+}
+
+#[test]
+fn customized_meeting_created() {
+    let style = AnnouncementStyle {
+        emoji: ":video_camera:".to_string(),
+        prefix: "Video call started".to_string(),
+        mention_creator: true,
+    };
+    let meeting = meeting(Some("Standup"), MeetLinkKind::Meet, 1);
+    assert_json_snapshot!(SlackResponse::meeting_created("alice", &meeting, &style));
+    assert_json_snapshot!(
+        "customized_meeting_created_without_mention",
+        SlackResponse::meeting_created(
+            "alice",
+            &meeting,
+            &AnnouncementStyle {
+                mention_creator: false,
+                ..style
+            }
+        )
+    );
+}
+
+#[test]
+fn meeting_scheduled() {
+    let meeting = meeting(Some("Standup"), MeetLinkKind::Meet, 1);
+    let starts_at = "2024-03-05T07:00:00Z".parse().unwrap();
+    assert_json_snapshot!(SlackResponse::meeting_scheduled(
+        "alice",
+        &meeting,
+        &AnnouncementStyle::default(),
+        starts_at,
+        Some(Duration::minutes(10)),
+        None,
+        None
+    ));
+    assert_json_snapshot!(
+        "meeting_scheduled_without_reminder",
+        SlackResponse::meeting_scheduled(
+            "alice",
+            &meeting,
+            &AnnouncementStyle::default(),
+            starts_at,
+            None,
+            None,
+            None
+        )
+    );
+}
+
+#[test]
+fn meeting_scheduled_add_to_calendar() {
+    let starts_at = "2024-03-05T07:00:00Z".parse().unwrap();
+    let style = AnnouncementStyle::default();
+    let timed = meeting(Some("Q&A 🇵🇱"), MeetLinkKind::Meet, 1)
+        .with_end(Some("2024-03-05T07:45:00Z".parse().unwrap()));
+    assert_json_snapshot!(
+        "meeting_scheduled_with_an_end",
+        SlackResponse::meeting_scheduled("alice", &timed, &style, starts_at, None, None, None)
+    );
+
+    let untitled = meeting(None, MeetLinkKind::Meet, 1);
+    assert_json_snapshot!(
+        "untitled_meeting_scheduled",
+        SlackResponse::meeting_scheduled("alice", &untitled, &style, starts_at, None, None, None)
+    );
+
+    let quiet =
+        meeting(Some("1:1"), MeetLinkKind::Meet, 1).with_visibility(MeetingVisibility::Quiet);
+    assert_json_snapshot!(
+        "quiet_meeting_scheduled",
+        SlackResponse::meeting_scheduled("alice", &quiet, &style, starts_at, None, None, None)
+    );
+
+    let with_file = SlackResponse::meeting_scheduled(
+        "alice",
+        &timed,
+        &style,
+        starts_at,
+        None,
+        Some("https://bot.example.com/ics/1.1709622000.ab12"),
+        None,
+    );
+    assert_json_snapshot!("meeting_scheduled_with_calendar_file", with_file);
+
+    let across_timezones = SlackResponse::meeting_scheduled(
+        "alice",
+        &timed,
+        &style,
+        starts_at,
+        None,
+        None,
+        Some("8:00 CET · 2:00 EST · 12:30 IST"),
+    );
+    assert_json_snapshot!("meeting_scheduled_across_timezones", across_timezones);
+
+    // A calendar event needs no adding
+    let event = meeting(Some("Standup"), MeetLinkKind::Calendar, 1);
+    assert_json_snapshot!(
+        "meeting_scheduled_without_meet_link",
+        SlackResponse::meeting_scheduled("alice", &event, &style, starts_at, None, None, None)
+    );
+}
+
+#[test]
+fn meeting_created_without_meet_link() {
+    let meeting = meeting(None, MeetLinkKind::Calendar, 1);
+    assert_json_snapshot!(SlackResponse::meeting_created(
+        "alice",
+        &meeting,
+        &AnnouncementStyle::default()
+    ));
+}
+
+#[test]
+fn quiet_meeting_created() {
+    let meeting =
+        meeting(Some("1:1"), MeetLinkKind::Meet, 1).with_visibility(MeetingVisibility::Quiet);
+    assert_json_snapshot!(SlackResponse::meeting_created(
+        "alice",
+        &meeting,
+        &AnnouncementStyle::default()
+    ));
+}
+
+#[test]
+fn recorded_meeting_created() {
+    let meeting = meeting(Some("All hands"), MeetLinkKind::Meet, 1).with_artifacts(Artifacts {
+        recording: true,
+        transcription: true,
+    });
+    assert_json_snapshot!(SlackResponse::meeting_created(
+        "alice",
+        &meeting,
+        &AnnouncementStyle::default()
+    ));
+}
+
+#[test]
+fn auth_prompt() {
+    assert_json_snapshot!(SlackResponse::with_auth_prompt(
+        "https://bot.example.com/auth/google?user_id=U012AB3CD".to_string()
+    ));
+}
+
+#[test]
+fn meeting_list() {
+    let mut untimed = meeting(None, MeetLinkKind::Meet, 1);
+    untimed.created_at = None;
+    let ended = |day: u32| Utc.with_ymd_and_hms(2024, 3, day, 10, 0, 0).single();
+    let now = Utc.with_ymd_and_hms(2024, 3, 3, 10, 30, 0).unwrap();
+    assert_json_snapshot!(SlackResponse::meeting_list(
+        &[
+            meeting(Some("Standup"), MeetLinkKind::Meet, 3).with_end(ended(3)),
+            meeting(Some("Planning"), MeetLinkKind::Calendar, 2),
+            meeting(Some("1:1"), MeetLinkKind::Meet, 2)
+                .with_visibility(MeetingVisibility::Quiet)
+                .with_end(ended(2)),
+            untimed,
+        ],
+        now
+    ));
+}
+
+#[test]
+fn shared_channel_meeting() {
+    let now = Utc.with_ymd_and_hms(2024, 3, 3, 10, 30, 0).unwrap();
+    assert_json_snapshot!(SlackResponse::channel_meeting(
+        &meeting(Some("Incident bridge"), MeetLinkKind::Meet, 3),
+        now,
+        Some("U012AB3CD"),
+    ));
+}
+
+#[test]
+fn empty_meeting_list() {
+    assert_json_snapshot!(SlackResponse::meeting_list(&[], Utc::now()));
+}
+
+#[test]
+fn usage_stats() {
+    assert_json_snapshot!(SlackResponse::usage_stats(
+        &UsageStats {
+            counts: MeetingCounts {
+                this_week: 3,
+                this_month: 11,
+                all_time: 42,
+            },
+            top_words: vec![("standup".to_string(), 20), ("retro".to_string(), 4)],
+            longest_streak: 5,
+        },
+        "/meet"
+    ));
+}
+
+#[test]
+fn empty_usage_stats() {
+    assert_json_snapshot!(SlackResponse::usage_stats(&UsageStats::default(), "/gmeet"));
+}
+
+#[test]
+fn weekly_digest() {
+    let schedule = DigestSchedule {
+        channel_id: "C012AB3CD".to_string(),
+        weekday: Weekday::Mon,
+        hour: 9,
+        configured_by: Some("U012AB3CD".to_string()),
+    };
+    let first_day = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+    let last_day = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+
+    assert_json_snapshot!(digest_message(
+        &schedule,
+        "/meet",
+        first_day,
+        last_day,
+        &TeamActivity {
+            meetings: 17,
+            top_creators: vec![
+                ("U012AB3CD".to_string(), 6),
+                ("U098ZY7XW".to_string(), 4),
+                ("U055XX5XX".to_string(), 2),
+            ],
+            busiest_day: NaiveDate::from_ymd_opt(2024, 3, 5).map(|day| (day, 7)),
+        }
+    ));
+    assert_json_snapshot!(
+        "quiet_weekly_digest",
+        digest_message(
+            &schedule,
+            "/meet",
+            first_day,
+            last_day,
+            &TeamActivity::default()
+        )
+    );
+}
+
+#[test]
+fn rate_limited() {
+    for (name, scope) in [("user", RateLimit::User), ("global", RateLimit::Global)] {
+        let text = AppError::RateLimited(scope).slack_text().unwrap();
+        assert_json_snapshot!(
+            format!("rate_limited_{}", name),
+            SlackResponse::ephemeral(text).with_error_ref(&request_id())
+        );
+    }
+}
+
+#[test]
+fn scheduling_poll() {
+    let mut poll = Poll {
+        id: Some(7),
+        user_id: 1,
+        slack_user_id: "U012AB3CD".to_string(),
+        slack_team_id: "T012AB3C4".to_string(),
+        channel_id: "C012AB3CD".to_string(),
+        title: Some("Retro".to_string()),
+        options: vec![
+            Utc.with_ymd_and_hms(2024, 3, 6, 14, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 3, 6, 16, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 3, 8, 10, 0, 0).unwrap(),
+        ],
+        booked_option: None,
+        created_at: None,
+    };
+    let votes = [
+        PollVote {
+            slack_user_id: "U098ZY7XW".to_string(),
+            option: 1,
+        },
+        PollVote {
+            slack_user_id: "U055XX5XX".to_string(),
+            option: 1,
+        },
+        PollVote {
+            slack_user_id: "U012AB3CD".to_string(),
+            option: 2,
+        },
+    ];
+
+    assert_json_snapshot!(polls::message(&poll, &votes));
+    poll.booked_option = Some(1);
+    let mut booked = polls::message(&poll, &votes);
+    booked.replace_original = true;
+    assert_json_snapshot!("booked_scheduling_poll", booked);
+}